@@ -5,18 +5,29 @@ fn main() {
     // to prost-build
     std::env::set_var("PROTOC", protobuf_src::protoc());
 
+    let mut config = prost_build::Config::new();
+    // sync messages can be archived with rkyv instead of protobuf. This requires rkyv's traits on
+    // the messages and on everything a vehicle carries with it.
+    for package in [".messages", ".population"] {
+        config.type_attribute(
+            package,
+            "#[cfg_attr(feature = \"rkyv\", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize), archive(check_bytes))]",
+        );
+    }
+
     // this line comes from the prost-build example and compiles items.proto into corresponding types.
     // the generated code is under ./target/<goal, e.g. debug>/build/<project-name>-<some-hash>/out
-    prost_build::compile_protos(
-        &[
-            "src/simulation/wire_types/messages.proto",
-            "src/simulation/wire_types/events.proto",
-            "src/simulation/wire_types/ids.proto",
-            "src/simulation/wire_types/network.proto",
-            "src/simulation/wire_types/population.proto",
-            "src/simulation/wire_types/vehicles.proto",
-        ],
-        &["src/"],
-    )
-    .unwrap();
+    config
+        .compile_protos(
+            &[
+                "src/simulation/wire_types/messages.proto",
+                "src/simulation/wire_types/events.proto",
+                "src/simulation/wire_types/ids.proto",
+                "src/simulation/wire_types/network.proto",
+                "src/simulation/wire_types/population.proto",
+                "src/simulation/wire_types/vehicles.proto",
+            ],
+            &["src/"],
+        )
+        .unwrap();
 }
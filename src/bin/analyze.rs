@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use tracing::info;
+
+use rust_q_sim::simulation::analysis::drt_stats::DrtStats;
+use rust_q_sim::simulation::analysis::event_counts::{Aggregation, EventCounts, PartitionCounts};
+use rust_q_sim::simulation::analysis::link_travel_times::LinkTravelTimes;
+#[cfg(feature = "sqlite")]
+use rust_q_sim::simulation::analysis::results_db::ResultsDatabase;
+use rust_q_sim::simulation::id;
+use rust_q_sim::simulation::io::proto_events::EventsReader;
+use rust_q_sim::simulation::io::xml_events::XmlEventsReader;
+use rust_q_sim::simulation::logging::init_std_out_logging;
+use rust_q_sim::simulation::messaging::events::EventsPublisher;
+
+/// Aggregates events of a run. Each selected aggregation is an events subscriber, which writes a
+/// csv file into the output directory once all events are read.
+///
+/// Either reads the proto events files of all partitions, or a single xml events file, e.g. of a
+/// run of java matsim. Partitions are read one after another, so events are only ordered by time
+/// within a partition.
+fn main() {
+    init_std_out_logging();
+    let args = InputArgs::parse();
+    info!("Analyze with args: {args:?}");
+
+    let output = PathBuf::from(&args.output);
+    let mut publisher = EventsPublisher::new();
+    for aggregation in &args.aggregate {
+        let path = output.join(format!("event_counts_{}.csv", aggregation.name()));
+        if *aggregation == Aggregation::Partition {
+            publisher.add_subscriber(Box::new(PartitionCounts::new(args.bin_size, Some(path))));
+        } else {
+            publisher.add_subscriber(Box::new(EventCounts::new(
+                *aggregation,
+                args.bin_size,
+                Some(path),
+            )));
+        }
+    }
+
+    if args.link_travel_times {
+        publisher.add_subscriber(Box::new(LinkTravelTimes::new(
+            args.bin_size,
+            Some(output.join("link_travel_times.csv")),
+        )));
+    }
+
+    if args.drt_stats {
+        publisher.add_subscriber(Box::new(DrtStats::new(
+            args.bin_size,
+            Some(output.join("drt_stats.csv")),
+        )));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if args.results_db {
+        publisher.add_subscriber(Box::new(ResultsDatabase::new(
+            &output.join("results.db"),
+            args.bin_size,
+        )));
+    }
+
+    if let Some(xml_events) = args.xml_events {
+        info!("Reading events from {xml_events}");
+        for (time, events) in XmlEventsReader::new(&PathBuf::from(xml_events)) {
+            for event in &events {
+                publisher.publish_event(time, event);
+            }
+        }
+    } else {
+        let id_store = args
+            .id_store
+            .expect("An id store is required to read proto events files.");
+        info!("Load Id Store");
+        id::load_from_file(&PathBuf::from(id_store));
+
+        for i in 0..args.num_parts {
+            if let Some(counts) = publisher.get_subscriber::<PartitionCounts>() {
+                counts.set_partition(i);
+            }
+            let file_path = PathBuf::from(format!("{}events.{i}.binpb", args.path));
+            info!("Reading events from {file_path:?}");
+            for (time, events) in EventsReader::from_file(&file_path) {
+                for event in &events {
+                    publisher.publish_event(time, event);
+                }
+            }
+        }
+    }
+
+    info!("Finished reading events. Writing aggregations to {output:?}");
+    publisher.finish();
+}
+
+#[derive(Parser, Debug)]
+struct InputArgs {
+    /// Prefix of the proto events files `<path>events.<partition>.binpb`.
+    #[arg(long, default_value = "")]
+    pub path: String,
+    #[arg(long)]
+    pub id_store: Option<String>,
+    #[arg(long, default_value_t = 1)]
+    pub num_parts: u32,
+    /// Read this xml events file instead of proto events files.
+    #[arg(long)]
+    pub xml_events: Option<String>,
+    #[arg(long)]
+    pub output: String,
+    /// Event counts to compute. May be repeated.
+    #[arg(long, value_enum)]
+    pub aggregate: Vec<Aggregation>,
+    /// Compute median and 85th percentile of travel times per link and time bin.
+    #[arg(long)]
+    pub link_travel_times: bool,
+    /// Compute wait times, detour factors, rejections and occupancy of drt per time bin.
+    #[arg(long)]
+    pub drt_stats: bool,
+    /// Write trips, legs, link volumes and person statistics into a SQLite database. Trips are
+    /// only complete, if all events of a person are read in order, e.g. from an xml events file.
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    pub results_db: bool,
+    /// Size of the time bins in seconds.
+    #[arg(long, default_value_t = 3600)]
+    pub bin_size: u32,
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::Parser;
+
+use rust_q_sim::simulation::benchmark::GridScenario;
+use rust_q_sim::simulation::config::CommandLineArgs;
+use rust_q_sim::simulation::controller;
+use rust_q_sim::simulation::id;
+
+/// Functions whose durations are reported after the run.
+const REPORTED_FUNCTIONS: [&str; 3] = ["move_nodes", "move_links", "send_recv"];
+
+/// Generates a grid scenario, simulates it with the channel communicator and prints the time spent
+/// in the main steps of the simulation loop.
+fn main() {
+    let args = InputArgs::parse();
+    let output_dir = PathBuf::from(&args.output_dir);
+    let input_dir = output_dir.join("input");
+    fs::create_dir_all(&input_dir).expect("Failed to create input dir");
+
+    let scenario = GridScenario {
+        size: args.size,
+        agents: args.agents,
+        partitions: args.num_parts,
+        link_length: 100.,
+        departure_window: args.departure_window,
+        seed: args.seed,
+    };
+    let network = scenario.create_network();
+    let mut garage = scenario.create_garage();
+    let population = scenario.create_population(&network, &mut garage);
+
+    network.to_file(&input_dir.join("network.binpb"));
+    population.to_file(&input_dir.join("population.binpb"));
+    garage.to_file(&input_dir.join("vehicles.binpb"));
+    id::store_to_file(&input_dir.join("ids.binpb"));
+
+    let config_path = output_dir.join("config.yml");
+    write_config(&config_path, &input_dir, &output_dir, &args);
+
+    let start = Instant::now();
+    controller::run_channel_with_args(CommandLineArgs {
+        config_path: config_path.to_str().unwrap().to_string(),
+        num_parts: None,
+    });
+    let duration = start.elapsed();
+
+    println!(
+        "Simulated a {size}x{size} grid with {agents} agents on {parts} partitions.",
+        size = args.size,
+        agents = args.agents,
+        parts = args.num_parts
+    );
+    println!(
+        "Wall time: {:.3}s, {:.0} time steps/s",
+        duration.as_secs_f64(),
+        (args.end_time + 1) as f64 / duration.as_secs_f64()
+    );
+    print_durations(&output_dir.join("instrument"));
+}
+
+fn write_config(path: &Path, input_dir: &Path, output_dir: &Path, args: &InputArgs) {
+    let input = |name: &str| input_dir.join(name).to_str().unwrap().to_string();
+    let config = format!(
+        r#"modules:
+  protofiles:
+    type: ProtoFiles
+    network: {network}
+    population: {population}
+    vehicles: {vehicles}
+    ids: {ids}
+  partitioning:
+    type: Partitioning
+    num_parts: {num_parts}
+    method: None
+  output:
+    type: Output
+    output_dir: {output_dir}
+    logging: None
+    profiling: !CSV
+      level: TRACE
+  routing:
+    type: Routing
+    mode: UsePlans
+  simulation:
+    type: Simulation
+    start_time: 0
+    end_time: {end_time}
+    sample_size: 1.0
+    stuck_threshold: {stuck_threshold}
+"#,
+        network = input("network.binpb"),
+        population = input("population.binpb"),
+        vehicles = input("vehicles.binpb"),
+        ids = input("ids.binpb"),
+        num_parts = args.num_parts,
+        output_dir = output_dir.to_str().unwrap(),
+        end_time = args.end_time,
+        stuck_threshold = u32::MAX,
+    );
+    fs::write(path, config).unwrap_or_else(|e| panic!("Failed to write config to {path:?}: {e}"));
+}
+
+/// Sums up the durations in all profiling files, grouped by target and function.
+fn print_durations(instrument_dir: &Path) {
+    let mut durations: BTreeMap<(String, String), (u64, u128)> = BTreeMap::new();
+    for entry in fs::read_dir(instrument_dir).expect("Failed to read profiling output") {
+        let path = entry.unwrap().path();
+        if !path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("instrument_process")
+        {
+            continue;
+        }
+        let content = fs::read_to_string(&path).unwrap();
+        // columns: timestamp,target,func_name,start,duration,sim_time,rank
+        for line in content.lines().skip(1) {
+            let columns: Vec<&str> = line.split(',').collect();
+            if !REPORTED_FUNCTIONS.contains(&columns[2]) {
+                continue;
+            }
+            let entry = durations
+                .entry((columns[1].to_string(), columns[2].to_string()))
+                .or_default();
+            entry.0 += 1;
+            entry.1 += columns[4].parse::<u128>().unwrap();
+        }
+    }
+
+    println!(
+        "{:<70} {:>10} {:>12} {:>12}",
+        "function", "calls", "total [ms]", "mean [µs]"
+    );
+    for ((target, func_name), (calls, nanos)) in durations {
+        println!(
+            "{:<70} {:>10} {:>12.1} {:>12.2}",
+            format!("{target}::{func_name}"),
+            calls,
+            nanos as f64 / 1e6,
+            nanos as f64 / calls as f64 / 1e3
+        );
+    }
+}
+
+#[derive(Parser, Debug)]
+struct InputArgs {
+    /// number of nodes per row and column of the grid
+    #[arg(long, default_value_t = 100)]
+    pub size: usize,
+    #[arg(long, default_value_t = 10_000)]
+    pub agents: usize,
+    #[arg(long, default_value_t = 1)]
+    pub num_parts: u32,
+    #[arg(long, default_value_t = 3600)]
+    pub end_time: u32,
+    #[arg(long, default_value_t = 1800)]
+    pub departure_window: u32,
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+    #[arg(long, default_value = "./benchmark_output")]
+    pub output_dir: String,
+}
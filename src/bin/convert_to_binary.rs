@@ -3,12 +3,15 @@ use std::path::PathBuf;
 use ahash::HashMapExt;
 use clap::Parser;
 use nohash_hasher::IntMap;
-use tracing::info;
+use tracing::{info, warn};
 
-use rust_q_sim::simulation::config::PartitionMethod;
+use rust_q_sim::simulation::io::xml_validation;
 use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::population::freight::Carriers;
 use rust_q_sim::simulation::population::population::Population;
-use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::population::unknown_modes;
+use rust_q_sim::simulation::population::unknown_modes::UnknownModeFallback;
+use rust_q_sim::simulation::scenario::Scenario;
 
 #[derive(Parser, Debug)]
 struct InputArgs {
@@ -16,21 +19,61 @@ struct InputArgs {
     pub network: PathBuf,
     #[arg(short, long)]
     pub population: PathBuf,
+    /// Default vehicles are created for the modes of the population, if no vehicles file is given.
     #[arg(short, long)]
-    pub vehicles: PathBuf,
+    pub vehicles: Option<PathBuf>,
     #[arg(short, long)]
     pub output_dir: PathBuf,
     #[arg(short, long)]
     pub run_id: String,
+    #[arg(long)]
+    pub households: Option<PathBuf>,
+    #[arg(long)]
+    pub carriers: Option<PathBuf>,
+    /// What happens with persons, whose legs use modes without a vehicle type.
+    #[arg(long, value_enum, default_value_t = UnknownModeFallback::Fail)]
+    pub unknown_modes: UnknownModeFallback,
+    /// Speed in m/s of teleported legs with unknown modes.
+    #[arg(long, default_value_t = 1.)]
+    pub unknown_mode_speed: f32,
+    /// Check the network and population xml files against the matsim dtds before converting them.
+    #[arg(long, default_value_t = false)]
+    pub validate: bool,
 }
 
 fn main() {
     rust_q_sim::simulation::logging::init_std_out_logging();
     let args = InputArgs::parse();
+    if args.validate {
+        validate(&[&args.network, &args.population]);
+    }
 
-    let mut veh = Garage::from_file(&args.vehicles);
-    let mut net = Network::from_file_path(&args.network, 1, PartitionMethod::None);
-    let pop = Population::from_file(&args.population, &mut veh);
+    let Scenario {
+        network: mut net,
+        garage: mut veh,
+        population: mut pop,
+        ..
+    } = Scenario::load(
+        &args.network,
+        args.vehicles.as_deref(),
+        &args.population,
+        None,
+    );
+    if let Some(households) = &args.households {
+        veh.add_households_from_file(households);
+    }
+    if let Some(carriers) = &args.carriers {
+        Carriers::from_file(carriers).add_to_population(&mut pop, &net, &mut veh);
+    }
+    let unknown_modes = unknown_modes::apply_fallback(
+        &mut pop,
+        &mut veh,
+        args.unknown_modes,
+        args.unknown_mode_speed,
+    );
+    if !unknown_modes.is_empty() {
+        info!("Applied fallback for unknown modes to persons: {unknown_modes}");
+    }
 
     let cmp_weights = compute_computational_weights(&pop);
     assign_computational_weights(&mut net, cmp_weights);
@@ -42,6 +85,20 @@ fn main() {
     info!("Finished conversion. Exiting.")
 }
 
+fn validate(files: &[&PathBuf]) {
+    let mut num_problems = 0;
+    for file in files {
+        info!("Validating {file:?}");
+        for problem in xml_validation::validate(file) {
+            warn!("{file:?}, {problem}");
+            num_problems += 1;
+        }
+    }
+    if num_problems > 0 {
+        panic!("Found {num_problems} problems in the input files. See the log for details.");
+    }
+}
+
 fn create_file_path(args: &InputArgs, extension: &str) -> PathBuf {
     args.output_dir
         .join(format!("{}.{}.binpb", args.run_id, extension))
@@ -3,10 +3,7 @@ use std::path::PathBuf;
 use clap::Parser;
 use tracing::info;
 
-use rust_q_sim::simulation::config::PartitionMethod;
-use rust_q_sim::simulation::network::global_network::Network;
-use rust_q_sim::simulation::population::population::Population;
-use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::scenario::Scenario;
 
 #[derive(Parser, Debug)]
 struct InputArgs {
@@ -28,14 +25,11 @@ fn main() {
     let veh_path = PathBuf::from(&args.vehicles);
     let ids_path = PathBuf::from(&args.ids);
 
-    rust_q_sim::simulation::id::load_from_file(&ids_path);
-    let net = Network::from_file_path(&net_path, 1, PartitionMethod::None);
-    let mut veh = Garage::from_file(&veh_path);
-    let pop = Population::from_file(&pop_path, &mut veh);
+    let scenario = Scenario::load(&net_path, Some(&veh_path), &pop_path, Some(&ids_path));
 
-    net.to_file(&replace_filename(net_path));
-    veh.to_file(&replace_filename(veh_path));
-    pop.to_file(&replace_filename(pop_path))
+    scenario.network.to_file(&replace_filename(net_path));
+    scenario.garage.to_file(&replace_filename(veh_path));
+    scenario.population.to_file(&replace_filename(pop_path))
 }
 
 fn replace_filename(path: PathBuf) -> PathBuf {
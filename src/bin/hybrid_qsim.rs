@@ -0,0 +1,18 @@
+use clap::Parser;
+
+use rust_q_sim::simulation::config::CommandLineArgs;
+use rust_q_sim::simulation::controller;
+
+fn main() {
+    let args = InputArgs::parse();
+    controller::run_hybrid(args.sim_args, args.threads_per_process);
+}
+
+#[derive(Parser, Debug)]
+struct InputArgs {
+    #[command(flatten)]
+    sim_args: CommandLineArgs,
+    /// number of partitions which run as threads of each mpi process
+    #[arg(long, short)]
+    threads_per_process: u32,
+}
@@ -1,5 +1,9 @@
-use std::io::{Read, Seek};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
 use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
 
 use clap::Parser;
 use tracing::info;
@@ -11,23 +15,14 @@ use rust_q_sim::simulation::logging::init_std_out_logging;
 use rust_q_sim::simulation::messaging::events::EventsPublisher;
 use rust_q_sim::simulation::wire_types::events::Event;
 
-struct StatefulReader<R: Read + Seek> {
-    reader: EventsReader<R>,
-    curr_time_step: (u32, Vec<Event>),
-}
+/// Number of decoded time steps, which may be buffered per partition in parallel mode. This keeps
+/// memory bounded, if decoding is faster than writing.
+const CHANNEL_CAPACITY: usize = 16;
 
-impl<R: Read + Seek> StatefulReader<R> {
-    pub fn load_next(&mut self) -> Option<()> {
-        match self.reader.next() {
-            None => None,
-            Some(time_step) => {
-                self.curr_time_step = time_step;
-                Some(())
-            }
-        }
-    }
-}
+type TimeSteps = Box<dyn Iterator<Item = (u32, Vec<Event>)>>;
 
+/// Time steps are merged with a k-way merge. Each reader only keeps the time step it is currently
+/// at in memory, so that memory usage doesn't depend on the size of the events files.
 fn main() {
     init_std_out_logging();
     let args = InputArgs::parse();
@@ -36,41 +31,49 @@ fn main() {
     info!("Load Id Store");
     id::load_from_file(&PathBuf::from(args.id_store));
 
-    let mut readers = Vec::new();
+    let (start, end) = args.time_window.unwrap_or((0, u32::MAX));
+    let mut sources: Vec<TimeSteps> = Vec::new();
     info!("Reading from Files: ");
     for i in 0..args.num_parts {
         let file_string = format!("{}events.{i}.binpb", args.path);
         info!("\t {}", file_string);
         let file_path = PathBuf::from(file_string);
-        let reader = EventsReader::from_file(&file_path);
-        let wrapper = StatefulReader {
-            reader,
-            curr_time_step: (0, Vec::new()),
+        let source: TimeSteps = if args.parallel {
+            decode_in_thread(file_path, start, end)
+        } else {
+            Box::new(in_window(EventsReader::from_file(&file_path), start, end))
         };
-        readers.push(wrapper);
+        sources.push(source);
+    }
+
+    // min heap of the current time step of each source. Ties are resolved by the source index, and
+    // events within a time step are ordered by their sequence numbers, so that the output is a
+    // deterministic total order of (time, partition, sequence).
+    let mut heap: BinaryHeap<Reverse<(u32, usize)>> = BinaryHeap::new();
+    let mut curr_events: Vec<Vec<Event>> = Vec::new();
+    for (i, source) in sources.iter_mut().enumerate() {
+        match source.next() {
+            Some((time, events)) => {
+                heap.push(Reverse((time, i)));
+                curr_events.push(events);
+            }
+            None => curr_events.push(Vec::new()),
+        }
     }
+
     let output_file_string = format!("{}events.xml", args.path);
     let output_file_path = PathBuf::from(output_file_string);
     let mut publisher = EventsPublisher::new();
     publisher.add_subscriber(Box::new(XmlEventsWriter::new(&output_file_path)));
 
-    while !readers.is_empty() {
-        readers.sort_by(|a, b| a.curr_time_step.0.cmp(&b.curr_time_step.0));
-
-        // get the reader with the smallest curr time step and process its events
-        let reader = readers.first_mut().unwrap();
-
-        process_events(
-            reader.curr_time_step.0,
-            &reader.curr_time_step.1,
-            &mut publisher,
-        );
-        match reader.load_next() {
-            None => {
-                readers.remove(0);
-            }
-            Some(_) => {}
-        };
+    while let Some(Reverse((time, i))) = heap.pop() {
+        for event in &curr_events[i] {
+            publisher.publish_event(time, event);
+        }
+        if let Some((next_time, events)) = sources[i].next() {
+            curr_events[i] = events;
+            heap.push(Reverse((next_time, i)));
+        }
     }
 
     info!("Finished reading proto files. Calling finish on XmlWriter");
@@ -78,10 +81,47 @@ fn main() {
     info!("Finished writing to xml-file.")
 }
 
-fn process_events(time: u32, events: &Vec<Event>, publisher: &mut EventsPublisher) {
-    for event in events {
-        publisher.publish_event(time, event);
+/// Time steps of the reader within `[start, end)`, with their events in sequence order.
+fn in_window(
+    reader: EventsReader<File>,
+    start: u32,
+    end: u32,
+) -> impl Iterator<Item = (u32, Vec<Event>)> {
+    reader
+        .skip_while(move |(time, _)| *time < start)
+        .take_while(move |(time, _)| *time < end)
+        .map(|(time, mut events)| {
+            events.sort_by_key(|event| event.sequence);
+            (time, events)
+        })
+}
+
+/// Decodes the events file in its own thread. Only decoding happens in the thread, as ids are
+/// stored per thread and are only loaded on the main thread, which writes the xml.
+fn decode_in_thread(file_path: PathBuf, start: u32, end: u32) -> TimeSteps {
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    thread::spawn(move || {
+        for time_step in in_window(EventsReader::from_file(&file_path), start, end) {
+            if sender.send(time_step).is_err() {
+                // the merger has stopped, so there is no one to send to.
+                break;
+            }
+        }
+    });
+    Box::new(receiver.into_iter())
+}
+
+/// Parses a time window in seconds, e.g. `21600:36000`. The end is exclusive.
+fn parse_time_window(value: &str) -> Result<(u32, u32), String> {
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("Expected a time window as <start>:<end>, but got {value}"))?;
+    let start: u32 = start.parse().map_err(|e| format!("Invalid start: {e}"))?;
+    let end: u32 = end.parse().map_err(|e| format!("Invalid end: {e}"))?;
+    if start >= end {
+        return Err(format!("Start {start} must be before end {end}"));
     }
+    Ok((start, end))
 }
 
 #[derive(Parser, Debug)]
@@ -92,4 +132,10 @@ struct InputArgs {
     pub id_store: String,
     #[arg(long, default_value_t = 1)]
     pub num_parts: u32,
+    /// Only convert events within <start>:<end> in seconds. The end is exclusive.
+    #[arg(long, value_parser = parse_time_window)]
+    pub time_window: Option<(u32, u32)>,
+    /// Decode each partition file in its own thread.
+    #[arg(long)]
+    pub parallel: bool,
 }
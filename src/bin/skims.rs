@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use tracing::info;
+
+use rust_q_sim::simulation::analysis::skims::{read_zones, MatrixFormat, Skims};
+use rust_q_sim::simulation::id;
+use rust_q_sim::simulation::id::Id;
+use rust_q_sim::simulation::io::proto_events::EventsReader;
+use rust_q_sim::simulation::io::xml_events::XmlEventsReader;
+use rust_q_sim::simulation::logging::init_std_out_logging;
+use rust_q_sim::simulation::messaging::events::EventsSubscriber;
+use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::replanning::routing::travel_time_collector::TravelTimeCollector;
+use rust_q_sim::simulation::vehicles::defaults::default_vehicle_type;
+use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::wire_types::vehicles::VehicleType;
+
+/// Computes zone to zone travel time and distance skims. Travel times are freespeed travel times,
+/// unless events of a run are passed, in which case the mean travel times of the links in the run
+/// are used.
+fn main() {
+    init_std_out_logging();
+    let args = InputArgs::parse();
+    info!("Skims with args: {args:?}");
+
+    // proto events reference internal ids, so the ids of the run have to be loaded first.
+    if let Some(id_store) = &args.id_store {
+        id::load_from_file(&PathBuf::from(id_store));
+    }
+    let network = Network::from_file_as_is(&PathBuf::from(&args.network));
+    let veh_type = if let Some(vehicles) = &args.vehicles {
+        let garage = Garage::from_file(&PathBuf::from(vehicles));
+        garage
+            .vehicle_types
+            .get(&Id::<VehicleType>::get_from_ext(&args.vehicle_type))
+            .unwrap_or_else(|| panic!("There is no vehicle type {}", args.vehicle_type))
+            .clone()
+    } else {
+        default_vehicle_type(&args.vehicle_type)
+    };
+    let zones = read_zones(&PathBuf::from(&args.zones));
+
+    let travel_times = if args.xml_events.is_some() || args.events_path.is_some() {
+        let mut collector = TravelTimeCollector::new();
+        read_events(&args, &mut collector);
+        Some(collector.get_travel_times())
+    } else {
+        None
+    };
+
+    info!("Computing skims for {} zones", zones.len());
+    let skims = Skims::compute(&network, &veh_type, &zones, travel_times);
+    skims.write(&PathBuf::from(&args.output), args.format);
+    info!("Finished writing skims to {}", args.output);
+}
+
+fn read_events(args: &InputArgs, collector: &mut TravelTimeCollector) {
+    if let Some(xml_events) = &args.xml_events {
+        info!("Reading events from {xml_events}");
+        for (time, events) in XmlEventsReader::new(&PathBuf::from(xml_events)) {
+            for event in &events {
+                collector.receive_event(time, event);
+            }
+        }
+    } else if let Some(path) = &args.events_path {
+        for i in 0..args.num_parts {
+            let file_path = PathBuf::from(format!("{path}events.{i}.binpb"));
+            info!("Reading events from {file_path:?}");
+            for (time, events) in EventsReader::from_file(&file_path) {
+                for event in &events {
+                    collector.receive_event(time, event);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct InputArgs {
+    #[arg(long)]
+    pub network: String,
+    /// Vehicles file containing the vehicle type. If not set, the default vehicle type of the mode
+    /// with the id of the vehicle type is used.
+    #[arg(long)]
+    pub vehicles: Option<String>,
+    #[arg(long, default_value = "car")]
+    pub vehicle_type: String,
+    /// Csv file with the header `id,x,y`.
+    #[arg(long)]
+    pub zones: String,
+    #[arg(long)]
+    pub output: String,
+    #[arg(long, value_enum, default_value_t = MatrixFormat::Square)]
+    pub format: MatrixFormat,
+    /// Xml events file of a run to compute travel times from.
+    #[arg(long)]
+    pub xml_events: Option<String>,
+    /// Prefix of the proto events files `<events_path>events.<partition>.binpb` of a run to compute
+    /// travel times from. Requires the id store of the run.
+    #[arg(long)]
+    pub events_path: Option<String>,
+    #[arg(long)]
+    pub id_store: Option<String>,
+    #[arg(long, default_value_t = 1)]
+    pub num_parts: u32,
+}
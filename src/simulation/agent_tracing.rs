@@ -0,0 +1,165 @@
+use std::any::Any;
+use std::fmt::Arguments;
+
+use nohash_hasher::{IntMap, IntSet};
+use tracing::info;
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::io::xml_events::XmlEventsWriter;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
+
+/// Logs every state transition of a configured set of persons together with the rank and the time
+/// it happened at. This is meant for debugging agents, which e.g. disappear at partition boundaries.
+///
+/// The tracer is an events subscriber and traces all events concerning the persons. Transitions
+/// which are not published as events, such as sending vehicles to other partitions, are traced by
+/// the simulation directly. All entries are logged with the target `agent_trace`.
+pub struct AgentTracer {
+    rank: u32,
+    persons: IntSet<u64>,
+    // traced persons by the vehicle they are currently in. Used to trace link events.
+    vehicles: IntMap<u64, IntSet<u64>>,
+}
+
+impl AgentTracer {
+    pub fn new(config: &config::AgentTracing, rank: u32) -> Self {
+        let persons = config
+            .person_ids
+            .iter()
+            .map(|id| Id::<Person>::get_from_ext(id).internal())
+            .collect();
+        AgentTracer {
+            rank,
+            persons,
+            vehicles: IntMap::default(),
+        }
+    }
+
+    pub fn is_traced(&self, person_id: u64) -> bool {
+        self.persons.contains(&person_id)
+    }
+
+    pub fn trace_person(&self, now: u32, person_id: u64, what: Arguments) {
+        if self.is_traced(person_id) {
+            info!(
+                target: "agent_trace",
+                rank = self.rank,
+                time = now,
+                person = Id::<Person>::get(person_id).external(),
+                "{what}"
+            );
+        }
+    }
+
+    /// Traces all persons in the vehicle. The vehicle is remembered, so that its link events on
+    /// this partition are traced as well.
+    pub fn trace_vehicle(&mut self, now: u32, vehicle: &Vehicle, what: Arguments) {
+        let traced: Vec<_> = vehicle
+            .driver
+            .iter()
+            .chain(vehicle.passengers.iter())
+            .map(|p| p.id)
+            .filter(|id| self.is_traced(*id))
+            .collect();
+        for person_id in traced {
+            self.vehicles
+                .entry(vehicle.id)
+                .or_default()
+                .insert(person_id);
+            self.trace_person(now, person_id, what);
+        }
+    }
+
+    fn persons_in_vehicle(&self, vehicle_id: u64) -> Vec<u64> {
+        self.vehicles
+            .get(&vehicle_id)
+            .map(|persons| persons.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl EventsSubscriber for AgentTracer {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        let persons = match event.r#type.as_ref().unwrap() {
+            Type::Generic(_) => vec![],
+            Type::ActStart(e) => vec![e.person],
+            Type::ActEnd(e) => vec![e.person],
+            Type::LinkEnter(e) => self.persons_in_vehicle(e.vehicle),
+            Type::LinkLeave(e) => self.persons_in_vehicle(e.vehicle),
+            Type::PersonEntersVeh(e) => {
+                if self.is_traced(e.person) {
+                    self.vehicles.entry(e.vehicle).or_default().insert(e.person);
+                }
+                vec![e.person]
+            }
+            Type::PersonLeavesVeh(e) => {
+                if let Some(persons) = self.vehicles.get_mut(&e.vehicle) {
+                    persons.remove(&e.person);
+                    if persons.is_empty() {
+                        self.vehicles.remove(&e.vehicle);
+                    }
+                }
+                vec![e.person]
+            }
+            Type::Departure(e) => vec![e.person],
+            Type::Arrival(e) => vec![e.person],
+            Type::Travelled(e) => vec![e.person],
+        };
+
+        for person_id in persons {
+            if self.is_traced(person_id) {
+                let event_string = XmlEventsWriter::event_2_string(time, event);
+                self.trace_person(time, person_id, format_args!("{}", event_string.trim_end()));
+            }
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::agent_tracing::AgentTracer;
+    use crate::simulation::config::AgentTracing;
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::Person;
+    use crate::test_utils;
+
+    #[test]
+    fn remember_vehicles_of_traced_persons() {
+        let traced: Id<Person> = Id::create("agent-tracing-traced");
+        let other: Id<Person> = Id::create("agent-tracing-other");
+        let mut tracer = AgentTracer::new(
+            &AgentTracing {
+                person_ids: vec![traced.external().to_string()],
+            },
+            0,
+        );
+        assert!(tracer.is_traced(traced.internal()));
+        assert!(!tracer.is_traced(other.internal()));
+
+        tracer.receive_event(0, &Event::new_person_enters_veh(traced.internal(), 1));
+        tracer.receive_event(0, &Event::new_person_enters_veh(other.internal(), 2));
+        assert_eq!(vec![traced.internal()], tracer.persons_in_vehicle(1));
+        assert!(tracer.persons_in_vehicle(2).is_empty());
+
+        tracer.receive_event(10, &Event::new_person_leaves_veh(traced.internal(), 1));
+        assert!(tracer.persons_in_vehicle(1).is_empty());
+
+        // vehicles received from other partitions are remembered as well
+        let agent = test_utils::create_agent(traced.internal(), vec![]);
+        let vehicle = Vehicle::new(3, 0, 10., 1., Some(agent));
+        tracer.trace_vehicle(20, &vehicle, format_args!("received"));
+        assert_eq!(vec![traced.internal()], tracer.persons_in_vehicle(3));
+    }
+}
@@ -0,0 +1,298 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+
+// Event types of matsim's drt contrib. They are read as generic events.
+pub const REQUEST_SUBMITTED: &str = "DrtRequest submitted";
+pub const REQUEST_REJECTED: &str = "PassengerRequest rejected";
+pub const PASSENGER_PICKED_UP: &str = "passenger picked up";
+pub const PASSENGER_DROPPED_OFF: &str = "passenger dropped off";
+
+/// Service quality of drt within one time bin. Requests, rejections, wait times and detours are
+/// assigned to the bin in which the request was submitted.
+#[derive(Debug, Default, PartialEq)]
+pub struct DrtBinStats {
+    pub requests: usize,
+    pub rejections: usize,
+    pub wait_times: Vec<u32>,
+    /// Ride time divided by the ride time of a direct trip.
+    pub detour_factors: Vec<f64>,
+    /// Seconds passengers spent in vehicles during the bin.
+    pub passenger_seconds: u64,
+    /// Seconds vehicles carried at least one passenger during the bin.
+    pub occupied_seconds: u64,
+}
+
+impl DrtBinStats {
+    pub fn rejection_rate(&self) -> f64 {
+        ratio(self.rejections as f64, self.requests as f64)
+    }
+
+    pub fn mean_wait_time(&self) -> f64 {
+        ratio(
+            self.wait_times.iter().map(|w| *w as f64).sum(),
+            self.wait_times.len() as f64,
+        )
+    }
+
+    pub fn mean_detour_factor(&self) -> f64 {
+        ratio(
+            self.detour_factors.iter().sum(),
+            self.detour_factors.len() as f64,
+        )
+    }
+
+    /// Average number of passengers in vehicles which carry at least one passenger.
+    pub fn mean_occupancy(&self) -> f64 {
+        ratio(self.passenger_seconds as f64, self.occupied_seconds as f64)
+    }
+}
+
+fn ratio(a: f64, b: f64) -> f64 {
+    if b == 0. {
+        0.
+    } else {
+        a / b
+    }
+}
+
+struct Request {
+    submitted: u32,
+    unshared_ride_time: f64,
+    picked_up: Option<u32>,
+}
+
+struct VehicleLoad {
+    passengers: u32,
+    since: u32,
+}
+
+/// Aggregates matsim drt events into service quality statistics per time bin and writes them to
+/// `drt_stats.csv`.
+pub struct DrtStats {
+    bin_size: u32,
+    requests: HashMap<String, Request>,
+    vehicles: HashMap<String, VehicleLoad>,
+    bins: BTreeMap<u32, DrtBinStats>,
+    output_path: Option<PathBuf>,
+}
+
+impl DrtStats {
+    pub fn new(bin_size: u32, output_path: Option<PathBuf>) -> Self {
+        assert!(bin_size > 0, "Time bins must be at least one second long.");
+        DrtStats {
+            bin_size,
+            requests: HashMap::new(),
+            vehicles: HashMap::new(),
+            bins: BTreeMap::new(),
+            output_path,
+        }
+    }
+
+    /// Statistics of the time bin which contains `time`.
+    pub fn stats(&self, time: u32) -> Option<&DrtBinStats> {
+        self.bins.get(&self.bin(time))
+    }
+
+    fn bin(&self, time: u32) -> u32 {
+        time / self.bin_size * self.bin_size
+    }
+
+    fn bin_stats(&mut self, time: u32) -> &mut DrtBinStats {
+        let bin = self.bin(time);
+        self.bins.entry(bin).or_default()
+    }
+
+    fn handle(&mut self, time: u32, event_type: &str, attrs: &HashMap<String, String>) {
+        let request_id = || attrs.get("request").cloned().unwrap_or_default();
+        match event_type {
+            REQUEST_SUBMITTED => {
+                let unshared_ride_time = attrs
+                    .get("unsharedRideTime")
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or(0.);
+                self.requests.insert(
+                    request_id(),
+                    Request {
+                        submitted: time,
+                        unshared_ride_time,
+                        picked_up: None,
+                    },
+                );
+                self.bin_stats(time).requests += 1;
+            }
+            REQUEST_REJECTED => {
+                let submitted = self
+                    .requests
+                    .remove(&request_id())
+                    .map_or(time, |r| r.submitted);
+                self.bin_stats(submitted).rejections += 1;
+            }
+            PASSENGER_PICKED_UP => {
+                self.change_load(time, attrs, 1);
+                let submitted = self.requests.get_mut(&request_id()).map(|request| {
+                    request.picked_up = Some(time);
+                    request.submitted
+                });
+                if let Some(submitted) = submitted {
+                    self.bin_stats(submitted).wait_times.push(time - submitted);
+                }
+            }
+            PASSENGER_DROPPED_OFF => {
+                self.change_load(time, attrs, -1);
+                if let Some(request) = self.requests.remove(&request_id()) {
+                    if let Some(picked_up) = request.picked_up {
+                        if request.unshared_ride_time > 0. {
+                            let detour = (time - picked_up) as f64 / request.unshared_ride_time;
+                            self.bin_stats(request.submitted)
+                                .detour_factors
+                                .push(detour);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn change_load(&mut self, time: u32, attrs: &HashMap<String, String>, change: i32) {
+        let vehicle = attrs.get("vehicle").cloned().unwrap_or_default();
+        let load = self.vehicles.entry(vehicle).or_insert(VehicleLoad {
+            passengers: 0,
+            since: time,
+        });
+        let (passengers, since) = (load.passengers, load.since);
+        load.passengers = (passengers as i32 + change).max(0) as u32;
+        load.since = time;
+        self.add_load(since, time, passengers);
+    }
+
+    /// Adds the load of a vehicle between `start` and `end` to the bins it overlaps.
+    fn add_load(&mut self, start: u32, end: u32, passengers: u32) {
+        if passengers == 0 {
+            return;
+        }
+        let mut from = start;
+        while from < end {
+            let to = end.min(self.bin(from) + self.bin_size);
+            let stats = self.bin_stats(from);
+            stats.passenger_seconds += (passengers * (to - from)) as u64;
+            stats.occupied_seconds += (to - from) as u64;
+            from = to;
+        }
+    }
+
+    fn write_csv(&mut self, path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file =
+            File::create(path).unwrap_or_else(|_| panic!("Failed to create drt stats at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(
+            writer,
+            "time_bin,requests,rejections,rejection_rate,mean_wait_time,p95_wait_time,mean_detour_factor,mean_occupancy"
+        )
+        .unwrap();
+        for (bin, stats) in self.bins.iter_mut() {
+            stats.wait_times.sort_unstable();
+            let p95 = if stats.wait_times.is_empty() {
+                0
+            } else {
+                let rank = (0.95 * stats.wait_times.len() as f64).ceil() as usize;
+                stats.wait_times[rank.clamp(1, stats.wait_times.len()) - 1]
+            };
+            writeln!(
+                writer,
+                "{bin},{},{},{},{},{p95},{},{}",
+                stats.requests,
+                stats.rejections,
+                stats.rejection_rate(),
+                stats.mean_wait_time(),
+                stats.mean_detour_factor(),
+                stats.mean_occupancy()
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+impl EventsSubscriber for DrtStats {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        if let Some(Type::Generic(e)) = event.r#type.as_ref() {
+            self.handle(time, &e.r#type, &e.attrs);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.output_path.clone() {
+            self.write_csv(&path);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::simulation::analysis::drt_stats::{
+        DrtStats, PASSENGER_DROPPED_OFF, PASSENGER_PICKED_UP, REQUEST_REJECTED, REQUEST_SUBMITTED,
+    };
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::wire_types::events::Event;
+
+    fn event(event_type: &str, attrs: &[(&str, &str)]) -> Event {
+        let attrs: HashMap<String, String> = attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Event::new_generic(event_type, attrs)
+    }
+
+    #[test]
+    fn service_quality() {
+        let mut stats = DrtStats::new(3600, None);
+        let submitted = |request| {
+            event(
+                REQUEST_SUBMITTED,
+                &[("request", request), ("unsharedRideTime", "100.0")],
+            )
+        };
+        stats.receive_event(100, &submitted("r1"));
+        stats.receive_event(200, &submitted("r2"));
+        stats.receive_event(300, &submitted("r3"));
+        stats.receive_event(301, &event(REQUEST_REJECTED, &[("request", "r3")]));
+
+        // both passengers share the vehicle between 500 and 600
+        let r1 = [("request", "r1"), ("vehicle", "v1")];
+        let r2 = [("request", "r2"), ("vehicle", "v1")];
+        stats.receive_event(400, &event(PASSENGER_PICKED_UP, &r1));
+        stats.receive_event(500, &event(PASSENGER_PICKED_UP, &r2));
+        stats.receive_event(600, &event(PASSENGER_DROPPED_OFF, &r1));
+        stats.receive_event(3700, &event(PASSENGER_DROPPED_OFF, &r2));
+
+        let first_hour = stats.stats(0).unwrap();
+        assert_eq!(3, first_hour.requests);
+        assert_eq!(1, first_hour.rejections);
+        assert_eq!(vec![300, 300], first_hour.wait_times);
+        assert_eq!(vec![2., 32.], first_hour.detour_factors);
+        // 100s with one, 100s with two, and 3000s with one passenger in the first hour.
+        assert_eq!(3300, first_hour.passenger_seconds);
+        assert_eq!(3200, first_hour.occupied_seconds);
+
+        let second_hour = stats.stats(3600).unwrap();
+        assert_eq!(0, second_hour.requests);
+        assert_eq!(100, second_hour.passenger_seconds);
+        assert_eq!(1., second_hour.mean_occupancy());
+    }
+}
@@ -0,0 +1,266 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+
+/// What events are counted by.
+#[derive(PartialEq, Eq, Debug, ValueEnum, Clone, Copy)]
+pub enum Aggregation {
+    /// The partition, whose events file contained the event.
+    Partition,
+    /// The link of the event.
+    Link,
+    /// The activity type of activity start and end events.
+    ActType,
+    /// The mode of departure, arrival and travelled events.
+    Mode,
+}
+
+impl Aggregation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Partition => "partition",
+            Aggregation::Link => "link",
+            Aggregation::ActType => "act_type",
+            Aggregation::Mode => "mode",
+        }
+    }
+}
+
+/// Number of events per key, event type and time bin.
+#[derive(Debug)]
+pub struct TimeBinnedCounts {
+    bin_size: u32,
+    counts: BTreeMap<(String, String, u32), usize>,
+}
+
+impl TimeBinnedCounts {
+    pub fn new(bin_size: u32) -> Self {
+        assert!(bin_size > 0, "Time bins must be at least one second long.");
+        TimeBinnedCounts {
+            bin_size,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: String, event: &Event, time: u32) {
+        let bin = time / self.bin_size * self.bin_size;
+        *self
+            .counts
+            .entry((key, event_type(event), bin))
+            .or_default() += 1;
+    }
+
+    /// Count for the time bin which contains `time`.
+    pub fn get(&self, key: &str, event_type: &str, time: u32) -> usize {
+        let bin = time / self.bin_size * self.bin_size;
+        self.counts
+            .get(&(key.to_string(), event_type.to_string(), bin))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Writes one row per key, event type and time bin, where `time_bin` is the start of the bin.
+    pub fn write_csv(&self, key_name: &str, path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Failed to create event counts at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{key_name},event_type,time_bin,count").unwrap();
+        for ((key, event_type, bin), count) in &self.counts {
+            writeln!(writer, "{key},{event_type},{bin},{count}").unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+/// Counts events by their link, activity type or mode. Events without such a key are ignored.
+pub struct EventCounts {
+    aggregation: Aggregation,
+    counts: TimeBinnedCounts,
+    output_path: Option<PathBuf>,
+}
+
+impl EventCounts {
+    pub fn new(aggregation: Aggregation, bin_size: u32, output_path: Option<PathBuf>) -> Self {
+        assert_ne!(
+            Aggregation::Partition,
+            aggregation,
+            "Events are counted per partition with PartitionCounts."
+        );
+        EventCounts {
+            aggregation,
+            counts: TimeBinnedCounts::new(bin_size),
+            output_path,
+        }
+    }
+
+    pub fn counts(&self) -> &TimeBinnedCounts {
+        &self.counts
+    }
+
+    fn key(&self, event: &Event) -> Option<String> {
+        let key = match (self.aggregation, event.r#type.as_ref().unwrap()) {
+            (Aggregation::Link, Type::ActStart(e)) => Id::<Link>::get(e.link),
+            (Aggregation::Link, Type::ActEnd(e)) => Id::<Link>::get(e.link),
+            (Aggregation::Link, Type::LinkEnter(e)) => Id::<Link>::get(e.link),
+            (Aggregation::Link, Type::LinkLeave(e)) => Id::<Link>::get(e.link),
+            (Aggregation::Link, Type::Departure(e)) => Id::<Link>::get(e.link),
+            (Aggregation::Link, Type::Arrival(e)) => Id::<Link>::get(e.link),
+            (Aggregation::ActType, Type::ActStart(e)) => return Some(string_id(e.act_type)),
+            (Aggregation::ActType, Type::ActEnd(e)) => return Some(string_id(e.act_type)),
+            (Aggregation::Mode, Type::Departure(e)) => return Some(string_id(e.leg_mode)),
+            (Aggregation::Mode, Type::Arrival(e)) => return Some(string_id(e.leg_mode)),
+            (Aggregation::Mode, Type::Travelled(e)) => return Some(string_id(e.mode)),
+            _ => return None,
+        };
+        Some(key.external().to_string())
+    }
+}
+
+impl EventsSubscriber for EventCounts {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        if let Some(key) = self.key(event) {
+            self.counts.add(key, event, time);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.output_path.as_ref() {
+            self.counts.write_csv(self.aggregation.name(), path);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Counts all events per partition. The partition is not part of the events, so it has to be set
+/// with [PartitionCounts::set_partition] before the events of a partition are published.
+pub struct PartitionCounts {
+    partition: u32,
+    counts: TimeBinnedCounts,
+    output_path: Option<PathBuf>,
+}
+
+impl PartitionCounts {
+    pub fn new(bin_size: u32, output_path: Option<PathBuf>) -> Self {
+        PartitionCounts {
+            partition: 0,
+            counts: TimeBinnedCounts::new(bin_size),
+            output_path,
+        }
+    }
+
+    pub fn set_partition(&mut self, partition: u32) {
+        self.partition = partition;
+    }
+
+    pub fn counts(&self) -> &TimeBinnedCounts {
+        &self.counts
+    }
+}
+
+impl EventsSubscriber for PartitionCounts {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        self.counts.add(self.partition.to_string(), event, time);
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.output_path.as_ref() {
+            self.counts.write_csv(Aggregation::Partition.name(), path);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Type of the event as it is called in matsim events files.
+pub fn event_type(event: &Event) -> String {
+    let name = match event.r#type.as_ref().unwrap() {
+        Type::Generic(e) => return e.r#type.clone(),
+        Type::ActStart(_) => "actstart",
+        Type::ActEnd(_) => "actend",
+        Type::LinkEnter(_) => "entered link",
+        Type::LinkLeave(_) => "left link",
+        Type::PersonEntersVeh(_) => "PersonEntersVehicle",
+        Type::PersonLeavesVeh(_) => "PersonLeavesVehicle",
+        Type::Departure(_) => "departure",
+        Type::Arrival(_) => "arrival",
+        Type::Travelled(_) => "travelled",
+    };
+    String::from(name)
+}
+
+fn string_id(id: u64) -> String {
+    Id::<String>::get(id).external().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::analysis::event_counts::{Aggregation, EventCounts, PartitionCounts};
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::population::Person;
+
+    #[test]
+    fn count_per_key_and_time_bin() {
+        let person: Id<Person> = Id::create("ec-p");
+        let link: Id<Link> = Id::create("ec-l");
+        let home: Id<String> = Id::create("ec-home");
+        let car: Id<String> = Id::create("ec-car");
+        let events = [
+            (
+                10,
+                Event::new_act_end(person.internal(), link.internal(), home.internal()),
+            ),
+            (
+                10,
+                Event::new_departure(person.internal(), link.internal(), car.internal()),
+            ),
+            (
+                3700,
+                Event::new_arrival(person.internal(), link.internal(), car.internal(), 0.),
+            ),
+            (
+                3700,
+                Event::new_act_start(person.internal(), link.internal(), home.internal()),
+            ),
+        ];
+        let mut by_link = EventCounts::new(Aggregation::Link, 3600, None);
+        let mut by_act_type = EventCounts::new(Aggregation::ActType, 3600, None);
+        let mut by_mode = EventCounts::new(Aggregation::Mode, 3600, None);
+        let mut by_partition = PartitionCounts::new(3600, None);
+        for (i, (time, event)) in events.iter().enumerate() {
+            by_partition.set_partition(i as u32 % 2);
+            by_link.receive_event(*time, event);
+            by_act_type.receive_event(*time, event);
+            by_mode.receive_event(*time, event);
+            by_partition.receive_event(*time, event);
+        }
+
+        assert_eq!(1, by_link.counts().get("ec-l", "departure", 0));
+        assert_eq!(1, by_link.counts().get("ec-l", "actstart", 3600));
+        assert_eq!(0, by_link.counts().get("ec-l", "actstart", 0));
+        assert_eq!(1, by_act_type.counts().get("ec-home", "actend", 0));
+        assert_eq!(0, by_act_type.counts().get("ec-home", "departure", 0));
+        assert_eq!(1, by_mode.counts().get("ec-car", "arrival", 3600));
+        assert_eq!(1, by_partition.counts().get("0", "actend", 0));
+        assert_eq!(1, by_partition.counts().get("1", "departure", 0));
+    }
+}
@@ -0,0 +1,177 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use nohash_hasher::IntMap;
+
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+
+/// Distribution of travel times on a link within one time bin.
+#[derive(Debug, PartialEq)]
+pub struct TravelTimeStats {
+    pub count: usize,
+    pub median: u32,
+    pub p85: u32,
+}
+
+/// Collects travel times of vehicles on links from pairs of link enter and leave events. Travel
+/// times are assigned to the time bin in which the vehicle entered the link. Vehicles which depart
+/// on a link have no enter event, so their travel time on the first link of a route is not
+/// collected.
+pub struct LinkTravelTimes {
+    bin_size: u32,
+    /// link and enter time of vehicles which are on a link.
+    entered: IntMap<u64, (u64, u32)>,
+    travel_times: BTreeMap<(u64, u32), Vec<u32>>,
+    output_path: Option<PathBuf>,
+}
+
+impl LinkTravelTimes {
+    pub fn new(bin_size: u32, output_path: Option<PathBuf>) -> Self {
+        assert!(bin_size > 0, "Time bins must be at least one second long.");
+        LinkTravelTimes {
+            bin_size,
+            entered: IntMap::default(),
+            travel_times: BTreeMap::new(),
+            output_path,
+        }
+    }
+
+    /// Statistics of the time bin which contains `time`.
+    pub fn stats(&mut self, link: &Id<Link>, time: u32) -> Option<TravelTimeStats> {
+        let bin = time / self.bin_size * self.bin_size;
+        self.travel_times
+            .get_mut(&(link.internal(), bin))
+            .map(|samples| Self::compute_stats(samples))
+    }
+
+    fn compute_stats(samples: &mut [u32]) -> TravelTimeStats {
+        samples.sort_unstable();
+        TravelTimeStats {
+            count: samples.len(),
+            median: percentile(samples, 0.5),
+            p85: percentile(samples, 0.85),
+        }
+    }
+
+    fn write_csv(&mut self, path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Failed to create link travel times at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "link,time_bin,count,median,p85").unwrap();
+        for ((link, bin), samples) in self.travel_times.iter_mut() {
+            let stats = Self::compute_stats(samples);
+            writeln!(
+                writer,
+                "{},{bin},{},{},{}",
+                Id::<Link>::get(*link).external(),
+                stats.count,
+                stats.median,
+                stats.p85
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+impl EventsSubscriber for LinkTravelTimes {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        match event.r#type.as_ref().unwrap() {
+            Type::LinkEnter(e) => {
+                self.entered.insert(e.vehicle, (e.link, time));
+            }
+            Type::LinkLeave(e) => {
+                if let Some((link, enter_time)) = self.entered.remove(&e.vehicle) {
+                    // events of several partitions are not necessarily ordered by time. Leave
+                    // events before the enter event of the same vehicle are skipped.
+                    let travel_time = time.checked_sub(enter_time).filter(|_| link == e.link);
+                    if let Some(travel_time) = travel_time {
+                        let bin = enter_time / self.bin_size * self.bin_size;
+                        self.travel_times
+                            .entry((link, bin))
+                            .or_default()
+                            .push(travel_time);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.output_path.clone() {
+            self.write_csv(&path);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u32], p: f64) -> u32 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::analysis::link_travel_times::{LinkTravelTimes, TravelTimeStats};
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::wire_types::events::Event;
+
+    #[test]
+    fn travel_time_percentiles() {
+        let link: Id<Link> = Id::create("ltt-l");
+        let next: Id<Link> = Id::create("ltt-next");
+        let mut travel_times = LinkTravelTimes::new(3600, None);
+        // 20 vehicles with travel times 1..=20 in the first hour, one vehicle in the second hour.
+        for veh in 1..=20u32 {
+            travel_times.receive_event(100, &Event::new_link_enter(link.internal(), veh as u64));
+            travel_times.receive_event(
+                100 + veh,
+                &Event::new_link_leave(link.internal(), veh as u64),
+            );
+        }
+        travel_times.receive_event(3590, &Event::new_link_enter(link.internal(), 21));
+        travel_times.receive_event(3650, &Event::new_link_leave(link.internal(), 21));
+        travel_times.receive_event(3700, &Event::new_link_enter(link.internal(), 22));
+        travel_times.receive_event(3730, &Event::new_link_leave(link.internal(), 22));
+        // leaving a different link than the one entered is not a travel time.
+        travel_times.receive_event(3700, &Event::new_link_enter(next.internal(), 23));
+        travel_times.receive_event(3730, &Event::new_link_leave(link.internal(), 23));
+        // a leave event before the enter event, e.g. from another partition's file, is skipped.
+        travel_times.receive_event(3720, &Event::new_link_enter(link.internal(), 24));
+        travel_times.receive_event(3710, &Event::new_link_leave(link.internal(), 24));
+
+        assert_eq!(
+            Some(TravelTimeStats {
+                count: 21,
+                median: 11,
+                p85: 18
+            }),
+            travel_times.stats(&link, 0)
+        );
+        assert_eq!(
+            Some(TravelTimeStats {
+                count: 1,
+                median: 30,
+                p85: 30
+            }),
+            travel_times.stats(&link, 3600)
+        );
+        assert_eq!(None, travel_times.stats(&next, 3600));
+    }
+}
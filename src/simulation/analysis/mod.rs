@@ -0,0 +1,6 @@
+pub mod drt_stats;
+pub mod event_counts;
+pub mod link_travel_times;
+#[cfg(feature = "sqlite")]
+pub mod results_db;
+pub mod skims;
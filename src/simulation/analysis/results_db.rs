@@ -0,0 +1,402 @@
+//! Writes the results of a run into a single SQLite file, which can be queried right away, e.g.
+//! trip durations per main mode or the volumes of a link. The database has the tables
+//!
+//! - `trips`: one row per trip between two main activities, with its main mode and distance.
+//! - `legs`: one row per leg, with the index of the trip it belongs to.
+//! - `link_volumes`: number of vehicles which entered a link per time bin.
+//! - `persons`: number of trips, travel time, distance and money of each person.
+//!
+//! The simulation doesn't score plans, so the `persons` table sums up the money events of a person
+//! instead of a score. All ids are written as external ids.
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use nohash_hasher::IntMap;
+use rusqlite::{params, Connection};
+
+use crate::simulation::fares::PERSON_MONEY_EVENT;
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::Person;
+
+const SCHEMA: &str = "
+    CREATE TABLE trips (
+        person TEXT NOT NULL,
+        trip INTEGER NOT NULL,
+        start_act TEXT NOT NULL,
+        end_act TEXT NOT NULL,
+        start_link TEXT NOT NULL,
+        end_link TEXT NOT NULL,
+        departure_time INTEGER NOT NULL,
+        arrival_time INTEGER NOT NULL,
+        main_mode TEXT NOT NULL,
+        distance REAL NOT NULL
+    );
+    CREATE TABLE legs (
+        person TEXT NOT NULL,
+        trip INTEGER NOT NULL,
+        leg INTEGER NOT NULL,
+        mode TEXT NOT NULL,
+        start_link TEXT NOT NULL,
+        end_link TEXT NOT NULL,
+        departure_time INTEGER NOT NULL,
+        arrival_time INTEGER NOT NULL,
+        distance REAL NOT NULL
+    );
+    CREATE TABLE link_volumes (
+        link TEXT NOT NULL,
+        time_bin INTEGER NOT NULL,
+        volume INTEGER NOT NULL
+    );
+    CREATE TABLE persons (
+        person TEXT PRIMARY KEY,
+        trips INTEGER NOT NULL,
+        travel_time INTEGER NOT NULL,
+        distance REAL NOT NULL,
+        money REAL NOT NULL
+    );";
+
+/// Indices are created after all rows are inserted, which is faster than updating them per row.
+const INDICES: &str = "
+    CREATE INDEX trips_person ON trips (person, trip);
+    CREATE INDEX trips_main_mode ON trips (main_mode);
+    CREATE INDEX legs_person ON legs (person, trip, leg);
+    CREATE INDEX legs_mode ON legs (mode);
+    CREATE INDEX link_volumes_link ON link_volumes (link, time_bin);";
+
+struct Leg {
+    mode: u64,
+    start_link: u64,
+    end_link: u64,
+    departure_time: u32,
+    arrival_time: u32,
+    distance: f64,
+}
+
+/// Trip of a person, which has ended its last main activity.
+struct Trip {
+    start_act: u64,
+    start_link: u64,
+    departure_time: u32,
+    legs: Vec<Leg>,
+}
+
+#[derive(Default)]
+struct PersonStats {
+    trips: u32,
+    travel_time: u32,
+    distance: f64,
+    money: f64,
+}
+
+/// Collects trips and legs from activity, departure and arrival events, and writes them into a
+/// SQLite database. Link volumes and person statistics are written once all events are read.
+pub struct ResultsDatabase {
+    connection: Connection,
+    bin_size: u32,
+    trips: IntMap<u64, Trip>,
+    /// Legs which have departed, but not arrived yet.
+    legs: IntMap<u64, Leg>,
+    link_volumes: BTreeMap<(u64, u32), u32>,
+    persons: IntMap<u64, PersonStats>,
+}
+
+impl ResultsDatabase {
+    /// Creates the database at `path`. An existing database at this path is replaced.
+    pub fn new(path: &Path, bin_size: u32) -> Self {
+        assert!(bin_size > 0, "Time bins must be at least one second long.");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        if path.exists() {
+            fs::remove_file(path).unwrap();
+        }
+        let connection = Connection::open(path)
+            .unwrap_or_else(|e| panic!("Failed to create results database at {path:?}: {e}"));
+        connection.execute_batch(SCHEMA).unwrap();
+        // all rows are inserted within one transaction, which is committed in finish.
+        connection.execute_batch("BEGIN").unwrap();
+        ResultsDatabase {
+            connection,
+            bin_size,
+            trips: IntMap::default(),
+            legs: IntMap::default(),
+            link_volumes: BTreeMap::new(),
+            persons: IntMap::default(),
+        }
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    fn start_trip(&mut self, person: u64, link: u64, act_type: u64, time: u32) {
+        self.trips.insert(
+            person,
+            Trip {
+                start_act: act_type,
+                start_link: link,
+                departure_time: time,
+                legs: Vec::new(),
+            },
+        );
+    }
+
+    fn end_trip(&mut self, person: u64, link: u64, act_type: u64, time: u32) {
+        let trip = match self.trips.remove(&person) {
+            Some(trip) if !trip.legs.is_empty() => trip,
+            _ => return,
+        };
+        let stats = self.persons.entry(person).or_default();
+        let index = stats.trips;
+        let distance: f64 = trip.legs.iter().map(|leg| leg.distance).sum();
+        stats.trips += 1;
+        stats.travel_time += time - trip.departure_time;
+        stats.distance += distance;
+
+        // the same rule as for plans: with access and egress legs, the second leg is the main leg.
+        let main_leg = if trip.legs.len() >= 3 { 1 } else { 0 };
+        let person_id = Id::<Person>::get(person);
+        self.connection
+            .prepare_cached("INSERT INTO trips VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")
+            .unwrap()
+            .execute(params![
+                person_id.external(),
+                index,
+                Id::<String>::get(trip.start_act).external(),
+                Id::<String>::get(act_type).external(),
+                Id::<Link>::get(trip.start_link).external(),
+                Id::<Link>::get(link).external(),
+                trip.departure_time,
+                time,
+                Id::<String>::get(trip.legs[main_leg].mode).external(),
+                distance,
+            ])
+            .unwrap();
+
+        let mut insert_leg = self
+            .connection
+            .prepare_cached("INSERT INTO legs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")
+            .unwrap();
+        for (leg_index, leg) in trip.legs.iter().enumerate() {
+            insert_leg
+                .execute(params![
+                    person_id.external(),
+                    index,
+                    leg_index,
+                    Id::<String>::get(leg.mode).external(),
+                    Id::<Link>::get(leg.start_link).external(),
+                    Id::<Link>::get(leg.end_link).external(),
+                    leg.departure_time,
+                    leg.arrival_time,
+                    leg.distance,
+                ])
+                .unwrap();
+        }
+    }
+
+    fn write_link_volumes(&self) {
+        let mut insert = self
+            .connection
+            .prepare_cached("INSERT INTO link_volumes VALUES (?1, ?2, ?3)")
+            .unwrap();
+        for ((link, bin), volume) in &self.link_volumes {
+            insert
+                .execute(params![Id::<Link>::get(*link).external(), bin, volume])
+                .unwrap();
+        }
+    }
+
+    fn write_persons(&self) {
+        let mut insert = self
+            .connection
+            .prepare_cached("INSERT INTO persons VALUES (?1, ?2, ?3, ?4, ?5)")
+            .unwrap();
+        for (person, stats) in &self.persons {
+            insert
+                .execute(params![
+                    Id::<Person>::get(*person).external(),
+                    stats.trips,
+                    stats.travel_time,
+                    stats.distance,
+                    stats.money,
+                ])
+                .unwrap();
+        }
+    }
+
+    fn add_money(&mut self, attrs: &HashMap<String, String>) {
+        let person = attrs
+            .get("person")
+            .map(|p| Id::<Person>::create(p.as_str()));
+        let amount = attrs.get("amount").and_then(|a| a.parse::<f64>().ok());
+        if let (Some(person), Some(amount)) = (person, amount) {
+            self.persons.entry(person.internal()).or_default().money += amount;
+        }
+    }
+}
+
+impl EventsSubscriber for ResultsDatabase {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        match event.r#type.as_ref().unwrap() {
+            Type::ActEnd(e) if !is_interaction(e.act_type) => {
+                self.start_trip(e.person, e.link, e.act_type, time);
+            }
+            Type::ActStart(e) if !is_interaction(e.act_type) => {
+                self.end_trip(e.person, e.link, e.act_type, time);
+            }
+            Type::Departure(e) => {
+                self.legs.insert(
+                    e.person,
+                    Leg {
+                        mode: e.leg_mode,
+                        start_link: e.link,
+                        end_link: e.link,
+                        departure_time: time,
+                        arrival_time: time,
+                        distance: 0.,
+                    },
+                );
+            }
+            Type::Travelled(e) => {
+                if let Some(leg) = self.legs.get_mut(&e.person) {
+                    leg.distance += e.distance;
+                }
+            }
+            Type::Arrival(e) => {
+                if let Some(mut leg) = self.legs.remove(&e.person) {
+                    leg.end_link = e.link;
+                    leg.arrival_time = time;
+                    // java matsim writes the distance of teleported legs into travelled events.
+                    leg.distance = leg.distance.max(e.distance);
+                    if let Some(trip) = self.trips.get_mut(&e.person) {
+                        trip.legs.push(leg);
+                    }
+                }
+            }
+            Type::LinkEnter(e) => {
+                let bin = time / self.bin_size * self.bin_size;
+                *self.link_volumes.entry((e.link, bin)).or_default() += 1;
+            }
+            Type::Generic(e) if e.r#type == PERSON_MONEY_EVENT => self.add_money(&e.attrs),
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        self.write_link_volumes();
+        self.write_persons();
+        self.connection.execute_batch(INDICES).unwrap();
+        self.connection.execute_batch("COMMIT").unwrap();
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn is_interaction(act_type: u64) -> bool {
+    Id::<String>::get(act_type)
+        .external()
+        .contains("interaction")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use crate::simulation::analysis::results_db::ResultsDatabase;
+    use crate::simulation::fares::PERSON_MONEY_EVENT;
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::population::Person;
+
+    #[test]
+    fn write_trips_and_legs() {
+        let path =
+            PathBuf::from("./test_output/analysis/results_db/write_trips_and_legs/results.db");
+        let mut db = ResultsDatabase::new(&path, 3600);
+        let person = Id::<Person>::create("rdb-p").internal();
+        let home = Id::<Link>::create("rdb-home").internal();
+        let stop = Id::<Link>::create("rdb-stop").internal();
+        let work = Id::<Link>::create("rdb-work").internal();
+        let h = Id::<String>::create("h").internal();
+        let w = Id::<String>::create("w").internal();
+        let interaction = Id::<String>::create("pt interaction").internal();
+        let walk = Id::<String>::create("walk").internal();
+        let pt = Id::<String>::create("pt").internal();
+
+        // home - walk - interaction - pt - interaction - walk - work
+        let events = vec![
+            (100, Event::new_act_end(person, home, h)),
+            (100, Event::new_departure(person, home, walk)),
+            (200, Event::new_arrival(person, stop, walk, 80.)),
+            (200, Event::new_act_start(person, stop, interaction)),
+            (200, Event::new_act_end(person, stop, interaction)),
+            (200, Event::new_departure(person, stop, pt)),
+            (250, Event::new_link_enter(work, 1)),
+            (280, Event::new_arrival(person, work, pt, 1000.)),
+            (280, Event::new_act_start(person, work, interaction)),
+            (280, Event::new_act_end(person, work, interaction)),
+            (280, Event::new_departure(person, work, walk)),
+            (300, Event::new_arrival(person, work, walk, 20.)),
+            (
+                300,
+                Event::new_generic(
+                    PERSON_MONEY_EVENT,
+                    HashMap::from([
+                        (String::from("person"), String::from("rdb-p")),
+                        (String::from("amount"), String::from("-2.5")),
+                    ]),
+                ),
+            ),
+            (300, Event::new_act_start(person, work, w)),
+        ];
+        for (time, event) in &events {
+            db.receive_event(*time, event);
+        }
+        db.finish();
+
+        let connection = db.connection();
+        let (main_mode, distance, duration): (String, f64, u32) = connection
+            .query_row(
+                "SELECT main_mode, distance, arrival_time - departure_time FROM trips WHERE person = 'rdb-p'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!("pt", main_mode);
+        assert_eq!(1100., distance);
+        assert_eq!(200, duration);
+
+        let legs: u32 = connection
+            .query_row("SELECT COUNT(*) FROM legs WHERE trip = 0", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(3, legs);
+        let volume: u32 = connection
+            .query_row(
+                "SELECT volume FROM link_volumes WHERE link = 'rdb-work' AND time_bin = 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(1, volume);
+        let money: f64 = connection
+            .query_row(
+                "SELECT money FROM persons WHERE person = 'rdb-p'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(-2.5, money);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::network::spatial_index::SpatialIndex;
+use crate::simulation::replanning::routing::alt_router::AltRouter;
+use crate::simulation::replanning::routing::network_converter::NetworkConverter;
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+/// A zone of a demand model, represented by a single point, e.g. its centroid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Reads zones from a csv file with the header `id,x,y`.
+pub fn read_zones(path: &Path) -> Vec<Zone> {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Failed to open zones at {path:?}"));
+    BufReader::new(file)
+        .lines()
+        .skip(1)
+        .map(|line| line.expect("Failed to read zones"))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let values: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+            if values.len() != 3 {
+                panic!("Expected a zone as id,x,y, but got {line}");
+            }
+            Zone {
+                id: values[0].to_string(),
+                x: values[1]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid x of zone {line}")),
+                y: values[2]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid y of zone {line}")),
+            }
+        })
+        .collect()
+}
+
+#[derive(PartialEq, Debug, Default, ValueEnum, Clone, Copy)]
+pub enum MatrixFormat {
+    /// One row per origin and one column per destination.
+    #[default]
+    Square,
+    /// One row per pair of zones: `from,to,travel_time,distance`.
+    Long,
+}
+
+/// Zone to zone travel times in seconds and distances in meters. Each zone is connected to the
+/// network at the closest link which allows the mode of the vehicle type. Trips start at the end
+/// and stop at the start of these links, like routes of the simulation. Pairs of zones without a
+/// route have no value.
+#[derive(Debug)]
+pub struct Skims {
+    pub zones: Vec<String>,
+    pub travel_times: Vec<Vec<Option<u32>>>,
+    pub distances: Vec<Vec<Option<f64>>>,
+}
+
+impl Skims {
+    /// Computes skims with freespeed travel times, or with the given travel times per link, e.g.
+    /// collected from the events of a run with a
+    /// [crate::simulation::replanning::routing::travel_time_collector::TravelTimeCollector].
+    pub fn compute(
+        network: &Network,
+        veh_type: &VehicleType,
+        zones: &[Zone],
+        travel_times: Option<HashMap<u64, u32>>,
+    ) -> Self {
+        let mut router = AltRouter::new(NetworkConverter::convert_network(network, Some(veh_type)));
+        if let Some(travel_times) = travel_times {
+            let graph = router
+                .current_graph()
+                .clone_with_new_travel_times_by_link(travel_times);
+            router.update(graph);
+        }
+
+        let index = SpatialIndex::from_network(network);
+        let mode: Id<String> = Id::get(veh_type.net_mode);
+        let links: Vec<u64> = zones
+            .iter()
+            .map(|zone| {
+                index
+                    .nearest_link(zone.x, zone.y, Some(&mode))
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "No link for mode {} close to zone {}",
+                            mode.external(),
+                            zone.id
+                        )
+                    })
+                    .internal()
+            })
+            .collect();
+
+        // origins are independent, so they are routed in parallel.
+        let (travel_times, distances): (Vec<_>, Vec<_>) = links
+            .par_iter()
+            .map(|from| {
+                let row: (Vec<Option<u32>>, Vec<Option<f64>>) = links
+                    .iter()
+                    .map(|to| Self::query(&router, network, *from, *to))
+                    .unzip();
+                row
+            })
+            .unzip();
+
+        Skims {
+            zones: zones.iter().map(|z| z.id.clone()).collect(),
+            travel_times,
+            distances,
+        }
+    }
+
+    fn query(
+        router: &AltRouter,
+        network: &Network,
+        from: u64,
+        to: u64,
+    ) -> (Option<u32>, Option<f64>) {
+        if from == to {
+            return (Some(0), Some(0.));
+        }
+        let result = router.query_links(from, to);
+        let distance = result.path.map(|path| {
+            path.iter()
+                .skip(1)
+                .map(|id| network.links[*id as usize].length)
+                .sum()
+        });
+        (result.travel_time, distance)
+    }
+
+    /// Writes `skims_travel_time.csv` and `skims_distance.csv` in the square format, or
+    /// `skims.csv` in the long format into the output directory.
+    pub fn write(&self, output_dir: &Path, format: MatrixFormat) {
+        fs::create_dir_all(output_dir).unwrap();
+        match format {
+            MatrixFormat::Square => {
+                Self::write_square(
+                    &self.zones,
+                    &self.travel_times,
+                    &output_dir.join("skims_travel_time.csv"),
+                );
+                Self::write_square(
+                    &self.zones,
+                    &self.distances,
+                    &output_dir.join("skims_distance.csv"),
+                );
+            }
+            MatrixFormat::Long => self.write_long(&output_dir.join("skims.csv")),
+        }
+    }
+
+    fn write_square<T: ToString>(zones: &[String], matrix: &[Vec<Option<T>>], path: &Path) {
+        let mut writer = Self::create_writer(path);
+        writeln!(writer, "from,{}", zones.join(",")).unwrap();
+        for (from, row) in zones.iter().zip(matrix) {
+            let values: Vec<String> = row.iter().map(Self::value_to_string).collect();
+            writeln!(writer, "{from},{}", values.join(",")).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    fn write_long(&self, path: &Path) {
+        let mut writer = Self::create_writer(path);
+        writeln!(writer, "from,to,travel_time,distance").unwrap();
+        for (i, from) in self.zones.iter().enumerate() {
+            for (j, to) in self.zones.iter().enumerate() {
+                writeln!(
+                    writer,
+                    "{from},{to},{},{}",
+                    Self::value_to_string(&self.travel_times[i][j]),
+                    Self::value_to_string(&self.distances[i][j])
+                )
+                .unwrap();
+            }
+        }
+        writer.flush().unwrap();
+    }
+
+    fn value_to_string<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map_or_else(String::new, |v| v.to_string())
+    }
+
+    fn create_writer(path: &Path) -> BufWriter<File> {
+        let file =
+            File::create(path).unwrap_or_else(|_| panic!("Failed to create skims at {path:?}"));
+        BufWriter::new(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::analysis::skims::{Skims, Zone};
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::wire_types::vehicles::VehicleType;
+    use crate::test_utils::create_vehicle_type;
+
+    /// Four nodes in a row, connected by links in both directions.
+    fn network() -> Network {
+        let mut network = Network::new();
+        let car: IntSet<Id<String>> = [Id::create("car")].into_iter().collect();
+        for i in 0..4 {
+            network.add_node(Node::new(
+                Id::create(&format!("sk-n{i}")),
+                i as f64 * 1000.,
+                0.,
+                0,
+                1,
+            ));
+        }
+        for (id, from, to) in [
+            ("sk-01", 0, 1),
+            ("sk-10", 1, 0),
+            ("sk-12", 1, 2),
+            ("sk-21", 2, 1),
+            ("sk-23", 2, 3),
+            ("sk-32", 3, 2),
+        ] {
+            let mut link =
+                Link::new_with_default(Id::create(id), &network.nodes[from], &network.nodes[to]);
+            link.freespeed = 10.;
+            link.modes = car.clone();
+            network.add_link(link);
+        }
+        network
+    }
+
+    fn zones() -> Vec<Zone> {
+        vec![
+            Zone {
+                id: String::from("west"),
+                x: 500.,
+                y: 1.,
+            },
+            Zone {
+                id: String::from("east"),
+                x: 2500.,
+                y: -1.,
+            },
+        ]
+    }
+
+    fn car() -> VehicleType {
+        let mut veh_type = create_vehicle_type(&Id::create("car"), Id::create("car"));
+        veh_type.max_v = 36.;
+        veh_type
+    }
+
+    #[test]
+    fn freespeed_skims() {
+        let network = network();
+        let veh_type = car();
+
+        let skims = Skims::compute(&network, &veh_type, &zones(), None);
+
+        assert_eq!(vec!["west", "east"], skims.zones);
+        assert_eq!(Some(0), skims.travel_times[0][0]);
+        assert!(skims.travel_times[0][1].is_some());
+        assert!(skims.distances[0][1].unwrap() > 0.);
+        assert!(skims.travel_times[1][0].is_some());
+    }
+
+    #[test]
+    fn congested_skims() {
+        let network = network();
+        let veh_type = car();
+        let free = Skims::compute(&network, &veh_type, &zones(), None);
+        let travel_times: HashMap<u64, u32> = network
+            .links
+            .iter()
+            .map(|l| (l.id.internal(), 1000))
+            .collect();
+
+        let congested = Skims::compute(&network, &veh_type, &zones(), Some(travel_times));
+
+        assert!(congested.travel_times[0][1].unwrap() > free.travel_times[0][1].unwrap());
+        assert_eq!(free.distances[0][1], congested.distances[0][1]);
+    }
+}
@@ -0,0 +1,259 @@
+use nohash_hasher::IntSet;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::{Link, Network, Node};
+use crate::simulation::population::population::Population;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// Parameters of a synthetic scenario, which consists of a grid network and a random population.
+/// Agents leave home at a random time, drive to a random link along a manhattan route and stay
+/// there until the end of the simulation.
+#[derive(Debug, Clone)]
+pub struct GridScenario {
+    /// number of nodes per row and column
+    pub size: usize,
+    pub agents: usize,
+    pub partitions: u32,
+    pub link_length: f64,
+    /// agents depart uniformly distributed within [0, departure_window)
+    pub departure_window: u32,
+    pub seed: u64,
+}
+
+impl GridScenario {
+    pub const MODE: &'static str = "car";
+
+    /// Creates a network with size x size nodes and links in both directions between neighboring
+    /// nodes. The grid is split into vertical strips of equal width, one strip per partition.
+    pub fn create_network(&self) -> Network {
+        let mut network = Network::new();
+        let modes: IntSet<Id<String>> = [Id::create(Self::MODE)].into_iter().collect();
+
+        for row in 0..self.size {
+            for col in 0..self.size {
+                let id = Id::create(&format!("{row}_{col}"));
+                let x = col as f64 * self.link_length;
+                let y = row as f64 * self.link_length;
+                network.add_node(Node::new(id, x, y, self.partition(col), 1));
+            }
+        }
+
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if col + 1 < self.size {
+                    self.add_link(&mut network, (row, col), (row, col + 1), &modes);
+                    self.add_link(&mut network, (row, col + 1), (row, col), &modes);
+                }
+                if row + 1 < self.size {
+                    self.add_link(&mut network, (row, col), (row + 1, col), &modes);
+                    self.add_link(&mut network, (row + 1, col), (row, col), &modes);
+                }
+            }
+        }
+
+        network
+    }
+
+    /// Creates the vehicle type of the scenario.
+    pub fn create_garage(&self) -> Garage {
+        let mut garage = Garage::new();
+        garage.add_veh_type(VehicleType {
+            id: Id::<VehicleType>::create(Self::MODE).internal(),
+            length: 7.5,
+            width: 1.,
+            max_v: 36.11,
+            pce: 1.,
+            fef: 1.,
+            net_mode: Id::<String>::create(Self::MODE).internal(),
+            lod: LevelOfDetail::Network as i32,
+            seats: 1,
+            standing_room: 0,
+            attributes: Default::default(),
+        });
+        garage
+    }
+
+    /// Creates the agents and registers their vehicles in the garage.
+    pub fn create_population(&self, network: &Network, garage: &mut Garage) -> Population {
+        assert!(self.size > 1, "The grid needs at least two nodes per row.");
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut population = Population::new();
+        let mode: Id<String> = Id::create(Self::MODE);
+        let veh_type: Id<VehicleType> = Id::create(Self::MODE);
+        let home: Id<String> = Id::create("home");
+        let work: Id<String> = Id::create("work");
+
+        for i in 0..self.agents {
+            let person_id: Id<Person> = Id::create(&i.to_string());
+            let veh_id = garage.add_veh_id(&person_id, &veh_type);
+
+            let start = &network.links[rng.gen_range(0..network.links.len())];
+            let end = loop {
+                let candidate = &network.links[rng.gen_range(0..network.links.len())];
+                if candidate.id != start.id {
+                    break candidate;
+                }
+            };
+            let route = self.route(network, start, end);
+            let distance = route
+                .iter()
+                .skip(1)
+                .map(|id| network.links[*id as usize].length)
+                .sum();
+
+            let departure = rng.gen_range(0..self.departure_window.max(1));
+            let mut plan = Plan::new();
+            plan.add_act(self.activity(network, start, home.internal(), Some(departure)));
+            plan.add_leg(Leg::new(
+                Route {
+                    veh_id: veh_id.internal(),
+                    distance,
+                    route,
+                },
+                mode.internal(),
+                0,
+                Some(departure),
+            ));
+            plan.add_act(self.activity(network, end, work.internal(), None));
+
+            population
+                .persons
+                .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+        }
+
+        population
+    }
+
+    fn partition(&self, col: usize) -> u32 {
+        (col * self.partitions as usize / self.size) as u32
+    }
+
+    fn add_link(
+        &self,
+        network: &mut Network,
+        from: (usize, usize),
+        to: (usize, usize),
+        modes: &IntSet<Id<String>>,
+    ) {
+        let id = Id::create(&format!("{}_{}-{}_{}", from.0, from.1, to.0, to.1));
+        let from_id = Id::get_from_ext(&format!("{}_{}", from.0, from.1));
+        let to_id = Id::get_from_ext(&format!("{}_{}", to.0, to.1));
+        network.add_link(Link::new(
+            id,
+            from_id,
+            to_id,
+            self.link_length,
+            1800.,
+            13.89,
+            1.,
+            modes.clone(),
+            self.partition(to.1),
+        ));
+    }
+
+    /// Route from the start link to the end link, first along the row of the start link's to node,
+    /// then along the column of the end link's from node.
+    fn route(&self, network: &Network, start: &Link, end: &Link) -> Vec<u64> {
+        let mut current = self.coord(network, &start.to);
+        let target = self.coord(network, &end.from);
+        let mut route = vec![start.id.internal()];
+
+        while current != target {
+            let next = if current.1 != target.1 {
+                let col = if current.1 < target.1 {
+                    current.1 + 1
+                } else {
+                    current.1 - 1
+                };
+                (current.0, col)
+            } else if current.0 < target.0 {
+                (current.0 + 1, current.1)
+            } else {
+                (current.0 - 1, current.1)
+            };
+            let link: Id<Link> = Id::get_from_ext(&format!(
+                "{}_{}-{}_{}",
+                current.0, current.1, next.0, next.1
+            ));
+            route.push(link.internal());
+            current = next;
+        }
+
+        route.push(end.id.internal());
+        route
+    }
+
+    /// Row and column of a node.
+    fn coord(&self, network: &Network, node: &Id<Node>) -> (usize, usize) {
+        let node = network.get_node(node);
+        (
+            (node.y / self.link_length).round() as usize,
+            (node.x / self.link_length).round() as usize,
+        )
+    }
+
+    fn activity(
+        &self,
+        network: &Network,
+        link: &Link,
+        act_type: u64,
+        end_time: Option<u32>,
+    ) -> Activity {
+        let to = network.get_node(&link.to);
+        Activity::new(
+            to.x,
+            to.y,
+            act_type,
+            link.id.internal(),
+            None,
+            end_time,
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::benchmark::GridScenario;
+
+    #[test]
+    fn grid_scenario() {
+        let scenario = GridScenario {
+            size: 4,
+            agents: 10,
+            partitions: 2,
+            link_length: 100.,
+            departure_window: 3600,
+            seed: 42,
+        };
+        let network = scenario.create_network();
+        let mut garage = scenario.create_garage();
+        let population = scenario.create_population(&network, &mut garage);
+
+        assert_eq!(16, network.nodes.len());
+        // 3 links per row and column in both directions
+        assert_eq!(2 * 2 * 4 * 3, network.links.len());
+        assert!(network.nodes.iter().any(|n| n.partition == 0));
+        assert!(network.nodes.iter().any(|n| n.partition == 1));
+        assert_eq!(10, population.persons.len());
+        assert_eq!(10, garage.vehicles.len());
+
+        // routes must be connected
+        for person in population.persons.values() {
+            let route = &person.plan.as_ref().unwrap().legs[0]
+                .route
+                .as_ref()
+                .unwrap()
+                .route;
+            for pair in route.windows(2) {
+                let prev = &network.links[pair[0] as usize];
+                let next = &network.links[pair[1] as usize];
+                assert_eq!(prev.to, next.from);
+            }
+        }
+    }
+}
@@ -19,12 +19,18 @@ pub struct CommandLineArgs {
     pub num_parts: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     modules: RefCell<HashMap<String, Box<dyn ConfigModule>>>,
 }
 
 impl Config {
+    /// Creates a config without any modules. Each module falls back to its defaults, until it is
+    /// set explicitly.
+    pub fn new() -> Self {
+        Config::default()
+    }
+
     pub fn from_file(args: &CommandLineArgs) -> Self {
         let reader = BufReader::new(File::open(&args.config_path).unwrap_or_else(|e| {
             panic!(
@@ -50,6 +56,9 @@ impl Config {
                 profiling: config.output().profiling,
                 logging: config.output().logging,
                 write_events: Default::default(),
+                log_filter: config.output().log_filter,
+                events_queue: config.output().events_queue,
+                snapshot_interval: config.output().snapshot_interval,
             });
         }
         config
@@ -99,6 +108,9 @@ impl Config {
                 profiling: Profiling::None,
                 logging: Logging::Info,
                 write_events: Default::default(),
+                log_filter: None,
+                events_queue: None,
+                snapshot_interval: None,
             };
             self.modules
                 .borrow_mut()
@@ -125,13 +137,17 @@ impl Config {
         }
     }
 
+    pub fn set_simulation(&mut self, simulation: Simulation) {
+        self.modules
+            .get_mut()
+            .insert("simulation".to_string(), Box::new(simulation));
+    }
+
     pub fn routing(&self) -> Routing {
         if let Some(routing) = self.module::<Routing>("routing") {
             routing
         } else {
-            let default = Routing {
-                mode: RoutingMode::UsePlans,
-            };
+            let default = Routing::default();
             self.modules
                 .borrow_mut()
                 .insert("routing".to_string(), Box::new(default.clone()));
@@ -139,6 +155,144 @@ impl Config {
         }
     }
 
+    pub fn strategy(&self) -> Strategy {
+        if let Some(strategy) = self.module::<Strategy>("strategy") {
+            strategy
+        } else {
+            let default = Strategy::default();
+            self.modules
+                .borrow_mut()
+                .insert("strategy".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    pub fn households(&self) -> Households {
+        if let Some(households) = self.module::<Households>("households") {
+            households
+        } else {
+            let default = Households::default();
+            self.modules
+                .borrow_mut()
+                .insert("households".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    pub fn mode_availability(&self) -> ModeAvailability {
+        if let Some(mode_availability) = self.module::<ModeAvailability>("mode_availability") {
+            mode_availability
+        } else {
+            let default = ModeAvailability::default();
+            self.modules
+                .borrow_mut()
+                .insert("mode_availability".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    pub fn teleportation(&self) -> Teleportation {
+        if let Some(teleportation) = self.module::<Teleportation>("teleportation") {
+            teleportation
+        } else {
+            let default = Teleportation::default();
+            self.modules
+                .borrow_mut()
+                .insert("teleportation".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    pub fn park_and_ride(&self) -> ParkAndRide {
+        if let Some(park_and_ride) = self.module::<ParkAndRide>("park_and_ride") {
+            park_and_ride
+        } else {
+            let default = ParkAndRide::default();
+            self.modules
+                .borrow_mut()
+                .insert("park_and_ride".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    pub fn communication(&self) -> Communication {
+        if let Some(communication) = self.module::<Communication>("communication") {
+            communication
+        } else {
+            let default = Communication::default();
+            self.modules
+                .borrow_mut()
+                .insert("communication".to_string(), Box::new(default.clone()));
+            default
+        }
+    }
+
+    /// Freight is only simulated if carriers are configured explicitly. Therefore, no default is
+    /// inserted here.
+    pub fn freight(&self) -> Option<Freight> {
+        self.module::<Freight>("freight")
+    }
+
+    pub fn fares(&self) -> Option<Fares> {
+        self.module::<Fares>("fares")
+    }
+
+    pub fn incidents(&self) -> Option<Incidents> {
+        self.module::<Incidents>("incidents")
+    }
+
+    pub fn speed_factors(&self) -> Option<SpeedFactors> {
+        self.module::<SpeedFactors>("speed_factors")
+    }
+
+    pub fn speed_variation(&self) -> Option<SpeedVariation> {
+        self.module::<SpeedVariation>("speed_variation")
+    }
+
+    pub fn opening_hours(&self) -> Option<OpeningHours> {
+        self.module::<OpeningHours>("opening_hours")
+    }
+
+    pub fn stuck_penalty(&self) -> Option<StuckPenalty> {
+        self.module::<StuckPenalty>("stuck_penalty")
+    }
+
+    pub fn evacuation(&self) -> Option<Evacuation> {
+        self.module::<Evacuation>("evacuation")
+    }
+
+    pub fn replay(&self) -> Option<Replay> {
+        self.module::<Replay>("replay")
+    }
+
+    pub fn agent_tracing(&self) -> Option<AgentTracing> {
+        self.module::<AgentTracing>("agent_tracing")
+    }
+
+    pub fn control(&self) -> Option<Control> {
+        self.module::<Control>("control")
+    }
+
+    pub fn consistency_check(&self) -> Option<ConsistencyCheck> {
+        self.module::<ConsistencyCheck>("consistency_check")
+    }
+
+    pub fn transit_priority(&self) -> Option<TransitPriority> {
+        self.module::<TransitPriority>("transit_priority")
+    }
+
+    pub fn telemetry(&self) -> Option<Telemetry> {
+        self.module::<Telemetry>("telemetry")
+    }
+
+    pub fn access_restrictions(&self) -> Option<AccessRestrictions> {
+        self.module::<AccessRestrictions>("access_restrictions")
+    }
+
+    pub fn signals(&self) -> Option<Signals> {
+        self.module::<Signals>("signals")
+    }
+
     fn module<T: Clone + 'static>(&self, key: &str) -> Option<T> {
         self.modules
             .borrow()
@@ -153,6 +307,13 @@ pub struct ProtoFiles {
     pub population: String,
     pub vehicles: String,
     pub ids: String,
+    /// Load only the internal ids of persons, vehicles, links and nodes. This saves start up time
+    /// and memory on large scenarios. External ids of these types are not available during the
+    /// simulation. Therefore, config modules which refer to these ids by their external ids, e.g.
+    /// agent tracing, are rejected. Ids created during the run keep their external ids. Tools
+    /// which convert the output, load the full id store.
+    #[serde(default)]
+    pub compact_ids: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -170,11 +331,116 @@ pub struct Output {
     pub logging: Logging,
     #[serde(default)]
     pub write_events: WriteEvents,
+    #[serde(default)]
+    pub log_filter: Option<LogFilter>,
+    /// Hand events to the events writer on a separate thread. Events are passed in batches of one
+    /// time step.
+    #[serde(default)]
+    pub events_queue: Option<EventsQueue>,
+    /// Writes the links of all vehicles on the network every `snapshot_interval` seconds. Snapshots
+    /// are only written together with `write_events: Hdf5`.
+    #[serde(default)]
+    pub snapshot_interval: Option<u32>,
+}
+
+/// Filter directives in the format of `RUST_LOG`, e.g. `info,rust_q_sim::simulation::messaging=trace`.
+/// If ranks are given, the directives are only applied on these ranks. All other ranks log on info
+/// level.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct LogFilter {
+    pub directives: String,
+    #[serde(default)]
+    pub ranks: Vec<u32>,
+}
+
+impl LogFilter {
+    pub fn applies_to(&self, rank: u32) -> bool {
+        self.ranks.is_empty() || self.ranks.contains(&rank)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct EventsQueue {
+    /// Number of time step batches which may wait for the writer thread.
+    pub capacity: usize,
+    #[serde(default)]
+    pub on_full: QueueFull,
+}
+
+/// What happens to events, if the writer can't keep up with the simulation.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum QueueFull {
+    /// The simulation waits until the writer has caught up.
+    #[default]
+    Block,
+    /// Events of the time step are dropped.
+    Drop,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Routing {
     pub mode: RoutingMode,
+    /// Re-route network legs whose routes are broken, e.g. after the network was changed, when the
+    /// plans are loaded. Otherwise, such plans make the simulation fail before it starts.
+    #[serde(default)]
+    pub repair_routes: bool,
+    /// Insert interaction activities and walking access and egress legs into plans, which only
+    /// consist of main activities and legs, when the plans are loaded.
+    #[serde(default)]
+    pub insert_interactions: bool,
+    /// Apply travel time updates of ad-hoc routing to the routing graphs on a background thread.
+    /// Routing continues with the old travel times until the update is ready, which avoids
+    /// stalling the simulation, but makes routes depend on the speed of the background thread.
+    #[serde(default)]
+    pub background_customization: bool,
+    /// Number of routes kept in the least recently used cache of the ad-hoc replanner. Routes are
+    /// cached per origin link, destination link, vehicle type and time bin, and the cache is
+    /// cleared whenever the routing graphs change. A size of 0 disables the cache.
+    #[serde(default = "default_route_cache_size")]
+    pub route_cache_size: usize,
+    #[serde(default)]
+    pub backend: RoutingBackend,
+    /// Generalized cost parameters by network mode. Routes of these modes minimize travel time
+    /// plus monetary cost, instead of travel time only. Other modes are routed by travel time.
+    #[serde(default)]
+    pub generalized_cost: HashMap<String, GeneralizedCost>,
+}
+
+/// Parameters to convert the monetary cost of a route into time. As routing graphs are shared by
+/// all agents using a mode, the value of time applies per mode and not per person.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GeneralizedCost {
+    /// Value of time in money per hour.
+    pub value_of_time: f64,
+    /// Cost in money per meter driven.
+    #[serde(default)]
+    pub distance_cost: f64,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Routing {
+            mode: RoutingMode::UsePlans,
+            repair_routes: false,
+            insert_interactions: false,
+            background_customization: false,
+            route_cache_size: default_route_cache_size(),
+            backend: RoutingBackend::default(),
+            generalized_cost: HashMap::default(),
+        }
+    }
+}
+
+/// Shortest path algorithm used by ad-hoc routing. Both backends work on the same forward backward
+/// graph, so travel time updates apply to either of them.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum RoutingBackend {
+    /// A* with landmarks. Distances to the landmarks are computed once on the free speed graph,
+    /// which keeps preprocessing cheap, even if the graph changes frequently.
+    #[default]
+    Alt,
+    /// Plain dijkstra without any preprocessing. Useful for very small scenarios.
+    Dijkstra,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -183,6 +449,399 @@ pub struct Simulation {
     pub end_time: u32,
     pub sample_size: f32,
     pub stuck_threshold: u32,
+    /// Time in seconds vehicles cruise for a free parking spot, before they park illegally at
+    /// their destination.
+    #[serde(default = "default_max_parking_search_time")]
+    pub max_parking_search_time: u32,
+    /// Time in seconds passengers of ride legs wait for their driver, before they are removed from
+    /// the simulation as stuck.
+    #[serde(default = "default_max_passenger_wait_time")]
+    pub max_passenger_wait_time: u32,
+    #[serde(default)]
+    pub core: SimulationCore,
+    /// Intersection capacity in PCE per hour applied to all nodes which don't specify a capacity
+    /// attribute. Node capacities are unrestricted if this is not set.
+    #[serde(default)]
+    pub node_capacity: Option<f32>,
+    /// Whether the storage capacity of a link fits at least one vehicle, whatever its pce, even if
+    /// the link is very short. Otherwise, it is only ensured that the flow capacity per time step fits onto a link.
+    #[serde(default)]
+    pub min_storage_of_one_vehicle: bool,
+    /// Move the links of a partition in parallel. This pays off for large partitions with many
+    /// active links. Results are the same as when moving links one after another.
+    #[serde(default)]
+    pub parallel_links: bool,
+    #[serde(default)]
+    pub link_model: LinkModel,
+    #[serde(default)]
+    pub activity_duration_interpretation: ActivityDurationInterpretation,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Households {
+    /// Vehicle type a household member uses instead, if all household vehicles of the requested
+    /// type are already in use by other members.
+    #[serde(default = "default_household_fallback")]
+    pub fallback_veh_type: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModeAvailability {
+    /// Modes which may only be used by persons with a driving license and a car available. By
+    /// default, every person may use every mode.
+    #[serde(default)]
+    pub driver_modes: Vec<String>,
+    /// Vehicle types persons use instead, if they may not use the vehicle of a leg. The first type
+    /// available to the person is chosen.
+    #[serde(default = "default_fallback_veh_types")]
+    pub fallback_veh_types: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Teleportation {
+    /// Beeline speed and distance factor per mode. Teleported legs of other modes use the maximum
+    /// velocity of their vehicle type and the plain beeline distance.
+    #[serde(default)]
+    pub modes: Vec<TeleportedModeParams>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TeleportedModeParams {
+    pub mode: String,
+    pub beeline_speed: f32,
+    #[serde(default = "default_beeline_distance_factor")]
+    pub beeline_distance_factor: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ParkAndRide {
+    /// Activity type agents perform when they switch between car and pt at a facility.
+    #[serde(default = "default_park_and_ride_act")]
+    pub act_type: String,
+    #[serde(default)]
+    pub facilities: Vec<ParkAndRideFacility>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ParkAndRideFacility {
+    pub link: String,
+    pub capacity: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Freight {
+    /// Carriers file. The contained tours are expected to be part of the population already. This
+    /// file is used to compute tour statistics.
+    pub carriers: String,
+}
+
+/// Plan innovation across the iterations of the controller. Every iteration starts from the plans
+/// the agents executed in the previous iteration. The strategies decide, which agents are
+/// re-routed or mutate their activity times in an iteration.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Strategy {
+    /// The controller runs the iterations 0 to `last_iteration`. If there is more than one, the
+    /// output of each iteration is written to `ITERS/it.<iteration>` in the output directory.
+    #[serde(default)]
+    pub last_iteration: u32,
+    /// Share of the iterations after which plan innovation is disabled. Afterwards, agents only
+    /// execute their existing plans and network legs keep their routes, so that the iterations can
+    /// converge. Innovation is never disabled, if this is not set.
+    #[serde(default)]
+    pub innovation_shutoff: Option<f64>,
+    /// Stop iterating, once the share of re-routed legs which switch routes falls below this
+    /// threshold.
+    #[serde(default)]
+    pub convergence_threshold: Option<f64>,
+    /// Plan strategies, of which each agent selects one per iteration according to the weights.
+    /// See [crate::simulation::replanning::strategies]. If there are none, all agents are
+    /// re-routed.
+    #[serde(default)]
+    pub strategies: Vec<StrategySettings>,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StrategySettings {
+    pub strategy: PlanStrategy,
+    pub weight: f64,
+    /// The last iteration in which the strategy is selected. Innovative strategies are disabled
+    /// with the innovation shutoff anyway.
+    #[serde(default)]
+    pub disable_after: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PlanStrategy {
+    /// Network legs are re-routed when the agent departs.
+    ReRoute,
+    /// Activity end times and durations of the plan of the previous iteration are shifted randomly
+    /// by up to `range` seconds. Routes are kept.
+    TimeMutation {
+        #[serde(default = "default_mutation_range")]
+        range: u32,
+    },
+    /// The plan of the previous iteration is executed again as it is.
+    KeepLastSelected,
+}
+
+impl PlanStrategy {
+    pub fn is_innovative(&self) -> bool {
+        !matches!(self, PlanStrategy::KeepLastSelected)
+    }
+}
+
+impl Strategy {
+    /// The first iteration without plan innovation.
+    pub fn innovation_shutoff_iteration(&self) -> Option<u32> {
+        self.innovation_shutoff
+            .map(|share| (self.last_iteration as f64 * share) as u32)
+    }
+
+    pub fn is_innovative(&self, iteration: u32) -> bool {
+        match self.innovation_shutoff_iteration() {
+            Some(shutoff) => iteration < shutoff,
+            None => true,
+        }
+    }
+
+    pub fn has_converged(&self, route_switch_rate: f64) -> bool {
+        self.convergence_threshold
+            .is_some_and(|threshold| route_switch_rate < threshold)
+    }
+}
+
+/// Charges fares for legs of public transport modes. See [crate::simulation::fares].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Fares {
+    /// Fare file, which defines the charged modes and the fare model.
+    pub file: String,
+}
+
+/// Temporarily reduces the capacity of links. See [crate::simulation::network::incidents].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Incidents {
+    /// Csv file with one incident per line: `link,start,end,capacity_factor`.
+    pub file: String,
+}
+
+/// Scales the free speed of links over time, e.g. for night speeds or rain. See
+/// [crate::simulation::network::speed_factors].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpeedFactors {
+    pub profiles: Vec<SpeedProfile>,
+}
+
+/// Factors of a profile are either given per hour or read from a time series.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpeedProfile {
+    /// External ids of the links the profile applies to. If empty, it applies to all links.
+    #[serde(default)]
+    pub links: Vec<String>,
+    /// Factors per hour starting at midnight. They repeat once all hours are used, so that 24
+    /// factors describe every day of the simulation.
+    #[serde(default)]
+    pub hourly: Vec<f32>,
+    /// Csv file with the header `time,factor`. Each factor applies until the next one.
+    #[serde(default)]
+    pub time_series: Option<String>,
+}
+
+/// Varies the speed of vehicles on each link they enter, to represent heterogeneous drivers. See
+/// [crate::simulation::network::speed_factors::SpeedVariation].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpeedVariation {
+    /// Distribution of the factors on the free speed.
+    pub distribution: SpeedDistribution,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SpeedDistribution {
+    Normal { mean: f32, std_dev: f32 },
+    Uniform { min: f32, max: f32 },
+}
+
+/// Opening hours of activity types. See [crate::simulation::opening_hours].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpeningHours {
+    /// Opening times by activity type.
+    pub activities: HashMap<String, OpeningTimes>,
+}
+
+/// Opening and closing time, either in seconds or as `HH:MM:SS`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpeningTimes {
+    pub open: String,
+    pub close: String,
+}
+
+/// Penalizes agents, which don't complete their plans by the end of the simulation. See
+/// [crate::simulation::stuck_agents].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StuckPenalty {
+    /// Scoring penalty of each stuck agent.
+    pub penalty: f64,
+}
+
+/// Evacuates the agents inside an area at a trigger time. See [crate::simulation::evacuation]. Routes
+/// to the safe nodes are computed within the simulation, so this requires ad-hoc routing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Evacuation {
+    /// Csv file with the corners of the polygon, which is evacuated.
+    pub area: String,
+    pub trigger_time: u32,
+    /// Vehicle type the agents leave the area with.
+    #[serde(default = "default_evacuation_veh_type")]
+    pub veh_type: String,
+    /// Activity type of the agents once they have reached a safe node.
+    #[serde(default = "default_evacuation_act_type")]
+    pub act_type: String,
+    /// External ids of the nodes agents are sent to. If empty, all nodes outside the area are safe.
+    #[serde(default)]
+    pub safe_nodes: Vec<String>,
+}
+
+fn default_evacuation_veh_type() -> String {
+    String::from("car")
+}
+
+fn default_evacuation_act_type() -> String {
+    String::from("evacuated")
+}
+
+/// Replays the vehicle movements of an events file of a previous run as background traffic. See
+/// [crate::simulation::replay]. Only trips of persons, which are not part of the simulated
+/// population, are replayed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// Xml events file of the previous run.
+    pub events: String,
+    /// Passenger car equivalents of replayed vehicles, e.g. to scale a sample of the previous run.
+    #[serde(default = "default_replay_pce")]
+    pub pce: f32,
+    /// Whether the network is a cut-out of the network of the previous run. Agents, whose plans
+    /// leave the cut-out, are not simulated. Their trips enter and leave the cut-out at its
+    /// boundary as replayed vehicles instead.
+    #[serde(default)]
+    pub cordon: bool,
+}
+
+fn default_replay_pce() -> f32 {
+    1.
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AgentTracing {
+    /// External ids of persons whose state transitions are logged.
+    pub person_ids: Vec<String>,
+}
+
+/// Http server on rank 0, which allows to observe and control a running simulation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Control {
+    #[serde(default = "default_control_address")]
+    pub address: String,
+}
+
+fn default_control_address() -> String {
+    String::from("127.0.0.1:8080")
+}
+
+/// Checks every `interval` time steps, that the partitions together still own all agents of the
+/// scenario. This is a collective operation, so it is meant for debugging lost vehicles.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ConsistencyCheck {
+    pub interval: u32,
+}
+
+/// Nodes serve in-links offering a transit vehicle before all other in-links, so that transit
+/// vehicles are not held up by the capacity-weighted random choice of the next in-link. Transit
+/// vehicles are recognized by the mode of their driver's leg.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TransitPriority {
+    #[serde(default = "default_transit_modes")]
+    pub modes: Vec<String>,
+}
+
+/// Publishes the occupancy of links and the blocked in-links of nodes as events every `interval`
+/// time steps. See [crate::simulation::telemetry].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Telemetry {
+    pub interval: u32,
+}
+
+/// Bans vehicle types from the links of zones during a time window of the day. See
+/// [crate::simulation::network::access_restrictions].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AccessRestrictions {
+    pub zones: Vec<RestrictedZone>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RestrictedZone {
+    /// Link attribute, which marks the links of the zone.
+    pub attribute: String,
+    /// Vehicle types, which may not enter the zone.
+    pub veh_types: Vec<String>,
+    /// Times of the day in seconds. The zone is restricted all day by default.
+    #[serde(default)]
+    pub start_time: u32,
+    #[serde(default = "default_restriction_end_time")]
+    pub end_time: u32,
+}
+
+/// Traffic signals at nodes, which cycle through fixed phases. A signal controller may adjust the
+/// durations of the phases every `control_interval` seconds. See [crate::simulation::signals].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Signals {
+    #[serde(default = "default_signal_control_interval")]
+    pub control_interval: u32,
+    pub systems: Vec<SignalSystem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignalSystem {
+    /// External id of the signalized node.
+    pub node: String,
+    pub phases: Vec<SignalPhase>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignalPhase {
+    /// External ids of the in-links of the node, which show green during the phase.
+    pub links: Vec<String>,
+    /// Duration of the phase in seconds.
+    pub duration: u32,
+}
+
+/// How partitions exchange sync messages via mpi.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub struct Communication {
+    #[serde(default)]
+    pub receive: ReceiveMode,
+    /// Receive messages, which have already arrived, before the nodes and links of a time step are
+    /// moved, so that the transfer of messages overlaps with the computation.
+    #[serde(default)]
+    pub early_receive: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum ReceiveMode {
+    /// Messages are received in the order they arrive, regardless of the sending process.
+    #[default]
+    AnySource,
+    /// Messages of the neighbor partitions are received one neighbor after another, matched by the
+    /// time step they were sent for. This makes the message exchange deterministic and allows to
+    /// validate each message, which eases debugging. Messages of other partitions, e.g. with
+    /// teleported vehicles, are received afterwards, if they have already arrived.
+    Neighbors,
+    /// Messages of the neighbor partitions are exchanged with mpi neighborhood collectives over a
+    /// graph of the partitions, which is created once. This saves setting up a request per neighbor
+    /// in each time step. Messages of other partitions are received as with `Neighbors`.
+    NeighborhoodCollective,
 }
 
 #[typetag::serde(tag = "type")]
@@ -225,24 +884,212 @@ impl ConfigModule for Simulation {
     }
 }
 
-impl Default for Simulation {
-    fn default() -> Self {
-        Self {
-            start_time: 0,
-            end_time: 86400,
-            sample_size: 1.0,
-            stuck_threshold: u32::MAX,
-        }
+#[typetag::serde]
+impl ConfigModule for Households {
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
-#[derive(PartialEq, Debug, ValueEnum, Clone, Copy, Serialize, Deserialize)]
-pub enum RoutingMode {
-    AdHoc,
-    UsePlans,
+#[typetag::serde]
+impl ConfigModule for ModeAvailability {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
-#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[typetag::serde]
+impl ConfigModule for Teleportation {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for ParkAndRide {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Freight {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Fares {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Incidents {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for SpeedFactors {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for SpeedVariation {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for OpeningHours {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Strategy {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for StuckPenalty {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Evacuation {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Replay {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for AgentTracing {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Control {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Communication {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for ConsistencyCheck {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for TransitPriority {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Telemetry {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for AccessRestrictions {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[typetag::serde]
+impl ConfigModule for Signals {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Default for ModeAvailability {
+    fn default() -> Self {
+        Self {
+            driver_modes: Vec::new(),
+            fallback_veh_types: default_fallback_veh_types(),
+        }
+    }
+}
+
+impl Default for ParkAndRide {
+    fn default() -> Self {
+        Self {
+            act_type: default_park_and_ride_act(),
+            facilities: Vec::new(),
+        }
+    }
+}
+
+impl Default for Households {
+    fn default() -> Self {
+        Self {
+            fallback_veh_type: default_household_fallback(),
+        }
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            start_time: 0,
+            end_time: 86400,
+            sample_size: 1.0,
+            stuck_threshold: u32::MAX,
+            max_parking_search_time: default_max_parking_search_time(),
+            max_passenger_wait_time: default_max_passenger_wait_time(),
+            core: SimulationCore::default(),
+            node_capacity: None,
+            min_storage_of_one_vehicle: false,
+            parallel_links: false,
+            link_model: LinkModel::default(),
+            activity_duration_interpretation: ActivityDurationInterpretation::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, ValueEnum, Clone, Copy, Serialize, Deserialize)]
+pub enum RoutingMode {
+    AdHoc,
+    UsePlans,
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub enum PartitionMethod {
     Metis(MetisOptions),
     None,
@@ -264,11 +1111,52 @@ pub enum Logging {
     Info,
 }
 
+/// How links are moved. The tick core moves every link with vehicles in every time step. The event
+/// driven core only moves links if vehicles entered or left them, or if their first vehicle is
+/// about to leave. This saves work on large networks with sparse traffic.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum SimulationCore {
+    #[default]
+    Tick,
+    EventDriven,
+}
+
+/// How long vehicles take to traverse a link. The queue model lets every vehicle drive at free
+/// speed and only delays vehicles which can't leave a link because of its flow or storage capacity.
+/// Greenshields' speed-density function additionally slows down vehicles which enter a link that
+/// is already occupied, so that speeds drop gradually with increasing density. Vehicles never drive
+/// slower than `min_speed_factor` times the free speed.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum LinkModel {
+    #[default]
+    Queue,
+    Greenshields {
+        min_speed_factor: f32,
+    },
+}
+
+/// How the end time of an activity is derived from its end time and maximum duration, as MATSim's
+/// `activityDurationInterpretation`. Activities without any of the considered times last until the
+/// end of the simulation.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum ActivityDurationInterpretation {
+    /// Use the end time if it is set, and the maximum duration otherwise.
+    #[default]
+    TryEndTimeThenDuration,
+    /// Ignore the maximum duration, except for interaction activities.
+    EndTimeOnly,
+    /// End the activity at whatever comes first.
+    MinOfDurationAndEndTime,
+}
+
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
 pub enum WriteEvents {
     #[default]
     None,
     Proto,
+    /// Events by type and snapshots by time in one hdf5 file per partition. See
+    /// [crate::simulation::io::hdf5_output]. This requires the cargo feature `hdf5`.
+    Hdf5,
 }
 
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Default)]
@@ -374,6 +1262,10 @@ fn u32_value_100() -> u32 {
     100
 }
 
+fn default_mutation_range() -> u32 {
+    1800
+}
+
 fn bool_value_false() -> bool {
     false
 }
@@ -382,6 +1274,46 @@ fn default_vertex_weight() -> Vec<VertexWeight> {
     vec![InLinkCapacity]
 }
 
+fn default_household_fallback() -> String {
+    String::from("walk")
+}
+
+fn default_fallback_veh_types() -> Vec<String> {
+    vec![String::from("walk")]
+}
+
+fn default_transit_modes() -> Vec<String> {
+    vec![String::from("bus"), String::from("pt")]
+}
+
+fn default_restriction_end_time() -> u32 {
+    24 * 3600
+}
+
+fn default_signal_control_interval() -> u32 {
+    60
+}
+
+fn default_beeline_distance_factor() -> f64 {
+    1.3
+}
+
+fn default_route_cache_size() -> usize {
+    10_000
+}
+
+fn default_max_parking_search_time() -> u32 {
+    900
+}
+
+fn default_max_passenger_wait_time() -> u32 {
+    3600
+}
+
+fn default_park_and_ride_act() -> String {
+    String::from("parkAndRide")
+}
+
 fn default_profiling_level() -> String {
     String::from("INFO")
 }
@@ -389,7 +1321,10 @@ fn default_profiling_level() -> String {
 #[cfg(test)]
 mod tests {
     use crate::simulation::config::{
-        Config, EdgeWeight, MetisOptions, PartitionMethod, Partitioning, VertexWeight,
+        Communication, Config, ConsistencyCheck, Control, EdgeWeight, Evacuation, EventsQueue,
+        GeneralizedCost, LogFilter, MetisOptions, OpeningTimes, PartitionMethod, Partitioning,
+        PlanStrategy, QueueFull, ReceiveMode, Replay, RestrictedZone, RoutingBackend, SignalPhase,
+        SpeedDistribution, SpeedProfile, StrategySettings, VertexWeight, WriteEvents,
     };
 
     #[test]
@@ -446,6 +1381,550 @@ mod tests {
         assert_eq!(parsed_config.partitioning().method, PartitionMethod::None);
     }
 
+    #[test]
+    fn read_log_filter() {
+        let yaml = r#"
+        modules:
+          output:
+            type: Output
+            output_dir: ./test_output/
+            log_filter:
+              directives: info,rust_q_sim::simulation::messaging=trace
+              ranks: [3]
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let filter = parsed_config.output().log_filter.unwrap();
+        assert_eq!(
+            LogFilter {
+                directives: "info,rust_q_sim::simulation::messaging=trace".to_string(),
+                ranks: vec![3],
+            },
+            filter
+        );
+        assert!(filter.applies_to(3));
+        assert!(!filter.applies_to(0));
+    }
+
+    #[test]
+    fn read_events_queue() {
+        let yaml = r#"
+        modules:
+          output:
+            type: Output
+            output_dir: ./test_output/
+            events_queue:
+              capacity: 16
+              on_full: Drop
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            Some(EventsQueue {
+                capacity: 16,
+                on_full: QueueFull::Drop,
+            }),
+            parsed_config.output().events_queue
+        );
+    }
+
+    #[test]
+    fn read_hdf5_output() {
+        let yaml = r#"
+        modules:
+          output:
+            type: Output
+            output_dir: ./test_output/
+            write_events: Hdf5
+            snapshot_interval: 60
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(WriteEvents::Hdf5, parsed_config.output().write_events);
+        assert_eq!(Some(60), parsed_config.output().snapshot_interval);
+    }
+
+    #[test]
+    fn read_control() {
+        let yaml = r#"
+        modules:
+          control:
+            type: Control
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            Some(Control {
+                address: String::from("127.0.0.1:8080")
+            }),
+            parsed_config.control()
+        );
+    }
+
+    #[test]
+    fn read_communication() {
+        let yaml = r#"
+        modules:
+          communication:
+            type: Communication
+            receive: Neighbors
+            early_receive: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            ReceiveMode::Neighbors,
+            parsed_config.communication().receive
+        );
+        assert!(parsed_config.communication().early_receive);
+        assert_eq!(Communication::default(), Config::default().communication());
+
+        let yaml = r#"
+        modules:
+          communication:
+            type: Communication
+            receive: NeighborhoodCollective
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            ReceiveMode::NeighborhoodCollective,
+            parsed_config.communication().receive
+        );
+    }
+
+    #[test]
+    fn read_consistency_check() {
+        let yaml = r#"
+        modules:
+          consistency_check:
+            type: ConsistencyCheck
+            interval: 600
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            Some(ConsistencyCheck { interval: 600 }),
+            parsed_config.consistency_check()
+        );
+        assert!(Config::default().consistency_check().is_none());
+    }
+
+    #[test]
+    fn read_mode_availability() {
+        let yaml = r#"
+        modules:
+          mode_availability:
+            type: ModeAvailability
+            driver_modes: [car]
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let mode_availability = parsed_config.mode_availability();
+        assert_eq!(vec![String::from("car")], mode_availability.driver_modes);
+        assert_eq!(
+            vec![String::from("walk")],
+            mode_availability.fallback_veh_types
+        );
+        assert!(Config::default()
+            .mode_availability()
+            .driver_modes
+            .is_empty());
+    }
+
+    #[test]
+    fn read_access_restrictions() {
+        let yaml = r#"
+        modules:
+          access_restrictions:
+            type: AccessRestrictions
+            zones:
+              - attribute: lez
+                veh_types: [truck, diesel]
+                start_time: 25200
+                end_time: 68400
+              - attribute: pedestrian_zone
+                veh_types: [car]
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let zones = parsed_config.access_restrictions().unwrap().zones;
+        assert_eq!(
+            RestrictedZone {
+                attribute: String::from("lez"),
+                veh_types: vec![String::from("truck"), String::from("diesel")],
+                start_time: 25200,
+                end_time: 68400,
+            },
+            zones[0]
+        );
+        assert_eq!(0, zones[1].start_time);
+        assert_eq!(24 * 3600, zones[1].end_time);
+        assert!(Config::default().access_restrictions().is_none());
+    }
+
+    #[test]
+    fn read_signals() {
+        let yaml = r#"
+        modules:
+          signals:
+            type: Signals
+            systems:
+              - node: "2"
+                phases:
+                  - links: ["1", "2"]
+                    duration: 30
+                  - links: ["3"]
+                    duration: 20
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let signals = parsed_config.signals().unwrap();
+        assert_eq!(60, signals.control_interval);
+        assert_eq!("2", signals.systems[0].node);
+        assert_eq!(
+            SignalPhase {
+                links: vec![String::from("3")],
+                duration: 20,
+            },
+            signals.systems[0].phases[1]
+        );
+        assert!(Config::default().signals().is_none());
+    }
+
+    #[test]
+    fn read_telemetry() {
+        let yaml = r#"
+        modules:
+          telemetry:
+            type: Telemetry
+            interval: 60
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(60, parsed_config.telemetry().unwrap().interval);
+        assert!(Config::default().telemetry().is_none());
+    }
+
+    #[test]
+    fn read_transit_priority() {
+        let yaml = r#"
+        modules:
+          transit_priority:
+            type: TransitPriority
+            modes: [tram]
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            vec![String::from("tram")],
+            parsed_config.transit_priority().unwrap().modes
+        );
+        assert!(Config::default().transit_priority().is_none());
+
+        let yaml = r#"
+        modules:
+          transit_priority:
+            type: TransitPriority
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            vec![String::from("bus"), String::from("pt")],
+            parsed_config.transit_priority().unwrap().modes
+        );
+    }
+
+    #[test]
+    fn read_replay() {
+        let yaml = r#"
+        modules:
+          replay:
+            type: Replay
+            events: ./previous/output_events.xml.gz
+            cordon: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(
+            Some(Replay {
+                events: String::from("./previous/output_events.xml.gz"),
+                pce: 1.,
+                cordon: true,
+            }),
+            parsed_config.replay()
+        );
+        assert!(Config::default().replay().is_none());
+    }
+
+    #[test]
+    fn read_repair_routes() {
+        let yaml = r#"
+        modules:
+          routing:
+            type: Routing
+            mode: UsePlans
+            repair_routes: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert!(parsed_config.routing().repair_routes);
+    }
+
+    #[test]
+    fn read_insert_interactions() {
+        let yaml = r#"
+        modules:
+          routing:
+            type: Routing
+            mode: UsePlans
+            insert_interactions: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert!(parsed_config.routing().insert_interactions);
+        assert!(!Config::default().routing().insert_interactions);
+    }
+
+    #[test]
+    fn read_background_customization() {
+        let yaml = r#"
+        modules:
+          routing:
+            type: Routing
+            mode: AdHoc
+            background_customization: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert!(parsed_config.routing().background_customization);
+        assert!(!parsed_config.routing().repair_routes);
+        assert_eq!(parsed_config.routing().route_cache_size, 10_000);
+        assert_eq!(parsed_config.routing().backend, RoutingBackend::Alt);
+    }
+
+    #[test]
+    fn read_generalized_cost() {
+        let yaml = r#"
+        modules:
+          routing:
+            type: Routing
+            mode: AdHoc
+            generalized_cost:
+              car:
+                value_of_time: 12.5
+                distance_cost: 0.0002
+              bike:
+                value_of_time: 8
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let costs = parsed_config.routing().generalized_cost;
+        assert_eq!(
+            costs.get("car"),
+            Some(&GeneralizedCost {
+                value_of_time: 12.5,
+                distance_cost: 0.0002,
+            })
+        );
+        assert_eq!(
+            costs.get("bike"),
+            Some(&GeneralizedCost {
+                value_of_time: 8.,
+                distance_cost: 0.,
+            })
+        );
+        assert!(Config::default().routing().generalized_cost.is_empty());
+    }
+
+    #[test]
+    fn read_routing_backend() {
+        let yaml = r#"
+        modules:
+          routing:
+            type: Routing
+            mode: AdHoc
+            backend: Dijkstra
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(parsed_config.routing().backend, RoutingBackend::Dijkstra);
+    }
+
+    #[test]
+    fn read_fares() {
+        let yaml = r#"
+        modules:
+          fares:
+            type: Fares
+            file: fares.yml
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!("fares.yml", parsed_config.fares().unwrap().file);
+        assert!(Config::default().fares().is_none());
+    }
+
+    #[test]
+    fn read_incidents() {
+        let yaml = r#"
+        modules:
+          incidents:
+            type: Incidents
+            file: incidents.csv
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!("incidents.csv", parsed_config.incidents().unwrap().file);
+        assert!(Config::default().incidents().is_none());
+    }
+
+    #[test]
+    fn read_speed_factors() {
+        let yaml = r#"
+        modules:
+          speed_factors:
+            type: SpeedFactors
+            profiles:
+              - hourly: [1.1, 1.0]
+              - links: [a, b]
+                time_series: rain.csv
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let speed_factors = parsed_config.speed_factors().unwrap();
+        assert_eq!(
+            vec![
+                SpeedProfile {
+                    links: vec![],
+                    hourly: vec![1.1, 1.0],
+                    time_series: None,
+                },
+                SpeedProfile {
+                    links: vec![String::from("a"), String::from("b")],
+                    hourly: vec![],
+                    time_series: Some(String::from("rain.csv")),
+                },
+            ],
+            speed_factors.profiles
+        );
+        assert!(Config::default().speed_factors().is_none());
+    }
+
+    #[test]
+    fn read_speed_variation() {
+        let yaml = r#"
+        modules:
+          speed_variation:
+            type: SpeedVariation
+            distribution: !Normal
+              mean: 1.0
+              std_dev: 0.1
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let speed_variation = parsed_config.speed_variation().unwrap();
+        assert_eq!(
+            SpeedDistribution::Normal {
+                mean: 1.0,
+                std_dev: 0.1
+            },
+            speed_variation.distribution
+        );
+        assert_eq!(0, speed_variation.seed);
+        assert!(Config::default().speed_variation().is_none());
+    }
+
+    #[test]
+    fn read_opening_hours() {
+        let yaml = r#"
+        modules:
+          opening_hours:
+            type: OpeningHours
+            activities:
+              shop:
+                open: "08:00:00"
+                close: "20:00:00"
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let opening_hours = parsed_config.opening_hours().unwrap();
+        assert_eq!(
+            &OpeningTimes {
+                open: String::from("08:00:00"),
+                close: String::from("20:00:00")
+            },
+            opening_hours.activities.get("shop").unwrap()
+        );
+        assert!(Config::default().opening_hours().is_none());
+    }
+
+    #[test]
+    fn read_strategy() {
+        let yaml = r#"
+        modules:
+          strategy:
+            type: Strategy
+            last_iteration: 100
+            innovation_shutoff: 0.8
+            convergence_threshold: 0.01
+            strategies:
+              - strategy: ReRoute
+                weight: 0.1
+              - strategy: !TimeMutation {}
+                weight: 0.1
+                disable_after: 50
+              - strategy: KeepLastSelected
+                weight: 0.8
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let strategy = parsed_config.strategy();
+        assert_eq!(Some(80), strategy.innovation_shutoff_iteration());
+        assert!(strategy.is_innovative(79));
+        assert!(!strategy.is_innovative(80));
+        assert!(strategy.has_converged(0.005));
+        assert!(!strategy.has_converged(0.02));
+        assert_eq!(
+            StrategySettings {
+                strategy: PlanStrategy::TimeMutation { range: 1800 },
+                weight: 0.1,
+                disable_after: Some(50)
+            },
+            strategy.strategies[1]
+        );
+        assert!(Config::default().strategy().is_innovative(0));
+        assert!(!Config::default().strategy().has_converged(0.));
+    }
+
+    #[test]
+    fn read_stuck_penalty() {
+        let yaml = r#"
+        modules:
+          stuck_penalty:
+            type: StuckPenalty
+            penalty: -120.5
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert_eq!(-120.5, parsed_config.stuck_penalty().unwrap().penalty);
+        assert!(Config::default().stuck_penalty().is_none());
+    }
+
+    #[test]
+    fn read_evacuation() {
+        let yaml = r#"
+        modules:
+          evacuation:
+            type: Evacuation
+            area: area.csv
+            trigger_time: 3600
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        let evacuation = parsed_config.evacuation().unwrap();
+        assert_eq!(
+            Evacuation {
+                area: String::from("area.csv"),
+                trigger_time: 3600,
+                veh_type: String::from("car"),
+                act_type: String::from("evacuated"),
+                safe_nodes: vec![],
+            },
+            evacuation
+        );
+        assert!(Config::default().evacuation().is_none());
+    }
+
+    #[test]
+    fn read_compact_ids() {
+        let yaml = r#"
+        modules:
+          protofiles:
+            type: ProtoFiles
+            network: network.binpb
+            population: population.binpb
+            vehicles: vehicles.binpb
+            ids: ids.binpb
+            compact_ids: true
+        "#;
+        let parsed_config: Config = serde_yaml::from_str(yaml).expect("failed to parse config");
+        assert!(parsed_config.proto_files().compact_ids);
+    }
+
     #[test]
     fn read_metis_partitioning() {
         let yaml = r#"
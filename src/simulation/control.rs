@@ -0,0 +1,220 @@
+//! Http server which allows to observe and control a running simulation. It is started on rank 0,
+//! if the config contains a `control` module. The server understands the following requests:
+//!
+//! - `GET /progress` returns the current time step and whether the simulation is paused or stopping.
+//! - `POST /pause` and `POST /resume` pause and resume all partitions.
+//! - `POST /stop` stops all partitions gracefully and writes the outputs, like SIGINT does.
+//! - `POST /logging?filter=<directives>` replaces the log filter of this process.
+//!
+//! Pause and stop requests are applied at the same time step on all partitions, which is why it can
+//! take a few time steps until they take effect.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::simulation::{logging, shutdown};
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static TIME: AtomicU32 = AtomicU32::new(0);
+static START_TIME: AtomicU32 = AtomicU32::new(0);
+static END_TIME: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Serialize)]
+struct Progress {
+    time: u32,
+    start_time: u32,
+    end_time: u32,
+    paused: bool,
+    stopping: bool,
+}
+
+/// Whether a pause was requested for this process.
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+pub fn set_time_range(start_time: u32, end_time: u32) {
+    START_TIME.store(start_time, Ordering::Relaxed);
+    END_TIME.store(end_time, Ordering::Relaxed);
+}
+
+/// Records the current time step, which is reported by the progress endpoint.
+pub fn set_time(now: u32) {
+    TIME.store(now, Ordering::Relaxed);
+}
+
+/// Starts the server on a background thread. The server runs until the process exits. If the
+/// address can't be bound, the simulation runs without it.
+pub fn start_server(address: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to start control server on {address}: {e}");
+            return;
+        }
+    };
+    info!("Control server listening on {address}");
+
+    thread::Builder::new()
+        .name(String::from("control-server"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = serve(stream) {
+                            warn!("Failed to answer control request: {e}");
+                        }
+                    }
+                    Err(e) => warn!("Failed to accept control connection: {e}"),
+                }
+            }
+        })
+        .expect("Failed to spawn control server thread");
+}
+
+fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers are not needed, but have to be read before answering.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (status, body) = handle(method, target);
+
+    let status_text = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {status_text}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Answers a request with a status code and a json body.
+fn handle(method: &str, target: &str) -> (u16, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match (method, path) {
+        ("GET", "/progress") => (200, progress_json()),
+        ("POST", "/pause") => {
+            info!("Pause was requested via the control server.");
+            PAUSED.store(true, Ordering::SeqCst);
+            (200, progress_json())
+        }
+        ("POST", "/resume") => {
+            info!("Resume was requested via the control server.");
+            PAUSED.store(false, Ordering::SeqCst);
+            (200, progress_json())
+        }
+        ("POST", "/stop") => {
+            shutdown::request();
+            (200, progress_json())
+        }
+        ("POST", "/logging") => {
+            let filter = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "filter")
+                .map(|(_, value)| percent_decode(value));
+            match filter {
+                Some(filter) => match logging::set_log_filter(&filter) {
+                    Ok(()) => (200, serde_json::json!({ "filter": filter }).to_string()),
+                    Err(e) => (400, serde_json::json!({ "error": e }).to_string()),
+                },
+                None => (
+                    400,
+                    serde_json::json!({ "error": "missing query parameter 'filter'" }).to_string(),
+                ),
+            }
+        }
+        (_, "/progress" | "/pause" | "/resume" | "/stop" | "/logging") => (
+            405,
+            serde_json::json!({ "error": "method not allowed" }).to_string(),
+        ),
+        _ => (404, serde_json::json!({ "error": "not found" }).to_string()),
+    }
+}
+
+fn progress_json() -> String {
+    let progress = Progress {
+        time: TIME.load(Ordering::Relaxed),
+        start_time: START_TIME.load(Ordering::Relaxed),
+        end_time: END_TIME.load(Ordering::Relaxed),
+        paused: is_paused(),
+        stopping: shutdown::is_requested(),
+    };
+    serde_json::to_string(&progress).unwrap()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = if bytes[i] == b'%' {
+            bytes.get(i + 1..i + 3)
+        } else {
+            None
+        };
+        let escaped = hex
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => result.push(b' '),
+            (None, byte) => result.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::control::{handle, percent_decode};
+
+    #[test]
+    fn decode_query_value() {
+        assert_eq!(
+            "info,rust_q_sim=debug",
+            percent_decode("info%2Crust_q_sim%3Ddebug")
+        );
+        assert_eq!("a b", percent_decode("a+b"));
+        assert_eq!("100%", percent_decode("100%"));
+    }
+
+    #[test]
+    fn unknown_requests() {
+        assert_eq!(404, handle("GET", "/unknown").0);
+        assert_eq!(405, handle("GET", "/stop").0);
+        assert_eq!(400, handle("POST", "/logging").0);
+    }
+
+    #[test]
+    fn progress() {
+        let (status, body) = handle("GET", "/progress");
+        assert_eq!(200, status);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(json.get("time").is_some());
+        assert!(json.get("paused").is_some());
+    }
+}
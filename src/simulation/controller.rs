@@ -1,37 +1,102 @@
+use std::any::Any;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread::{sleep, JoinHandle};
 use std::time::Duration;
 use std::{fs, thread};
 
 use clap::Parser;
+use mpi::environment::Threading;
+use mpi::topology::SystemCommunicator;
 use mpi::traits::{Communicator, CommunicatorCollectives};
 use nohash_hasher::IntMap;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::simulation::config::{
-    CommandLineArgs, Config, PartitionMethod, RoutingMode, WriteEvents,
+    CommandLineArgs, Config, Output, PartitionMethod, RoutingMode, WriteEvents,
 };
+use crate::simulation::controller_listener::{ControllerEvent, ControllerListenerFactory};
+use crate::simulation::engines::EngineFactory;
+use crate::simulation::evacuation::Evacuation;
+use crate::simulation::id::{Id, SharedIdStore};
+#[cfg(feature = "hdf5")]
+use crate::simulation::io::hdf5_output;
+#[cfg(feature = "hdf5")]
+use crate::simulation::io::hdf5_output::{Hdf5EventsWriter, Hdf5SnapshotWriter};
+use crate::simulation::io::output_files::{get_numbered_output_filename, iteration_output_dir};
 use crate::simulation::io::proto_events::ProtoEventsWriter;
 use crate::simulation::messaging::communication::communicators::{
-    ChannelSimCommunicator, MpiSimCommunicator, SimCommunicator,
+    ChannelSimCommunicator, HybridPartition, HybridSimCommunicator, MpiSimCommunicator,
+    SimCommunicator,
 };
 use crate::simulation::messaging::communication::message_broker::NetMessageBroker;
-use crate::simulation::messaging::events::EventsPublisher;
+use crate::simulation::messaging::events::{
+    EventsPublisher, EventsSubscriber, EventsSubscriberFactory,
+};
+use crate::simulation::messaging::events_queue::QueuedEventsSubscriber;
+use crate::simulation::mobsim_listener::{MobsimListener, MobsimListenerFactory};
+use crate::simulation::network::access_restrictions::AccessRestrictions;
 use crate::simulation::network::global_network::Network;
 use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::population::freight::{Carriers, FreightTourTracker};
 use crate::simulation::population::population::Population;
-use crate::simulation::replanning::replanner::{DummyReplanner, ReRouteTripReplanner, Replanner};
+use crate::simulation::replanning::replanner::{
+    DummyReplanner, ReRouteTripReplanner, Replanner, RouteSwitches, TeleportedLegsReplanner,
+};
 use crate::simulation::replanning::routing::travel_time_collector::TravelTimeCollector;
+use crate::simulation::replanning::strategies::StrategyChooser;
+use crate::simulation::replay::Replay;
+use crate::simulation::scenario::Scenario;
+use crate::simulation::signals::SignalControllerFactory;
 use crate::simulation::simulation::Simulation;
 use crate::simulation::vehicles::garage::Garage;
-use crate::simulation::{id, logging};
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::Person;
+use crate::simulation::{control, crash_dump, id, logging, scenario, shutdown};
+
+/// Engines and listeners which are added to every partition. They are passed as factories, because
+/// partitions are set up on their own threads.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    pub engines: Vec<EngineFactory>,
+    pub mobsim_listeners: Vec<MobsimListenerFactory>,
+    pub controller_listeners: Vec<ControllerListenerFactory>,
+    pub events_subscribers: Vec<EventsSubscriberFactory>,
+    /// Adjusts the signals of the signals module every control interval.
+    pub signal_controller: Option<SignalControllerFactory>,
+}
+
+/// Result of [run_scenario].
+#[derive(Debug)]
+pub struct ScenarioRun {
+    /// False, if the run was stopped before the end time.
+    pub completed: bool,
+    /// Events of all partitions, ordered by iteration and time. Events of the same time step are
+    /// ordered by partition.
+    pub events: Vec<(u32, Event)>,
+    pub output_dir: PathBuf,
+}
 
 pub fn run_channel() {
-    let args = CommandLineArgs::parse();
+    run_channel_with_args(CommandLineArgs::parse());
+}
+
+/// Runs all partitions as threads of this process, with arguments which don't necessarily come
+/// from the command line.
+pub fn run_channel_with_args(args: CommandLineArgs) {
+    run_channel_with_extensions(args, Extensions::default());
+}
+
+/// Like [run_channel_with_args], but every partition additionally registers the given engines and
+/// listeners.
+pub fn run_channel_with_extensions(args: CommandLineArgs, extensions: Extensions) {
     let config = Config::from_file(&args);
 
     let _guards = logging::init_logging(&config, config.partitioning().num_parts);
+    shutdown::install_signal_handler();
 
     info!(
         "Starting Multithreaded Simulation with {} partitions.",
@@ -39,15 +104,21 @@ pub fn run_channel() {
     );
     let comms = ChannelSimCommunicator::create_n_2_n(config.partitioning().num_parts);
 
+    // all partitions share one id store, instead of loading a copy of it in every thread.
+    let ids = id::shared_store();
     let handles: IntMap<u32, JoinHandle<()>> = comms
         .into_iter()
         .map(|comm| {
-            let config_path = args.clone();
+            let args = args.clone();
+            let ids = ids.clone();
+            let extensions = extensions.clone();
             (
                 comm.rank(),
                 thread::Builder::new()
                     .name(comm.rank().to_string())
-                    .spawn(move || execute_partition(comm, &config_path))
+                    .spawn(move || {
+                        execute_partition(comm, Config::from_file(&args), Some(ids), &extensions);
+                    })
                     .unwrap(),
             )
         })
@@ -56,43 +127,234 @@ pub fn run_channel() {
     try_join(handles);
 }
 
+/// Runs the whole simulation within this process, without mpi, and returns the events of all
+/// partitions. This is meant for embedding the simulation into other applications. All events are
+/// kept in memory, so large scenarios should rather write events to the output directory.
+///
+/// Unlike the binaries, this neither sets up logging nor installs a signal handler. This is left to
+/// the embedding application.
+pub fn run_scenario(config: &Config) -> ScenarioRun {
+    run_scenario_with_extensions(config, Extensions::default())
+}
+
+/// Like [run_scenario], but every partition additionally registers the given engines and listeners.
+pub fn run_scenario_with_extensions(config: &Config, mut extensions: Extensions) -> ScenarioRun {
+    // the config is not Send. Each partition reads its own copy.
+    let yaml = serde_yaml::to_string(config).expect("Failed to serialize config");
+    let (sender, receiver) = channel();
+    // the subscribers of a partition are created once per iteration.
+    let iterations: Mutex<IntMap<u32, u32>> = Mutex::default();
+    extensions
+        .events_subscribers
+        .push(Arc::new(move |rank| -> Box<dyn EventsSubscriber + Send> {
+            let mut iterations = iterations.lock().unwrap();
+            let iteration = iterations.entry(rank).or_insert(0);
+            let subscriber = CollectingSubscriber {
+                rank,
+                iteration: *iteration,
+                sender: sender.clone(),
+            };
+            *iteration += 1;
+            Box::new(subscriber)
+        }));
+
+    let comms = ChannelSimCommunicator::create_n_2_n(config.partitioning().num_parts);
+    let ids = id::shared_store();
+    let mut handles: IntMap<u32, JoinHandle<bool>> = comms
+        .into_iter()
+        .map(|comm| {
+            let yaml = yaml.clone();
+            let ids = ids.clone();
+            let extensions = extensions.clone();
+            (
+                comm.rank(),
+                thread::Builder::new()
+                    .name(comm.rank().to_string())
+                    .spawn(move || {
+                        let config: Config =
+                            serde_yaml::from_str(&yaml).expect("Failed to parse config");
+                        execute_partition(comm, config, Some(ids), &extensions)
+                    })
+                    .unwrap(),
+            )
+        })
+        .collect();
+    // only the subscribers of the partitions hold senders now. Once they are done, the receiver
+    // stops.
+    drop(extensions);
+
+    // if a partition panics, the others may wait for it forever and never drop their senders.
+    // Therefore, the threads are checked whenever no events arrive for a while.
+    let mut events = Vec::new();
+    let mut results = IntMap::default();
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout) => join_finished(&mut handles, &mut results),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    results.extend(try_join(handles));
+    let completed = results.values().all(|completed| *completed);
+    // sort_by_key is stable, so events of a partition keep their order.
+    events.sort_by_key(|(iteration, time, rank, _)| (*iteration, *time, *rank));
+
+    ScenarioRun {
+        completed,
+        events: events
+            .into_iter()
+            .map(|(_, time, _, event)| (time, event))
+            .collect(),
+        output_dir: PathBuf::from(config.output().output_dir),
+    }
+}
+
+/// Sends the events of a partition to [run_scenario].
+struct CollectingSubscriber {
+    rank: u32,
+    iteration: u32,
+    sender: Sender<(u32, u32, u32, Event)>,
+}
+
+impl EventsSubscriber for CollectingSubscriber {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        self.sender
+            .send((self.iteration, time, self.rank, event.clone()))
+            .expect("Failed to pass event to the caller of run_scenario");
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
 pub fn run_mpi() {
+    run_mpi_with_extensions(Extensions::default());
+}
+
+/// Like [run_mpi], but the partition additionally registers the given engines and listeners.
+pub fn run_mpi_with_extensions(extensions: Extensions) {
     let universe = mpi::initialize().unwrap();
     let world = universe.world();
-    let comm = MpiSimCommunicator {
-        mpi_communicator: world,
-    };
 
     let mut args = CommandLineArgs::parse();
     // override the num part argument, with the number of processes mpi has started.
     args.num_parts = Some(world.size() as u32);
     let config = Config::from_file(&args);
+    let comm = MpiSimCommunicator::new(world).with_receive_mode(config.communication().receive);
 
     let _guards = logging::init_logging(&config, comm.rank());
+    shutdown::install_signal_handler();
 
     info!(
         "Starting MPI Simulation with {} partitions",
         config.partitioning().num_parts
     );
-    execute_partition(comm, &args);
+    execute_partition(comm, config, None, &extensions);
 
     info!("#{} at barrier.", world.rank());
     universe.world().barrier();
     info!("Process #{} finishing.", world.rank());
 }
 
-fn execute_partition<C: SimCommunicator + 'static>(comm: C, args: &CommandLineArgs) {
-    let config = Config::from_file(args);
+/// Runs several partitions as threads of each mpi process. Partitions of the same process exchange
+/// vehicles via channels, partitions of different processes via mpi.
+pub fn run_hybrid(args: CommandLineArgs, threads_per_process: u32) {
+    run_hybrid_with_extensions(args, threads_per_process, Extensions::default());
+}
+
+/// Like [run_hybrid], but every partition additionally registers the given engines and listeners.
+pub fn run_hybrid_with_extensions(
+    mut args: CommandLineArgs,
+    threads_per_process: u32,
+    extensions: Extensions,
+) {
+    let (universe, threading) = mpi::initialize_with_threading(Threading::Multiple).unwrap();
+    assert_eq!(
+        Threading::Multiple,
+        threading,
+        "The hybrid simulation requires an mpi implementation which supports MPI_THREAD_MULTIPLE."
+    );
+    let world = universe.world();
+    let process = world.rank() as u32;
+
+    // override the num part argument, with the number of partitions of all processes.
+    args.num_parts = Some(world.size() as u32 * threads_per_process);
+    let config = Config::from_file(&args);
+
+    let _guards = logging::init_logging(&config, process);
+    shutdown::install_signal_handler();
+
+    info!(
+        "Starting hybrid Simulation with {} partitions on {} processes",
+        config.partitioning().num_parts,
+        world.size()
+    );
+
+    // the partitions of a process share one id store. On the first process, it is loaded while the
+    // input for all partitions is prepared. The other processes load it before starting their
+    // partitions.
+    if process != 0 {
+        scenario::load_ids(&config);
+    }
+    let ids = id::shared_store();
+    let handles: IntMap<u32, JoinHandle<()>> =
+        HybridPartition::create(process, threads_per_process)
+            .into_iter()
+            .map(|partition| {
+                let args = args.clone();
+                let ids = ids.clone();
+                let extensions = extensions.clone();
+                (
+                    partition.rank(),
+                    thread::Builder::new()
+                        .name(partition.rank().to_string())
+                        .spawn(move || {
+                            let comm =
+                                HybridSimCommunicator::new(partition, SystemCommunicator::world());
+                            let config = Config::from_file(&args);
+                            execute_partition(comm, config, Some(ids), &extensions);
+                        })
+                        .unwrap(),
+                )
+            })
+            .collect();
+
+    try_join(handles);
+
+    info!("#{process} at barrier.");
+    world.barrier();
+    info!("Process #{process} finishing.");
+}
+
+/// Runs one partition. If an id store is shared between partitions, it is loaded once by the
+/// partition which prepares the input. Otherwise, every partition loads its own copy. Returns
+/// false, if the partition was stopped before the end time.
+fn execute_partition<C: SimCommunicator + 'static>(
+    comm: C,
+    config: Config,
+    shared_ids: Option<SharedIdStore>,
+    extensions: &Extensions,
+) -> bool {
+    // rank 0 loads the ids into the shared store while preparing the input.
+    let ids_shared = shared_ids.is_some();
+    if let Some(ids) = shared_ids {
+        id::use_shared_store(ids);
+    }
 
     let rank = comm.rank();
     let size = config.partitioning().num_parts;
 
     let output_path = PathBuf::from(&config.output().output_dir);
     fs::create_dir_all(&output_path).expect("Failed to create output path");
+    crash_dump::init(rank, &output_path);
 
     if rank == 0 {
         info!("#{rank} preparing to create input for partitions.");
         partition_input(&config);
+        if let Some(control_config) = config.control() {
+            control::start_server(&control_config.address);
+        }
     }
 
     info!("Process #{rank} of {size} has started. Waiting for other processes to arrive at initial barrier. ");
@@ -100,86 +362,324 @@ fn execute_partition<C: SimCommunicator + 'static>(comm: C, args: &CommandLineAr
     //comm.send_receive_travel_times(0, std::collections::HashMap::new());
     comm.barrier();
 
-    id::load_from_file(&PathBuf::from(config.proto_files().ids));
-    let network = Network::from_file_as_is(&get_numbered_output_filename(
-        &output_path,
-        &PathBuf::from(config.proto_files().network),
-        config.partitioning().num_parts,
-    ));
-    let mut garage = Garage::from_file(&PathBuf::from(config.proto_files().vehicles));
-
-    let population = Population::from_file_filtered_part(
-        &PathBuf::from(config.proto_files().population),
-        &network,
-        &mut garage,
-        comm.rank(),
-    );
+    let mut scenario = Scenario::load_partition(&config, rank, ids_shared);
+    if let Some(restrictions) = config.access_restrictions() {
+        scenario.network.access_restrictions =
+            AccessRestrictions::from_config(&restrictions, &scenario.network);
+    }
+    if config.routing().insert_interactions {
+        let changed = scenario.insert_interactions(&config.teleportation());
+        info!("#{rank} inserted interaction activities into {changed} plans.");
+    }
+    if config.routing().repair_routes {
+        let repaired = scenario.repair_routes();
+        info!("#{rank} repaired the routes of {repaired} plans.");
+    }
+    let with_distance = scenario.compute_route_distances();
+    info!("#{rank} computed the distance of {with_distance} routes.");
+    let problems = scenario.validate(config.routing().mode);
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{problem}");
+        }
+        panic!(
+            "#{rank} found {} problems in the plans of its agents. See the log for details.",
+            problems.len()
+        );
+    }
+    let Scenario {
+        network,
+        garage,
+        mut population,
+        ..
+    } = scenario;
 
-    let network_partition = SimNetworkPartition::from_network(&network, rank, config.simulation());
-    info!(
-        "Partition #{rank} network has: {} nodes and {} links. Population has {} agents",
-        network_partition.nodes.len(),
-        network_partition.links.len(),
-        population.persons.len()
-    );
+    let carriers = config
+        .freight()
+        .map(|freight| Carriers::from_file(&PathBuf::from(freight.carriers)));
+    let rc = Rc::new(comm);
 
-    let mut events = EventsPublisher::new();
+    let mut controller_listeners: Vec<_> = extensions
+        .controller_listeners
+        .iter()
+        .map(|factory| factory(rank))
+        .collect();
+    let mut controller_event = ControllerEvent {
+        rank,
+        iteration: 0,
+        output_dir: output_path.clone(),
+    };
+    for listener in controller_listeners.iter_mut() {
+        listener.startup(&controller_event);
+    }
+
+    // the executed plans of an iteration are the plans of the next one. Agents move between
+    // partitions during the mobsim, so they are sent back to the partition which started them at
+    // the end of each iteration. The replanner is kept as well, so that its routers start from the
+    // travel times of the previous iteration.
+    let strategy = config.strategy();
+    let mut kept_replanner: Option<Box<dyn Replanner>> = None;
+    let mut completed = true;
+    for iteration in 0..=strategy.last_iteration {
+        controller_event.iteration = iteration;
+        for listener in controller_listeners.iter_mut() {
+            listener.iteration_starts(&controller_event);
+        }
+
+        let iteration_path = if strategy.last_iteration == 0 {
+            output_path.clone()
+        } else {
+            iteration_output_dir(&output_path, iteration)
+        };
+        fs::create_dir_all(&iteration_path).expect("Failed to create iteration output path");
+
+        let mut garage = garage.clone();
+        let innovation = strategy.is_innovative(iteration);
+        if !innovation {
+            info!("#{rank} plan innovation is disabled in iteration {iteration}.");
+        }
+        let strategies = StrategyChooser::from_config(&strategy, iteration);
+        let mutated = strategies.mutate_times(&mut population);
+        if mutated > 0 {
+            info!("#{rank} mutated the activity times of {mutated} plans.");
+        }
+
+        let mut network_partition =
+            SimNetworkPartition::from_network(&network, rank, config.simulation());
+        network_partition.restrict_veh_types(&network, &garage.vehicle_types);
+        info!(
+            "Partition #{rank} network has: {} nodes and {} links. Population has {} agents",
+            network_partition.nodes.len(),
+            network_partition.links.len(),
+            population.persons.len()
+        );
+
+        let mut events = EventsPublisher::new();
+
+        let (events_writer, snapshot_writer) =
+            output_writers(&config.output(), &iteration_path, rank);
+        if let Some(writer) = events_writer {
+            if let Some(queue) = config.output().events_queue {
+                events.add_subscriber(Box::new(QueuedEventsSubscriber::new(
+                    writer,
+                    queue.capacity,
+                    queue.on_full,
+                )));
+            } else {
+                events.add_subscriber(writer);
+            }
+        }
+        for factory in &extensions.events_subscribers {
+            events.add_subscriber(factory(rank));
+        }
+        let travel_time_collector = Box::new(TravelTimeCollector::new());
+        events.add_subscriber(travel_time_collector);
+
+        if let Some(carriers) = &carriers {
+            events.add_subscriber(Box::new(FreightTourTracker::new(
+                carriers,
+                &network,
+                &garage,
+                Some(FreightTourTracker::partition_file(&iteration_path, rank)),
+            )));
+        }
+
+        let mut replanner = kept_replanner.take().unwrap_or_else(|| {
+            create_replanner(&config, &network, &network_partition, &garage, &rc)
+        });
+        replanner.start_iteration(innovation, strategies);
+        let net_message_broker =
+            NetMessageBroker::new(Rc::clone(&rc), &network, &network_partition);
+        let evacuation = config.evacuation().map(|evacuation| {
+            assert_eq!(
+                RoutingMode::AdHoc,
+                config.routing().mode,
+                "Evacuations require ad-hoc routing to route agents to the safe nodes."
+            );
+            Evacuation::from_config(&evacuation, &network, &garage)
+        });
+        let replay = config.replay().map(|replay| {
+            let population_file = PathBuf::from(config.proto_files().population);
+            let start_time = config.simulation().start_time;
+            Replay::from_config(&replay, &population_file, &network, rank, start_time)
+        });
+        if let Some(replay) = &replay {
+            garage.add_veh_type(replay.vehicle_type().clone());
+        }
+
+        let mut simulation: Simulation<C> = Simulation::new(
+            &config,
+            network_partition,
+            garage,
+            population.clone(),
+            net_message_broker,
+            events,
+            replanner,
+        );
+
+        if let Some(evacuation) = evacuation {
+            simulation.set_evacuation(evacuation);
+        }
+        if let Some(replay) = replay {
+            simulation.set_replay(replay);
+        }
+        for factory in &extensions.engines {
+            simulation.add_engine(factory(rank));
+        }
+        if let Some(snapshot_writer) = snapshot_writer {
+            simulation.add_listener(snapshot_writer);
+        }
+        for factory in &extensions.mobsim_listeners {
+            simulation.add_listener(factory(rank));
+        }
+        if let Some(factory) = &extensions.signal_controller {
+            simulation.set_signal_controller(factory(rank));
+        }
 
-    if config.output().write_events == WriteEvents::Proto {
-        let events_file = format!("events.{rank}.binpb");
-        let events_path = output_path.join(events_file);
-        events.add_subscriber(Box::new(ProtoEventsWriter::new(&events_path)));
+        completed = simulation.run();
+        // all partitions have to agree on whether the iterations have converged. Otherwise, the
+        // remaining partitions would wait for the others forever.
+        let local_switches = simulation.replanner().route_switches();
+        let route_switches = RouteSwitches {
+            rerouted: rc.sum(local_switches.rerouted as u64) as usize,
+            switched: rc.sum(local_switches.switched as u64) as usize,
+        };
+        info!(
+            "#{rank} route switch rate in iteration {iteration}: {:.4} ({} of {} re-routed legs)",
+            route_switches.rate(),
+            route_switches.switched,
+            route_switches.rerouted
+        );
+        let converged =
+            route_switches.rerouted > 0 && strategy.has_converged(route_switches.rate());
+        if converged && iteration < strategy.last_iteration {
+            info!("#{rank} has converged in iteration {iteration}. Skipping the remaining iterations.");
+        }
+        if let Some(carriers) = &carriers {
+            // tours cross partitions. Wait until all partitions have written what they observed.
+            rc.barrier();
+            if rank == 0 {
+                FreightTourTracker::merge_partitions(
+                    carriers,
+                    &network,
+                    simulation.garage(),
+                    &iteration_path,
+                    size,
+                );
+            }
+        }
+        simulation
+            .garage()
+            .to_file(&Garage::partition_file(&iteration_path, rank));
+        rc.barrier();
+        if rank == 0 {
+            Garage::merge_partitions(&iteration_path, size);
+        }
+
+        for listener in controller_listeners.iter_mut() {
+            listener.iteration_ends(&controller_event);
+        }
+        if !completed || converged || iteration == strategy.last_iteration {
+            break;
+        }
+
+        let returned = return_executed_plans(&mut simulation, &network, &rc, &mut population);
+        info!("#{rank} carries the executed plans of {returned} agents into the next iteration.");
+        kept_replanner = Some(simulation.into_replanner());
     }
-    let travel_time_collector = Box::new(TravelTimeCollector::new());
-    events.add_subscriber(travel_time_collector);
 
-    let rc = Rc::new(comm);
+    for listener in controller_listeners.iter_mut() {
+        listener.shutdown(&controller_event, !completed);
+    }
+
+    if !completed {
+        // events are flushed at the end of the run. Also write the id store, so that the partial
+        // output can be converted. Compact ids can't be written, but the input ids file can be
+        // used instead.
+        if !config.proto_files().compact_ids {
+            id::store_to_file(&output_path.join(format!("output_ids.{rank}.binpb")));
+        }
+        info!("#{rank} was shut down before the end time. Partial output was written to {output_path:?}");
+    }
+    completed
+}
 
-    let replanner: Box<dyn Replanner> = if config.routing().mode == RoutingMode::AdHoc {
+fn create_replanner<C: SimCommunicator + 'static>(
+    config: &Config,
+    network: &Network,
+    network_partition: &SimNetworkPartition,
+    garage: &Garage,
+    rc: &Rc<C>,
+) -> Box<dyn Replanner> {
+    if config.routing().mode == RoutingMode::AdHoc {
         Box::new(ReRouteTripReplanner::new(
-            &network,
-            &network_partition,
-            &garage,
-            Rc::clone(&rc),
+            network,
+            network_partition,
+            garage,
+            Rc::clone(rc),
+            &config.teleportation(),
+            &config.routing(),
         ))
+    } else if !config.teleportation().modes.is_empty() {
+        Box::new(TeleportedLegsReplanner::new(&config.teleportation()))
     } else {
         Box::new(DummyReplanner {})
-    };
-    let net_message_broker = NetMessageBroker::new(rc, &network, &network_partition);
+    }
+}
 
-    let mut simulation: Simulation<C> = Simulation::new(
-        config,
-        network_partition,
-        garage,
-        population,
-        net_message_broker,
-        events,
-        replanner,
-    );
+/// Sends the agents, which are at an activity at the end of the iteration, back to the partition
+/// of their first activity, where their executed plans replace the plans in the population. Agents,
+/// which are still on their way, keep their previous plans. Returns the number of replaced plans.
+fn return_executed_plans<C: SimCommunicator>(
+    simulation: &mut Simulation<C>,
+    network: &Network,
+    rc: &Rc<C>,
+    population: &mut Population,
+) -> usize {
+    let mut by_home: HashMap<u32, Vec<Person>> = HashMap::new();
+    for mut agent in simulation.take_agents_at_activity() {
+        agent.curr_plan_elem = 0;
+        let home = network.links[agent.curr_act().link_id as usize].partition;
+        by_home.entry(home).or_default().push(agent);
+    }
 
-    simulation.run();
+    let mut returned = 0;
+    for agent in rc.send_receive_persons(by_home) {
+        // agents, which were created during the mobsim, are not part of the population.
+        if let Some(person) = population.persons.get_mut(&Id::get(agent.id)) {
+            *person = agent;
+            returned += 1;
+        }
+    }
+    returned
 }
 
 /// Have this more complicated join logic, so that threads in the back of the handle vec can also
 /// cause the main thread to panic.
-fn try_join(mut handles: IntMap<u32, JoinHandle<()>>) {
+fn try_join<T>(mut handles: IntMap<u32, JoinHandle<T>>) -> IntMap<u32, T> {
+    let mut results = IntMap::default();
     while !handles.is_empty() {
         sleep(Duration::from_secs(1)); // test for finished threads once a second
-        let mut finished = Vec::new();
-        for (i, handle) in handles.iter() {
-            if handle.is_finished() {
-                finished.push(*i);
-            }
-        }
-        for i in finished {
-            let handle = handles.remove(&i).unwrap();
-            handle.join().expect("Error in a thread");
-        }
+        join_finished(&mut handles, &mut results);
+    }
+    results
+}
+
+/// Joins the threads which have finished already. Panics, if one of them has panicked.
+fn join_finished<T>(handles: &mut IntMap<u32, JoinHandle<T>>, results: &mut IntMap<u32, T>) {
+    let finished: Vec<_> = handles
+        .iter()
+        .filter(|(_, handle)| handle.is_finished())
+        .map(|(i, _)| *i)
+        .collect();
+    for i in finished {
+        let handle = handles.remove(&i).unwrap();
+        results.insert(i, handle.join().expect("Error in a thread"));
     }
 }
 
 pub fn partition_input(config: &Config) {
-    id::load_from_file(&PathBuf::from(config.proto_files().ids));
+    scenario::load_ids(config);
     let _net = if let PartitionMethod::Metis(_) = config.partitioning().method {
         info!("Config param Partition method was set to metis. Loading input network, running metis conversion and then store it into output folder");
         partition_network(config)
@@ -194,40 +694,60 @@ fn partition_network(config: &Config) -> Network {
     let num_parts = config.partitioning().num_parts;
     let network = Network::from_file_path(&net_in_path, num_parts, config.partitioning().method);
 
-    let mut net_out_path =
-        create_output_filename(&PathBuf::from(config.output().output_dir), &net_in_path);
-    net_out_path = insert_number_in_proto_filename(&net_out_path, num_parts);
+    let net_out_path = get_numbered_output_filename(
+        &PathBuf::from(config.output().output_dir),
+        &net_in_path,
+        num_parts,
+    );
     network.to_file(&net_out_path);
     network
 }
 
-fn copy_network_into_output(config: &Config) -> Network {
-    let net_in_path = PathBuf::from(config.proto_files().network);
-    let num_parts = config.partitioning().num_parts;
-    let network = Network::from_file_as_is(&net_in_path);
-    let mut net_out_path =
-        create_output_filename(&PathBuf::from(config.output().output_dir), &net_in_path);
-    net_out_path = insert_number_in_proto_filename(&net_out_path, num_parts);
-    network.to_file(&net_out_path);
-    network
+type OutputWriters = (
+    Option<Box<dyn EventsSubscriber + Send>>,
+    Option<Box<dyn MobsimListener>>,
+);
+
+/// Creates the events writer of the configured format, and the snapshot writer, if the format
+/// supports snapshots.
+fn output_writers(output: &Output, output_path: &Path, rank: u32) -> OutputWriters {
+    match output.write_events {
+        WriteEvents::None => (None, None),
+        WriteEvents::Proto => {
+            let events_path = output_path.join(format!("events.{rank}.binpb"));
+            (Some(Box::new(ProtoEventsWriter::new(&events_path))), None)
+        }
+        WriteEvents::Hdf5 => hdf5_writers(output, output_path, rank),
+    }
 }
 
-pub fn get_numbered_output_filename(output_dir: &Path, input_file: &Path, part: u32) -> PathBuf {
-    let out = create_output_filename(output_dir, input_file);
-    insert_number_in_proto_filename(&out, part)
+/// Events and snapshots are written into the same file.
+#[cfg(feature = "hdf5")]
+fn hdf5_writers(output: &Output, output_path: &Path, rank: u32) -> OutputWriters {
+    let file = hdf5_output::create_file(&output_path.join(format!("output.{rank}.h5")));
+    let snapshot_writer = output.snapshot_interval.map(|interval| {
+        Box::new(Hdf5SnapshotWriter::new(&file, interval)) as Box<dyn MobsimListener>
+    });
+    (
+        Some(Box::new(Hdf5EventsWriter::new(&file))),
+        snapshot_writer,
+    )
 }
 
-fn create_output_filename(output_dir: &Path, input_file: &Path) -> PathBuf {
-    let filename = input_file.file_name().unwrap();
-    output_dir.join(filename)
+#[cfg(not(feature = "hdf5"))]
+fn hdf5_writers(_output: &Output, _output_path: &Path, _rank: u32) -> OutputWriters {
+    panic!("Writing hdf5 output requires the cargo feature `hdf5`.")
 }
 
-fn insert_number_in_proto_filename(path: &Path, part: u32) -> PathBuf {
-    let filename = path.file_name().unwrap().to_str().unwrap();
-    let mut stripped = filename.strip_suffix(".binpb").unwrap();
-    if let Some(s) = stripped.strip_suffix(format!(".{part}").as_str()) {
-        stripped = s;
-    }
-    let new_filename = format!("{stripped}.{part}.binpb");
-    path.parent().unwrap().join(new_filename)
+fn copy_network_into_output(config: &Config) -> Network {
+    let net_in_path = PathBuf::from(config.proto_files().network);
+    let num_parts = config.partitioning().num_parts;
+    let network = Network::from_file_as_is(&net_in_path);
+    let net_out_path = get_numbered_output_filename(
+        &PathBuf::from(config.output().output_dir),
+        &net_in_path,
+        num_parts,
+    );
+    network.to_file(&net_out_path);
+    network
 }
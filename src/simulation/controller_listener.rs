@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Information about the run of a partition, which is passed to controller listeners.
+#[derive(Debug, Clone)]
+pub struct ControllerEvent {
+    pub rank: u32,
+    pub iteration: u32,
+    pub output_dir: PathBuf,
+}
+
+/// Hooks into the lifecycle of the controller, the way MATSim's controler listeners do.
+/// `iteration_starts` and `iteration_ends` are called once for each iteration of the run.
+pub trait ControllerListener {
+    /// Called once the input of the partition is loaded and before the first iteration.
+    fn startup(&mut self, _event: &ControllerEvent) {}
+
+    fn iteration_starts(&mut self, _event: &ControllerEvent) {}
+
+    /// Called after the mobsim of the iteration has finished and all events were processed.
+    fn iteration_ends(&mut self, _event: &ControllerEvent) {}
+
+    /// Called last. `unexpected` is true, if the run was stopped early due to a shutdown request.
+    fn shutdown(&mut self, _event: &ControllerEvent, _unexpected: bool) {}
+}
+
+/// Creates the controller listener of a partition from the rank of the partition.
+pub type ControllerListenerFactory = Arc<dyn Fn(u32) -> Box<dyn ControllerListener> + Send + Sync>;
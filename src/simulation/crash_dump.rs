@@ -0,0 +1,147 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use tracing::error;
+
+/// Number of vehicle ids which are kept in the snapshot.
+const LAST_VEHICLES: usize = 20;
+
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    static SNAPSHOT: RefCell<Option<Snapshot>> = RefCell::new(None);
+}
+
+/// Diagnostic state of a partition. The simulation keeps it up to date, and it is written into the
+/// output directory when the partition panics, so that crashes on clusters can be analyzed after
+/// the fact.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub rank: u32,
+    pub time: u32,
+    pub activity_q: usize,
+    pub teleportation_q: usize,
+    pub veh_on_net: usize,
+    pub active_nodes: usize,
+    pub active_links: usize,
+    /// messages received from other partitions, which are meant for future time steps
+    pub buffered_in_messages: usize,
+    /// vehicles which are waiting to be sent to other partitions
+    pub pending_out_vehicles: usize,
+    last_vehicles: VecDeque<u64>,
+    output_dir: PathBuf,
+}
+
+impl Snapshot {
+    fn new(rank: u32, output_dir: &Path) -> Self {
+        Snapshot {
+            rank,
+            output_dir: output_dir.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    pub fn record_vehicle(&mut self, id: u64) {
+        if self.last_vehicles.len() == LAST_VEHICLES {
+            self.last_vehicles.pop_front();
+        }
+        self.last_vehicles.push_back(id);
+    }
+
+    fn file_path(&self) -> PathBuf {
+        self.output_dir
+            .join(format!("crash_dump.{}.txt", self.rank))
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "rank: {}", self.rank)?;
+        writeln!(f, "time: {}", self.time)?;
+        writeln!(f, "activity queue: {}", self.activity_q)?;
+        writeln!(f, "teleportation queue: {}", self.teleportation_q)?;
+        writeln!(f, "vehicles on network: {}", self.veh_on_net)?;
+        writeln!(f, "active nodes: {}", self.active_nodes)?;
+        writeln!(f, "active links: {}", self.active_links)?;
+        writeln!(f, "buffered in messages: {}", self.buffered_in_messages)?;
+        writeln!(f, "pending out vehicles: {}", self.pending_out_vehicles)?;
+        write!(
+            f,
+            "last vehicles (internal ids, oldest first): {:?}",
+            self.last_vehicles
+        )
+    }
+}
+
+/// Enables crash dumps for the partition running on the current thread. The panic hook is
+/// installed once per process and chains the previously installed hook.
+pub fn init(rank: u32, output_dir: &Path) {
+    SNAPSHOT.with(|s| *s.borrow_mut() = Some(Snapshot::new(rank, output_dir)));
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            write_dump(info);
+            previous(info);
+        }));
+    });
+}
+
+/// Updates the snapshot of the current thread. Does nothing if crash dumps are not enabled.
+pub fn update<F: FnOnce(&mut Snapshot)>(f: F) {
+    SNAPSHOT.with(|s| {
+        if let Some(snapshot) = s.borrow_mut().as_mut() {
+            f(snapshot);
+        }
+    });
+}
+
+fn write_dump(info: &PanicHookInfo) {
+    // the thread local might be destroyed or borrowed already, if the panic happens while updating.
+    let _ = SNAPSHOT.try_with(|s| {
+        if let Ok(snapshot) = s.try_borrow() {
+            if let Some(snapshot) = snapshot.as_ref() {
+                let path = snapshot.file_path();
+                let content = format!("{info}\n{snapshot}\n");
+                match fs::write(&path, content) {
+                    Ok(_) => error!("#{} panicked. Wrote crash dump to {path:?}", snapshot.rank),
+                    Err(e) => error!(
+                        "#{} panicked. Failed to write crash dump: {e}",
+                        snapshot.rank
+                    ),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::simulation::crash_dump::{Snapshot, LAST_VEHICLES};
+
+    #[test]
+    fn keeps_last_vehicles() {
+        let mut snapshot = Snapshot::new(3, &PathBuf::from("./test_output/crash_dump/"));
+        for id in 0..(LAST_VEHICLES as u64 + 5) {
+            snapshot.record_vehicle(id);
+        }
+        snapshot.time = 42;
+
+        assert_eq!(LAST_VEHICLES, snapshot.last_vehicles.len());
+        assert_eq!(Some(&5), snapshot.last_vehicles.front());
+
+        let dump = snapshot.to_string();
+        assert!(dump.contains("rank: 3"));
+        assert!(dump.contains("time: 42"));
+        assert_eq!(
+            PathBuf::from("./test_output/crash_dump/crash_dump.3.txt"),
+            snapshot.file_path()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsPublisher;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::population::Person;
+
+/// An engine simulates the legs of one or several modes. The simulation hands agents, whose next leg
+/// has one of the engine's modes, to the engine once they depart. The engine hands the agents back
+/// via [EngineContext::end_leg], once they have arrived at the end of the leg.
+///
+/// The built-in parts of the simulation (activities, teleportation and the network) run first in
+/// each time step. Registered engines run afterwards, in the order they were added.
+pub trait Engine {
+    /// Modes of the legs which are handled by this engine.
+    fn modes(&self) -> Vec<Id<String>> {
+        Vec::new()
+    }
+
+    /// The agent has ended its activity and departs on its current leg. The departure event was
+    /// already published.
+    fn receive_departure(&mut self, agent: Person, now: u32, ctx: &mut EngineContext);
+
+    /// Called once per time step.
+    fn do_step(&mut self, now: u32, ctx: &mut EngineContext);
+
+    /// Number of agents, which are currently on a leg of this engine. This is used by the
+    /// consistency check of the simulation.
+    fn num_agents(&self) -> usize {
+        0
+    }
+
+    /// Called once after the last time step, before the events are finished.
+    fn finish(&mut self, _ctx: &mut EngineContext) {}
+}
+
+/// Parts of the simulation which are accessible from within an engine.
+pub struct EngineContext<'a> {
+    pub events: &'a mut EventsPublisher,
+    pub garage: &'a mut Garage,
+    pub rank: u32,
+    arrivals: Vec<Person>,
+}
+
+impl<'a> EngineContext<'a> {
+    pub fn new(events: &'a mut EventsPublisher, garage: &'a mut Garage, rank: u32) -> Self {
+        EngineContext {
+            events,
+            garage,
+            rank,
+            arrivals: Vec::new(),
+        }
+    }
+
+    /// The agent has arrived at the end of its current leg. The simulation publishes the arrival
+    /// and lets the agent start its next activity.
+    pub fn end_leg(&mut self, agent: Person) {
+        self.arrivals.push(agent);
+    }
+
+    pub fn take_arrivals(&mut self) -> Vec<Person> {
+        std::mem::take(&mut self.arrivals)
+    }
+}
+
+/// Creates the engine of a partition. Partitions are set up on their own threads, so engines are
+/// created from a factory which receives the rank of the partition.
+pub type EngineFactory = Arc<dyn Fn(u32) -> Box<dyn Engine> + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::engines::EngineContext;
+    use crate::simulation::messaging::events::EventsPublisher;
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::population::{Person, Plan};
+
+    #[test]
+    fn take_arrivals() {
+        let mut events = EventsPublisher::new();
+        let mut garage = Garage::new();
+        let mut ctx = EngineContext::new(&mut events, &mut garage, 0);
+
+        ctx.end_leg(Person::new(1, Plan::new()));
+        ctx.end_leg(Person::new(2, Plan::new()));
+
+        let ids: Vec<_> = ctx.take_arrivals().iter().map(|p| p.id).collect();
+        assert_eq!(vec![1, 2], ids);
+        assert!(ctx.take_arrivals().is_empty());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use nohash_hasher::IntSet;
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::{Link, Network, Node};
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::{Activity, Leg, Person, Route};
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+/// Event type, which is published for each evacuated person.
+pub const EVACUATION_EVENT: &str = "evacuation";
+
+/// Polygon, which is read from a csv file with the header `x,y` and one corner per line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Area {
+    corners: Vec<(f64, f64)>,
+}
+
+impl Area {
+    pub fn new(corners: Vec<(f64, f64)>) -> Self {
+        assert!(
+            corners.len() >= 3,
+            "An area needs at least three corners, but got {corners:?}"
+        );
+        Area { corners }
+    }
+
+    pub fn from_file(path: &Path) -> Self {
+        let file = File::open(path).unwrap_or_else(|_| panic!("Failed to open area at {path:?}"));
+        let corners = BufReader::new(file)
+            .lines()
+            .skip(1)
+            .map(|line| line.expect("Failed to read area"))
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let values: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+                if values.len() != 2 {
+                    panic!("Expected a corner as x,y, but got {line}");
+                }
+                let x = values[0]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid x of corner {line}"));
+                let y = values[1]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid y of corner {line}"));
+                (x, y)
+            })
+            .collect();
+        Self::new(corners)
+    }
+
+    /// Even-odd rule: a point is inside, if a ray starting at the point crosses the boundary an
+    /// odd number of times.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let mut inside = false;
+        let mut j = self.corners.len() - 1;
+        for i in 0..self.corners.len() {
+            let (xi, yi) = self.corners[i];
+            let (xj, yj) = self.corners[j];
+            if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+            j = i;
+        }
+        inside
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SafeLink {
+    link: u64,
+    x: f64,
+    y: f64,
+}
+
+/// At the trigger time, all agents performing an activity inside the evacuation area abort their
+/// plans. Their remaining plan is replaced with a leg to the closest safe node, where they stay for
+/// the rest of the simulation. Routes of these legs are computed by the within-day replanner, so
+/// congestion on the way out results from the link capacities like for any other leg.
+///
+/// Agents, which are travelling at the trigger time, continue their current trip.
+pub struct Evacuation {
+    area: Area,
+    trigger_time: u32,
+    veh_type: Id<VehicleType>,
+    act_type: Id<String>,
+    safe_links: Vec<SafeLink>,
+}
+
+impl Evacuation {
+    pub fn from_config(config: &config::Evacuation, network: &Network, garage: &Garage) -> Self {
+        let area = Area::from_file(Path::new(&config.area));
+        let safe_nodes: Option<IntSet<Id<Node>>> = if config.safe_nodes.is_empty() {
+            None
+        } else {
+            Some(
+                config
+                    .safe_nodes
+                    .iter()
+                    .map(|id| Id::get_from_ext(id))
+                    .collect(),
+            )
+        };
+        Self::new(
+            area,
+            config.trigger_time,
+            Id::get_from_ext(&config.veh_type),
+            Id::create(&config.act_type),
+            network,
+            garage,
+            safe_nodes,
+        )
+    }
+
+    /// Safe nodes are the given nodes, or all nodes outside the area. Agents are sent to an in-link
+    /// of the safe node, which allows the network mode of the evacuation vehicle type.
+    pub fn new(
+        area: Area,
+        trigger_time: u32,
+        veh_type: Id<VehicleType>,
+        act_type: Id<String>,
+        network: &Network,
+        garage: &Garage,
+        safe_nodes: Option<IntSet<Id<Node>>>,
+    ) -> Self {
+        let mode = garage
+            .vehicle_types
+            .get(&veh_type)
+            .unwrap_or_else(|| panic!("Evacuation vehicle type {veh_type} does not exist."))
+            .net_mode;
+        let safe_links: Vec<_> = network
+            .nodes
+            .iter()
+            .filter(|node| match &safe_nodes {
+                Some(safe_nodes) => safe_nodes.contains(&node.id),
+                None => !area.contains(node.x, node.y),
+            })
+            .filter_map(|node| {
+                node.in_links
+                    .iter()
+                    .find(|link_id| network.get_link(link_id).contains_mode(mode))
+                    .map(|link_id| SafeLink {
+                        link: link_id.internal(),
+                        x: node.x,
+                        y: node.y,
+                    })
+            })
+            .collect();
+        assert!(
+            !safe_links.is_empty(),
+            "There is no safe node, which can be reached by vehicles of type {veh_type}."
+        );
+
+        Evacuation {
+            area,
+            trigger_time,
+            veh_type,
+            act_type,
+            safe_links,
+        }
+    }
+
+    pub fn trigger_time(&self) -> u32 {
+        self.trigger_time
+    }
+
+    /// Whether the agent performs an activity inside the area. Agents at interaction activities are
+    /// in the middle of a trip and continue it.
+    pub fn is_affected(&self, agent: &Person) -> bool {
+        if agent.curr_plan_elem % 2 != 0 {
+            return false;
+        }
+        let act = agent.curr_act();
+        !act.is_interaction() && self.area.contains(act.x, act.y)
+    }
+
+    /// Replaces the remaining plan of the agent with a leg to the closest safe link, which starts
+    /// now. Returns the safe link.
+    pub fn evacuate(&self, agent: &mut Person, garage: &Garage, now: u32) -> u64 {
+        let act_index = (agent.curr_plan_elem / 2) as usize;
+        let veh_id = garage.veh_id(&Id::get(agent.id), &self.veh_type);
+        let mode = garage.vehicle_types.get(&self.veh_type).unwrap().net_mode;
+
+        let curr_act = agent.curr_act_mut();
+        curr_act.end_time = Some(now);
+        curr_act.max_dur = None;
+        let (x, y, from_link) = (curr_act.x, curr_act.y, curr_act.link_id);
+        let safe = self.closest_safe_link(x, y);
+
+        let plan = agent.plan.as_mut().unwrap();
+        plan.acts.truncate(act_index + 1);
+        plan.legs.truncate(act_index);
+        plan.add_leg(Leg {
+            mode,
+            routing_mode: mode,
+            dep_time: Some(now),
+            trav_time: 0,
+            route: Some(Route {
+                veh_id: veh_id.internal(),
+                distance: 0.,
+                route: vec![from_link, safe.link],
+            }),
+            driver: None,
+        });
+        plan.add_act(Activity::new(
+            safe.x,
+            safe.y,
+            self.act_type.internal(),
+            safe.link,
+            None,
+            None,
+            None,
+        ));
+        safe.link
+    }
+
+    pub fn event(&self, person: u64, safe_link: u64) -> Event {
+        let attrs = HashMap::from([
+            (
+                String::from("person"),
+                Id::<Person>::get(person).external().to_string(),
+            ),
+            (
+                String::from("link"),
+                Id::<Link>::get(safe_link).external().to_string(),
+            ),
+        ]);
+        Event::new_generic(EVACUATION_EVENT, attrs)
+    }
+
+    fn closest_safe_link(&self, x: f64, y: f64) -> SafeLink {
+        *self
+            .safe_links
+            .iter()
+            .min_by(|a, b| {
+                let dist_a = (a.x - x).powi(2) + (a.y - y).powi(2);
+                let dist_b = (b.x - x).powi(2) + (b.y - y).powi(2);
+                dist_a.total_cmp(&dist_b)
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::evacuation::{Area, Evacuation};
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::test_utils;
+
+    #[test]
+    fn area_contains() {
+        let area = Area::new(vec![(0., 0.), (10., 0.), (10., 10.), (5., 5.), (0., 10.)]);
+        assert!(area.contains(5., 2.));
+        assert!(area.contains(9., 8.));
+        // inside the notch of the polygon
+        assert!(!area.contains(5., 8.));
+        assert!(!area.contains(-1., 5.));
+        assert!(!area.contains(11., 5.));
+    }
+
+    #[test]
+    fn evacuate_to_closest_safe_node() {
+        let car = Id::<String>::create("car");
+        let mut network = Network::new();
+        let nodes = [
+            Node::new(Id::create("evac-inside-0"), 0., 0., 0, 1),
+            Node::new(Id::create("evac-inside-1"), 100., 0., 0, 1),
+            Node::new(Id::create("evac-east"), 1000., 0., 0, 1),
+            Node::new(Id::create("evac-west"), -1200., 0., 0, 1),
+        ];
+        let link_ids = [
+            ("evac-0-1", 0, 1),
+            ("evac-1-east", 1, 2),
+            ("evac-0-west", 0, 3),
+        ];
+        for node in &nodes {
+            network.add_node(node.clone());
+        }
+        for (id, from, to) in link_ids {
+            let mut link = Link::new_with_default(Id::create(id), &nodes[from], &nodes[to]);
+            link.modes.insert(car.clone());
+            network.add_link(link);
+        }
+
+        let mut garage = Garage::new();
+        let veh_type = Id::create("car");
+        garage.add_veh_type(test_utils::create_vehicle_type(&veh_type, car.clone()));
+        let person_id = Id::<Person>::create("evacuee");
+        let veh_id = garage.add_veh_id(&person_id, &veh_type);
+
+        let evacuation = Evacuation::new(
+            Area::new(vec![(-50., -50.), (200., -50.), (200., 50.), (-50., 50.)]),
+            1800,
+            veh_type,
+            Id::create("evacuated"),
+            &network,
+            &garage,
+            None,
+        );
+
+        let link_0_1 = Id::<Link>::get_from_ext("evac-0-1").internal();
+        let mut plan = Plan::new();
+        let home = Id::<String>::create("home").internal();
+        let work = Id::<String>::create("work").internal();
+        plan.add_act(Activity::new(
+            150.,
+            0.,
+            home,
+            link_0_1,
+            None,
+            Some(3600),
+            None,
+        ));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: veh_id.internal(),
+                distance: 0.,
+                route: vec![link_0_1, link_0_1],
+            },
+            car.internal(),
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(150., 0., work, link_0_1, None, None, None));
+        let mut agent = Person::new(person_id.internal(), plan);
+
+        assert!(evacuation.is_affected(&agent));
+        let safe_link = evacuation.evacuate(&mut agent, &garage, 1800);
+
+        // the east node is closer to the activity than the west node
+        assert_eq!(
+            Id::<Link>::get_from_ext("evac-1-east").internal(),
+            safe_link
+        );
+        let plan = agent.plan.as_ref().unwrap();
+        assert_eq!(2, plan.acts.len());
+        assert_eq!(1, plan.legs.len());
+        assert_eq!(Some(1800), plan.acts[0].end_time);
+        assert_eq!(safe_link, plan.acts[1].link_id);
+        assert_eq!(None, plan.acts[1].end_time);
+        assert_eq!(
+            vec![link_0_1, safe_link],
+            plan.legs[0].route.as_ref().unwrap().route
+        );
+        assert!(!evacuation.is_affected(&Person::new(
+            person_id.internal(),
+            Plan {
+                acts: vec![Activity::new(500., 0., home, link_0_1, None, None, None)],
+                legs: vec![],
+            }
+        )));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use nohash_hasher::IntSet;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::analysis::skims::{read_zones, Zone};
+use crate::simulation::id::Id;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::Person;
+
+/// Event type of monetary transactions, as in matsim.
+pub const PERSON_MONEY_EVENT: &str = "personMoney";
+pub const FARE_PURPOSE: &str = "ptFare";
+
+/// Contents of the fare file referenced by the fares config module.
+///
+/// ```yaml
+/// modes: [pt]
+/// transaction_partner: pt operator
+/// model: !Distance
+///   base_fare: 1.5
+///   fare_per_km: 0.2
+///   max_fare: 4.0
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FareConfig {
+    /// Leg modes which are charged.
+    pub modes: Vec<String>,
+    /// Receiver of the fares, which is written into the money events.
+    #[serde(default = "default_transaction_partner")]
+    pub transaction_partner: String,
+    pub model: FareModel,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum FareModel {
+    /// A base fare plus a fare per km of the leg's distance, optionally capped.
+    Distance {
+        base_fare: f64,
+        fare_per_km: f64,
+        #[serde(default)]
+        max_fare: Option<f64>,
+    },
+    /// Fares between zones. Locations belong to the zone with the closest center. Zones are read
+    /// from a csv file with the header `id,x,y`.
+    Zones {
+        zones: String,
+        fares: Vec<ZoneFare>,
+        /// Fare of trips between zones without an explicit fare.
+        default_fare: f64,
+    },
+}
+
+/// Fare between two zones. It applies in both directions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ZoneFare {
+    pub from: String,
+    pub to: String,
+    pub fare: f64,
+}
+
+fn default_transaction_partner() -> String {
+    String::from("pt")
+}
+
+enum Calculator {
+    Distance {
+        base_fare: f64,
+        fare_per_km: f64,
+        max_fare: Option<f64>,
+    },
+    Zones {
+        zones: Vec<Zone>,
+        fares: HashMap<(String, String), f64>,
+        default_fare: f64,
+    },
+}
+
+/// Computes the fares of legs of the charged modes.
+pub struct FareSystem {
+    modes: IntSet<Id<String>>,
+    transaction_partner: String,
+    calculator: Calculator,
+}
+
+impl FareSystem {
+    pub fn from_file(path: &Path) -> Self {
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("Failed to open fares at {path:?}: {e}"));
+        let config: FareConfig = serde_yaml::from_reader(file)
+            .unwrap_or_else(|e| panic!("Failed to parse fares at {path:?}: {e}"));
+        Self::new(config)
+    }
+
+    pub fn new(config: FareConfig) -> Self {
+        let calculator = match config.model {
+            FareModel::Distance {
+                base_fare,
+                fare_per_km,
+                max_fare,
+            } => Calculator::Distance {
+                base_fare,
+                fare_per_km,
+                max_fare,
+            },
+            FareModel::Zones {
+                zones,
+                fares,
+                default_fare,
+            } => Calculator::Zones {
+                zones: read_zones(Path::new(&zones)),
+                fares: fares
+                    .into_iter()
+                    .flat_map(|f| {
+                        [
+                            ((f.from.clone(), f.to.clone()), f.fare),
+                            ((f.to, f.from), f.fare),
+                        ]
+                    })
+                    .collect(),
+                default_fare,
+            },
+        };
+        FareSystem {
+            modes: config.modes.iter().map(|m| Id::create(m)).collect(),
+            transaction_partner: config.transaction_partner,
+            calculator,
+        }
+    }
+
+    pub fn is_charged(&self, mode: &Id<String>) -> bool {
+        self.modes.contains(mode)
+    }
+
+    /// Fare of a leg with the given distance in meters between the two locations.
+    pub fn fare(&self, distance: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
+        match &self.calculator {
+            Calculator::Distance {
+                base_fare,
+                fare_per_km,
+                max_fare,
+            } => {
+                let fare = base_fare + fare_per_km * distance / 1000.;
+                max_fare.map_or(fare, |max| fare.min(max))
+            }
+            Calculator::Zones {
+                zones,
+                fares,
+                default_fare,
+            } => {
+                let (from_zone, to_zone) = match (nearest(zones, from), nearest(zones, to)) {
+                    (Some(from_zone), Some(to_zone)) => (from_zone, to_zone),
+                    _ => return *default_fare,
+                };
+                *fares
+                    .get(&(from_zone.id.clone(), to_zone.id.clone()))
+                    .unwrap_or(default_fare)
+            }
+        }
+    }
+
+    /// The money event charging the person for a leg. Amounts paid by persons are negative.
+    pub fn money_event(&self, person: u64, fare: f64) -> Event {
+        let attrs = HashMap::from([
+            (
+                String::from("person"),
+                Id::<Person>::get(person).external().to_string(),
+            ),
+            (String::from("amount"), (-fare).to_string()),
+            (String::from("purpose"), String::from(FARE_PURPOSE)),
+            (
+                String::from("transactionPartner"),
+                self.transaction_partner.clone(),
+            ),
+        ]);
+        Event::new_generic(PERSON_MONEY_EVENT, attrs)
+    }
+}
+
+fn nearest(zones: &[Zone], (x, y): (f64, f64)) -> Option<&Zone> {
+    zones.iter().min_by(|a, b| {
+        let dist_a = (a.x - x).powi(2) + (a.y - y).powi(2);
+        let dist_b = (b.x - x).powi(2) + (b.y - y).powi(2);
+        dist_a.total_cmp(&dist_b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::simulation::fares::{FareConfig, FareModel, FareSystem, ZoneFare};
+    use crate::simulation::id::Id;
+
+    #[test]
+    fn distance_fare() {
+        let fares = FareSystem::new(FareConfig {
+            modes: vec![String::from("pt")],
+            transaction_partner: String::from("pt"),
+            model: FareModel::Distance {
+                base_fare: 1.5,
+                fare_per_km: 0.2,
+                max_fare: Some(4.),
+            },
+        });
+
+        assert!(fares.is_charged(&Id::create("pt")));
+        assert!(!fares.is_charged(&Id::create("car")));
+        assert_eq!(2.5, fares.fare(5000., (0., 0.), (0., 0.)));
+        assert_eq!(4., fares.fare(50000., (0., 0.), (0., 0.)));
+    }
+
+    #[test]
+    fn zone_fare() {
+        let dir = PathBuf::from("./test_output/simulation/fares/zone_fare/");
+        std::fs::create_dir_all(&dir).unwrap();
+        let zones = dir.join("zones.csv");
+        let mut file = std::fs::File::create(&zones).unwrap();
+        writeln!(file, "id,x,y\ncenter,0,0\nsuburb,1000,0\nfar,5000,0").unwrap();
+
+        let fares = FareSystem::new(FareConfig {
+            modes: vec![String::from("pt")],
+            transaction_partner: String::from("pt"),
+            model: FareModel::Zones {
+                zones: zones.to_str().unwrap().to_string(),
+                fares: vec![
+                    ZoneFare {
+                        from: String::from("center"),
+                        to: String::from("center"),
+                        fare: 1.,
+                    },
+                    ZoneFare {
+                        from: String::from("center"),
+                        to: String::from("suburb"),
+                        fare: 2.,
+                    },
+                ],
+                default_fare: 3.,
+            },
+        });
+
+        assert_eq!(1., fares.fare(100., (10., 10.), (-20., 5.)));
+        // zone fares apply in both directions
+        assert_eq!(2., fares.fare(1000., (10., 0.), (900., 0.)));
+        assert_eq!(2., fares.fare(1000., (900., 0.), (10., 0.)));
+        assert_eq!(3., fares.fare(5000., (0., 0.), (4800., 0.)));
+    }
+
+    #[test]
+    fn read_fare_config() {
+        let yaml = r#"
+        modes: [pt]
+        model: !Distance
+          base_fare: 1.5
+          fare_per_km: 0.2
+        "#;
+        let config: FareConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(String::from("pt"), config.transaction_partner);
+        assert_eq!(
+            FareModel::Distance {
+                base_fare: 1.5,
+                fare_per_km: 0.2,
+                max_fare: None,
+            },
+            config.model
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::{Debug, Formatter};
+
+use crate::simulation::id::serializable_type::StableTypeId;
+use crate::simulation::id::Id;
+
+/// Map from ids to values, which stores the values in a vec indexed by the internal ids. Internal
+/// ids are handed out consecutively per type, so the vec is dense and lookups are a single index
+/// operation. This is meant for maps, which contain most ids of a type and are accessed on hot
+/// paths, e.g. vehicles and vehicle types. Iteration is ordered by internal id.
+///
+/// The methods mirror those of [std::collections::HashMap].
+pub struct IdMap<K: StableTypeId + 'static, V> {
+    entries: Vec<Option<(Id<K>, V)>>,
+    len: usize,
+}
+
+impl<K: StableTypeId + 'static, V> IdMap<K, V> {
+    pub fn new() -> Self {
+        IdMap {
+            entries: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, id: &Id<K>) -> Option<&V> {
+        self.get_internal(id.internal())
+    }
+
+    /// Looks up a value by the internal id of its key, without creating an [Id].
+    pub fn get_internal(&self, internal: u64) -> Option<&V> {
+        self.entries
+            .get(internal as usize)
+            .and_then(|entry| entry.as_ref())
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, id: &Id<K>) -> Option<&mut V> {
+        self.entries
+            .get_mut(id.internal() as usize)
+            .and_then(|entry| entry.as_mut())
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, id: &Id<K>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Inserts a value and returns the previous value of the id, if there was one.
+    pub fn insert(&mut self, id: Id<K>, value: V) -> Option<V> {
+        let index = id.internal() as usize;
+        if index >= self.entries.len() {
+            self.entries.resize_with(index + 1, || None);
+        }
+        let previous = self.entries[index].replace((id, value));
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous.map(|(_, value)| value)
+    }
+
+    pub fn remove(&mut self, id: &Id<K>) -> Option<V> {
+        let removed = self
+            .entries
+            .get_mut(id.internal() as usize)
+            .and_then(|entry| entry.take());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed.map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Id<K>, &V)> {
+        self.entries.iter().filter_map(|entry| {
+            let (id, value) = entry.as_ref()?;
+            Some((id, value))
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Id<K>> {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.entries
+            .iter_mut()
+            .filter_map(|entry| entry.as_mut().map(|(_, value)| value))
+    }
+}
+
+impl<K: StableTypeId + 'static, V> Default for IdMap<K, V> {
+    fn default() -> Self {
+        IdMap::new()
+    }
+}
+
+impl<K: StableTypeId + 'static, V: Clone> Clone for IdMap<K, V> {
+    fn clone(&self) -> Self {
+        IdMap {
+            entries: self.entries.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// Maps are equal if they contain the same entries, independent of the capacity of their vecs.
+impl<K: StableTypeId + 'static, V: PartialEq> PartialEq for IdMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: StableTypeId + 'static, V: Debug> Debug for IdMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: StableTypeId + 'static, V> FromIterator<(Id<K>, V)> for IdMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (Id<K>, V)>>(iter: T) -> Self {
+        let mut map = IdMap::new();
+        for (id, value) in iter {
+            map.insert(id, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::id_map::IdMap;
+    use crate::simulation::id::Id;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map: IdMap<(), &str> = IdMap::new();
+        let id_3 = Id::new_internal(3);
+        let id_1 = Id::new_internal(1);
+
+        assert_eq!(None, map.insert(id_3.clone(), "three"));
+        assert_eq!(None, map.insert(id_1.clone(), "one"));
+        assert_eq!(Some("three"), map.insert(id_3.clone(), "drei"));
+        assert_eq!(2, map.len());
+        assert_eq!(Some(&"drei"), map.get(&id_3));
+        assert_eq!(Some(&"one"), map.get_internal(1));
+        assert_eq!(None, map.get_internal(2));
+        assert_eq!(None, map.get(&Id::new_internal(42)));
+        assert_eq!(
+            vec![1, 3],
+            map.keys().map(|id| id.internal()).collect::<Vec<_>>()
+        );
+
+        assert_eq!(Some("one"), map.remove(&id_1));
+        assert_eq!(None, map.remove(&id_1));
+        assert_eq!(1, map.len());
+        assert!(!map.contains_key(&id_1));
+
+        // the same entries in a shorter vec
+        let other: IdMap<(), &str> = [(id_3, "drei")].into_iter().collect();
+        assert_eq!(other, map);
+    }
+}
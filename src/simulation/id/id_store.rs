@@ -1,13 +1,12 @@
 use std::fs;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write};
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 
 use ahash::{AHashMap, RandomState};
 use bytes::{Buf, BufMut};
 use nohash_hasher::IntMap;
-use prost::encoding::{DecodeContext, WireType};
 use prost::Message;
 use tracing::info;
 
@@ -37,8 +36,14 @@ fn serialize_to_file(store: &IdStore, file_path: &Path, compression: IdCompressi
 }
 
 fn serialize<W: Write>(store: &IdStore, writer: &mut W, compression: IdCompression) {
-    for (type_id, ids) in &store.ids {
-        let data = serialize_ids(ids, compression);
+    let types = store.types.read().unwrap();
+    for (type_id, typed_ids) in types.iter() {
+        let typed_ids = typed_ids.read().unwrap();
+        assert!(
+            typed_ids.compact == 0,
+            "Ids of type {type_id} were loaded without their external ids and can't be written."
+        );
+        let data = serialize_ids(&typed_ids.ids, compression);
         let ids = IdsWithType {
             type_id: *type_id,
             data: Some(data),
@@ -53,34 +58,42 @@ fn serialize<W: Write>(store: &IdStore, writer: &mut W, compression: IdCompressi
         .expect("Failed to flush writer after serializing id store");
 }
 
-fn deserialize_from_file(store: &mut IdStore, file_path: &Path) {
+fn deserialize_from_file(store: &IdStore, file_path: &Path, compact_types: &[u64]) {
     info!("Starting to load IdStore from file {file_path:?}");
     let file = File::open(file_path).unwrap();
     let mut file_reader = BufReader::new(file);
-    deserialize(store, &mut file_reader);
+    deserialize(store, &mut file_reader, compact_types);
 }
 
 /// This method takes a BufReader instance as we are relying on 'seek_relative' which is not part of
 /// the Read trait. I think it is ok, to let callees wrap their bytes into a BufReader.
-fn deserialize<R: Read + Seek>(store: &mut IdStore, reader: R) {
+///
+/// For types listed in compact_types only the number of ids is loaded. The external ids of these
+/// types are skipped.
+fn deserialize<R: Read + Seek>(store: &IdStore, reader: R, compact_types: &[u64]) {
     info!("Starting to de-serialize Id store.");
     let delim_reader: MessageIter<IdsWithType, R> = MessageIter::new(reader);
     for message in delim_reader {
-        let ids = deserialize_ids(&message);
-        store.replace_ids(&ids, message.type_id);
+        if compact_types.contains(&message.type_id) {
+            let count = count_ids(&message);
+            store.replace_ids_compact(count, message.type_id);
+        } else {
+            let ids = deserialize_ids(&message);
+            store.replace_ids(ids, message.type_id);
+        }
     }
 
     info!("Finished de-serializing id store.");
 }
 
-fn serialize_ids(ids: &Vec<Rc<UntypedId>>, mode: IdCompression) -> Data {
+fn serialize_ids(ids: &[Arc<UntypedId>], mode: IdCompression) -> Data {
     match mode {
         IdCompression::LZ4 => serialize_ids_compressed(ids),
         IdCompression::None => serialize_ids_uncompressed(ids),
     }
 }
 
-fn serialize_ids_uncompressed(ids: &Vec<Rc<UntypedId>>) -> Data {
+fn serialize_ids_uncompressed(ids: &[Arc<UntypedId>]) -> Data {
     let mut writer = BufWriter::new(Vec::new());
     encode_ids(ids, &mut writer);
 
@@ -90,7 +103,7 @@ fn serialize_ids_uncompressed(ids: &Vec<Rc<UntypedId>>) -> Data {
     Data::Raw(bytes)
 }
 
-fn serialize_ids_compressed(ids: &Vec<Rc<UntypedId>>) -> Data {
+fn serialize_ids_compressed(ids: &[Arc<UntypedId>]) -> Data {
     let writer = BufWriter::new(Vec::new());
     let mut compressor = lz4_flex::frame::FrameEncoder::new(writer);
 
@@ -103,12 +116,12 @@ fn serialize_ids_compressed(ids: &Vec<Rc<UntypedId>>) -> Data {
     Data::Lz4Data(bytes)
 }
 
-fn encode_ids<W: Write>(ids: &Vec<Rc<UntypedId>>, writer: &mut W) {
+fn encode_ids<W: Write>(ids: &[Arc<UntypedId>], writer: &mut W) {
     let mut id_buffer = Vec::new();
 
     for id in ids {
-        prost::encoding::encode_varint(id.external.len() as u64, &mut id_buffer);
-        id_buffer.put_slice(id.external.as_bytes());
+        prost::encoding::encode_varint(id.external().len() as u64, &mut id_buffer);
+        id_buffer.put_slice(id.external().as_bytes());
         writer
             .write_all(&id_buffer)
             .expect("Failed to write encoded String.");
@@ -117,18 +130,18 @@ fn encode_ids<W: Write>(ids: &Vec<Rc<UntypedId>>, writer: &mut W) {
     writer.flush().expect("Failed to flush writer.");
 }
 
-fn deserialize_ids(ids: &IdsWithType) -> Vec<String> {
+fn deserialize_ids(ids: &IdsWithType) -> ExternalArena {
     if let Some(bytes) = &ids.data {
         match bytes {
             Data::Raw(raw_bytes) => deserialize_ids_uncompressed(raw_bytes),
             Data::Lz4Data(lz4_bytes) => deserialize_ids_compressed(lz4_bytes),
         }
     } else {
-        Vec::new()
+        ExternalArena::default()
     }
 }
 
-fn deserialize_ids_compressed(bytes: &[u8]) -> Vec<String> {
+fn deserialize_ids_compressed(bytes: &[u8]) -> ExternalArena {
     let compressed_reader = Cursor::new(bytes);
     let mut decompressor = lz4_flex::frame::FrameDecoder::new(compressed_reader);
 
@@ -141,143 +154,368 @@ fn deserialize_ids_compressed(bytes: &[u8]) -> Vec<String> {
     decode_ids(&mut uncompressed_reader)
 }
 
-fn deserialize_ids_uncompressed(bytes: &[u8]) -> Vec<String> {
+fn deserialize_ids_uncompressed(bytes: &[u8]) -> ExternalArena {
     let mut cursor = Cursor::new(bytes);
     decode_ids(&mut cursor)
 }
 
-fn decode_ids<B: Buf>(buffer: &mut B) -> Vec<String> {
-    let mut result = Vec::new();
+fn count_ids(ids: &IdsWithType) -> usize {
+    match &ids.data {
+        Some(Data::Raw(raw_bytes)) => skip_ids(Cursor::new(raw_bytes)),
+        Some(Data::Lz4Data(lz4_bytes)) => {
+            skip_ids(lz4_flex::frame::FrameDecoder::new(Cursor::new(lz4_bytes)))
+        }
+        None => 0,
+    }
+}
 
-    while buffer.has_remaining() {
-        let mut external_id = String::new();
-        prost::encoding::string::merge(
-            WireType::LengthDelimited,
-            &mut external_id,
-            buffer,
-            DecodeContext::default(),
-        )
-        .expect("Error decoding String");
+/// Counts length prefixed strings while streaming over them. This way, the de-compressed external
+/// ids are never held in memory as a whole.
+fn skip_ids<R: Read>(reader: R) -> usize {
+    let mut reader = BufReader::new(reader);
+    let mut count = 0;
+    while let Some(len) = read_varint(&mut reader) {
+        let skipped = io::copy(&mut (&mut reader).take(len), &mut io::sink())
+            .expect("Failed to skip external id");
+        assert_eq!(
+            len, skipped,
+            "Buffer ended before the external id of length {len} was complete."
+        );
+        count += 1;
+    }
+    count
+}
 
-        result.push(external_id);
+/// Reads a varint byte by byte. Returns None if the reader is at its end.
+fn read_varint<R: Read>(reader: &mut R) -> Option<u64> {
+    let mut result = 0;
+    let mut byte = [0u8; 1];
+    for shift in (0..64).step_by(7) {
+        if reader.read(&mut byte).expect("Failed to read varint") == 0 {
+            assert_eq!(0, shift, "Buffer ended in the middle of a varint.");
+            return None;
+        }
+        result |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    panic!("Error decoding String length. Varint is longer than 10 bytes.");
+}
+
+/// Decodes length prefixed strings directly into an arena, instead of allocating a String per id.
+fn decode_ids<B: Buf>(buffer: &mut B) -> ExternalArena {
+    let mut result = ExternalArena::default();
+    let mut bytes = Vec::new();
+
+    while buffer.has_remaining() {
+        let len =
+            prost::encoding::decode_varint(buffer).expect("Error decoding String length") as usize;
+        assert!(
+            buffer.remaining() >= len,
+            "Buffer ended before the external id of length {len} was complete."
+        );
+        bytes.resize(len, 0);
+        buffer.copy_to_slice(&mut bytes);
+        let external = std::str::from_utf8(&bytes).expect("Error decoding String");
+        result.push(external);
     }
     result
 }
 
+/// External ids of one type, which are stored back to back in a single String.
+#[derive(Debug, Default)]
+struct ExternalArena {
+    text: String,
+    ranges: Vec<(u32, u32)>,
+}
+
+impl ExternalArena {
+    fn push(&mut self, external: &str) {
+        let start = self.text.len();
+        self.text.push_str(external);
+        let end = self.text.len();
+        assert!(
+            end <= u32::MAX as usize,
+            "External ids of one type must not exceed 4GB."
+        );
+        self.ranges.push((start as u32, end as u32));
+    }
+}
+
+/// An id with its internal and external representation. The external id is a slice of a shared
+/// chunk of text. Ids which were loaded from a file share one chunk per type. Ids which were
+/// created one by one have their own chunk.
 #[derive(Debug)]
 pub struct UntypedId {
     pub(crate) internal: u64,
-    pub(crate) external: String,
+    chunk: Arc<str>,
+    start: u32,
+    end: u32,
 }
 
 impl UntypedId {
     pub(crate) fn new(internal: u64, external: String) -> Self {
-        Self { internal, external }
+        let end = external.len() as u32;
+        Self {
+            internal,
+            chunk: Arc::from(external),
+            start: 0,
+            end,
+        }
+    }
+
+    pub(crate) fn external(&self) -> &str {
+        &self.chunk[self.start as usize..self.end as usize]
     }
 }
 
+/// Ids of one type. Each type is locked separately, so that creating ids of one type doesn't block
+/// look ups of other types. The mapping from external to internal ids is only built, once it is
+/// needed. A simulation run, which loads its ids from a file, mostly looks up ids by their internal
+/// id and doesn't need to keep the mapping in memory.
+///
+/// Compact ids only know their internal ids. Their external ids are empty and can't be looked up.
+/// Ids which are created after loading compact ids, e.g. vehicles created during the simulation,
+/// keep their external ids and can be looked up as usual. They get the internal ids after the
+/// loaded ones. As the external ids of the loaded ids are unknown, it can't be checked whether
+/// such an id was part of the loaded file already.
 #[derive(Debug)]
-pub struct IdStore<'ext> {
-    ids: IntMap<u64, Vec<Rc<UntypedId>>>,
-    mapping: IntMap<u64, AHashMap<&'ext str, u64>>,
+struct TypedIds {
+    ids: Vec<Arc<UntypedId>>,
+    mapping: Option<AHashMap<&'static str, u64>>,
+    /// Number of ids at the start of `ids`, which were loaded without their external ids.
+    compact: usize,
 }
 
-/// Cache for ids. All methods are public, so that they can be used from mod.rs. The module doesn't
-/// export this module, so that everything is kept package private
-impl<'ext> IdStore<'ext> {
-    pub fn new() -> Self {
-        Self {
-            ids: IntMap::default(),
-            mapping: IntMap::default(),
+impl TypedIds {
+    fn new() -> Self {
+        TypedIds {
+            ids: Vec::new(),
+            mapping: None,
+            compact: 0,
         }
     }
 
-    fn create_id_with_type_id(&mut self, id: &str, type_id: u64) -> Rc<UntypedId> {
-        let type_mapping = self
-            .mapping
-            .entry(type_id)
-            .or_insert_with(|| AHashMap::with_hasher(RandomState::with_seed(42)));
+    fn has_mapping(&self) -> bool {
+        self.mapping.is_some()
+    }
 
-        if type_mapping.contains_key(id) {
-            let internal = type_mapping.get(id).unwrap();
-            return self
-                .ids
-                .get(&type_id)
-                .unwrap()
-                .get(*internal as usize)
-                .unwrap()
-                .clone();
+    fn build_mapping(&mut self) {
+        if self.mapping.is_some() {
+            return;
+        }
+        let mut mapping = AHashMap::with_capacity_and_hasher(
+            self.ids.len() - self.compact,
+            RandomState::with_seed(42),
+        );
+        for id in &self.ids[self.compact..] {
+            mapping.insert(Self::external_ref(id), id.internal);
         }
+        self.mapping = Some(mapping);
+    }
+
+    /// Requires the mapping to be built.
+    fn get_from_ext(&self, external: &str) -> Option<Arc<UntypedId>> {
+        self.mapping
+            .as_ref()
+            .expect("Mapping of external ids must be built before looking up ids.")
+            .get(external)
+            .map(|internal| self.ids.get(*internal as usize).unwrap().clone())
+    }
+
+    fn create(&mut self, id: &str) -> Arc<UntypedId> {
+        self.build_mapping();
+        // another thread might have created the id, while we were waiting for the write lock.
+        if let Some(existing) = self.get_from_ext(id) {
+            return existing;
+        }
+
+        let next_internal = self.ids.len() as u64;
+        let next_id = Arc::new(UntypedId::new(next_internal, String::from(id)));
+        self.push(next_id.clone());
+        next_id
+    }
 
-        let type_ids = self.ids.entry(type_id).or_insert_with(Vec::default);
-        let next_internal = type_ids.len() as u64;
-        let next_id = Rc::new(UntypedId::new(next_internal, String::from(id)));
-        type_ids.push(next_id.clone());
+    fn push(&mut self, id: Arc<UntypedId>) {
+        if let Some(mapping) = self.mapping.as_mut() {
+            mapping.insert(Self::external_ref(&id), id.internal);
+        }
+        self.ids.push(id);
+    }
 
-        let ptr_external: *const String = &next_id.external;
+    fn external_ref(id: &Arc<UntypedId>) -> &'static str {
+        let ptr_external: *const str = id.external();
         /*
         # Safety:
 
-        As the external Strings are allocated by the ids, which keep a pointer to that allocation
-        The allocated string will not move as long as the id exists. This means as long as the id
-        is in the map, the ref to the external String which is used as a key in the map will be valid
+        The external ids are slices of chunks, which are kept alive by the ids. The chunks are
+        immutable and don't move as long as the ids exist. This means as long as the id is in the
+        ids vec, the ref to the external id which is used as a key in the map will be valid.
+        The mapping is always cleared together with or before the ids vec.
          */
-        let external_ref = unsafe { ptr_external.as_ref() }.unwrap();
-        type_mapping.insert(external_ref, next_id.internal);
+        unsafe { ptr_external.as_ref() }.unwrap()
+    }
 
-        next_id
+    /// Replaces all ids with the ids of the arena. All external ids share a single allocation.
+    fn replace(&mut self, arena: ExternalArena) {
+        self.mapping = None;
+        self.compact = 0;
+        self.ids.clear();
+
+        let chunk: Arc<str> = Arc::from(arena.text);
+        self.ids.reserve_exact(arena.ranges.len());
+        for (internal, (start, end)) in arena.ranges.into_iter().enumerate() {
+            self.ids.push(Arc::new(UntypedId {
+                internal: internal as u64,
+                chunk: chunk.clone(),
+                start,
+                end,
+            }));
+        }
     }
 
-    fn replace_ids(&mut self, ids: &Vec<String>, type_id: u64) {
-        if let Some(type_mapping) = self.mapping.get_mut(&type_id) {
-            type_mapping.clear();
+    /// Replaces all ids with count ids, which only have an internal id.
+    fn replace_compact(&mut self, count: usize) {
+        self.mapping = None;
+        self.compact = count;
+        self.ids.clear();
+
+        let chunk: Arc<str> = Arc::from("");
+        self.ids.reserve_exact(count);
+        for internal in 0..count {
+            self.ids.push(Arc::new(UntypedId {
+                internal: internal as u64,
+                chunk: chunk.clone(),
+                start: 0,
+                end: 0,
+            }));
         }
-        if let Some(type_ids) = self.ids.get_mut(&type_id) {
-            type_ids.clear();
+    }
+}
+
+/// Thread safe cache for ids. Ids are shared via Arc, so that they can be sent across threads.
+#[derive(Debug)]
+pub struct IdStore {
+    types: RwLock<IntMap<u64, Arc<RwLock<TypedIds>>>>,
+}
+
+/// Cache for ids. All methods are public, so that they can be used from mod.rs. The module doesn't
+/// export this module, so that everything is kept package private
+impl IdStore {
+    pub fn new() -> Self {
+        Self {
+            types: RwLock::new(IntMap::default()),
         }
+    }
+
+    fn typed_ids(&self, type_id: u64) -> Option<Arc<RwLock<TypedIds>>> {
+        self.types.read().unwrap().get(&type_id).cloned()
+    }
+
+    fn typed_ids_or_insert(&self, type_id: u64) -> Arc<RwLock<TypedIds>> {
+        if let Some(typed_ids) = self.typed_ids(type_id) {
+            return typed_ids;
+        }
+        self.types
+            .write()
+            .unwrap()
+            .entry(type_id)
+            .or_insert_with(|| Arc::new(RwLock::new(TypedIds::new())))
+            .clone()
+    }
+
+    fn create_id_with_type_id(&self, id: &str, type_id: u64) -> Arc<UntypedId> {
+        let typed_ids = self.typed_ids_or_insert(type_id);
 
-        for external_id in ids {
-            self.create_id_with_type_id(external_id, type_id);
+        // most ids exist already. Only take the write lock, if the id must be created.
+        if let Some(existing) = Self::lookup(&typed_ids, id) {
+            return existing;
         }
+        let mut typed_ids = typed_ids.write().unwrap();
+        typed_ids.create(id)
+    }
+
+    /// Looks up an id by its external id. Builds the mapping of the type on first use.
+    fn lookup(typed_ids: &RwLock<TypedIds>, external: &str) -> Option<Arc<UntypedId>> {
+        {
+            let typed_ids = typed_ids.read().unwrap();
+            if typed_ids.has_mapping() {
+                return typed_ids.get_from_ext(external);
+            }
+        }
+        let mut typed_ids = typed_ids.write().unwrap();
+        typed_ids.build_mapping();
+        typed_ids.get_from_ext(external)
+    }
+
+    fn replace_ids(&self, ids: ExternalArena, type_id: u64) {
+        let typed_ids = self.typed_ids_or_insert(type_id);
+        typed_ids.write().unwrap().replace(ids);
+    }
+
+    fn replace_ids_compact(&self, count: usize, type_id: u64) {
+        let typed_ids = self.typed_ids_or_insert(type_id);
+        typed_ids.write().unwrap().replace_compact(count);
     }
 
-    pub(crate) fn create_id<T: StableTypeId + 'static>(&mut self, id: &str) -> Id<T> {
+    pub(crate) fn create_id<T: StableTypeId + 'static>(&self, id: &str) -> Id<T> {
         let type_id = T::stable_type_id();
         Id::new(self.create_id_with_type_id(id, type_id))
     }
 
     pub(crate) fn get<T: StableTypeId + 'static>(&self, internal: u64) -> Id<T> {
         let type_id = T::stable_type_id();
-        let type_ids = self.ids.get(&type_id).unwrap_or_else(|| {
+        let typed_ids = self.typed_ids(type_id).unwrap_or_else(|| {
             panic!("No ids for type {type_id:?}. Use Id::create::<T>(...) to create ids")
         });
 
-        let untyped_id = type_ids
+        let untyped_id = typed_ids
+            .read()
+            .unwrap()
+            .ids
             .get(internal as usize)
             .unwrap_or_else(|| panic!("No id found for internal {internal}"))
             .clone();
         Id::new(untyped_id)
     }
 
+    pub(crate) fn try_get<T: StableTypeId + 'static>(&self, internal: u64) -> Option<Id<T>> {
+        let typed_ids = self.typed_ids(T::stable_type_id())?;
+        let untyped_id = typed_ids
+            .read()
+            .unwrap()
+            .ids
+            .get(internal as usize)?
+            .clone();
+        Some(Id::new(untyped_id))
+    }
+
     pub(crate) fn get_from_ext<T: StableTypeId + 'static>(&self, external: &str) -> Id<T> {
         let type_id = T::stable_type_id();
-        let type_mapping = self.mapping.get(&type_id).unwrap_or_else(|| {
+        let typed_ids = self.typed_ids(type_id).unwrap_or_else(|| {
             panic!("No ids for type {type_id:?}. Use Id::create::<T>(...) to create ids")
         });
 
-        let index = type_mapping.get(external).unwrap_or_else(|| {
+        let untyped_id = Self::lookup(&typed_ids, external).unwrap_or_else(|| {
+            if typed_ids.read().unwrap().compact > 0 {
+                panic!("Could not find id for external id: {external}. The external ids of this type were not loaded, because the id store was loaded in compact mode. Only ids created during the run can be looked up by their external id.");
+            }
             panic!("Could not find id for external id: {external}");
         });
-
-        self.get(*index)
+        Id::new(untyped_id)
     }
 
     pub(crate) fn to_file(&self, file_path: &Path) {
         serialize_to_file(self, file_path, IdCompression::LZ4);
     }
 
-    pub(crate) fn load_from_file(&mut self, file_path: &Path) {
-        deserialize_from_file(self, file_path);
+    pub(crate) fn load_from_file(&self, file_path: &Path) {
+        deserialize_from_file(self, file_path, &[]);
+    }
+
+    pub(crate) fn load_from_file_compact(&self, file_path: &Path, compact_types: &[u64]) {
+        deserialize_from_file(self, file_path, compact_types);
     }
 }
 
@@ -286,12 +524,14 @@ mod tests {
     use std::io::{BufReader, BufWriter, Cursor};
     use std::ops::Sub;
     use std::path::PathBuf;
+    use std::sync::Arc;
     use std::time::Instant;
 
     use crate::simulation::config::PartitionMethod;
     use crate::simulation::id::id_store::{
         deserialize, deserialize_from_file, serialize, serialize_to_file, IdCompression, IdStore,
     };
+    use crate::simulation::id::serializable_type::StableTypeId;
     use crate::simulation::logging::init_std_out_logging;
     use crate::simulation::network::global_network::{Link, Network, Node};
     use crate::simulation::population::population::Population;
@@ -307,14 +547,14 @@ mod tests {
             "./test_output/simulation/id/id_store/write_read_ids_store/",
         ));
         let file = folder.join("ids.pbf");
-        let mut store = IdStore::new();
+        let store = IdStore::new();
         store.create_id::<()>("test-1");
         store.create_id::<()>("test-2");
         store.create_id::<String>("string-id");
 
         serialize_to_file(&store, &file, IdCompression::LZ4);
-        let mut result = IdStore::new();
-        deserialize_from_file(&mut result, &file);
+        let result = IdStore::new();
+        deserialize_from_file(&result, &file, &[]);
 
         println!("{result:?}");
 
@@ -334,14 +574,14 @@ mod tests {
             "./test_output/simulation/id/id_store/write_read_ids_store_uncompressed/",
         ));
         let file = folder.join("ids.pbf");
-        let mut store = IdStore::new();
+        let store = IdStore::new();
         store.create_id::<()>("test-1");
         store.create_id::<()>("test-2");
         store.create_id::<String>("string-id");
 
         serialize_to_file(&store, &file, IdCompression::None);
-        let mut result = IdStore::new();
-        deserialize_from_file(&mut result, &file);
+        let result = IdStore::new();
+        deserialize_from_file(&result, &file, &[]);
 
         println!("{result:?}");
 
@@ -357,7 +597,7 @@ mod tests {
 
     #[test]
     fn test_serialize_ids() {
-        let mut store = IdStore::new();
+        let store = IdStore::new();
         store.create_id::<()>("test-1");
         store.create_id::<()>("test-2");
         store.create_id::<String>("string-id");
@@ -373,8 +613,8 @@ mod tests {
         println!("{serialized_bytes:?}");
 
         let mut vec_reader = BufReader::new(Cursor::new(serialized_bytes));
-        let mut result = IdStore::new();
-        deserialize(&mut result, &mut vec_reader);
+        let result = IdStore::new();
+        deserialize(&result, &mut vec_reader, &[]);
 
         println!("{result:?}");
 
@@ -388,6 +628,117 @@ mod tests {
         );
     }
 
+    #[test]
+    fn load_into_arena_with_lazy_mapping() {
+        let store = IdStore::new();
+        store.create_id::<()>("test-1");
+        store.create_id::<()>("test-2");
+
+        let mut writer = BufWriter::new(Vec::new());
+        serialize(&store, &mut writer, IdCompression::LZ4);
+        let bytes = writer.into_inner().unwrap();
+        let result = IdStore::new();
+        deserialize(&result, &mut BufReader::new(Cursor::new(bytes)), &[]);
+
+        let type_id = <() as StableTypeId>::stable_type_id();
+        let typed_ids = result.typed_ids(type_id).unwrap();
+        assert!(!typed_ids.read().unwrap().has_mapping());
+
+        // look ups by internal id don't need the mapping
+        let id_1 = result.get::<()>(0);
+        let id_2 = result.get::<()>(1);
+        assert_eq!("test-1", id_1.external());
+        assert_eq!("test-2", id_2.external());
+        assert!(!typed_ids.read().unwrap().has_mapping());
+        // loaded ids share one allocation for their external ids
+        assert!(Arc::ptr_eq(&id_1.id.chunk, &id_2.id.chunk));
+
+        assert_eq!(id_2, result.get_from_ext::<()>("test-2"));
+        assert!(typed_ids.read().unwrap().has_mapping());
+        assert_eq!(2, result.create_id::<()>("test-3").internal());
+    }
+
+    #[test]
+    fn load_compact() {
+        let store = IdStore::new();
+        store.create_id::<()>("test-1");
+        store.create_id::<()>("test-2");
+        store.create_id::<String>("string-id");
+
+        let mut writer = BufWriter::new(Vec::new());
+        serialize(&store, &mut writer, IdCompression::LZ4);
+        let bytes = writer.into_inner().unwrap();
+        let result = IdStore::new();
+        let compact_type = <() as StableTypeId>::stable_type_id();
+        deserialize(
+            &result,
+            &mut BufReader::new(Cursor::new(bytes)),
+            &[compact_type],
+        );
+
+        let id = result.get::<()>(1);
+        assert_eq!(1, id.internal());
+        assert_eq!("", id.external());
+        assert!(
+            result
+                .typed_ids(compact_type)
+                .unwrap()
+                .read()
+                .unwrap()
+                .compact
+                == 2
+        );
+        // other types are loaded with their external ids
+        assert_eq!(
+            store.get_from_ext::<String>("string-id"),
+            result.get_from_ext::<String>("string-id")
+        );
+    }
+
+    #[test]
+    fn create_after_load_compact() {
+        let store = IdStore::new();
+        store.create_id::<()>("test-1");
+
+        let mut writer = BufWriter::new(Vec::new());
+        serialize(&store, &mut writer, IdCompression::None);
+        let bytes = writer.into_inner().unwrap();
+        let result = IdStore::new();
+        let compact_type = <() as StableTypeId>::stable_type_id();
+        deserialize(
+            &result,
+            &mut BufReader::new(Cursor::new(bytes)),
+            &[compact_type],
+        );
+
+        // ids created during the run keep their external ids
+        let created = result.create_id::<()>("created");
+        assert_eq!(1, created.internal());
+        assert_eq!("created", created.external());
+        assert_eq!(created, result.get_from_ext::<()>("created"));
+        assert_eq!(created, result.create_id::<()>("created"));
+    }
+
+    #[test]
+    #[should_panic(expected = "compact mode")]
+    fn get_from_ext_compact() {
+        let store = IdStore::new();
+        store.create_id::<()>("test-1");
+
+        let mut writer = BufWriter::new(Vec::new());
+        serialize(&store, &mut writer, IdCompression::None);
+        let bytes = writer.into_inner().unwrap();
+        let result = IdStore::new();
+        let compact_type = <() as StableTypeId>::stable_type_id();
+        deserialize(
+            &result,
+            &mut BufReader::new(Cursor::new(bytes)),
+            &[compact_type],
+        );
+
+        result.get_from_ext::<()>("test-1");
+    }
+
     #[test]
     #[ignore]
     fn compare_compression() {
@@ -395,7 +746,7 @@ mod tests {
         let folder = create_folders(PathBuf::from(
             "./test_output/simulation/id/id_store/compare_compression/",
         ));
-        let mut store = IdStore::new();
+        let store = IdStore::new();
 
         let net = Network::from_file_path(
             &PathBuf::from("/Users/janek/Documents/rust_q_sim/input/rvr.network.xml.gz"),
@@ -445,16 +796,16 @@ mod tests {
 
         println!("Starting to read id store uncompressed");
         let start = Instant::now();
-        let mut result_uncompressed = IdStore::new();
-        deserialize_from_file(&mut result_uncompressed, &folder.join("ids.raw.pbf"));
+        let result_uncompressed = IdStore::new();
+        deserialize_from_file(&result_uncompressed, &folder.join("ids.raw.pbf"), &[]);
         let end = Instant::now();
         let duration = end.sub(start).as_millis();
         println!("reading uncompressed took: {duration}ms");
 
         println!("Starting to read id store compressed");
         let start = Instant::now();
-        let mut result_compressed = IdStore::new();
-        deserialize_from_file(&mut result_compressed, &folder.join("ids.lz4.pbf"));
+        let result_compressed = IdStore::new();
+        deserialize_from_file(&result_compressed, &folder.join("ids.lz4.pbf"), &[]);
         let end = Instant::now();
         let duration = end.sub(start).as_millis();
         println!("reading compressed took: {duration}ms");
@@ -4,12 +4,16 @@ use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::simulation::id::id_store::IdStore;
 use crate::simulation::id::id_store::UntypedId;
 use crate::simulation::id::serializable_type::StableTypeId;
+use crate::simulation::network::global_network::{Link, Node};
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
 
+pub mod id_map;
 // keep this private, as we don't want to leak how we cache ids.
 mod id_store;
 pub mod serializable_type;
@@ -17,8 +21,8 @@ pub mod serializable_type;
 /// This type represents a reference counted pointer to a matsim id. It can be used in hash maps/sets
 /// in combination with NoHashHasher, to achieve fast look ups with no randomness involved.
 ///
-/// As this type wraps Rc<IdImpl<T>>, using clone produces a new Rc pointer to the actual Id and is
-/// the intended way of passing around ids.
+/// As this type wraps Arc<IdImpl<T>>, using clone produces a new Arc pointer to the actual Id and is
+/// the intended way of passing around ids. Ids can be sent to other threads.
 ///
 /// This type uses the newtype pattern https://rust-unofficial.github.io/patterns/patterns/behavioural/newtype.html
 /// to hide internal representation and to enable implementing IsEnabled for using the NoHashHasher create
@@ -27,11 +31,11 @@ pub mod serializable_type;
 #[derive(Debug)]
 pub struct Id<T: StableTypeId> {
     _type_marker: PhantomData<T>,
-    id: Rc<UntypedId>,
+    id: Arc<UntypedId>,
 }
 
 impl<T: StableTypeId + 'static> Id<T> {
-    fn new(untyped_id: Rc<UntypedId>) -> Self {
+    fn new(untyped_id: Arc<UntypedId>) -> Self {
         Self {
             _type_marker: PhantomData,
             id: untyped_id,
@@ -43,7 +47,7 @@ impl<T: StableTypeId + 'static> Id<T> {
     #[cfg(test)]
     pub(crate) fn new_internal(internal: u64) -> Self {
         let untyped_id = UntypedId::new(internal, String::from(""));
-        Self::new(Rc::new(untyped_id))
+        Self::new(Arc::new(untyped_id))
     }
 
     pub fn internal(&self) -> u64 {
@@ -51,17 +55,23 @@ impl<T: StableTypeId + 'static> Id<T> {
     }
 
     pub fn external(&self) -> &str {
-        &self.id.external
+        self.id.external()
     }
 
     pub fn create(id: &str) -> Self {
-        ID_STORE.with(|store| store.borrow_mut().create_id(id))
+        ID_STORE.with(|store| store.borrow().create_id(id))
     }
 
     pub fn get(internal: u64) -> Self {
         ID_STORE.with(|store| store.borrow().get(internal))
     }
 
+    /// Like [Id::get], but returns None instead of panicking, if there is no id with this internal
+    /// id, e.g. while validating input which may refer to ids which don't exist.
+    pub fn try_get(internal: u64) -> Option<Self> {
+        ID_STORE.with(|store| store.borrow().try_get(internal))
+    }
+
     pub fn get_from_ext(external: &str) -> Self {
         ID_STORE.with(|store| store.borrow().get_from_ext(external))
     }
@@ -72,7 +82,39 @@ pub fn store_to_file(file_path: &Path) {
 }
 
 pub fn load_from_file(file_path: &Path) {
-    ID_STORE.with(|store| store.borrow_mut().load_from_file(file_path))
+    ID_STORE.with(|store| store.borrow().load_from_file(file_path))
+}
+
+/// Loads the id store without the external ids of persons, vehicles, links and nodes. Ids of these
+/// types can only be retrieved by their internal id and have an empty external id. All other types
+/// are loaded completely.
+pub fn load_from_file_compact(file_path: &Path) {
+    let compact_types = [
+        Person::stable_type_id(),
+        Vehicle::stable_type_id(),
+        Link::stable_type_id(),
+        Node::stable_type_id(),
+    ];
+    ID_STORE.with(|store| {
+        store
+            .borrow()
+            .load_from_file_compact(file_path, &compact_types)
+    })
+}
+
+/// Handle to the id store of the current thread, which can be passed to other threads.
+#[derive(Clone, Debug)]
+pub struct SharedIdStore(Arc<IdStore>);
+
+/// Returns a handle to the id store used by the current thread.
+pub fn shared_store() -> SharedIdStore {
+    ID_STORE.with(|store| SharedIdStore(store.borrow().clone()))
+}
+
+/// Makes the current thread use the given id store. This way, several threads can work with the
+/// same ids, without loading a copy of the store in every thread.
+pub fn use_shared_store(shared: SharedIdStore) {
+    ID_STORE.with(|store| *store.borrow_mut() = shared.0)
 }
 
 /// Mark Id as enabled for the nohash_hasher::NoHashHasher t
@@ -114,7 +156,7 @@ impl<T: StableTypeId + 'static> Display for Id<T> {
     }
 }
 
-/// This creates a new struct with a cloned Rc pointer
+/// This creates a new struct with a cloned Arc pointer
 impl<T: StableTypeId> Clone for Id<T> {
     fn clone(&self) -> Self {
         Self {
@@ -124,26 +166,28 @@ impl<T: StableTypeId> Clone for Id<T> {
     }
 }
 
-thread_local! {static ID_STORE: RefCell<IdStore<'static>> = RefCell::new(IdStore::new())}
+// Every thread starts with its own store. Threads can share a store via use_shared_store.
+thread_local! {static ID_STORE: RefCell<Arc<IdStore>> = RefCell::new(Arc::new(IdStore::new()))}
 
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
 
-    use crate::simulation::id::{Id, UntypedId};
+    use crate::simulation::id::{shared_store, use_shared_store, Id, UntypedId};
 
     #[test]
     fn test_id_eq() {
-        let id: Id<()> = Id::new(Rc::new(UntypedId::new(1, String::from("external-id"))));
+        let id: Id<()> = Id::new(Arc::new(UntypedId::new(1, String::from("external-id"))));
         assert_eq!(id, id.clone());
 
-        let equal = Id::new(Rc::new(UntypedId::new(
+        let equal = Id::new(Arc::new(UntypedId::new(
             1,
             String::from("other-external-value-which-should-be-ignored"),
         )));
         assert_eq!(id, equal);
 
-        let unequal = Id::new(Rc::new(UntypedId::new(2, String::from("external-id"))));
+        let unequal = Id::new(Arc::new(UntypedId::new(2, String::from("external-id"))));
         assert_ne!(id, unequal)
     }
 
@@ -192,6 +236,16 @@ mod tests {
         assert_eq!(fetched_2.external(), external_2);
     }
 
+    #[test]
+    fn try_get_id() {
+        let id: Id<()> = Id::create("id-1");
+
+        assert_eq!(Some(id.clone()), Id::try_get(id.internal()));
+        assert_eq!(None, Id::<()>::try_get(id.internal() + 1));
+        // no ids of this type were created at all
+        assert_eq!(None, Id::<i64>::try_get(0));
+    }
+
     #[test]
     fn id_store_get_ext() {
         let external_1 = String::from("id-1");
@@ -204,4 +258,21 @@ mod tests {
         assert_eq!(fetched_1.external(), external_1);
         assert_eq!(fetched_2.external(), external_2);
     }
+
+    #[test]
+    fn share_store_between_threads() {
+        let id: Id<()> = Id::create("shared-id");
+        let shared = shared_store();
+
+        let handle = thread::spawn(move || {
+            use_shared_store(shared);
+            let fetched: Id<()> = Id::get_from_ext("shared-id");
+            let created: Id<()> = Id::create("created-in-other-thread");
+            (fetched, created)
+        });
+        let (fetched, created) = handle.join().unwrap();
+
+        assert_eq!(id, fetched);
+        assert_eq!(created, Id::<()>::get_from_ext("created-in-other-thread"));
+    }
 }
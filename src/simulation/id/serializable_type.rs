@@ -1,7 +1,7 @@
 use crate::simulation::network::global_network::{Link, Node};
 use crate::simulation::wire_types::messages::Vehicle;
 use crate::simulation::wire_types::population::Person;
-use crate::simulation::wire_types::vehicles::VehicleType;
+use crate::simulation::wire_types::vehicles::{Household, VehicleType};
 
 pub trait StableTypeId {
     fn stable_type_id() -> u64;
@@ -43,6 +43,12 @@ impl StableTypeId for Vehicle {
     }
 }
 
+impl StableTypeId for Household {
+    fn stable_type_id() -> u64 {
+        HOUSEHOLD_TYPE_ID
+    }
+}
+
 impl StableTypeId for () {
     fn stable_type_id() -> u64 {
         0
@@ -83,3 +89,4 @@ pub const I32_TYPE_ID: u64 = 7;
 pub const I64_TYPE_ID: u64 = 8;
 pub const U32_TYPE_ID: u64 = 9;
 pub const F32_TYPE_ID: u64 = 10;
+pub const HOUSEHOLD_TYPE_ID: u64 = 11;
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -38,6 +40,13 @@ impl Attrs {
         }
     }
 
+    pub fn find(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|&attr| attr.name.eq(name))
+            .map(|attr| attr.value.as_str())
+    }
+
     pub fn find_or_else_opt<'a, F>(attrs_opt: &'a Option<Attrs>, name: &str, f: F) -> &'a str
     where
         F: FnOnce() -> &'a str,
@@ -48,4 +57,29 @@ impl Attrs {
             f()
         }
     }
+
+    /// All attributes except the `known` ones, which are interpreted and stored separately. This
+    /// way, attributes which are only meaningful to other tools can be written back to output files.
+    pub fn unknown_to_map(attrs_opt: &Option<Attrs>, known: &[&str]) -> HashMap<String, String> {
+        attrs_opt
+            .iter()
+            .flat_map(|attrs| attrs.attributes.iter())
+            .filter(|attr| !known.contains(&attr.name.as_str()))
+            .map(|attr| (attr.name.clone(), attr.value.clone()))
+            .collect()
+    }
+
+    /// Adds the entries of the map as attributes, sorted by their names. The class of the values is
+    /// not stored in the map, so they are written as strings.
+    pub fn extend_from_map(&mut self, map: &HashMap<String, String>) {
+        let mut names: Vec<&String> = map.keys().collect();
+        names.sort();
+        for name in names {
+            self.attributes.push(Attr {
+                name: name.clone(),
+                class: String::from("java.lang.String"),
+                value: map.get(name).unwrap().clone(),
+            });
+        }
+    }
 }
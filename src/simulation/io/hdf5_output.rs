@@ -0,0 +1,374 @@
+//! Output of events and snapshots into one hdf5 file per partition. Large outputs stay manageable,
+//! because all datasets are chunked and compressed, and they can be read column by column, e.g.
+//! with h5py. The file has the following layout:
+//!
+//! ```text
+//! /events/<event type>/time         u32
+//! /events/<event type>/<attribute>  u64 for ids, f64 for distances, strings for generic events
+//! /snapshots/<time>/vehicle         u64
+//! /snapshots/<time>/link            u64
+//! ```
+//!
+//! Ids are written as internal ids. They can be mapped to external ids with the ids file of the
+//! scenario. Events of a type are rows of the datasets of its group, which all have the same length.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::Path;
+
+use hdf5::types::VarLenUnicode;
+use hdf5::{Dataset, File, Group, H5Type};
+
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::mobsim_listener::{MobsimListener, SimState};
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+
+/// Number of rows per chunk. Rows are buffered and appended to the datasets one chunk at a time.
+const CHUNK_SIZE: usize = 1 << 16;
+const COMPRESSION_LEVEL: u8 = 4;
+
+/// Creates the output file of a partition. The file handle can be cloned, so that the events
+/// writer and the snapshot writer share one file.
+pub fn create_file(path: &Path) -> File {
+    File::create(path).unwrap_or_else(|e| panic!("Could not create hdf5 file {path:?}: {e}"))
+}
+
+fn create_group(parent: &Group, name: &str) -> Group {
+    parent
+        .create_group(name)
+        .unwrap_or_else(|e| panic!("Could not create hdf5 group {name}: {e}"))
+}
+
+/// Writes the events of a partition into a group per event type.
+pub struct Hdf5EventsWriter {
+    events: Group,
+    tables: HashMap<&'static str, Table>,
+}
+
+impl Hdf5EventsWriter {
+    pub fn new(file: &File) -> Self {
+        Hdf5EventsWriter {
+            events: create_group(file, "events"),
+            tables: HashMap::new(),
+        }
+    }
+}
+
+impl EventsSubscriber for Hdf5EventsWriter {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        let (event_type, row) = row(event);
+        let events = &self.events;
+        let table = self
+            .tables
+            .entry(event_type)
+            .or_insert_with(|| Table::new(&create_group(events, event_type), &row));
+        table.push(time, row);
+    }
+
+    fn finish(&mut self) {
+        for table in self.tables.values_mut() {
+            table.flush();
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Writes the links of all vehicles on the network of a partition every `interval` seconds.
+pub struct Hdf5SnapshotWriter {
+    snapshots: Group,
+    interval: u32,
+}
+
+impl Hdf5SnapshotWriter {
+    pub fn new(file: &File, interval: u32) -> Self {
+        Hdf5SnapshotWriter {
+            snapshots: create_group(file, "snapshots"),
+            interval,
+        }
+    }
+}
+
+impl MobsimListener for Hdf5SnapshotWriter {
+    fn after_step(&mut self, state: &SimState) {
+        if state.time % self.interval != 0 {
+            return;
+        }
+        let (vehicles, links): (Vec<u64>, Vec<u64>) = state
+            .network
+            .vehicles()
+            .map(|vehicle| (vehicle.id, vehicle.curr_link_id().unwrap()))
+            .unzip();
+        let snapshot = create_group(&self.snapshots, &state.time.to_string());
+        write_dataset(&snapshot, "vehicle", &vehicles);
+        write_dataset(&snapshot, "link", &links);
+    }
+}
+
+fn write_dataset<T: H5Type>(group: &Group, name: &str, values: &[T]) {
+    group
+        .new_dataset_builder()
+        .chunk(values.len().clamp(1, CHUNK_SIZE))
+        .deflate(COMPRESSION_LEVEL)
+        .with_data(values)
+        .create(name)
+        .unwrap_or_else(|e| panic!("Could not write hdf5 dataset {name}: {e}"));
+}
+
+/// Value of an attribute of an event.
+enum Value {
+    Id(u64),
+    Number(f64),
+    Text(String),
+}
+
+/// Event type and attributes of an event. Events of the same type always have the same attributes.
+fn row(event: &Event) -> (&'static str, Vec<(&'static str, Value)>) {
+    use Value::{Id, Number, Text};
+    match event.r#type.as_ref().unwrap() {
+        Type::Generic(e) => (
+            "generic",
+            vec![
+                ("type", Text(e.r#type.clone())),
+                ("attrs", Text(serde_json::to_string(&e.attrs).unwrap())),
+            ],
+        ),
+        Type::ActStart(e) => (
+            "actStart",
+            vec![
+                ("person", Id(e.person)),
+                ("link", Id(e.link)),
+                ("actType", Id(e.act_type)),
+            ],
+        ),
+        Type::ActEnd(e) => (
+            "actEnd",
+            vec![
+                ("person", Id(e.person)),
+                ("link", Id(e.link)),
+                ("actType", Id(e.act_type)),
+            ],
+        ),
+        Type::LinkEnter(e) => (
+            "linkEnter",
+            vec![("link", Id(e.link)), ("vehicle", Id(e.vehicle))],
+        ),
+        Type::LinkLeave(e) => (
+            "linkLeave",
+            vec![("link", Id(e.link)), ("vehicle", Id(e.vehicle))],
+        ),
+        Type::PersonEntersVeh(e) => (
+            "personEntersVeh",
+            vec![("person", Id(e.person)), ("vehicle", Id(e.vehicle))],
+        ),
+        Type::PersonLeavesVeh(e) => (
+            "personLeavesVeh",
+            vec![("person", Id(e.person)), ("vehicle", Id(e.vehicle))],
+        ),
+        Type::Departure(e) => (
+            "departure",
+            vec![
+                ("person", Id(e.person)),
+                ("link", Id(e.link)),
+                ("legMode", Id(e.leg_mode)),
+            ],
+        ),
+        Type::Arrival(e) => (
+            "arrival",
+            vec![
+                ("person", Id(e.person)),
+                ("link", Id(e.link)),
+                ("legMode", Id(e.leg_mode)),
+                ("distance", Number(e.distance)),
+            ],
+        ),
+        Type::Travelled(e) => (
+            "travelled",
+            vec![
+                ("person", Id(e.person)),
+                ("mode", Id(e.mode)),
+                ("distance", Number(e.distance)),
+            ],
+        ),
+    }
+}
+
+/// Buffered values of a column. They are appended to the dataset of the column once a chunk is
+/// full.
+enum Values {
+    Time(Vec<u32>),
+    Ids(Vec<u64>),
+    Numbers(Vec<f64>),
+    Texts(Vec<VarLenUnicode>),
+}
+
+impl Values {
+    fn push(&mut self, value: Value) {
+        match (self, value) {
+            (Values::Ids(values), Value::Id(value)) => values.push(value),
+            (Values::Numbers(values), Value::Number(value)) => values.push(value),
+            (Values::Texts(values), Value::Text(value)) => values.push(
+                value
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Could not write {value} to hdf5: {e}")),
+            ),
+            _ => panic!("Events of the same type must have the same attributes."),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Values::Time(values) => values.clear(),
+            Values::Ids(values) => values.clear(),
+            Values::Numbers(values) => values.clear(),
+            Values::Texts(values) => values.clear(),
+        }
+    }
+}
+
+struct Column {
+    dataset: Dataset,
+    values: Values,
+}
+
+impl Column {
+    fn new(group: &Group, name: &str, values: Values) -> Self {
+        let dataset = match values {
+            Values::Time(_) => Self::create_dataset::<u32>(group, name),
+            Values::Ids(_) => Self::create_dataset::<u64>(group, name),
+            Values::Numbers(_) => Self::create_dataset::<f64>(group, name),
+            Values::Texts(_) => Self::create_dataset::<VarLenUnicode>(group, name),
+        };
+        Column { dataset, values }
+    }
+
+    /// Creates an empty dataset, which grows as rows are appended.
+    fn create_dataset<T: H5Type>(group: &Group, name: &str) -> Dataset {
+        group
+            .new_dataset::<T>()
+            .chunk(CHUNK_SIZE)
+            .deflate(COMPRESSION_LEVEL)
+            .shape(0..)
+            .create(name)
+            .unwrap_or_else(|e| panic!("Could not create hdf5 dataset {name}: {e}"))
+    }
+
+    /// Appends the buffered values to the dataset, which has `written` rows so far.
+    fn append(&mut self, written: usize, buffered: usize) {
+        let rows = written..written + buffered;
+        self.dataset
+            .resize(rows.end)
+            .unwrap_or_else(|e| panic!("Could not resize hdf5 dataset: {e}"));
+        let result = match &self.values {
+            Values::Time(values) => self.dataset.write_slice(values, rows),
+            Values::Ids(values) => self.dataset.write_slice(values, rows),
+            Values::Numbers(values) => self.dataset.write_slice(values, rows),
+            Values::Texts(values) => self.dataset.write_slice(values, rows),
+        };
+        result.unwrap_or_else(|e| panic!("Could not write to hdf5 dataset: {e}"));
+        self.values.clear();
+    }
+}
+
+/// Columns of the events of one type. The first column holds the times of the events.
+struct Table {
+    columns: Vec<Column>,
+    written: usize,
+    buffered: usize,
+}
+
+impl Table {
+    fn new(group: &Group, row: &[(&'static str, Value)]) -> Self {
+        let mut columns = vec![Column::new(group, "time", Values::Time(Vec::new()))];
+        columns.extend(row.iter().map(|(name, value)| {
+            let values = match value {
+                Value::Id(_) => Values::Ids(Vec::new()),
+                Value::Number(_) => Values::Numbers(Vec::new()),
+                Value::Text(_) => Values::Texts(Vec::new()),
+            };
+            Column::new(group, name, values)
+        }));
+        Table {
+            columns,
+            written: 0,
+            buffered: 0,
+        }
+    }
+
+    fn push(&mut self, time: u32, row: Vec<(&'static str, Value)>) {
+        match &mut self.columns[0].values {
+            Values::Time(times) => times.push(time),
+            _ => unreachable!("The first column holds the times of the events."),
+        }
+        for (column, (_, value)) in self.columns[1..].iter_mut().zip(row) {
+            column.values.push(value);
+        }
+        self.buffered += 1;
+        if self.buffered == CHUNK_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.buffered == 0 {
+            return;
+        }
+        for column in &mut self.columns {
+            column.append(self.written, self.buffered);
+        }
+        self.written += self.buffered;
+        self.buffered = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::simulation::io::hdf5_output::{create_file, Hdf5EventsWriter, CHUNK_SIZE};
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::wire_types::events::Event;
+
+    #[test]
+    fn write_events_by_type() {
+        let path = PathBuf::from("./test_output/io/hdf5_output/write_events_by_type/output.h5");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = create_file(&path);
+        let mut writer = Hdf5EventsWriter::new(&file);
+        // more events than fit into one chunk
+        let num_link_enters = CHUNK_SIZE + 10;
+        for i in 0..num_link_enters {
+            writer.receive_event(i as u32, &Event::new_link_enter(i as u64, 42));
+        }
+        writer.receive_event(7, &Event::new_arrival(1, 2, 3, 12.5));
+        let attrs = HashMap::from([(String::from("key"), String::from("value"))]);
+        writer.receive_event(8, &Event::new_generic("custom", attrs));
+        writer.finish();
+
+        let links: Vec<u64> = file
+            .dataset("events/linkEnter/link")
+            .unwrap()
+            .read_raw()
+            .unwrap();
+        assert_eq!(num_link_enters, links.len());
+        assert_eq!(CHUNK_SIZE as u64 + 1, links[CHUNK_SIZE + 1]);
+        let times: Vec<u32> = file
+            .dataset("events/arrival/time")
+            .unwrap()
+            .read_raw()
+            .unwrap();
+        assert_eq!(vec![7], times);
+        let distances: Vec<f64> = file
+            .dataset("events/arrival/distance")
+            .unwrap()
+            .read_raw()
+            .unwrap();
+        assert_eq!(vec![12.5], distances);
+        assert!(file.dataset("events/generic/attrs").is_ok());
+    }
+}
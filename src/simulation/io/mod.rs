@@ -1,8 +1,12 @@
 pub mod attributes;
+#[cfg(feature = "hdf5")]
+pub mod hdf5_output;
 pub mod matsim_id;
 pub mod non_blocking_io;
+pub mod output_files;
 pub mod proto;
 pub mod proto_events;
 mod worker;
 pub mod xml;
 pub mod xml_events;
+pub mod xml_validation;
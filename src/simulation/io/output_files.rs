@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+/// Path of the copy of an input file in the output directory, which is numbered with the number of
+/// partitions. E.g. the network `input/network.binpb` of a run with 4 partitions is written to
+/// `output/network.4.binpb`.
+pub fn get_numbered_output_filename(output_dir: &Path, input_file: &Path, part: u32) -> PathBuf {
+    let out = create_output_filename(output_dir, input_file);
+    insert_number_in_proto_filename(&out, part)
+}
+
+/// Output directory of an iteration, if the controller runs several iterations. E.g. the output
+/// of iteration 3 is written to `output/ITERS/it.3`, the way MATSim structures its output.
+pub fn iteration_output_dir(output_dir: &Path, iteration: u32) -> PathBuf {
+    output_dir.join("ITERS").join(format!("it.{iteration}"))
+}
+
+fn create_output_filename(output_dir: &Path, input_file: &Path) -> PathBuf {
+    let filename = input_file.file_name().unwrap();
+    output_dir.join(filename)
+}
+
+fn insert_number_in_proto_filename(path: &Path, part: u32) -> PathBuf {
+    let filename = path.file_name().unwrap().to_str().unwrap();
+    let mut stripped = filename.strip_suffix(".binpb").unwrap();
+    if let Some(s) = stripped.strip_suffix(format!(".{part}").as_str()) {
+        stripped = s;
+    }
+    let new_filename = format!("{stripped}.{part}.binpb");
+    path.parent().unwrap().join(new_filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::simulation::io::output_files::{get_numbered_output_filename, iteration_output_dir};
+
+    #[test]
+    fn numbered_output_filename() {
+        let output_dir = PathBuf::from("output");
+        assert_eq!(
+            PathBuf::from("output/network.4.binpb"),
+            get_numbered_output_filename(&output_dir, &PathBuf::from("in/network.binpb"), 4)
+        );
+        // files which are numbered already are not numbered twice.
+        assert_eq!(
+            PathBuf::from("output/network.4.binpb"),
+            get_numbered_output_filename(&output_dir, &PathBuf::from("output/network.4.binpb"), 4)
+        );
+    }
+
+    #[test]
+    fn iteration_output_directory() {
+        assert_eq!(
+            PathBuf::from("output/ITERS/it.3"),
+            iteration_output_dir(&PathBuf::from("output"), 3)
+        );
+    }
+}
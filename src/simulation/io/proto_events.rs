@@ -8,9 +8,12 @@ use prost::Message;
 use crate::simulation::messaging::events::EventsSubscriber;
 use crate::simulation::wire_types::events::{Event, TimeStep};
 
+/// Writes the events of a partition. Events are numbered within their time step in the order they
+/// were published, so that tools can order the events of all partitions deterministically.
 pub struct ProtoEventsWriter {
     encoded_events: Vec<u8>,
     curr_time_step: u32,
+    sequence: u32,
     writer: BufWriter<File>,
 }
 
@@ -20,6 +23,7 @@ impl ProtoEventsWriter {
         let writer = BufWriter::new(file);
         ProtoEventsWriter {
             curr_time_step: 0,
+            sequence: 0,
             encoded_events: Vec::new(),
             writer,
         }
@@ -31,6 +35,7 @@ impl ProtoEventsWriter {
                 self.write_time_step();
             }
             self.curr_time_step = time;
+            self.sequence = 0;
         }
     }
 
@@ -54,6 +59,11 @@ impl EventsSubscriber for ProtoEventsWriter {
     fn receive_event(&mut self, time: u32, event: &Event) {
         self.update_time_step(time);
 
+        let event = Event {
+            sequence: self.sequence,
+            ..event.clone()
+        };
+        self.sequence += 1;
         event
             .encode_length_delimited(&mut self.encoded_events)
             .expect("Error encoding event.");
@@ -263,6 +273,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn number_events_per_time_step() {
+        let path = create_path_with_prefix(
+            "./test_output/io/proto_events/number_events_per_time_step/events.pbf",
+        );
+        let mut writer = ProtoEventsWriter::new(&path);
+        writer.receive_event(1, &Event::new_act_end(1, 1, 1));
+        writer.receive_event(1, &Event::new_act_end(2, 1, 1));
+        writer.receive_event(2, &Event::new_act_start(1, 1, 1));
+        writer.finish();
+
+        let sequences: Vec<_> = EventsReader::from_file(&path)
+            .map(|(time, events)| (time, events.iter().map(|e| e.sequence).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(vec![(1, vec![0, 1]), (2, vec![0])], sequences);
+    }
+
     fn create_path_with_prefix(path: &str) -> PathBuf {
         // create path and corresponding directories
         let path_buf = PathBuf::from(path);
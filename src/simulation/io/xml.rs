@@ -4,10 +4,14 @@ use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 
 use flate2::Compression;
+use quick_xml::de::DeError;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_path_to_error::Segment;
 use tracing::info;
 
+use crate::simulation::io::xml_validation;
+
 pub fn read_from_file<T>(file_path: &str) -> T
 where
     T: DeserializeOwned,
@@ -26,17 +30,13 @@ where
 
         match serde_path_to_error::deserialize(&mut deserializer) {
             Ok(parsed) => parsed,
-            Err(_err) => {
-                panic!("{_err:#?}");
-            }
+            Err(e) => panic!("{}", parse_error(file_path, &e)),
         }
     } else if file_path.ends_with(".xml") {
         let mut deserializer = quick_xml::de::Deserializer::from_reader(buffered_reader);
         match serde_path_to_error::deserialize(&mut deserializer) {
             Ok(x) => x,
-            Err(_e) => {
-                panic!("Problem reading file: {_e:?}")
-            }
+            Err(e) => panic!("{}", parse_error(file_path, &e)),
         }
     } else {
         panic!(
@@ -46,6 +46,59 @@ where
     }
 }
 
+/// Describes a deserialization error with the line, element path and id of the element, where it
+/// occurred. The element is found by reading the file a second time.
+fn parse_error(file_path: &str, error: &serde_path_to_error::Error<DeError>) -> String {
+    parse_error_within(file_path, error, Vec::new())
+}
+
+/// Like [parse_error], for an error which occurred while deserializing a single element of the file
+/// on its own, e.g. a person of a plans file. The path of the error is relative to this `element`,
+/// which is given as path below the root element.
+pub(crate) fn parse_error_within(
+    file_path: &str,
+    error: &serde_path_to_error::Error<DeError>,
+    mut element: Vec<(String, usize)>,
+) -> String {
+    let mut message = format!(
+        "Problem reading file {file_path}: {} at {}",
+        error.inner(),
+        error.path()
+    );
+    element.extend(elements(error.path()));
+    if let Some(location) = xml_validation::locate(Path::new(file_path), &element) {
+        message.push_str(&format!(" ({location})"));
+    }
+    message
+}
+
+/// Converts a serde path into child elements and their index among the children with the same
+/// name. Single elements have no index in the serde path, sequences of elements have one.
+/// Attributes look like child elements in the path. They are not found when locating the element,
+/// so that the element with the attribute is located instead.
+fn elements(path: &serde_path_to_error::Path) -> Vec<(String, usize)> {
+    let mut elements = Vec::new();
+    let mut pending: Option<String> = None;
+    for segment in path.iter() {
+        match segment {
+            Segment::Map { key } => {
+                if let Some(name) = pending.take() {
+                    elements.push((name, 0));
+                }
+                pending = Some(key.clone());
+            }
+            Segment::Seq { index } => {
+                if let Some(name) = pending.take() {
+                    elements.push((name, *index));
+                }
+            }
+            Segment::Enum { .. } | Segment::Unknown => {}
+        }
+    }
+    elements.extend(pending.map(|name| (name, 0)));
+    elements
+}
+
 pub fn write_to_file<T: Serialize>(serde_message: &T, path: &Path, dtd_spec: &str) {
     // Create the file and all necessary directories
     // this doesn't cover some edge cases, but this will do for now
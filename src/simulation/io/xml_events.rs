@@ -1,10 +1,11 @@
 use std::any::Any;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
-use tracing::info;
-use xml::attribute::OwnedAttribute;
+use flate2::read::MultiGzDecoder;
+use tracing::{info, warn};
 use xml::reader::XmlEvent;
 use xml::EventReader;
 
@@ -120,8 +121,15 @@ impl EventsSubscriber for XmlEventsWriter {
     }
 }
 
+/// Reads events files in the matsim format, either as written by [XmlEventsWriter] or by the java
+/// implementation of matsim. Files ending on `.gz` are decompressed on the fly.
+///
+/// Attributes are looked up by name, so additional attributes of java events, such as the
+/// `computationalRoutingMode` of departures, are ignored. Event types, which have no counterpart
+/// in [Type] are read as [Type::Generic] events with all their attributes.
 pub struct XmlEventsReader {
-    parser: EventReader<BufReader<File>>,
+    parser: EventReader<Box<dyn Read>>,
+    next_event: Option<(u32, Event)>,
 }
 
 impl XmlEventsReader {
@@ -129,10 +137,22 @@ impl XmlEventsReader {
         let file = File::open(events_file)
             .unwrap_or_else(|_| panic!("Could not open events file: {:?}", events_file));
         let buffered_reader = BufReader::new(file);
-        let parser = EventReader::new(buffered_reader);
-        Self { parser }
+        let reader: Box<dyn Read> = if events_file.extension().is_some_and(|e| e.eq("gz")) {
+            Box::new(BufReader::new(MultiGzDecoder::new(buffered_reader)))
+        } else {
+            Box::new(buffered_reader)
+        };
+        let parser = EventReader::new(reader);
+        Self {
+            parser,
+            next_event: None,
+        }
     }
+
     pub fn read_next(&mut self) -> Option<(u32, Event)> {
+        if let Some(event) = self.next_event.take() {
+            return Some(event);
+        }
         loop {
             let result = self.parser.next();
             match result {
@@ -140,13 +160,20 @@ impl XmlEventsReader {
                     name, attributes, ..
                 }) => {
                     if name.local_name.eq("event") {
-                        let time: u32 = attributes.get(0).unwrap().value.parse().unwrap();
-                        let event = handle(attributes);
+                        let attrs: HashMap<String, String> = attributes
+                            .into_iter()
+                            .map(|a| (a.name.local_name, a.value))
+                            .collect();
+                        let time = parse_time(&attrs);
+                        let event = handle(attrs);
                         return Some((time, event));
                     }
                 }
                 Ok(XmlEvent::EndDocument) => return None,
-                Err(_) => return None,
+                Err(e) => {
+                    warn!("Stopped reading events file, because of an error: {e}");
+                    return None;
+                }
                 _ => {
                     continue;
                 }
@@ -155,77 +182,231 @@ impl XmlEventsReader {
     }
 }
 
-fn handle(attr: Vec<OwnedAttribute>) -> Event {
-    let ev_type = &attr.get(1).unwrap().value;
-    match ev_type.as_str() {
-        "actend" => handle_act_end(attr),
-        "departure" => handle_departure(attr),
-        "travelled" => travelled(attr),
-        "arrival" => handle_arrival(attr),
-        "actstart" => handle_act_start(attr),
-        "PersonEntersVehicle" => handle_person_enters_veh(attr),
-        "PersonLeavesVehicle" => handle_person_leaves_veh(attr),
-        "entered link" => handle_link_enter(attr),
-        "left link" => handle_link_leave(attr),
-        _ => panic!("Unknown event type {ev_type}"),
+/// Yields all events of a time step at once, like [crate::simulation::io::proto_events::EventsReader].
+impl Iterator for XmlEventsReader {
+    type Item = (u32, Vec<Event>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (time, event) = self.read_next()?;
+        let mut events = vec![event];
+        while let Some((next_time, next_event)) = self.read_next() {
+            if next_time != time {
+                self.next_event = Some((next_time, next_event));
+                break;
+            }
+            events.push(next_event);
+        }
+        Some((time, events))
     }
 }
 
-fn handle_act_end(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let link: Id<Link> = Id::create(&attr.get(3).unwrap().value);
-    let act_type: Id<String> = Id::create(&attr.get(4).unwrap().value);
+fn parse_time(attrs: &HashMap<String, String>) -> u32 {
+    // java matsim writes times as floating point numbers, e.g. 21600.0
+    let time = get(attrs, "time");
+    time.parse::<u32>()
+        .or_else(|_| time.parse::<f64>().map(|t| t as u32))
+        .unwrap_or_else(|_| panic!("Could not parse event time {time}"))
+}
+
+fn get<'a>(attrs: &'a HashMap<String, String>, name: &str) -> &'a str {
+    attrs
+        .get(name)
+        .unwrap_or_else(|| panic!("Event is missing attribute '{name}': {attrs:?}"))
+}
+
+fn handle(mut attrs: HashMap<String, String>) -> Event {
+    let ev_type = get(&attrs, "type");
+    match ev_type {
+        "actend" => handle_act_end(&attrs),
+        "departure" => handle_departure(&attrs),
+        "travelled" => travelled(&attrs),
+        "arrival" => handle_arrival(&attrs),
+        "actstart" => handle_act_start(&attrs),
+        "PersonEntersVehicle" => handle_person_enters_veh(&attrs),
+        "PersonLeavesVehicle" => handle_person_leaves_veh(&attrs),
+        "entered link" => handle_link_enter(&attrs),
+        "left link" => handle_link_leave(&attrs),
+        _ => {
+            let ev_type = attrs.remove("type").unwrap();
+            attrs.remove("time");
+            Event::new_generic(&ev_type, attrs)
+        }
+    }
+}
+
+fn handle_act_end(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let act_type: Id<String> = Id::create(get(attrs, "actType"));
     Event::new_act_end(person.internal(), link.internal(), act_type.internal())
 }
 
-fn handle_act_start(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let link: Id<Link> = Id::create(&attr.get(3).unwrap().value);
-    let act_type: Id<String> = Id::create(&attr.get(4).unwrap().value);
+fn handle_act_start(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let act_type: Id<String> = Id::create(get(attrs, "actType"));
     Event::new_act_start(person.internal(), link.internal(), act_type.internal())
 }
 
-fn handle_departure(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let link: Id<Link> = Id::create(&attr.get(3).unwrap().value);
-    let mode: Id<String> = Id::create(&attr.get(4).unwrap().value);
+fn handle_departure(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let mode: Id<String> = Id::create(get(attrs, "legMode"));
     Event::new_departure(person.internal(), link.internal(), mode.internal())
 }
 
-fn handle_arrival(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let link: Id<Link> = Id::create(&attr.get(3).unwrap().value);
-    let mode: Id<String> = Id::create(&attr.get(4).unwrap().value);
-    Event::new_arrival(person.internal(), link.internal(), mode.internal())
+fn handle_arrival(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let mode: Id<String> = Id::create(get(attrs, "legMode"));
+    // matsim doesn't write the distance of legs into arrival events.
+    let distance = attrs
+        .get("distance")
+        .map(|d| d.parse().unwrap())
+        .unwrap_or(0.);
+    Event::new_arrival(
+        person.internal(),
+        link.internal(),
+        mode.internal(),
+        distance,
+    )
 }
 
-fn travelled(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let dist: f64 = attr.get(3).unwrap().value.parse().unwrap();
-    let mode: Id<String> = Id::create(&attr.get(4).unwrap().value);
+fn travelled(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let dist: f64 = get(attrs, "distance").parse().unwrap();
+    let mode: Id<String> = Id::create(get(attrs, "mode"));
     Event::new_travelled(person.internal(), dist, mode.internal())
 }
 
-fn handle_person_enters_veh(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let vehicle: Id<Vehicle> = Id::create(&attr.get(3).unwrap().value);
+fn handle_person_enters_veh(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let vehicle: Id<Vehicle> = Id::create(get(attrs, "vehicle"));
     Event::new_person_enters_veh(person.internal(), vehicle.internal())
 }
 
-fn handle_person_leaves_veh(attr: Vec<OwnedAttribute>) -> Event {
-    let person: Id<Person> = Id::create(&attr.get(2).unwrap().value);
-    let vehicle: Id<Vehicle> = Id::create(&attr.get(3).unwrap().value);
+fn handle_person_leaves_veh(attrs: &HashMap<String, String>) -> Event {
+    let person: Id<Person> = Id::create(get(attrs, "person"));
+    let vehicle: Id<Vehicle> = Id::create(get(attrs, "vehicle"));
     Event::new_person_leaves_veh(person.internal(), vehicle.internal())
 }
 
-fn handle_link_enter(attr: Vec<OwnedAttribute>) -> Event {
-    let link: Id<Link> = Id::create(&attr.get(2).unwrap().value);
-    let vehicle: Id<Vehicle> = Id::create(&attr.get(3).unwrap().value);
+fn handle_link_enter(attrs: &HashMap<String, String>) -> Event {
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let vehicle: Id<Vehicle> = Id::create(get(attrs, "vehicle"));
     Event::new_link_enter(link.internal(), vehicle.internal())
 }
 
-fn handle_link_leave(attr: Vec<OwnedAttribute>) -> Event {
-    let link: Id<Link> = Id::create(&attr.get(2).unwrap().value);
-    let vehicle: Id<Vehicle> = Id::create(&attr.get(3).unwrap().value);
+fn handle_link_leave(attrs: &HashMap<String, String>) -> Event {
+    let link: Id<Link> = Id::create(get(attrs, "link"));
+    let vehicle: Id<Vehicle> = Id::create(get(attrs, "vehicle"));
     Event::new_link_leave(link.internal(), vehicle.internal())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use crate::simulation::id::Id;
+    use crate::simulation::io::xml_events::{XmlEventsReader, XmlEventsWriter};
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::wire_types::events::event::Type;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::population::Person;
+
+    const JAVA_EVENTS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<events version="1.0">
+	<event time="21600.0" type="actend" person="xe-1" link="xe-l1" actType="h"  />
+	<event time="21600.0" type="departure" person="xe-1" link="xe-l1" legMode="car" computationalRoutingMode="car"  />
+	<event time="21600.0" type="vehicle enters traffic" person="xe-1" link="xe-l1" vehicle="xe-1" networkMode="car" relativePosition="1.0"  />
+	<event time="21601.0" type="left link" vehicle="xe-1" link="xe-l1"  />
+	<event time="21601.0" type="entered link" vehicle="xe-1" link="xe-l2"  />
+	<event time="22000.0" type="actstart" person="xe-1" link="xe-l2" x="10000.0" y="0.0" actType="w"  />
+</events>"#;
+
+    fn write_file(path: &str, gzip: bool) -> PathBuf {
+        let path = PathBuf::from(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(&path).unwrap();
+        if gzip {
+            let mut encoder = GzEncoder::new(file, Compression::fast());
+            encoder.write_all(JAVA_EVENTS.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+        } else {
+            let mut file = file;
+            file.write_all(JAVA_EVENTS.as_bytes()).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn read_java_events() {
+        for (path, gzip) in [
+            (
+                "./test_output/io/xml_events/read_java_events/events.xml",
+                false,
+            ),
+            (
+                "./test_output/io/xml_events/read_java_events/events.xml.gz",
+                true,
+            ),
+        ] {
+            let reader = XmlEventsReader::new(&write_file(path, gzip));
+            let time_steps: Vec<(u32, Vec<Event>)> = reader.collect();
+
+            let times: Vec<u32> = time_steps.iter().map(|(time, _)| *time).collect();
+            assert_eq!(vec![21600, 21601, 22000], times);
+            assert_eq!(3, time_steps[0].1.len());
+            assert_eq!(2, time_steps[1].1.len());
+
+            match time_steps[0].1[2].r#type.as_ref().unwrap() {
+                Type::Generic(e) => {
+                    assert_eq!("vehicle enters traffic", e.r#type);
+                    assert_eq!(Some(&String::from("car")), e.attrs.get("networkMode"));
+                    assert!(!e.attrs.contains_key("time"));
+                }
+                t => panic!("Expected a generic event, but got {t:?}"),
+            }
+            match time_steps[2].1[0].r#type.as_ref().unwrap() {
+                Type::ActStart(e) => {
+                    assert_eq!(Id::<Person>::get_from_ext("xe-1").internal(), e.person);
+                    assert_eq!(Id::<Link>::get_from_ext("xe-l2").internal(), e.link);
+                    assert_eq!(Id::<String>::get_from_ext("w").internal(), e.act_type);
+                }
+                t => panic!("Expected an act start event, but got {t:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let path = PathBuf::from("./test_output/io/xml_events/write_read/events.xml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let person: Id<Person> = Id::create("xe-wr-p");
+        let link: Id<Link> = Id::create("xe-wr-l");
+        let mode: Id<String> = Id::create("car");
+        let events = vec![
+            Event::new_departure(person.internal(), link.internal(), mode.internal()),
+            Event::new_travelled(person.internal(), 42.5, mode.internal()),
+            Event::new_arrival(person.internal(), link.internal(), mode.internal(), 0.),
+        ];
+        let mut writer = XmlEventsWriter::new(&path);
+        for (time, event) in events.iter().enumerate() {
+            writer.receive_event(time as u32, event);
+        }
+        writer.finish();
+
+        let mut reader = XmlEventsReader::new(&path);
+        for (time, event) in events.iter().enumerate() {
+            assert_eq!(Some((time as u32, event.clone())), reader.read_next());
+        }
+        assert_eq!(None, reader.read_next());
+    }
+}
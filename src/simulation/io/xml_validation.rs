@@ -0,0 +1,362 @@
+//! Diagnostics for matsim xml files. Deserializing large network and plans files only tells what
+//! went wrong, but not where. This module validates files against the structure of the matsim
+//! dtds, and locates elements by the path of a deserialization error. Problems are reported with
+//! the line number, the element path and the id of the enclosing node, link or person.
+//!
+//! The validation checks which elements may contain which children and which attributes are
+//! required. It doesn't check the values of attributes, nor the order of elements.
+
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use xml::attribute::OwnedAttribute;
+use xml::common::Position;
+use xml::reader::XmlEvent;
+use xml::EventReader;
+
+/// Allowed children and required attributes of an element.
+struct ElementRule {
+    name: &'static str,
+    children: &'static [&'static str],
+    required: &'static [&'static str],
+}
+
+const fn rule(
+    name: &'static str,
+    children: &'static [&'static str],
+    required: &'static [&'static str],
+) -> ElementRule {
+    ElementRule {
+        name,
+        children,
+        required,
+    }
+}
+
+/// Elements of network_v2.dtd.
+const NETWORK: &[ElementRule] = &[
+    rule("network", &["attributes", "nodes", "links"], &[]),
+    rule("nodes", &["node"], &[]),
+    rule("node", &["attributes"], &["id", "x", "y"]),
+    rule("links", &["link"], &[]),
+    rule(
+        "link",
+        &["attributes"],
+        &[
+            "id",
+            "from",
+            "to",
+            "length",
+            "freespeed",
+            "capacity",
+            "permlanes",
+        ],
+    ),
+    rule("attributes", &["attribute"], &[]),
+    rule("attribute", &[], &["name", "class"]),
+];
+
+/// Elements of population_v6.dtd.
+const POPULATION: &[ElementRule] = &[
+    rule("population", &["attributes", "person"], &[]),
+    rule("person", &["attributes", "plan"], &["id"]),
+    rule("plan", &["attributes", "activity", "act", "leg"], &[]),
+    rule("activity", &["attributes"], &["type"]),
+    rule("act", &["attributes"], &["type"]),
+    rule("leg", &["attributes", "route"], &["mode"]),
+    rule("route", &[], &["type"]),
+    rule("attributes", &["attribute"], &[]),
+    rule("attribute", &[], &["name", "class"]),
+];
+
+/// A problem of an xml file with the position where it occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlProblem {
+    pub location: XmlLocation,
+    pub message: String,
+}
+
+impl Display for XmlProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Position of an element. The id is the id of the element, or of its closest ancestor with an
+/// id, e.g. the person of an activity.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct XmlLocation {
+    /// Line and column, starting at 1.
+    pub line: u64,
+    pub column: u64,
+    /// Names of the element and its ancestors, e.g. `population/person/plan/leg`.
+    pub path: String,
+    pub id: Option<String>,
+}
+
+impl Display for XmlLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}, {}",
+            self.line, self.column, self.path
+        )?;
+        if let Some(id) = &self.id {
+            write!(f, " (id '{id}')")?;
+        }
+        Ok(())
+    }
+}
+
+/// Open elements while streaming through a file.
+#[derive(Default)]
+struct Ancestors {
+    names: Vec<String>,
+    ids: Vec<Option<String>>,
+}
+
+impl Ancestors {
+    fn push(&mut self, name: &str, attributes: &[OwnedAttribute]) {
+        let id = attribute(attributes, "id")
+            .map(String::from)
+            .or_else(|| self.ids.last().cloned().flatten());
+        self.names.push(name.to_string());
+        self.ids.push(id);
+    }
+
+    fn pop(&mut self) {
+        self.names.pop();
+        self.ids.pop();
+    }
+
+    fn location(&self, position: &impl Position) -> XmlLocation {
+        let position = position.position();
+        XmlLocation {
+            line: position.row + 1,
+            column: position.column + 1,
+            path: self.names.join("/"),
+            id: self.ids.last().cloned().flatten(),
+        }
+    }
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+/// Opens an xml file for streaming. Files ending on `.gz` are decompressed on the fly.
+fn open(path: &Path) -> EventReader<Box<dyn Read>> {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Could not open xml file {path:?}"));
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e.eq("gz")) {
+        Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file))))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    EventReader::new(reader)
+}
+
+/// Validates a network or plans file against the structure of its dtd. The dtd is chosen by the
+/// root element. Files with other root elements are only checked for being well-formed xml.
+pub fn validate(path: &Path) -> Vec<XmlProblem> {
+    validate_reader(open(path))
+}
+
+fn validate_reader<R: Read>(mut reader: EventReader<R>) -> Vec<XmlProblem> {
+    let mut problems = Vec::new();
+    let mut rules: &[ElementRule] = &[];
+    let mut ancestors = Ancestors::default();
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let name = name.local_name;
+                if ancestors.names.is_empty() {
+                    rules = match name.as_str() {
+                        "network" => NETWORK,
+                        "population" => POPULATION,
+                        _ => &[],
+                    };
+                }
+                let parent = ancestors.names.last().cloned();
+                ancestors.push(&name, &attributes);
+                if rules.is_empty() {
+                    continue;
+                }
+                let mut problem = |message: String| {
+                    problems.push(XmlProblem {
+                        location: ancestors.location(&reader),
+                        message,
+                    })
+                };
+                let parent_rule = parent.and_then(|p| rules.iter().find(|r| r.name == p));
+                if parent_rule.is_some_and(|r| !r.children.contains(&name.as_str())) {
+                    problem(format!("Unexpected element <{name}>."));
+                }
+                if let Some(rule) = rules.iter().find(|r| r.name == name) {
+                    for required in rule.required {
+                        if attribute(&attributes, required).is_none() {
+                            problem(format!("Missing attribute '{required}' of <{name}>."));
+                        }
+                    }
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => ancestors.pop(),
+            Ok(XmlEvent::EndDocument) => break,
+            Ok(_) => {}
+            Err(e) => {
+                problems.push(XmlProblem {
+                    location: ancestors.location(&e),
+                    message: e.msg().to_string(),
+                });
+                break;
+            }
+        }
+    }
+    problems
+}
+
+/// Locates the element, which a deserialization error refers to. The path consists of the names
+/// of the child elements and their index among the children with the same name, starting below
+/// the root element, e.g. `[("person", 3), ("plan", 0)]`. `$value` matches children of any name.
+/// Returns the deepest element of the path, which exists in the file.
+pub fn locate(path: &Path, elements: &[(String, usize)]) -> Option<XmlLocation> {
+    locate_in_reader(open(path), elements)
+}
+
+fn locate_in_reader<R: Read>(
+    mut reader: EventReader<R>,
+    elements: &[(String, usize)],
+) -> Option<XmlLocation> {
+    let mut ancestors = Ancestors::default();
+    let mut location = None;
+    // number of elements of the path which were found, and the siblings of the next element,
+    // which were passed already.
+    let mut found = 0;
+    let mut siblings = 0;
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                ancestors.push(&name.local_name, &attributes);
+                // the root element is at depth 1 and the first element of the path at depth 2.
+                if ancestors.names.len() != found + 2 || found == elements.len() {
+                    continue;
+                }
+                let (expected, index) = &elements[found];
+                if expected != "$value" && *expected != name.local_name {
+                    continue;
+                }
+                if siblings < *index {
+                    siblings += 1;
+                    continue;
+                }
+                location = Some(ancestors.location(&reader));
+                found += 1;
+                siblings = 0;
+                if found == elements.len() {
+                    return location;
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => {
+                // the deepest element found so far ends, without containing the next element.
+                if ancestors.names.len() == found + 1 {
+                    return location;
+                }
+                ancestors.pop();
+            }
+            Ok(XmlEvent::EndDocument) | Err(_) => return location,
+            Ok(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xml::EventReader;
+
+    use crate::simulation::io::xml_validation::{locate_in_reader, validate_reader, XmlLocation};
+
+    const POPULATION: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<population>
+    <person id="p1">
+        <plan selected="yes">
+            <activity type="h" link="1" x="0" y="0" end_time="08:00:00"/>
+            <leg mode="car">
+                <route type="links">1 2</route>
+            </leg>
+            <activity link="2" x="0" y="0"/>
+        </plan>
+    </person>
+    <person id="p2">
+        <plan selected="yes">
+            <activity type="h" link="1" x="0" y="0"/>
+            <lag mode="car"/>
+        </plan>
+    </person>
+</population>"#;
+
+    #[test]
+    fn validate_population() {
+        let problems = validate_reader(EventReader::new(POPULATION.as_bytes()));
+        assert_eq!(2, problems.len(), "{problems:?}");
+        assert_eq!(
+            "line 8, column 13, population/person/plan/activity (id 'p1'): Missing attribute 'type' of <activity>.",
+            problems[0].to_string()
+        );
+        assert_eq!(15, problems[1].location.line);
+        assert_eq!(Some(String::from("p2")), problems[1].location.id);
+        assert_eq!("Unexpected element <lag>.", problems[1].message);
+    }
+
+    #[test]
+    fn validate_malformed_network() {
+        let network = "<network>\n<nodes>\n<node id=\"1\" x=\"0\" y=\"0\">\n</nodes>\n</network>";
+        let problems = validate_reader(EventReader::new(network.as_bytes()));
+        assert_eq!(1, problems.len());
+        assert_eq!(4, problems[0].location.line);
+    }
+
+    #[test]
+    fn locate_by_path() {
+        let location = locate_in_reader(
+            EventReader::new(POPULATION.as_bytes()),
+            &[
+                (String::from("person"), 0),
+                (String::from("plan"), 0),
+                (String::from("$value"), 1),
+                (String::from("route"), 0),
+            ],
+        );
+        assert_eq!(
+            Some(XmlLocation {
+                line: 7,
+                column: 17,
+                path: String::from("population/person/plan/leg/route"),
+                id: Some(String::from("p1")),
+            }),
+            location
+        );
+
+        // the plan of the second person has no third element, so the plan is the deepest element.
+        let location = locate_in_reader(
+            EventReader::new(POPULATION.as_bytes()),
+            &[
+                (String::from("person"), 1),
+                (String::from("plan"), 0),
+                (String::from("$value"), 2),
+            ],
+        )
+        .unwrap();
+        assert_eq!(13, location.line);
+        assert_eq!("population/person/plan", location.path);
+        assert_eq!(Some(String::from("p2")), location.id);
+    }
+}
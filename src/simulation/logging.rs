@@ -1,5 +1,6 @@
 use std::io;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use tracing::level_filters::LevelFilter;
 use tracing_appender::non_blocking::WorkerGuard;
@@ -7,11 +8,16 @@ use tracing_appender::{non_blocking, rolling};
 use tracing_subscriber::fmt;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::Layer;
+use tracing_subscriber::{reload, EnvFilter, Layer};
 
 use crate::simulation::config::{Config, Logging, Profiling};
 use crate::simulation::profiling::{SpanDurationToCSVLayer, WriterGuard};
 
+type ReloadFilter = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+/// Replaces the filters of the log file and the console, once logging is initialized.
+static RELOAD_FILTER: OnceLock<ReloadFilter> = OnceLock::new();
+
 pub fn init_std_out_logging() {
     let collector = tracing_subscriber::registry().with(
         fmt::Layer::new()
@@ -35,19 +41,21 @@ pub fn init_logging(config: &Config, part: u32) -> (Option<WorkerGuard>, Option<
     } else {
         (None, None)
     };
-    let (log_layer, log_guard) = if Logging::Info == config.output().logging {
+    let (log_layer, log_guard, log_handle) = if Logging::Info == config.output().logging {
         let log_file_name = format!("log_process_{file_discriminant}.txt");
         let log_file_appender = rolling::never(&dir, log_file_name);
         let (log_file, log_guard) = non_blocking(log_file_appender);
+        let (filter, handle) = reload::Layer::new(create_filter(config, part));
         let layer = fmt::Layer::new()
             .with_writer(log_file)
             .json()
             .with_ansi(false)
-            .with_filter(LevelFilter::INFO);
-        (Some(layer), Some(log_guard))
+            .with_filter(filter);
+        (Some(layer), Some(log_guard), Some(handle))
     } else {
-        (None, None)
+        (None, None, None)
     };
+    let (console_filter, console_handle) = reload::Layer::new(create_filter(config, part));
 
     let collector = tracing_subscriber::registry()
         .with(csv_layer)
@@ -57,9 +65,42 @@ pub fn init_logging(config: &Config, part: u32) -> (Option<WorkerGuard>, Option<
             fmt::layer()
                 .with_writer(io::stdout)
                 .with_span_events(FmtSpan::CLOSE)
-                .with_filter(LevelFilter::INFO)
+                .with_filter(console_filter)
         }));
 
     tracing::subscriber::set_global_default(collector).expect("Unable to set a global collector");
+
+    let reload_filter: ReloadFilter = Box::new(move |directives: &str| {
+        // EnvFilter is not Clone, so it is parsed once per layer.
+        let parse = || EnvFilter::try_new(directives).map_err(|e| e.to_string());
+        if let Some(handle) = &log_handle {
+            handle.reload(parse()?).map_err(|e| e.to_string())?;
+        }
+        // only process 0 logs to the console.
+        if part == 0 {
+            console_handle.reload(parse()?).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    });
+    // logging is initialized once per process.
+    let _ = RELOAD_FILTER.set(reload_filter);
     (log_guard, guard)
 }
+
+/// Replaces the log filter of this process with the given directives, e.g. `info,rust_q_sim=debug`.
+pub fn set_log_filter(directives: &str) -> Result<(), String> {
+    let reload = RELOAD_FILTER
+        .get()
+        .ok_or_else(|| String::from("Logging was not initialized."))?;
+    reload(directives)
+}
+
+/// Creates a filter from the configured directives, if they apply to this partition. Otherwise,
+/// everything on info level is logged.
+fn create_filter(config: &Config, part: u32) -> EnvFilter {
+    match config.output().log_filter {
+        Some(filter) if filter.applies_to(part) => EnvFilter::try_new(&filter.directives)
+            .unwrap_or_else(|e| panic!("Invalid log filter '{}': {e}", filter.directives)),
+        _ => EnvFilter::new("info"),
+    }
+}
@@ -1,15 +1,26 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, Barrier};
+use std::sync::{Arc, Barrier, Mutex};
 
-use mpi::collective::CommunicatorCollectives;
-use mpi::datatype::PartitionMut;
-use mpi::point_to_point::{Destination, Source};
+use mpi::collective::{CommunicatorCollectives, SystemOperation};
+use mpi::datatype::{Equivalence, PartitionMut};
+use mpi::point_to_point::{Destination, Message, Source, Status};
 use mpi::topology::{Communicator, SystemCommunicator};
-use mpi::{Count, Rank};
+use mpi::{Count, Rank, Tag};
+use prost::Message as _;
 use tracing::{debug, info, instrument, span, Level};
 
-use crate::simulation::wire_types::messages::{SimMessage, SyncMessage, TravelTimesMessage};
+use crate::simulation::config::ReceiveMode;
+use crate::simulation::messaging::communication::neighborhood::NeighborhoodExchange;
+use crate::simulation::messaging::pool::Pool;
+use crate::simulation::messaging::sync_codec;
+use crate::simulation::messaging::sync_codec::SyncBuffer;
+use crate::simulation::wire_types::messages::{
+    PersonsMessage, SimMessage, SyncMessage, TravelTimesMessage,
+};
+use crate::simulation::wire_types::population::Person;
 
 pub trait SimCommunicator {
     fn send_receive_vehicles<F>(
@@ -27,8 +38,26 @@ pub trait SimCommunicator {
         travel_times: HashMap<u64, u32>,
     ) -> Vec<TravelTimesMessage>;
 
+    /// Collective operation which sends the persons to the ranks they are keyed by. Returns the
+    /// persons sent to this rank by all ranks, including itself.
+    fn send_receive_persons(&self, persons: HashMap<u32, Vec<Person>>) -> Vec<Person>;
+
+    /// Receives the messages, which have already arrived, without waiting for any. This allows to
+    /// receive messages while the partition computes a time step.
+    fn receive_arrived<F>(&self, _on_msg: F)
+    where
+        F: FnMut(SyncMessage),
+    {
+    }
+
     fn barrier(&self);
 
+    /// Collective operation which returns true on all ranks, if at least one rank passed true.
+    fn any(&self, value: bool) -> bool;
+
+    /// Collective operation which returns the sum of the values of all ranks on all ranks.
+    fn sum(&self, value: u64) -> u64;
+
     fn rank(&self) -> u32;
 }
 
@@ -55,10 +84,22 @@ impl SimCommunicator for DummySimCommunicator {
         vec![TravelTimesMessage::from(travel_times)]
     }
 
+    fn send_receive_persons(&self, persons: HashMap<u32, Vec<Person>>) -> Vec<Person> {
+        persons.into_values().flatten().collect()
+    }
+
     fn barrier(&self) {
         info!("Barrier was called on DummySimCommunicator, which doesn't do anything.")
     }
 
+    fn any(&self, value: bool) -> bool {
+        value
+    }
+
+    fn sum(&self, value: u64) -> u64 {
+        value
+    }
+
     fn rank(&self) -> u32 {
         0
     }
@@ -71,6 +112,8 @@ pub struct ChannelSimCommunicator {
     tt_senders: Vec<Sender<SimMessage>>,
     rank: u32,
     barrier: Arc<Barrier>,
+    any_flag: Arc<AtomicBool>,
+    sum: Arc<AtomicU64>,
 }
 
 impl ChannelSimCommunicator {
@@ -79,6 +122,8 @@ impl ChannelSimCommunicator {
         let mut tt_senders: Vec<_> = Vec::new();
         let mut comms: Vec<_> = Vec::new();
         let barrier = Arc::new(Barrier::new(num_parts as usize));
+        let any_flag = Arc::new(AtomicBool::new(false));
+        let sum = Arc::new(AtomicU64::new(0));
 
         for rank in 0..num_parts {
             let (sender, receiver) = channel();
@@ -90,6 +135,8 @@ impl ChannelSimCommunicator {
                 tt_senders: vec![],
                 rank,
                 barrier: barrier.clone(),
+                any_flag: any_flag.clone(),
+                sum: sum.clone(),
             };
             senders.push(sender);
             tt_senders.push(tt_sender);
@@ -153,6 +200,15 @@ impl SimCommunicator for ChannelSimCommunicator {
         }
     }
 
+    fn receive_arrived<F>(&self, mut on_msg: F)
+    where
+        F: FnMut(SyncMessage),
+    {
+        while let Ok(msg) = self.receiver.try_recv() {
+            on_msg(msg.sync_message());
+        }
+    }
+
     fn send_receive_travel_times(
         &self,
         _now: u32,
@@ -183,17 +239,83 @@ impl SimCommunicator for ChannelSimCommunicator {
         result
     }
 
+    fn send_receive_persons(&self, mut persons: HashMap<u32, Vec<Person>>) -> Vec<Person> {
+        // same as for travel times: the barrier keeps messages of consecutive calls apart.
+        self.barrier.wait();
+
+        // send a message to everybody, so that receivers know how many messages to expect.
+        for (to_process, sender) in self.tt_senders.iter().enumerate() {
+            let message = PersonsMessage {
+                to_process: to_process as u32,
+                persons: persons.remove(&(to_process as u32)).unwrap_or_default(),
+            };
+            sender
+                .send(SimMessage::from_persons_message(message))
+                .expect("Failed to send persons message");
+        }
+
+        let mut result = Vec::new();
+        for _ in 0..self.tt_senders.len() {
+            let message = self
+                .tt_receiver
+                .recv()
+                .expect("Error while receiving messages")
+                .persons_message();
+            result.extend(message.persons);
+        }
+        result
+    }
+
     fn barrier(&self) {
         self.barrier.wait();
     }
 
+    fn any(&self, value: bool) -> bool {
+        // make sure the flag was reset by the previous call, before anyone sets it.
+        self.barrier.wait();
+        if value {
+            self.any_flag.store(true, Ordering::SeqCst);
+        }
+        self.barrier.wait();
+        let result = self.any_flag.load(Ordering::SeqCst);
+        // everybody has read the flag. One thread resets it for the next call.
+        if self.barrier.wait().is_leader() {
+            self.any_flag.store(false, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn sum(&self, value: u64) -> u64 {
+        // same as any: the sum is reset by one thread, after everybody has read it.
+        self.barrier.wait();
+        self.sum.fetch_add(value, Ordering::SeqCst);
+        self.barrier.wait();
+        let result = self.sum.load(Ordering::SeqCst);
+        if self.barrier.wait().is_leader() {
+            self.sum.store(0, Ordering::SeqCst);
+        }
+        result
+    }
+
     fn rank(&self) -> u32 {
         self.rank
     }
 }
 
+/// Number of serialization buffers kept for reuse.
+const SEND_BUFFER_POOL_SIZE: usize = 32;
+/// Sync messages are tagged with their time step modulo this value, as mpi only guarantees tags
+/// up to 32767. Neighbors are at most one time step apart, so that tags are unique between them.
+const TIME_TAG_MODULUS: u32 = 32768;
+
 pub struct MpiSimCommunicator {
     pub mpi_communicator: SystemCommunicator,
+    // buffers for serialized messages are reused across time steps
+    send_buffers: RefCell<Pool<SyncBuffer>>,
+    receive_buffer: RefCell<SyncBuffer>,
+    receive_mode: ReceiveMode,
+    // created with the first exchange, if neighborhood collectives are used
+    neighborhood: RefCell<Option<NeighborhoodExchange>>,
 }
 
 impl SimCommunicator for MpiSimCommunicator {
@@ -207,12 +329,25 @@ impl SimCommunicator for MpiSimCommunicator {
     ) where
         F: FnMut(SyncMessage),
     {
+        if self.receive_mode == ReceiveMode::NeighborhoodCollective {
+            self.send_receive_neighborhood(out_messages, expected_vehicle_messages, now, on_msg);
+            return;
+        }
+
         let send_span = span!(Level::TRACE, "send_msgs", rank = self.rank(), now = now);
         let send_time = send_span.enter();
-        let buf_msg: Vec<_> = out_messages
-            .into_iter()
-            .map(|(to, m)| (to, SimMessage::from_sync_message(m).serialize()))
-            .collect();
+        let buf_msg: Vec<_> = {
+            let mut send_buffers = self.send_buffers.borrow_mut();
+            out_messages
+                .into_iter()
+                .map(|(to, m)| {
+                    let tag = time_tag(m.time);
+                    let mut buf = send_buffers.take();
+                    sync_codec::encode(m, &mut buf);
+                    (to, tag, buf)
+                })
+                .collect()
+        };
 
         // we have to use at least immediate send here. Otherwise we risk blocking on send as explained
         // in https://paperpile.com/app/p/e209e0b3-9bdb-08c7-8a62-b1180a9ac954 chapter 4.3, 4.4 and 4.12.
@@ -230,11 +365,11 @@ impl SimCommunicator for MpiSimCommunicator {
         // can ensure that a buffer is not moved while the request is in progress.
         mpi::request::multiple_scope(buf_msg.len(), |scope, reqs| {
             // ------- Send Part ---------
-            for (message, buf) in buf_msg.iter() {
+            for (to, tag, buf) in buf_msg.iter() {
                 let req = self
                     .mpi_communicator
-                    .process_at_rank(*message as Rank)
-                    .immediate_send(scope, buf);
+                    .process_at_rank(*to as Rank)
+                    .immediate_send_with_tag(scope, &buf[..], *tag);
                 reqs.add(req);
             }
             drop(send_time);
@@ -246,12 +381,41 @@ impl SimCommunicator for MpiSimCommunicator {
             while !expected_vehicle_messages.is_empty() {
                 // measure the wait time for receiving
                 let receive_time = receive_span.enter();
-                let (encoded_msg, _status) = self.mpi_communicator.any_process().receive_vec();
+                // probe first, so that the message can be received into the reused buffer
+                let neighbor = match self.receive_mode {
+                    ReceiveMode::AnySource => {
+                        self.receive_into_buffer(
+                            self.mpi_communicator.any_process().matched_probe(),
+                        );
+                        None
+                    }
+                    ReceiveMode::Neighbors => {
+                        let neighbor = *expected_vehicle_messages.iter().min().unwrap();
+                        self.receive_into_buffer(
+                            self.mpi_communicator
+                                .process_at_rank(neighbor as Rank)
+                                .matched_probe_with_tag(time_tag(now)),
+                        );
+                        Some(neighbor)
+                    }
+                    ReceiveMode::NeighborhoodCollective => {
+                        unreachable!("Neighborhood collectives are exchanged separately.")
+                    }
+                };
                 drop(receive_time);
 
                 let handle_time = handle_span.enter();
-                let msg = SimMessage::deserialize(&encoded_msg).sync_message();
+                let msg = sync_codec::decode(&self.receive_buffer.borrow()[..]);
                 let from_rank = msg.from_process;
+                if let Some(neighbor) = neighbor {
+                    if from_rank != neighbor || msg.time != now {
+                        panic!(
+                            "#{} expected the message of #{neighbor} for time step {now}, but received a message of #{from_rank} for time step {}.",
+                            self.rank(),
+                            msg.time
+                        );
+                    }
+                }
 
                 // If a message was received from a neighbor partition for this very time step, remove
                 // that partition from expected messages which indicates which partitions we are waiting
@@ -264,6 +428,12 @@ impl SimCommunicator for MpiSimCommunicator {
                 drop(handle_time);
             }
 
+            // messages of partitions, which are not neighbors, are not waited for. They are
+            // received as soon as they have arrived.
+            if self.receive_mode == ReceiveMode::Neighbors {
+                self.receive_arrived(&mut on_msg);
+            }
+
             // wait here, so that all requests finish. This is necessary, because a process might send
             // more messages than it receives. This happens, if a process sends messages to remote
             // partitions (teleported legs) but only receives messages from neighbor partitions.
@@ -272,6 +442,25 @@ impl SimCommunicator for MpiSimCommunicator {
             reqs.wait_all(&mut Vec::new());
             drop(receive_time)
         });
+
+        let mut send_buffers = self.send_buffers.borrow_mut();
+        for (_, buf) in buf_msg {
+            send_buffers.put(buf);
+        }
+    }
+
+    fn receive_arrived<F>(&self, mut on_msg: F)
+    where
+        F: FnMut(SyncMessage),
+    {
+        while let Some(probed) = self
+            .mpi_communicator
+            .any_process()
+            .immediate_matched_probe()
+        {
+            self.receive_into_buffer(probed);
+            on_msg(sync_codec::decode(&self.receive_buffer.borrow()[..]));
+        }
     }
 
     fn send_receive_travel_times(
@@ -294,16 +483,164 @@ impl SimCommunicator for MpiSimCommunicator {
         messages
     }
 
+    fn send_receive_persons(&self, persons: HashMap<u32, Vec<Person>>) -> Vec<Person> {
+        let bytes = encode_persons(persons);
+        let gathered = self.all_gather_bytes(&bytes);
+        decode_persons(&gathered, self.rank())
+    }
+
     fn barrier(&self) {
         self.mpi_communicator.barrier();
     }
 
+    fn any(&self, value: bool) -> bool {
+        let mut result = 0i32;
+        self.mpi_communicator.all_reduce_into(
+            &(value as i32),
+            &mut result,
+            SystemOperation::logical_or(),
+        );
+        result != 0
+    }
+
+    fn sum(&self, value: u64) -> u64 {
+        let mut result = 0u64;
+        self.mpi_communicator
+            .all_reduce_into(&value, &mut result, SystemOperation::sum());
+        result
+    }
+
     fn rank(&self) -> u32 {
         self.mpi_communicator.rank() as u32
     }
 }
 
 impl MpiSimCommunicator {
+    pub fn new(mpi_communicator: SystemCommunicator) -> Self {
+        MpiSimCommunicator {
+            mpi_communicator,
+            send_buffers: RefCell::new(Pool::new(SEND_BUFFER_POOL_SIZE)),
+            receive_buffer: RefCell::new(SyncBuffer::default()),
+            receive_mode: ReceiveMode::default(),
+            neighborhood: RefCell::new(None),
+        }
+    }
+
+    pub fn with_receive_mode(mut self, receive_mode: ReceiveMode) -> Self {
+        self.receive_mode = receive_mode;
+        self
+    }
+
+    /// Exchanges the messages of the neighbor partitions with neighborhood collectives. Messages to
+    /// other partitions, e.g. with teleported vehicles, are sent point-to-point and received once
+    /// they have arrived, as with [ReceiveMode::Neighbors].
+    fn send_receive_neighborhood<F>(
+        &self,
+        mut out_messages: HashMap<u32, SyncMessage>,
+        expected_vehicle_messages: &mut HashSet<u32>,
+        now: u32,
+        mut on_msg: F,
+    ) where
+        F: FnMut(SyncMessage),
+    {
+        let mut neighborhood = self.neighborhood.borrow_mut();
+        // messages of neighbors are only received by the collectives, so none of them are cached
+        // and all neighbors are expected in the first time step.
+        let neighborhood = neighborhood.get_or_insert_with(|| {
+            NeighborhoodExchange::create(&self.mpi_communicator, expected_vehicle_messages)
+        });
+
+        let mut send_buffers = self.send_buffers.borrow_mut();
+        let neighbor_bufs: Vec<_> = neighborhood
+            .neighbors()
+            .iter()
+            .map(|neighbor| {
+                let msg = out_messages.remove(neighbor).unwrap_or_else(|| {
+                    panic!(
+                        "#{} has no message for neighbor #{neighbor} in time step {now}.",
+                        self.rank()
+                    )
+                });
+                let mut buf = send_buffers.take();
+                sync_codec::encode(msg, &mut buf);
+                buf
+            })
+            .collect();
+        let remote_bufs: Vec<_> = out_messages
+            .into_iter()
+            .map(|(to, msg)| {
+                let tag = time_tag(msg.time);
+                let mut buf = send_buffers.take();
+                sync_codec::encode(msg, &mut buf);
+                (to, tag, buf)
+            })
+            .collect();
+        drop(send_buffers);
+
+        mpi::request::multiple_scope(remote_bufs.len(), |scope, reqs| {
+            for (to, tag, buf) in remote_bufs.iter() {
+                let req = self
+                    .mpi_communicator
+                    .process_at_rank(*to as Rank)
+                    .immediate_send_with_tag(scope, &buf[..], *tag);
+                reqs.add(req);
+            }
+
+            neighborhood.exchange(&neighbor_bufs, |neighbor, bytes| {
+                // copy the message, as rkyv requires aligned bytes
+                let msg = {
+                    let mut buffer = self.receive_buffer.borrow_mut();
+                    buffer.clear();
+                    buffer.extend_from_slice(bytes);
+                    sync_codec::decode(&buffer[..])
+                };
+                if msg.from_process != neighbor || msg.time != now {
+                    panic!(
+                        "#{} expected the message of #{neighbor} for time step {now}, but received a message of #{} for time step {}.",
+                        self.rank(),
+                        msg.from_process,
+                        msg.time
+                    );
+                }
+                expected_vehicle_messages.remove(&neighbor);
+                on_msg(msg);
+            });
+            assert!(
+                expected_vehicle_messages.is_empty(),
+                "#{} expected messages of partitions which are not its neighbors: {expected_vehicle_messages:?}",
+                self.rank()
+            );
+
+            self.receive_arrived(&mut on_msg);
+            reqs.wait_all(&mut Vec::new());
+        });
+
+        let mut send_buffers = self.send_buffers.borrow_mut();
+        for buf in neighbor_bufs {
+            send_buffers.put(buf);
+        }
+        for (_, _, buf) in remote_bufs {
+            send_buffers.put(buf);
+        }
+    }
+
+    /// Receives a probed message into the reused receive buffer.
+    fn receive_into_buffer(&self, (mpi_msg, status): (Message, Status)) {
+        let mut encoded_msg = self.receive_buffer.borrow_mut();
+        let len = status.count(u8::equivalent_datatype()) as usize;
+        encoded_msg.resize(len, 0);
+        mpi_msg.matched_receive_into(&mut encoded_msg[..]);
+    }
+
+    /// Gathers the bytes of all processes. The bytes of the processes are concatenated in rank order.
+    fn all_gather_bytes(&self, bytes: &Vec<u8>) -> Vec<u8> {
+        let mut lengths = self.gather_travel_time_lengths(&bytes);
+        if lengths.iter().sum::<i32>() <= 0 {
+            return Vec::new();
+        }
+        self.gather_travel_times_var_count(&bytes, &mut lengths)
+    }
+
     fn gather_travel_times(&self, sim_travel_times_message: &Vec<u8>) -> Vec<TravelTimesMessage> {
         // ------- Gather traffic info lengths -------
         let mut travel_times_length_buffer =
@@ -385,3 +722,426 @@ impl MpiSimCommunicator {
             .collect()
     }
 }
+
+/// State shared by the partitions which run as threads of the same process.
+struct LocalGroup {
+    barrier: Barrier,
+    any_flag: AtomicBool,
+    any_result: AtomicBool,
+    sum: AtomicU64,
+    sum_result: AtomicU64,
+    travel_times: Mutex<Vec<(u32, TravelTimesMessage)>>,
+    gathered_travel_times: Mutex<Vec<TravelTimesMessage>>,
+    persons: Mutex<HashMap<u32, Vec<Person>>>,
+    gathered_persons: Mutex<Vec<u8>>,
+}
+
+/// Encodes one length delimited message per target rank. Ranks without persons are left out.
+fn encode_persons(persons: HashMap<u32, Vec<Person>>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (to_process, persons) in persons {
+        SimMessage::from_persons_message(PersonsMessage {
+            to_process,
+            persons,
+        })
+        .encode_length_delimited(&mut bytes)
+        .expect("Failed to encode persons message");
+    }
+    bytes
+}
+
+/// Decodes the messages gathered from all processes and keeps the persons sent to the rank.
+fn decode_persons(mut buffer: &[u8], rank: u32) -> Vec<Person> {
+    let mut result = Vec::new();
+    while !buffer.is_empty() {
+        let message = SimMessage::decode_length_delimited(&mut buffer)
+            .expect("Failed to decode persons message")
+            .persons_message();
+        if message.to_process == rank {
+            result.extend(message.persons);
+        }
+    }
+    result
+}
+
+fn time_tag(time: u32) -> Tag {
+    (time % TIME_TAG_MODULUS) as Tag
+}
+
+/// Channel endpoints of a partition, which runs as a thread of an mpi process. This is created on
+/// the main thread and moved into the partition's thread, where it is turned into a
+/// HybridSimCommunicator.
+pub struct HybridPartition {
+    rank: u32,
+    threads_per_process: u32,
+    receiver: Receiver<SyncMessage>,
+    local_senders: Vec<Sender<SyncMessage>>,
+    group: Arc<LocalGroup>,
+}
+
+impl HybridPartition {
+    /// Creates the partitions of one process. Partitions are numbered consecutively, so that
+    /// process p runs partitions p * threads_per_process until (p + 1) * threads_per_process.
+    pub fn create(process: u32, threads_per_process: u32) -> Vec<HybridPartition> {
+        let group = Arc::new(LocalGroup {
+            barrier: Barrier::new(threads_per_process as usize),
+            any_flag: AtomicBool::new(false),
+            any_result: AtomicBool::new(false),
+            sum: AtomicU64::new(0),
+            sum_result: AtomicU64::new(0),
+            travel_times: Mutex::new(Vec::new()),
+            gathered_travel_times: Mutex::new(Vec::new()),
+            persons: Mutex::new(HashMap::new()),
+            gathered_persons: Mutex::new(Vec::new()),
+        });
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..threads_per_process).map(|_| channel()).unzip();
+
+        receivers
+            .into_iter()
+            .enumerate()
+            .map(|(local, receiver)| HybridPartition {
+                rank: process * threads_per_process + local as u32,
+                threads_per_process,
+                receiver,
+                local_senders: senders.clone(),
+                group: group.clone(),
+            })
+            .collect()
+    }
+
+    pub fn rank(&self) -> u32 {
+        self.rank
+    }
+}
+
+/// Communicator for partitions which run as threads of several mpi processes. Vehicles are passed
+/// to partitions of the same process via channels without serializing them. Only messages to
+/// partitions of other processes are sent via mpi. Mpi messages are tagged with the receiving
+/// partition, so that each thread only receives its own messages. This requires an mpi
+/// implementation which supports MPI_THREAD_MULTIPLE.
+///
+/// Collective operations are first synchronized between the threads of a process. Then, one thread
+/// per process performs the mpi operation.
+pub struct HybridSimCommunicator {
+    partition: HybridPartition,
+    mpi: MpiSimCommunicator,
+}
+
+impl HybridSimCommunicator {
+    pub fn new(partition: HybridPartition, mpi_communicator: SystemCommunicator) -> Self {
+        HybridSimCommunicator {
+            partition,
+            mpi: MpiSimCommunicator::new(mpi_communicator),
+        }
+    }
+
+    fn process_of(&self, rank: u32) -> u32 {
+        rank / self.partition.threads_per_process
+    }
+
+    fn is_local(&self, rank: u32) -> bool {
+        self.process_of(rank) == self.process_of(self.partition.rank)
+    }
+
+    /// Blocks until a message arrives. Messages of local partitions, which have arrived already,
+    /// are returned first. Otherwise, the thread waits for mpi messages as long as messages of
+    /// other processes are expected, and for the channel once only local partitions are left.
+    fn receive(&self, expected: &HashSet<u32>) -> SyncMessage {
+        if let Ok(msg) = self.partition.receiver.try_recv() {
+            return msg;
+        }
+        if expected.iter().any(|rank| !self.is_local(*rank)) {
+            let probed = self
+                .mpi
+                .mpi_communicator
+                .any_process()
+                .matched_probe_with_tag(self.partition.rank as Tag);
+            self.mpi.receive_into_buffer(probed);
+            sync_codec::decode(&self.mpi.receive_buffer.borrow()[..])
+        } else {
+            self.partition
+                .receiver
+                .recv()
+                .expect("The channels of local partitions were closed")
+        }
+    }
+
+    fn try_receive(&self) -> Option<SyncMessage> {
+        if let Ok(msg) = self.partition.receiver.try_recv() {
+            return Some(msg);
+        }
+        let probed = self
+            .mpi
+            .mpi_communicator
+            .any_process()
+            .immediate_matched_probe_with_tag(self.partition.rank as Tag)?;
+        self.mpi.receive_into_buffer(probed);
+        Some(sync_codec::decode(&self.mpi.receive_buffer.borrow()[..]))
+    }
+}
+
+impl SimCommunicator for HybridSimCommunicator {
+    #[instrument(level = "trace", skip(self, on_msg), fields(rank = self.rank()))]
+    fn send_receive_vehicles<F>(
+        &self,
+        out_messages: HashMap<u32, SyncMessage>,
+        expected_vehicle_messages: &mut HashSet<u32>,
+        now: u32,
+        mut on_msg: F,
+    ) where
+        F: FnMut(SyncMessage),
+    {
+        let buf_msg: Vec<_> = {
+            let mut send_buffers = self.mpi.send_buffers.borrow_mut();
+            let mut remote = Vec::new();
+            for (to, msg) in out_messages {
+                if self.is_local(to) {
+                    let local = (to % self.partition.threads_per_process) as usize;
+                    self.partition.local_senders[local]
+                        .send(msg)
+                        .expect("Failed to send vehicle message to local partition");
+                } else {
+                    let mut buf = send_buffers.take();
+                    sync_codec::encode(msg, &mut buf);
+                    remote.push((to, buf));
+                }
+            }
+            remote
+        };
+
+        // see MpiSimCommunicator for why immediate sends are necessary.
+        mpi::request::multiple_scope(buf_msg.len(), |scope, reqs| {
+            for (to, buf) in buf_msg.iter() {
+                let req = self
+                    .mpi
+                    .mpi_communicator
+                    .process_at_rank(self.process_of(*to) as Rank)
+                    .immediate_send_with_tag(scope, &buf[..], *to as Tag);
+                reqs.add(req);
+            }
+
+            while !expected_vehicle_messages.is_empty() {
+                let msg = self.receive(expected_vehicle_messages);
+                if msg.time == now {
+                    expected_vehicle_messages.remove(&msg.from_process);
+                }
+                on_msg(msg);
+            }
+
+            reqs.wait_all(&mut Vec::new());
+        });
+
+        let mut send_buffers = self.mpi.send_buffers.borrow_mut();
+        for (_, buf) in buf_msg {
+            send_buffers.put(buf);
+        }
+    }
+
+    fn receive_arrived<F>(&self, mut on_msg: F)
+    where
+        F: FnMut(SyncMessage),
+    {
+        while let Some(msg) = self.try_receive() {
+            on_msg(msg);
+        }
+    }
+
+    fn send_receive_travel_times(
+        &self,
+        _now: u32,
+        travel_times: HashMap<u64, u32>,
+    ) -> Vec<TravelTimesMessage> {
+        let group = &self.partition.group;
+        // make sure, that all threads have read the result of the previous call.
+        group.barrier.wait();
+        group
+            .travel_times
+            .lock()
+            .unwrap()
+            .push((self.rank(), TravelTimesMessage::from(travel_times)));
+
+        if group.barrier.wait().is_leader() {
+            let mut local = std::mem::take(&mut *group.travel_times.lock().unwrap());
+            local.sort_by_key(|(rank, _)| *rank);
+            let mut bytes = Vec::new();
+            for (_, message) in local {
+                SimMessage::from_travel_times_message(message)
+                    .encode_length_delimited(&mut bytes)
+                    .expect("Failed to encode travel times message");
+            }
+
+            let gathered = self.mpi.all_gather_bytes(&bytes);
+            let mut buffer = &gathered[..];
+            let mut result = Vec::new();
+            while !buffer.is_empty() {
+                let message = SimMessage::decode_length_delimited(&mut buffer)
+                    .expect("Failed to decode travel times message");
+                result.push(message.travel_times_message());
+            }
+            *group.gathered_travel_times.lock().unwrap() = result;
+        }
+        group.barrier.wait();
+        group.gathered_travel_times.lock().unwrap().clone()
+    }
+
+    fn send_receive_persons(&self, persons: HashMap<u32, Vec<Person>>) -> Vec<Person> {
+        let group = &self.partition.group;
+        // make sure, that all threads have read the result of the previous call.
+        group.barrier.wait();
+        {
+            let mut local = group.persons.lock().unwrap();
+            for (to, persons) in persons {
+                local.entry(to).or_default().extend(persons);
+            }
+        }
+
+        if group.barrier.wait().is_leader() {
+            let local = std::mem::take(&mut *group.persons.lock().unwrap());
+            let gathered = self.mpi.all_gather_bytes(&encode_persons(local));
+            *group.gathered_persons.lock().unwrap() = gathered;
+        }
+        group.barrier.wait();
+        decode_persons(&group.gathered_persons.lock().unwrap(), self.rank())
+    }
+
+    fn barrier(&self) {
+        if self.partition.group.barrier.wait().is_leader() {
+            self.mpi.barrier();
+        }
+        self.partition.group.barrier.wait();
+    }
+
+    fn any(&self, value: bool) -> bool {
+        let group = &self.partition.group;
+        // make sure, that all threads have read the result of the previous call.
+        group.barrier.wait();
+        if value {
+            group.any_flag.store(true, Ordering::SeqCst);
+        }
+        if group.barrier.wait().is_leader() {
+            let local = group.any_flag.swap(false, Ordering::SeqCst);
+            group
+                .any_result
+                .store(self.mpi.any(local), Ordering::SeqCst);
+        }
+        group.barrier.wait();
+        group.any_result.load(Ordering::SeqCst)
+    }
+
+    fn sum(&self, value: u64) -> u64 {
+        let group = &self.partition.group;
+        // make sure, that all threads have read the result of the previous call.
+        group.barrier.wait();
+        group.sum.fetch_add(value, Ordering::SeqCst);
+        if group.barrier.wait().is_leader() {
+            let local = group.sum.swap(0, Ordering::SeqCst);
+            group
+                .sum_result
+                .store(self.mpi.sum(local), Ordering::SeqCst);
+        }
+        group.barrier.wait();
+        group.sum_result.load(Ordering::SeqCst)
+    }
+
+    fn rank(&self) -> u32 {
+        self.partition.rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::thread;
+
+    use crate::simulation::messaging::communication::communicators::{
+        ChannelSimCommunicator, HybridPartition, SimCommunicator,
+    };
+    use crate::simulation::wire_types::messages::SyncMessage;
+    use crate::simulation::wire_types::population::Person;
+
+    #[test]
+    fn channel_any() {
+        let comms = ChannelSimCommunicator::create_n_2_n(3);
+        let handles: Vec<_> = comms
+            .into_iter()
+            .map(|comm| {
+                thread::spawn(move || {
+                    let rank = comm.rank();
+                    // only rank 1 passes true in the first round, nobody in the second
+                    let first = comm.any(rank == 1);
+                    let second = comm.any(false);
+                    (first, second)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!((true, false), handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn channel_sum() {
+        let comms = ChannelSimCommunicator::create_n_2_n(3);
+        let handles: Vec<_> = comms
+            .into_iter()
+            .map(|comm| {
+                thread::spawn(move || {
+                    let rank = comm.rank() as u64;
+                    let first = comm.sum(rank + 1);
+                    let second = comm.sum(rank);
+                    (first, second)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!((6, 3), handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn channel_send_receive_persons() {
+        let comms = ChannelSimCommunicator::create_n_2_n(3);
+        let handles: Vec<_> = comms
+            .into_iter()
+            .map(|comm| {
+                thread::spawn(move || {
+                    // every rank sends a person with its rank as id to the next rank
+                    let rank = comm.rank();
+                    let person = Person {
+                        id: rank as u64,
+                        ..Default::default()
+                    };
+                    let first =
+                        comm.send_receive_persons(HashMap::from([((rank + 1) % 3, vec![person])]));
+                    // nobody sends anything in the second round
+                    let second = comm.send_receive_persons(HashMap::new());
+                    (rank, first, second)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let (rank, first, second) = handle.join().unwrap();
+            assert_eq!(1, first.len());
+            assert_eq!((rank + 2) % 3, first[0].id as u32);
+            assert!(second.is_empty());
+        }
+    }
+
+    #[test]
+    fn hybrid_partitions() {
+        let partitions = HybridPartition::create(2, 3);
+        let ranks: Vec<_> = partitions.iter().map(|p| p.rank()).collect();
+        assert_eq!(vec![6, 7, 8], ranks);
+
+        // partitions of a process can send to each other
+        partitions[0].local_senders[2]
+            .send(SyncMessage::new(0, 6, 8))
+            .unwrap();
+        let received = partitions[2].receiver.recv().unwrap();
+        assert_eq!(6, received.from_process);
+    }
+}
@@ -1,13 +1,19 @@
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::rc::Rc;
 
+use tracing::instrument;
+
 use crate::simulation::messaging::communication::communicators::SimCommunicator;
+use crate::simulation::messaging::pool::Pool;
 use crate::simulation::network::global_network::Network;
 use crate::simulation::network::sim_network::{SimNetworkPartition, StorageUpdate};
 use crate::simulation::wire_types::messages::{
     StorageCap, SyncMessage, TravelTimesMessage, Vehicle,
 };
 
+/// Number of vehicle vecs kept for reuse. This covers one message per neighbor for most partitions.
+const VEHICLE_BUFFER_POOL_SIZE: usize = 32;
+
 pub struct TravelTimesMessageBroker<C>
 where
     C: SimCommunicator,
@@ -45,6 +51,12 @@ where
     // ids (usize) and this way we don't need to keep a reference to the global network's id store
     link_mapping: HashMap<u64, u32>,
     neighbors: HashSet<u32>,
+    // vehicle vecs of consumed messages, which are reused for outgoing messages
+    vehicle_buffers: Pool<Vec<Vehicle>>,
+    // sequence number of the next message to each partition
+    out_sequences: HashMap<u32, u64>,
+    // sequence number and time of the last message received from each partition
+    in_sequences: HashMap<u32, (u64, u32)>,
 }
 
 impl<C> NetMessageBroker<C>
@@ -65,6 +77,9 @@ where
             in_messages: Default::default(),
             link_mapping,
             neighbors,
+            vehicle_buffers: Pool::new(VEHICLE_BUFFER_POOL_SIZE),
+            out_sequences: HashMap::new(),
+            in_sequences: HashMap::new(),
         }
     }
 
@@ -72,6 +87,35 @@ where
         self.communicator.rank()
     }
 
+    /// Returns true on all partitions, if any partition passes true.
+    pub fn any(&self, value: bool) -> bool {
+        self.communicator.any(value)
+    }
+
+    /// Returns the sum of the values of all partitions on all partitions.
+    pub fn sum(&self, value: u64) -> u64 {
+        self.communicator.sum(value)
+    }
+
+    /// Number of received messages, which are meant for future time steps.
+    pub fn buffered_in_messages(&self) -> usize {
+        self.in_messages.len()
+    }
+
+    /// Number of vehicles, which are waiting to be sent to other partitions.
+    pub fn pending_out_vehicles(&self) -> usize {
+        self.out_messages.values().map(|m| m.vehicles.len()).sum()
+    }
+
+    /// Number of persons in vehicles, which were received for future time steps.
+    pub fn buffered_in_agents(&self) -> usize {
+        self.in_messages
+            .iter()
+            .flat_map(|m| m.vehicles.iter())
+            .map(|v| v.num_agents())
+            .sum()
+    }
+
     pub fn rank_for_link(&self, link_id: u64) -> u32 {
         *self.link_mapping.get(&(link_id)).unwrap()
     }
@@ -80,10 +124,11 @@ where
         let link_id = vehicle.curr_link_id().unwrap();
         let partition = *self.link_mapping.get(&link_id).unwrap();
         let rank = self.rank();
+        let buffers = &mut self.vehicle_buffers;
         let message = self
             .out_messages
             .entry(partition)
-            .or_insert_with(|| SyncMessage::new(now, rank, partition));
+            .or_insert_with(|| SyncMessage::with_vehicles(now, rank, partition, buffers.take()));
         message.add_veh(vehicle);
     }
 
@@ -95,10 +140,29 @@ where
             .or_insert_with(|| SyncMessage::new(now, rank, cap.from_part));
         message.add_storage_cap(StorageCap {
             link_id: cap.link_id,
-            value: cap.released,
+            value: cap.used,
+        });
+    }
+
+    /// Hands back a received message after its vehicles were taken out, so that the allocation of
+    /// its vehicle vec can be reused for outgoing messages.
+    pub fn recycle(&mut self, message: SyncMessage) {
+        self.vehicle_buffers.put(message.vehicles);
+    }
+
+    /// Receives the messages, which have already arrived, before a time step is computed. This way,
+    /// the transfer of messages overlaps with the computation. The messages are handed out by the
+    /// next call to send_recv.
+    pub fn begin_recv(&mut self) {
+        let in_msgs_ref = &mut self.in_messages;
+        let in_sequences_ref = &mut self.in_sequences;
+        self.communicator.receive_arrived(|msg| {
+            check_sequence(in_sequences_ref, &msg);
+            in_msgs_ref.push(msg);
         });
     }
 
+    #[instrument(level = "trace", skip(self), fields(rank = self.rank()))]
     pub fn send_recv(&mut self, now: u32) -> Vec<SyncMessage> {
         let vehicles = self.prepare_send_recv_vehicles(now);
         let mut result: Vec<SyncMessage> = Vec::new();
@@ -110,8 +174,10 @@ where
         // of passing self around, which would lock them because we would hold multiple mut refs to self
         let comm_ref = &self.communicator;
         let in_msgs_ref = &mut self.in_messages;
+        let in_sequences_ref = &mut self.in_sequences;
 
         comm_ref.send_receive_vehicles(vehicles, &mut expected_vehicle_messages, now, |msg| {
+            check_sequence(in_sequences_ref, &msg);
             Self::handle_incoming_msg(msg, &mut result, in_msgs_ref, now)
         });
 
@@ -152,16 +218,62 @@ where
         let mut messages =
             std::mem::replace(&mut self.out_messages, HashMap::with_capacity(capacity));
 
+        let rank = self.rank();
         for partition in &self.neighbors {
             let neighbor_rank = *partition;
-            messages
-                .entry(neighbor_rank)
-                .or_insert_with(|| SyncMessage::new(now, self.rank(), neighbor_rank));
+            let buffers = &mut self.vehicle_buffers;
+            messages.entry(neighbor_rank).or_insert_with(|| {
+                SyncMessage::with_vehicles(now, rank, neighbor_rank, buffers.take())
+            });
+        }
+        for (to, message) in messages.iter_mut() {
+            let sequence = self.out_sequences.entry(*to).or_default();
+            message.sequence = *sequence;
+            *sequence += 1;
         }
         messages
     }
 }
 
+/// Messages from one partition to another are received in the order they were sent, as mpi
+/// messages with the same tag don't overtake each other and channels are fifo. A gap in the
+/// sequence numbers means that a message was dropped, a sequence number which was already received
+/// means that a message was duplicated. Either would corrupt the simulation silently, e.g. by
+/// losing vehicles or by letting a partition wait for a message which never arrives.
+fn check_sequence(in_sequences: &mut HashMap<u32, (u64, u32)>, msg: &SyncMessage) {
+    let from = msg.from_process;
+    let to = msg.to_process;
+    if let Some((last_sequence, last_time)) = in_sequences.get(&from) {
+        let expected = last_sequence + 1;
+        if msg.sequence < expected {
+            panic!(
+                "#{to} received message #{} for time step {} from #{from} twice. The last message from #{from} was #{last_sequence} for time step {last_time}.",
+                msg.sequence, msg.time
+            );
+        }
+        if msg.sequence > expected {
+            panic!(
+                "#{to} received message #{} for time step {} from #{from}, but expected message #{expected}. {} message(s) after time step {last_time} were dropped.",
+                msg.sequence,
+                msg.time,
+                msg.sequence - expected
+            );
+        }
+        if msg.time <= *last_time {
+            panic!(
+                "#{to} received message #{} from #{from} for time step {}, which is not after time step {last_time} of the previous message.",
+                msg.sequence, msg.time
+            );
+        }
+    } else if msg.sequence != 0 {
+        panic!(
+            "#{to} received message #{} for time step {} as first message from #{from}. {} message(s) were dropped.",
+            msg.sequence, msg.time, msg.sequence
+        );
+    }
+    in_sequences.insert(from, (msg.sequence, msg.time));
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -174,11 +286,11 @@ mod tests {
     use crate::simulation::id::Id;
     use crate::simulation::messaging::communication::communicators::ChannelSimCommunicator;
     use crate::simulation::messaging::communication::message_broker::{
-        NetMessageBroker, TravelTimesMessageBroker,
+        check_sequence, NetMessageBroker, TravelTimesMessageBroker,
     };
     use crate::simulation::network::global_network::{Link, Network, Node};
     use crate::simulation::network::sim_network::{SimNetworkPartition, StorageUpdate};
-    use crate::simulation::wire_types::messages::{TravelTimesMessage, Vehicle};
+    use crate::simulation::wire_types::messages::{SyncMessage, TravelTimesMessage, Vehicle};
     use crate::test_utils::create_agent;
 
     #[test]
@@ -216,6 +328,70 @@ mod tests {
         });
     }
 
+    #[test]
+    fn send_recv_after_early_receive() {
+        execute_test(|communicator| {
+            let mut broker = create_net_message_broker(communicator);
+            broker.send_recv(0);
+
+            // messages of neighbors, which have already sent their messages for the next time step,
+            // are received early. All others are received by send_recv.
+            broker.begin_recv();
+            let result = broker.send_recv(1);
+
+            match broker.rank() {
+                0 | 1 => assert_eq!(2, result.len()),
+                2 => assert_eq!(3, result.len()),
+                3 => assert_eq!(1, result.len()),
+                _ => panic!("Not expecting this rank!"),
+            };
+            for msg in result {
+                assert_eq!(1, msg.time);
+            }
+            assert_eq!(0, broker.buffered_in_messages());
+        });
+    }
+
+    fn message(time: u32, sequence: u64) -> SyncMessage {
+        let mut message = SyncMessage::new(time, 1, 0);
+        message.sequence = sequence;
+        message
+    }
+
+    #[test]
+    fn accept_consecutive_messages() {
+        let mut in_sequences = HashMap::new();
+        check_sequence(&mut in_sequences, &message(0, 0));
+        check_sequence(&mut in_sequences, &message(1, 1));
+        check_sequence(&mut in_sequences, &message(3, 2));
+        assert_eq!(Some(&(2, 3)), in_sequences.get(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "twice")]
+    fn detect_duplicate_message() {
+        let mut in_sequences = HashMap::new();
+        check_sequence(&mut in_sequences, &message(0, 0));
+        check_sequence(&mut in_sequences, &message(1, 1));
+        check_sequence(&mut in_sequences, &message(1, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "dropped")]
+    fn detect_dropped_message() {
+        let mut in_sequences = HashMap::new();
+        check_sequence(&mut in_sequences, &message(0, 0));
+        check_sequence(&mut in_sequences, &message(2, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not after")]
+    fn detect_reordered_message() {
+        let mut in_sequences = HashMap::new();
+        check_sequence(&mut in_sequences, &message(5, 0));
+        check_sequence(&mut in_sequences, &message(4, 1));
+    }
+
     /// This test moves a vehicle from partition 0 to 2 and then to partition 3. The test involves
     /// Two send_recv steps.
     #[test]
@@ -359,6 +535,15 @@ mod tests {
             end_time: 0,
             sample_size: 0.0,
             stuck_threshold: 0,
+            max_parking_search_time: 0,
+            max_passenger_wait_time: 0,
+            core: config::SimulationCore::Tick,
+            node_capacity: None,
+            min_storage_of_one_vehicle: false,
+            parallel_links: false,
+            link_model: config::LinkModel::Queue,
+            activity_duration_interpretation:
+                config::ActivityDurationInterpretation::TryEndTimeThenDuration,
         };
         let broker = NetMessageBroker::new(
             Rc::new(communicator),
@@ -377,7 +562,7 @@ mod tests {
                 broker.add_cap_update(
                     StorageUpdate {
                         link_id: 4,
-                        released: 42.0,
+                        used: 42.0,
                         from_part: 1,
                     },
                     0,
@@ -508,16 +693,16 @@ mod tests {
     }
 
     fn create_link(id: u64, from: Id<Node>, to: Id<Node>, partition: u32) -> Link {
-        Link {
-            id: Id::new_internal(id),
+        Link::new(
+            Id::new_internal(id),
             from,
             to,
-            length: 10.0,
-            capacity: 1.0,
-            freespeed: 1.0,
-            permlanes: 0.0,
-            modes: Default::default(),
+            10.0,
+            1.0,
+            1.0,
+            0.0,
+            Default::default(),
             partition,
-        }
+        )
     }
 }
@@ -1,2 +1,3 @@
 pub mod communicators;
 pub mod message_broker;
+pub mod neighborhood;
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::ffi::c_void;
+
+use mpi::datatype::Equivalence;
+use mpi::ffi;
+use mpi::raw::AsRaw;
+use mpi::topology::{SystemCommunicator, UserCommunicator};
+use mpi::Count;
+
+/// Exchanges one message with each neighbor partition per time step via mpi neighborhood
+/// collectives. The neighbors of a partition don't change during a run, so they are described once
+/// by a distributed graph communicator. Each exchange then consists of two collectives over this
+/// graph: one for the message sizes and one for the messages. Unlike point-to-point messages, no
+/// requests have to be set up per neighbor and time step, and the mpi implementation can schedule
+/// the exchange with the knowledge of the whole neighborhood.
+///
+/// rsmpi has no wrappers for distributed graph communicators, so they are used via the raw mpi
+/// interface.
+pub struct NeighborhoodExchange {
+    graph: UserCommunicator,
+    /// Neighbors in the order in which they were passed to the graph communicator. Neighborhood
+    /// collectives send and receive the blocks of the neighbors in this order.
+    neighbors: Vec<u32>,
+    send_bytes: Vec<u8>,
+    receive_bytes: Vec<u8>,
+}
+
+impl NeighborhoodExchange {
+    /// Creates the graph communicator of the partitions. This is a collective operation, which all
+    /// processes of `world` have to call. Neighbors must be symmetric: if a partition lists another
+    /// partition as neighbor, it has to be listed as neighbor by that partition as well.
+    pub fn create(world: &SystemCommunicator, neighbors: &HashSet<u32>) -> Self {
+        let mut neighbors: Vec<u32> = neighbors.iter().copied().collect();
+        neighbors.sort_unstable();
+        let ranks: Vec<Count> = neighbors.iter().map(|n| *n as Count).collect();
+        // weights are ignored, but all processes have to either pass weights or none.
+        let weights: Vec<Count> = vec![1; ranks.len()];
+
+        let mut raw_graph = unsafe { ffi::RSMPI_COMM_NULL };
+        // SAFETY: the rank and weight arrays outlive the call and have the passed lengths. Ranks
+        // are not reordered, so that ranks of the graph communicator equal the ranks of `world`.
+        unsafe {
+            ffi::MPI_Dist_graph_create_adjacent(
+                world.as_raw(),
+                ranks.len() as Count,
+                ranks.as_ptr(),
+                weights.as_ptr(),
+                ranks.len() as Count,
+                ranks.as_ptr(),
+                weights.as_ptr(),
+                ffi::RSMPI_INFO_NULL,
+                0,
+                &mut raw_graph,
+            );
+        }
+        // SAFETY: the communicator was just created and is owned by the returned value, which
+        // frees it when it is dropped.
+        let graph = unsafe { UserCommunicator::from_raw(raw_graph) }
+            .expect("Failed to create the graph communicator of the neighbor partitions");
+
+        NeighborhoodExchange {
+            graph,
+            neighbors,
+            send_bytes: Vec::new(),
+            receive_bytes: Vec::new(),
+        }
+    }
+
+    pub fn neighbors(&self) -> &[u32] {
+        &self.neighbors
+    }
+
+    /// Sends one message to each neighbor and receives one message from each neighbor. The
+    /// messages are passed in the order of [NeighborhoodExchange::neighbors]. The received
+    /// messages are passed to `on_msg` in the same order. This is a collective operation, which
+    /// all neighbors have to call.
+    pub fn exchange<B, F>(&mut self, messages: &[B], mut on_msg: F)
+    where
+        B: AsRef<[u8]>,
+        F: FnMut(u32, &[u8]),
+    {
+        assert_eq!(
+            self.neighbors.len(),
+            messages.len(),
+            "Expected one message per neighbor."
+        );
+
+        let send_counts: Vec<Count> = messages
+            .iter()
+            .map(|msg| msg.as_ref().len() as Count)
+            .collect();
+        let mut receive_counts: Vec<Count> = vec![0; self.neighbors.len()];
+        // SAFETY: both buffers hold one count per neighbor, which is the block size of the call.
+        unsafe {
+            ffi::MPI_Neighbor_alltoall(
+                send_counts.as_ptr() as *const c_void,
+                1,
+                Count::equivalent_datatype().as_raw(),
+                receive_counts.as_mut_ptr() as *mut c_void,
+                1,
+                Count::equivalent_datatype().as_raw(),
+                self.graph.as_raw(),
+            );
+        }
+
+        self.send_bytes.clear();
+        for msg in messages {
+            self.send_bytes.extend_from_slice(msg.as_ref());
+        }
+        let send_displs = displacements(&send_counts);
+        let receive_displs = displacements(&receive_counts);
+        let receive_len = receive_counts.iter().sum::<Count>() as usize;
+        self.receive_bytes.resize(receive_len, 0);
+        // SAFETY: counts and displacements describe blocks within the send and receive buffers,
+        // which were sized accordingly.
+        unsafe {
+            ffi::MPI_Neighbor_alltoallv(
+                self.send_bytes.as_ptr() as *const c_void,
+                send_counts.as_ptr(),
+                send_displs.as_ptr(),
+                u8::equivalent_datatype().as_raw(),
+                self.receive_bytes.as_mut_ptr() as *mut c_void,
+                receive_counts.as_ptr(),
+                receive_displs.as_ptr(),
+                u8::equivalent_datatype().as_raw(),
+                self.graph.as_raw(),
+            );
+        }
+
+        for (i, neighbor) in self.neighbors.iter().enumerate() {
+            let begin = receive_displs[i] as usize;
+            let end = begin + receive_counts[i] as usize;
+            on_msg(*neighbor, &self.receive_bytes[begin..end]);
+        }
+    }
+}
+
+/// Offsets of consecutive blocks with the given lengths.
+fn displacements(counts: &[Count]) -> Vec<Count> {
+    counts
+        .iter()
+        .scan(0, |offset, count| {
+            let displ = *offset;
+            *offset += count;
+            Some(displ)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::messaging::communication::neighborhood::displacements;
+
+    #[test]
+    fn displacements_of_blocks() {
+        assert_eq!(vec![0, 3, 3, 10], displacements(&[3, 0, 7, 2]));
+        assert!(displacements(&[]).is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use tracing::{info, instrument};
 
@@ -40,6 +41,10 @@ impl EventsSubscriber for EventsLogger {
     }
 }
 
+/// Creates an events subscriber for the partition with the given rank.
+pub type EventsSubscriberFactory =
+    Arc<dyn Fn(u32) -> Box<dyn EventsSubscriber + Send> + Send + Sync>;
+
 #[derive(Default, Debug)]
 pub struct EventsPublisher {
     handlers: Vec<Box<dyn EventsSubscriber + Send>>,
@@ -117,6 +122,7 @@ impl Event {
                 r#type: String::from(event_type),
                 attrs,
             })),
+            sequence: 0,
         }
     }
 
@@ -131,6 +137,7 @@ impl Event {
                 link,
                 act_type,
             })),
+            sequence: 0,
         }
     }
 
@@ -141,18 +148,21 @@ impl Event {
                 link,
                 act_type,
             })),
+            sequence: 0,
         }
     }
 
     pub fn new_link_enter(link: u64, vehicle: u64) -> Event {
         Event {
             r#type: Some(LinkEnter(LinkEnterEvent { link, vehicle })),
+            sequence: 0,
         }
     }
 
     pub fn new_link_leave(link: u64, vehicle: u64) -> Event {
         Event {
             r#type: Some(LinkLeave(LinkLeaveEvent { link, vehicle })),
+            sequence: 0,
         }
     }
 
@@ -162,6 +172,7 @@ impl Event {
                 person,
                 vehicle,
             })),
+            sequence: 0,
         }
     }
 
@@ -171,6 +182,7 @@ impl Event {
                 person,
                 vehicle,
             })),
+            sequence: 0,
         }
     }
 
@@ -181,16 +193,19 @@ impl Event {
                 link,
                 leg_mode,
             })),
+            sequence: 0,
         }
     }
 
-    pub fn new_arrival(person: u64, link: u64, leg_mode: u64) -> Event {
+    pub fn new_arrival(person: u64, link: u64, leg_mode: u64, distance: f64) -> Event {
         Event {
             r#type: Some(Arrival(ArrivalEvent {
                 person,
                 link,
                 leg_mode,
+                distance,
             })),
+            sequence: 0,
         }
     }
 
@@ -201,6 +216,7 @@ impl Event {
                 distance,
                 mode,
             })),
+            sequence: 0,
         }
     }
 }
@@ -0,0 +1,224 @@
+use std::any::Any;
+use std::mem;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use crate::simulation::config::QueueFull;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::wire_types::events::Event;
+
+/// Events of one time step.
+struct Batch {
+    time: u32,
+    events: Vec<Event>,
+}
+
+/// Counters of the events queue. These are only updated on the simulation thread.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EventsQueueStats {
+    pub batches: u64,
+    pub events: u64,
+    pub dropped_events: u64,
+    /// Time the simulation waited for the writer thread.
+    pub blocked: Duration,
+}
+
+/// Subscriber which collects the events of a time step and passes them in one batch to another
+/// subscriber, which runs on its own thread. The number of batches waiting for the writer thread is
+/// bounded. If the queue is full, the simulation either waits or drops the batch.
+pub struct QueuedEventsSubscriber {
+    batch: Batch,
+    sender: Option<SyncSender<Batch>>,
+    writer: Option<JoinHandle<()>>,
+    on_full: QueueFull,
+    stats: EventsQueueStats,
+}
+
+impl QueuedEventsSubscriber {
+    pub fn new(
+        mut subscriber: Box<dyn EventsSubscriber + Send>,
+        capacity: usize,
+        on_full: QueueFull,
+    ) -> Self {
+        let (sender, receiver) = sync_channel::<Batch>(capacity);
+        let writer = std::thread::Builder::new()
+            .name(String::from("events-writer"))
+            .spawn(move || {
+                // the loop ends, once the sender is dropped
+                for batch in receiver {
+                    for event in &batch.events {
+                        subscriber.receive_event(batch.time, event);
+                    }
+                }
+                subscriber.finish();
+            })
+            .expect("Failed to spawn events writer thread");
+
+        QueuedEventsSubscriber {
+            batch: Batch {
+                time: 0,
+                events: Vec::new(),
+            },
+            sender: Some(sender),
+            writer: Some(writer),
+            on_full,
+            stats: EventsQueueStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> &EventsQueueStats {
+        &self.stats
+    }
+
+    fn send_batch(&mut self) {
+        if self.batch.events.is_empty() {
+            return;
+        }
+        let capacity = self.batch.events.len();
+        let batch = mem::replace(
+            &mut self.batch,
+            Batch {
+                time: self.batch.time,
+                events: Vec::with_capacity(capacity),
+            },
+        );
+        let num_events = batch.events.len() as u64;
+        let sender = self
+            .sender
+            .as_ref()
+            .expect("Events queue was already finished.");
+
+        match sender.try_send(batch) {
+            Ok(()) => {}
+            Err(TrySendError::Full(batch)) => match self.on_full {
+                QueueFull::Block => {
+                    let start = Instant::now();
+                    sender
+                        .send(batch)
+                        .expect("Events writer thread has stopped.");
+                    self.stats.blocked += start.elapsed();
+                }
+                QueueFull::Drop => {
+                    self.stats.dropped_events += num_events;
+                    return;
+                }
+            },
+            Err(TrySendError::Disconnected(_)) => panic!("Events writer thread has stopped."),
+        }
+        self.stats.batches += 1;
+        self.stats.events += num_events;
+    }
+}
+
+impl EventsSubscriber for QueuedEventsSubscriber {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        if time != self.batch.time {
+            self.send_batch();
+            self.batch.time = time;
+        }
+        self.batch.events.push(event.clone());
+    }
+
+    fn finish(&mut self) {
+        self.send_batch();
+        // dropping the sender ends the writer thread, once it has processed all batches.
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            writer.join().expect("Events writer thread panicked");
+        }
+        info!(
+            "Events queue passed {} events in {} batches. Dropped {} events. Waited {}ms for the writer.",
+            self.stats.events,
+            self.stats.batches,
+            self.stats.dropped_events,
+            self.stats.blocked.as_millis()
+        );
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use crate::simulation::config::QueueFull;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::messaging::events_queue::QueuedEventsSubscriber;
+    use crate::simulation::wire_types::events::Event;
+
+    /// Forwards events to the test thread. It blocks until the test allows it to proceed.
+    struct BlockingSubscriber {
+        events: Sender<(u32, Event)>,
+        proceed: Receiver<()>,
+    }
+
+    impl EventsSubscriber for BlockingSubscriber {
+        fn receive_event(&mut self, time: u32, event: &Event) {
+            self.proceed.recv().unwrap();
+            self.events.send((time, event.clone())).unwrap();
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn blocking_subscriber() -> (BlockingSubscriber, Receiver<(u32, Event)>, Sender<()>) {
+        let (events, received) = channel();
+        let (proceed_sender, proceed) = channel();
+        (
+            BlockingSubscriber { events, proceed },
+            received,
+            proceed_sender,
+        )
+    }
+
+    #[test]
+    fn pass_events_in_order() {
+        let (subscriber, received, proceed) = blocking_subscriber();
+        let mut queue = QueuedEventsSubscriber::new(Box::new(subscriber), 10, QueueFull::Block);
+        for _ in 0..4 {
+            proceed.send(()).unwrap();
+        }
+
+        queue.receive_event(1, &Event::new_arrival(1, 1, 1, 0.));
+        queue.receive_event(1, &Event::new_arrival(2, 1, 1, 0.));
+        queue.receive_event(2, &Event::new_arrival(3, 1, 1, 0.));
+        queue.receive_event(5, &Event::new_arrival(4, 1, 1, 0.));
+        queue.finish();
+
+        let times: Vec<_> = received.try_iter().map(|(time, _)| time).collect();
+        assert_eq!(vec![1, 1, 2, 5], times);
+        assert_eq!(3, queue.stats().batches);
+        assert_eq!(4, queue.stats().events);
+        assert_eq!(0, queue.stats().dropped_events);
+    }
+
+    #[test]
+    fn drop_if_full() {
+        let (subscriber, received, proceed) = blocking_subscriber();
+        let mut queue = QueuedEventsSubscriber::new(Box::new(subscriber), 1, QueueFull::Drop);
+
+        // the writer blocks on the first batch it takes. The queue holds at most one more batch.
+        // Therefore, some of the following batches are dropped.
+        for time in 0..6 {
+            queue.receive_event(time, &Event::new_arrival(time as u64, 1, 1, 0.));
+        }
+        for _ in 0..6 {
+            proceed.send(()).unwrap();
+        }
+        queue.finish();
+
+        let received = received.try_iter().count() as u64;
+        assert_eq!(queue.stats().events, received);
+        assert_eq!(6, queue.stats().events + queue.stats().dropped_events);
+        assert!(queue.stats().dropped_events > 0);
+    }
+}
@@ -4,12 +4,13 @@ use std::io::Cursor;
 
 use prost::Message;
 
-use crate::simulation::time_queue::EndTime;
+use crate::simulation::time_queue::{EndTime, QueueId};
 use crate::simulation::wire_types::messages::sim_message::Type;
 use crate::simulation::wire_types::messages::{
-    Empty, SimMessage, StorageCap, SyncMessage, TravelTimesMessage, Vehicle,
+    Empty, PersonsMessage, SimMessage, StorageCap, SyncMessage, TravelTimesMessage, Vehicle,
 };
 use crate::simulation::wire_types::population::Person;
+use crate::simulation::wire_types::vehicles::VehicleType;
 
 impl SimMessage {
     pub fn sync_message(self) -> SyncMessage {
@@ -26,6 +27,13 @@ impl SimMessage {
         }
     }
 
+    pub fn persons_message(self) -> PersonsMessage {
+        match self.r#type.unwrap() {
+            Type::Persons(m) => m,
+            _ => panic!("That message is no persons message."),
+        }
+    }
+
     pub fn from_sync_message(m: SyncMessage) -> SimMessage {
         SimMessage {
             r#type: Some(Type::Sync(m)),
@@ -38,6 +46,12 @@ impl SimMessage {
         }
     }
 
+    pub fn from_persons_message(m: PersonsMessage) -> SimMessage {
+        SimMessage {
+            r#type: Some(Type::Persons(m)),
+        }
+    }
+
     pub fn from_empty(m: Empty) -> SimMessage {
         SimMessage {
             r#type: Some(Type::Barrier(m)),
@@ -50,6 +64,13 @@ impl SimMessage {
         buffer
     }
 
+    /// Serializes the message into an existing buffer, so that its allocation can be reused.
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.reserve(self.encoded_len());
+        self.encode(buffer).unwrap();
+    }
+
     pub fn deserialize(buffer: &[u8]) -> SimMessage {
         SimMessage::decode(&mut Cursor::new(buffer)).unwrap()
     }
@@ -63,6 +84,19 @@ impl SyncMessage {
             to_process: to,
             vehicles: Vec::new(),
             storage_capacities: Vec::new(),
+            sequence: 0,
+        }
+    }
+
+    /// Creates a message which stores its vehicles in the given, usually recycled, vec.
+    pub fn with_vehicles(time: u32, from: u32, to: u32, vehicles: Vec<Vehicle>) -> Self {
+        Self {
+            time,
+            from_process: from,
+            to_process: to,
+            vehicles,
+            storage_capacities: Vec::new(),
+            sequence: 0,
         }
     }
 
@@ -117,9 +151,63 @@ impl Vehicle {
             curr_route_elem: 0,
             r#type: veh_type,
             max_v,
-            pce,
+            base_pce: pce,
             passengers: vec![],
+            capacity: 0,
+            fef: 1.,
+            pce_per_passenger: 0.,
+        }
+    }
+
+    /// Creates a vehicle with the properties of its type. Apart from the driver, it can carry as
+    /// many passengers as the type has seats and standing room.
+    pub fn from_type(id: u64, veh_type: &VehicleType, driver: Option<Person>) -> Vehicle {
+        Vehicle {
+            fef: veh_type.fef,
+            ..Vehicle::new(id, veh_type.id, veh_type.max_v, veh_type.pce, driver)
         }
+        .with_capacity((veh_type.seats + veh_type.standing_room).saturating_sub(1))
+    }
+
+    pub fn with_pce_per_passenger(mut self, pce_per_passenger: f32) -> Vehicle {
+        self.pce_per_passenger = pce_per_passenger;
+        self
+    }
+
+    /// Passenger car equivalents the vehicle currently occupies on links. It depends on the number
+    /// of passengers on board, which doesn't change while the vehicle is on a link. Thus, vehicles
+    /// release the same storage capacity they have consumed.
+    pub fn pce(&self) -> f32 {
+        self.base_pce + self.pce_per_passenger * self.passengers.len() as f32
+    }
+
+    pub fn with_capacity(mut self, capacity: u32) -> Vehicle {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn free_seats(&self) -> u32 {
+        self.capacity.saturating_sub(self.passengers.len() as u32)
+    }
+
+    /// Lets the passenger board the vehicle. If all seats are occupied the passenger is handed back
+    /// to the caller.
+    pub fn board(&mut self, passenger: Person) -> Result<(), Person> {
+        if self.free_seats() == 0 {
+            return Err(passenger);
+        }
+        self.passengers.push(passenger);
+        Ok(())
+    }
+
+    /// Removes all passengers from the vehicle.
+    pub fn alight_all(&mut self) -> Vec<Person> {
+        std::mem::take(&mut self.passengers)
+    }
+
+    /// Number of persons in the vehicle, including the driver.
+    pub fn num_agents(&self) -> usize {
+        self.driver.iter().count() + self.passengers.len()
     }
 
     pub fn driver(&self) -> &Person {
@@ -143,6 +231,23 @@ impl Vehicle {
         self.curr_route_elem = route_len - 1;
     }
 
+    /// Moves the pointer of the route to the next occurrence of `link`, e.g. if the vehicle is
+    /// injected into the network in the middle of its route. Panics, if the rest of the route
+    /// doesn't contain the link.
+    pub fn route_index_to(&mut self, link: u64) {
+        let route = &self.driver().curr_leg().route.as_ref().unwrap().route;
+        let offset = route[self.curr_route_elem as usize..]
+            .iter()
+            .position(|l| *l == link)
+            .unwrap_or_else(|| {
+                panic!(
+                    "The rest of the route of vehicle {} doesn't contain link {link}.",
+                    self.id
+                )
+            });
+        self.curr_route_elem += offset as u32;
+    }
+
     pub fn curr_link_id(&self) -> Option<u64> {
         let leg = self.driver().curr_leg();
         let route = leg.route.as_ref().unwrap();
@@ -169,3 +274,9 @@ impl EndTime for Vehicle {
         self.driver().end_time(now)
     }
 }
+
+impl QueueId for Vehicle {
+    fn queue_id(&self) -> u64 {
+        self.id
+    }
+}
@@ -1,3 +1,6 @@
 pub mod communication;
 pub mod events;
+pub mod events_queue;
 pub mod messages;
+pub mod pool;
+pub mod sync_codec;
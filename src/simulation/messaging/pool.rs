@@ -0,0 +1,76 @@
+/// Values which can be reset to an empty state, while keeping their allocated memory.
+pub trait Recycle {
+    fn recycle(&mut self);
+}
+
+impl<T> Recycle for Vec<T> {
+    fn recycle(&mut self) {
+        self.clear();
+    }
+}
+
+/// Keeps allocations of values which were handed back, so that they can be reused in the next
+/// time step instead of allocating new ones. The number of kept values is limited, so that a
+/// single peak doesn't hold on to memory for the rest of the simulation.
+#[derive(Debug)]
+pub struct Pool<T> {
+    free: Vec<T>,
+    max_size: usize,
+}
+
+impl<T: Default + Recycle> Pool<T> {
+    pub fn new(max_size: usize) -> Self {
+        Pool {
+            free: Vec::new(),
+            max_size,
+        }
+    }
+
+    /// Returns a previously used value, or a new one if the pool is empty.
+    pub fn take(&mut self) -> T {
+        self.free.pop().unwrap_or_default()
+    }
+
+    /// Hands back a value. It is reset before it is reused.
+    pub fn put(&mut self, mut value: T) {
+        if self.free.len() < self.max_size {
+            value.recycle();
+            self.free.push(value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::messaging::pool::Pool;
+
+    #[test]
+    fn reuse_allocation() {
+        let mut pool: Pool<Vec<u8>> = Pool::new(1);
+        let mut buffer = pool.take();
+        buffer.extend_from_slice(&[1, 2, 3]);
+        let capacity = buffer.capacity();
+        pool.put(buffer);
+
+        let buffer = pool.take();
+        assert!(buffer.is_empty());
+        assert_eq!(capacity, buffer.capacity());
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn limit_size() {
+        let mut pool: Pool<Vec<u8>> = Pool::new(1);
+        pool.put(vec![1]);
+        pool.put(vec![2]);
+        assert_eq!(1, pool.len());
+    }
+}
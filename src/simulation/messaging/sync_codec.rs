@@ -0,0 +1,180 @@
+//! Serialization of sync messages which are exchanged between processes. By default, messages are
+//! encoded with protobuf. With the cargo feature `rkyv`, messages are archived with rkyv instead.
+//! Archived messages are validated in place, and then deserialized into an owned [SyncMessage],
+//! because the received vehicles are moved into the link queues of the partition. This is not
+//! zero-copy, but it skips decoding varints and length delimited fields. Whether this pays off
+//! depends on the scenario. See the ignored benchmark `compare_with_protobuf`.
+
+use crate::simulation::wire_types::messages::SyncMessage;
+
+#[cfg(not(feature = "rkyv"))]
+pub type SyncBuffer = Vec<u8>;
+
+/// rkyv requires archived data to be aligned, so we can't receive into a plain Vec<u8>.
+#[cfg(feature = "rkyv")]
+pub type SyncBuffer = rkyv::AlignedVec;
+
+#[cfg(feature = "rkyv")]
+impl crate::simulation::messaging::pool::Recycle for rkyv::AlignedVec {
+    fn recycle(&mut self) {
+        self.clear();
+    }
+}
+
+/// Serializes the message into the buffer. The allocation of the buffer is reused.
+#[cfg(not(feature = "rkyv"))]
+pub fn encode(message: SyncMessage, buffer: &mut SyncBuffer) {
+    use crate::simulation::wire_types::messages::SimMessage;
+
+    SimMessage::from_sync_message(message).serialize_into(buffer);
+}
+
+#[cfg(not(feature = "rkyv"))]
+pub fn decode(bytes: &[u8]) -> SyncMessage {
+    use crate::simulation::wire_types::messages::SimMessage;
+
+    SimMessage::deserialize(bytes).sync_message()
+}
+
+/// Serializes the message into the buffer. The allocation of the buffer is reused.
+#[cfg(feature = "rkyv")]
+pub fn encode(message: SyncMessage, buffer: &mut SyncBuffer) {
+    use rkyv::ser::serializers::{AlignedSerializer, AllocSerializer, CompositeSerializer};
+    use rkyv::ser::Serializer;
+
+    let mut reused = std::mem::replace(buffer, rkyv::AlignedVec::new());
+    reused.clear();
+    let mut serializer: AllocSerializer<4096> = CompositeSerializer::new(
+        AlignedSerializer::new(reused),
+        Default::default(),
+        Default::default(),
+    );
+    serializer
+        .serialize_value(&message)
+        .expect("Failed to archive sync message");
+    *buffer = serializer.into_serializer().into_inner();
+}
+
+/// Expects bytes which start at an aligned address, as provided by SyncBuffer. The archive is
+/// checked before it is deserialized, so that corrupted messages are rejected instead of being read
+/// out of bounds.
+#[cfg(feature = "rkyv")]
+pub fn decode(bytes: &[u8]) -> SyncMessage {
+    use rkyv::Deserialize;
+
+    let archived = rkyv::check_archived_root::<SyncMessage>(bytes)
+        .unwrap_or_else(|e| panic!("Received invalid sync message: {e}"));
+    archived
+        .deserialize(&mut rkyv::Infallible)
+        .expect("Failed to deserialize archived sync message")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::simulation::messaging::sync_codec::{decode, encode, SyncBuffer};
+    use crate::simulation::wire_types::messages::{SimMessage, StorageCap, SyncMessage, Vehicle};
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+
+    fn create_message(num_vehicles: u64) -> SyncMessage {
+        let mut message = SyncMessage::new(42, 1, 2);
+        for i in 0..num_vehicles {
+            let mut plan = Plan::new();
+            plan.add_act(Activity::new(1., 2., 0, 0, None, Some(10), None));
+            plan.add_leg(Leg::new(
+                Route {
+                    veh_id: i,
+                    distance: 100.,
+                    route: (0..20).collect(),
+                },
+                0,
+                0,
+                Some(10),
+            ));
+            plan.add_act(Activity::new(3., 4., 0, 19, None, None, None));
+            let person = Person::new(i, plan);
+            message.add_veh(Vehicle::new(i, 0, 13.89, 1., Some(person)));
+        }
+        message.add_storage_cap(StorageCap {
+            link_id: 7,
+            value: 2.5,
+        });
+        message
+    }
+
+    #[test]
+    fn encode_decode() {
+        let message = create_message(3);
+        let mut buffer = SyncBuffer::default();
+
+        encode(message.clone(), &mut buffer);
+        let decoded = decode(&buffer[..]);
+
+        assert_eq!(message, decoded);
+    }
+
+    #[test]
+    fn encode_reuses_buffer() {
+        let mut buffer = SyncBuffer::default();
+        encode(create_message(10), &mut buffer);
+        let first = decode(&buffer[..]);
+
+        // the second message is smaller than the first. No trailing bytes must remain.
+        encode(create_message(1), &mut buffer);
+        let second = decode(&buffer[..]);
+
+        assert_eq!(10, first.vehicles.len());
+        assert_eq!(create_message(1), second);
+    }
+
+    #[test]
+    #[cfg(feature = "rkyv")]
+    #[should_panic(expected = "Received invalid sync message")]
+    fn reject_corrupted_archive() {
+        let mut buffer = SyncBuffer::default();
+        encode(create_message(3), &mut buffer);
+        // the root of an archive is at its end. Overwriting it makes the relative pointers of the
+        // message point out of the buffer.
+        let len = buffer.len();
+        for byte in &mut buffer[len - 16..] {
+            *byte = 0xff;
+        }
+
+        decode(&buffer[..]);
+    }
+
+    /// Compares the selected backend with protobuf. Run with and without the feature `rkyv`.
+    #[test]
+    #[ignore]
+    fn compare_with_protobuf() {
+        let message = create_message(1000);
+        let iterations = 1000;
+
+        let mut buffer = SyncBuffer::default();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            encode(message.clone(), &mut buffer);
+            let decoded = decode(&buffer[..]);
+            assert_eq!(1000, decoded.vehicles.len());
+        }
+        println!(
+            "selected backend: {} bytes, {}ms",
+            buffer.len(),
+            start.elapsed().as_millis()
+        );
+
+        let mut proto_buffer = Vec::new();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            SimMessage::from_sync_message(message.clone()).serialize_into(&mut proto_buffer);
+            let decoded = SimMessage::deserialize(&proto_buffer).sync_message();
+            assert_eq!(1000, decoded.vehicles.len());
+        }
+        println!(
+            "protobuf: {} bytes, {}ms",
+            proto_buffer.len(),
+            start.elapsed().as_millis()
+        );
+    }
+}
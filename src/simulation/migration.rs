@@ -0,0 +1,29 @@
+//! Moving agents between partitions outside of the regular boundary crossing of vehicles. An agent
+//! is taken out of the state of one partition together with its vehicle, sent to another partition
+//! by the caller, and injected there at an arbitrary link and time. This is the building block for
+//! dynamic load balancing and for rebalancing of demand responsive vehicles.
+
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
+
+/// An agent, which was taken out of a partition, in the state it was in when it was taken.
+#[derive(Debug)]
+pub enum MigratingAgent {
+    /// The agent performs an activity, which ends at `end_time`.
+    Activity { agent: Person, end_time: u32 },
+    /// The agent drives a vehicle on the network. Passengers travel along with the vehicle.
+    Driving { vehicle: Vehicle },
+    /// The agent is teleported and arrives at the end of its leg at `arrival`.
+    Teleported { vehicle: Vehicle, arrival: u32 },
+}
+
+impl MigratingAgent {
+    pub fn person_id(&self) -> u64 {
+        match self {
+            MigratingAgent::Activity { agent, .. } => agent.id,
+            MigratingAgent::Driving { vehicle } | MigratingAgent::Teleported { vehicle, .. } => {
+                vehicle.driver().id
+            }
+        }
+    }
+}
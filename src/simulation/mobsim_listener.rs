@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::time_queue::TimeQueue;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
+
+/// Read only view of a partition's simulation state, which is passed to mobsim listeners.
+pub struct SimState<'a> {
+    pub time: u32,
+    pub rank: u32,
+    pub network: &'a SimNetworkPartition,
+    pub garage: &'a Garage,
+    pub activity_q: &'a TimeQueue<Person>,
+    pub teleportation_q: &'a TimeQueue<Vehicle>,
+}
+
+/// Hooks which are called around each time step of the simulation. Listeners can observe the
+/// simulation, e.g. to write snapshots or to monitor a run, without changing the main loop.
+pub trait MobsimListener {
+    /// Called before agents are woken up in the time step.
+    fn before_step(&mut self, _state: &SimState) {}
+
+    /// Called after all parts of the simulation have processed the time step.
+    fn after_step(&mut self, _state: &SimState) {}
+
+    /// Called once after the last time step.
+    fn finish(&mut self, _state: &SimState) {}
+}
+
+/// Creates the mobsim listener of a partition from the rank of the partition.
+pub type MobsimListenerFactory = Arc<dyn Fn(u32) -> Box<dyn MobsimListener> + Send + Sync>;
@@ -1,15 +1,35 @@
+pub mod agent_tracing;
+pub mod analysis;
+pub mod benchmark;
 pub mod config;
+pub mod control;
 pub mod controller;
+pub mod controller_listener;
+pub mod crash_dump;
+pub mod engines;
+pub mod evacuation;
+pub mod fares;
 pub mod id;
 pub mod io;
 pub mod logging;
 pub mod messaging;
+pub mod migration;
+pub mod mobsim_listener;
 pub mod network;
+pub mod opening_hours;
 pub mod population;
 pub mod profiling;
+pub mod random;
 pub mod replanning;
+pub mod replay;
+pub mod scenario;
+pub mod shutdown;
+pub mod signals;
 #[allow(clippy::module_inception)]
 pub mod simulation;
+pub mod stuck_agents;
+pub mod telemetry;
+pub mod teleportation;
 pub mod time_queue;
 pub mod vehicles;
 pub mod wire_types;
@@ -0,0 +1,166 @@
+//! Access restrictions ban vehicle types from links during a time window of the day, e.g. trucks
+//! from city centers or vehicles without an emission badge from low emission zones. The links of a
+//! zone are marked by a link attribute.
+//!
+//! Routers exclude the links of a zone from the routing graphs of the banned vehicle types at all
+//! times, as routes are not time dependent. The simulation only holds vehicles back while the
+//! restriction is in effect. Vehicles, whose route continues on a restricted link, wait at the end
+//! of their current link until the restriction ends, e.g. vehicles with routes from before the
+//! restrictions were introduced.
+
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+const DAY: u32 = 24 * 3600;
+
+/// A ban of vehicle types from a link between two times of the day. Windows with an end before
+/// their start span midnight, e.g. a night time ban from 22:00 to 06:00.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessRestriction {
+    pub veh_types: IntSet<u64>,
+    pub start_time: u32,
+    pub end_time: u32,
+}
+
+impl AccessRestriction {
+    pub fn is_active(&self, now: u32) -> bool {
+        let time_of_day = now % DAY;
+        if self.start_time <= self.end_time {
+            self.start_time <= time_of_day && time_of_day < self.end_time
+        } else {
+            self.start_time <= time_of_day || time_of_day < self.end_time
+        }
+    }
+
+    pub fn restricts(&self, veh_type: u64, now: u32) -> bool {
+        self.veh_types.contains(&veh_type) && self.is_active(now)
+    }
+}
+
+/// Access restrictions of all links by internal link id.
+#[derive(Debug, Clone, Default)]
+pub struct AccessRestrictions {
+    by_link: IntMap<u64, Vec<AccessRestriction>>,
+}
+
+impl AccessRestrictions {
+    /// Applies the restrictions of each zone to all links, which have the attribute of the zone.
+    /// Attributes with the value `false` don't mark a link.
+    pub fn from_config(config: &config::AccessRestrictions, network: &Network) -> Self {
+        let mut by_link: IntMap<u64, Vec<AccessRestriction>> = IntMap::default();
+        for zone in &config.zones {
+            let restriction = AccessRestriction {
+                veh_types: zone
+                    .veh_types
+                    .iter()
+                    .map(|veh_type| Id::<VehicleType>::create(veh_type).internal())
+                    .collect(),
+                start_time: zone.start_time % DAY,
+                end_time: zone.end_time.min(DAY),
+            };
+            let links = network.links.iter().filter(|link| {
+                link.attributes
+                    .get(&zone.attribute)
+                    .is_some_and(|value| value != "false")
+            });
+            for link in links {
+                by_link
+                    .entry(link.id.internal())
+                    .or_default()
+                    .push(restriction.clone());
+            }
+        }
+        AccessRestrictions { by_link }
+    }
+
+    pub fn get(&self, link_id: u64) -> &[AccessRestriction] {
+        self.by_link
+            .get(&link_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether vehicles of the type may not enter the link at some time of the day.
+    pub fn restricts_any_time(&self, link_id: u64, veh_type: u64) -> bool {
+        self.get(link_id)
+            .iter()
+            .any(|restriction| restriction.veh_types.contains(&veh_type))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_link.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::config;
+    use crate::simulation::id::Id;
+    use crate::simulation::network::access_restrictions::{AccessRestriction, AccessRestrictions};
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::wire_types::vehicles::VehicleType;
+
+    #[test]
+    fn time_windows() {
+        let day = AccessRestriction {
+            veh_types: [1].into_iter().collect(),
+            start_time: 7 * 3600,
+            end_time: 19 * 3600,
+        };
+        assert!(!day.is_active(7 * 3600 - 1));
+        assert!(day.is_active(7 * 3600));
+        assert!(!day.is_active(19 * 3600));
+        // the next day
+        assert!(day.is_active(31 * 3600));
+        assert!(day.restricts(1, 8 * 3600));
+        assert!(!day.restricts(2, 8 * 3600));
+
+        let night = AccessRestriction {
+            veh_types: [1].into_iter().collect(),
+            start_time: 22 * 3600,
+            end_time: 6 * 3600,
+        };
+        assert!(night.is_active(23 * 3600));
+        assert!(night.is_active(5 * 3600));
+        assert!(!night.is_active(12 * 3600));
+    }
+
+    #[test]
+    fn restrict_links_by_attribute() {
+        let mut network = Network::new();
+        let from = Node::new(Id::create("ar-from"), 0., 0., 0, 1);
+        let to = Node::new(Id::create("ar-to"), 100., 0., 0, 1);
+        let mut lez = Link::new_with_default(Id::create("ar-lez"), &from, &to);
+        lez.attributes
+            .insert(String::from("lez"), String::from("true"));
+        let mut other = Link::new_with_default(Id::create("ar-other"), &to, &from);
+        other
+            .attributes
+            .insert(String::from("lez"), String::from("false"));
+        network.add_node(from);
+        network.add_node(to);
+        network.add_link(lez);
+        network.add_link(other);
+
+        let config = config::AccessRestrictions {
+            zones: vec![config::RestrictedZone {
+                attribute: String::from("lez"),
+                veh_types: vec![String::from("ar-truck")],
+                start_time: 0,
+                end_time: 24 * 3600,
+            }],
+        };
+        let restrictions = AccessRestrictions::from_config(&config, &network);
+        let truck = Id::<VehicleType>::get_from_ext("ar-truck").internal();
+        let lez: Id<Link> = Id::get_from_ext("ar-lez");
+        let other: Id<Link> = Id::get_from_ext("ar-other");
+        assert!(restrictions.restricts_any_time(lez.internal(), truck));
+        assert!(!restrictions.restricts_any_time(other.internal(), truck));
+        assert!(!restrictions.restricts_any_time(lez.internal(), truck + 1));
+        assert_eq!(1, restrictions.get(lez.internal()).len());
+    }
+}
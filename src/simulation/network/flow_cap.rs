@@ -3,6 +3,8 @@ pub struct Flowcap {
     last_update_time: u32,
     accumulated_capacity: f32,
     capacity_s: f32,
+    /// Scales the capacity temporarily, e.g. during an incident.
+    factor: f32,
 }
 
 impl Flowcap {
@@ -12,6 +14,7 @@ impl Flowcap {
             last_update_time: 0,
             accumulated_capacity: capacity_s,
             capacity_s,
+            factor: 1.,
         }
     }
 
@@ -21,8 +24,8 @@ impl Flowcap {
     pub fn update_capacity(&mut self, now: u32) {
         if self.last_update_time < now {
             let time_steps: f32 = (now - self.last_update_time) as f32;
-            let acc_flow_cap = time_steps * self.capacity_s + self.accumulated_capacity;
-            self.accumulated_capacity = f32::min(acc_flow_cap, self.capacity_s);
+            let acc_flow_cap = time_steps * self.capacity() + self.accumulated_capacity;
+            self.accumulated_capacity = f32::min(acc_flow_cap, self.capacity());
             self.last_update_time = now;
         }
     }
@@ -31,12 +34,23 @@ impl Flowcap {
         self.accumulated_capacity > 1e-10
     }
 
-    pub fn consume_capacity(&mut self, by: f32) {
-        self.accumulated_capacity -= by;
+    /// Consumes the flow capacity of a vehicle with the given pce. Vehicles with a higher flow
+    /// efficiency factor consume less capacity, i.e. `pce / fef`. Non positive factors are treated
+    /// as 1, as they are the default value of vehicles which don't specify one.
+    pub fn consume_capacity(&mut self, pce: f32, fef: f32) {
+        let fef = if fef > 0. { fef } else { 1. };
+        self.accumulated_capacity -= pce / fef;
     }
 
     pub fn capacity(&self) -> f32 {
-        self.capacity_s
+        self.capacity_s * self.factor
+    }
+
+    /// Scales the capacity per second by `factor` until it is set again. Capacity, which was
+    /// accumulated before, is capped at the new capacity.
+    pub fn set_factor(&mut self, factor: f32) {
+        self.factor = factor;
+        self.accumulated_capacity = f32::min(self.accumulated_capacity, self.capacity());
     }
 }
 
@@ -57,10 +71,23 @@ mod tests {
         let mut flowcap = Flowcap::new(36000., 1.);
         assert!(flowcap.has_capacity());
 
-        flowcap.consume_capacity(20.0);
+        flowcap.consume_capacity(20.0, 1.0);
         assert!(!flowcap.has_capacity());
     }
 
+    #[test]
+    fn flowcap_consume_capacity_fef() {
+        let mut flowcap = Flowcap::new(3600., 1.);
+
+        // a motorcycle with a fef of 2 only consumes half of the pce
+        flowcap.consume_capacity(1.0, 2.0);
+        assert_eq!(0.5, flowcap.accumulated_capacity);
+
+        // a fef of 0 is treated as 1
+        flowcap.consume_capacity(0.5, 0.0);
+        assert_eq!(0.0, flowcap.accumulated_capacity);
+    }
+
     #[test]
     fn flowcap_max_capacity_s() {
         let mut flowcap = Flowcap::new(36000., 1.);
@@ -71,13 +98,30 @@ mod tests {
         assert_eq!(20, flowcap.last_update_time);
     }
 
+    #[test]
+    fn flowcap_factor() {
+        let mut flowcap = Flowcap::new(7200., 1.);
+
+        flowcap.set_factor(0.25);
+        assert_eq!(0.5, flowcap.capacity());
+        assert_eq!(0.5, flowcap.accumulated_capacity);
+
+        flowcap.consume_capacity(1.0, 1.0);
+        flowcap.update_capacity(2);
+        assert_eq!(0.5, flowcap.accumulated_capacity);
+
+        flowcap.set_factor(1.);
+        flowcap.update_capacity(3);
+        assert_eq!(2., flowcap.accumulated_capacity);
+    }
+
     #[test]
     fn flowcap_acc_capacity() {
         let mut flowcap = Flowcap::new(900., 1.);
         assert!(flowcap.has_capacity());
 
         // accumulated_capacity should be at -0.75 after this.
-        flowcap.consume_capacity(1.0);
+        flowcap.consume_capacity(1.0, 1.0);
         assert!(!flowcap.has_capacity());
 
         // accumulated_capacity should be at -0.5
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::{collections::HashSet, path::Path};
 
@@ -7,6 +8,7 @@ use nohash_hasher::IntSet;
 use crate::simulation::config::PartitionMethod;
 use crate::simulation::id::Id;
 
+use super::access_restrictions::AccessRestrictions;
 use super::metis_partitioning;
 
 /// This is called global network but could also be renamed into network when things are sorted out a little
@@ -15,6 +17,9 @@ pub struct Network {
     pub nodes: Vec<Node>,
     pub links: Vec<Link>,
     pub effective_cell_size: f32,
+    /// Bans of vehicle types from links. They are not part of network files, but set up from the
+    /// config.
+    pub access_restrictions: AccessRestrictions,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +31,12 @@ pub struct Node {
     pub out_links: Vec<Id<Link>>,
     pub partition: u32,
     pub cmp_weight: u32,
+    /// Optional intersection capacity in PCE per hour. Limits how many vehicles can cross the
+    /// node, regardless of the capacities of its links.
+    pub capacity: Option<f32>,
+    /// Attributes of the input, which are not interpreted by the simulation. They are written back
+    /// to output networks.
+    pub attributes: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +50,20 @@ pub struct Link {
     pub permlanes: f32,
     pub modes: IntSet<Id<String>>,
     pub partition: u32,
+    pub on_street_parking: Option<u32>,
+    pub off_street_parking: Option<u32>,
+    /// Lanes reserved for a single mode, e.g. a bus lane. These lanes are subtracted from the
+    /// permlanes available to all other modes.
+    pub dedicated_lanes: Vec<DedicatedLanes>,
+    /// Attributes of the input, which are not interpreted by the simulation. They are written back
+    /// to output networks.
+    pub attributes: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedicatedLanes {
+    pub mode: Id<String>,
+    pub lanes: f32,
 }
 
 impl Default for Network {
@@ -53,6 +78,7 @@ impl Network {
             nodes: Vec::new(),
             links: Vec::new(),
             effective_cell_size: 7.5,
+            access_restrictions: AccessRestrictions::default(),
         }
     }
 
@@ -126,6 +152,27 @@ impl Network {
         self.links.get(id as usize).unwrap()
     }
 
+    /// Distance of a network route given as internal link ids. Like in matsim, the first link is
+    /// not counted, as vehicles depart at the end of it, but the last link is counted completely.
+    pub fn route_distance(&self, route: &[u64]) -> f64 {
+        route
+            .iter()
+            .skip(1)
+            .map(|id| {
+                self.links
+                    .get(*id as usize)
+                    .unwrap_or_else(|| panic!("No link with id {:?}", id))
+                    .length
+            })
+            .sum()
+    }
+
+    /// Splits the network into `num_parts` partitions. The partition of each node and link is
+    /// updated in place.
+    pub fn partition(&mut self, num_parts: u32, partition_method: PartitionMethod) {
+        Self::partition_network(self, partition_method, num_parts);
+    }
+
     fn partition_network(network: &mut Network, partition_method: PartitionMethod, num_parts: u32) {
         match partition_method {
             PartitionMethod::Metis(options) => {
@@ -169,6 +216,8 @@ impl Node {
             out_links: Vec::new(),
             partition: part,
             cmp_weight,
+            capacity: None,
+            attributes: HashMap::new(),
         }
     }
 }
@@ -196,6 +245,10 @@ impl Link {
             permlanes,
             modes,
             partition,
+            on_street_parking: None,
+            off_street_parking: None,
+            dedicated_lanes: Vec::new(),
+            attributes: HashMap::new(),
         }
     }
 
@@ -215,6 +268,15 @@ impl Link {
         )
     }
 
+    /// Number of parking spots on this link. Links without any parking information have an
+    /// unlimited number of spots.
+    pub fn parking_capacity(&self) -> Option<u32> {
+        match (self.on_street_parking, self.off_street_parking) {
+            (None, None) => None,
+            (on, off) => Some(on.unwrap_or(0) + off.unwrap_or(0)),
+        }
+    }
+
     pub fn contains_mode(&self, mode: u64) -> bool {
         self.modes.iter().map(|m| m.internal()).contains(&mode)
     }
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::population::population_data::parse_time_or_seconds;
+
+/// Temporarily reduces the flow and storage capacity of a link between `start` (inclusive) and
+/// `end` (exclusive).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub link: Id<Link>,
+    pub start: u32,
+    pub end: u32,
+    pub capacity_factor: f32,
+}
+
+/// Incidents of a scenario. Every partition applies all incidents to the links it holds. Links
+/// split between two partitions are thus reduced on both ends: the downstream partition reduces
+/// the flow and storage capacity, and the upstream partition reduces the storage capacity it checks
+/// before sending vehicles.
+#[derive(Debug, Default)]
+pub struct Incidents {
+    incidents: Vec<Incident>,
+    /// Time steps at which the capacity of links changes.
+    changes: BTreeMap<u32, Vec<u64>>,
+}
+
+impl Incidents {
+    pub fn new(incidents: Vec<Incident>) -> Self {
+        let mut changes: BTreeMap<u32, Vec<u64>> = BTreeMap::new();
+        for incident in &incidents {
+            assert!(
+                incident.start < incident.end,
+                "Incident on link {} ends before it starts.",
+                incident.link
+            );
+            assert!(
+                incident.capacity_factor >= 0.,
+                "Incident on link {} has a negative capacity factor.",
+                incident.link
+            );
+            for time in [incident.start, incident.end] {
+                let links = changes.entry(time).or_default();
+                if !links.contains(&incident.link.internal()) {
+                    links.push(incident.link.internal());
+                }
+            }
+        }
+        Incidents { incidents, changes }
+    }
+
+    /// Reads incidents from a csv file with the header `link,start,end,capacity_factor`. Times are
+    /// either seconds or `HH:MM:SS`.
+    pub fn from_file(path: &Path) -> Self {
+        let file =
+            File::open(path).unwrap_or_else(|_| panic!("Failed to open incidents at {path:?}"));
+        let incidents = BufReader::new(file)
+            .lines()
+            .skip(1)
+            .map(|line| line.expect("Failed to read incidents"))
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let values: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+                if values.len() != 4 {
+                    panic!(
+                        "Expected an incident as link,start,end,capacity_factor, but got {line}"
+                    );
+                }
+                let time = |value: &str| {
+                    parse_time_or_seconds(value)
+                        .unwrap_or_else(|| panic!("Invalid time {value} of incident {line}"))
+                };
+                Incident {
+                    link: Id::get_from_ext(values[0]),
+                    start: time(values[1]),
+                    end: time(values[2]),
+                    capacity_factor: values[3]
+                        .parse()
+                        .unwrap_or_else(|_| panic!("Invalid capacity factor of incident {line}")),
+                }
+            })
+            .collect();
+        Self::new(incidents)
+    }
+
+    /// Links whose capacity changes at `now`, with their new capacity factor. Overlapping incidents
+    /// on the same link multiply their factors.
+    pub fn changes(&self, now: u32) -> Vec<(u64, f32)> {
+        self.changes
+            .get(&now)
+            .map(|links| {
+                links
+                    .iter()
+                    .map(|link| (*link, self.capacity_factor(*link, now)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn capacity_factor(&self, link: u64, now: u32) -> f32 {
+        self.incidents
+            .iter()
+            .filter(|i| i.link.internal() == link && i.start <= now && now < i.end)
+            .map(|i| i.capacity_factor)
+            .product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.incidents.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::Id;
+    use crate::simulation::network::incidents::{Incident, Incidents};
+
+    #[test]
+    fn overlapping_incidents() {
+        let link = Id::new_internal(1);
+        let incidents = Incidents::new(vec![
+            Incident {
+                link: link.clone(),
+                start: 10,
+                end: 30,
+                capacity_factor: 0.5,
+            },
+            Incident {
+                link: link.clone(),
+                start: 20,
+                end: 40,
+                capacity_factor: 0.5,
+            },
+        ]);
+
+        assert!(incidents.changes(0).is_empty());
+        assert_eq!(vec![(1, 0.5)], incidents.changes(10));
+        assert!(incidents.changes(15).is_empty());
+        assert_eq!(vec![(1, 0.25)], incidents.changes(20));
+        assert_eq!(vec![(1, 0.5)], incidents.changes(30));
+        assert_eq!(vec![(1, 1.)], incidents.changes(40));
+    }
+}
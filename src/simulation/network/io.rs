@@ -10,7 +10,23 @@ use crate::simulation::id::Id;
 use crate::simulation::io::attributes::{Attr, Attrs};
 use crate::simulation::io::matsim_id::MatsimId;
 use crate::simulation::io::xml;
-use crate::simulation::network::global_network::{Link, Network, Node};
+use crate::simulation::network::global_network::{DedicatedLanes, Link, Network, Node};
+
+const ON_STREET_PARKING: &str = "onstreetParking";
+const OFF_STREET_PARKING: &str = "offstreetParking";
+const NODE_CAPACITY: &str = "capacity";
+const DEDICATED_LANES: &str = "dedicatedLanes";
+const PARTITION: &str = "partition";
+const CMP_WEIGHT: &str = "cmp_weight";
+/// Node attributes, which are stored in the fields of [Node].
+const NODE_ATTRIBUTES: [&str; 3] = [PARTITION, CMP_WEIGHT, NODE_CAPACITY];
+/// Link attributes, which are stored in the fields of [Link].
+const LINK_ATTRIBUTES: [&str; 4] = [
+    PARTITION,
+    ON_STREET_PARKING,
+    OFF_STREET_PARKING,
+    DEDICATED_LANES,
+];
 
 pub fn from_file(path: &Path) -> Network {
     if path.extension().unwrap().eq("binpb") {
@@ -53,7 +69,7 @@ fn write_to_xml(network: &Network, path: &Path) {
     let mut result = IONetwork::new(None);
 
     for node in &network.nodes {
-        let attributes = Attrs {
+        let mut attributes = Attrs {
             attributes: vec![
                 Attr {
                     name: "partition".to_string(),
@@ -67,6 +83,14 @@ fn write_to_xml(network: &Network, path: &Path) {
                 },
             ],
         };
+        if let Some(capacity) = node.capacity {
+            attributes.attributes.push(Attr {
+                name: String::from(NODE_CAPACITY),
+                value: capacity.to_string(),
+                class: String::from("java.lang.Double"),
+            });
+        }
+        attributes.extend_from_map(&node.attributes);
         let io_node = IONode {
             id: node.id.external().to_string(),
             x: node.x,
@@ -83,13 +107,39 @@ fn write_to_xml(network: &Network, path: &Path) {
             .map(|m| m.external().to_string())
             .reduce(|modes, mode| format!("{modes},{mode}"))
             .unwrap();
-        let attributes = Attrs {
+        let mut attributes = Attrs {
             attributes: vec![Attr {
                 name: String::from("partition"),
                 value: link.partition.to_string(),
                 class: String::from("java.lang.Integer"),
             }],
         };
+        for (name, value) in [
+            (ON_STREET_PARKING, link.on_street_parking),
+            (OFF_STREET_PARKING, link.off_street_parking),
+        ] {
+            if let Some(value) = value {
+                attributes.attributes.push(Attr {
+                    name: String::from(name),
+                    value: value.to_string(),
+                    class: String::from("java.lang.Integer"),
+                });
+            }
+        }
+        if !link.dedicated_lanes.is_empty() {
+            let value = link
+                .dedicated_lanes
+                .iter()
+                .map(|d| format!("{}:{}", d.mode.external(), d.lanes))
+                .reduce(|all, d| format!("{all},{d}"))
+                .unwrap();
+            attributes.attributes.push(Attr {
+                name: String::from(DEDICATED_LANES),
+                value,
+                class: String::from("java.lang.String"),
+            });
+        }
+        attributes.extend_from_map(&link.attributes);
 
         let io_link = IOLink {
             id: link.id.external().to_string(),
@@ -115,13 +165,15 @@ fn load_from_proto(path: &Path) -> Network {
     let mut result = Network::new();
     result.effective_cell_size = wire_net.effective_cell_size;
     for wn in &wire_net.nodes {
-        let node = Node::new(Id::get(wn.id), wn.x, wn.y, wn.partition, wn.cmp_weight);
+        let mut node = Node::new(Id::get(wn.id), wn.x, wn.y, wn.partition, wn.cmp_weight);
+        node.capacity = wn.capacity;
+        node.attributes = wn.attributes.clone();
         result.add_node(node);
     }
     for wl in &wire_net.links {
         let modes: IntSet<Id<String>> = wl.modes.iter().map(|id| Id::get(*id)).collect();
 
-        let link = Link::new(
+        let mut link = Link::new(
             Id::get(wl.id),
             Id::get(wl.from),
             Id::get(wl.to),
@@ -132,6 +184,17 @@ fn load_from_proto(path: &Path) -> Network {
             modes,
             wl.partition,
         );
+        link.on_street_parking = wl.on_street_parking;
+        link.off_street_parking = wl.off_street_parking;
+        link.dedicated_lanes = wl
+            .dedicated_lanes
+            .iter()
+            .map(|d| DedicatedLanes {
+                mode: Id::get(d.mode),
+                lanes: d.lanes,
+            })
+            .collect();
+        link.attributes = wl.attributes.clone();
         result.add_link(link);
     }
     info!("Finished converting protobuf wire type into Network");
@@ -149,6 +212,8 @@ fn write_to_proto(network: &Network, path: &Path) {
             y: n.y,
             partition: n.partition,
             cmp_weight: n.cmp_weight,
+            capacity: n.capacity,
+            attributes: n.attributes.clone(),
         })
         .collect();
     let links: Vec<_> = network
@@ -164,6 +229,17 @@ fn write_to_proto(network: &Network, path: &Path) {
             permlanes: l.permlanes,
             modes: l.modes.iter().map(|id| id.internal()).collect(),
             partition: l.partition,
+            on_street_parking: l.on_street_parking,
+            off_street_parking: l.off_street_parking,
+            dedicated_lanes: l
+                .dedicated_lanes
+                .iter()
+                .map(|d| crate::simulation::wire_types::network::DedicatedLanes {
+                    mode: d.mode.internal(),
+                    lanes: d.lanes,
+                })
+                .collect(),
+            attributes: l.attributes.clone(),
         })
         .collect();
 
@@ -284,19 +360,29 @@ impl IONetwork {
 
 fn add_io_node(network: &mut Network, io_node: &IONode) {
     let id = Id::create(&io_node.id);
-    let part_attr = Attrs::find_or_else_opt(&io_node.attributes, "partition", || "0");
-    let cmp_weight_attr = Attrs::find_or_else_opt(&io_node.attributes, "cmp_weight", || "1");
+    let part_attr = Attrs::find_or_else_opt(&io_node.attributes, PARTITION, || "0");
+    let cmp_weight_attr = Attrs::find_or_else_opt(&io_node.attributes, CMP_WEIGHT, || "1");
     let partition = u32::from_str(part_attr).unwrap();
     let cmp_weight = u32::from_str(cmp_weight_attr).unwrap();
 
     let mut node = Node::new(id, io_node.x, io_node.y, partition, cmp_weight);
     node.partition = partition;
+    node.capacity = io_node
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.find(NODE_CAPACITY))
+        .map(|value| {
+            f32::from_str(value).unwrap_or_else(|_| {
+                panic!("Could not parse capacity of node {}: {value}", io_node.id)
+            })
+        });
+    node.attributes = Attrs::unknown_to_map(&io_node.attributes, &NODE_ATTRIBUTES);
     network.add_node(node);
 }
 
 fn add_io_link(network: &mut Network, io_link: &IOLink) {
     let id = Id::create(&io_link.id);
-    let part_attr = Attrs::find_or_else_opt(&io_link.attributes, "partition", || "0");
+    let part_attr = Attrs::find_or_else_opt(&io_link.attributes, PARTITION, || "0");
     let partition = u32::from_str(part_attr).unwrap();
     let modes: IntSet<Id<String>> = io_link
         .modes
@@ -307,7 +393,7 @@ fn add_io_link(network: &mut Network, io_link: &IOLink) {
     let from_id = Id::get_from_ext(&io_link.from);
     let to_id = Id::get_from_ext(&io_link.to);
 
-    let link = Link::new(
+    let mut link = Link::new(
         id,
         from_id,
         to_id,
@@ -318,9 +404,71 @@ fn add_io_link(network: &mut Network, io_link: &IOLink) {
         modes,
         partition,
     );
+    link.on_street_parking = parse_parking_attr(io_link, ON_STREET_PARKING);
+    link.off_street_parking = parse_parking_attr(io_link, OFF_STREET_PARKING);
+    link.dedicated_lanes = parse_dedicated_lanes(io_link);
+    link.attributes = Attrs::unknown_to_map(&io_link.attributes, &LINK_ATTRIBUTES);
     network.add_link(link);
 }
 
+/// Parses dedicated lanes from an attribute in the format `bus:1,taxi:0.5`
+fn parse_dedicated_lanes(io_link: &IOLink) -> Vec<DedicatedLanes> {
+    let value = if let Some(value) = io_link
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.find(DEDICATED_LANES))
+    {
+        value
+    } else {
+        return Vec::new();
+    };
+
+    let dedicated_lanes: Vec<_> = value
+        .split(',')
+        .map(|entry| {
+            let (mode, lanes) = entry.trim().split_once(':').unwrap_or_else(|| {
+                panic!(
+                    "Could not parse {DEDICATED_LANES} of link {}: {value}",
+                    io_link.id
+                )
+            });
+            let lanes = f32::from_str(lanes.trim()).unwrap_or_else(|_| {
+                panic!(
+                    "Could not parse {DEDICATED_LANES} of link {}: {value}",
+                    io_link.id
+                )
+            });
+            DedicatedLanes {
+                mode: Id::create(mode.trim()),
+                lanes,
+            }
+        })
+        .collect();
+
+    // reject invalid lanes while loading the network, instead of failing once the simulation
+    // network is created on each partition.
+    let dedicated: f32 = dedicated_lanes.iter().map(|d| d.lanes).sum();
+    if dedicated_lanes.iter().any(|d| d.lanes <= 0.) || dedicated >= io_link.permlanes {
+        panic!(
+            "Invalid {DEDICATED_LANES} of link {}: {value}. Each mode needs more than 0 lanes, and the {dedicated} dedicated lanes must be less than the {} permlanes of the link, so that some capacity is left for the other modes.",
+            io_link.id, io_link.permlanes
+        );
+    }
+    dedicated_lanes
+}
+
+fn parse_parking_attr(io_link: &IOLink, name: &str) -> Option<u32> {
+    io_link
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.find(name))
+        .map(|value| {
+            u32::from_str(value).unwrap_or_else(|_| {
+                panic!("Could not parse {name} of link {}: {value}", io_link.id)
+            })
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use std::error::Error;
@@ -330,8 +478,11 @@ mod tests {
     use quick_xml::de::from_str;
 
     use crate::simulation::id::Id;
-    use crate::simulation::network::global_network::Network;
-    use crate::simulation::network::io::{add_io_link, add_io_node, IOLink, IONetwork, IONode};
+    use crate::simulation::io::attributes::{Attr, Attrs};
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::network::io::{
+        add_io_link, add_io_node, parse_dedicated_lanes, IOLink, IONetwork, IONode,
+    };
 
     static OUTPUT_FOLDER: &str = "./test_output/io/network/";
 
@@ -483,6 +634,28 @@ mod tests {
         assert_eq!(4288, network.links().len());
     }
 
+    #[test]
+    #[should_panic(expected = "network/links/link")]
+    fn read_file_with_missing_attribute() {
+        let folder = get_output_folder("read_file_with_missing_attribute");
+        fs::create_dir_all(&folder).unwrap();
+        let file_path = folder.join("network.xml");
+        let network = r#"<?xml version="1.0" encoding="UTF-8"?>
+<network>
+    <nodes>
+        <node id="n1" x="0" y="0"/>
+        <node id="n2" x="100" y="0"/>
+    </nodes>
+    <links>
+        <link id="l1" from="n1" to="n2" length="100" freespeed="10" capacity="3600" permlanes="1"/>
+        <link id="l2" from="n2" length="100" freespeed="10" capacity="3600" permlanes="1"/>
+    </links>
+</network>"#;
+        fs::write(&file_path, network).unwrap();
+
+        IONetwork::from_file(file_path.to_str().unwrap());
+    }
+
     #[test]
     fn test_add_io_node() {
         let external_id = String::from("some-id");
@@ -559,5 +732,57 @@ mod tests {
         assert!(link.modes.contains(&Id::get_from_ext("car")));
         assert!(link.modes.contains(&Id::get_from_ext("ride")));
         assert!(link.modes.contains(&Id::get_from_ext("bike")));
+        assert_eq!(None, link.parking_capacity());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid dedicatedLanes of link link")]
+    fn reject_dedicated_lanes_without_remaining_lanes() {
+        let io_link = IOLink {
+            id: String::from("link"),
+            from: String::from("from"),
+            to: String::from("to"),
+            length: 100.,
+            capacity: 100.,
+            freespeed: 10.,
+            permlanes: 2.,
+            modes: String::from("car,bus"),
+            attributes: Some(Attrs {
+                attributes: vec![Attr::new(
+                    String::from("dedicatedLanes"),
+                    String::from("bus:1,taxi:1"),
+                )],
+            }),
+        };
+        parse_dedicated_lanes(&io_link);
+    }
+
+    #[test]
+    fn unknown_attributes_round_trip() {
+        let test_name = "unknown_attributes_round_trip";
+        clear_output_folder(test_name);
+        let mut network = Network::new();
+        let mut from = Node::new(Id::create("attr-from"), 0., 0., 0, 1);
+        from.attributes
+            .insert(String::from("signal"), String::from("yes"));
+        let to = Node::new(Id::create("attr-to"), 100., 0., 0, 1);
+        let mut link = Link::new_with_default(Id::create("attr-link"), &from, &to);
+        link.modes.insert(Id::create("car"));
+        link.attributes
+            .insert(String::from("osm:way:highway"), String::from("primary"));
+        network.add_node(from);
+        network.add_node(to);
+        network.add_link(link);
+
+        for file_name in ["network.xml.gz", "network.binpb"] {
+            let path = get_output_folder(test_name).join(file_name);
+            network.to_file(&path);
+            let loaded = Network::from_file_as_is(&path);
+
+            assert_eq!(network.nodes[0].attributes, loaded.nodes[0].attributes);
+            assert_eq!(network.links[0].attributes, loaded.links[0].attributes);
+            // interpreted attributes are not duplicated.
+            assert!(!loaded.nodes[0].attributes.contains_key("partition"));
+        }
     }
 }
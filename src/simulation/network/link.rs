@@ -2,10 +2,15 @@ use std::collections::VecDeque;
 use std::fmt::Debug;
 
 use crate::simulation::config;
+use crate::simulation::config::LinkModel;
+use nohash_hasher::IntSet;
+
 use crate::simulation::id::Id;
+use crate::simulation::network::access_restrictions::AccessRestriction;
 use crate::simulation::network::flow_cap::Flowcap;
 use crate::simulation::network::global_network::Node;
 use crate::simulation::network::sim_network::StorageUpdate;
+use crate::simulation::network::speed_factors::SpeedVariation;
 use crate::simulation::network::storage_cap::StorageCap;
 use crate::simulation::network::stuck_timer::StuckTimer;
 use crate::simulation::wire_types::messages::Vehicle;
@@ -60,8 +65,8 @@ impl SimLink {
 
     pub fn flow_cap(&self) -> f32 {
         match self {
-            SimLink::Local(l) => l.flow_cap.capacity(),
-            SimLink::In(il) => il.local_link.flow_cap.capacity(),
+            SimLink::Local(l) => l.flow_capacity(),
+            SimLink::In(il) => il.local_link.flow_capacity(),
             SimLink::Out(_) => {
                 panic!("no flow cap for out links")
             }
@@ -78,6 +83,17 @@ impl SimLink {
         }
     }
 
+    /// Earliest exit time of the first vehicle in the queue.
+    pub fn front_exit_time(&self) -> Option<u32> {
+        match self {
+            SimLink::Local(ll) => ll.front_exit_time(),
+            SimLink::In(il) => il.local_link.front_exit_time(),
+            SimLink::Out(_) => {
+                panic!("Out links don't offer vehicles. ")
+            }
+        }
+    }
+
     pub fn is_veh_stuck(&self, now: u32) -> bool {
         match self {
             SimLink::Local(ll) => ll.stuck_timer.is_stuck(now),
@@ -98,19 +114,105 @@ impl SimLink {
         }
     }
 
+    /// Whether the vehicle can enter the link. Vehicles of a mode with dedicated lanes only
+    /// consider the storage capacity of those lanes. Split out links only track the storage
+    /// capacity of the whole link.
+    pub fn is_available_for(&self, vehicle: &Vehicle) -> bool {
+        match self {
+            SimLink::Local(ll) => ll.is_available_for(vehicle),
+            SimLink::In(_) => {
+                panic!("In Links can't accept vehicles")
+            }
+            SimLink::Out(ol) => ol.storage_cap.is_available_for(vehicle.pce()),
+        }
+    }
+
+    /// Whether the vehicle type of the vehicle may enter the link at all, independent of the
+    /// available capacity.
+    pub fn allows(&self, vehicle: &Vehicle) -> bool {
+        let veh_types = match self {
+            SimLink::Local(ll) => &ll.veh_types,
+            SimLink::In(il) => &il.local_link.veh_types,
+            SimLink::Out(ol) => &ol.veh_types,
+        };
+        match veh_types {
+            Some(veh_types) => veh_types.contains(&vehicle.r#type),
+            None => true,
+        }
+    }
+
+    /// Whether the vehicle type of the vehicle is banned from the link at `now` by an access
+    /// restriction.
+    pub fn is_restricted_for(&self, vehicle: &Vehicle, now: u32) -> bool {
+        let restrictions = match self {
+            SimLink::Local(ll) => &ll.access_restrictions,
+            SimLink::In(il) => &il.local_link.access_restrictions,
+            SimLink::Out(ol) => &ol.access_restrictions,
+        };
+        restrictions
+            .iter()
+            .any(|restriction| restriction.restricts(vehicle.r#type, now))
+    }
+
+    pub fn set_access_restrictions(&mut self, restrictions: Vec<AccessRestriction>) {
+        match self {
+            SimLink::Local(ll) => ll.access_restrictions = restrictions,
+            SimLink::In(il) => il.local_link.access_restrictions = restrictions,
+            SimLink::Out(ol) => ol.access_restrictions = restrictions,
+        }
+    }
+
+    /// Restricts the link to the given vehicle types. Links accept all vehicle types otherwise.
+    pub fn restrict_veh_types(&mut self, veh_types: IntSet<u64>) {
+        match self {
+            SimLink::Local(ll) => ll.veh_types = Some(veh_types),
+            SimLink::In(il) => il.local_link.veh_types = Some(veh_types),
+            SimLink::Out(ol) => ol.veh_types = Some(veh_types),
+        }
+    }
+
+    /// Entries allocated by the vehicle queues of the link. Out links don't hold vehicles.
+    pub fn queue_capacity(&self) -> usize {
+        match self {
+            SimLink::Local(ll) => ll.queue_capacity(),
+            SimLink::In(il) => il.local_link.queue_capacity(),
+            SimLink::Out(_) => 0,
+        }
+    }
+
     pub fn used_storage(&self) -> f32 {
         match self {
-            SimLink::Local(ll) => ll.storage_cap.currently_used(),
-            SimLink::In(il) => il.local_link.storage_cap.currently_used(),
+            SimLink::Local(ll) => ll.used_storage(),
+            SimLink::In(il) => il.local_link.used_storage(),
             SimLink::Out(ol) => ol.storage_cap.currently_used(),
         }
     }
 
+    /// Whether the signal at the end of the link shows red. Out links end on the downstream
+    /// partition, which owns the signal.
+    pub fn is_red(&self) -> bool {
+        match self {
+            SimLink::Local(ll) => ll.is_red(),
+            SimLink::In(il) => il.local_link.is_red(),
+            SimLink::Out(_) => false,
+        }
+    }
+
+    pub fn set_red(&mut self, red: bool) {
+        match self {
+            SimLink::Local(ll) => ll.set_red(red),
+            SimLink::In(il) => il.local_link.set_red(red),
+            SimLink::Out(_) => {
+                panic!("Can't set a signal on an out link. Its end node is on another partition.")
+            }
+        }
+    }
+
     pub fn push_veh(&mut self, vehicle: Vehicle, now: u32) {
         match self {
             SimLink::Local(l) => l.push_veh(vehicle, now),
             SimLink::In(il) => il.local_link.push_veh(vehicle, now),
-            SimLink::Out(ol) => ol.push_veh(vehicle),
+            SimLink::Out(ol) => ol.push_veh(vehicle, now),
         }
     }
 
@@ -134,6 +236,34 @@ impl SimLink {
         }
     }
 
+    /// Scales flow and storage capacity of the link. Split out links only hold the storage
+    /// capacity, which is checked before vehicles are sent to the downstream partition.
+    pub fn set_capacity_factor(&mut self, factor: f32) {
+        match self {
+            SimLink::Local(ll) => ll.set_capacity_factor(factor),
+            SimLink::In(il) => il.local_link.set_capacity_factor(factor),
+            SimLink::Out(ol) => ol.storage_cap.set_factor(factor),
+        }
+    }
+
+    /// Scales the free speed of the link. Split out links are ignored, as vehicles travel on the
+    /// downstream partition.
+    pub fn set_speed_factor(&mut self, factor: f32) {
+        match self {
+            SimLink::Local(ll) => ll.set_speed_factor(factor),
+            SimLink::In(il) => il.local_link.set_speed_factor(factor),
+            SimLink::Out(_) => {}
+        }
+    }
+
+    pub fn set_speed_variation(&mut self, variation: SpeedVariation) {
+        match self {
+            SimLink::Local(ll) => ll.set_speed_variation(variation),
+            SimLink::In(il) => il.local_link.set_speed_variation(variation),
+            SimLink::Out(_) => {}
+        }
+    }
+
     pub fn update_released_storage_cap(&mut self) {
         match self {
             SimLink::Local(l) => l.apply_storage_cap_updates(),
@@ -151,9 +281,22 @@ pub struct LocalLink {
     q: VecDeque<VehicleQEntry>,
     length: f64,
     free_speed: f32,
+    /// Scales the free speed, e.g. to model night speeds or rain.
+    speed_factor: f32,
+    speed_variation: Option<SpeedVariation>,
+    link_model: LinkModel,
     storage_cap: StorageCap,
     flow_cap: Flowcap,
     stuck_timer: StuckTimer,
+    dedicated_lanes: Vec<DedicatedLane>,
+    /// Vehicle types, which may enter the link. `None` if all types may enter.
+    veh_types: Option<IntSet<u64>>,
+    access_restrictions: Vec<AccessRestriction>,
+    /// Whether the signal at the end of the link shows red. Vehicles wait on the link until it
+    /// turns green. See [crate::simulation::signals].
+    red: bool,
+    /// Vehicles which have left the link over its end node so far.
+    outflow: u64,
     pub from: Id<Node>,
     pub to: Id<Node>,
 }
@@ -164,19 +307,69 @@ struct VehicleQEntry {
     earliest_exit_time: u32,
 }
 
+/// Lanes of a link which may only be used by one mode. They have their own queue and flow and
+/// storage capacities, so that vehicles of that mode are not held up by the remaining traffic.
+#[derive(Debug, Clone)]
+struct DedicatedLane {
+    mode: u64,
+    q: VecDeque<VehicleQEntry>,
+    storage_cap: StorageCap,
+    flow_cap: Flowcap,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Lane {
+    General,
+    Dedicated(usize),
+}
+
 impl LocalLink {
     pub fn from_link(link: &Link, effective_cell_size: f32, config: config::Simulation) -> Self {
-        LocalLink::new(
+        // dedicated lanes get their share of the link's capacity. The remaining lanes are left
+        // for all other modes.
+        let dedicated: f32 = link.dedicated_lanes.iter().map(|d| d.lanes).sum();
+        if dedicated >= link.permlanes {
+            panic!(
+                "Link {} has {} permlanes, but {dedicated} of them are dedicated to single modes. At least some capacity must be left for the other modes.",
+                link.id.external(),
+                link.permlanes
+            );
+        }
+        let share = |lanes: f32| lanes / link.permlanes;
+        let general_lanes = link.permlanes - dedicated;
+
+        let mut result = LocalLink::new(
             link.id.clone(),
-            link.capacity,
+            link.capacity * share(general_lanes),
             link.freespeed,
-            link.permlanes,
+            general_lanes,
             link.length,
             effective_cell_size,
             config,
             link.from.clone(),
             link.to.clone(),
-        )
+        );
+        result.dedicated_lanes = link
+            .dedicated_lanes
+            .iter()
+            .map(|d| {
+                let capacity_h = link.capacity * share(d.lanes);
+                DedicatedLane {
+                    mode: d.mode.internal(),
+                    q: VecDeque::new(),
+                    storage_cap: StorageCap::new(
+                        link.length,
+                        d.lanes,
+                        capacity_h,
+                        config.sample_size,
+                        effective_cell_size,
+                    )
+                    .with_min_of_one_vehicle(config.min_storage_of_one_vehicle),
+                    flow_cap: Flowcap::new(capacity_h, config.sample_size),
+                }
+            })
+            .collect();
+        result
     }
 
     pub fn new_with_defaults(id: Id<Link>, from: Id<Node>, to: Id<Node>) -> Self {
@@ -185,9 +378,17 @@ impl LocalLink {
             q: VecDeque::new(),
             length: 1.0,
             free_speed: 1.0,
+            speed_factor: 1.0,
+            speed_variation: None,
+            link_model: LinkModel::Queue,
             storage_cap: StorageCap::new(0., 1., 1., 1.0, 7.5),
             flow_cap: Flowcap::new(3600., 1.0),
             stuck_timer: StuckTimer::new(u32::MAX),
+            dedicated_lanes: Vec::new(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
+            red: false,
+            outflow: 0,
             from,
             to,
         }
@@ -210,78 +411,326 @@ impl LocalLink {
             capacity_h,
             config.sample_size,
             effective_cell_size,
-        );
+        )
+        .with_min_of_one_vehicle(config.min_storage_of_one_vehicle);
 
         LocalLink {
             id,
             q: VecDeque::new(),
             length,
             free_speed,
+            speed_factor: 1.0,
+            speed_variation: None,
+            link_model: config.link_model,
             storage_cap,
             flow_cap: Flowcap::new(capacity_h, config.sample_size),
             stuck_timer: StuckTimer::new(config.stuck_threshold),
+            dedicated_lanes: Vec::new(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
+            red: false,
+            outflow: 0,
             from,
             to,
         }
     }
 
     pub fn push_veh(&mut self, vehicle: Vehicle, now: u32) {
-        let speed = self.free_speed.min(vehicle.max_v);
+        let lane = self.lane_for(&vehicle);
+        let variation = self.speed_variation.map_or(1., |variation| {
+            variation.factor(vehicle.id, self.id.internal(), now)
+        });
+        let density = match self.link_model {
+            LinkModel::Queue => 1.,
+            LinkModel::Greenshields { min_speed_factor } => {
+                (1. - self.storage_cap(lane).occupancy()).max(min_speed_factor)
+            }
+        };
+        let speed = (self.free_speed * self.speed_factor * variation * density).min(vehicle.max_v);
         let duration = 1.max((self.length / speed as f64) as u32); // at least 1 second per link
         let earliest_exit_time = now + duration;
-
-        // update state
-        self.storage_cap.consume(vehicle.pce);
-        self.q.push_back(VehicleQEntry {
+        let entry = VehicleQEntry {
             vehicle,
             earliest_exit_time,
-        });
+        };
+
+        // update state
+        match lane {
+            Lane::General => {
+                self.storage_cap.consume(entry.vehicle.pce());
+                self.q.push_back(entry);
+            }
+            Lane::Dedicated(i) => {
+                let lane = &mut self.dedicated_lanes[i];
+                lane.storage_cap.consume(entry.vehicle.pce());
+                lane.q.push_back(entry);
+            }
+        }
     }
 
     pub fn pop_front(&mut self) -> Vehicle {
-        let veh = self.q.pop_front().unwrap_or_else(|| panic!("There was no vehicle in the queue. Use 'offers_veh' to test if a vehicle is present first."));
-        self.flow_cap.consume_capacity(veh.vehicle.pce);
-        self.storage_cap.release(veh.vehicle.pce);
+        let lane = self.next_lane().unwrap_or_else(|| panic!("There was no vehicle in the queue. Use 'offers_veh' to test if a vehicle is present first."));
+        let veh = match lane {
+            Lane::General => {
+                let veh = self.q.pop_front().unwrap();
+                self.flow_cap
+                    .consume_capacity(veh.vehicle.pce(), veh.vehicle.fef);
+                self.storage_cap.release(veh.vehicle.pce());
+                veh
+            }
+            Lane::Dedicated(i) => {
+                let lane = &mut self.dedicated_lanes[i];
+                let veh = lane.q.pop_front().unwrap();
+                lane.flow_cap
+                    .consume_capacity(veh.vehicle.pce(), veh.vehicle.fef);
+                lane.storage_cap.release(veh.vehicle.pce());
+                veh
+            }
+        };
         self.stuck_timer.reset();
+        self.outflow += 1;
         veh.vehicle
     }
 
+    /// Removes the vehicle of `driver` from the link, e.g. to move it to another partition. The
+    /// vehicle releases its storage capacity, as if it had left the link.
+    pub fn take_vehicle_of(&mut self, driver: u64) -> Option<Vehicle> {
+        let is_driver = |entry: &VehicleQEntry| {
+            entry
+                .vehicle
+                .driver
+                .as_ref()
+                .is_some_and(|person| person.id == driver)
+        };
+        if let Some(index) = self.q.iter().position(is_driver) {
+            let entry = self.q.remove(index).unwrap();
+            self.storage_cap.release(entry.vehicle.pce());
+            if index == 0 {
+                self.stuck_timer.reset();
+            }
+            return Some(entry.vehicle);
+        }
+        for lane in &mut self.dedicated_lanes {
+            if let Some(index) = lane.q.iter().position(is_driver) {
+                let entry = lane.q.remove(index).unwrap();
+                lane.storage_cap.release(entry.vehicle.pce());
+                return Some(entry.vehicle);
+            }
+        }
+        None
+    }
+
     pub fn update_flow_cap(&mut self, now: u32) {
         // increase flow cap if new time step
         self.flow_cap.update_capacity(now);
+        for lane in &mut self.dedicated_lanes {
+            lane.flow_cap.update_capacity(now);
+        }
     }
 
-    pub fn q_front(&self, now: u32) -> Option<&Vehicle> {
-        // check if we have flow cap left for current time step, otherwise abort
-        if !self.flow_cap.has_capacity() {
-            return None;
+    /// Scales the free speed of vehicles entering the link from now on.
+    pub fn set_speed_factor(&mut self, factor: f32) {
+        self.speed_factor = factor;
+    }
+
+    pub fn set_speed_variation(&mut self, variation: SpeedVariation) {
+        self.speed_variation = Some(variation);
+    }
+
+    /// Scales flow and storage capacity of all lanes.
+    pub fn set_capacity_factor(&mut self, factor: f32) {
+        self.flow_cap.set_factor(factor);
+        self.storage_cap.set_factor(factor);
+        for lane in &mut self.dedicated_lanes {
+            lane.flow_cap.set_factor(factor);
+            lane.storage_cap.set_factor(factor);
         }
+    }
+
+    pub fn q_front(&self, now: u32) -> Option<&Vehicle> {
+        // take the lane with flow cap left for the current time step, whose first vehicle leaves
+        // first. Abort, if there is none.
+        let entry = match self.next_lane()? {
+            Lane::General => self.q.front(),
+            Lane::Dedicated(i) => self.dedicated_lanes[i].q.front(),
+        }?;
 
         // peek if fist vehicle in queue can leave
-        if let Some(entry) = self.q.front() {
-            if entry.earliest_exit_time <= now {
-                self.stuck_timer.start(now);
-                return Some(&entry.vehicle);
-            }
+        if entry.earliest_exit_time <= now {
+            self.stuck_timer.start(now);
+            return Some(&entry.vehicle);
         }
 
         None
     }
 
+    /// The lane whose first vehicle may leave the link next, considering only lanes with flow
+    /// capacity left. The general lane wins ties.
+    fn next_lane(&self) -> Option<Lane> {
+        let general = self
+            .q
+            .front()
+            .filter(|_| self.flow_cap.has_capacity())
+            .map(|e| (e.earliest_exit_time, Lane::General));
+        let dedicated = self
+            .dedicated_lanes
+            .iter()
+            .enumerate()
+            .filter(|(_, lane)| lane.flow_cap.has_capacity())
+            .filter_map(|(i, lane)| {
+                lane.q
+                    .front()
+                    .map(|e| (e.earliest_exit_time, Lane::Dedicated(i)))
+            });
+        general
+            .into_iter()
+            .chain(dedicated)
+            .min_by_key(|(time, _)| *time)
+            .map(|(_, lane)| lane)
+    }
+
+    fn storage_cap(&self, lane: Lane) -> &StorageCap {
+        match lane {
+            Lane::General => &self.storage_cap,
+            Lane::Dedicated(i) => &self.dedicated_lanes[i].storage_cap,
+        }
+    }
+
+    fn lane_for(&self, vehicle: &Vehicle) -> Lane {
+        if self.dedicated_lanes.is_empty() {
+            return Lane::General;
+        }
+        let mode = vehicle.driver().curr_leg().mode;
+        self.dedicated_lanes
+            .iter()
+            .position(|lane| lane.mode == mode)
+            .map_or(Lane::General, Lane::Dedicated)
+    }
+
+    pub fn front_exit_time(&self) -> Option<u32> {
+        self.q
+            .front()
+            .into_iter()
+            .chain(
+                self.dedicated_lanes
+                    .iter()
+                    .filter_map(|lane| lane.q.front()),
+            )
+            .map(|entry| entry.earliest_exit_time)
+            .min()
+    }
+
+    /// Vehicles on the link, including the ones on dedicated lanes.
+    pub fn vehicles(&self) -> impl Iterator<Item = &Vehicle> {
+        self.q
+            .iter()
+            .chain(self.dedicated_lanes.iter().flat_map(|lane| lane.q.iter()))
+            .map(|entry| &entry.vehicle)
+    }
+
+    /// Entries allocated by the queues of all lanes.
+    pub fn queue_capacity(&self) -> usize {
+        self.q.capacity()
+            + self
+                .dedicated_lanes
+                .iter()
+                .map(|lane| lane.q.capacity())
+                .sum::<usize>()
+    }
+
+    /// Vehicles on any lane, which have reached the end of the link and wait to leave it.
+    pub fn queue_length(&self, now: u32) -> usize {
+        self.q
+            .iter()
+            .chain(self.dedicated_lanes.iter().flat_map(|lane| lane.q.iter()))
+            .filter(|entry| entry.earliest_exit_time <= now)
+            .count()
+    }
+
+    pub fn outflow(&self) -> u64 {
+        self.outflow
+    }
+
+    pub fn is_red(&self) -> bool {
+        self.red
+    }
+
+    /// Waiting at a red signal doesn't count as being stuck. The stuck timer starts over, once the
+    /// signal turns green.
+    pub fn set_red(&mut self, red: bool) {
+        if self.red && !red {
+            self.stuck_timer.reset();
+        }
+        self.red = red;
+    }
+
     pub fn veh_count(&self) -> usize {
         self.q.len()
+            + self
+                .dedicated_lanes
+                .iter()
+                .map(|lane| lane.q.len())
+                .sum::<usize>()
+    }
+
+    /// Flow capacity per second of all lanes.
+    pub fn flow_capacity(&self) -> f32 {
+        self.flow_cap.capacity()
+            + self
+                .dedicated_lanes
+                .iter()
+                .map(|lane| lane.flow_cap.capacity())
+                .sum::<f32>()
     }
 
+    /// Whether the lanes shared by all modes have storage capacity left.
     pub fn is_available(&self) -> bool {
         self.storage_cap.is_available()
     }
 
+    pub fn is_available_for(&self, vehicle: &Vehicle) -> bool {
+        match self.lane_for(vehicle) {
+            Lane::General => self.storage_cap.is_available_for(vehicle.pce()),
+            Lane::Dedicated(i) => self.dedicated_lanes[i]
+                .storage_cap
+                .is_available_for(vehicle.pce()),
+        }
+    }
+
     pub fn apply_storage_cap_updates(&mut self) {
         self.storage_cap.apply_updates();
+        for lane in &mut self.dedicated_lanes {
+            lane.storage_cap.apply_updates();
+        }
     }
 
     pub fn used_storage(&self) -> f32 {
         self.storage_cap.currently_used()
+            + self
+                .dedicated_lanes
+                .iter()
+                .map(|lane| lane.storage_cap.currently_used())
+                .sum::<f32>()
+    }
+
+    /// Storage capacity released by vehicles leaving any lane during the current time step.
+    pub fn released_storage(&self) -> f32 {
+        self.storage_cap.released()
+            + self
+                .dedicated_lanes
+                .iter()
+                .map(|lane| lane.storage_cap.released())
+                .sum::<f32>()
+    }
+
+    /// Whether vehicles have entered or left any lane during the current time step.
+    pub fn storage_changed(&self) -> bool {
+        self.released_storage() > 0.
+            || self.storage_cap.consumed() > 0.
+            || self
+                .dedicated_lanes
+                .iter()
+                .any(|lane| lane.storage_cap.consumed() > 0.)
     }
 
     pub fn from(&self) -> &Id<Node> {
@@ -293,40 +742,62 @@ impl LocalLink {
     }
 }
 
+/// The upstream end of a link whose downstream end is simulated on another partition. The storage
+/// capacity of the link is owned by the downstream partition, which reports its used capacity
+/// whenever it changes. Vehicles sent in the meantime reserve storage capacity locally, until a
+/// report of the downstream partition includes them.
 #[derive(Debug, Clone)]
 pub struct SplitOutLink {
     pub id: Id<Link>,
     pub to_part: u32,
     q: VecDeque<Vehicle>,
     storage_cap: StorageCap,
+    /// send time and pce of vehicles which are not yet confirmed by the downstream partition.
+    reservations: VecDeque<(u32, f32)>,
+    /// Vehicle types, which may enter the link. `None` if all types may enter.
+    veh_types: Option<IntSet<u64>>,
+    access_restrictions: Vec<AccessRestriction>,
 }
 
 impl SplitOutLink {
     pub fn new(
         link: &Link,
         effective_cell_size: f32,
-        sample_size: f32,
+        config: config::Simulation,
         to_part: u32,
     ) -> SplitOutLink {
         let storage_cap = StorageCap::new(
             link.length,
             link.permlanes,
             link.capacity,
-            sample_size,
+            config.sample_size,
             effective_cell_size,
-        );
+        )
+        .with_min_of_one_vehicle(config.min_storage_of_one_vehicle);
 
         SplitOutLink {
             id: link.id.clone(),
             to_part,
             q: VecDeque::default(),
             storage_cap,
+            reservations: VecDeque::default(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
         }
     }
 
-    pub fn apply_storage_cap_update(&mut self, released: f32) {
-        self.storage_cap.consume(-released);
-        self.storage_cap.apply_updates();
+    /// Applies the used storage capacity reported by the downstream partition at `time`. The report
+    /// includes all vehicles which were sent before `time`. Vehicles sent since then are still
+    /// reserved.
+    pub fn apply_storage_cap_update(&mut self, used: f32, time: u32) {
+        while let Some((sent, _)) = self.reservations.front() {
+            if *sent >= time {
+                break;
+            }
+            self.reservations.pop_front();
+        }
+        let reserved: f32 = self.reservations.iter().map(|(_, pce)| pce).sum();
+        self.storage_cap.set_used(used + reserved);
     }
 
     pub fn take_veh(&mut self) -> VecDeque<Vehicle> {
@@ -334,8 +805,9 @@ impl SplitOutLink {
         std::mem::take(&mut self.q)
     }
 
-    pub fn push_veh(&mut self, veh: Vehicle) {
-        self.storage_cap.consume(veh.pce);
+    pub fn push_veh(&mut self, veh: Vehicle, now: u32) {
+        self.storage_cap.consume(veh.pce());
+        self.reservations.push_back((now, veh.pce()));
         self.q.push_back(veh);
     }
 }
@@ -354,12 +826,15 @@ impl SplitInLink {
         }
     }
 
+    /// Reports the storage capacity used at the end of the current time step to the upstream
+    /// partition, if vehicles have entered or left the link.
     pub fn storage_cap_updates(&self) -> Option<StorageUpdate> {
-        if self.has_released() {
-            let released = self.local_link.storage_cap.released();
+        if self.local_link.storage_changed() {
+            let used =
+                (self.local_link.used_storage() - self.local_link.released_storage()).max(0.);
             Some(StorageUpdate {
                 link_id: self.local_link.id.internal(),
-                released,
+                used,
                 from_part: self.from_part,
             })
         } else {
@@ -368,7 +843,7 @@ impl SplitInLink {
     }
 
     pub fn has_released(&self) -> bool {
-        self.local_link.storage_cap.released() > 0.
+        self.local_link.released_storage() > 0.
     }
 }
 
@@ -378,6 +853,7 @@ mod sim_link_tests {
 
     use crate::simulation::config;
     use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{DedicatedLanes, Link};
     use crate::simulation::network::link::{LocalLink, SimLink};
     use crate::simulation::wire_types::messages::Vehicle;
     use crate::test_utils;
@@ -541,6 +1017,106 @@ mod sim_link_tests {
         assert_eq!(id2, popped_vehicle2.id);
     }
 
+    #[test]
+    fn dedicated_lane() {
+        let bus: Id<String> = Id::create("dedicated-lane-bus");
+        let mut link = Link::new(
+            Id::new_internal(1),
+            Id::new_internal(1),
+            Id::new_internal(2),
+            100.,
+            720.,
+            10.,
+            2.,
+            Default::default(),
+            0,
+        );
+        link.dedicated_lanes.push(DedicatedLanes {
+            mode: bus.clone(),
+            lanes: 1.,
+        });
+        let mut link = SimLink::Local(LocalLink::from_link(&link, 7.5, test_utils::config()));
+
+        for id in 1..3 {
+            let agent = create_agent(id, vec![]);
+            link.push_veh(Vehicle::new(id, 0, 10., 1., Some(agent)), 0);
+        }
+        let mut bus_agent = create_agent(3, vec![]);
+        bus_agent.curr_leg_mut().mode = bus.internal();
+        link.push_veh(Vehicle::new(3, 0, 10., 1., Some(bus_agent)), 0);
+
+        // both lanes together have the capacity of the whole link
+        assert_approx_eq!(3., link.used_storage());
+        assert_approx_eq!(0.2, link.flow_cap());
+
+        link.update_flow_cap(10);
+        assert_eq!(1, link.pop_veh().id);
+
+        // the flow capacity of the general lane is used up, but the bus passes on its own lane
+        assert_eq!(3, link.offers_veh(10).unwrap().id);
+        assert_eq!(3, link.pop_veh().id);
+        assert!(link.offers_veh(10).is_none());
+    }
+
+    #[test]
+    fn capacity_factor() {
+        let mut link = SimLink::Local(LocalLink::new(
+            Id::new_internal(1),
+            3600.,
+            10.,
+            1.,
+            15.,
+            7.5,
+            test_utils::config(),
+            Id::new_internal(1),
+            Id::new_internal(2),
+        ));
+        link.set_capacity_factor(0.5);
+        assert_approx_eq!(0.5, link.flow_cap());
+
+        // the storage capacity of 2 vehicles is halved
+        link.push_veh(
+            Vehicle::new(1, 0, 10., 1., Some(create_agent(1, vec![]))),
+            0,
+        );
+        assert!(!link.is_available());
+
+        link.set_capacity_factor(1.);
+        assert_approx_eq!(1., link.flow_cap());
+        assert!(link.is_available());
+    }
+
+    #[test]
+    fn greenshields() {
+        let mut config = test_utils::config();
+        config.link_model = config::LinkModel::Greenshields {
+            min_speed_factor: 0.2,
+        };
+        // storage capacity of 4 vehicles
+        let mut link = SimLink::Local(LocalLink::new(
+            Id::new_internal(1),
+            3600.,
+            10.,
+            1.,
+            100.,
+            25.,
+            config,
+            Id::new_internal(1),
+            Id::new_internal(2),
+        ));
+
+        // the first vehicle enters an empty link and drives at free speed. Each following vehicle
+        // is slower by a quarter of the free speed, until the minimum speed is reached.
+        for id in 1..6 {
+            let agent = create_agent(id, vec![]);
+            link.push_veh(Vehicle::new(id, 0, 10., 1., Some(agent)), 0);
+        }
+        if let SimLink::Local(ll) = link {
+            let exit_times: Vec<u32> = ll.q.iter().map(|e| e.earliest_exit_time).collect();
+            assert_eq!(vec![10, 13, 20, 40, 50], exit_times);
+        }
+    }
+
     #[test]
     pub fn stuck_time() {
         let stuck_threshold = 10;
@@ -549,6 +1125,14 @@ mod sim_link_tests {
             end_time: 0,
             sample_size: 1.0,
             stuck_threshold,
+            max_parking_search_time: 0,
+            core: config::SimulationCore::Tick,
+            node_capacity: None,
+            min_storage_of_one_vehicle: false,
+            parallel_links: false,
+            link_model: config::LinkModel::Queue,
+            activity_duration_interpretation:
+                config::ActivityDurationInterpretation::TryEndTimeThenDuration,
         };
         let mut link = SimLink::Local(LocalLink::new(
             Id::create("stuck-link"),
@@ -587,6 +1171,14 @@ mod sim_link_tests {
             end_time: 0,
             sample_size: 1.0,
             stuck_threshold,
+            max_parking_search_time: 0,
+            core: config::SimulationCore::Tick,
+            node_capacity: None,
+            min_storage_of_one_vehicle: false,
+            parallel_links: false,
+            link_model: config::LinkModel::Queue,
+            activity_duration_interpretation:
+                config::ActivityDurationInterpretation::TryEndTimeThenDuration,
         };
         let mut link = SimLink::Local(LocalLink::new(
             Id::create("stuck-link"),
@@ -637,6 +1229,9 @@ mod out_link_tests {
             to_part: 1,
             q: Default::default(),
             storage_cap: StorageCap::new(100., 1., 1., 1., 1.),
+            reservations: Default::default(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
         });
         let id1 = 42;
         let id2 = 43;
@@ -679,11 +1274,45 @@ mod out_link_tests {
             to_part: 1,
             q: Default::default(),
             storage_cap: cap,
+            reservations: Default::default(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
         };
 
         assert_eq!(2., out_link.storage_cap.currently_used());
-        out_link.apply_storage_cap_update(2.);
+        out_link.apply_storage_cap_update(0., 1);
 
         assert_eq!(0., out_link.storage_cap.currently_used());
     }
+
+    #[test]
+    fn reserve_storage_until_confirmed() {
+        let mut link = SplitOutLink {
+            id: Id::new_internal(0),
+            to_part: 1,
+            q: Default::default(),
+            storage_cap: StorageCap::new(100., 1., 1., 1., 1.),
+            reservations: Default::default(),
+            veh_types: None,
+            access_restrictions: Vec::new(),
+        };
+        let agent1 = create_agent(1, vec![]);
+        link.push_veh(Vehicle::new(1, 0, 10., 1., Some(agent1)), 0);
+        let agent2 = create_agent(2, vec![]);
+        link.push_veh(Vehicle::new(2, 0, 10., 2., Some(agent2)), 1);
+        let _ = link.take_veh();
+
+        // the downstream partition hasn't received any vehicle at t=0. Both stay reserved
+        link.apply_storage_cap_update(0., 0);
+        assert_eq!(3., link.storage_cap.currently_used());
+
+        // the report at t=1 includes the first vehicle. Other vehicles entering the downstream end
+        // of the link are included as well.
+        link.apply_storage_cap_update(5., 1);
+        assert_eq!(7., link.storage_cap.currently_used());
+
+        // the report at t=2 includes both vehicles
+        link.apply_storage_cap_update(3., 2);
+        assert_eq!(3., link.storage_cap.currently_used());
+    }
 }
@@ -1,8 +1,13 @@
+pub mod access_restrictions;
 mod flow_cap;
 pub mod global_network;
+pub mod incidents;
 mod io;
 pub mod link;
 pub mod metis_partitioning;
+pub mod parking;
 pub mod sim_network;
+pub mod spatial_index;
+pub mod speed_factors;
 mod storage_cap;
 mod stuck_timer;
@@ -0,0 +1,234 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::simulation::network::global_network::Network;
+
+/// Parking spots on the links of one partition. Links without parking capacities have an
+/// unlimited number of spots.
+#[derive(Debug, Default)]
+pub struct Parking {
+    links: IntMap<u64, ParkingLink>,
+    out_links: IntMap<u64, Vec<u64>>,
+    parked: IntMap<u64, u64>,
+    max_search_time: u32,
+}
+
+#[derive(Debug)]
+struct ParkingLink {
+    capacity: Option<u32>,
+    occupied: u32,
+    to: u64,
+    length: f64,
+    travel_time: u32,
+}
+
+impl ParkingLink {
+    fn has_free_spot(&self) -> bool {
+        self.capacity.map_or(true, |cap| self.occupied < cap)
+    }
+}
+
+/// Result of a parking search.
+#[derive(Debug, PartialEq)]
+pub struct ParkingSpot {
+    pub link: u64,
+    pub search_time: u32,
+    pub search_distance: f64,
+    /// Whether a free spot was found within the maximum search time. Otherwise, the vehicle is
+    /// parked illegally at the destination link.
+    pub found: bool,
+}
+
+impl Parking {
+    pub fn from_network(network: &Network, partition: u32, max_search_time: u32) -> Self {
+        let links: IntMap<_, _> = network
+            .links
+            .iter()
+            .filter(|l| l.partition == partition)
+            .map(|l| {
+                let parking_link = ParkingLink {
+                    capacity: l.parking_capacity(),
+                    occupied: 0,
+                    to: l.to.internal(),
+                    length: l.length,
+                    travel_time: (l.length / l.freespeed as f64).ceil() as u32,
+                };
+                (l.id.internal(), parking_link)
+            })
+            .collect();
+        let out_links = network
+            .nodes
+            .iter()
+            .filter(|n| n.partition == partition)
+            .map(|n| {
+                let out: Vec<_> = n
+                    .out_links
+                    .iter()
+                    .map(|l| l.internal())
+                    .filter(|l| links.contains_key(l))
+                    .collect();
+                (n.id.internal(), out)
+            })
+            .collect();
+
+        Parking {
+            links,
+            out_links,
+            parked: IntMap::default(),
+            max_search_time,
+        }
+    }
+
+    /// Whether parking at this link is restricted and a search might be necessary.
+    pub fn is_restricted(&self, link_id: u64) -> bool {
+        self.links
+            .get(&link_id)
+            .map_or(false, |l| l.capacity.is_some())
+    }
+
+    /// Parks the vehicle at the closest link with a free spot, starting with the destination link.
+    /// The search is not simulated on the network. Instead, vehicles are assumed to cruise with
+    /// freespeed along the links of this partition, and the first link with a free spot in terms of
+    /// travel time is taken.
+    pub fn park(&mut self, veh_id: u64, destination: u64) -> ParkingSpot {
+        let mut visited = IntSet::default();
+        // travel time and distance of the fastest path found so far for each link
+        let mut best: IntMap<u64, (u32, f64)> = IntMap::default();
+        let mut queue = BinaryHeap::new();
+        // the vehicle is at the end of its destination link already. Searching there is free.
+        queue.push(Reverse((0, destination)));
+        best.insert(destination, (0, 0.));
+
+        let spot = loop {
+            let Reverse((time, link_id)) = if let Some(next) = queue.pop() {
+                next
+            } else {
+                break None;
+            };
+            if time > self.max_search_time {
+                break None;
+            }
+            if !visited.insert(link_id) {
+                continue;
+            }
+            let link = if let Some(link) = self.links.get(&link_id) {
+                link
+            } else {
+                continue;
+            };
+            let (_, distance) = *best.get(&link_id).unwrap();
+            if link.has_free_spot() {
+                break Some(ParkingSpot {
+                    link: link_id,
+                    search_time: time,
+                    search_distance: distance,
+                    found: true,
+                });
+            }
+            for out_id in self.out_links.get(&link.to).into_iter().flatten() {
+                if visited.contains(out_id) {
+                    continue;
+                }
+                let out = self.links.get(out_id).unwrap();
+                let out_time = time + out.travel_time;
+                if best.get(out_id).map_or(true, |(t, _)| out_time < *t) {
+                    best.insert(*out_id, (out_time, distance + out.length));
+                    queue.push(Reverse((out_time, *out_id)));
+                }
+            }
+        };
+
+        let spot = spot.unwrap_or(ParkingSpot {
+            link: destination,
+            search_time: self.max_search_time,
+            search_distance: 0.,
+            found: false,
+        });
+        if let Some(link) = self.links.get_mut(&spot.link) {
+            link.occupied += 1;
+        }
+        self.parked.insert(veh_id, spot.link);
+        spot
+    }
+
+    /// Releases the spot of a vehicle, when it departs. Does nothing if the vehicle was not parked.
+    pub fn release(&mut self, veh_id: u64) {
+        if let Some(link_id) = self.parked.remove(&veh_id) {
+            if let Some(link) = self.links.get_mut(&link_id) {
+                link.occupied = link.occupied.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::network::parking::Parking;
+
+    /// n1 --l1--> n2 --l2--> n3 --l3--> n1, each link 100m long with a freespeed of 10m/s
+    fn create_network() -> Network {
+        let mut network = Network::new();
+        let n1 = Node::new(Id::create("parking-n1"), 0., 0., 0, 1);
+        let n2 = Node::new(Id::create("parking-n2"), 100., 0., 0, 1);
+        let n3 = Node::new(Id::create("parking-n3"), 200., 0., 0, 1);
+        let mut l1 = Link::new_with_default(Id::create("parking-l1"), &n1, &n2);
+        let mut l2 = Link::new_with_default(Id::create("parking-l2"), &n2, &n3);
+        let mut l3 = Link::new_with_default(Id::create("parking-l3"), &n3, &n1);
+        for link in [&mut l1, &mut l2, &mut l3] {
+            link.length = 100.;
+            link.freespeed = 10.;
+        }
+        l1.on_street_parking = Some(1);
+        l2.off_street_parking = Some(1);
+        network.add_node(n1);
+        network.add_node(n2);
+        network.add_node(n3);
+        network.add_link(l1);
+        network.add_link(l2);
+        network.add_link(l3);
+        network
+    }
+
+    #[test]
+    fn park_and_release() {
+        let network = create_network();
+        let mut parking = Parking::from_network(&network, 0, 900);
+        let l1 = Id::<Link>::get_from_ext("parking-l1").internal();
+        let l2 = Id::<Link>::get_from_ext("parking-l2").internal();
+
+        assert!(parking.is_restricted(l1));
+        let spot = parking.park(1, l1);
+        assert_eq!(l1, spot.link);
+        assert_eq!(0, spot.search_time);
+
+        // l1 is full, the next vehicle has to cruise to l2
+        let spot = parking.park(2, l1);
+        assert_eq!(l2, spot.link);
+        assert_eq!(10, spot.search_time);
+        assert_eq!(100., spot.search_distance);
+        assert!(spot.found);
+
+        // once the first vehicle departs, its spot is free again
+        parking.release(1);
+        let spot = parking.park(3, l1);
+        assert_eq!(l1, spot.link);
+    }
+
+    #[test]
+    fn park_illegally() {
+        let network = create_network();
+        let mut parking = Parking::from_network(&network, 0, 5);
+        let l1 = Id::<Link>::get_from_ext("parking-l1").internal();
+
+        parking.park(1, l1);
+        let spot = parking.park(2, l1);
+
+        // l2 can't be reached within the maximum search time
+        assert_eq!(l1, spot.link);
+        assert!(!spot.found);
+    }
+}
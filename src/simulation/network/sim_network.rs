@@ -1,26 +1,51 @@
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
 use nohash_hasher::{IntMap, IntSet};
 use rand::rngs::ThreadRng;
 use rand::{thread_rng, Rng};
+use rayon::prelude::*;
 use tracing::instrument;
 
 use crate::simulation::config;
+use crate::simulation::config::SimulationCore;
+use crate::simulation::id::id_map::IdMap;
 use crate::simulation::id::Id;
 use crate::simulation::messaging::events::EventsPublisher;
 use crate::simulation::wire_types::events::Event;
 use crate::simulation::wire_types::messages::{StorageCap, Vehicle};
 use crate::simulation::wire_types::population::Person;
+use crate::simulation::wire_types::vehicles::VehicleType;
 
 use super::{
+    flow_cap::Flowcap,
     global_network::{Link, Network, Node},
     link::{LocalLink, SimLink, SplitInLink, SplitOutLink},
+    parking::Parking,
+    speed_factors::SpeedVariation,
 };
 
+/// Minimal number of links which must be moved in a time step, before moving links in parallel pays
+/// off.
+const PARALLEL_LINKS_THRESHOLD: usize = 1024;
+
+/// Event type for vehicles, whose route continues on a link which doesn't allow their mode.
+pub const LINK_MODE_VIOLATION_EVENT: &str = "linkModeViolation";
+
 pub struct StorageUpdate {
     pub link_id: u64,
     pub from_part: u32,
-    pub released: f32,
+    /// storage capacity used on the link after the current time step
+    pub used: f32,
+}
+
+/// Occupancy of a link at the current time step, e.g. for controllers which react to congestion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkOccupancy {
+    pub link_id: u64,
+    pub vehicles: usize,
+    /// Used storage capacity in PCE.
+    pub used_storage: f32,
 }
 
 #[derive(Debug)]
@@ -30,15 +55,105 @@ pub struct SimNetworkPartition {
     pub links: IntMap<u64, SimLink>,
     rnd: ThreadRng,
     active_nodes: IntSet<u64>,
-    active_links: IntSet<u64>,
+    active_links: ActiveLinks,
+    core: SimulationCore,
     veh_counter: usize,
     partition: u32,
+    pub parking: Parking,
+    // links are moved in parallel, if at least this many links are due
+    parallel_threshold: Option<usize>,
+    // modes of transit vehicles, which are served first at nodes
+    transit_modes: Option<IntSet<u64>>,
+    // length and freespeed of all links of the global network, indexed by internal link id
+    free_flow: Vec<(f64, f32)>,
+}
+
+/// Changes which result from moving links. Moving a link only changes the link itself. Everything
+/// else is collected here and applied afterwards, so that links can be moved in parallel.
+#[derive(Default)]
+struct MovedLinks {
+    activate_nodes: Vec<u64>,
+    deactivate_links: Vec<u64>,
+    wakeups: Vec<(u64, u32)>,
+    storage_cap_updates: Vec<StorageUpdate>,
+    vehicles: Vec<Vehicle>,
+}
+
+impl MovedLinks {
+    fn append(mut self, mut other: MovedLinks) -> MovedLinks {
+        self.activate_nodes.append(&mut other.activate_nodes);
+        self.deactivate_links.append(&mut other.deactivate_links);
+        self.wakeups.append(&mut other.wakeups);
+        self.storage_cap_updates
+            .append(&mut other.storage_cap_updates);
+        self.vehicles.append(&mut other.vehicles);
+        self
+    }
+}
+
+/// Bookkeeping of links with vehicles on them. For the event driven core, this also keeps track of
+/// which links must be moved in a time step.
+#[derive(Debug, Default)]
+struct ActiveLinks {
+    links: IntSet<u64>,
+    // links on which vehicles have entered or left during the current time step
+    changed: IntSet<u64>,
+    wakeups: BinaryHeap<Reverse<(u32, u64)>>,
+}
+
+impl ActiveLinks {
+    fn activate(&mut self, link_id: u64) {
+        self.links.insert(link_id);
+        self.changed.insert(link_id);
+    }
+
+    fn deactivate(&mut self, link_id: &u64) {
+        self.links.remove(link_id);
+    }
+
+    fn contains(&self, link_id: &u64) -> bool {
+        self.links.contains(link_id)
+    }
+
+    fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    fn schedule(&mut self, link_id: u64, time: u32) {
+        self.wakeups.push(Reverse((time, link_id)));
+    }
+
+    /// Links which have changed during this time step, and active links which were scheduled to
+    /// be moved at or before `now`.
+    fn take_due(&mut self, now: u32) -> IntSet<u64> {
+        let mut due = std::mem::take(&mut self.changed);
+        while let Some(Reverse((time, link_id))) = self.wakeups.peek() {
+            if *time > now {
+                break;
+            }
+            if self.links.contains(link_id) {
+                due.insert(*link_id);
+            }
+            self.wakeups.pop();
+        }
+        due
+    }
 }
 
 #[derive(Debug)]
 pub struct SimNode {
     id: u64,
     in_links: Vec<u64>,
+    /// Intersection capacity. Limits the PCE crossing the node per time step, if present.
+    flow_cap: Option<Flowcap>,
+}
+
+impl SimNode {
+    fn has_flow_cap(&self) -> bool {
+        self.flow_cap
+            .as_ref()
+            .map_or(true, |cap| cap.has_capacity())
+    }
 }
 
 impl SimNetworkPartition {
@@ -77,18 +192,39 @@ impl SimNetworkPartition {
 
         let sim_nodes: IntMap<u64, SimNode> = nodes
             .iter()
-            .map(|n| (n.id.internal(), Self::create_sim_node(n)))
+            .map(|n| (n.id.internal(), Self::create_sim_node(n, config)))
             .collect();
 
-        Self::new(sim_nodes, sim_links, partition)
+        let mut result = Self::new(sim_nodes, sim_links, partition);
+        if !global_network.access_restrictions.is_empty() {
+            for (id, link) in result.links.iter_mut() {
+                let restrictions = global_network.access_restrictions.get(*id);
+                link.set_access_restrictions(restrictions.to_vec());
+            }
+        }
+        result.parking =
+            Parking::from_network(global_network, partition, config.max_parking_search_time);
+        result.core = config.core;
+        result.parallel_threshold = config.parallel_links.then_some(PARALLEL_LINKS_THRESHOLD);
+        result.free_flow = global_network
+            .links
+            .iter()
+            .map(|link| (link.length, link.freespeed))
+            .collect();
+        result
     }
 
-    fn create_sim_node(node: &Node) -> SimNode {
+    fn create_sim_node(node: &Node, config: config::Simulation) -> SimNode {
         let in_links: Vec<u64> = node.in_links.iter().map(|l_id| l_id.internal()).collect();
+        let flow_cap = node
+            .capacity
+            .or(config.node_capacity)
+            .map(|cap| Flowcap::new(cap, config.sample_size));
 
         SimNode {
             id: node.id.internal(),
             in_links,
+            flow_cap,
         }
     }
 
@@ -111,7 +247,7 @@ impl SimNetworkPartition {
             SimLink::Out(SplitOutLink::new(
                 link,
                 effective_cell_size,
-                config.sample_size,
+                config,
                 to_part,
             ))
         }
@@ -122,10 +258,51 @@ impl SimNetworkPartition {
             nodes,
             links,
             rnd: thread_rng(),
-            active_links: Default::default(),
+            active_links: ActiveLinks::default(),
+            core: SimulationCore::Tick,
             active_nodes: Default::default(),
             veh_counter: 0,
             partition,
+            parking: Parking::default(),
+            parallel_threshold: None,
+            transit_modes: None,
+            free_flow: Vec::new(),
+        }
+    }
+
+    /// Free flow travel time of a vehicle with `max_v` along a network route given as internal
+    /// link ids. The route may leave this partition. Like [Network::route_distance], the first
+    /// link is not counted.
+    pub fn free_flow_trav_time(&self, route: &[u64], max_v: f32) -> u32 {
+        route
+            .iter()
+            .skip(1)
+            .map(|id| {
+                let (length, freespeed) = self
+                    .free_flow
+                    .get(*id as usize)
+                    .unwrap_or_else(|| panic!("No link with id {:?}", id));
+                length / freespeed.min(max_v) as f64
+            })
+            .sum::<f64>() as u32
+    }
+
+    /// Restricts the links of this partition to the vehicle types, whose network mode they allow.
+    /// Vehicles are checked when they move from one link to the next. The departure link is not
+    /// checked, as vehicles start at its end.
+    pub fn restrict_veh_types(
+        &mut self,
+        network: &Network,
+        veh_types: &IdMap<VehicleType, VehicleType>,
+    ) {
+        for link in self.links.values_mut() {
+            let global_link = network.get_link(link.id());
+            let allowed = veh_types
+                .values()
+                .filter(|veh_type| global_link.contains_mode(veh_type.net_mode))
+                .map(|veh_type| veh_type.id)
+                .collect();
+            link.restrict_veh_types(allowed);
         }
     }
 
@@ -155,6 +332,101 @@ impl SimNetworkPartition {
         self.veh_counter
     }
 
+    /// Vehicles on the links of this partition. Vehicles on split out links are on their way to
+    /// the downstream partition and belong to it.
+    /// Entries allocated by the vehicle queues of all links of this partition.
+    pub fn link_queue_capacity(&self) -> usize {
+        self.links.values().map(SimLink::queue_capacity).sum()
+    }
+
+    pub fn vehicles(&self) -> impl Iterator<Item = &Vehicle> {
+        self.links
+            .values()
+            .filter_map(|link| match link {
+                SimLink::Local(ll) => Some(ll),
+                SimLink::In(il) => Some(&il.local_link),
+                SimLink::Out(_) => None,
+            })
+            .flat_map(|link| link.vehicles())
+    }
+
+    /// Removes the vehicle of `driver` from the links of this partition, so that it can be
+    /// injected elsewhere. Vehicles on split out links belong to the downstream partition.
+    pub fn take_vehicle_of(&mut self, driver: u64) -> Option<Vehicle> {
+        let vehicle = self.links.values_mut().find_map(|link| match link {
+            SimLink::Local(ll) => ll.take_vehicle_of(driver),
+            SimLink::In(il) => il.local_link.take_vehicle_of(driver),
+            SimLink::Out(_) => None,
+        })?;
+        self.veh_counter -= 1;
+        Some(vehicle)
+    }
+
+    /// Occupancy of a link of this partition. Split out links belong to the downstream partition.
+    pub fn link_occupancy(&self, link_id: u64) -> Option<LinkOccupancy> {
+        let link = match self.links.get(&link_id)? {
+            SimLink::Local(ll) => ll,
+            SimLink::In(il) => &il.local_link,
+            SimLink::Out(_) => return None,
+        };
+        Some(LinkOccupancy {
+            link_id,
+            vehicles: link.veh_count(),
+            used_storage: link.used_storage(),
+        })
+    }
+
+    /// Occupancy of all links of this partition, which hold vehicles.
+    pub fn link_occupancies(&self) -> impl Iterator<Item = LinkOccupancy> + '_ {
+        self.links
+            .keys()
+            .filter_map(|id| self.link_occupancy(*id))
+            .filter(|occupancy| occupancy.vehicles > 0)
+    }
+
+    /// Number of in-links of a node, whose first vehicle could have left the link by `now`, but is
+    /// still waiting, e.g. because of the flow capacity or a full downstream link. This is meant to
+    /// be queried after the nodes were moved in the time step. Returns `None`, if the node is not
+    /// part of this partition.
+    pub fn blocked_in_links(&self, node_id: u64, now: u32) -> Option<usize> {
+        let node = self.nodes.get(&node_id)?;
+        let blocked = node
+            .in_links
+            .iter()
+            .filter_map(|id| self.links.get(id).unwrap().front_exit_time())
+            .filter(|exit_time| *exit_time <= now)
+            .count();
+        Some(blocked)
+    }
+
+    /// Number of vehicles on a link, which have reached its end and wait to leave it. Returns
+    /// `None`, if the end of the link is not part of this partition.
+    pub fn queue_length(&self, link_id: u64, now: u32) -> Option<usize> {
+        match self.links.get(&link_id)? {
+            SimLink::Local(ll) => Some(ll.queue_length(now)),
+            SimLink::In(il) => Some(il.local_link.queue_length(now)),
+            SimLink::Out(_) => None,
+        }
+    }
+
+    /// Number of vehicles, which have left a link over its end node since the start of the
+    /// simulation. Returns `None`, if the end of the link is not part of this partition.
+    pub fn link_outflow(&self, link_id: u64) -> Option<u64> {
+        match self.links.get(&link_id)? {
+            SimLink::Local(ll) => Some(ll.outflow()),
+            SimLink::In(il) => Some(il.local_link.outflow()),
+            SimLink::Out(_) => None,
+        }
+    }
+
+    /// Switches the signal at the end of a link. Vehicles wait on the link, while it shows red.
+    pub fn set_signal(&mut self, link_id: u64, red: bool) {
+        self.links
+            .get_mut(&link_id)
+            .unwrap_or_else(|| panic!("Link {link_id} is not part of partition {}", self.partition))
+            .set_red(red);
+    }
+
     pub fn get_link_ids(&self) -> HashSet<u64> {
         self.links
             .iter()
@@ -203,13 +475,46 @@ impl SimNetworkPartition {
         link.push_veh(vehicle, now);
         self.veh_counter += 1;
 
-        Self::activate_link(&mut self.active_links, link.id().internal());
+        self.active_links.activate(link.id().internal());
     }
 
-    pub fn apply_storage_cap_updates(&mut self, storage_caps: Vec<StorageCap>) {
+    /// Scales the capacities of a link, if it is part of this partition. Active links are moved in
+    /// the current time step, so that the change takes effect immediately.
+    pub fn set_capacity_factor(&mut self, link_id: u64, factor: f32) {
+        if let Some(link) = self.links.get_mut(&link_id) {
+            link.set_capacity_factor(factor);
+            if self.active_links.contains(&link_id) {
+                self.active_links.activate(link_id);
+            }
+        }
+    }
+
+    /// Sets the speed factor of every link of this partition.
+    pub fn set_speed_factors(&mut self, factor: impl Fn(u64) -> f32) {
+        for (id, link) in self.links.iter_mut() {
+            link.set_speed_factor(factor(*id));
+        }
+    }
+
+    /// Varies the speed of vehicles entering any link of this partition.
+    pub fn set_speed_variation(&mut self, variation: SpeedVariation) {
+        for link in self.links.values_mut() {
+            link.set_speed_variation(variation);
+        }
+    }
+
+    /// Lets nodes serve in-links, which offer a vehicle driven on a leg of one of the transit modes,
+    /// before all other in-links. Other in-links are served by the capacity-weighted random draw
+    /// with the remaining node capacity.
+    pub fn set_transit_priority(&mut self, modes: IntSet<u64>) {
+        self.transit_modes = Some(modes);
+    }
+
+    /// Applies the storage capacities reported by a neighbor partition at `time`.
+    pub fn apply_storage_cap_updates(&mut self, storage_caps: Vec<StorageCap>, time: u32) {
         for cap in storage_caps {
             if let SimLink::Out(link) = self.links.get_mut(&cap.link_id).unwrap() {
-                link.apply_storage_cap_update(cap.value);
+                link.apply_storage_cap_update(cap.value, time);
             } else {
                 panic!("only expecting ids for split out links ")
             }
@@ -218,42 +523,100 @@ impl SimNetworkPartition {
 
     #[instrument(level = "trace", skip(self), fields(rank = self.partition))]
     pub fn move_links(&mut self, now: u32) -> (Vec<Vehicle>, Vec<StorageUpdate>) {
-        let mut storage_cap_updates: Vec<_> = Vec::new();
-        let mut vehicles: Vec<_> = Vec::new();
-        let mut deactivate: IntSet<u64> = IntSet::default();
-
-        for id in &self.active_links {
-            let link = self.links.get_mut(id).unwrap();
-            let is_active = match link {
-                SimLink::Local(ll) => Self::move_local_link(ll, &mut self.active_nodes, now),
-                SimLink::In(il) => {
-                    Self::move_in_link(il, &mut self.active_nodes, &mut storage_cap_updates, now)
-                }
-                SimLink::Out(ol) => Self::move_out_link(ol, &mut vehicles),
-            };
+        let due = match self.core {
+            SimulationCore::Tick => {
+                self.active_links.changed.clear();
+                std::mem::take(&mut self.active_links.links)
+            }
+            SimulationCore::EventDriven => self.active_links.take_due(now),
+        };
 
-            if !is_active {
-                deactivate.insert(link.id().internal());
+        let core = self.core;
+        let moved = if self
+            .parallel_threshold
+            .is_some_and(|threshold| due.len() >= threshold)
+        {
+            // take the due links out of the map, so that they can be moved in parallel without
+            // scanning all links. They are moved in the same order as in the serial case, and the
+            // results are appended in this order, so that both yield the same vehicles and updates.
+            let mut due_links: Vec<_> = due
+                .iter()
+                .map(|id| (*id, self.links.remove(id).unwrap()))
+                .collect();
+            let moved = due_links
+                .par_iter_mut()
+                .fold(MovedLinks::default, |mut moved, (id, link)| {
+                    Self::move_link(*id, link, core, now, &mut moved);
+                    moved
+                })
+                .reduce(MovedLinks::default, MovedLinks::append);
+            self.links.extend(due_links);
+            moved
+        } else {
+            let mut moved = MovedLinks::default();
+            for id in &due {
+                let link = self.links.get_mut(id).unwrap();
+                Self::move_link(*id, link, core, now, &mut moved);
             }
-        }
+            moved
+        };
 
+        // the tick core has moved all active links. Those are still active, unless deactivated below.
+        if self.core == SimulationCore::Tick {
+            self.active_links.links = due;
+        }
         // bookkeeping. Empty links are no longer active.
-        for id in deactivate {
-            self.active_links.remove(&id);
+        for id in moved.deactivate_links {
+            self.active_links.deactivate(&id);
+        }
+        for (id, wakeup) in moved.wakeups {
+            self.active_links.schedule(id, wakeup);
         }
+        self.active_nodes.extend(moved.activate_nodes);
         // vehicles leaving this partition are no longer part of the veh count
-        self.veh_counter -= vehicles.len();
+        self.veh_counter -= moved.vehicles.len();
 
-        (vehicles, storage_cap_updates)
+        (moved.vehicles, moved.storage_cap_updates)
     }
 
-    fn move_local_link(link: &mut LocalLink, active_nodes: &mut IntSet<u64>, now: u32) -> bool {
+    fn move_link(
+        id: u64,
+        link: &mut SimLink,
+        core: SimulationCore,
+        now: u32,
+        moved: &mut MovedLinks,
+    ) {
+        let is_active = match link {
+            SimLink::Local(ll) => Self::move_local_link(ll, &mut moved.activate_nodes, now),
+            SimLink::In(il) => Self::move_in_link(
+                il,
+                &mut moved.activate_nodes,
+                &mut moved.storage_cap_updates,
+                now,
+            ),
+            SimLink::Out(ol) => Self::move_out_link(ol, &mut moved.vehicles),
+        };
+
+        if !is_active {
+            moved.deactivate_links.push(id);
+        } else if core == SimulationCore::EventDriven {
+            // move the link again one time step before its first vehicle may leave, so that the
+            // downstream node is activated in time. Links with vehicles which should have left
+            // already are waiting for flow or storage capacity and are moved every time step.
+            if let Some(exit_time) = link.front_exit_time() {
+                let wakeup = exit_time.saturating_sub(1).max(now + 1);
+                moved.wakeups.push((id, wakeup));
+            }
+        }
+    }
+
+    fn move_local_link(link: &mut LocalLink, activate_nodes: &mut Vec<u64>, now: u32) -> bool {
         link.update_flow_cap(now);
         link.apply_storage_cap_updates();
         // the node will only look at the vehicle at the at the top of the queue in the next timestep
         // therefore, peek whether vehicles are available for the next timestep.
         if link.q_front(now + 1).is_some() {
-            Self::activate_node(active_nodes, link.to.internal());
+            activate_nodes.push(link.to.internal());
         }
 
         // indicate whether link is active. The link is active if it has vehicles on it.
@@ -262,7 +625,7 @@ impl SimNetworkPartition {
 
     fn move_in_link(
         link: &mut SplitInLink,
-        active_nodes: &mut IntSet<u64>,
+        activate_nodes: &mut Vec<u64>,
         storage_cap_updates: &mut Vec<StorageUpdate>,
         now: u32,
     ) -> bool {
@@ -273,7 +636,7 @@ impl SimNetworkPartition {
             storage_cap_updates.push(cap_update);
         }
 
-        Self::move_local_link(&mut link.local_link, active_nodes, now)
+        Self::move_local_link(&mut link.local_link, activate_nodes, now)
     }
 
     fn move_out_link(link: &mut SplitOutLink, vehicles: &mut Vec<Vehicle>) -> bool {
@@ -287,55 +650,74 @@ impl SimNetworkPartition {
     #[instrument(level = "trace", skip(self), fields(rank = self.partition))]
     pub fn move_nodes(&mut self, events: &mut EventsPublisher, now: u32) -> Vec<Vehicle> {
         let mut exited_vehicles = Vec::new();
-        let new_active_nodes: IntSet<_> = self
-            .active_nodes
-            .iter()
-            .map(|id| self.nodes.get(id).unwrap())
-            // this map has side effects. Not sure whether this is appropriate here,
-            // but it is convenient to use map, so that the 'active' result can be used and
-            // filtered.
-            .map(|node| {
-                let active = Self::move_node_capacity_priority(
-                    node,
-                    &mut self.links,
-                    &mut self.active_links,
-                    &mut exited_vehicles,
-                    events,
-                    &mut self.rnd,
-                    now,
-                );
-                (node, active)
-            })
-            .filter(|(_node, active)| *active)
-            .map(|(node, _)| node.id)
-            .collect();
+        let mut new_active_nodes = IntSet::default();
+        for id in &self.active_nodes {
+            let node = self.nodes.get_mut(id).unwrap();
+            let active = Self::move_node_capacity_priority(
+                node,
+                &mut self.links,
+                &mut self.active_links,
+                &mut exited_vehicles,
+                events,
+                &mut self.rnd,
+                self.transit_modes.as_ref(),
+                now,
+            );
+            if active {
+                new_active_nodes.insert(node.id);
+            }
+        }
 
         self.active_nodes = new_active_nodes;
         self.veh_counter -= exited_vehicles.len();
         exited_vehicles
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn move_node_capacity_priority(
-        node: &SimNode,
+        node: &mut SimNode,
         links: &mut IntMap<u64, SimLink>,
-        active_links: &mut IntSet<u64>,
+        active_links: &mut ActiveLinks,
         exited_vehicles: &mut Vec<Vehicle>,
         events: &mut EventsPublisher,
         rnd: &mut ThreadRng,
+        transit_modes: Option<&IntSet<u64>>,
         now: u32,
     ) -> bool {
         let (active, mut avail_capacity) =
             Self::get_active_in_links(&node.in_links, active_links, links);
         let mut exhausted_links: Vec<Option<()>> = vec![None; active.len()];
         let mut sel_cap: f32 = 0.;
+        if let Some(flow_cap) = node.flow_cap.as_mut() {
+            flow_cap.update_capacity(now);
+        }
 
-        while avail_capacity > 1e-10 {
+        // transit vehicles are released first, as long as the node has capacity left.
+        if let Some(modes) = transit_modes {
+            for link_id in &active {
+                while node.has_flow_cap()
+                    && Self::offers_transit_veh(link_id, links, modes, now)
+                    && Self::should_veh_move_out(link_id, links, now)
+                {
+                    let veh = links.get_mut(link_id).unwrap().pop_veh();
+                    Self::release_veh(veh, node, links, active_links, exited_vehicles, events, now);
+                    active_links.activate(*link_id);
+                }
+            }
+        }
+
+        'outer: while avail_capacity > 1e-10 {
             // draw random number between 0 and available capacity
             let rnd_num: f32 = rnd.gen::<f32>() * avail_capacity;
 
             #[allow(clippy::needless_range_loop)]
             // go through all in links and fetch one, which is not exhausted yet.
             for i in 0..active.len() {
+                // the intersection can't take any more vehicles in this time step
+                if !node.has_flow_cap() {
+                    break 'outer;
+                }
+
                 // if the link is exhausted, try next link
                 if exhausted_links[i].is_some() {
                     // reduce the available capacity a little bit. Sometimes we have rounding errors
@@ -357,11 +739,16 @@ impl SimNetworkPartition {
 
                     if sel_cap >= rnd_num {
                         let veh = in_link.pop_veh();
-                        if veh.peek_next_route_element().is_some() {
-                            Self::move_vehicle(veh, links, active_links, events, now);
-                        } else {
-                            exited_vehicles.push(veh);
-                        }
+                        active_links.activate(*link_id);
+                        Self::release_veh(
+                            veh,
+                            node,
+                            links,
+                            active_links,
+                            exited_vehicles,
+                            events,
+                            now,
+                        );
                     }
                 } else {
                     // in case the vehicle on the link can't move, we add the link to the exhausted
@@ -377,9 +764,49 @@ impl SimNetworkPartition {
         Self::any_link_offers(&active, links, now + 1)
     }
 
+    /// Lets a vehicle, which was taken from an in-link of the node, pass the node. It either moves
+    /// onto the next link of its route or leaves the network.
+    fn release_veh(
+        veh: Vehicle,
+        node: &mut SimNode,
+        links: &mut IntMap<u64, SimLink>,
+        active_links: &mut ActiveLinks,
+        exited_vehicles: &mut Vec<Vehicle>,
+        events: &mut EventsPublisher,
+        now: u32,
+    ) {
+        if let Some(flow_cap) = node.flow_cap.as_mut() {
+            flow_cap.consume_capacity(veh.pce(), veh.fef);
+        }
+        // vehicles, which may not enter the next link, leave the network with the rest of their
+        // route. The simulation removes their agents as stuck, as they can't reach their destination.
+        match veh.peek_next_route_element() {
+            Some(next_id) if !links.get(&next_id).unwrap().allows(&veh) => {
+                Self::publish_rejection(LINK_MODE_VIOLATION_EVENT, &veh, next_id, events, now);
+                exited_vehicles.push(veh);
+            }
+            Some(_) => Self::move_vehicle(veh, links, active_links, events, now),
+            None => exited_vehicles.push(veh),
+        }
+    }
+
+    fn offers_transit_veh(
+        link_id: &u64,
+        links: &IntMap<u64, SimLink>,
+        modes: &IntSet<u64>,
+        now: u32,
+    ) -> bool {
+        links
+            .get(link_id)
+            .unwrap()
+            .offers_veh(now)
+            .and_then(|veh| veh.driver.as_ref())
+            .is_some_and(|driver| modes.contains(&driver.curr_leg().mode))
+    }
+
     fn get_active_in_links(
         in_links: &Vec<u64>,
-        active_links: &IntSet<u64>,
+        active_links: &ActiveLinks,
         links: &IntMap<u64, SimLink>,
     ) -> (Vec<u64>, f32) {
         let mut active: Vec<u64> = Vec::new();
@@ -403,23 +830,27 @@ impl SimNetworkPartition {
             .any(|link| link.offers_veh(time).is_some())
     }
 
-    fn activate_node(active_nodes: &mut IntSet<u64>, node_id: u64) {
-        active_nodes.insert(node_id);
-    }
-
-    fn activate_link(active_links: &mut IntSet<u64>, link_id: u64) {
-        active_links.insert(link_id);
-    }
-
     fn should_veh_move_out(in_id: &u64, links: &IntMap<u64, SimLink>, now: u32) -> bool {
         let in_link = links.get(in_id).unwrap();
+        // vehicles wait at a red signal, even if they are stuck or done with their route.
+        if in_link.is_red() {
+            return false;
+        }
         if let Some(veh_ref) = in_link.offers_veh(now) {
             return if let Some(next_id_int) = veh_ref.peek_next_route_element() {
-                // if the vehicle has a next link id, it should move out of the current link.
-                // if the vehicle has reached its stuck threshold, we push it to the next link regardless of the available
-                // storage capacity. Under normal conditions, we check whether the downstream link has storage capacity available
+                // the vehicle waits, while the next link of its route is restricted for its vehicle
+                // type at this time of day, even if it is stuck. Otherwise, a stuck vehicle moves
+                // out regardless of the storage capacity of the next link. A vehicle, which may
+                // not enter the next link at all, moves out as well and leaves the network at the
+                // node. All other vehicles move out, if the next link has storage capacity
+                // available.
                 let out_link = links.get(&next_id_int).unwrap();
-                in_link.is_veh_stuck(now) || out_link.is_available()
+                if out_link.is_restricted_for(veh_ref, now) {
+                    return false;
+                }
+                in_link.is_veh_stuck(now)
+                    || !out_link.allows(veh_ref)
+                    || out_link.is_available_for(veh_ref)
             } else {
                 // if there is no next link, the vehicle is done with its route and we can take it out
                 // of the network
@@ -430,10 +861,43 @@ impl SimNetworkPartition {
         false
     }
 
+    /// Publishes an event of the given type for a vehicle, which may not enter the link.
+    fn publish_rejection(
+        event_type: &str,
+        vehicle: &Vehicle,
+        link_id: u64,
+        events: &mut EventsPublisher,
+        now: u32,
+    ) {
+        let attrs = HashMap::from([
+            (
+                String::from("vehicle"),
+                Id::<Vehicle>::get(vehicle.id).external().to_string(),
+            ),
+            (
+                String::from("person"),
+                Id::<Person>::get(vehicle.driver().id)
+                    .external()
+                    .to_string(),
+            ),
+            (
+                String::from("link"),
+                Id::<Link>::get(link_id).external().to_string(),
+            ),
+            (
+                String::from("vehicleType"),
+                Id::<VehicleType>::get(vehicle.r#type)
+                    .external()
+                    .to_string(),
+            ),
+        ]);
+        events.publish_event(now, &Event::new_generic(event_type, attrs));
+    }
+
     fn move_vehicle(
         mut vehicle: Vehicle,
         links: &mut IntMap<u64, SimLink>,
-        active_links: &mut IntSet<u64>,
+        active_links: &mut ActiveLinks,
         events: &mut EventsPublisher,
         now: u32,
     ) {
@@ -454,7 +918,7 @@ impl SimNetworkPartition {
         }
 
         link.push_veh(vehicle, now);
-        Self::activate_link(active_links, link_id);
+        active_links.activate(link_id);
     }
 }
 
@@ -462,14 +926,18 @@ impl SimNetworkPartition {
 mod tests {
     use assert_approx_eq::assert_approx_eq;
 
-    use crate::simulation::config::{MetisOptions, PartitionMethod};
+    use crate::simulation::config::{MetisOptions, PartitionMethod, SimulationCore};
+    use crate::simulation::id::id_map::IdMap;
     use crate::simulation::id::Id;
     use crate::simulation::messaging::events::EventsPublisher;
     use crate::simulation::network::{
+        access_restrictions::AccessRestriction,
         global_network::{Link, Network, Node},
         link::SimLink,
     };
     use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::Person;
+    use crate::simulation::wire_types::vehicles::VehicleType;
     use crate::test_utils;
 
     use super::SimNetworkPartition;
@@ -542,6 +1010,71 @@ mod tests {
         assert_eq!(0, network.veh_on_net());
     }
 
+    #[test]
+    fn take_and_inject_vehicle() {
+        let global_net = Network::from_file(
+            "./assets/3-links/3-links-network.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        let agent = test_utils::create_agent(1, vec![0, 1, 2]);
+        let vehicle = Vehicle::new(1, 0, 10., 1., Some(agent));
+        network.send_veh_en_route(vehicle, None, 0);
+
+        assert!(network.take_vehicle_of(2).is_none());
+        let mut vehicle = network.take_vehicle_of(1).unwrap();
+        assert_eq!(0, network.veh_on_net());
+        assert_eq!(0, vehicle.curr_route_elem);
+
+        // the vehicle continues its route on the last link
+        vehicle.route_index_to(2);
+        assert_eq!(2, vehicle.curr_link_id().unwrap());
+        network.send_veh_en_route(vehicle, None, 10);
+        assert_eq!(1, network.veh_on_net());
+        assert!(network
+            .vehicles()
+            .all(|vehicle| vehicle.curr_route_elem == 2));
+    }
+
+    #[test]
+    fn vehicle_travels_local_event_driven() {
+        let mut publisher = EventsPublisher::new();
+        let global_net = Network::from_file(
+            "./assets/3-links/3-links-network.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+        let mut config = test_utils::config();
+        config.core = SimulationCore::EventDriven;
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, config);
+        let agent = test_utils::create_agent(1, vec![0, 1, 2]);
+        let vehicle = Vehicle::new(1, 0, 10., 1., Some(agent));
+        network.send_veh_en_route(vehicle, None, 0);
+
+        for i in 0..121 {
+            let result = network.move_nodes(&mut publisher, i);
+            let _ = network.move_links(i);
+
+            // the event driven core must activate nodes in the same time steps as the tick core
+            if i == 9 || i == 109 || i == 119 {
+                assert_eq!(1, network.active_nodes());
+            } else {
+                assert_eq!(0, network.active_nodes(), "There was an active node at {i}");
+            }
+
+            if i == 120 {
+                assert_eq!(1, result.len());
+            } else {
+                assert_eq!(0, result.len());
+                assert_eq!(1, network.active_links());
+            }
+        }
+
+        assert_eq!(0, network.active_links());
+        assert_eq!(0, network.veh_on_net());
+    }
+
     #[test]
     fn vehicle_reaches_boundary() {
         let mut publisher = EventsPublisher::new();
@@ -572,6 +1105,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn move_links_parallel() {
+        let mut publisher = EventsPublisher::new();
+        let global_net = Network::from_file(
+            "./assets/3-links/3-links-network.xml",
+            2,
+            PartitionMethod::None,
+        );
+        let mut serial = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        let mut parallel = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        parallel.parallel_threshold = Some(0);
+        for i in 0..10 {
+            let agent = test_utils::create_agent(i, vec![0, 1, 2]);
+            serial.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent.clone())), None, 0);
+            parallel.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent)), None, 0);
+        }
+
+        for now in 0..100 {
+            serial.move_nodes(&mut publisher, now);
+            parallel.move_nodes(&mut publisher, now);
+            let (serial_vehicles, _) = serial.move_links(now);
+            let (parallel_vehicles, _) = parallel.move_links(now);
+
+            assert_eq!(serial_vehicles, parallel_vehicles);
+            assert_eq!(serial.active_nodes, parallel.active_nodes);
+            assert_eq!(serial.active_links.links, parallel.active_links.links);
+        }
+        assert_eq!(0, parallel.veh_counter);
+    }
+
     #[test]
     fn move_nodes_flow_cap_constraint() {
         let mut publisher = EventsPublisher::new();
@@ -605,6 +1168,113 @@ mod tests {
         assert_eq!(100, counter);
     }
 
+    #[test]
+    fn move_nodes_node_capacity_constraint() {
+        let mut publisher = EventsPublisher::new();
+        let global_net = Network::from_file(
+            "./assets/3-links/3-links-network.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+        let mut config = test_utils::config();
+        config.node_capacity = Some(1800.);
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, config);
+
+        // place 100 vehicles on first link
+        for i in 0..100 {
+            let agent = test_utils::create_agent(i, vec![0]);
+            let vehicle = Vehicle::new(i, 0, 10., 1., Some(agent));
+            network.send_veh_en_route(vehicle, None, 0);
+        }
+
+        // link1 could release one vehicle/s, but the node at its end only lets one vehicle pass
+        // every other second, starting at t=10
+        let mut counter = 0;
+        for now in 0..110 {
+            let result = network.move_nodes(&mut publisher, now);
+            let _ = network.move_links(now);
+            if now >= 10 && (now - 10) % 2 == 0 {
+                assert_eq!(1, result.len());
+                counter += 1;
+            } else {
+                assert!(result.is_empty());
+            }
+        }
+        assert_eq!(50, counter);
+    }
+
+    #[test]
+    fn move_nodes_transit_priority() {
+        let mut publisher = EventsPublisher::new();
+        let mut global_net = Network::new();
+        let node_a = Node::new(Id::create("a"), -100., 0., 0, 1);
+        let node_b = Node::new(Id::create("b"), 0., 100., 0, 1);
+        let mut node_c = Node::new(Id::create("c"), 0., 0., 0, 1);
+        node_c.capacity = Some(1800.);
+        let mut bus_link = Link::new_with_default(Id::create("bus-link"), &node_a, &node_c);
+        bus_link.capacity = 3600.;
+        bus_link.freespeed = 10.;
+        // the draw favors the car link by far, if transit isn't preferred
+        let mut car_link = Link::new_with_default(Id::create("car-link"), &node_b, &node_c);
+        car_link.capacity = 360000.;
+        car_link.freespeed = 10.;
+        global_net.add_node(node_a);
+        global_net.add_node(node_b);
+        global_net.add_node(node_c);
+        global_net.add_link(bus_link);
+        global_net.add_link(car_link);
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        let bus_mode: Id<String> = Id::create("bus");
+        network.set_transit_priority([bus_mode.internal()].into_iter().collect());
+
+        for i in 0..10 {
+            let agent = test_utils::create_agent(i, vec![1]);
+            network.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent)), None, 0);
+        }
+        let mut bus_driver = test_utils::create_agent(10, vec![0]);
+        bus_driver.plan.as_mut().unwrap().legs[0].mode = bus_mode.internal();
+        network.send_veh_en_route(Vehicle::new(10, 0, 10., 1., Some(bus_driver)), None, 0);
+
+        // the node lets one vehicle pass every other second. The bus is the first one.
+        let mut exited = Vec::new();
+        for now in 0..11 {
+            exited.append(&mut network.move_nodes(&mut publisher, now));
+            let _ = network.move_links(now);
+        }
+        assert_eq!(1, exited.len());
+        assert_eq!(10, exited[0].id);
+    }
+
+    #[test]
+    fn occupancy_and_blocked_in_links() {
+        let mut publisher = EventsPublisher::new();
+        let mut global_net = Network::new();
+        init_three_node_network(&mut global_net);
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+
+        // link-1 can release one vehicle per second, starting at t=10.
+        for i in 0..3 {
+            let agent = test_utils::create_agent(i, vec![0, 1]);
+            network.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent)), None, 0);
+        }
+        let occupancy = network.link_occupancy(0).unwrap();
+        assert_eq!(3, occupancy.vehicles);
+        assert_eq!(3., occupancy.used_storage);
+        assert_eq!(1, network.link_occupancies().count());
+        assert_eq!(None, network.link_occupancy(42));
+
+        for now in 0..11 {
+            let _ = network.move_nodes(&mut publisher, now);
+            let _ = network.move_links(now);
+        }
+        // one vehicle has passed node-2, the others wait at the end of link-1
+        assert_eq!(2, network.link_occupancy(0).unwrap().vehicles);
+        assert_eq!(1, network.link_occupancy(1).unwrap().vehicles);
+        assert_eq!(Some(1), network.blocked_in_links(1, 10));
+        assert_eq!(Some(0), network.blocked_in_links(0, 10));
+        assert_eq!(None, network.blocked_in_links(42, 10));
+    }
+
     #[test]
     fn move_nodes_storage_cap_constraint() {
         let mut publisher = EventsPublisher::new();
@@ -707,6 +1377,8 @@ mod tests {
             out_links: vec![],
             partition: 0,
             cmp_weight: 1,
+            capacity: None,
+            attributes: Default::default(),
         };
         let node2 = Node {
             id: Id::new_internal(1),
@@ -735,6 +1407,10 @@ mod tests {
             permlanes: 1.0,
             modes: Default::default(),
             partition: 0,
+            on_street_parking: None,
+            off_street_parking: None,
+            dedicated_lanes: vec![],
+            attributes: Default::default(),
         });
         net.add_link(Link {
             id: Id::new_internal(1),
@@ -746,6 +1422,10 @@ mod tests {
             permlanes: 1.0,
             modes: Default::default(),
             partition: 0,
+            on_street_parking: None,
+            off_street_parking: None,
+            dedicated_lanes: vec![],
+            attributes: Default::default(),
         });
         net.add_link(Link {
             id: Id::new_internal(2),
@@ -757,6 +1437,10 @@ mod tests {
             permlanes: 1.0,
             modes: Default::default(),
             partition: 0,
+            on_street_parking: None,
+            off_street_parking: None,
+            dedicated_lanes: vec![],
+            attributes: Default::default(),
         });
         let mut sim_net = SimNetworkPartition::from_network(&net, 0, test_utils::config());
 
@@ -811,14 +1495,17 @@ mod tests {
         assert_eq!(0, storage_caps.len());
 
         // now place vehicle on network and collect storage caps again.
-        // in links only report their releases. Therfore, no storage cap
-        // updates should be collected
+        // in links report their used storage, once vehicles enter or leave
         net2.send_veh_en_route(vehicle, None, 0);
+        let _ = net2.move_nodes(&mut publisher, 0);
+        let (_, storage_caps) = net2.move_links(0);
+        assert_eq!(1, storage_caps.len());
+        assert_approx_eq!(100., storage_caps.first().unwrap().used, 0.00001);
 
         // now, in the next time step, nothing has changed on the link. It should therefore not
         // report any storage capacities
-        let _ = net2.move_nodes(&mut publisher, 0);
-        let (_, storage_caps) = net2.move_links(0);
+        let _ = net2.move_nodes(&mut publisher, 1);
+        let (_, storage_caps) = net2.move_links(1);
         assert_eq!(0, storage_caps.len());
 
         // Now, test whether storage caps are emitted to upstream partitions as well
@@ -832,7 +1519,7 @@ mod tests {
         assert_eq!(1, storage_caps.len());
         let storage_cap = storage_caps.first().unwrap();
         assert_eq!(split_link_id.internal(), storage_cap.link_id);
-        assert_approx_eq!(100., storage_cap.released, 0.00001);
+        assert_approx_eq!(0., storage_cap.used, 0.00001);
     }
 
     #[test]
@@ -880,6 +1567,132 @@ mod tests {
         assert!(!neighbors.contains(&4));
     }
 
+    #[test]
+    fn vehicle_leaves_before_restricted_link() {
+        let mut publisher = EventsPublisher::new();
+        let mut global_net = Network::new();
+        init_three_node_network(&mut global_net);
+        let car: Id<String> = Id::create("car");
+        let bike: Id<String> = Id::create("bike");
+        global_net.links[0].modes = [car.clone(), bike.clone()].into_iter().collect();
+        global_net.links[1].modes = [car.clone()].into_iter().collect();
+        let car_type = Id::create("car-type");
+        let bike_type = Id::create("bike-type");
+        let veh_types: IdMap<VehicleType, VehicleType> = [
+            (
+                car_type.clone(),
+                test_utils::create_vehicle_type(&car_type, car),
+            ),
+            (
+                bike_type.clone(),
+                test_utils::create_vehicle_type(&bike_type, bike),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        network.restrict_veh_types(&global_net, &veh_types);
+
+        let car_veh = Vehicle::new(1, car_type.internal(), 10., 1., None);
+        assert!(network.links.get(&1).unwrap().allows(&car_veh));
+
+        // the violation event refers to the external ids of vehicle and driver.
+        let bike_id: Id<Vehicle> = Id::create("bike");
+        let cyclist: Id<Person> = Id::create("cyclist");
+        let agent = test_utils::create_agent(cyclist.internal(), vec![0, 1]);
+        let bike_veh = Vehicle::new(
+            bike_id.internal(),
+            bike_type.internal(),
+            10.,
+            1.,
+            Some(agent),
+        );
+        assert!(!network.links.get(&1).unwrap().allows(&bike_veh));
+        network.send_veh_en_route(bike_veh, None, 0);
+
+        let mut exited = Vec::new();
+        for now in 0..20 {
+            exited.append(&mut network.move_nodes(&mut publisher, now));
+            let _ = network.move_links(now);
+        }
+
+        // the bike leaves the network at the end of the first link, instead of entering the second.
+        // It keeps the rest of its route, so that it isn't mistaken for an arrival.
+        assert_eq!(1, exited.len());
+        assert_eq!(0, exited[0].curr_link_id().unwrap());
+        assert_eq!(Some(1), exited[0].peek_next_route_element());
+        assert_eq!(0, network.veh_on_net());
+    }
+
+    #[test]
+    fn vehicle_waits_for_access_restriction() {
+        let mut publisher = EventsPublisher::new();
+        let mut global_net = Network::new();
+        init_three_node_network(&mut global_net);
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        let truck_type = Id::<VehicleType>::create("restricted-truck").internal();
+        network
+            .links
+            .get_mut(&1)
+            .unwrap()
+            .set_access_restrictions(vec![AccessRestriction {
+                veh_types: [truck_type].into_iter().collect(),
+                start_time: 0,
+                end_time: 30,
+            }]);
+
+        let agent = test_utils::create_agent(1, vec![0, 1]);
+        let truck = Vehicle::new(1, truck_type, 10., 1., Some(agent));
+        network.send_veh_en_route(truck, None, 0);
+
+        let mut exited = Vec::new();
+        for now in 0..60 {
+            exited.append(&mut network.move_nodes(&mut publisher, now));
+            let _ = network.move_links(now);
+            if now < 30 {
+                // the truck could leave the first link after 10s, but waits for the restriction
+                // to end.
+                assert_eq!(1, network.link_occupancy(0).unwrap().vehicles);
+                assert_eq!(0, network.link_occupancy(1).unwrap().vehicles);
+            }
+        }
+
+        assert_eq!(1, exited.len());
+        assert_eq!(1, exited[0].curr_link_id().unwrap());
+        assert_eq!(0, network.veh_on_net());
+    }
+
+    #[test]
+    fn vehicles_wait_at_red_signal() {
+        let mut publisher = EventsPublisher::new();
+        let mut global_net = Network::new();
+        init_three_node_network(&mut global_net);
+        let mut network = SimNetworkPartition::from_network(&global_net, 0, test_utils::config());
+        network.set_signal(0, true);
+
+        for i in 0..3 {
+            let agent = test_utils::create_agent(i, vec![0, 1]);
+            network.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent)), None, 0);
+        }
+        for now in 0..30 {
+            let _ = network.move_nodes(&mut publisher, now);
+            let _ = network.move_links(now);
+        }
+        // all vehicles have reached the end of link-1 at t=10, but wait for the signal
+        assert_eq!(Some(3), network.queue_length(0, 29));
+        assert_eq!(Some(0), network.link_outflow(0));
+        assert_eq!(None, network.queue_length(42, 29));
+
+        // link-1 releases one vehicle per second, once the signal turns green
+        network.set_signal(0, false);
+        for now in 30..32 {
+            let _ = network.move_nodes(&mut publisher, now);
+            let _ = network.move_links(now);
+        }
+        assert_eq!(Some(1), network.queue_length(0, 31));
+        assert_eq!(Some(2), network.link_outflow(0));
+    }
+
     fn init_three_node_network(network: &mut Network) {
         let node1 = Node::new(Id::create("node-1"), -100., 0., 0, 1);
         let node2 = Node::new(Id::create("node-2"), 0., 0., 0, 1);
@@ -0,0 +1,377 @@
+use geo::{EuclideanDistance, Line, Point};
+use nohash_hasher::IntSet;
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::{Link, Network, Node};
+
+/// Maximum number of entries stored in a quad tree cell before it is split into four children.
+const MAX_ENTRIES: usize = 16;
+/// Maximum depth of the tree. Cells on this level are not split anymore, no matter how many entries
+/// they hold. This prevents endless splitting if many items share the same location.
+const MAX_DEPTH: usize = 16;
+
+/// Spatial index over the links and nodes of a [Network]. This is used to find the closest link or
+/// node to an arbitrary coordinate, e.g. for activities which only have a coordinate but no link id.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    links: QuadTree<IndexedLink>,
+    nodes: QuadTree<IndexedNode>,
+}
+
+impl SpatialIndex {
+    pub fn from_network(network: &Network) -> Self {
+        let bounds = Bounds::from_nodes(&network.nodes);
+        let mut links = QuadTree::new(bounds);
+        let mut nodes = QuadTree::new(bounds);
+
+        for node in &network.nodes {
+            nodes.insert(IndexedNode {
+                id: node.id.clone(),
+                point: Point::new(node.x, node.y),
+            });
+        }
+
+        for link in &network.links {
+            let from = network.get_node(&link.from);
+            let to = network.get_node(&link.to);
+            links.insert(IndexedLink {
+                id: link.id.clone(),
+                line: Line::new(Point::new(from.x, from.y), Point::new(to.x, to.y)),
+                modes: link.modes.clone(),
+            });
+        }
+
+        SpatialIndex { links, nodes }
+    }
+
+    /// Returns the id of the link closest to (x, y). The distance is measured between the point and
+    /// the straight line between from and to node of a link. If a mode is passed, only links which
+    /// allow that mode are considered.
+    pub fn nearest_link(&self, x: f64, y: f64, mode: Option<&Id<String>>) -> Option<&Id<Link>> {
+        self.links
+            .nearest(&Point::new(x, y), |link| {
+                mode.map_or(true, |m| link.modes.contains(m))
+            })
+            .map(|link| &link.id)
+    }
+
+    /// Returns the id of the node closest to (x, y)
+    pub fn nearest_node(&self, x: f64, y: f64) -> Option<&Id<Node>> {
+        self.nodes
+            .nearest(&Point::new(x, y), |_| true)
+            .map(|node| &node.id)
+    }
+}
+
+trait SpatialItem {
+    fn bounds(&self) -> Bounds;
+    fn distance(&self, point: &Point) -> f64;
+}
+
+#[derive(Debug)]
+struct IndexedLink {
+    id: Id<Link>,
+    line: Line,
+    modes: IntSet<Id<String>>,
+}
+
+impl SpatialItem for IndexedLink {
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            min_x: self.line.start.x.min(self.line.end.x),
+            min_y: self.line.start.y.min(self.line.end.y),
+            max_x: self.line.start.x.max(self.line.end.x),
+            max_y: self.line.start.y.max(self.line.end.y),
+        }
+    }
+
+    fn distance(&self, point: &Point) -> f64 {
+        point.euclidean_distance(&self.line)
+    }
+}
+
+#[derive(Debug)]
+struct IndexedNode {
+    id: Id<Node>,
+    point: Point,
+}
+
+impl SpatialItem for IndexedNode {
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            min_x: self.point.x(),
+            min_y: self.point.y(),
+            max_x: self.point.x(),
+            max_y: self.point.y(),
+        }
+    }
+
+    fn distance(&self, point: &Point) -> f64 {
+        point.euclidean_distance(&self.point)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bounds {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Bounds {
+    fn from_nodes(nodes: &[Node]) -> Self {
+        nodes.iter().fold(
+            Bounds {
+                min_x: f64::INFINITY,
+                min_y: f64::INFINITY,
+                max_x: f64::NEG_INFINITY,
+                max_y: f64::NEG_INFINITY,
+            },
+            |b, n| Bounds {
+                min_x: b.min_x.min(n.x),
+                min_y: b.min_y.min(n.y),
+                max_x: b.max_x.max(n.x),
+                max_y: b.max_y.max(n.y),
+            },
+        )
+    }
+
+    fn contains(&self, other: &Bounds) -> bool {
+        self.min_x <= other.min_x
+            && self.min_y <= other.min_y
+            && self.max_x >= other.max_x
+            && self.max_y >= other.max_y
+    }
+
+    /// Distance from the point to the closest point of the bounding box. This is 0 if the point
+    /// lies within the bounds.
+    fn distance(&self, point: &Point) -> f64 {
+        let dx = (self.min_x - point.x()).max(point.x() - self.max_x).max(0.);
+        let dy = (self.min_y - point.y()).max(point.y() - self.max_y).max(0.);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    fn quadrants(&self) -> [Bounds; 4] {
+        let center_x = (self.min_x + self.max_x) / 2.;
+        let center_y = (self.min_y + self.max_y) / 2.;
+        [
+            Bounds {
+                min_x: self.min_x,
+                min_y: self.min_y,
+                max_x: center_x,
+                max_y: center_y,
+            },
+            Bounds {
+                min_x: center_x,
+                min_y: self.min_y,
+                max_x: self.max_x,
+                max_y: center_y,
+            },
+            Bounds {
+                min_x: self.min_x,
+                min_y: center_y,
+                max_x: center_x,
+                max_y: self.max_y,
+            },
+            Bounds {
+                min_x: center_x,
+                min_y: center_y,
+                max_x: self.max_x,
+                max_y: self.max_y,
+            },
+        ]
+    }
+}
+
+/// Quad tree which stores items in the smallest cell which fully contains the item's bounding box.
+/// Items which are located outside the bounds of the root cell are kept in the root cell.
+#[derive(Debug)]
+struct QuadTree<T: SpatialItem> {
+    root: Cell<T>,
+}
+
+#[derive(Debug)]
+struct Cell<T: SpatialItem> {
+    bounds: Bounds,
+    depth: usize,
+    entries: Vec<T>,
+    children: Option<Box<[Cell<T>; 4]>>,
+}
+
+impl<T: SpatialItem> QuadTree<T> {
+    fn new(bounds: Bounds) -> Self {
+        QuadTree {
+            root: Cell::new(bounds, 0),
+        }
+    }
+
+    fn insert(&mut self, item: T) {
+        self.root.insert(item);
+    }
+
+    fn nearest<F: Fn(&T) -> bool>(&self, point: &Point, filter: F) -> Option<&T> {
+        let mut best: Option<(f64, &T)> = None;
+        self.root.nearest(point, &filter, &mut best);
+        best.map(|(_, item)| item)
+    }
+}
+
+impl<T: SpatialItem> Cell<T> {
+    fn new(bounds: Bounds, depth: usize) -> Self {
+        Cell {
+            bounds,
+            depth,
+            entries: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn insert(&mut self, item: T) {
+        if let Some(children) = self.children.as_mut() {
+            let item_bounds = item.bounds();
+            if let Some(child) = children
+                .iter_mut()
+                .find(|c| c.bounds.contains(&item_bounds))
+            {
+                child.insert(item);
+                return;
+            }
+        }
+
+        self.entries.push(item);
+
+        if self.children.is_none() && self.entries.len() > MAX_ENTRIES && self.depth < MAX_DEPTH {
+            self.split();
+        }
+    }
+
+    fn split(&mut self) {
+        let [q0, q1, q2, q3] = self.bounds.quadrants();
+        let depth = self.depth + 1;
+        self.children = Some(Box::new([
+            Cell::new(q0, depth),
+            Cell::new(q1, depth),
+            Cell::new(q2, depth),
+            Cell::new(q3, depth),
+        ]));
+
+        for item in std::mem::take(&mut self.entries) {
+            self.insert(item);
+        }
+    }
+
+    fn nearest<'a, F: Fn(&T) -> bool>(
+        &'a self,
+        point: &Point,
+        filter: &F,
+        best: &mut Option<(f64, &'a T)>,
+    ) {
+        for item in self.entries.iter().filter(|item| filter(*item)) {
+            let distance = item.distance(point);
+            if best.map_or(true, |(best_dist, _)| distance < best_dist) {
+                *best = Some((distance, item));
+            }
+        }
+
+        if let Some(children) = self.children.as_ref() {
+            // visit closest cells first, so that more cells can be pruned afterwards.
+            let mut ordered: Vec<(f64, &Cell<T>)> = children
+                .iter()
+                .map(|c| (c.bounds.distance(point), c))
+                .collect();
+            ordered.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            for (cell_dist, cell) in ordered {
+                if best.map_or(true, |(best_dist, _)| cell_dist < best_dist) {
+                    cell.nearest(point, filter, best);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::network::spatial_index::SpatialIndex;
+
+    /// Creates a grid of size x size nodes with a spacing of 100m. Links are created in x-direction
+    /// only. Every link allows mode "car" and links in the last row additionally allow "bike".
+    fn create_grid(size: usize) -> Network {
+        let mut network = Network::new();
+        let car = Id::<String>::create("car");
+        let bike = Id::<String>::create("bike");
+
+        for row in 0..size {
+            for col in 0..size {
+                let id = Id::create(&format!("n-{row}-{col}"));
+                network.add_node(Node::new(id, col as f64 * 100., row as f64 * 100., 0, 1));
+            }
+        }
+
+        for row in 0..size {
+            for col in 0..size - 1 {
+                let from = &network.nodes[row * size + col];
+                let to = &network.nodes[row * size + col + 1];
+                let mut link =
+                    Link::new_with_default(Id::create(&format!("l-{row}-{col}")), from, to);
+                let mut modes = IntSet::default();
+                modes.insert(car.clone());
+                if row == size - 1 {
+                    modes.insert(bike.clone());
+                }
+                link.modes = modes;
+                network.add_link(link);
+            }
+        }
+        network
+    }
+
+    #[test]
+    fn nearest_link() {
+        let network = create_grid(20);
+        let index = SpatialIndex::from_network(&network);
+
+        let link = index.nearest_link(250., 510., None).unwrap();
+        assert_eq!(&Id::<Link>::get_from_ext("l-5-2"), link);
+
+        // outside the network bounds
+        let link = index.nearest_link(-1000., -50., None).unwrap();
+        assert_eq!(&Id::<Link>::get_from_ext("l-0-0"), link);
+    }
+
+    #[test]
+    fn nearest_link_with_mode() {
+        let network = create_grid(20);
+        let index = SpatialIndex::from_network(&network);
+        let bike = Id::<String>::get_from_ext("bike");
+
+        let link = index.nearest_link(250., 510., Some(&bike)).unwrap();
+        assert_eq!(&Id::<Link>::get_from_ext("l-19-2"), link);
+
+        let walk = Id::<String>::create("walk");
+        assert!(index.nearest_link(250., 510., Some(&walk)).is_none());
+    }
+
+    #[test]
+    fn nearest_node() {
+        let network = create_grid(20);
+        let index = SpatialIndex::from_network(&network);
+
+        let node = index.nearest_node(1234., 560.).unwrap();
+        assert_eq!(&Id::<Node>::get_from_ext("n-6-12"), node);
+    }
+
+    #[test]
+    fn empty_network() {
+        let network = Network::new();
+        let index = SpatialIndex::from_network(&network);
+
+        assert!(index.nearest_link(0., 0., None).is_none());
+        assert!(index.nearest_node(0., 0.).is_none());
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use nohash_hasher::IntSet;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal, Uniform};
+
+use crate::simulation::config;
+use crate::simulation::config::SpeedDistribution;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::population::population_data::parse_time_or_seconds;
+use crate::simulation::random::split_mix;
+
+#[derive(Debug)]
+enum Factors {
+    /// One factor per hour, which repeat once all hours are used.
+    Hourly(Vec<f32>),
+    /// Each factor applies from its time until the next one. Before the first one, links keep
+    /// their free speed.
+    TimeSeries(BTreeMap<u32, f32>),
+}
+
+impl Factors {
+    fn at(&self, now: u32) -> f32 {
+        match self {
+            Factors::Hourly(factors) => factors[(now / 3600) as usize % factors.len()],
+            Factors::TimeSeries(factors) => factors
+                .range(..=now)
+                .next_back()
+                .map_or(1., |(_, factor)| *factor),
+        }
+    }
+
+    fn changes_at(&self, now: u32) -> bool {
+        match self {
+            Factors::Hourly(_) => now % 3600 == 0,
+            Factors::TimeSeries(factors) => factors.contains_key(&now),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Profile {
+    /// Internal ids of the links the profile applies to. All links, if None.
+    links: Option<IntSet<u64>>,
+    factors: Factors,
+}
+
+/// Time dependent factors on the free speed of links, e.g. to model lower speeds during rain or
+/// higher speeds at night. Links covered by several profiles multiply their factors.
+#[derive(Debug)]
+pub struct SpeedFactors {
+    profiles: Vec<Profile>,
+}
+
+impl SpeedFactors {
+    pub fn from_config(config: &config::SpeedFactors) -> Self {
+        let profiles = config
+            .profiles
+            .iter()
+            .map(|profile| {
+                let links = if profile.links.is_empty() {
+                    None
+                } else {
+                    Some(
+                        profile
+                            .links
+                            .iter()
+                            .map(|id| Id::<Link>::get_from_ext(id).internal())
+                            .collect(),
+                    )
+                };
+                let factors = match (&profile.time_series, profile.hourly.is_empty()) {
+                    (None, false) => Factors::Hourly(profile.hourly.clone()),
+                    (Some(path), true) => Factors::TimeSeries(read_time_series(Path::new(path))),
+                    _ => panic!(
+                        "A speed factor profile needs either hourly factors or a time series."
+                    ),
+                };
+                Profile { links, factors }
+            })
+            .collect();
+        SpeedFactors { profiles }
+    }
+
+    /// Whether the factor of any profile changes at `now`.
+    pub fn changes_at(&self, now: u32) -> bool {
+        self.profiles.iter().any(|p| p.factors.changes_at(now))
+    }
+
+    pub fn factor(&self, link: u64, now: u32) -> f32 {
+        self.profiles
+            .iter()
+            .filter(|p| match &p.links {
+                Some(links) => links.contains(&link),
+                None => true,
+            })
+            .map(|p| p.factors.at(now))
+            .product()
+    }
+}
+
+/// Factors below this are raised to it, so that vehicles don't stand still or drive backwards.
+const MIN_SPEED_VARIATION: f32 = 0.1;
+
+/// Draws a speed factor each time a vehicle enters a link, to represent drivers who drive faster
+/// or slower than the free speed. The factor is derived from the seed, the vehicle, the link and
+/// the time step only. This way, results are reproducible regardless of the order in which
+/// vehicles are moved and how the network is partitioned.
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedVariation {
+    distribution: SpeedDistribution,
+    seed: u64,
+}
+
+impl SpeedVariation {
+    pub fn from_config(config: &config::SpeedVariation) -> Self {
+        SpeedVariation {
+            distribution: config.distribution,
+            seed: config.seed,
+        }
+    }
+
+    pub fn factor(&self, vehicle: u64, link: u64, now: u32) -> f32 {
+        let mut state = split_mix(self.seed);
+        for value in [vehicle, link, now as u64] {
+            state = split_mix(state ^ value);
+        }
+        let mut rng = StdRng::seed_from_u64(state);
+        let factor = match self.distribution {
+            SpeedDistribution::Normal { mean, std_dev } => Normal::new(mean, std_dev)
+                .expect("Invalid normal distribution of speed factors")
+                .sample(&mut rng),
+            SpeedDistribution::Uniform { min, max } => {
+                Uniform::new_inclusive(min, max).sample(&mut rng)
+            }
+        };
+        factor.max(MIN_SPEED_VARIATION)
+    }
+}
+
+fn read_time_series(path: &Path) -> BTreeMap<u32, f32> {
+    let file =
+        File::open(path).unwrap_or_else(|_| panic!("Failed to open speed factors at {path:?}"));
+    BufReader::new(file)
+        .lines()
+        .skip(1)
+        .map(|line| line.expect("Failed to read speed factors"))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let values: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+            if values.len() != 2 {
+                panic!("Expected a speed factor as time,factor, but got {line}");
+            }
+            let time = parse_time_or_seconds(values[0])
+                .unwrap_or_else(|| panic!("Invalid time of speed factor {line}"));
+            let factor = values[1]
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid speed factor {line}"));
+            (time, factor)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::simulation::config::{
+        SpeedDistribution, SpeedFactors as SpeedFactorsConfig, SpeedProfile,
+        SpeedVariation as SpeedVariationConfig,
+    };
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::network::speed_factors::{SpeedFactors, SpeedVariation};
+
+    #[test]
+    fn hourly_and_time_series() {
+        let dir = PathBuf::from("./test_output/simulation/network/speed_factors/");
+        std::fs::create_dir_all(&dir).unwrap();
+        let rain = dir.join("rain.csv");
+        let mut file = std::fs::File::create(&rain).unwrap();
+        writeln!(file, "time,factor\n07:30:00,0.8\n9000,1.0").unwrap();
+
+        let link = Id::<Link>::create("rainy-link").internal();
+        let other = Id::<Link>::create("dry-link").internal();
+        let factors = SpeedFactors::from_config(&SpeedFactorsConfig {
+            profiles: vec![
+                SpeedProfile {
+                    links: vec![],
+                    hourly: vec![1.2, 1.0],
+                    time_series: None,
+                },
+                SpeedProfile {
+                    links: vec![String::from("rainy-link")],
+                    hourly: vec![],
+                    time_series: Some(rain.to_str().unwrap().to_string()),
+                },
+            ],
+        });
+
+        // hourly factors repeat after two hours
+        assert_eq!(1.2, factors.factor(other, 0));
+        assert_eq!(1.0, factors.factor(other, 3600));
+        assert_eq!(1.2, factors.factor(other, 7200));
+        // the time series starts at 9000
+        assert_eq!(1.2, factors.factor(link, 7200));
+
+        assert!(factors.changes_at(7200));
+        assert!(factors.changes_at(27000));
+        assert!(!factors.changes_at(27001));
+        assert_eq!(0.8, factors.factor(link, 27000));
+        assert_eq!(1.0, factors.factor(other, 27000));
+        assert_eq!(1.2, factors.factor(link, 9000));
+    }
+
+    #[test]
+    fn speed_variation_is_reproducible() {
+        let variation = SpeedVariation::from_config(&SpeedVariationConfig {
+            distribution: SpeedDistribution::Uniform { min: 0.8, max: 1.2 },
+            seed: 42,
+        });
+
+        let factors: Vec<f32> = (0..100).map(|veh| variation.factor(veh, 1, 0)).collect();
+        assert!(factors.iter().all(|f| (0.8..=1.2).contains(f)));
+        // vehicles get different factors, but the same vehicle always gets the same one
+        assert!(factors.iter().any(|f| *f != factors[0]));
+        assert_eq!(factors[3], variation.factor(3, 1, 0));
+        assert_ne!(factors[3], variation.factor(3, 2, 0));
+
+        let other_seed = SpeedVariation::from_config(&SpeedVariationConfig {
+            distribution: SpeedDistribution::Uniform { min: 0.8, max: 1.2 },
+            seed: 43,
+        });
+        assert_ne!(factors[3], other_seed.factor(3, 1, 0));
+    }
+
+    #[test]
+    fn speed_variation_has_a_minimum() {
+        let variation = SpeedVariation::from_config(&SpeedVariationConfig {
+            distribution: SpeedDistribution::Normal {
+                mean: -1.,
+                std_dev: 0.01,
+            },
+            seed: 0,
+        });
+        assert_eq!(0.1, variation.factor(1, 1, 0));
+    }
+}
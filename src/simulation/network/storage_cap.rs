@@ -19,6 +19,10 @@ pub struct StorageCap {
     released: f32,
     consumed: f32,
     used: f32,
+    /// Scales the maximum storage capacity temporarily, e.g. during an incident.
+    factor: f32,
+    /// Whether the link stores at least one vehicle, regardless of its pce.
+    min_of_one_vehicle: bool,
 }
 
 impl StorageCap {
@@ -42,9 +46,19 @@ impl StorageCap {
             released: 0.0,
             consumed: 0.0,
             used: 0.0,
+            factor: 1.0,
+            min_of_one_vehicle: false,
         }
     }
 
+    /// Lets the storage capacity fit at least one vehicle of the pce of the vehicle, which wants to
+    /// enter. This prevents very short links from holding less than a single vehicle, as MATSim
+    /// does.
+    pub fn with_min_of_one_vehicle(mut self, enabled: bool) -> Self {
+        self.min_of_one_vehicle = enabled;
+        self
+    }
+
     pub fn currently_used(&self) -> f32 {
         self.used + self.consumed
     }
@@ -53,6 +67,16 @@ impl StorageCap {
         self.released
     }
 
+    pub fn consumed(&self) -> f32 {
+        self.consumed
+    }
+
+    /// Overrides the used storage capacity, e.g. with the value reported by another partition.
+    /// Consumed and released capacities of the current time step are kept.
+    pub fn set_used(&mut self, used: f32) {
+        self.used = used;
+    }
+
     /// Consumes storage capacity on a link
     ///
     /// This method should be called when a vehicle enters a link.
@@ -78,9 +102,36 @@ impl StorageCap {
         self.consumed = 0.0;
     }
 
-    /// Tests whether there is storage capacity available on the link.
+    /// Scales the maximum storage capacity by `factor`. Vehicles, which are already on the link,
+    /// stay there, but no further vehicles may enter while the link is over capacity.
+    pub fn set_factor(&mut self, factor: f32) {
+        self.factor = factor;
+    }
+
+    /// Share of the storage capacity in use, between 0 and 1. Links without any storage capacity,
+    /// e.g. because of an incident, count as full.
+    pub fn occupancy(&self) -> f32 {
+        let max = self.max * self.factor;
+        if max <= 0. {
+            return 1.;
+        }
+        (self.currently_used() / max).clamp(0., 1.)
+    }
+
+    /// Tests whether there is storage capacity available on the link for a vehicle with a pce of 1.
     pub fn is_available(&self) -> bool {
-        let available_cap = self.max - self.currently_used();
+        self.is_available_for(1.)
+    }
+
+    /// Tests whether there is storage capacity available on the link for a vehicle with the given
+    /// pce.
+    pub fn is_available_for(&self, pce: f32) -> bool {
+        let max = if self.min_of_one_vehicle {
+            self.max.max(pce)
+        } else {
+            self.max
+        };
+        let available_cap = max * self.factor - self.currently_used();
         available_cap > 0.0
     }
 }
@@ -101,4 +152,34 @@ mod test {
         // we expect a storage size of 20. because it the flow cap/s is 20 (36000 * 0.2 / 3600)
         assert_eq!(20., cap.max);
     }
+
+    #[test]
+    fn factor() {
+        let mut cap = StorageCap::new(100., 3., 1., 0.2, 7.5);
+        cap.consume(3.);
+        assert!(cap.is_available());
+
+        cap.set_factor(0.25);
+        assert!(!cap.is_available());
+
+        cap.set_factor(1.);
+        assert!(cap.is_available());
+    }
+
+    #[test]
+    fn init_min_of_one_vehicle() {
+        let mut cap = StorageCap::new(1., 1., 1., 1., 7.5).with_min_of_one_vehicle(false);
+        assert!(cap.max < 1.);
+        cap.consume(0.5);
+        assert!(!cap.is_available_for(3.));
+
+        // the link fits a truck with a pce of 3, even though it is shorter than a car.
+        let mut cap = StorageCap::new(1., 1., 1., 1., 7.5).with_min_of_one_vehicle(true);
+        cap.consume(0.5);
+        assert!(cap.is_available_for(3.));
+        assert!(cap.is_available());
+        cap.consume(1.);
+        assert!(cap.is_available_for(3.));
+        assert!(!cap.is_available());
+    }
 }
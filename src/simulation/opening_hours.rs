@@ -0,0 +1,138 @@
+//! Opening hours of activity types. Agents who arrive before an activity opens wait until it opens,
+//! and activities end at the latest when they close.
+
+use std::collections::HashMap;
+
+use nohash_hasher::IntMap;
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::population::population_data::parse_time_or_seconds;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::Person;
+
+/// Event type of agents waiting for their activity to open.
+pub const WAITING_FOR_OPENING_EVENT: &str = "waitingForOpening";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpeningTime {
+    pub open: u32,
+    pub close: u32,
+}
+
+impl OpeningTime {
+    /// Time at which an agent, who arrives at `now`, can start the activity.
+    pub fn start(&self, now: u32) -> u32 {
+        now.max(self.open)
+    }
+
+    /// Clips the end time of an activity to the closing time. Agents who arrive after closing
+    /// leave right away.
+    pub fn clip(&self, end_time: u32, now: u32) -> u32 {
+        end_time.min(self.close).max(now)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OpeningHours {
+    /// Opening times by activity type.
+    times: IntMap<u64, OpeningTime>,
+}
+
+impl OpeningHours {
+    pub fn new(times: IntMap<u64, OpeningTime>) -> Self {
+        OpeningHours { times }
+    }
+
+    pub fn from_config(config: &config::OpeningHours) -> Self {
+        let times = config
+            .activities
+            .iter()
+            .map(|(act_type, times)| {
+                let time = |value: &str| {
+                    parse_time_or_seconds(value).unwrap_or_else(|| {
+                        panic!("Invalid opening time {value} of activity type {act_type}.")
+                    })
+                };
+                let opening_time = OpeningTime {
+                    open: time(&times.open),
+                    close: time(&times.close),
+                };
+                assert!(
+                    opening_time.open <= opening_time.close,
+                    "Activity type {act_type} closes before it opens."
+                );
+                (Id::<String>::create(act_type).internal(), opening_time)
+            })
+            .collect();
+        Self::new(times)
+    }
+
+    pub fn get(&self, act_type: u64) -> Option<OpeningTime> {
+        self.times.get(&act_type).copied()
+    }
+
+    pub fn waiting_event(person: u64, link: u64, act_type: u64, opens: u32) -> Event {
+        let attrs = HashMap::from([
+            (
+                String::from("person"),
+                Id::<Person>::get(person).external().to_string(),
+            ),
+            (
+                String::from("link"),
+                Id::<Link>::get(link).external().to_string(),
+            ),
+            (
+                String::from("actType"),
+                Id::<String>::get(act_type).external().to_string(),
+            ),
+            (String::from("openingTime"), opens.to_string()),
+        ]);
+        Event::new_generic(WAITING_FOR_OPENING_EVENT, attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::config::{OpeningHours as OpeningHoursConfig, OpeningTimes};
+    use crate::simulation::id::Id;
+    use crate::simulation::opening_hours::{OpeningHours, OpeningTime};
+
+    #[test]
+    fn wait_and_clip() {
+        let opening_hours = OpeningHours::from_config(&OpeningHoursConfig {
+            // the config uses ahash maps, which can't be created from an array directly.
+            activities: [(
+                String::from("oh-shop"),
+                OpeningTimes {
+                    open: String::from("08:00:00"),
+                    close: String::from("72000"),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        });
+        let shop = opening_hours
+            .get(Id::<String>::get_from_ext("oh-shop").internal())
+            .unwrap();
+        assert_eq!(
+            OpeningTime {
+                open: 28800,
+                close: 72000
+            },
+            shop
+        );
+        assert!(opening_hours
+            .get(Id::<String>::create("oh-home").internal())
+            .is_none());
+
+        // agents arriving early start the activity once it opens
+        assert_eq!(28800, shop.start(25000));
+        assert_eq!(30000, shop.start(30000));
+        // activities end when the shop closes, or right away if it is closed already
+        assert_eq!(72000, shop.clip(u32::MAX, 30000));
+        assert_eq!(40000, shop.clip(40000, 30000));
+        assert_eq!(80000, shop.clip(90000, 80000));
+    }
+}
@@ -0,0 +1,170 @@
+use tracing::warn;
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::network::spatial_index::SpatialIndex;
+use crate::simulation::population::population::Population;
+use crate::simulation::wire_types::population::{Person, Plan};
+
+/// Assigns the closest link to activities, which only have a coordinate. Only links which allow
+/// the mode of an adjacent leg are considered, so that vehicles can reach the activity. If no link
+/// allows that mode, e.g. for teleported modes, the closest link of any mode is taken.
+///
+/// Returns the number of activities whose link was resolved.
+pub fn resolve_activity_links(population: &mut Population, network: &Network) -> usize {
+    let has_unresolved = population
+        .persons
+        .values()
+        .filter_map(|person| person.plan.as_ref())
+        .any(|plan| plan.acts.iter().any(|act| !act.has_link()));
+    if !has_unresolved {
+        return 0;
+    }
+
+    // building the index is expensive, so it is only done if there are activities to resolve.
+    let index = SpatialIndex::from_network(network);
+    let mut resolved = 0;
+    for person in population.persons.values_mut() {
+        let plan = if let Some(plan) = person.plan.as_mut() {
+            plan
+        } else {
+            continue;
+        };
+        for i in 0..plan.acts.len() {
+            if plan.acts[i].has_link() {
+                continue;
+            }
+            let mode = adjacent_mode(plan, i);
+            let act = &mut plan.acts[i];
+            let link = mode
+                .and_then(|mode| index.nearest_link(act.x, act.y, Some(&mode)))
+                .or_else(|| index.nearest_link(act.x, act.y, None))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Can't find a link for an activity of person {}, as the network has no links.",
+                        Id::<Person>::get(person.id).external()
+                    )
+                });
+            act.link_id = link.internal();
+            resolved += 1;
+        }
+        warn_about_routes(person.id, plan);
+    }
+    resolved
+}
+
+/// The mode of the leg after the activity, or the leg before it for the last activity.
+fn adjacent_mode(plan: &Plan, act_index: usize) -> Option<Id<String>> {
+    plan.legs
+        .get(act_index)
+        .or_else(|| act_index.checked_sub(1).and_then(|i| plan.legs.get(i)))
+        .map(|leg| Id::get(leg.mode))
+}
+
+/// Routes of the plans were created without knowing the links of the activities. They may thus
+/// start or end on another link.
+fn warn_about_routes(person: u64, plan: &Plan) {
+    for (i, leg) in plan.legs.iter().enumerate() {
+        let route = match leg.route.as_ref() {
+            Some(route) if !route.route.is_empty() => route,
+            _ => continue,
+        };
+        if route.start_link() != plan.acts[i].link_id
+            || route.end_link() != plan.acts[i + 1].link_id
+        {
+            warn!(
+                "The route of leg {i} of person {} doesn't connect the links of its activities.",
+                Id::<Person>::get(person).external()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::activity_links::resolve_activity_links;
+    use crate::simulation::population::population::Population;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+
+    #[test]
+    fn resolve_closest_link_of_mode() {
+        let mut network = Network::new();
+        for (i, (x, y)) in [(0., 0.), (100., 0.), (0., 10.), (100., 10.)]
+            .into_iter()
+            .enumerate()
+        {
+            network.add_node(Node::new(Id::create(&format!("al-{i}")), x, y, 0, 1));
+        }
+        // a bike path at y = 0 and a road at y = 10
+        for (id, from, to, mode) in [("al-bike", 0, 1, "bike"), ("al-road", 2, 3, "car")] {
+            let mut link =
+                Link::new_with_default(Id::create(id), &network.nodes[from], &network.nodes[to]);
+            link.modes = [Id::create(mode)].into_iter().collect::<IntSet<_>>();
+            network.add_link(link);
+        }
+        let bike_path = Id::<Link>::get_from_ext("al-bike").internal();
+        let road = Id::<Link>::get_from_ext("al-road").internal();
+
+        let act_type = Id::<String>::create("al-act").internal();
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(
+            50.,
+            1.,
+            act_type,
+            Activity::UNRESOLVED_LINK,
+            None,
+            None,
+            None,
+        ));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: 0,
+                distance: 0.,
+                route: vec![],
+            },
+            Id::<String>::get_from_ext("car").internal(),
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(
+            50., 1., act_type, bike_path, None, None, None,
+        ));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: 0,
+                distance: 0.,
+                route: vec![],
+            },
+            Id::<String>::create("al-walk").internal(),
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(
+            50.,
+            1.,
+            act_type,
+            Activity::UNRESOLVED_LINK,
+            None,
+            None,
+            None,
+        ));
+        let person_id: Id<Person> = Id::create("al-person");
+        let mut population = Population::new();
+        population
+            .persons
+            .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+
+        assert_eq!(2, resolve_activity_links(&mut population, &network));
+
+        let acts = &population.persons[&person_id].plan.as_ref().unwrap().acts;
+        // the first activity is reached by car and gets the road, although the bike path is closer.
+        assert_eq!(road, acts[0].link_id);
+        // no link allows walking. Thus, the closest link is taken.
+        assert_eq!(bike_path, acts[2].link_id);
+        assert_eq!(0, resolve_activity_links(&mut population, &network));
+    }
+}
@@ -0,0 +1,672 @@
+use std::any::Any;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use nohash_hasher::IntMap;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::simulation::id::Id;
+use crate::simulation::io::xml;
+use crate::simulation::messaging::events::EventsSubscriber;
+use crate::simulation::network::global_network::{Link, Network};
+use crate::simulation::population::population::Population;
+use crate::simulation::population::population_data::{parse_time, parse_time_opt};
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+pub const FREIGHT_SUBPOPULATION: &str = "freight";
+pub const TOUR_START_ACT: &str = "freight_start";
+pub const SERVICE_ACT: &str = "freight_service";
+pub const TOUR_END_ACT: &str = "freight_end";
+
+/// Carriers operate delivery tours. Each tour is performed by one vehicle, which starts at a depot,
+/// visits a sequence of service stops and returns to the depot afterwards.
+#[derive(Debug, Default)]
+pub struct Carriers {
+    pub carriers: Vec<Carrier>,
+}
+
+#[derive(Debug)]
+pub struct Carrier {
+    pub id: String,
+    pub tours: Vec<Tour>,
+}
+
+#[derive(Debug)]
+pub struct Tour {
+    pub id: String,
+    pub veh_type: Id<VehicleType>,
+    pub depot_link: Id<Link>,
+    pub start_time: u32,
+    pub services: Vec<Service>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub id: String,
+    pub link: Id<Link>,
+    pub duration: u32,
+    pub earliest_start: Option<u32>,
+    pub latest_start: Option<u32>,
+}
+
+impl Carriers {
+    /// Reads carriers from xml. Vehicle types and links referenced by the tours must be known already.
+    pub fn from_file(path: &Path) -> Self {
+        let io_carriers: IOCarriers = xml::read_from_file(path.to_str().unwrap());
+        let carriers: Vec<_> = io_carriers.carriers.iter().map(Carrier::from_io).collect();
+        info!(
+            "Loaded {} carriers with {} tours from {path:?}",
+            carriers.len(),
+            carriers.iter().map(|c| c.tours.len()).sum::<usize>()
+        );
+        Carriers { carriers }
+    }
+
+    /// Converts each tour into an agent, which is added to the population. The agent's plan consists
+    /// of a start activity at the depot, one activity per service stop and an end activity at the
+    /// depot. Legs only contain start and end link, so that they must be routed by the replanner.
+    pub fn add_to_population(
+        &self,
+        population: &mut Population,
+        network: &Network,
+        garage: &mut Garage,
+    ) {
+        let start_type: Id<String> = Id::create(TOUR_START_ACT);
+        let service_type: Id<String> = Id::create(SERVICE_ACT);
+        let end_type: Id<String> = Id::create(TOUR_END_ACT);
+
+        for carrier in &self.carriers {
+            for tour in &carrier.tours {
+                let person_id: Id<Person> = Id::create(&tour_agent_id(carrier, tour));
+                let veh_id = garage.add_veh_id(&person_id, &tour.veh_type);
+                let net_mode = garage.vehicle_types.get(&tour.veh_type).unwrap().net_mode;
+
+                let mut plan = Plan::new();
+                plan.add_act(create_act(
+                    network,
+                    &tour.depot_link,
+                    &start_type,
+                    Some(tour.start_time),
+                    None,
+                ));
+                let mut prev_link = &tour.depot_link;
+                for service in &tour.services {
+                    plan.add_leg(create_leg(prev_link, &service.link, &veh_id, net_mode));
+                    plan.add_act(create_act(
+                        network,
+                        &service.link,
+                        &service_type,
+                        None,
+                        Some(service.duration),
+                    ));
+                    prev_link = &service.link;
+                }
+                plan.add_leg(create_leg(prev_link, &tour.depot_link, &veh_id, net_mode));
+                plan.add_act(create_act(network, &tour.depot_link, &end_type, None, None));
+
+                let mut person = Person::new(person_id.internal(), plan);
+                person.set_attribute(Person::SUBPOPULATION, FREIGHT_SUBPOPULATION);
+                person.set_attribute("carrier", &carrier.id);
+                person.set_attribute("tour", &tour.id);
+                population.persons.insert(person_id, person);
+            }
+        }
+    }
+}
+
+fn tour_agent_id(carrier: &Carrier, tour: &Tour) -> String {
+    format!("freight_{}_{}", carrier.id, tour.id)
+}
+
+fn create_act(
+    network: &Network,
+    link_id: &Id<Link>,
+    act_type: &Id<String>,
+    end_time: Option<u32>,
+    max_dur: Option<u32>,
+) -> Activity {
+    let link = network.get_link(link_id);
+    let to = network.get_node(&link.to);
+    Activity::new(
+        to.x,
+        to.y,
+        act_type.internal(),
+        link_id.internal(),
+        None,
+        end_time,
+        max_dur,
+    )
+}
+
+fn create_leg(from: &Id<Link>, to: &Id<Link>, veh_id: &Id<Vehicle>, net_mode: u64) -> Leg {
+    let route = Route {
+        veh_id: veh_id.internal(),
+        distance: 0.,
+        route: vec![from.internal(), to.internal()],
+    };
+    Leg::new(route, net_mode, 0, None)
+}
+
+impl Carrier {
+    fn from_io(io_carrier: &IOCarrier) -> Self {
+        let tours = io_carrier
+            .tours
+            .iter()
+            .map(|io_tour| Tour {
+                id: io_tour.id.clone(),
+                veh_type: Id::get_from_ext(&io_tour.vehicle_type),
+                depot_link: Id::get_from_ext(&io_tour.depot_link),
+                start_time: parse_time(&io_tour.start_time)
+                    .unwrap_or_else(|| panic!("Could not parse start time of tour {}", io_tour.id)),
+                services: io_tour
+                    .services
+                    .iter()
+                    .map(|io_service| Service {
+                        id: io_service.id.clone(),
+                        link: Id::get_from_ext(&io_service.link),
+                        duration: parse_time(&io_service.service_duration).unwrap_or(0),
+                        earliest_start: parse_time_opt(&io_service.earliest_start),
+                        latest_start: parse_time_opt(&io_service.latest_start),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Carrier {
+            id: io_carrier.id.clone(),
+            tours,
+        }
+    }
+}
+
+/// Tour level record, which is created by the [FreightTourTracker] once a tour is finished.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TourRecord {
+    pub agent: u64,
+    pub start_time: u32,
+    pub end_time: u32,
+    pub distance: f64,
+    pub services: u32,
+    pub late_services: u32,
+    pub total_delay: u32,
+}
+
+/// What a partition has observed of a tour. Tours cross partitions, so that each partition only
+/// sees a part of the events of a tour. The states of all partitions are merged, before the KPIs
+/// of a tour are computed.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct TourState {
+    start_time: Option<u32>,
+    end_time: Option<u32>,
+    distance: f64,
+    service_starts: Vec<u32>,
+}
+
+impl TourState {
+    fn merge(&mut self, other: TourState) {
+        self.start_time = self.start_time.or(other.start_time);
+        self.end_time = self.end_time.or(other.end_time);
+        self.distance += other.distance;
+        self.service_starts.extend(other.service_starts);
+    }
+
+    /// Computes the KPIs of a finished tour. Services are visited in the order of the tour, so that
+    /// the n-th service start belongs to the n-th service.
+    fn record(&self, agent: u64, windows: &[Service]) -> Option<TourRecord> {
+        let end_time = self.end_time?;
+        let mut service_starts = self.service_starts.clone();
+        service_starts.sort_unstable();
+
+        let mut late_services = 0;
+        let mut total_delay = 0;
+        for (window, time) in windows.iter().zip(service_starts.iter()) {
+            if let Some(latest_start) = window.latest_start {
+                if *time > latest_start {
+                    late_services += 1;
+                    total_delay += time - latest_start;
+                }
+            }
+        }
+        Some(TourRecord {
+            agent,
+            start_time: self.start_time.unwrap_or_default(),
+            end_time,
+            distance: self.distance,
+            services: service_starts.len() as u32,
+            late_services,
+            total_delay,
+        })
+    }
+}
+
+/// Listens to the events of freight agents and computes tour level KPIs, i.e. the driven distance
+/// and how punctual service stops were reached. Each partition writes what it has observed into its
+/// own file, which are combined by [FreightTourTracker::merge_partitions] after the simulation.
+pub struct FreightTourTracker {
+    link_lengths: IntMap<u64, f64>,
+    windows: IntMap<u64, Vec<Service>>,
+    tours: IntMap<u64, TourState>,
+    vehicle_2_agent: IntMap<u64, u64>,
+    output_path: Option<PathBuf>,
+}
+
+impl FreightTourTracker {
+    /// The vehicles of the tours are looked up in the garage, so that each partition can attribute
+    /// link events of vehicles, which departed on another partition.
+    pub fn new(
+        carriers: &Carriers,
+        network: &Network,
+        garage: &Garage,
+        output_path: Option<PathBuf>,
+    ) -> Self {
+        let link_lengths = network
+            .links
+            .iter()
+            .map(|l| (l.id.internal(), l.length))
+            .collect();
+        let mut windows = IntMap::default();
+        let mut vehicle_2_agent = IntMap::default();
+        for carrier in &carriers.carriers {
+            for tour in &carrier.tours {
+                let agent: Id<Person> = Id::create(&tour_agent_id(carrier, tour));
+                let veh_id = garage.veh_id(&agent, &tour.veh_type);
+                vehicle_2_agent.insert(veh_id.internal(), agent.internal());
+                windows.insert(agent.internal(), tour.services.clone());
+            }
+        }
+        let tours = windows
+            .keys()
+            .map(|agent| (*agent, TourState::default()))
+            .collect();
+
+        FreightTourTracker {
+            link_lengths,
+            windows,
+            tours,
+            vehicle_2_agent,
+            output_path,
+        }
+    }
+
+    /// File, into which a partition writes the state of the tours it has observed.
+    pub fn partition_file(output_dir: &Path, rank: u32) -> PathBuf {
+        output_dir.join(format!("freight_tours.{rank}.csv"))
+    }
+
+    /// Merges the tour states, which the partitions have written, and writes the KPIs of all
+    /// finished tours to `freight_tours.csv`. The files of the partitions are removed afterwards.
+    pub fn merge_partitions(
+        carriers: &Carriers,
+        network: &Network,
+        garage: &Garage,
+        output_dir: &Path,
+        num_parts: u32,
+    ) {
+        let mut tracker = Self::new(carriers, network, garage, None);
+        for rank in 0..num_parts {
+            let path = Self::partition_file(output_dir, rank);
+            tracker.read_state(&path);
+            fs::remove_file(&path)
+                .unwrap_or_else(|_| panic!("Failed to remove freight tours file {path:?}"));
+        }
+        let path = output_dir.join("freight_tours.csv");
+        tracker.write_records(&path);
+        info!(
+            "Wrote {} finished freight tours to {path:?}",
+            tracker.records().len()
+        );
+    }
+
+    /// Records of all finished tours, ordered by agent.
+    pub fn records(&self) -> Vec<TourRecord> {
+        let mut records: Vec<_> = self
+            .tours
+            .iter()
+            .filter_map(|(agent, state)| state.record(*agent, self.windows.get(agent).unwrap()))
+            .collect();
+        records.sort_by_key(|record| record.agent);
+        records
+    }
+
+    fn is_act(act_type: u64, name: &str) -> bool {
+        Id::<String>::get(act_type).external().eq(name)
+    }
+
+    fn write_state(&self, path: &Path) {
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Failed to create freight output at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "agent,start_time,end_time,distance,service_starts").unwrap();
+        for (agent, state) in &self.tours {
+            let service_starts: Vec<_> = state
+                .service_starts
+                .iter()
+                .map(|time| time.to_string())
+                .collect();
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                Id::<Person>::get(*agent).external(),
+                state.start_time.map(|t| t.to_string()).unwrap_or_default(),
+                state.end_time.map(|t| t.to_string()).unwrap_or_default(),
+                state.distance,
+                service_starts.join(";")
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    fn read_state(&mut self, path: &Path) {
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Failed to read freight tours file {path:?}"));
+        for line in content.lines().skip(1) {
+            let fields: Vec<_> = line.split(',').collect();
+            assert_eq!(5, fields.len(), "Invalid line in {path:?}: {line}");
+            let parse = |value: &str| -> u32 {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid time {value} in {path:?}"))
+            };
+            let agent = Id::<Person>::get_from_ext(fields[0]).internal();
+            let state = TourState {
+                start_time: Some(fields[1]).filter(|v| !v.is_empty()).map(parse),
+                end_time: Some(fields[2]).filter(|v| !v.is_empty()).map(parse),
+                distance: fields[3]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid distance in {path:?}: {line}")),
+                service_starts: fields[4]
+                    .split(';')
+                    .filter(|v| !v.is_empty())
+                    .map(parse)
+                    .collect(),
+            };
+            self.tours.entry(agent).or_default().merge(state);
+        }
+    }
+
+    fn write_records(&self, path: &Path) {
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Failed to create freight output at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(
+            writer,
+            "agent,start_time,end_time,distance,services,late_services,total_delay"
+        )
+        .unwrap();
+        for r in &self.records() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                Id::<Person>::get(r.agent).external(),
+                r.start_time,
+                r.end_time,
+                r.distance,
+                r.services,
+                r.late_services,
+                r.total_delay
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+impl EventsSubscriber for FreightTourTracker {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        match event.r#type.as_ref().unwrap() {
+            Type::ActEnd(e) => {
+                if let Some(state) = self.tours.get_mut(&e.person) {
+                    if Self::is_act(e.act_type, TOUR_START_ACT) {
+                        state.start_time = Some(time);
+                    }
+                }
+            }
+            Type::LinkEnter(e) => {
+                if let Some(agent) = self.vehicle_2_agent.get(&e.vehicle) {
+                    let state = self.tours.get_mut(agent).unwrap();
+                    state.distance += self.link_lengths.get(&e.link).unwrap();
+                }
+            }
+            Type::ActStart(e) => {
+                let state = if let Some(state) = self.tours.get_mut(&e.person) {
+                    state
+                } else {
+                    return;
+                };
+                if Self::is_act(e.act_type, SERVICE_ACT) {
+                    state.service_starts.push(time);
+                } else if Self::is_act(e.act_type, TOUR_END_ACT) {
+                    state.end_time = Some(time);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(path) = self.output_path.as_ref() {
+            self.write_state(path);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct IOCarriers {
+    #[serde(rename = "carrier", default)]
+    carriers: Vec<IOCarrier>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct IOCarrier {
+    id: String,
+    #[serde(rename = "tour", default)]
+    tours: Vec<IOTour>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct IOTour {
+    id: String,
+    vehicle_type: String,
+    depot_link: String,
+    start_time: String,
+    #[serde(rename = "service", default)]
+    services: Vec<IOService>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct IOService {
+    id: String,
+    link: String,
+    service_duration: String,
+    earliest_start: Option<String>,
+    latest_start: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use quick_xml::de::from_str;
+
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsSubscriber;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::freight::{
+        Carrier, Carriers, FreightTourTracker, IOCarriers, Service, Tour, SERVICE_ACT,
+        TOUR_END_ACT, TOUR_START_ACT,
+    };
+    use crate::simulation::population::population::Population;
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::Person;
+    use crate::test_utils::create_vehicle_type;
+
+    #[test]
+    fn read_carriers_from_string() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <carriers>\
+                <carrier id=\"c-1\">\
+                    <tour id=\"t-1\" vehicleType=\"heavy\" depotLink=\"1\" startTime=\"06:00:00\">\
+                        <service id=\"s-1\" link=\"2\" serviceDuration=\"00:10:00\" earliestStart=\"07:00:00\" latestStart=\"08:00:00\"/>\
+                        <service id=\"s-2\" link=\"3\" serviceDuration=\"00:05:00\"/>\
+                    </tour>\
+                </carrier>\
+            </carriers>";
+        let carriers: IOCarriers = from_str(xml).unwrap();
+
+        assert_eq!(1, carriers.carriers.len());
+        let tour = &carriers.carriers[0].tours[0];
+        assert_eq!("heavy", tour.vehicle_type);
+        assert_eq!(2, tour.services.len());
+        assert_eq!(
+            Some(String::from("08:00:00")),
+            tour.services[0].latest_start
+        );
+        assert_eq!(None, tour.services[1].earliest_start);
+    }
+
+    fn create_carriers() -> (Carriers, Network, Garage) {
+        let mut network = Network::new();
+        let n1 = Node::new(Id::create("n1"), 0., 0., 0, 1);
+        let n2 = Node::new(Id::create("n2"), 100., 0., 0, 1);
+        let l1 = Link::new_with_default(Id::create("l1"), &n1, &n2);
+        let l2 = Link::new_with_default(Id::create("l2"), &n2, &n1);
+        network.add_node(n1);
+        network.add_node(n2);
+        network.add_link(l1);
+        network.add_link(l2);
+
+        let mut garage = Garage::new();
+        let heavy = Id::create("heavy");
+        garage.add_veh_type(create_vehicle_type(&heavy, Id::create("truck")));
+
+        let carriers = Carriers {
+            carriers: vec![Carrier {
+                id: String::from("c"),
+                tours: vec![Tour {
+                    id: String::from("t"),
+                    veh_type: heavy,
+                    depot_link: Id::get_from_ext("l1"),
+                    start_time: 100,
+                    services: vec![Service {
+                        id: String::from("s"),
+                        link: Id::get_from_ext("l2"),
+                        duration: 60,
+                        earliest_start: None,
+                        latest_start: Some(150),
+                    }],
+                }],
+            }],
+        };
+        (carriers, network, garage)
+    }
+
+    #[test]
+    fn tours_to_agents() {
+        let (carriers, network, mut garage) = create_carriers();
+        let mut population = Population::new();
+
+        carriers.add_to_population(&mut population, &network, &mut garage);
+
+        assert_eq!(1, population.persons.len());
+        let agent = population
+            .persons
+            .get(&Id::get_from_ext("freight_c_t"))
+            .unwrap();
+        let plan = agent.plan.as_ref().unwrap();
+        assert_eq!(3, plan.acts.len());
+        assert_eq!(2, plan.legs.len());
+        assert_eq!(Some(100), plan.acts[0].end_time);
+        assert_eq!(Some(60), plan.acts[1].max_dur);
+        assert_eq!(Some("freight"), agent.subpopulation());
+        assert_eq!(1, garage.vehicles.len());
+    }
+
+    #[test]
+    fn track_tour() {
+        let (carriers, network, mut garage) = create_carriers();
+        carriers.add_to_population(&mut Population::new(), &network, &mut garage);
+        let mut tracker = FreightTourTracker::new(&carriers, &network, &garage, None);
+        let agent = Id::<Person>::get_from_ext("freight_c_t").internal();
+        let vehicle = Id::<Vehicle>::get_from_ext("freight_c_t_heavy").internal();
+        let start = Id::<String>::create(TOUR_START_ACT).internal();
+        let service = Id::<String>::create(SERVICE_ACT).internal();
+        let end = Id::<String>::create(TOUR_END_ACT).internal();
+
+        tracker.receive_event(100, &Event::new_act_end(agent, 0, start));
+        tracker.receive_event(100, &Event::new_person_enters_veh(agent, vehicle));
+        tracker.receive_event(130, &Event::new_link_enter(1, vehicle));
+        tracker.receive_event(200, &Event::new_act_start(agent, 1, service));
+        tracker.receive_event(260, &Event::new_act_end(agent, 1, service));
+        tracker.receive_event(280, &Event::new_link_enter(0, vehicle));
+        tracker.receive_event(300, &Event::new_act_start(agent, 0, end));
+
+        assert_eq!(1, tracker.records().len());
+        let record = &tracker.records()[0];
+        assert_eq!(100, record.start_time);
+        assert_eq!(300, record.end_time);
+        assert_eq!(200., record.distance);
+        assert_eq!(1, record.services);
+        assert_eq!(1, record.late_services);
+        assert_eq!(50, record.total_delay);
+    }
+
+    #[test]
+    fn merge_partitions() {
+        let (carriers, network, mut garage) = create_carriers();
+        carriers.add_to_population(&mut Population::new(), &network, &mut garage);
+        let agent = Id::<Person>::get_from_ext("freight_c_t").internal();
+        let vehicle = Id::<Vehicle>::get_from_ext("freight_c_t_heavy").internal();
+        let start = Id::<String>::create(TOUR_START_ACT).internal();
+        let service = Id::<String>::create(SERVICE_ACT).internal();
+        let end = Id::<String>::create(TOUR_END_ACT).internal();
+        let output_dir = PathBuf::from("./test_output/simulation/population/freight/merge");
+        fs::create_dir_all(&output_dir).unwrap();
+
+        // the tour starts and ends on partition 0, but the service is on partition 1.
+        let mut tracker_0 = FreightTourTracker::new(
+            &carriers,
+            &network,
+            &garage,
+            Some(FreightTourTracker::partition_file(&output_dir, 0)),
+        );
+        tracker_0.receive_event(100, &Event::new_act_end(agent, 0, start));
+        tracker_0.receive_event(280, &Event::new_link_enter(0, vehicle));
+        tracker_0.receive_event(300, &Event::new_act_start(agent, 0, end));
+        tracker_0.finish();
+        let mut tracker_1 = FreightTourTracker::new(
+            &carriers,
+            &network,
+            &garage,
+            Some(FreightTourTracker::partition_file(&output_dir, 1)),
+        );
+        tracker_1.receive_event(130, &Event::new_link_enter(1, vehicle));
+        tracker_1.receive_event(200, &Event::new_act_start(agent, 1, service));
+        tracker_1.finish();
+
+        FreightTourTracker::merge_partitions(&carriers, &network, &garage, &output_dir, 2);
+
+        let content = fs::read_to_string(output_dir.join("freight_tours.csv")).unwrap();
+        assert_eq!(
+            "agent,start_time,end_time,distance,services,late_services,total_delay\nfreight_c_t,100,300,200,1,1,50\n",
+            content
+        );
+        assert!(!FreightTourTracker::partition_file(&output_dir, 0).exists());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::population::population::Population;
+use crate::simulation::replanning::teleported_router::{BeeLineDistanceRouter, TeleportedRouter};
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::population::{Activity, Leg, Person, Route};
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+/// Vehicle type of access and egress legs, as in the ad-hoc replanner.
+const WALK: &str = "walk";
+
+/// Turns plans which only contain main activities and legs into plans as MATSim's router creates
+/// them. Each leg is wrapped into `<vehicle type> interaction` activities at the links of the
+/// adjacent activities, and walking access and egress legs from and to these activities. This way,
+/// raw demand can be simulated without routing it in MATSim first. Walk legs and ride legs are
+/// left as they are. Plans which contain interaction activities already are skipped.
+///
+/// Access and egress legs are teleported with the beeline parameters of the walk mode, or with the
+/// speed of the walk vehicle type if there are none.
+///
+/// Returns the number of plans which were changed.
+pub fn insert_interactions(
+    population: &mut Population,
+    network: &Network,
+    garage: &Garage,
+    teleportation: &config::Teleportation,
+) -> usize {
+    let walk_type_id = Id::<VehicleType>::get_from_ext(WALK);
+    let walk_type = garage
+        .vehicle_types
+        .get(&walk_type_id)
+        .expect("No walk vehicle type");
+    let router = BeeLineDistanceRouter::from_config(teleportation);
+    let mut changed_plans = 0;
+
+    for person in population.persons.values_mut() {
+        let person_id: Id<Person> = Id::get(person.id);
+        let plan = if let Some(plan) = person.plan.as_mut() {
+            plan
+        } else {
+            continue;
+        };
+        if plan.legs.is_empty() || plan.acts.iter().any(|act| act.is_interaction()) {
+            continue;
+        }
+
+        let walk_veh_id = garage.veh_id(&person_id, &walk_type_id).internal();
+        let walk_leg = |act: &Activity, main_leg: &Leg| {
+            let walk =
+                router.query_access_egress(act, walk_type.net_mode, walk_type.max_v, network);
+            Leg {
+                mode: walk_type.net_mode,
+                routing_mode: main_leg.routing_mode,
+                dep_time: None,
+                trav_time: walk.duration,
+                route: Some(Route {
+                    veh_id: walk_veh_id,
+                    distance: walk.distance,
+                    route: vec![act.link_id, act.link_id],
+                }),
+                driver: None,
+            }
+        };
+
+        let mut old_acts = std::mem::take(&mut plan.acts).into_iter();
+        let old_legs = std::mem::take(&mut plan.legs);
+        let mut prev = old_acts.next().unwrap();
+        for (leg, next) in old_legs.into_iter().zip(old_acts) {
+            let veh_type_id = leg.vehicle_type_id(garage);
+            if leg.is_ride() || *veh_type_id == walk_type_id {
+                plan.acts.push(prev);
+                plan.legs.push(leg);
+                prev = next;
+                continue;
+            }
+
+            let interaction =
+                Id::<String>::get_from_ext(&format!("{} interaction", veh_type_id.external()))
+                    .internal();
+            let access = walk_leg(&prev, &leg);
+            let egress = walk_leg(&next, &leg);
+            let from_interaction = Activity::interaction(prev.link_id, interaction);
+            let to_interaction = Activity::interaction(next.link_id, interaction);
+
+            plan.acts.extend([prev, from_interaction, to_interaction]);
+            plan.legs.extend([access, leg, egress]);
+            prev = next;
+        }
+        plan.acts.push(prev);
+        changed_plans += 1;
+    }
+
+    changed_plans
+}
+
+#[cfg(test)]
+mod tests {
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::config::Teleportation;
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::interactions::insert_interactions;
+    use crate::simulation::population::population::Population;
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::test_utils::create_vehicle_type;
+
+    #[test]
+    fn wrap_legs_into_interactions() {
+        let mut network = Network::new();
+        let modes: IntSet<Id<String>> = [Id::create("car")].into_iter().collect();
+        for i in 0..3 {
+            let id = Id::create(&format!("ia-{i}"));
+            network.add_node(Node::new(id, i as f64 * 100., 0., 0, 1));
+        }
+        for (id, from, to) in [("ia-a", 0, 1), ("ia-b", 1, 2)] {
+            let mut link =
+                Link::new_with_default(Id::create(id), &network.nodes[from], &network.nodes[to]);
+            link.modes = modes.clone();
+            network.add_link(link);
+        }
+        let a = Id::<Link>::get_from_ext("ia-a").internal();
+        let b = Id::<Link>::get_from_ext("ia-b").internal();
+
+        let mut garage = Garage::new();
+        for mode in ["car", "walk"] {
+            let mut veh_type = create_vehicle_type(&Id::create(mode), Id::create(mode));
+            veh_type.max_v = 1.;
+            garage.add_veh_type(veh_type);
+            Id::<String>::create(&format!("{mode} interaction"));
+        }
+        let person_id: Id<Person> = Id::create("ia-person");
+        let car = garage.add_veh_id(&person_id, &Id::get_from_ext("car"));
+        garage.add_veh_id(&person_id, &Id::get_from_ext("walk"));
+
+        let home = Id::<String>::create("home").internal();
+        let mut plan = Plan::new();
+        // the activity is 10m away from its link
+        plan.add_act(Activity::new(50., 10., home, a, None, Some(0), None));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: car.internal(),
+                distance: 200.,
+                route: vec![a, b],
+            },
+            Id::<String>::get_from_ext("car").internal(),
+            20,
+            None,
+        ));
+        plan.add_act(Activity::new(150., 0., home, b, None, None, None));
+        let mut population = Population::new();
+        population
+            .persons
+            .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+
+        let teleportation = Teleportation::default();
+        assert_eq!(
+            1,
+            insert_interactions(&mut population, &network, &garage, &teleportation)
+        );
+        // plans with interactions are not changed again
+        assert_eq!(
+            0,
+            insert_interactions(&mut population, &network, &garage, &teleportation)
+        );
+
+        let plan = population.persons[&person_id].plan.as_ref().unwrap();
+        let act_types: Vec<String> = plan
+            .acts
+            .iter()
+            .map(|act| Id::<String>::get(act.act_type).external().to_string())
+            .collect();
+        assert_eq!(
+            vec!["home", "car interaction", "car interaction", "home"],
+            act_types
+        );
+        assert_eq!(
+            vec![a, a, b, b],
+            plan.acts.iter().map(|a| a.link_id).collect::<Vec<_>>()
+        );
+
+        let walk_mode = Id::<String>::get_from_ext("walk").internal();
+        assert_eq!(3, plan.legs.len());
+        assert_eq!(walk_mode, plan.legs[0].mode);
+        assert_eq!(10, plan.legs[0].trav_time);
+        assert_eq!(20, plan.legs[1].trav_time);
+        assert_eq!(walk_mode, plan.legs[2].mode);
+        assert_eq!(0, plan.legs[2].trav_time);
+    }
+}
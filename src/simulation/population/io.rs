@@ -4,18 +4,24 @@ use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use prost::Message;
 use serde::Deserialize;
 use tracing::info;
 
 use crate::simulation::id::Id;
-use crate::simulation::io::attributes::Attrs;
+use crate::simulation::io::attributes::{Attr, Attrs};
+use crate::simulation::io::proto;
 use crate::simulation::io::proto::MessageIter;
-use crate::simulation::io::{proto, xml};
+use crate::simulation::network::global_network::Link;
 use crate::simulation::population::population::Population;
+use crate::simulation::population::population_data::format_time;
+use crate::simulation::population::xml_persons;
 use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
 use crate::simulation::wire_types::population::Header;
-use crate::simulation::wire_types::population::Person;
+use crate::simulation::wire_types::population::{Activity, Leg, Person};
 
 pub fn from_file<F: Fn(&Person) -> bool>(
     path: &Path,
@@ -51,8 +57,146 @@ fn load_from_xml(path: &Path, garage: &mut Garage) -> HashMap<Id<Person>, Person
     create_population(&io_pop)
 }
 
-fn write_to_xml(_population: &Population, _path: &Path) {
-    panic!("Write to xml is not implemented for Population. Only writing to `.binpb` is supported")
+/// Writes the selected plans in the matsim population format. All routes are written as `links`
+/// routes with their vehicle, so that they can be read back without knowing the vehicle types.
+fn write_to_xml(population: &Population, path: &Path) {
+    info!("Writing population to {path:?}");
+    let prefix = path.parent().unwrap();
+    fs::create_dir_all(prefix).unwrap();
+    let file = File::create(path).unwrap_or_else(|_| panic!("Failed to create file at: {path:?}"));
+    let writer = BufWriter::new(file);
+    let result = if path.extension().unwrap().eq("gz") {
+        let mut encoder = GzEncoder::new(writer, Compression::fast());
+        write_xml(population, &mut encoder).and_then(|_| encoder.finish().map(|_| ()))
+    } else {
+        let mut writer = writer;
+        write_xml(population, &mut writer).and_then(|_| writer.flush())
+    };
+    result.unwrap_or_else(|e| panic!("Failed to write population to {path:?}: {e}"));
+}
+
+fn write_xml<W: Write>(population: &Population, writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(
+        writer,
+        "<!DOCTYPE population SYSTEM \"http://www.matsim.org/files/dtd/population_v6.dtd\">"
+    )?;
+    writeln!(writer, "<population>")?;
+
+    let mut persons: Vec<&Person> = population.persons.values().collect();
+    persons.sort_by_key(|p| p.id);
+    for person in persons {
+        let person_id: Id<Person> = Id::get(person.id);
+        writeln!(writer, "\t<person id=\"{}\">", escape(person_id.external()))?;
+        if !person.attributes.is_empty() {
+            let mut attrs = Attrs::default();
+            attrs.extend_from_map(&person.attributes);
+            write_attributes(writer, &attrs, "\t\t")?;
+        }
+        writeln!(writer, "\t\t<plan selected=\"yes\">")?;
+        if let Some(plan) = person.plan.as_ref() {
+            for (i, act) in plan.acts.iter().enumerate() {
+                write_activity(writer, act)?;
+                if let Some(leg) = plan.legs.get(i) {
+                    write_leg(writer, leg)?;
+                }
+            }
+        }
+        writeln!(writer, "\t\t</plan>")?;
+        writeln!(writer, "\t</person>")?;
+    }
+    writeln!(writer, "</population>")
+}
+
+fn write_activity<W: Write>(writer: &mut W, act: &Activity) -> std::io::Result<()> {
+    let act_type: Id<String> = Id::get(act.act_type);
+    let link: Id<Link> = Id::get(act.link_id);
+    write!(
+        writer,
+        "\t\t\t<activity type=\"{}\" link=\"{}\" x=\"{}\" y=\"{}\"",
+        escape(act_type.external()),
+        escape(link.external()),
+        act.x,
+        act.y
+    )?;
+    for (name, time) in [
+        ("start_time", act.start_time),
+        ("end_time", act.end_time),
+        ("max_dur", act.max_dur),
+    ] {
+        if let Some(time) = time {
+            write!(writer, " {name}=\"{}\"", format_time(time))?;
+        }
+    }
+    writeln!(writer, " />")
+}
+
+fn write_leg<W: Write>(writer: &mut W, leg: &Leg) -> std::io::Result<()> {
+    let mode: Id<String> = Id::get(leg.mode);
+    write!(writer, "\t\t\t<leg mode=\"{}\"", escape(mode.external()))?;
+    if let Some(dep_time) = leg.dep_time {
+        write!(writer, " dep_time=\"{}\"", format_time(dep_time))?;
+    }
+    writeln!(writer, " trav_time=\"{}\">", format_time(leg.trav_time))?;
+
+    let mut attrs = Attrs {
+        attributes: vec![Attr {
+            name: String::from("routingMode"),
+            class: String::from("java.lang.String"),
+            value: Id::<String>::get(leg.routing_mode).external().to_owned(),
+        }],
+    };
+    if let Some(driver) = leg.driver {
+        attrs.attributes.push(Attr {
+            name: String::from(Leg::DRIVER_ATTRIBUTE),
+            class: String::from("java.lang.String"),
+            value: Id::<Person>::get(driver).external().to_owned(),
+        });
+    }
+    write_attributes(writer, &attrs, "\t\t\t\t")?;
+
+    if let Some(route) = leg.route.as_ref().filter(|r| !r.route.is_empty()) {
+        let links: Vec<String> = route
+            .route
+            .iter()
+            .map(|id| Id::<Link>::get(*id).external().to_string())
+            .collect();
+        let veh_id: Id<Vehicle> = Id::get(route.veh_id);
+        writeln!(
+            writer,
+            "\t\t\t\t<route type=\"links\" start_link=\"{}\" end_link=\"{}\" trav_time=\"{}\" distance=\"{}\" vehicleRefId=\"{}\">{}</route>",
+            escape(links.first().unwrap()),
+            escape(links.last().unwrap()),
+            format_time(leg.trav_time),
+            route.distance,
+            escape(veh_id.external()),
+            escape(&links.join(" "))
+        )?;
+    }
+    writeln!(writer, "\t\t\t</leg>")
+}
+
+fn write_attributes<W: Write>(writer: &mut W, attrs: &Attrs, indent: &str) -> std::io::Result<()> {
+    writeln!(writer, "{indent}<attributes>")?;
+    for attr in &attrs.attributes {
+        writeln!(
+            writer,
+            "{indent}\t<attribute name=\"{}\" class=\"{}\">{}</attribute>",
+            escape(&attr.name),
+            escape(&attr.class),
+            escape(&attr.value)
+        )?;
+    }
+    writeln!(writer, "{indent}</attributes>")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
 }
 
 fn load_from_proto<F>(path: &Path, filter: F) -> Population
@@ -183,7 +327,8 @@ pub struct IORoute {
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct IOActivity {
     pub r#type: String,
-    pub link: String,
+    /// Activities may only have a coordinate. Their link is resolved once the network is known.
+    pub link: Option<String>,
     pub x: f64,
     pub y: f64,
     pub start_time: Option<String>,
@@ -249,6 +394,7 @@ pub struct IOPlan {
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct IOPerson {
     pub id: String,
+    pub attributes: Option<Attrs>,
     #[serde(rename = "plan")]
     pub plans: Vec<IOPlan>,
 }
@@ -266,13 +412,11 @@ pub struct IOPopulation {
 }
 
 impl IOPopulation {
+    /// Reads the persons of a plans file in parallel. See `xml_persons`.
     pub fn from_file(file_path: &str) -> IOPopulation {
-        let population: IOPopulation = xml::read_from_file(file_path);
-        info!(
-            "IOPopulation: Finished reading population. Population contains {} persons",
-            population.persons.len()
-        );
-        population
+        IOPopulation {
+            persons: xml_persons::read_persons(Path::new(file_path)),
+        }
     }
 }
 
@@ -348,6 +492,10 @@ mod tests {
         let person = population.persons.get(0).unwrap();
         assert_eq!("1", person.id);
         assert_eq!(1, person.plans.len());
+        assert_eq!(
+            "vehicles",
+            person.attributes.as_ref().unwrap().attributes[0].name
+        );
 
         let plan = person.plans.get(0).unwrap();
         assert!(plan.selected);
@@ -381,7 +529,7 @@ mod tests {
             IOPlanElement::Activity(activity) => {
                 //<activity type=\"w\" link=\"20\" x=\"10000.0\" y=\"0.0\" max_dur=\"03:30:00\" >
                 assert_eq!("w", activity.r#type);
-                assert_eq!("20", activity.link);
+                assert_eq!(Some(String::from("20")), activity.link);
                 assert_eq!(10000.0, activity.x);
                 assert_eq!(0.0, activity.y);
                 assert_eq!(Some(String::from("03:30:00")), activity.max_dur);
@@ -467,4 +615,30 @@ mod tests {
         assert_eq!(1, proto_pop.persons.len());
         assert!(proto_pop.persons.contains_key(&expected_id));
     }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let _net = Network::from_file_as_is(&PathBuf::from("./assets/equil/equil-network.xml"));
+        let mut garage = Garage::from_file(&PathBuf::from("./assets/equil/equil-vehicles.xml"));
+        let mut pop = Population::from_file(
+            &PathBuf::from("./assets/equil/equil-plans.xml.gz"),
+            &mut garage,
+        );
+        let person = pop.persons.get_mut(&Id::get_from_ext("1")).unwrap();
+        person.set_attribute("income", "<high> & \"rich\"");
+
+        let file_path = PathBuf::from(
+            "./test_output/simulation/population/io/test_xml_round_trip/plans.xml.gz",
+        );
+        pop.to_file(&file_path);
+
+        let xml_pop = Population::from_file(&file_path, &mut garage);
+
+        assert_eq!(pop.persons.len(), xml_pop.persons.len());
+        for (id, person) in &pop.persons {
+            assert_eq!(person, xml_pop.persons.get(id).unwrap());
+        }
+        let xml_person = xml_pop.persons.get(&Id::get_from_ext("1")).unwrap();
+        assert_eq!(Some("<high> & \"rich\""), xml_person.attribute("income"));
+    }
 }
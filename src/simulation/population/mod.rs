@@ -1,4 +1,11 @@
+pub mod activity_links;
+pub mod freight;
+pub mod interactions;
 mod io;
 #[allow(clippy::module_inception)]
 pub mod population;
 pub mod population_data;
+pub mod route_repair;
+pub mod unknown_modes;
+pub mod validation;
+mod xml_persons;
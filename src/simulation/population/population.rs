@@ -7,7 +7,7 @@ use crate::simulation::population::io::{from_file, to_file};
 use crate::simulation::vehicles::garage::Garage;
 use crate::simulation::wire_types::population::Person;
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Population {
     pub persons: HashMap<Id<Person>, Person>,
 }
@@ -43,6 +43,24 @@ impl Population {
         })
     }
 
+    /// Like [Population::from_file_filtered_part], but persons whose plans leave the network are
+    /// dropped instead of failing on them. See [crate::simulation::wire_types::population::Plan::is_within].
+    pub fn from_file_within_network(
+        file_path: &Path,
+        net: &Network,
+        garage: &mut Garage,
+        part: u32,
+    ) -> Self {
+        from_file(file_path, garage, |p| {
+            if !p.plan.as_ref().is_some_and(|plan| plan.is_within(net)) {
+                return false;
+            }
+            let act = p.curr_act();
+            let partition = net.links.get(act.link_id as usize).unwrap().partition;
+            partition == part
+        })
+    }
+
     pub fn to_file(&self, file_path: &Path) {
         to_file(self, file_path);
     }
@@ -1,18 +1,27 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use tracing::debug;
 
+use crate::simulation::config::ActivityDurationInterpretation;
 use crate::simulation::id::Id;
 use crate::simulation::io::attributes::Attrs;
-use crate::simulation::network::global_network::Link;
+use crate::simulation::network::global_network::{Link, Network};
 use crate::simulation::population::io::{
     IOActivity, IOLeg, IOPerson, IOPlan, IOPlanElement, IORoute,
 };
-use crate::simulation::time_queue::EndTime;
+use crate::simulation::random::split_mix;
+use crate::simulation::time_queue::{EndTime, QueueId};
 use crate::simulation::vehicles::garage::Garage;
 use crate::simulation::wire_types::messages::Vehicle;
 use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
 use crate::simulation::wire_types::vehicles::VehicleType;
 
 impl Person {
+    pub const SUBPOPULATION: &'static str = "subpopulation";
+    pub const INCOME: &'static str = "income";
+    pub const CAR_AVAILABILITY: &'static str = "carAvail";
+    pub const LICENSE: &'static str = "hasLicense";
+
     pub fn from_io(io_person: &IOPerson) -> Person {
         let person_id = Id::get_from_ext(&io_person.id);
 
@@ -22,10 +31,25 @@ impl Person {
             debug!("There is an empty plan for person {:?}", io_person.id);
         }
 
+        let attributes = io_person
+            .attributes
+            .as_ref()
+            .map(|attrs| {
+                attrs
+                    .attributes
+                    .iter()
+                    .map(|attr| (attr.name.clone(), attr.value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Person {
             id: person_id.internal(),
             plan: Some(plan),
             curr_plan_elem: 0,
+            attributes,
+            rng_seed: person_id.internal(),
+            rng_counter: 0,
         }
     }
 
@@ -34,13 +58,81 @@ impl Person {
             id,
             curr_plan_elem: 0,
             plan: Some(plan),
+            attributes: Default::default(),
+            rng_seed: id,
+            rng_counter: 0,
         }
     }
 
+    /// Random numbers for the next decision of the agent. Each call advances the agent's counter.
+    pub fn next_rng(&mut self) -> StdRng {
+        let rng = agent_rng([self.rng_seed, 0, self.rng_counter]);
+        self.rng_counter += 1;
+        rng
+    }
+
+    /// Random numbers, which only depend on the agent's seed and `salt`. This is for decisions,
+    /// which have to turn out the same each time they are made, e.g. the strategy of an iteration.
+    pub fn salted_rng(&self, salt: u64) -> StdRng {
+        agent_rng([self.rng_seed, 1, salt])
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
 
+    /// Raw value of a person attribute as it was stated in the plans file.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|v| v.as_str())
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        self.attributes
+            .insert(String::from(name), String::from(value));
+    }
+
+    pub fn subpopulation(&self) -> Option<&str> {
+        self.attribute(Self::SUBPOPULATION)
+    }
+
+    /// Income of the person. Panics if the attribute is present but can't be parsed into a number.
+    pub fn income(&self) -> Option<f64> {
+        self.attribute(Self::INCOME).map(|income| {
+            income
+                .parse()
+                .unwrap_or_else(|_| panic!("Could not parse income {income} of person {}", self.id))
+        })
+    }
+
+    /// Whether the person has a car available. Persons without the attribute are assumed to have
+    /// a car available, as it is done in matsim.
+    pub fn has_car_available(&self) -> bool {
+        self.attribute(Self::CAR_AVAILABILITY)
+            .map_or(true, |avail| !avail.eq("never"))
+    }
+
+    /// Whether the person has a driving license. As in matsim, only persons with the attribute set
+    /// to "no" don't have one.
+    pub fn has_license(&self) -> bool {
+        self.attribute(Self::LICENSE)
+            .map_or(true, |license| !license.eq("no"))
+    }
+
+    /// Persons may only drive a car if they have a license and a car available.
+    pub fn can_drive(&self) -> bool {
+        self.has_license() && self.has_car_available()
+    }
+
+    /// Main mode of the trip the current or next leg belongs to. See [Plan::main_mode].
+    pub fn curr_trip_main_mode(&self) -> u64 {
+        let leg_index = if self.curr_plan_elem % 2 == 0 {
+            self.next_leg_index()
+        } else {
+            self.curr_plan_elem / 2
+        };
+        self.plan.as_ref().unwrap().main_mode(leg_index as usize)
+    }
+
     pub fn add_act_after_curr(&mut self, to_add: Vec<Activity>) {
         let next_act_index = self.next_act_index() as usize;
         self.plan
@@ -79,6 +171,14 @@ impl Person {
         self.get_act_at_index_mut(act_index)
     }
 
+    /// The activity before the current leg.
+    pub fn prev_act(&self) -> &Activity {
+        if self.curr_plan_elem % 2 != 1 {
+            panic!("Current element is not a leg.");
+        }
+        self.get_act_at_index((self.curr_plan_elem - 1) / 2)
+    }
+
     pub fn next_act(&self) -> &Activity {
         let act_index = self.next_act_index();
         self.get_act_at_index(act_index)
@@ -118,6 +218,15 @@ impl Person {
             .unwrap()
     }
 
+    pub fn curr_leg_mut(&mut self) -> &mut Leg {
+        if self.curr_plan_elem % 2 != 1 {
+            panic!("Current element is not a leg.");
+        }
+
+        let leg_index = (self.curr_plan_elem - 1) / 2;
+        self.get_leg_at_index_mut(leg_index)
+    }
+
     pub fn next_leg(&self) -> &Leg {
         let next_leg_index = self.next_leg_index();
         self.get_leg_at_index(next_leg_index)
@@ -212,15 +321,41 @@ impl Person {
         }
         self.curr_plan_elem = next;
     }
+
+    /// Whether the person has reached the last activity of its plan.
+    pub fn has_finished_plan(&self) -> bool {
+        let plan = self.plan.as_ref().unwrap();
+        plan.acts.len() + plan.legs.len() == self.curr_plan_elem as usize + 1
+    }
+}
+
+fn agent_rng(values: [u64; 3]) -> StdRng {
+    let state = values
+        .into_iter()
+        .fold(0, |state, value| split_mix(state ^ value));
+    StdRng::seed_from_u64(state)
 }
 
 impl EndTime for Person {
     fn end_time(&self, now: u32) -> u32 {
-        return if self.curr_plan_elem % 2 == 0 {
-            self.curr_act().cmp_end_time(now)
+        self.wakeup_time(now, ActivityDurationInterpretation::default())
+    }
+}
+
+impl Person {
+    /// Time at which the person ends its current activity or leg, if it starts it at `now`.
+    pub fn wakeup_time(&self, now: u32, interpretation: ActivityDurationInterpretation) -> u32 {
+        if self.curr_plan_elem % 2 == 0 {
+            self.curr_act().cmp_end_time(now, interpretation)
         } else {
             self.curr_leg().trav_time + now
-        };
+        }
+    }
+}
+
+impl QueueId for Person {
+    fn queue_id(&self) -> u64 {
+        self.id
     }
 }
 
@@ -269,17 +404,63 @@ impl Plan {
     pub fn add_act(&mut self, activity: Activity) {
         self.acts.push(activity);
     }
+
+    /// Whether all activities and routes of the plan are located on links of the network. This is
+    /// not the case, if the network is a cut-out of the network the plan was created for. Links of
+    /// the cut-out come first, so that links outside of it have larger internal ids. Activities
+    /// without links are resolved to links of the network later.
+    pub fn is_within(&self, network: &Network) -> bool {
+        let num_links = network.links.len() as u64;
+        let acts_within = self
+            .acts
+            .iter()
+            .all(|act| !act.has_link() || act.link_id < num_links);
+        let routes_within = self
+            .legs
+            .iter()
+            .filter_map(|leg| leg.route.as_ref())
+            .all(|route| route.route.iter().all(|link| *link < num_links));
+        acts_within && routes_within
+    }
+
+    /// Main mode of the trip the leg at `leg_index` belongs to. A trip consists of all legs between
+    /// two activities, which are not interaction activities. If a trip has at least three legs, the
+    /// first and the last leg are considered access and egress legs and the main mode is the mode
+    /// of the second leg. Otherwise, it is the mode of the first leg.
+    pub fn main_mode(&self, leg_index: usize) -> u64 {
+        // leg i is located between act i and act i + 1
+        let mut first = leg_index;
+        while first > 0 && self.acts[first].is_interaction() {
+            first -= 1;
+        }
+        let mut last = leg_index;
+        while last + 1 < self.legs.len() && self.acts[last + 1].is_interaction() {
+            last += 1;
+        }
+
+        if last - first < 2 {
+            self.legs[first].mode
+        } else {
+            self.legs[first + 1].mode
+        }
+    }
 }
 
 impl Activity {
+    /// Link id of activities, which only have a coordinate, until the closest link is looked up.
+    /// See [crate::simulation::population::activity_links::resolve_activity_links].
+    pub const UNRESOLVED_LINK: u64 = u64::MAX;
+
     fn from_io(io_act: &IOActivity) -> Self {
-        let link_id: Id<Link> = Id::get_from_ext(&io_act.link);
+        let link_id = io_act.link.as_ref().map_or(Self::UNRESOLVED_LINK, |link| {
+            Id::<Link>::get_from_ext(link).internal()
+        });
         let act_type: Id<String> = Id::get_from_ext(&io_act.r#type);
         Activity {
             x: io_act.x,
             y: io_act.y,
             act_type: act_type.internal(),
-            link_id: link_id.internal(),
+            link_id,
             start_time: parse_time_opt(&io_act.start_time),
             end_time: parse_time_opt(&io_act.end_time),
             max_dur: parse_time_opt(&io_act.max_dur),
@@ -318,15 +499,28 @@ impl Activity {
         }
     }
 
-    fn cmp_end_time(&self, now: u32) -> u32 {
-        if let Some(end_time) = self.end_time {
-            end_time
-        } else if let Some(max_dur) = self.max_dur {
-            now + max_dur
-        } else {
-            // supposed to be an equivalent for OptionalTime.undefined() in the java code
-            u32::MAX
-        }
+    fn cmp_end_time(&self, now: u32, interpretation: ActivityDurationInterpretation) -> u32 {
+        let by_duration = self.max_dur.map(|max_dur| now + max_dur);
+        let end_time = match interpretation {
+            ActivityDurationInterpretation::TryEndTimeThenDuration => self.end_time.or(by_duration),
+            // interaction activities only have a duration. They must end nevertheless.
+            ActivityDurationInterpretation::EndTimeOnly if self.end_time.is_none() => {
+                by_duration.filter(|_| self.is_interaction())
+            }
+            ActivityDurationInterpretation::EndTimeOnly => self.end_time,
+            ActivityDurationInterpretation::MinOfDurationAndEndTime => {
+                match (self.end_time, by_duration) {
+                    (Some(end_time), Some(by_duration)) => Some(end_time.min(by_duration)),
+                    (end_time, by_duration) => end_time.or(by_duration),
+                }
+            }
+        };
+        // supposed to be an equivalent for OptionalTime.undefined() in the java code
+        end_time.unwrap_or(u32::MAX)
+    }
+
+    pub fn has_link(&self) -> bool {
+        self.link_id != Self::UNRESOLVED_LINK
     }
 
     pub fn is_interaction(&self) -> bool {
@@ -337,12 +531,22 @@ impl Activity {
 }
 
 impl Leg {
+    /// Name of the leg attribute in plans files, which references the driver of a ride leg.
+    pub const DRIVER_ATTRIBUTE: &'static str = "driverId";
+
     fn from_io(io_leg: &IOLeg, person_id: &Id<Person>) -> Self {
         let routing_mode_ext = Attrs::find_or_else_opt(&io_leg.attributes, "routingMode", || "car");
 
         let routing_mode: Id<String> = Id::create(routing_mode_ext);
-        let mode = Id::get_from_ext(io_leg.mode.as_str());
+        // modes and vehicles without a vehicle type are created here, so that they can be handled
+        // by a fallback after loading. See [crate::simulation::population::unknown_modes].
+        let mode = Id::create(io_leg.mode.as_str());
         let route = Route::from_io(&io_leg.route, person_id, &mode);
+        let driver = io_leg
+            .attributes
+            .as_ref()
+            .and_then(|attrs| attrs.find(Self::DRIVER_ATTRIBUTE))
+            .map(|driver| Id::<Person>::get_from_ext(driver).internal());
 
         Self {
             route: Some(route),
@@ -350,6 +554,7 @@ impl Leg {
             trav_time: Self::parse_trav_time(&io_leg.trav_time, &io_leg.route.trav_time),
             dep_time: parse_time_opt(&io_leg.dep_time),
             routing_mode: routing_mode.internal(),
+            driver,
         }
     }
 
@@ -360,6 +565,7 @@ impl Leg {
             trav_time,
             dep_time,
             routing_mode: 0,
+            driver: None,
         }
     }
 
@@ -374,9 +580,20 @@ impl Leg {
                 distance: 0.0,
                 route: Vec::new(),
             }),
+            driver: None,
         }
     }
 
+    /// A ride leg, where the person is a passenger in another agent's vehicle.
+    pub fn is_ride(&self) -> bool {
+        self.driver.is_some()
+    }
+
+    /// Distance of the route of the leg, or 0 if the leg has no route.
+    pub fn distance(&self) -> f64 {
+        self.route.as_ref().map_or(0., |route| route.distance)
+    }
+
     fn parse_trav_time(leg_trav_time: &Option<String>, route_trav_time: &Option<String>) -> u32 {
         if let Some(trav_time) = parse_time_opt(leg_trav_time) {
             trav_time
@@ -404,21 +621,30 @@ impl Route {
         *self.route.last().unwrap()
     }
 
+    /// Only routes of type `links` are simulated on the network. There is no transit simulation,
+    /// so `default_pt`, `experimentalPt1` and routes of any other type are read like `generic`
+    /// routes from their start and end link. Their legs are teleported with the vehicle type of
+    /// their mode, or with the fallback for unknown modes, if there is none. See
+    /// [crate::simulation::population::unknown_modes].
     fn from_io(io_route: &IORoute, person_id: &Id<Person>, mode: &Id<String>) -> Self {
-        let route = match io_route.r#type.as_str() {
-            "generic" => Self::from_io_generic(io_route, person_id, mode),
+        match io_route.r#type.as_str() {
             "links" => Self::from_io_net_route(io_route, person_id, mode),
-            _t => panic!("Unsupported route type: '{_t}'"),
-        };
-
-        route
+            "generic" => Self::from_io_generic(io_route, person_id, mode),
+            other => {
+                debug!(
+                    "Reading route of type '{other}' of person {} as generic route.",
+                    person_id.external()
+                );
+                Self::from_io_generic(io_route, person_id, mode)
+            }
+        }
     }
 
     fn from_io_generic(io_route: &IORoute, person_id: &Id<Person>, mode: &Id<String>) -> Self {
         let start_link: Id<Link> = Id::get_from_ext(&io_route.start_link);
         let end_link: Id<Link> = Id::get_from_ext(&io_route.end_link);
         let external = format!("{}_{}", person_id.external(), mode.external());
-        let veh_id: Id<Vehicle> = Id::get_from_ext(&external);
+        let veh_id: Id<Vehicle> = Id::create(&external);
 
         Route {
             distance: io_route.distance,
@@ -434,7 +660,7 @@ impl Route {
             if veh_id_ext.eq("null") {
                 Self::from_io_generic(io_route, person_id, mode)
             } else {
-                let veh_id: Id<Vehicle> = Id::get_from_ext(veh_id_ext.as_str());
+                let veh_id: Id<Vehicle> = Id::create(veh_id_ext.as_str());
                 let link_ids = match &io_route.route {
                     None => Vec::new(),
                     Some(encoded_links) => encoded_links
@@ -454,7 +680,7 @@ impl Route {
     }
 }
 
-fn parse_time_opt(value: &Option<String>) -> Option<u32> {
+pub(crate) fn parse_time_opt(value: &Option<String>) -> Option<u32> {
     if let Some(time) = value.as_ref() {
         parse_time(time)
     } else {
@@ -462,7 +688,17 @@ fn parse_time_opt(value: &Option<String>) -> Option<u32> {
     }
 }
 
-fn parse_time(value: &str) -> Option<u32> {
+/// Formats seconds as `HH:MM:SS`, the way matsim writes times. Hours may exceed 24.
+pub(crate) fn format_time(time: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+pub(crate) fn parse_time(value: &str) -> Option<u32> {
     let split: Vec<&str> = value.split(':').collect();
     if split.len() == 3 {
         let hour: u32 = split.first().unwrap().parse().unwrap();
@@ -474,3 +710,186 @@ fn parse_time(value: &str) -> Option<u32> {
         None
     }
 }
+
+/// Parses times given either as `HH:MM:SS` or as seconds.
+pub(crate) fn parse_time_or_seconds(value: &str) -> Option<u32> {
+    parse_time(value).or_else(|| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use rand::Rng;
+
+    use crate::simulation::config::ActivityDurationInterpretation;
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::io::IORoute;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+
+    #[test]
+    fn activity_duration_interpretation() {
+        let work = Id::<String>::create("work").internal();
+        let interaction = Id::<String>::create("car interaction").internal();
+        let end_time_of = |act: Activity, interpretation| {
+            let mut plan = Plan::new();
+            plan.add_act(act);
+            Person::new(1, plan).wakeup_time(100, interpretation)
+        };
+        let both = || Activity::new(0., 0., work, 0, None, Some(1000), Some(500));
+        let duration = || Activity::new(0., 0., work, 0, None, None, Some(500));
+
+        use ActivityDurationInterpretation::*;
+        assert_eq!(1000, end_time_of(both(), TryEndTimeThenDuration));
+        assert_eq!(600, end_time_of(duration(), TryEndTimeThenDuration));
+        assert_eq!(1000, end_time_of(both(), EndTimeOnly));
+        assert_eq!(u32::MAX, end_time_of(duration(), EndTimeOnly));
+        assert_eq!(
+            100,
+            end_time_of(Activity::interaction(0, interaction), EndTimeOnly)
+        );
+        assert_eq!(600, end_time_of(both(), MinOfDurationAndEndTime));
+        assert_eq!(600, end_time_of(duration(), MinOfDurationAndEndTime));
+    }
+
+    #[test]
+    fn person_attributes() {
+        let mut person = Person::new(1, Plan::new());
+        assert_eq!(None, person.subpopulation());
+        assert_eq!(None, person.income());
+        assert!(person.has_car_available());
+
+        person.set_attribute(Person::SUBPOPULATION, "freight");
+        person.set_attribute(Person::INCOME, "4200.5");
+        person.set_attribute(Person::CAR_AVAILABILITY, "never");
+
+        assert_eq!(Some("freight"), person.subpopulation());
+        assert_eq!(Some(4200.5), person.income());
+        assert!(!person.has_car_available());
+        assert_eq!(Some("never"), person.attribute("carAvail"));
+    }
+
+    #[test]
+    fn agent_rng_travels_with_agent() {
+        let mut person = Person::new(1, Plan::new());
+        let first = person.next_rng().gen::<u64>();
+        assert_eq!(1, person.rng_counter);
+
+        // a migrated agent continues with the same random numbers as the original one
+        let mut migrated = Person::decode(person.encode_to_vec().as_slice()).unwrap();
+        let second = person.next_rng().gen::<u64>();
+        assert_ne!(first, second);
+        assert_eq!(second, migrated.next_rng().gen::<u64>());
+
+        // salted numbers don't advance the counter
+        assert_eq!(
+            person.salted_rng(7).gen::<u64>(),
+            person.salted_rng(7).gen::<u64>()
+        );
+        assert_eq!(2, person.rng_counter);
+    }
+
+    #[test]
+    fn person_can_drive() {
+        let mut person = Person::new(1, Plan::new());
+        assert!(person.can_drive());
+
+        person.set_attribute(Person::LICENSE, "no");
+        assert!(!person.has_license());
+        assert!(!person.can_drive());
+    }
+
+    #[test]
+    fn main_mode() {
+        let home: Id<String> = Id::create("home");
+        let interaction: Id<String> = Id::create("car interaction");
+        let walk: Id<String> = Id::create("walk");
+        let car: Id<String> = Id::create("car");
+
+        // home - walk - interaction - car - interaction - walk - home - walk - home
+        let mut plan = Plan::new();
+        let act =
+            |act_type: &Id<String>| Activity::new(0., 0., act_type.internal(), 0, None, None, None);
+        let leg = |mode: &Id<String>| Leg::new(Route::default(), mode.internal(), 0, None);
+        plan.add_act(act(&home));
+        plan.add_leg(leg(&walk));
+        plan.add_act(act(&interaction));
+        plan.add_leg(leg(&car));
+        plan.add_act(act(&interaction));
+        plan.add_leg(leg(&walk));
+        plan.add_act(act(&home));
+        plan.add_leg(leg(&walk));
+        plan.add_act(act(&home));
+
+        for leg_index in 0..3 {
+            assert_eq!(car.internal(), plan.main_mode(leg_index));
+        }
+        assert_eq!(walk.internal(), plan.main_mode(3));
+    }
+
+    #[test]
+    fn plan_within_network() {
+        let mut network = Network::new();
+        let from = Node::new(Id::create("pw-n0"), 0., 0., 0, 1);
+        let to = Node::new(Id::create("pw-n1"), 100., 0., 0, 1);
+        let link = Link::new_with_default(Id::create("pw-l0"), &from, &to);
+        network.add_node(from);
+        network.add_node(to);
+        network.add_link(link);
+        // a link of the full network, which is not part of the cut-out.
+        let outside = Id::<Link>::create("pw-outside").internal();
+
+        let home = Id::<String>::create("pw-home").internal();
+        let car = Id::<String>::create("car").internal();
+        let plan = |act_link: u64, route: Vec<u64>| {
+            let mut plan = Plan::new();
+            plan.add_act(Activity::new(0., 0., home, act_link, None, Some(0), None));
+            plan.add_leg(Leg::new(
+                Route {
+                    veh_id: 0,
+                    distance: 0.,
+                    route,
+                },
+                car,
+                0,
+                None,
+            ));
+            plan.add_act(Activity::new(0., 0., home, 0, None, None, None));
+            plan
+        };
+
+        assert!(plan(0, vec![0, 0]).is_within(&network));
+        assert!(plan(Activity::UNRESOLVED_LINK, vec![0]).is_within(&network));
+        assert!(!plan(outside, vec![0]).is_within(&network));
+        assert!(!plan(0, vec![0, outside, 0]).is_within(&network));
+    }
+
+    #[test]
+    fn pt_route_is_read_as_generic_route() {
+        let start = Id::<Link>::create("pt-route-start");
+        let end = Id::<Link>::create("pt-route-end");
+        let person = Id::<Person>::create("pt-route-person");
+        let pt = Id::<String>::create("pt");
+        let io_route = IORoute {
+            r#type: String::from("default_pt"),
+            start_link: String::from("pt-route-start"),
+            end_link: String::from("pt-route-end"),
+            trav_time: Some(String::from("00:10:00")),
+            distance: 1500.,
+            vehicle: None,
+            route: Some(String::from(
+                r#"{"transitRouteId":"r1","boardingTime":"08:05:00","transitLineId":"l1","accessFacilityId":"s1","egressFacilityId":"s2"}"#,
+            )),
+        };
+
+        let route = Route::from_io(&io_route, &person, &pt);
+
+        assert_eq!(vec![start.internal(), end.internal()], route.route);
+        assert_eq!(1500., route.distance);
+        assert_eq!(
+            Id::<Vehicle>::get_from_ext("pt-route-person_pt").internal(),
+            route.veh_id
+        );
+    }
+}
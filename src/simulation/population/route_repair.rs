@@ -0,0 +1,317 @@
+use nohash_hasher::IntMap;
+use tracing::warn;
+
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::population::population::Population;
+use crate::simulation::population::validation::{first_gap, first_link_without_mode, link_exists};
+use crate::simulation::replanning::routing::alt_router::AltRouter;
+use crate::simulation::replanning::routing::network_converter::NetworkConverter;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Leg, Person};
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// Re-routes network legs whose routes are not connected or use links which don't allow the mode
+/// of the vehicle. The new route is the fastest route between the first and the last link of the
+/// old route. Legs which can't be repaired are left as they are, so that they are reported by
+/// [crate::simulation::population::validation::validate].
+///
+/// Returns the number of plans which were repaired.
+pub fn repair_routes(population: &mut Population, network: &Network, garage: &Garage) -> usize {
+    // creating a router is expensive, so routers are only created for vehicle types which need one.
+    let mut routers: IntMap<Id<VehicleType>, AltRouter> = IntMap::default();
+    let mut repaired_plans = 0;
+
+    for person in population.persons.values_mut() {
+        let person_id: Id<Person> = Id::get(person.id);
+        let plan = if let Some(plan) = person.plan.as_mut() {
+            plan
+        } else {
+            continue;
+        };
+        let mut repaired = false;
+        for leg in plan.legs.iter_mut() {
+            if repair_leg(leg, network, garage, &mut routers) {
+                repaired = true;
+            } else if is_broken(leg, network, garage) {
+                warn!(
+                    "Failed to repair the route of a leg of person {}.",
+                    person_id.external()
+                );
+            }
+        }
+        if repaired {
+            repaired_plans += 1;
+        }
+    }
+
+    repaired_plans
+}
+
+/// Sets the distance of network routes which have none, e.g. because the plans were created
+/// without distances, to the sum of their link lengths. See [Network::route_distance].
+///
+/// Returns the number of legs whose distance was set.
+pub fn compute_route_distances(
+    population: &mut Population,
+    network: &Network,
+    garage: &Garage,
+) -> usize {
+    let mut updated = 0;
+    let legs = population
+        .persons
+        .values_mut()
+        .filter_map(|person| person.plan.as_mut())
+        .flat_map(|plan| plan.legs.iter_mut());
+    for leg in legs {
+        if leg.is_ride() {
+            continue;
+        }
+        let route = if let Some(route) = leg.route.as_mut() {
+            route
+        } else {
+            continue;
+        };
+        if route.distance > 0.
+            || route.route.len() < 2
+            || !route.route.iter().all(|l| link_exists(network, *l))
+            || network_vehicle_type(route.veh_id, garage).is_none()
+        {
+            continue;
+        }
+        route.distance = network.route_distance(&route.route);
+        updated += 1;
+    }
+    updated
+}
+
+/// Returns true, if the route of the leg was broken and replaced with a new route.
+fn repair_leg(
+    leg: &mut Leg,
+    network: &Network,
+    garage: &Garage,
+    routers: &mut IntMap<Id<VehicleType>, AltRouter>,
+) -> bool {
+    if !is_broken(leg, network, garage) {
+        return false;
+    }
+    let route = leg.route.as_mut().unwrap();
+    let type_id = garage
+        .vehicles
+        .get(&Id::<Vehicle>::get(route.veh_id))
+        .unwrap();
+    let veh_type = garage.vehicle_types.get(type_id).unwrap();
+    let start = *route.route.first().unwrap();
+    let end = *route.route.last().unwrap();
+    // start and end link are kept, so they have to be usable with the vehicle.
+    if first_link_without_mode(&[start, end], network, veh_type).is_some() {
+        return false;
+    }
+
+    let router = routers.entry(type_id.clone()).or_insert_with(|| {
+        AltRouter::new(NetworkConverter::convert_network(network, Some(veh_type)))
+    });
+    let path = if let Some(path) = router.query_links(start, end).path {
+        path
+    } else {
+        return false;
+    };
+
+    route.distance = network.route_distance(&path);
+    route.route = path;
+    true
+}
+
+/// Whether the leg is a network leg, which can't be simulated with its route. Legs with missing
+/// links or vehicles are not considered broken, as re-routing can't fix them.
+fn is_broken(leg: &Leg, network: &Network, garage: &Garage) -> bool {
+    if leg.is_ride() {
+        return false;
+    }
+    let route = if let Some(route) = leg.route.as_ref() {
+        route
+    } else {
+        return false;
+    };
+    if route.route.len() < 2 || !route.route.iter().all(|l| link_exists(network, *l)) {
+        return false;
+    }
+    let veh_type = if let Some(veh_type) = network_vehicle_type(route.veh_id, garage) {
+        veh_type
+    } else {
+        return false;
+    };
+
+    first_gap(&route.route, network).is_some()
+        || first_link_without_mode(&route.route, network, veh_type).is_some()
+}
+
+/// The type of the vehicle, if the vehicle is known and simulated on the network.
+fn network_vehicle_type(veh_id: u64, garage: &Garage) -> Option<&VehicleType> {
+    garage
+        .vehicles
+        .get(&Id::<Vehicle>::get(veh_id))
+        .and_then(|type_id| garage.vehicle_types.get(type_id))
+        .filter(|veh_type| veh_type.lod() == LevelOfDetail::Network)
+}
+
+#[cfg(test)]
+mod tests {
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::config::RoutingMode;
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::population::Population;
+    use crate::simulation::population::route_repair::{compute_route_distances, repair_routes};
+    use crate::simulation::population::validation::validate;
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::test_utils::create_vehicle_type;
+
+    /// Four links in a row, and a shortcut from the first to the last node which cars may not use.
+    fn network() -> Network {
+        let mut network = Network::new();
+        let car: IntSet<Id<String>> = [Id::create("car")].into_iter().collect();
+        for i in 0..5 {
+            network.add_node(Node::new(
+                Id::create(&format!("rr-n{i}")),
+                i as f64 * 100.,
+                0.,
+                0,
+                1,
+            ));
+        }
+        for (id, from, to) in [
+            ("rr-a", 0, 1),
+            ("rr-b", 1, 2),
+            ("rr-c", 2, 3),
+            ("rr-d", 3, 4),
+            ("rr-shortcut", 1, 3),
+        ] {
+            let mut link =
+                Link::new_with_default(Id::create(id), &network.nodes[from], &network.nodes[to]);
+            link.length = 100.;
+            if id != "rr-shortcut" {
+                link.modes = car.clone();
+            }
+            network.add_link(link);
+        }
+        network
+    }
+
+    fn person(id: &str, route: Vec<&str>, garage: &mut Garage) -> Person {
+        let person_id: Id<Person> = Id::create(id);
+        let veh_id = garage.add_veh_id(&person_id, &Id::get_from_ext("car"));
+        let route: Vec<u64> = route
+            .into_iter()
+            .map(|l| Id::<Link>::get_from_ext(l).internal())
+            .collect();
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., 0, route[0], None, Some(0), None));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: veh_id.internal(),
+                distance: 0.,
+                route: route.clone(),
+            },
+            0,
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(
+            0.,
+            0.,
+            0,
+            *route.last().unwrap(),
+            None,
+            None,
+            None,
+        ));
+        Person::new(person_id.internal(), plan)
+    }
+
+    #[test]
+    fn repair_broken_routes() {
+        let network = network();
+        let mut garage = Garage::new();
+        let mut car = create_vehicle_type(&Id::create("car"), Id::create("car"));
+        // the router computes travel times from the speed of the vehicle type.
+        car.max_v = 10.;
+        garage.add_veh_type(car);
+        let mut population = Population::new();
+        for person in [
+            person("rr-valid", vec!["rr-a", "rr-b", "rr-c"], &mut garage),
+            person("rr-gap", vec!["rr-a", "rr-d"], &mut garage),
+            person("rr-mode", vec!["rr-a", "rr-shortcut", "rr-d"], &mut garage),
+        ] {
+            population.persons.insert(Id::get(person.id), person);
+        }
+
+        let repaired = repair_routes(&mut population, &network, &garage);
+
+        assert_eq!(2, repaired);
+        assert!(validate(&population, &network, &garage, RoutingMode::UsePlans).is_empty());
+        let expected: Vec<u64> = ["rr-a", "rr-b", "rr-c", "rr-d"]
+            .into_iter()
+            .map(|l| Id::<Link>::get_from_ext(l).internal())
+            .collect();
+        for id in ["rr-gap", "rr-mode"] {
+            let person = population.persons.get(&Id::get_from_ext(id)).unwrap();
+            let route = person.plan.as_ref().unwrap().legs[0]
+                .route
+                .as_ref()
+                .unwrap();
+            assert_eq!(expected, route.route);
+            assert_eq!(300., route.distance);
+        }
+    }
+
+    #[test]
+    fn compute_missing_distances() {
+        let network = network();
+        let mut garage = Garage::new();
+        garage.add_veh_type(create_vehicle_type(&Id::create("car"), Id::create("car")));
+        let mut population = Population::new();
+        let mut with_distance = person("rd-with-distance", vec!["rr-a", "rr-b"], &mut garage);
+        with_distance.plan.as_mut().unwrap().legs[0]
+            .route
+            .as_mut()
+            .unwrap()
+            .distance = 42.;
+        for person in [
+            person(
+                "rd-without-distance",
+                vec!["rr-a", "rr-b", "rr-c"],
+                &mut garage,
+            ),
+            with_distance,
+        ] {
+            population.persons.insert(Id::get(person.id), person);
+        }
+
+        assert_eq!(
+            1,
+            compute_route_distances(&mut population, &network, &garage)
+        );
+
+        let distance = |id: &str| {
+            population
+                .persons
+                .get(&Id::get_from_ext(id))
+                .unwrap()
+                .plan
+                .as_ref()
+                .unwrap()
+                .legs[0]
+                .route
+                .as_ref()
+                .unwrap()
+                .distance
+        };
+        // the first link is not counted
+        assert_eq!(200., distance("rd-without-distance"));
+        assert_eq!(42., distance("rd-with-distance"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use clap::ValueEnum;
+use tracing::warn;
+
+use crate::simulation::id::Id;
+use crate::simulation::population::population::Population;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Leg, Person};
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// What happens with persons whose plans contain legs of modes, for which there is no vehicle.
+#[derive(PartialEq, Debug, Default, ValueEnum, Clone, Copy)]
+pub enum UnknownModeFallback {
+    /// Refuse to load the population.
+    #[default]
+    Fail,
+    /// Teleport such legs with a vehicle type, which is created for each unknown mode.
+    Teleport,
+    /// Remove the affected persons from the population.
+    Skip,
+}
+
+/// Number of affected persons per unknown mode.
+#[derive(Debug, Default, PartialEq)]
+pub struct UnknownModes {
+    pub persons_by_mode: BTreeMap<String, usize>,
+}
+
+impl UnknownModes {
+    pub fn is_empty(&self) -> bool {
+        self.persons_by_mode.is_empty()
+    }
+}
+
+impl Display for UnknownModes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let modes: Vec<String> = self
+            .persons_by_mode
+            .iter()
+            .map(|(mode, count)| format!("{mode}: {count} persons"))
+            .collect();
+        write!(f, "{}", modes.join(", "))
+    }
+}
+
+/// Finds legs, whose vehicles are not part of the garage, which usually happens if there is no
+/// vehicle type for the mode of the leg, and applies the fallback to them. Ride legs are not
+/// affected, as passengers use the vehicle of their driver.
+///
+/// Teleported legs use `speed` in m/s, unless the leg already has a travel time. Returns the
+/// affected persons per mode. Panics with a summary of the unknown modes if the fallback is
+/// [UnknownModeFallback::Fail].
+pub fn apply_fallback(
+    population: &mut Population,
+    garage: &mut Garage,
+    fallback: UnknownModeFallback,
+    speed: f32,
+) -> UnknownModes {
+    let mut summary = UnknownModes::default();
+    let mut affected: Vec<Id<Person>> = Vec::new();
+    for (person_id, person) in population.persons.iter() {
+        let mut modes: Vec<String> = unknown_legs(person, garage)
+            .map(|leg| Id::<String>::get(leg.mode).external().to_string())
+            .collect();
+        if modes.is_empty() {
+            continue;
+        }
+        modes.sort();
+        modes.dedup();
+        for mode in modes {
+            *summary.persons_by_mode.entry(mode).or_default() += 1;
+        }
+        affected.push(person_id.clone());
+    }
+    if summary.is_empty() {
+        return summary;
+    }
+
+    match fallback {
+        UnknownModeFallback::Fail => {
+            panic!("There are legs with modes, which have no vehicle type: {summary}")
+        }
+        UnknownModeFallback::Teleport => {
+            warn!("Teleporting legs with modes, which have no vehicle type: {summary}");
+            // vehicle types are created in a fixed order, so that their ids don't depend on the
+            // iteration order of the population.
+            for mode in summary.persons_by_mode.keys() {
+                let type_id = Id::<VehicleType>::create(mode);
+                if !garage.vehicle_types.contains_key(&type_id) {
+                    garage.add_veh_type(teleported_type(&type_id, mode, speed));
+                }
+            }
+            affected.sort_by_key(|id| id.internal());
+            for person_id in affected {
+                let person = population.persons.get_mut(&person_id).unwrap();
+                teleport_unknown_legs(person, garage);
+            }
+        }
+        UnknownModeFallback::Skip => {
+            warn!("Skipping persons with legs of modes, which have no vehicle type: {summary}");
+            for person_id in affected {
+                population.persons.remove(&person_id);
+            }
+        }
+    }
+    summary
+}
+
+fn unknown_legs<'p>(person: &'p Person, garage: &'p Garage) -> impl Iterator<Item = &'p Leg> {
+    person
+        .plan
+        .iter()
+        .flat_map(|plan| plan.legs.iter())
+        .filter(|leg| !leg.is_ride())
+        .filter(|leg| {
+            leg.route
+                .as_ref()
+                .is_some_and(|r| !garage.vehicles.contains_key(&Id::<Vehicle>::get(r.veh_id)))
+        })
+}
+
+fn teleport_unknown_legs(person: &mut Person, garage: &mut Garage) {
+    let person_id: Id<Person> = Id::get(person.id);
+    for leg in person.plan.as_mut().unwrap().legs.iter_mut() {
+        if leg.is_ride() {
+            continue;
+        }
+        let route = if let Some(route) = leg.route.as_mut() {
+            route
+        } else {
+            continue;
+        };
+        let veh_id: Id<Vehicle> = Id::get(route.veh_id);
+        if garage.vehicles.contains_key(&veh_id) {
+            continue;
+        }
+        let mode: Id<String> = Id::get(leg.mode);
+        let type_id = Id::<VehicleType>::get_from_ext(mode.external());
+        let veh_type = garage.vehicle_types.get(&type_id).unwrap();
+        if leg.trav_time == 0 && veh_type.max_v > 0. {
+            leg.trav_time = (route.distance / veh_type.max_v as f64) as u32;
+        }
+        garage.vehicles.insert(veh_id.clone(), type_id);
+        garage.set_owner(veh_id, person_id.clone());
+    }
+}
+
+fn teleported_type(id: &Id<VehicleType>, mode: &str, speed: f32) -> VehicleType {
+    VehicleType {
+        id: id.internal(),
+        length: 0.,
+        width: 0.,
+        max_v: speed,
+        pce: 0.,
+        fef: 0.,
+        net_mode: Id::<String>::get_from_ext(mode).internal(),
+        lod: LevelOfDetail::Teleported as i32,
+        seats: 1,
+        standing_room: 0,
+        attributes: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::Id;
+    use crate::simulation::population::population::Population;
+    use crate::simulation::population::unknown_modes::{apply_fallback, UnknownModeFallback};
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+    use crate::test_utils::create_vehicle_type;
+
+    fn setup() -> (Population, Garage) {
+        let mut garage = Garage::new();
+        garage.add_veh_type(create_vehicle_type(
+            &Id::create("um-car"),
+            Id::create("um-car"),
+        ));
+        let mut population = Population::new();
+        for (person, mode) in [("um-p1", "um-car"), ("um-p2", "um-hoverboard")] {
+            let person_id: Id<Person> = Id::create(person);
+            let mode: Id<String> = Id::create(mode);
+            let veh_id: Id<Vehicle> = Id::create(&format!("{person}_{}", mode.external()));
+            if mode.external() == "um-car" {
+                garage.add_veh_id(&person_id, &Id::get_from_ext("um-car"));
+            }
+            let mut plan = Plan::new();
+            plan.add_act(Activity::new(0., 0., 0, 0, None, Some(0), None));
+            plan.add_leg(Leg::new(
+                Route {
+                    veh_id: veh_id.internal(),
+                    distance: 100.,
+                    route: vec![0, 1],
+                },
+                mode.internal(),
+                0,
+                None,
+            ));
+            plan.add_act(Activity::new(0., 0., 0, 1, None, None, None));
+            population
+                .persons
+                .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+        }
+        (population, garage)
+    }
+
+    #[test]
+    fn teleport_unknown_modes() {
+        let (mut population, mut garage) = setup();
+
+        let summary = apply_fallback(
+            &mut population,
+            &mut garage,
+            UnknownModeFallback::Teleport,
+            2.,
+        );
+
+        assert_eq!(Some(&1), summary.persons_by_mode.get("um-hoverboard"));
+        assert_eq!(2, population.persons.len());
+        let veh_type = garage
+            .vehicle_types
+            .get(&Id::<VehicleType>::get_from_ext("um-hoverboard"))
+            .unwrap();
+        assert_eq!(LevelOfDetail::Teleported, veh_type.lod());
+        let person = population.persons.get(&Id::get_from_ext("um-p2")).unwrap();
+        assert_eq!(50, person.plan.as_ref().unwrap().legs[0].trav_time);
+        assert!(garage
+            .vehicles
+            .contains_key(&Id::get_from_ext("um-p2_um-hoverboard")));
+    }
+
+    #[test]
+    fn skip_unknown_modes() {
+        let (mut population, mut garage) = setup();
+
+        let summary = apply_fallback(&mut population, &mut garage, UnknownModeFallback::Skip, 2.);
+
+        assert_eq!(1, summary.persons_by_mode.len());
+        assert_eq!(1, population.persons.len());
+        assert!(population.persons.contains_key(&Id::get_from_ext("um-p1")));
+    }
+
+    #[test]
+    #[should_panic(expected = "um-hoverboard: 1 persons")]
+    fn fail_on_unknown_modes() {
+        let (mut population, mut garage) = setup();
+        apply_fallback(&mut population, &mut garage, UnknownModeFallback::Fail, 2.);
+    }
+}
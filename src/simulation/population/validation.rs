@@ -0,0 +1,356 @@
+use std::fmt::{Display, Formatter};
+
+use crate::simulation::config::RoutingMode;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::{Link, Network};
+use crate::simulation::population::population::Population;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Leg, Person, Plan};
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// A problem with the plan of a person, which would make the simulation fail or behave unexpectedly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanProblem {
+    pub person: Id<Person>,
+    pub message: String,
+}
+
+impl Display for PlanProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Person {} (#{}): {}",
+            self.person.external(),
+            self.person.internal(),
+            self.message
+        )
+    }
+}
+
+/// Checks the plans of all persons against the network and the vehicles. This checks that
+/// - activities and legs alternate, starting and ending with an activity,
+/// - activities and routes refer to existing links,
+/// - legs refer to existing vehicles,
+/// - network routes are connected and all links allow the mode of the vehicle.
+///
+/// With ad-hoc routing, legs are routed during the simulation. Then, routes may be missing or empty
+/// and their links are not checked.
+///
+/// All problems are returned, so that they can be reported at once before the simulation starts.
+pub fn validate(
+    population: &Population,
+    network: &Network,
+    garage: &Garage,
+    routing_mode: RoutingMode,
+) -> Vec<PlanProblem> {
+    let mut problems = Vec::new();
+    for person in population.persons.values() {
+        let person_id: Id<Person> = Id::get(person.id);
+        let mut report = |message: String| {
+            problems.push(PlanProblem {
+                person: person_id.clone(),
+                message,
+            })
+        };
+        match person.plan.as_ref() {
+            Some(plan) => validate_plan(plan, network, garage, routing_mode, &mut report),
+            None => report(String::from("has no plan")),
+        }
+    }
+    problems.sort_by_key(|p| p.person.internal());
+    problems
+}
+
+fn validate_plan(
+    plan: &Plan,
+    network: &Network,
+    garage: &Garage,
+    routing_mode: RoutingMode,
+    report: &mut impl FnMut(String),
+) {
+    if plan.acts.is_empty() {
+        report(String::from("has no activities"));
+        return;
+    }
+    if plan.acts.len() != plan.legs.len() + 1 {
+        report(format!(
+            "has {} activities and {} legs. Activities and legs must alternate, starting and ending with an activity",
+            plan.acts.len(),
+            plan.legs.len()
+        ));
+    }
+
+    for (i, act) in plan.acts.iter().enumerate() {
+        if !link_exists(network, act.link_id) {
+            report(format!(
+                "activity {i} is on link #{}, which is not part of the network",
+                act.link_id
+            ));
+        }
+    }
+
+    for (i, leg) in plan.legs.iter().enumerate() {
+        validate_leg(i, leg, network, garage, routing_mode, report);
+    }
+}
+
+fn validate_leg(
+    index: usize,
+    leg: &Leg,
+    network: &Network,
+    garage: &Garage,
+    routing_mode: RoutingMode,
+    report: &mut impl FnMut(String),
+) {
+    let adhoc = routing_mode == RoutingMode::AdHoc;
+    let route = if let Some(route) = leg.route.as_ref() {
+        route
+    } else {
+        if !adhoc {
+            report(format!("leg {index} has no route"));
+        }
+        return;
+    };
+    if !adhoc {
+        if route.route.is_empty() {
+            report(format!("leg {index} has a route without links"));
+            return;
+        }
+        if let Some(link) = route.route.iter().find(|l| !link_exists(network, **l)) {
+            report(format!(
+                "leg {index} has a route with link #{link}, which is not part of the network"
+            ));
+            return;
+        }
+    }
+    // passengers travel with the vehicle of their driver.
+    if leg.is_ride() {
+        return;
+    }
+
+    let veh_type = Id::<Vehicle>::try_get(route.veh_id)
+        .and_then(|veh_id| garage.vehicles.get(&veh_id))
+        .and_then(|type_id| garage.vehicle_types.get(type_id));
+    let veh_type = if let Some(veh_type) = veh_type {
+        veh_type
+    } else {
+        report(format!(
+            "leg {index} uses vehicle #{}, which is not part of the vehicles",
+            route.veh_id
+        ));
+        return;
+    };
+    if adhoc || veh_type.lod() != LevelOfDetail::Network {
+        return;
+    }
+
+    if let Some((from, to)) = first_gap(&route.route, network) {
+        report(format!(
+            "leg {index} has a route which is not connected between links {} and {}",
+            from.id.external(),
+            to.id.external()
+        ));
+    }
+    let mode: Id<String> = Id::get(veh_type.net_mode);
+    if let Some(link) = first_link_without_mode(&route.route, network, veh_type) {
+        report(format!(
+            "leg {index} uses link {}, which doesn't allow mode {}",
+            link.id.external(),
+            mode.external()
+        ));
+    }
+}
+
+/// Links are looked up by their position in the network, which equals their internal id for
+/// networks set up with [Network::add_link]. The id is compared anyway, so that a link is not
+/// mistaken for another one in networks which were put together differently.
+pub(crate) fn link_exists(network: &Network, link_id: u64) -> bool {
+    network
+        .links
+        .get(link_id as usize)
+        .is_some_and(|link| link.id.internal() == link_id)
+}
+
+/// The first pair of consecutive links of a route, where the second link doesn't start at the end
+/// of the first one.
+pub(crate) fn first_gap<'n>(route: &[u64], network: &'n Network) -> Option<(&'n Link, &'n Link)> {
+    route
+        .windows(2)
+        .map(|pair| {
+            (
+                &network.links[pair[0] as usize],
+                &network.links[pair[1] as usize],
+            )
+        })
+        .find(|(from, to)| from.to != to.from)
+}
+
+/// The first link of a route, which doesn't allow the network mode of the vehicle type.
+pub(crate) fn first_link_without_mode<'n>(
+    route: &[u64],
+    network: &'n Network,
+    veh_type: &VehicleType,
+) -> Option<&'n Link> {
+    route
+        .iter()
+        .map(|l| &network.links[*l as usize])
+        .find(|link| !link.contains_mode(veh_type.net_mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::config::RoutingMode;
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::population::population::Population;
+    use crate::simulation::population::validation::validate;
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::test_utils::create_vehicle_type;
+
+    /// Three links in a row and one link which is not connected to them.
+    fn network() -> Network {
+        let mut network = Network::new();
+        let modes: IntSet<Id<String>> = [Id::create("car")].into_iter().collect();
+        for i in 0..6 {
+            network.add_node(Node::new(Id::create(&i.to_string()), i as f64, 0., 0, 1));
+        }
+        for (id, from, to) in [("a", 0, 1), ("b", 1, 2), ("c", 2, 3), ("d", 4, 5)] {
+            let mut link =
+                Link::new_with_default(Id::create(id), &network.nodes[from], &network.nodes[to]);
+            link.modes = modes.clone();
+            network.add_link(link);
+        }
+        network
+    }
+
+    fn garage() -> Garage {
+        let mut garage = Garage::new();
+        let car = Id::create("car");
+        garage.add_veh_type(create_vehicle_type(&car, Id::create("car")));
+        garage
+    }
+
+    fn person(id: &str, route: Vec<u64>, garage: &mut Garage) -> Person {
+        let person_id: Id<Person> = Id::create(id);
+        let veh_id = garage.add_veh_id(&person_id, &Id::get_from_ext("car"));
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., 0, route[0], None, Some(0), None));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: veh_id.internal(),
+                distance: 0.,
+                route: route.clone(),
+            },
+            0,
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(
+            0.,
+            0.,
+            0,
+            *route.last().unwrap(),
+            None,
+            None,
+            None,
+        ));
+        Person::new(person_id.internal(), plan)
+    }
+
+    fn population(persons: Vec<Person>) -> Population {
+        let mut population = Population::new();
+        for person in persons {
+            population.persons.insert(Id::get(person.id), person);
+        }
+        population
+    }
+
+    #[test]
+    fn valid_plan() {
+        let network = network();
+        let mut garage = garage();
+        let population = population(vec![person("p", vec![0, 1, 2], &mut garage)]);
+
+        assert!(validate(&population, &network, &garage, RoutingMode::UsePlans).is_empty());
+    }
+
+    #[test]
+    fn report_all_problems() {
+        let network = network();
+        let mut garage = garage();
+        let disconnected = person("disconnected", vec![0, 3], &mut garage);
+        let unknown_link = person("unknown-link", vec![0, 42], &mut garage);
+        let mut no_leg = person("no-leg", vec![0, 1], &mut garage);
+        no_leg.plan.as_mut().unwrap().legs.clear();
+        let population = population(vec![disconnected, unknown_link, no_leg]);
+
+        let problems = validate(&population, &network, &garage, RoutingMode::UsePlans);
+
+        let persons: Vec<_> = problems.iter().map(|p| p.person.external()).collect();
+        // activity of unknown-link is on link 42 as well.
+        assert_eq!(
+            vec!["disconnected", "unknown-link", "unknown-link", "no-leg"],
+            persons
+        );
+        assert!(problems[0].message.contains("not connected"));
+    }
+
+    #[test]
+    fn mode_not_allowed() {
+        let mut network = network();
+        network.links[1].modes.clear();
+        let mut garage = garage();
+        let population = population(vec![person("p", vec![0, 1, 2], &mut garage)]);
+
+        let problems = validate(&population, &network, &garage, RoutingMode::UsePlans);
+
+        assert_eq!(1, problems.len());
+        assert!(problems[0].message.contains("doesn't allow mode car"));
+    }
+
+    #[test]
+    fn unknown_vehicle() {
+        let network = network();
+        let mut garage = garage();
+        let mut person = person("p", vec![0, 1, 2], &mut garage);
+        // there is no vehicle with this internal id at all.
+        person.plan.as_mut().unwrap().legs[0]
+            .route
+            .as_mut()
+            .unwrap()
+            .veh_id = 42;
+        let population = population(vec![person]);
+
+        let problems = validate(&population, &network, &garage, RoutingMode::UsePlans);
+
+        assert_eq!(1, problems.len());
+        assert!(problems[0].message.contains("uses vehicle #42"));
+    }
+
+    #[test]
+    fn routes_are_not_checked_with_adhoc_routing() {
+        let network = network();
+        let mut garage = garage();
+        let mut without_route = person("without-route", vec![0, 1], &mut garage);
+        without_route.plan.as_mut().unwrap().legs[0].route = None;
+        let mut empty_route = person("empty-route", vec![0, 1], &mut garage);
+        empty_route.plan.as_mut().unwrap().legs[0]
+            .route
+            .as_mut()
+            .unwrap()
+            .route
+            .clear();
+        let disconnected = person("disconnected", vec![0, 3], &mut garage);
+        let population = population(vec![without_route, empty_route, disconnected]);
+
+        assert_eq!(
+            3,
+            validate(&population, &network, &garage, RoutingMode::UsePlans).len()
+        );
+        assert!(validate(&population, &network, &garage, RoutingMode::AdHoc).is_empty());
+    }
+}
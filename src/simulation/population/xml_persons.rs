@@ -0,0 +1,324 @@
+//! Parallel reading of matsim plans files. Deserializing persons dominates the time to load large
+//! plans files, so it is spread over the rayon thread pool: A producer thread streams the file and
+//! cuts it into the xml snippets of single persons, which are deserialized by the workers in
+//! batches.
+//!
+//! Only the deserialization into [IOPerson] happens in parallel. Ids are stored per thread and
+//! their internal ids depend on the order of creation, so persons are converted on the calling
+//! thread, as before. The persons are returned in the order of the file.
+//!
+//! Persons are found by their `<person` and `</person>` tags. Comments, CDATA sections and the
+//! DOCTYPE declaration are skipped, so that persons within them are not read.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::simulation::io::xml;
+use crate::simulation::population::io::IOPerson;
+
+/// Number of persons, which are sent to the workers at once.
+const BATCH_SIZE: usize = 1024;
+/// Number of batches, which the producer may read ahead.
+const BATCHES_AHEAD: usize = 8;
+const READ_SIZE: usize = 1 << 20;
+
+const START_TAG: &[u8] = b"<person";
+const END_TAG: &[u8] = b"</person>";
+const COMMENT: (&[u8], &[u8]) = (b"<!--", b"-->");
+const CDATA: (&[u8], &[u8]) = (b"<![CDATA[", b"]]>");
+const DOCTYPE: &[u8] = b"<!DOCTYPE";
+
+/// The xml of a person and the byte offset of its start tag in the (uncompressed) file.
+struct PersonSnippet {
+    offset: usize,
+    xml: String,
+}
+
+pub(super) fn read_persons(path: &Path) -> Vec<IOPerson> {
+    info!("Starting to read persons from {path:?} in parallel.");
+    let file = File::open(path).unwrap_or_else(|_| panic!("Could not open file at {path:?}"));
+    let reader: Box<dyn Read + Send> = if path.extension().is_some_and(|e| e.eq("gz")) {
+        Box::new(GzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let persons = thread::scope(|scope| {
+        let (sender, receiver) = sync_channel::<Vec<PersonSnippet>>(BATCHES_AHEAD);
+        scope.spawn(move || {
+            let mut snippets = PersonSnippets::new(reader, READ_SIZE);
+            loop {
+                let batch: Vec<PersonSnippet> = snippets.by_ref().take(BATCH_SIZE).collect();
+                // the receiver is gone, if parsing failed.
+                if batch.is_empty() || sender.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut persons = Vec::new();
+        for batch in receiver {
+            let first = persons.len();
+            persons.par_extend(
+                batch
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, snippet)| parse_person(path, first + i, snippet)),
+            );
+        }
+        persons
+    });
+
+    info!(
+        "Finished reading persons. Population contains {} persons",
+        persons.len()
+    );
+    persons
+}
+
+/// Deserializes the `index`th person of the file. Errors are reported with their location in the
+/// file, like errors of [xml::read_from_file].
+fn parse_person(path: &Path, index: usize, snippet: &PersonSnippet) -> IOPerson {
+    let mut deserializer = quick_xml::de::Deserializer::from_str(&snippet.xml);
+    serde_path_to_error::deserialize(&mut deserializer).unwrap_or_else(|e| {
+        let message = xml::parse_error_within(
+            &path.to_string_lossy(),
+            &e,
+            vec![(String::from("person"), index)],
+        );
+        panic!(
+            "{message}. The person starts at byte {} of the file.",
+            snippet.offset
+        )
+    })
+}
+
+/// Cuts the `<person>` elements out of a plans file.
+struct PersonSnippets<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    read_size: usize,
+    /// Number of bytes of the file, which were removed from the buffer already.
+    consumed: usize,
+}
+
+impl<R: Read> PersonSnippets<R> {
+    fn new(reader: R, read_size: usize) -> Self {
+        PersonSnippets {
+            reader,
+            buffer: Vec::new(),
+            read_size,
+            consumed: 0,
+        }
+    }
+
+    /// Appends the next bytes of the file to the buffer. Returns false at the end of the file.
+    fn fill(&mut self) -> bool {
+        let len = self.buffer.len();
+        self.buffer.resize(len + self.read_size, 0);
+        let read = self
+            .reader
+            .read(&mut self.buffer[len..])
+            .unwrap_or_else(|e| panic!("Could not read plans file: {e}"));
+        self.buffer.truncate(len + read);
+        read > 0
+    }
+
+    /// Reads more of the file until the buffer holds `len` bytes from `at`, or the file ends.
+    fn ensure(&mut self, at: usize, len: usize) {
+        while self.buffer.len() < at + len && self.fill() {}
+    }
+
+    /// Removes the first `len` bytes from the buffer.
+    fn consume(&mut self, len: usize) -> Vec<u8> {
+        self.consumed += len;
+        self.buffer.drain(..len).collect()
+    }
+
+    /// Position after the next occurrence of `pattern` at or after `from`. Patterns may be split
+    /// between two reads, so the end of the buffer is searched again after each read.
+    fn find_after(&mut self, from: usize, pattern: &[u8]) -> Option<usize> {
+        let mut from = from;
+        loop {
+            if let Some(i) = self
+                .buffer
+                .get(from..)
+                .and_then(|rest| rest.windows(pattern.len()).position(|w| w == pattern))
+            {
+                return Some(from + i + pattern.len());
+            }
+            from = from.max(self.buffer.len().saturating_sub(pattern.len() - 1));
+            if !self.fill() {
+                return None;
+            }
+        }
+    }
+
+    /// Position of the next tag at or after `from`, which is neither a comment, a CDATA section nor
+    /// the DOCTYPE declaration.
+    fn next_tag(&mut self, from: usize) -> Option<usize> {
+        let mut from = from;
+        loop {
+            let tag = self.find_after(from, b"<")? - 1;
+            self.ensure(tag, CDATA.0.len());
+            let rest = &self.buffer[tag..];
+            from = if rest.starts_with(COMMENT.0) {
+                self.find_after(tag, COMMENT.1)
+                    .expect("Plans file ends within a comment.")
+            } else if rest.starts_with(CDATA.0) {
+                self.find_after(tag, CDATA.1)
+                    .expect("Plans file ends within a CDATA section.")
+            } else if rest.starts_with(DOCTYPE) {
+                self.doctype_end(tag)
+            } else {
+                return Some(tag);
+            };
+        }
+    }
+
+    /// Position after the DOCTYPE declaration starting at `start`. The declaration may contain an
+    /// internal subset in brackets, whose declarations end with `>` as well.
+    fn doctype_end(&mut self, start: usize) -> usize {
+        let end = self
+            .find_after(start, b">")
+            .expect("Plans file ends within the DOCTYPE declaration.");
+        match self.buffer[start..end].iter().position(|b| *b == b'[') {
+            Some(subset) => self
+                .find_after(start + subset, b"]")
+                .and_then(|subset_end| self.find_after(subset_end, b">"))
+                .expect("Plans file ends within the DOCTYPE declaration."),
+            None => end,
+        }
+    }
+
+    /// Whether a `<person` tag starts at `tag`. Other elements starting with `person` don't match.
+    fn is_person_start(&mut self, tag: usize) -> bool {
+        self.ensure(tag, START_TAG.len() + 1);
+        self.buffer[tag..].starts_with(START_TAG)
+            && self
+                .buffer
+                .get(tag + START_TAG.len())
+                .is_some_and(|b| b.is_ascii_whitespace() || *b == b'>' || *b == b'/')
+    }
+
+    /// End of the person element, which starts at the beginning of the buffer.
+    fn person_end(&mut self) -> usize {
+        let start_tag_end = self
+            .find_after(0, b">")
+            .expect("Plans file ends within a <person> tag.");
+        if self.buffer[start_tag_end - 2] == b'/' {
+            return start_tag_end;
+        }
+        let mut from = start_tag_end;
+        loop {
+            let tag = self
+                .next_tag(from)
+                .expect("Plans file ends within a <person> element.");
+            self.ensure(tag, END_TAG.len());
+            if self.buffer[tag..].starts_with(END_TAG) {
+                return tag + END_TAG.len();
+            }
+            from = tag + 1;
+        }
+    }
+}
+
+impl<R: Read> Iterator for PersonSnippets<R> {
+    type Item = PersonSnippet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let tag = self.next_tag(0)?;
+            if self.is_person_start(tag) {
+                self.consume(tag);
+                break;
+            }
+            self.consume(tag + 1);
+        }
+        let offset = self.consumed;
+        let end = self.person_end();
+        let xml =
+            String::from_utf8(self.consume(end)).expect("Plans files must be encoded as utf-8.");
+        Some(PersonSnippet { offset, xml })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::simulation::io::xml;
+    use crate::simulation::population::io::IOPopulation;
+    use crate::simulation::population::xml_persons::{read_persons, PersonSnippets};
+
+    #[test]
+    fn snippets_across_reads() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE population SYSTEM "http://www.matsim.org/files/dtd/population_v6.dtd" [
+    <!ENTITY example "<person id='0'/>">
+]>
+<population>
+    <attributes><attribute name="personality" class="java.lang.String">x</attribute></attributes>
+    <person id="1"><plan selected="yes"><!-- </person> --></plan></person>
+    <personX/>
+    <!-- <person id="commented"/> -->
+    <person id="2"/>
+    <person
+        id="3"><attributes/></person>
+</population>"#;
+
+        for read_size in [1, 2, 7, 1024] {
+            let snippets: Vec<_> = PersonSnippets::new(xml.as_bytes(), read_size).collect();
+            assert_eq!(
+                vec![
+                    r#"<person id="1"><plan selected="yes"><!-- </person> --></plan></person>"#,
+                    r#"<person id="2"/>"#,
+                    "<person\n        id=\"3\"><attributes/></person>",
+                ],
+                snippets.iter().map(|s| s.xml.as_str()).collect::<Vec<_>>()
+            );
+            for snippet in &snippets {
+                assert!(xml[snippet.offset..].starts_with(&snippet.xml));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "population/person/plan (id '2')")]
+    fn report_location_of_invalid_person() {
+        let folder = PathBuf::from("./test_output/simulation/population/xml_persons/");
+        fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("invalid_person.xml");
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<population>
+    <!-- <person id="commented"/> -->
+    <person id="1"/>
+    <person id="2">
+    <plan selected="maybe"/>
+    </person>
+</population>"#;
+        fs::write(&file, xml).unwrap();
+
+        read_persons(&file);
+    }
+
+    #[test]
+    fn read_example_file() {
+        for file in [
+            "./assets/population-v6-34-persons.xml",
+            "./assets/population-v6-34-persons.xml.gz",
+        ] {
+            let expected: IOPopulation = xml::read_from_file(file);
+            let persons = read_persons(&PathBuf::from(file));
+            assert_eq!(expected.persons, persons);
+        }
+    }
+}
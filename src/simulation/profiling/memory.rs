@@ -0,0 +1,115 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Time steps between two memory samples.
+pub const MEMORY_STATS_INTERVAL: u32 = 900;
+
+/// Sizes of the data structures of one partition which grow with the scenario.
+#[derive(Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub sim_time: u32,
+    pub rank: u32,
+    /// resident set size of the process in bytes. All partitions of the channel simulation share
+    /// one process.
+    pub rss: Option<u64>,
+    pub veh_on_net: usize,
+    /// Entries allocated by the queues of all links. Queues don't shrink, so that this reflects the
+    /// most crowded state of each link so far.
+    pub link_queues: usize,
+    pub activity_q: usize,
+    pub teleportation_q: usize,
+    pub buffered_in_messages: usize,
+    pub pending_out_vehicles: usize,
+}
+
+/// Writes memory samples of one partition into a csv file.
+pub struct MemoryStatsWriter {
+    writer: BufWriter<File>,
+}
+
+impl MemoryStatsWriter {
+    pub fn new(path: &Path) -> Self {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file =
+            File::create(path).unwrap_or_else(|_e| panic!("Failed to open file at: {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(
+            writer,
+            "sim_time,rank,rss,veh_on_net,link_queues,activity_q,teleportation_q,buffered_in_messages,pending_out_vehicles"
+        )
+        .unwrap_or_else(|_e| panic!("Failed to write header."));
+        MemoryStatsWriter { writer }
+    }
+
+    pub fn write(&mut self, stats: &MemoryStats) {
+        let rss = stats.rss.map_or(-1, |rss| rss as i64);
+        writeln!(
+            self.writer,
+            "{},{},{rss},{},{},{},{},{},{}",
+            stats.sim_time,
+            stats.rank,
+            stats.veh_on_net,
+            stats.link_queues,
+            stats.activity_q,
+            stats.teleportation_q,
+            stats.buffered_in_messages,
+            stats.pending_out_vehicles
+        )
+        .unwrap();
+    }
+
+    pub fn flush(&mut self) {
+        self.writer.flush().expect("Failed to flush memory stats");
+    }
+}
+
+/// Resident set size of the current process in bytes. This is only available on Linux, where it is
+/// read from /proc/self/status, which reports it in kB independent of the page size.
+pub fn rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilo_bytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilo_bytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::simulation::profiling::memory::{parse_vm_rss, MemoryStats, MemoryStatsWriter};
+
+    #[test]
+    fn write_stats() {
+        let path = PathBuf::from("./test_output/simulation/profiling/memory_stats.csv");
+        let mut writer = MemoryStatsWriter::new(&path);
+        writer.write(&MemoryStats {
+            sim_time: 900,
+            rank: 1,
+            rss: None,
+            veh_on_net: 2,
+            link_queues: 7,
+            activity_q: 3,
+            teleportation_q: 4,
+            buffered_in_messages: 5,
+            pending_out_vehicles: 6,
+        });
+        writer.flush();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!("900,1,-1,2,7,3,4,5,6", content.lines().nth(1).unwrap());
+    }
+
+    #[test]
+    fn vm_rss() {
+        let status = "Name:\trust_q_sim\nVmHWM:\t  20480 kB\nVmRSS:\t  10240 kB\nThreads:\t4\n";
+        assert_eq!(Some(10240 * 1024), parse_vm_rss(status));
+        assert_eq!(None, parse_vm_rss("Name:\trust_q_sim\n"));
+    }
+}
@@ -15,6 +15,8 @@ use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Layer;
 
+pub mod memory;
+
 const DEFAULT_PERFORMANCE_INTERVAL: u32 = 900;
 
 pub fn measure_duration<Out, F: FnOnce() -> Out>(
@@ -57,6 +59,8 @@ pub struct WriterGuard {
 }
 
 struct SpanDuration {
+    // unix timestamp in nanoseconds, when the span was created
+    start: u128,
     elapsed: u64,
     last: Instant,
 }
@@ -109,7 +113,7 @@ impl SpanDurationToCSVLayer {
         // write header for csv file
         std::io::Write::write(
             &mut writer,
-            "timestamp,target,func_name,duration,sim_time,rank\n".as_bytes(),
+            "timestamp,target,func_name,start,duration,sim_time,rank\n".as_bytes(),
         )
         .unwrap_or_else(|_e| panic!("Failed to write header."));
 
@@ -160,6 +164,18 @@ where
 
         let mut visitor = MetadataVisitor::new();
         attrs.record(&mut visitor as &mut dyn Visit);
+
+        // spans which don't record rank or time themselves inherit them from their parent span.
+        if let Some(parent) = span.parent() {
+            let parent_extensions = parent.extensions();
+            if visitor.rank.is_none() {
+                visitor.rank = parent_extensions.get::<Rank>().map(|r| r.0);
+            }
+            if visitor.sim_time.is_none() {
+                visitor.sim_time = parent_extensions.get::<SimTime>().map(|t| t.0);
+            }
+        }
+
         if let Some(rank) = visitor.rank {
             extensions.insert(Rank(rank));
         }
@@ -211,7 +227,7 @@ where
         Self::write_metadata(writer, meta);
 
         let span_duration = extensions.get::<SpanDuration>().unwrap();
-        write!(writer, "{},", span_duration.elapsed).unwrap();
+        write!(writer, "{},{},", span_duration.start, span_duration.elapsed).unwrap();
 
         let sim_time = extensions
             .get::<SimTime>()
@@ -238,6 +254,10 @@ impl Drop for WriterGuard {
 impl SpanDuration {
     fn new() -> Self {
         Self {
+            start: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
             elapsed: 0,
             last: Instant::now(),
         }
@@ -246,6 +266,7 @@ impl SpanDuration {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
     use std::path::PathBuf;
     use std::thread::sleep;
     use std::time::Duration;
@@ -263,7 +284,7 @@ mod tests {
     fn test_events() {
         let path = PathBuf::from("./test_output/simulation/profiling/test_events.csv");
 
-        let (csv_layer, _guard) = SpanDurationToCSVLayer::new(&path, Level::INFO);
+        let (csv_layer, guard) = SpanDurationToCSVLayer::new(&path, Level::INFO);
         let layers = tracing_subscriber::registry().with(csv_layer).with(
             Layer::new()
                 .with_span_events(FmtSpan::CLOSE)
@@ -276,6 +297,42 @@ mod tests {
         info!("After func");
 
         some_other_function(7, std::f32::consts::PI);
+        ranked_function(3);
+
+        // flush the writer
+        drop(guard);
+        let content = fs::read_to_string(&path).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(
+            Some("timestamp,target,func_name,start,duration,sim_time,rank"),
+            lines.next()
+        );
+        let rows: Vec<Vec<&str>> = lines.map(|l| l.split(',').collect()).collect();
+        // trace spans are not recorded
+        assert!(!rows.iter().any(|r| r[2] == "some_other_function"));
+        assert_eq!(
+            -1,
+            find_row(&rows, "some_function")[6].parse::<i64>().unwrap()
+        );
+        let nested = find_row(&rows, "nested_function");
+        assert_eq!("3", nested[5]);
+        assert_eq!("42", nested[6]);
+    }
+
+    fn find_row<'a>(rows: &'a [Vec<&'a str>], func_name: &str) -> &'a Vec<&'a str> {
+        rows.iter()
+            .find(|r| r[2] == func_name)
+            .unwrap_or_else(|| panic!("No row for {func_name}"))
+    }
+
+    #[instrument(fields(rank = 42u32))]
+    fn ranked_function(_now: u32) {
+        nested_function();
+    }
+
+    #[instrument]
+    fn nested_function() {
+        info!("Inside nested function");
     }
 
     #[instrument]
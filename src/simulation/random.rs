@@ -0,0 +1,22 @@
+/// Scrambles the bits of a value, so that similar inputs result in unrelated seeds. This is the
+/// finalizer of the SplitMix64 generator. Unlike a thread rng, it derives the same seed on every
+/// partition, so that random decisions don't depend on how the scenario is partitioned.
+pub(crate) fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::random::split_mix;
+
+    #[test]
+    fn split_mix_scrambles_similar_values() {
+        assert_eq!(split_mix(42), split_mix(42));
+        assert_ne!(split_mix(42), split_mix(43));
+        // neighbouring inputs differ in many bits.
+        assert!((split_mix(42) ^ split_mix(43)).count_ones() > 16);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod replanner;
 pub mod routing;
+pub mod strategies;
 pub mod teleported_router;
@@ -1,14 +1,21 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
 use tracing::debug;
 
+use crate::simulation::config;
+use crate::simulation::config::PlanStrategy;
 use crate::simulation::id::Id;
 use crate::simulation::messaging::communication::communicators::SimCommunicator;
 use crate::simulation::messaging::events::EventsPublisher;
-use crate::simulation::network::global_network::{Link, Network};
+use crate::simulation::network::global_network::Network;
 use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::replanning::routing::network_converter::NetworkConverter;
+use crate::simulation::replanning::routing::route_cache::{RouteCache, RouteCacheKey};
 use crate::simulation::replanning::routing::router::NetworkRouter;
 use crate::simulation::replanning::routing::travel_times_collecting_alt_router::TravelTimesCollectingAltRouter;
+use crate::simulation::replanning::strategies::StrategyChooser;
 use crate::simulation::replanning::teleported_router::{BeeLineDistanceRouter, TeleportedRouter};
 use crate::simulation::vehicles::garage::Garage;
 use crate::simulation::wire_types::messages::Vehicle;
@@ -18,8 +25,49 @@ use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
 pub trait Replanner {
     fn update_time(&mut self, now: u32, events: &mut EventsPublisher);
     fn replan(&self, now: u32, agent: &mut Person, garage: &Garage);
+
+    /// Replans all agents which depart in the same time step. Implementations can use this to
+    /// share work between agents. By default, agents are replanned one by one.
+    fn replan_batch(&self, now: u32, agents: &mut [Person], garage: &Garage) {
+        for agent in agents {
+            self.replan(now, agent, garage);
+        }
+    }
+
+    /// Re-routed legs of the iteration, which serve as a convergence indicator.
+    fn route_switches(&self) -> RouteSwitches {
+        RouteSwitches::default()
+    }
+
+    /// Called before each iteration. The replanner of a partition is kept between iterations, so
+    /// that what it has learned, e.g. the travel times of the network, carries over into the next
+    /// iteration.
+    fn start_iteration(&mut self, _innovation: bool, _strategies: StrategyChooser) {}
+}
+
+/// Counts network legs, which had a route before they were re-routed, and how many of them got a
+/// different route. As the plans of an iteration are the executed plans of the previous one, new
+/// routes are compared with the routes selected in the previous iteration. Once few agents switch
+/// routes, the iterations have converged.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RouteSwitches {
+    pub rerouted: usize,
+    pub switched: usize,
 }
 
+impl RouteSwitches {
+    /// Share of re-routed legs, which switched routes. 0 if no leg was re-routed.
+    pub fn rate(&self) -> f64 {
+        if self.rerouted == 0 {
+            0.
+        } else {
+            self.switched as f64 / self.rerouted as f64
+        }
+    }
+}
+
+type Routes = HashMap<RouteCacheKey, (Vec<u64>, Option<u32>)>;
+
 #[derive(Eq, PartialEq)]
 enum LegType {
     TripPlaceholder,
@@ -36,21 +84,93 @@ impl Replanner for DummyReplanner {
     fn replan(&self, _now: u32, _agent: &mut Person, _garage: &Garage) {}
 }
 
+/// Keeps the routes of network legs as stated in the plans, but computes teleported legs from the
+/// coordinates of the surrounding activities. This way, teleported legs don't need pre-computed
+/// routes.
+pub struct TeleportedLegsReplanner {
+    teleported_router: Box<dyn TeleportedRouter>,
+}
+
+impl TeleportedLegsReplanner {
+    pub fn new(teleportation: &config::Teleportation) -> Self {
+        TeleportedLegsReplanner {
+            teleported_router: Box::new(BeeLineDistanceRouter::from_config(teleportation)),
+        }
+    }
+}
+
+impl Replanner for TeleportedLegsReplanner {
+    fn update_time(&mut self, _now: u32, _events: &mut EventsPublisher) {}
+
+    fn replan(&self, _now: u32, agent: &mut Person, garage: &Garage) {
+        let veh_type_id = agent.next_leg().vehicle_type_id(garage);
+        let veh_type = garage.vehicle_types.get(veh_type_id).unwrap();
+        if veh_type.lod() == LevelOfDetail::Teleported {
+            replan_teleported(self.teleported_router.as_ref(), agent, garage);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ReRouteTripReplanner {
     network_router: Box<dyn NetworkRouter>,
     teleported_router: Box<dyn TeleportedRouter>,
     global_network: Network,
+    route_cache: RefCell<RouteCache>,
+    /// Whether main network legs are re-routed. Otherwise, legs with a route keep it, and only
+    /// legs without a route are routed.
+    innovation: bool,
+    /// Only agents, which selected re-routing, get new routes for legs with a route.
+    strategies: Option<StrategyChooser>,
+    route_switches: Cell<RouteSwitches>,
 }
 
 impl Replanner for ReRouteTripReplanner {
     #[tracing::instrument(level = "trace", skip(self, events))]
     fn update_time(&mut self, now: u32, events: &mut EventsPublisher) {
-        self.network_router.next_time_step(now, events)
+        if self.network_router.next_time_step(now, events) {
+            let cache = self.route_cache.get_mut();
+            debug!(
+                "Routing graphs changed at {now}. Clearing route cache with {} entries ({} hits, {} misses so far).",
+                cache.len(),
+                cache.hits(),
+                cache.misses()
+            );
+            cache.clear();
+        }
     }
 
     #[tracing::instrument(level = "trace", skip(self, agent, garage))]
-    fn replan(&self, _now: u32, agent: &mut Person, garage: &Garage) {
+    fn replan(&self, now: u32, agent: &mut Person, garage: &Garage) {
+        self.replan_with_routes(now, agent, garage, &Routes::new())
+    }
+
+    /// Agents, which start their main network leg at the same link in the same time step, are
+    /// routed with one one-to-many query per link and vehicle type.
+    #[tracing::instrument(level = "trace", skip(self, agents, garage))]
+    fn replan_batch(&self, now: u32, agents: &mut [Person], garage: &Garage) {
+        let routes = self.batch_routes(now, agents, garage);
+        for agent in agents {
+            self.replan_with_routes(now, agent, garage, &routes);
+        }
+    }
+
+    fn route_switches(&self) -> RouteSwitches {
+        self.route_switches.get()
+    }
+
+    /// Keeps the routing graphs with the travel times of the previous iteration, but clears the
+    /// route cache, as its time bins repeat in every iteration.
+    fn start_iteration(&mut self, innovation: bool, strategies: StrategyChooser) {
+        self.innovation = innovation;
+        self.strategies = Some(strategies);
+        self.route_switches.set(RouteSwitches::default());
+        self.route_cache.get_mut().clear();
+    }
+}
+
+impl ReRouteTripReplanner {
+    fn replan_with_routes(&self, now: u32, agent: &mut Person, garage: &Garage, routes: &Routes) {
         let leg_type = Self::get_leg_type(agent, garage);
         if leg_type == LegType::TripPlaceholder {
             self.insert_access_egress(agent, garage);
@@ -62,40 +182,138 @@ impl Replanner for ReRouteTripReplanner {
             LegType::AccessEgress | LegType::TripPlaceholder => {
                 self.replan_access_egress(agent, garage)
             }
-            LegType::MainNetwork => self.replan_main(agent, garage),
+            LegType::MainNetwork if self.keeps_route(agent) => {}
+            LegType::MainNetwork => self.replan_main(now, agent, garage, routes),
             LegType::MainTeleported => self.replan_teleported_main(agent, garage),
         };
     }
-}
 
-impl ReRouteTripReplanner {
+    /// Computes the routes of all main network legs in the batch, which are neither cached nor
+    /// the only leg starting at their link.
+    fn batch_routes(&self, now: u32, agents: &[Person], garage: &Garage) -> Routes {
+        let mut targets_by_origin: BTreeMap<(u64, u64), Vec<u64>> = BTreeMap::new();
+        for agent in agents {
+            if Self::get_leg_type(agent, garage) != LegType::MainNetwork || self.keeps_route(agent)
+            {
+                continue;
+            }
+            let veh_type_id = Self::main_leg_veh_type_id(agent, garage);
+            let key = RouteCacheKey::new(
+                agent.curr_act().link_id,
+                agent.next_act().link_id,
+                veh_type_id.internal(),
+                now,
+            );
+            if self.route_cache.borrow().contains(&key) {
+                continue;
+            }
+            let targets = targets_by_origin
+                .entry((key.from_link, key.veh_type))
+                .or_default();
+            if !targets.contains(&key.to_link) {
+                targets.push(key.to_link);
+            }
+        }
+
+        let mut routes = Routes::new();
+        for ((from_link, veh_type), to_links) in targets_by_origin {
+            if to_links.len() < 2 {
+                continue;
+            }
+            let results = self.network_router.query_links_one_to_many(
+                from_link,
+                &to_links,
+                &Id::<VehicleType>::get(veh_type),
+            );
+            for (to_link, result) in to_links.into_iter().zip(results) {
+                let route = result.path.expect("There is no route!");
+                routes.insert(
+                    RouteCacheKey::new(from_link, to_link, veh_type, now),
+                    (route, result.travel_time),
+                );
+            }
+        }
+        routes
+    }
+
+    fn main_leg_veh_type_id<'g>(agent: &Person, garage: &'g Garage) -> &'g Id<VehicleType> {
+        garage
+            .vehicles
+            .get(&Id::<Vehicle>::get(
+                agent.next_leg().route.as_ref().unwrap().veh_id,
+            ))
+            .unwrap()
+    }
+
     pub fn new<C: SimCommunicator + 'static>(
         global_network: &Network,
         sim_network: &SimNetworkPartition,
         garage: &Garage,
         communicator: Rc<C>,
+        teleportation: &config::Teleportation,
+        routing: &config::Routing,
     ) -> ReRouteTripReplanner {
-        let forward_backward_graph_by_veh_type =
+        let mut forward_backward_graph_by_veh_type =
             TravelTimesCollectingAltRouter::<C>::get_forward_backward_graph_by_veh_type(
                 global_network,
                 &garage.vehicle_types,
             );
+        for (veh_type_id, graph) in forward_backward_graph_by_veh_type.iter_mut() {
+            let veh_type = garage.vehicle_types.get(veh_type_id).unwrap();
+            let mode = Id::<String>::get(veh_type.net_mode);
+            if let Some(cost) = routing.generalized_cost.get(mode.external()) {
+                NetworkConverter::apply_generalized_cost(graph, global_network, cost);
+            }
+        }
 
         let router: Box<dyn NetworkRouter> = Box::new(TravelTimesCollectingAltRouter::new(
             forward_backward_graph_by_veh_type,
             communicator,
             sim_network.get_link_ids(),
+            routing.background_customization,
+            routing.backend,
         ));
 
-        let teleported_router: Box<dyn TeleportedRouter> = Box::new(BeeLineDistanceRouter::new());
+        let teleported_router: Box<dyn TeleportedRouter> =
+            Box::new(BeeLineDistanceRouter::from_config(teleportation));
 
         ReRouteTripReplanner {
             network_router: router,
             teleported_router,
             global_network: global_network.clone(),
+            route_cache: RefCell::new(RouteCache::new(routing.route_cache_size)),
+            innovation: true,
+            strategies: None,
+            route_switches: Cell::new(RouteSwitches::default()),
         }
     }
 
+    /// Disables re-routing of main network legs, which already have a route, e.g. after plan
+    /// innovation was shut off.
+    pub fn with_innovation(mut self, innovation: bool) -> Self {
+        self.innovation = innovation;
+        self
+    }
+
+    pub fn with_strategies(mut self, strategies: StrategyChooser) -> Self {
+        self.strategies = Some(strategies);
+        self
+    }
+
+    fn keeps_route(&self, agent: &Person) -> bool {
+        let re_routes = self.innovation
+            && match self.strategies.as_ref() {
+                Some(strategies) => strategies.choose(agent) == PlanStrategy::ReRoute,
+                None => true,
+            };
+        !re_routes
+            && agent
+                .next_leg()
+                .route
+                .as_ref()
+                .is_some_and(|route| !route.route.is_empty())
+    }
+
     fn insert_access_egress(&self, agent: &mut Person, garage: &Garage) {
         // So far, we have:
         // act (current) - leg (next) - act (next)
@@ -133,24 +351,21 @@ impl ReRouteTripReplanner {
         agent.replace_next_leg(vec![access, agent.next_leg().clone(), egress]);
     }
 
-    fn replan_main(&self, agent: &mut Person, garage: &Garage) {
+    fn replan_main(&self, now: u32, agent: &mut Person, garage: &Garage, routes: &Routes) {
         let curr_act = agent.curr_act();
 
-        let veh_type_id = garage
-            .vehicles
-            .get(&Id::<Vehicle>::get(
-                agent.next_leg().route.as_ref().unwrap().veh_id,
-            ))
-            .unwrap();
+        let veh_type_id = Self::main_leg_veh_type_id(agent, garage);
 
-        let (route, travel_time) = self.find_route(agent.curr_act(), agent.next_act(), veh_type_id);
+        let (route, travel_time) =
+            self.find_route(now, agent.curr_act(), agent.next_act(), veh_type_id, routes);
         let dep_time = curr_act.end_time;
 
         let vehicle_type_id = agent.next_leg().vehicle_type_id(garage);
 
         let veh_id = garage.veh_id(&Id::<Person>::get(agent.id), vehicle_type_id);
 
-        let distance = self.calculate_distance(&route);
+        let distance = self.global_network.route_distance(&route);
+        self.count_route_switch(agent, &route);
 
         agent.update_next_leg(
             dep_time,
@@ -161,6 +376,20 @@ impl ReRouteTripReplanner {
         );
     }
 
+    /// Compares the new route with the route of the plan, i.e. the route of the previous iteration.
+    fn count_route_switch(&self, agent: &Person, route: &[u64]) {
+        let old_route = match agent.next_leg().route.as_ref() {
+            Some(old_route) if !old_route.route.is_empty() => old_route,
+            _ => return,
+        };
+        let mut switches = self.route_switches.get();
+        switches.rerouted += 1;
+        if old_route.route != route {
+            switches.switched += 1;
+        }
+        self.route_switches.set(switches);
+    }
+
     fn replan_access_egress(&self, agent: &mut Person, garage: &Garage) {
         let curr_act = agent.curr_act();
         let next_act = agent.next_act();
@@ -176,6 +405,7 @@ impl ReRouteTripReplanner {
             // curr activity is interaction => it's an egress leg => next activity has location
             self.teleported_router.query_access_egress(
                 next_act,
+                agent.next_leg().mode,
                 access_egress_speed,
                 &self.global_network,
             )
@@ -184,6 +414,7 @@ impl ReRouteTripReplanner {
             // curr activity is an actual activity => it's an access leg => curr activity has location
             self.teleported_router.query_access_egress(
                 curr_act,
+                agent.next_leg().mode,
                 access_egress_speed,
                 &self.global_network,
             )
@@ -201,34 +432,34 @@ impl ReRouteTripReplanner {
     }
 
     fn replan_teleported_main(&self, agent: &mut Person, garage: &Garage) {
-        let curr_act = agent.curr_act();
-        let next_act = agent.next_act();
-
-        let veh_type_id = agent.next_leg().vehicle_type_id(garage);
-        let speed = garage.vehicle_types.get(veh_type_id).unwrap().max_v;
-
-        let dep_time = curr_act.end_time;
-        let teleportation = self
-            .teleported_router
-            .query_between_acts(curr_act, next_act, speed);
-
-        let vehicle_id = garage.veh_id(&Id::<Person>::get(agent.id), veh_type_id);
-        agent.update_next_leg(
-            dep_time,
-            teleportation.duration,
-            vec![agent.curr_act().link_id, agent.next_act().link_id],
-            teleportation.distance,
-            vehicle_id.internal(),
-        );
+        replan_teleported(self.teleported_router.as_ref(), agent, garage);
     }
 
-    #[tracing::instrument(level = "trace", skip(self, from_act, to_act, veh_type_id))]
+    #[tracing::instrument(level = "trace", skip(self, from_act, to_act, veh_type_id, routes))]
     fn find_route(
         &self,
+        now: u32,
         from_act: &Activity,
         to_act: &Activity,
         veh_type_id: &Id<VehicleType>,
+        routes: &Routes,
     ) -> (Vec<u64>, Option<u32>) {
+        let key = RouteCacheKey::new(
+            from_act.link_id,
+            to_act.link_id,
+            veh_type_id.internal(),
+            now,
+        );
+        if let Some(cached) = self.route_cache.borrow_mut().get(&key) {
+            return cached;
+        }
+        if let Some((route, travel_time)) = routes.get(&key) {
+            self.route_cache
+                .borrow_mut()
+                .insert(key, route.clone(), *travel_time);
+            return (route.clone(), *travel_time);
+        }
+
         let query_result =
             self.network_router
                 .query_links(from_act.link_id, to_act.link_id, veh_type_id);
@@ -240,6 +471,9 @@ impl ReRouteTripReplanner {
             debug!("Route between {:?} and {:?} is empty.", from_act, to_act);
         }
 
+        self.route_cache
+            .borrow_mut()
+            .insert(key, route.clone(), travel_time);
         (route, travel_time)
     }
 
@@ -286,21 +520,26 @@ impl ReRouteTripReplanner {
             panic!("Computing a leg between two main activities should never happen.")
         }
     }
+}
 
-    fn calculate_distance(&self, route: &[u64]) -> f64 {
-        let distance: f64 = route
-            .iter()
-            .map(|id| {
-                self.global_network
-                    .links
-                    .iter()
-                    .find(|l| l.id == Id::<Link>::get(*id))
-                    .unwrap_or_else(|| panic!("No link with id {:?}", id))
-            })
-            .map(|l| l.length)
-            .sum();
-        distance
-    }
+fn replan_teleported(router: &dyn TeleportedRouter, agent: &mut Person, garage: &Garage) {
+    let curr_act = agent.curr_act();
+    let next_act = agent.next_act();
+
+    let veh_type_id = agent.next_leg().vehicle_type_id(garage);
+    let speed = garage.vehicle_types.get(veh_type_id).unwrap().max_v;
+
+    let dep_time = curr_act.end_time;
+    let teleportation = router.query_between_acts(curr_act, next_act, agent.next_leg().mode, speed);
+
+    let vehicle_id = garage.veh_id(&Id::<Person>::get(agent.id), veh_type_id);
+    agent.update_next_leg(
+        dep_time,
+        teleportation.duration,
+        vec![agent.curr_act().link_id, agent.next_act().link_id],
+        teleportation.distance,
+        vehicle_id.internal(),
+    );
 }
 
 #[cfg(test)]
@@ -308,7 +547,7 @@ mod tests {
     use std::path::PathBuf;
     use std::rc::Rc;
 
-    use crate::simulation::config::{MetisOptions, PartitionMethod};
+    use crate::simulation::config::{MetisOptions, PartitionMethod, Routing, Teleportation};
     use crate::simulation::id::Id;
     use crate::simulation::messaging::communication::communicators::DummySimCommunicator;
     use crate::simulation::network::global_network::Network;
@@ -339,8 +578,17 @@ mod tests {
         let agent_id = Id::get_from_ext("100");
         let mut agent = population.persons.get_mut(&agent_id).unwrap();
 
-        let replanner =
-            ReRouteTripReplanner::new(&network, &sim_net, &garage, Rc::new(DummySimCommunicator()));
+        let replanner = ReRouteTripReplanner::new(
+            &network,
+            &sim_net,
+            &garage,
+            Rc::new(DummySimCommunicator()),
+            &Teleportation::default(),
+            &Routing {
+                route_cache_size: 0,
+                ..Routing::default()
+            },
+        );
 
         //do change
         replanner.replan(0, &mut agent, &garage);
@@ -376,8 +624,17 @@ mod tests {
         let agent_id = Id::get_from_ext("100");
         let mut agent = population.persons.get_mut(&agent_id).unwrap();
 
-        let replanner =
-            ReRouteTripReplanner::new(&network, &sim_net, &garage, Rc::new(DummySimCommunicator()));
+        let replanner = ReRouteTripReplanner::new(
+            &network,
+            &sim_net,
+            &garage,
+            Rc::new(DummySimCommunicator()),
+            &Teleportation::default(),
+            &Routing {
+                route_cache_size: 0,
+                ..Routing::default()
+            },
+        );
 
         //do change
         replanner.replan(0, &mut agent, &garage);
@@ -431,8 +688,17 @@ mod tests {
         let agent_id = Id::get_from_ext("100");
         let mut agent = population.persons.get_mut(&agent_id).unwrap();
 
-        let replanner =
-            ReRouteTripReplanner::new(&network, &sim_net, &garage, Rc::new(DummySimCommunicator()));
+        let replanner = ReRouteTripReplanner::new(
+            &network,
+            &sim_net,
+            &garage,
+            Rc::new(DummySimCommunicator()),
+            &Teleportation::default(),
+            &Routing {
+                route_cache_size: 0,
+                ..Routing::default()
+            },
+        );
 
         //do change
         replanner.replan(0, &mut agent, &garage);
@@ -506,6 +772,88 @@ mod tests {
         );
     }
 
+    #[test]
+    fn keep_main_leg_route_without_innovation() {
+        let (mut population, garage, replanner) = replanner_at_main_leg(false);
+        let agent = population
+            .persons
+            .get_mut(&Id::get_from_ext("100"))
+            .unwrap();
+
+        // the main leg has a route now, which is not replaced
+        let route = Route {
+            veh_id: 0,
+            distance: 42.,
+            route: vec![0, 1, 2],
+        };
+        agent.plan.as_mut().unwrap().legs[1].route = Some(route.clone());
+        replanner.replan(0, agent, &garage);
+        assert_eq!(
+            &route,
+            agent.plan.as_ref().unwrap().legs[1].route.as_ref().unwrap()
+        );
+    }
+
+    #[test]
+    fn count_route_switches() {
+        let (population, garage, replanner) = replanner_at_main_leg(true);
+        let agent = population.persons.get(&Id::get_from_ext("100")).unwrap();
+
+        for (old_route, switched) in [(vec![0, 1, 2], 0), (vec![0, 2], 1)] {
+            let mut agent = agent.clone();
+            agent.plan.as_mut().unwrap().legs[1].route = Some(Route {
+                veh_id: 0,
+                distance: 0.,
+                route: old_route,
+            });
+            replanner.replan(0, &mut agent, &garage);
+            assert_eq!(switched, replanner.route_switches().switched);
+        }
+        assert_eq!(2, replanner.route_switches().rerouted);
+        assert_eq!(0.5, replanner.route_switches().rate());
+    }
+
+    /// Loads the 3-links scenario and moves its agent to the interaction activity before the main
+    /// leg.
+    fn replanner_at_main_leg(innovation: bool) -> (Population, Garage, ReRouteTripReplanner) {
+        let network = Network::from_file(
+            "./assets/3-links/3-links-network.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+        let mut garage = Garage::from_file(&PathBuf::from("./assets/3-links/vehicles.xml"));
+        let mut population = Population::from_file_filtered_part(
+            &PathBuf::from("./assets/3-links/1-agent-trip-leg.xml"),
+            &network,
+            &mut garage,
+            0,
+        );
+        let sim_net = SimNetworkPartition::from_network(&network, 0, test_utils::config());
+        let agent = population
+            .persons
+            .get_mut(&Id::get_from_ext("100"))
+            .unwrap();
+
+        let replanner = ReRouteTripReplanner::new(
+            &network,
+            &sim_net,
+            &garage,
+            Rc::new(DummySimCommunicator()),
+            &Teleportation::default(),
+            &Routing {
+                route_cache_size: 0,
+                ..Routing::default()
+            },
+        )
+        .with_innovation(innovation);
+
+        // the placeholder leg has no route and is routed regardless of innovation
+        replanner.replan(0, agent, &garage);
+        agent.advance_plan();
+        agent.advance_plan();
+        (population, garage, replanner)
+    }
+
     fn advance_plan_and_update_main_leg(mut garage: &mut Garage) -> Population {
         let network = Network::from_file(
             "./assets/3-links/3-links-network.xml",
@@ -522,8 +870,17 @@ mod tests {
         let agent_id = Id::get_from_ext("100");
         let mut agent = population.persons.get_mut(&agent_id).unwrap();
 
-        let replanner =
-            ReRouteTripReplanner::new(&network, &sim_net, &garage, Rc::new(DummySimCommunicator()));
+        let replanner = ReRouteTripReplanner::new(
+            &network,
+            &sim_net,
+            &garage,
+            Rc::new(DummySimCommunicator()),
+            &Teleportation::default(),
+            &Routing {
+                route_cache_size: 0,
+                ..Routing::default()
+            },
+        );
 
         //do change of walk leg
         replanner.replan(0, &mut agent, &garage);
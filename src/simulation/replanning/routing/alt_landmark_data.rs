@@ -25,6 +25,15 @@ impl AltLandmarkData {
         }
     }
 
+    /// Landmark data without any landmarks. The heuristic is always 0 then, which turns the ALT
+    /// search into a plain dijkstra.
+    pub fn without_landmarks() -> AltLandmarkData {
+        AltLandmarkData {
+            landmarks: Vec::new(),
+            travel_times_to_all: Vec::new(),
+        }
+    }
+
     pub fn travel_times_to_all(&self) -> &Vec<Vec<ForwardBackwardTravelTime>> {
         &self.travel_times_to_all
     }
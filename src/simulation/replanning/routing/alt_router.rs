@@ -1,5 +1,8 @@
+use std::collections::HashSet;
+
 use keyed_priority_queue::Entry;
 
+use crate::simulation::config::RoutingBackend;
 use crate::simulation::replanning::routing::alt_landmark_data::AltLandmarkData;
 use crate::simulation::replanning::routing::dijsktra::{Dijkstra, Distance};
 use crate::simulation::replanning::routing::graph::ForwardBackwardGraph;
@@ -32,7 +35,14 @@ pub struct AltRouter {
 
 impl AltRouter {
     pub fn new(graph: ForwardBackwardGraph) -> Self {
-        let landmark_data = AltLandmarkData::new(&graph);
+        Self::with_backend(graph, RoutingBackend::Alt)
+    }
+
+    pub fn with_backend(graph: ForwardBackwardGraph, backend: RoutingBackend) -> Self {
+        let landmark_data = match backend {
+            RoutingBackend::Alt => AltLandmarkData::new(&graph),
+            RoutingBackend::Dijkstra => AltLandmarkData::without_landmarks(),
+        };
         AltRouter {
             landmark_data,
             current_graph: graph.clone(),
@@ -58,11 +68,128 @@ impl AltRouter {
             });
 
         CustomQueryResult {
-            travel_time,
+            travel_time: self.travel_time_of_path(travel_time, edge_path.as_ref()),
             path: edge_path,
         }
     }
 
+    /// Computes routes from one link to many links with a single dijkstra search. The search
+    /// stops as soon as all targets are settled. Results are returned in the order of `to_links`.
+    pub fn query_links_one_to_many(
+        &self,
+        from_link: u64,
+        to_links: &[u64],
+    ) -> Vec<CustomQueryResult> {
+        let targets = to_links
+            .iter()
+            .map(|l| self.get_start_node(*l))
+            .collect::<Vec<_>>();
+        let results = self.query_one_to_many(self.get_end_node(from_link), &targets);
+
+        results
+            .into_iter()
+            .zip(to_links)
+            .map(|(result, to_link)| {
+                let travel_time = result.travel_time;
+                let path = result.node_path().map(|node_path| {
+                    let mut path = Self::get_edge_path(node_path, &self.current_graph);
+                    path.insert(0, from_link);
+                    path.push(*to_link);
+                    path
+                });
+                CustomQueryResult {
+                    travel_time: self.travel_time_of_path(travel_time, path.as_ref()),
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    /// With a generalized cost, the search minimizes the cost, which is not a travel time. The
+    /// travel time is then summed up along the path, excluding the first and the last link, like
+    /// the search does.
+    fn travel_time_of_path(
+        &self,
+        search_result: Option<u32>,
+        path: Option<&Vec<u64>>,
+    ) -> Option<u32> {
+        if !self.current_graph.has_generalized_cost() {
+            return search_result;
+        }
+        path.map(|path| {
+            path[1..path.len() - 1]
+                .iter()
+                .map(|link| self.get_current_travel_time(*link).unwrap())
+                .sum()
+        })
+    }
+
+    fn query_one_to_many(&self, from: usize, targets: &[usize]) -> Vec<AltQueryResult> {
+        let number_of_nodes = self.current_graph.forward_first_out().len() - 1;
+        let (mut queue, mut distances) = Dijkstra::get_initial_queue(number_of_nodes, from);
+        let mut parents: Vec<Option<usize>> = (0..number_of_nodes).map(|_| None).collect();
+        let mut settled = vec![false; number_of_nodes];
+        let target_set = targets.iter().copied().collect::<HashSet<_>>();
+        let mut remaining = target_set.len();
+
+        while let Some((current_id, _)) = queue.pop() {
+            let current_distance = distances[current_id];
+
+            if current_distance == u32::MAX {
+                //The smallest value in queue was unreachable. So abort here.
+                break;
+            }
+
+            settled[current_id] = true;
+            if target_set.contains(&current_id) {
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+
+            let graph = &self.current_graph.forward_graph;
+            for i in graph.first_out[current_id]..graph.first_out[current_id + 1] {
+                let neighbour = graph.head[i];
+
+                if let Entry::Vacant(_) = queue.entry(neighbour) {
+                    continue;
+                }
+
+                let neighbour_distance = current_distance + graph.weight(i);
+
+                if distances[neighbour] > neighbour_distance {
+                    distances[neighbour] = neighbour_distance;
+
+                    match queue.entry(neighbour) {
+                        Entry::Occupied(e) => {
+                            e.set_priority(Distance(neighbour_distance));
+                        }
+                        Entry::Vacant(_) => {
+                            unreachable!()
+                        }
+                    }
+
+                    parents[neighbour] = Some(current_id);
+                }
+            }
+        }
+
+        targets
+            .iter()
+            .map(|to| {
+                if settled[*to] {
+                    AltQueryResult {
+                        travel_time: Some(distances[*to]),
+                        node_path: Some(Self::extract_path(*to, &parents)),
+                    }
+                } else {
+                    AltQueryResult::empty()
+                }
+            })
+            .collect()
+    }
+
     fn query(&self, from: usize, to: usize) -> AltQueryResult {
         let number_of_nodes = self.current_graph.forward_first_out().len() - 1;
         let (mut queue, mut distances) = Dijkstra::get_initial_queue(number_of_nodes, from);
@@ -79,7 +206,7 @@ impl AltRouter {
             if current_id == to {
                 return AltQueryResult {
                     travel_time: Some(current_distance),
-                    node_path: Some(Self::extract_path(to, parents)),
+                    node_path: Some(Self::extract_path(to, &parents)),
                 };
             }
 
@@ -96,7 +223,7 @@ impl AltRouter {
                 }
 
                 let neighbour_distance =
-                    current_distance + self.current_graph.forward_graph.travel_time[i];
+                    current_distance + self.current_graph.forward_graph.weight(i);
 
                 if distances[neighbour] > neighbour_distance {
                     //perform update
@@ -148,7 +275,7 @@ impl AltRouter {
         }
     }
 
-    fn extract_path(to: usize, parent: Vec<Option<usize>>) -> Vec<usize> {
+    fn extract_path(to: usize, parent: &[Option<usize>]) -> Vec<usize> {
         let mut path = Vec::new();
         let mut current = to;
 
@@ -270,12 +397,16 @@ mod tests {
     use std::collections::HashMap;
     use std::path::PathBuf;
 
-    use crate::simulation::config::{MetisOptions, PartitionMethod};
+    use crate::simulation::config::{
+        GeneralizedCost, MetisOptions, PartitionMethod, RoutingBackend,
+    };
     use crate::simulation::id::Id;
-    use crate::simulation::network::global_network::Network;
+    use crate::simulation::network::global_network::{Link, Network};
     use crate::simulation::replanning::routing::alt_router::{AltQueryResult, AltRouter};
     use crate::simulation::replanning::routing::graph::tests::get_triangle_test_graph;
-    use crate::simulation::replanning::routing::network_converter::NetworkConverter;
+    use crate::simulation::replanning::routing::network_converter::{
+        NetworkConverter, TOLL_ATTRIBUTE,
+    };
     use crate::simulation::vehicles::garage::Garage;
     use crate::simulation::wire_types::vehicles::VehicleType;
 
@@ -307,6 +438,113 @@ mod tests {
         query_and_check(&router, 0, 1, None, None);
     }
 
+    #[test]
+    fn test_generalized_cost() {
+        let mut network = Network::from_file(
+            "./assets/routing_tests/triangle-network.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+        // the direct link from node 1 to node 2 takes 1s, the detour via node 3 takes 7s.
+        let direct = Id::<Link>::get_from_ext("1");
+        network
+            .links
+            .iter_mut()
+            .find(|l| l.id == direct)
+            .unwrap()
+            .attributes
+            .insert(String::from(TOLL_ATTRIBUTE), String::from("10"));
+        let link_ids = |ext: &[&str]| {
+            ext.iter()
+                .map(|l| Id::<Link>::get_from_ext(l).internal())
+                .collect::<Vec<_>>()
+        };
+        let from = Id::<Link>::get_from_ext("5").internal();
+        let to = Id::<Link>::get_from_ext("3").internal();
+
+        // a value of time of 3600 per hour makes a toll of 10 cost 10 seconds.
+        let mut graph = NetworkConverter::convert_network(&network, None);
+        NetworkConverter::apply_generalized_cost(
+            &mut graph,
+            &network,
+            &GeneralizedCost {
+                value_of_time: 3600.,
+                distance_cost: 0.,
+            },
+        );
+        let router = AltRouter::new(graph);
+        let result = router.query_links(from, to);
+        assert_eq!(Some(link_ids(&["5", "2", "6", "3"])), result.path);
+        assert_eq!(Some(7), result.travel_time);
+        let results = router.query_links_one_to_many(from, &[to]);
+        assert_eq!(Some(link_ids(&["5", "2", "6", "3"])), results[0].path);
+        assert_eq!(Some(7), results[0].travel_time);
+
+        // with a high value of time, the toll doesn't matter.
+        let mut graph = NetworkConverter::convert_network(&network, None);
+        NetworkConverter::apply_generalized_cost(
+            &mut graph,
+            &network,
+            &GeneralizedCost {
+                value_of_time: 360000.,
+                distance_cost: 0.,
+            },
+        );
+        let result = AltRouter::new(graph).query_links(from, to);
+        assert_eq!(Some(link_ids(&["5", "1", "3"])), result.path);
+        assert_eq!(Some(1), result.travel_time);
+    }
+
+    #[test]
+    fn test_dijkstra_backend() {
+        let alt = AltRouter::new(get_triangle_test_graph());
+        let dijkstra = AltRouter::with_backend(get_triangle_test_graph(), RoutingBackend::Dijkstra);
+
+        for from in 0..4 {
+            for to in 0..4 {
+                assert_eq!(alt.query(from, to), dijkstra.query(from, to));
+            }
+        }
+    }
+
+    #[test]
+    fn test_one_to_many() {
+        let graph = get_triangle_test_graph();
+        let router = AltRouter::new(graph);
+
+        let results = router.query_one_to_many(2, &[1, 3, 2, 0, 1]);
+        assert_eq!(
+            results,
+            vec![
+                AltQueryResult {
+                    travel_time: Some(6),
+                    node_path: Some(vec![2, 3, 1]),
+                },
+                AltQueryResult {
+                    travel_time: Some(4),
+                    node_path: Some(vec![2, 3]),
+                },
+                AltQueryResult {
+                    travel_time: Some(0),
+                    node_path: Some(vec![2]),
+                },
+                AltQueryResult::empty(),
+                AltQueryResult {
+                    travel_time: Some(6),
+                    node_path: Some(vec![2, 3, 1]),
+                },
+            ]
+        );
+
+        // one-to-many results must match the point-to-point queries
+        for to in 0..4 {
+            assert_eq!(
+                router.query_one_to_many(3, &[to]),
+                vec![router.query(3, to)]
+            );
+        }
+    }
+
     #[test]
     fn test_mode_alt_routing() {
         let network = Network::from_file(
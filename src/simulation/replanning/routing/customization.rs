@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread;
+use std::thread::JoinHandle;
+
+use nohash_hasher::IntMap;
+
+use crate::simulation::replanning::routing::graph::ForwardBackwardGraph;
+
+struct CustomizationJob {
+    veh_type: u64,
+    travel_times: HashMap<u64, u32>,
+}
+
+/// Applies travel time updates to routing graphs on a background thread, so that the simulation
+/// does not stall while graphs are copied and updated. The worker keeps its own copy of the graph
+/// of each vehicle type, to which the updates are applied in the order they were issued.
+///
+/// Finished graphs are collected with [BackgroundCustomizer::try_finished]. Until then, routing
+/// continues with the old travel times. As this depends on the speed of the worker thread, routes
+/// may differ between otherwise identical runs.
+pub struct BackgroundCustomizer {
+    jobs: Option<Sender<CustomizationJob>>,
+    finished: Receiver<(u64, ForwardBackwardGraph)>,
+    pending: usize,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl BackgroundCustomizer {
+    pub fn new(graphs: IntMap<u64, ForwardBackwardGraph>) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<CustomizationJob>();
+        let (finished_sender, finished_receiver) = mpsc::channel();
+        let worker = thread::Builder::new()
+            .name(String::from("graph-customization"))
+            .spawn(move || {
+                let mut graphs = graphs;
+                for job in job_receiver {
+                    let graph = graphs
+                        .get_mut(&job.veh_type)
+                        .unwrap_or_else(|| panic!("No graph for vehicle type {}", job.veh_type));
+                    *graph = graph.clone_with_new_travel_times_by_link(job.travel_times);
+                    if finished_sender.send((job.veh_type, graph.clone())).is_err() {
+                        // the router was dropped, so nobody needs the graph anymore.
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn graph customization thread");
+
+        BackgroundCustomizer {
+            jobs: Some(job_sender),
+            finished: finished_receiver,
+            pending: 0,
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues an update of the graph of the vehicle type with the internal id `veh_type`.
+    pub fn customize(&mut self, veh_type: u64, travel_times: HashMap<u64, u32>) {
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(CustomizationJob {
+                veh_type,
+                travel_times,
+            })
+            .expect("Graph customization thread has stopped.");
+        self.pending += 1;
+    }
+
+    /// Graphs which were customized since the last call, in the order the updates were issued.
+    pub fn try_finished(&mut self) -> Vec<(u64, ForwardBackwardGraph)> {
+        let mut result = Vec::new();
+        while self.pending > 0 {
+            match self.finished.try_recv() {
+                Ok(finished) => {
+                    self.pending -= 1;
+                    result.push(finished);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    panic!("Graph customization thread has stopped.")
+                }
+            }
+        }
+        result
+    }
+
+    /// Blocks until all queued updates are applied and returns the customized graphs.
+    pub fn wait_all(&mut self) -> Vec<(u64, ForwardBackwardGraph)> {
+        let mut result = Vec::new();
+        while self.pending > 0 {
+            let finished = self
+                .finished
+                .recv()
+                .expect("Graph customization thread has stopped.");
+            self.pending -= 1;
+            result.push(finished);
+        }
+        result
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+impl Drop for BackgroundCustomizer {
+    fn drop(&mut self) {
+        // closing the channel stops the worker.
+        self.jobs.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nohash_hasher::IntMap;
+
+    use crate::simulation::replanning::routing::customization::BackgroundCustomizer;
+    use crate::simulation::replanning::routing::graph::tests::get_triangle_test_graph;
+
+    #[test]
+    fn customize_in_background() {
+        let graph = get_triangle_test_graph();
+        let graphs: IntMap<u64, _> = [(7, graph.clone())].into_iter().collect();
+        let mut customizer = BackgroundCustomizer::new(graphs);
+
+        customizer.customize(7, HashMap::from([(5, 42)]));
+        customizer.customize(7, HashMap::from([(6, 43)]));
+        assert_eq!(2, customizer.pending());
+        let finished = customizer.wait_all();
+
+        assert_eq!(0, customizer.pending());
+        assert_eq!(2, finished.len());
+        // updates are applied on top of each other.
+        let expected = graph.clone_with_new_travel_times_by_link(HashMap::from([(5, 42), (6, 43)]));
+        assert_eq!((7, expected), finished[1]);
+        assert!(customizer.try_finished().is_empty());
+    }
+}
@@ -48,19 +48,19 @@ impl Dijkstra {
                 }
 
                 if queue.get_priority(&neighbour).unwrap().get()
-                    > current_distance.get() + graph.travel_time[i]
+                    > current_distance.get() + graph.weight(i)
                 {
                     //perform update
                     match queue.entry(neighbour) {
                         Entry::Occupied(e) => {
-                            e.set_priority(Distance(current_distance.get() + graph.travel_time[i]));
+                            e.set_priority(Distance(current_distance.get() + graph.weight(i)));
                         }
                         Entry::Vacant(_) => {
                             unreachable!();
                         }
                     }
                     //store in distance vec to return
-                    distances[neighbour] = current_distance.get() + graph.travel_time[i];
+                    distances[neighbour] = current_distance.get() + graph.weight(i);
                 }
             }
         }
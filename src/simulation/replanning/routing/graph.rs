@@ -1,4 +1,14 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Everything reachable within a travel time budget. See [ForwardBackwardGraph::isochrone].
+#[derive(Debug, PartialEq)]
+pub struct Isochrone {
+    /// Index of the reachable nodes in the graph and their travel time from the start.
+    pub nodes: Vec<(usize, u32)>,
+    /// Ids of the links which can be traversed completely, and the travel time to their end.
+    pub links: Vec<(u64, u32)>,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ForwardBackwardGraph {
@@ -46,6 +56,12 @@ impl ForwardBackwardGraph {
                 .unwrap_or_else(|| panic!("There is no travel time for link {:?}", link_id))
         })
     }
+    /// Whether the weights of the graph include more than the travel time. The shortest path
+    /// distance is not a travel time in that case.
+    pub fn has_generalized_cost(&self) -> bool {
+        !self.forward_graph.extra_cost.is_empty()
+    }
+
     pub fn forward_first_out(&self) -> &Vec<usize> {
         &self.forward_graph.first_out
     }
@@ -70,6 +86,80 @@ impl ForwardBackwardGraph {
         self.forward_graph.first_out.len() - 1
     }
 
+    pub fn node_coord(&self, node: usize) -> (f64, f64) {
+        (self.forward_graph.x[node], self.forward_graph.y[node])
+    }
+
+    /// Index of the node closest to (x, y). Returns None for graphs without nodes.
+    pub fn nearest_node(&self, x: f64, y: f64) -> Option<usize> {
+        (0..self.number_of_nodes())
+            .map(|node| {
+                let (node_x, node_y) = self.node_coord(node);
+                (node, (node_x - x).powi(2) + (node_y - y).powi(2))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(node, _)| node)
+    }
+
+    /// One-to-many query from the node closest to (x, y), which returns all nodes and links
+    /// reachable within `budget` seconds with the current travel times of the graph. In contrast to
+    /// a full dijkstra, only the part of the graph within the budget is visited.
+    pub fn isochrone(&self, x: f64, y: f64, budget: u32) -> Isochrone {
+        let from = match self.nearest_node(x, y) {
+            Some(node) => node,
+            None => {
+                return Isochrone {
+                    nodes: Vec::new(),
+                    links: Vec::new(),
+                }
+            }
+        };
+        let graph = &self.forward_graph;
+        let mut distances: HashMap<usize, u32> = HashMap::from([(from, 0)]);
+        let mut settled: Vec<(usize, u32)> = Vec::new();
+        let mut queue = BinaryHeap::from([Reverse((0, from))]);
+
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            // the queue may contain outdated entries for nodes which were reached faster later on.
+            if distances.get(&node).is_some_and(|d| *d < distance) {
+                continue;
+            }
+            settled.push((node, distance));
+            for i in graph.first_out[node]..graph.first_out[node + 1] {
+                let neighbour = graph.head[i];
+                let neighbour_distance = distance.saturating_add(graph.travel_time[i]);
+                if neighbour_distance > budget {
+                    continue;
+                }
+                if distances
+                    .get(&neighbour)
+                    .map_or(true, |d| neighbour_distance < *d)
+                {
+                    distances.insert(neighbour, neighbour_distance);
+                    queue.push(Reverse((neighbour_distance, neighbour)));
+                }
+            }
+        }
+
+        let links = settled
+            .iter()
+            .flat_map(|(node, distance)| {
+                (graph.first_out[*node]..graph.first_out[*node + 1]).map(move |i| {
+                    (
+                        graph.link_ids[i],
+                        distance.saturating_add(graph.travel_time[i]),
+                    )
+                })
+            })
+            .filter(|(_, travel_time)| *travel_time <= budget)
+            .collect();
+
+        Isochrone {
+            nodes: settled,
+            links,
+        }
+    }
+
     #[cfg(test)]
     pub fn number_of_links(&self) -> usize {
         self.forward_graph.head.len()
@@ -100,6 +190,9 @@ pub struct Graph {
     pub(crate) x: Vec<f64>,
     pub(crate) y: Vec<f64>,
     pub(crate) link_id_pos: HashMap<u64, usize>,
+    /// Cost of traversing a link on top of its travel time, e.g. from tolls, in seconds. Empty if
+    /// routing doesn't use a generalized cost.
+    pub(crate) extra_cost: Vec<u32>,
 }
 
 impl Graph {
@@ -113,9 +206,17 @@ impl Graph {
             x: vec![],
             y: vec![],
             link_id_pos: HashMap::new(),
+            extra_cost: vec![],
         }
     }
 
+    /// The weight of a link for shortest path searches. This is the travel time plus the extra
+    /// cost of the link, if there is one.
+    pub(crate) fn weight(&self, index: usize) -> u32 {
+        let extra_cost = self.extra_cost.get(index).copied().unwrap_or(0);
+        self.travel_time[index].saturating_add(extra_cost)
+    }
+
     #[tracing::instrument(level = "trace", skip(new_travel_times_by_link))]
     pub fn clone_with_new_travel_times_by_link(
         &self,
@@ -148,6 +249,8 @@ pub(crate) mod tests {
     use crate::simulation::config::{MetisOptions, PartitionMethod};
     use std::collections::HashMap;
 
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::Link;
     use crate::simulation::network::global_network::Network;
     use crate::simulation::replanning::routing::graph::{ForwardBackwardGraph, Graph};
     use crate::simulation::replanning::routing::network_converter::NetworkConverter;
@@ -210,4 +313,31 @@ pub(crate) mod tests {
         graph.backward_graph.travel_time[3] = 42;
         assert_eq!(graph, new_graph);
     }
+
+    #[test]
+    fn isochrone() {
+        let graph = get_triangle_test_graph();
+
+        // close to node 1. Links 1 and 2 take 1 and 2 seconds, link 3 is a loop at node 2.
+        let isochrone = graph.isochrone(-15000., 0., 3);
+
+        let nodes: Vec<(f64, u32)> = isochrone
+            .nodes
+            .iter()
+            .map(|(node, time)| (graph.node_coord(*node).0, *time))
+            .collect();
+        assert_eq!(vec![(-15000.002, 0), (-865.213, 1), (-2498.0, 2)], nodes);
+        let mut links: Vec<(String, u32)> = isochrone
+            .links
+            .iter()
+            .map(|(id, time)| (Id::<Link>::get(*id).external().to_string(), *time))
+            .collect();
+        links.sort();
+        let expected: Vec<(String, u32)> = vec![
+            (String::from("1"), 1),
+            (String::from("2"), 2),
+            (String::from("3"), 2),
+        ];
+        assert_eq!(expected, links);
+    }
 }
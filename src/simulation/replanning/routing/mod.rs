@@ -1,8 +1,10 @@
 pub mod alt_landmark_data;
 pub mod alt_router;
+pub mod customization;
 mod dijsktra;
-mod graph;
-mod network_converter;
+pub mod graph;
+pub(crate) mod network_converter;
+pub mod route_cache;
 pub mod router;
 pub mod travel_time_collector;
 pub mod travel_times_collecting_alt_router;
@@ -4,17 +4,22 @@ use itertools::Itertools;
 use nohash_hasher::IntMap;
 use tracing::info;
 
+use crate::simulation::config::GeneralizedCost;
+use crate::simulation::id::id_map::IdMap;
 use crate::simulation::id::Id;
 use crate::simulation::network::global_network::{Link, Network};
 use crate::simulation::replanning::routing::graph::{ForwardBackwardGraph, Graph};
 use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
 
+/// Link attribute holding the toll for traversing a link.
+pub const TOLL_ATTRIBUTE: &str = "toll";
+
 pub struct NetworkConverter {}
 
 impl NetworkConverter {
     pub fn convert_network_with_vehicle_types(
         network: &Network,
-        vehicle_types: &IntMap<Id<VehicleType>, VehicleType>,
+        vehicle_types: &IdMap<VehicleType, VehicleType>,
     ) -> IntMap<Id<VehicleType>, ForwardBackwardGraph> {
         vehicle_types
             .iter()
@@ -61,8 +66,8 @@ impl NetworkConverter {
 
         for node in nodes.iter() {
             //set x and y
-            y.push(node.x);
-            x.push(node.y);
+            x.push(node.x);
+            y.push(node.y);
 
             forward_first_out.push(forward_links_before);
             backward_first_out.push(backward_links_before);
@@ -129,6 +134,7 @@ impl NetworkConverter {
             x: x.clone(),
             y: y.clone(),
             link_id_pos: forward_link_id_pos,
+            extra_cost: Vec::new(),
         };
 
         let backward_graph = Graph {
@@ -139,6 +145,7 @@ impl NetworkConverter {
             x,
             y,
             link_id_pos: backward_link_id_pos,
+            extra_cost: Vec::new(),
         };
 
         info!(
@@ -149,6 +156,52 @@ impl NetworkConverter {
         ForwardBackwardGraph::new(forward_graph, backward_graph)
     }
 
+    /// Adds the monetary cost of links to the weights of the graph, so that routes minimize the
+    /// generalized cost instead of the travel time. The cost of a link consists of a distance
+    /// based cost and an optional toll, which is read from the link attribute [TOLL_ATTRIBUTE].
+    /// It is converted to seconds with the value of time.
+    pub(crate) fn apply_generalized_cost(
+        graph: &mut ForwardBackwardGraph,
+        network: &Network,
+        cost: &GeneralizedCost,
+    ) {
+        assert!(
+            cost.value_of_time > 0.,
+            "The value of time must be positive, but was {}.",
+            cost.value_of_time
+        );
+        let money_per_second = cost.value_of_time / 3600.;
+        let extra_cost = |link_id: &u64| {
+            let link = network.get_link(&Id::get(*link_id));
+            let toll = link
+                .attributes
+                .get(TOLL_ATTRIBUTE)
+                .map(|toll| {
+                    toll.parse::<f64>().unwrap_or_else(|_| {
+                        panic!(
+                            "Toll {toll} of link {} is not a number.",
+                            link.id.external()
+                        )
+                    })
+                })
+                .unwrap_or(0.);
+            ((cost.distance_cost * link.length + toll) / money_per_second).round() as u32
+        };
+
+        graph.forward_graph.extra_cost = graph
+            .forward_graph
+            .link_ids
+            .iter()
+            .map(extra_cost)
+            .collect();
+        graph.backward_graph.extra_cost = graph
+            .backward_graph
+            .link_ids
+            .iter()
+            .map(extra_cost)
+            .collect();
+    }
+
     fn get_links<'net>(
         link_ids: &Vec<Id<Link>>,
         network: &'net Network,
@@ -161,6 +214,9 @@ impl NetworkConverter {
             .filter(|&l| {
                 if let Some(vt) = vehicle_type {
                     l.contains_mode(vt.net_mode)
+                        && !network
+                            .access_restrictions
+                            .restricts_any_time(l.id.internal(), vt.id)
                 } else {
                     true
                 }
@@ -173,8 +229,10 @@ impl NetworkConverter {
 mod test {
     use std::path::PathBuf;
 
+    use crate::simulation::config;
     use crate::simulation::config::{MetisOptions, PartitionMethod};
     use crate::simulation::id::Id;
+    use crate::simulation::network::access_restrictions::AccessRestrictions;
     use crate::simulation::network::global_network::Network;
     use crate::simulation::replanning::routing::network_converter::NetworkConverter;
     use crate::simulation::vehicles::garage::Garage;
@@ -242,6 +300,51 @@ mod test {
         assert_eq!(bike_network.forward_link_ids().len(), 4);
     }
 
+    #[test]
+    fn test_access_restrictions() {
+        let mut network = Network::from_file(
+            "./assets/routing_tests/network_different_modes.xml",
+            1,
+            PartitionMethod::Metis(MetisOptions::default()),
+        );
+
+        let mut garage = Garage::new();
+        let car_type_id = Id::<VehicleType>::create("car");
+        let car_id = Id::<String>::get_from_ext("car");
+        garage.add_veh_type(create_vehicle_type(&car_type_id, car_id.clone()));
+        let bike_type_id = Id::<VehicleType>::create("bike");
+        let bike_id = Id::<String>::get_from_ext("bike");
+        garage.add_veh_type(create_vehicle_type(&bike_type_id, bike_id));
+
+        // ban cars from one of their links
+        let restricted = network
+            .links
+            .iter_mut()
+            .find(|link| link.contains_mode(car_id.internal()))
+            .unwrap();
+        restricted
+            .attributes
+            .insert(String::from("car_free"), String::from("true"));
+        let restricted = restricted.id.internal();
+        let config = config::AccessRestrictions {
+            zones: vec![config::RestrictedZone {
+                attribute: String::from("car_free"),
+                veh_types: vec![String::from("car")],
+                start_time: 8 * 3600,
+                end_time: 9 * 3600,
+            }],
+        };
+        network.access_restrictions = AccessRestrictions::from_config(&config, &network);
+
+        let graph_by_mode =
+            NetworkConverter::convert_network_with_vehicle_types(&network, &garage.vehicle_types);
+        let car_network = graph_by_mode.get(&car_type_id).unwrap();
+        assert_eq!(car_network.forward_link_ids().len(), 3);
+        assert!(!car_network.forward_link_ids().contains(&restricted));
+        let bike_network = graph_by_mode.get(&bike_type_id).unwrap();
+        assert_eq!(bike_network.forward_link_ids().len(), 4);
+    }
+
     #[test]
     fn test_mode_filter() {
         let network = Network::from_file(
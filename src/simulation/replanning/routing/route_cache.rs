@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Length of the time bins routes are cached for. This matches the interval in which ad-hoc
+/// routing updates its travel times.
+pub const ROUTE_CACHE_TIME_BIN: u32 = 900;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RouteCacheKey {
+    pub from_link: u64,
+    pub to_link: u64,
+    pub veh_type: u64,
+    pub time_bin: u32,
+}
+
+impl RouteCacheKey {
+    pub fn new(from_link: u64, to_link: u64, veh_type: u64, now: u32) -> Self {
+        RouteCacheKey {
+            from_link,
+            to_link,
+            veh_type,
+            time_bin: now / ROUTE_CACHE_TIME_BIN,
+        }
+    }
+}
+
+/// Least recently used cache for shortest path queries. Each entry remembers the tick of its last
+/// access, and the ticks are indexed in a sorted map, so that the least recently used entry can be
+/// evicted in logarithmic time.
+#[derive(Debug)]
+pub struct RouteCache {
+    capacity: usize,
+    entries: HashMap<RouteCacheKey, (Vec<u64>, Option<u32>, u64)>,
+    by_last_use: BTreeMap<u64, RouteCacheKey>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl RouteCache {
+    pub fn new(capacity: usize) -> Self {
+        RouteCache {
+            capacity,
+            entries: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &RouteCacheKey) -> Option<(Vec<u64>, Option<u32>)> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some((route, travel_time, last_use)) => {
+                self.by_last_use.remove(last_use);
+                self.by_last_use.insert(tick, *key);
+                *last_use = tick;
+                self.hits += 1;
+                Some((route.clone(), *travel_time))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Checks for an entry without counting it as a use.
+    pub fn contains(&self, key: &RouteCacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: RouteCacheKey, route: Vec<u64>, travel_time: Option<u32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, _, last_use)) = self.entries.remove(&key) {
+            self.by_last_use.remove(&last_use);
+        } else if self.entries.len() >= self.capacity {
+            let (_, oldest) = self
+                .by_last_use
+                .pop_first()
+                .expect("Cache is full, but has no entries.");
+            self.entries.remove(&oldest);
+        }
+        self.by_last_use.insert(self.tick, key);
+        self.entries.insert(key, (route, travel_time, self.tick));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.by_last_use.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::replanning::routing::route_cache::{RouteCache, RouteCacheKey};
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = RouteCache::new(2);
+        let a = RouteCacheKey::new(1, 2, 0, 0);
+        let b = RouteCacheKey::new(2, 3, 0, 0);
+        let c = RouteCacheKey::new(3, 4, 0, 0);
+
+        cache.insert(a, vec![1, 2], Some(10));
+        cache.insert(b, vec![2, 3], Some(20));
+        // touch a, so that b becomes the least recently used entry
+        assert_eq!(Some((vec![1, 2], Some(10))), cache.get(&a));
+        cache.insert(c, vec![3, 4], Some(30));
+
+        assert_eq!(2, cache.len());
+        assert!(cache.get(&b).is_none());
+        assert_eq!(Some((vec![1, 2], Some(10))), cache.get(&a));
+        assert_eq!(Some((vec![3, 4], Some(30))), cache.get(&c));
+        assert_eq!(3, cache.hits());
+        assert_eq!(1, cache.misses());
+    }
+
+    #[test]
+    fn time_bins_and_clear() {
+        let mut cache = RouteCache::new(10);
+        cache.insert(RouteCacheKey::new(1, 2, 0, 100), vec![1, 2], Some(10));
+
+        assert!(cache.get(&RouteCacheKey::new(1, 2, 0, 899)).is_some());
+        assert!(cache.get(&RouteCacheKey::new(1, 2, 0, 900)).is_none());
+        assert!(cache.get(&RouteCacheKey::new(1, 2, 1, 100)).is_none());
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(cache.get(&RouteCacheKey::new(1, 2, 0, 100)).is_none());
+    }
+
+    #[test]
+    fn disabled() {
+        let mut cache = RouteCache::new(0);
+        cache.insert(RouteCacheKey::new(1, 2, 0, 0), vec![1, 2], Some(10));
+        assert!(cache.is_empty());
+    }
+}
@@ -11,7 +11,23 @@ pub trait NetworkRouter {
         veh_type_id: &Id<VehicleType>,
     ) -> CustomQueryResult;
 
-    fn next_time_step(&mut self, now: u32, events: &mut EventsPublisher);
+    /// Routes from one link to many links. Routers may answer this with a single search, which
+    /// is cheaper than independent point to point queries. Results are in the order of `to_links`.
+    fn query_links_one_to_many(
+        &self,
+        from_link: u64,
+        to_links: &[u64],
+        veh_type_id: &Id<VehicleType>,
+    ) -> Vec<CustomQueryResult> {
+        to_links
+            .iter()
+            .map(|to_link| self.query_links(from_link, *to_link, veh_type_id))
+            .collect()
+    }
+
+    /// Advances the router to the given time step. Returns whether the routing graphs changed,
+    /// i.e. whether previous query results may be outdated.
+    fn next_time_step(&mut self, now: u32, events: &mut EventsPublisher) -> bool;
 }
 
 impl Debug for dyn NetworkRouter {
@@ -5,12 +5,15 @@ use std::rc::Rc;
 use nohash_hasher::IntMap;
 use tracing::{debug, info};
 
+use crate::simulation::config::RoutingBackend;
+use crate::simulation::id::id_map::IdMap;
 use crate::simulation::id::Id;
 use crate::simulation::messaging::communication::communicators::SimCommunicator;
 use crate::simulation::messaging::communication::message_broker::TravelTimesMessageBroker;
 use crate::simulation::messaging::events::EventsPublisher;
 use crate::simulation::network::global_network::Network;
 use crate::simulation::replanning::routing::alt_router::AltRouter;
+use crate::simulation::replanning::routing::customization::BackgroundCustomizer;
 use crate::simulation::replanning::routing::graph::ForwardBackwardGraph;
 use crate::simulation::replanning::routing::network_converter::NetworkConverter;
 use crate::simulation::replanning::routing::router::{CustomQueryResult, NetworkRouter};
@@ -22,6 +25,9 @@ pub struct TravelTimesCollectingAltRouter<C: SimCommunicator> {
     router_by_veh_type: BTreeMap<Id<VehicleType>, AltRouter>,
     traffic_message_broker: TravelTimesMessageBroker<C>,
     link_ids_of_process: HashSet<u64>,
+    customizer: BackgroundCustomizer,
+    /// whether routing continues with old travel times until the customization is done.
+    background_customization: bool,
 }
 
 impl<C: SimCommunicator> Debug for TravelTimesCollectingAltRouter<C> {
@@ -47,12 +53,35 @@ impl<C: SimCommunicator> NetworkRouter for TravelTimesCollectingAltRouter<C> {
             .query_links(from_link, to_link)
     }
 
-    fn next_time_step(&mut self, now: u32, events: &mut EventsPublisher) {
+    fn query_links_one_to_many(
+        &self,
+        from_link: u64,
+        to_links: &[u64],
+        veh_type_id: &Id<VehicleType>,
+    ) -> Vec<CustomQueryResult> {
+        self.get_router_by_mode(veh_type_id)
+            .unwrap_or_else(|| {
+                panic!(
+                    "There is no router for mode {:?}. Check the vehicle definitions.",
+                    veh_type_id
+                )
+            })
+            .query_links_one_to_many(from_link, to_links)
+    }
+
+    fn next_time_step(&mut self, now: u32, events: &mut EventsPublisher) -> bool {
+        let finished = self.customizer.try_finished();
+        let mut changed = self.swap_graphs(finished);
+
         let traffic_update_interval_in_min = 15;
         if now % (60 * traffic_update_interval_in_min) != 0 {
-            return;
+            return changed;
         }
 
+        // updates are computed relative to the current graphs, so previous updates must be applied.
+        let finished = self.customizer.wait_all();
+        changed |= self.swap_graphs(finished);
+
         let _hour = now / 3600;
         let _min = (now % 3600) / 60;
         debug!(
@@ -78,12 +107,18 @@ impl<C: SimCommunicator> NetworkRouter for TravelTimesCollectingAltRouter<C> {
         for (veh_type_id, message) in received_messages_by_veh_type_id.into_iter() {
             self.handle_traffic_info_messages(now, veh_type_id, message);
         }
+        if !self.background_customization {
+            let finished = self.customizer.wait_all();
+            changed |= self.swap_graphs(finished);
+        }
 
         //reset travel times
         events
             .get_subscriber::<TravelTimeCollector>()
             .expect("There is no TravelTimeCollector as EventSubscriber.")
             .flush();
+
+        changed
     }
 }
 
@@ -111,11 +146,19 @@ impl<C: SimCommunicator> TravelTimesCollectingAltRouter<C> {
         forward_backward_graph_by_mode: IntMap<Id<VehicleType>, ForwardBackwardGraph>,
         communicator: Rc<C>,
         link_ids_of_process: HashSet<u64>,
+        background_customization: bool,
+        backend: RoutingBackend,
     ) -> Self {
         let router_by_vehicle_type = forward_backward_graph_by_mode
             .iter()
-            .map(|(m, g)| (m.clone(), AltRouter::new(g.clone())))
+            .map(|(m, g)| (m.clone(), AltRouter::with_backend(g.clone(), backend)))
             .collect::<BTreeMap<_, _>>();
+        let customizer = BackgroundCustomizer::new(
+            forward_backward_graph_by_mode
+                .into_iter()
+                .map(|(m, g)| (m.internal(), g))
+                .collect(),
+        );
 
         info!(
             "Created TravelTimesCollectingAltRouter with vehicle types: {:?}",
@@ -129,7 +172,21 @@ impl<C: SimCommunicator> TravelTimesCollectingAltRouter<C> {
             router_by_veh_type: router_by_vehicle_type,
             traffic_message_broker: TravelTimesMessageBroker::new(communicator),
             link_ids_of_process,
+            customizer,
+            background_customization,
+        }
+    }
+
+    /// Returns whether any graph was replaced.
+    fn swap_graphs(&mut self, graphs: Vec<(u64, ForwardBackwardGraph)>) -> bool {
+        let changed = !graphs.is_empty();
+        for (veh_type_id, graph) in graphs {
+            self.router_by_veh_type
+                .get_mut(&Id::<VehicleType>::get(veh_type_id))
+                .unwrap()
+                .update(graph);
         }
+        changed
     }
 
     #[tracing::instrument(level = "trace", skip(veh_type_id_internal, traffic_info_messages))]
@@ -158,19 +215,9 @@ impl<C: SimCommunicator> TravelTimesCollectingAltRouter<C> {
             travel_times_by_link.len()
         );
 
-        let veh_type_id = Id::<VehicleType>::get(veh_type_id_internal);
-
-        let new_graph = self
-            .router_by_veh_type
-            .get(&veh_type_id)
-            .unwrap()
-            .current_graph()
-            .clone_with_new_travel_times_by_link(travel_times_by_link);
-
-        self.router_by_veh_type
-            .get_mut(&veh_type_id)
-            .unwrap()
-            .update(new_graph);
+        // the graph is swapped in once the worker has applied the travel times.
+        self.customizer
+            .customize(veh_type_id_internal, travel_times_by_link);
     }
 
     fn get_router_by_mode(&self, veh_type_id: &Id<VehicleType>) -> Option<&AltRouter> {
@@ -220,7 +267,7 @@ impl<C: SimCommunicator> TravelTimesCollectingAltRouter<C> {
 
     pub fn get_forward_backward_graph_by_veh_type(
         network: &Network,
-        vehicle_types: &IntMap<Id<VehicleType>, VehicleType>,
+        vehicle_types: &IdMap<VehicleType, VehicleType>,
     ) -> IntMap<Id<VehicleType>, ForwardBackwardGraph> {
         NetworkConverter::convert_network_with_vehicle_types(network, vehicle_types)
     }
@@ -0,0 +1,182 @@
+use rand::Rng;
+
+use crate::simulation::config;
+use crate::simulation::config::PlanStrategy;
+use crate::simulation::population::population::Population;
+use crate::simulation::random::split_mix;
+use crate::simulation::wire_types::population::Person;
+
+/// Selects a plan strategy for each agent of an iteration according to the configured weights.
+/// The selection is derived from the seed, the iteration and the random seed of the agent only.
+/// This way, all partitions select the same strategy for an agent, and the time mutation, which is
+/// applied on the agent's first partition before the iteration starts, agrees with the re-routing
+/// decisions of the ad-hoc replanner on the partitions the agent travels through.
+#[derive(Debug, Clone)]
+pub struct StrategyChooser {
+    /// Strategies with their cumulative weights.
+    strategies: Vec<(PlanStrategy, f64)>,
+    seed: u64,
+    iteration: u32,
+}
+
+impl StrategyChooser {
+    pub fn from_config(config: &config::Strategy, iteration: u32) -> Self {
+        let innovative = config.is_innovative(iteration);
+        let mut total = 0.;
+        let strategies = config
+            .strategies
+            .iter()
+            .filter(|settings| settings.weight > 0.)
+            .filter(|settings| innovative || !settings.strategy.is_innovative())
+            .filter(|settings| {
+                !matches!(settings.disable_after, Some(last_iteration) if iteration > last_iteration)
+            })
+            .map(|settings| {
+                total += settings.weight;
+                (settings.strategy, total)
+            })
+            .collect();
+        StrategyChooser {
+            strategies,
+            seed: config.seed,
+            iteration,
+        }
+    }
+
+    pub fn choose(&self, person: &Person) -> PlanStrategy {
+        let total = match self.strategies.last() {
+            Some((_, total)) => *total,
+            None => return PlanStrategy::ReRoute,
+        };
+        let salt = split_mix(self.seed ^ self.iteration as u64);
+        let draw = person.salted_rng(salt).gen_range(0.0..total);
+        self.strategies
+            .iter()
+            .find(|(_, cumulative)| draw < *cumulative)
+            .map(|(strategy, _)| *strategy)
+            .unwrap_or(self.strategies.last().unwrap().0)
+    }
+
+    /// Applies the time mutation to the plans of all agents, which selected it. The plans are the
+    /// ones executed in the previous iteration, so that mutations accumulate over the iterations.
+    /// Returns the number of mutated plans.
+    pub fn mutate_times(&self, population: &mut Population) -> usize {
+        let mut mutated = 0;
+        for person in population.persons.values_mut() {
+            if let PlanStrategy::TimeMutation { range } = self.choose(person) {
+                let mut rng = person.next_rng();
+                mutate_times(person, range, &mut rng);
+                mutated += 1;
+            }
+        }
+        mutated
+    }
+}
+
+/// Shifts end times and maximum durations of the main activities by a random offset within
+/// `range` seconds. Interaction activities are left as they are.
+fn mutate_times(person: &mut Person, range: u32, rng: &mut impl Rng) {
+    let range = range as i64;
+    let mut shift = |time: u32| (time as i64 + rng.gen_range(-range..=range)).max(0) as u32;
+    let plan = match person.plan.as_mut() {
+        Some(plan) => plan,
+        None => return,
+    };
+    for act in plan.acts.iter_mut().filter(|act| !act.is_interaction()) {
+        act.end_time = act.end_time.map(&mut shift);
+        act.max_dur = act.max_dur.map(&mut shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::config::{PlanStrategy, Strategy, StrategySettings};
+    use crate::simulation::id::Id;
+    use crate::simulation::population::population::Population;
+    use crate::simulation::replanning::strategies::StrategyChooser;
+    use crate::simulation::wire_types::population::{Activity, Person, Plan};
+
+    fn strategy(settings: &[(PlanStrategy, f64, Option<u32>)]) -> Strategy {
+        Strategy {
+            last_iteration: 10,
+            innovation_shutoff: Some(0.5),
+            strategies: settings
+                .iter()
+                .map(|(strategy, weight, disable_after)| StrategySettings {
+                    strategy: *strategy,
+                    weight: *weight,
+                    disable_after: *disable_after,
+                })
+                .collect(),
+            ..Strategy::default()
+        }
+    }
+
+    #[test]
+    fn choose_by_weight() {
+        let config = strategy(&[
+            (PlanStrategy::ReRoute, 0.1, None),
+            (PlanStrategy::TimeMutation { range: 1800 }, 0.1, Some(2)),
+            (PlanStrategy::KeepLastSelected, 0.8, None),
+        ]);
+        let persons: Vec<Person> = (0..10000).map(|id| Person::new(id, Plan::new())).collect();
+        let count = |chooser: &StrategyChooser, strategy: PlanStrategy| {
+            persons
+                .iter()
+                .filter(|person| chooser.choose(person) == strategy)
+                .count()
+        };
+
+        let chooser = StrategyChooser::from_config(&config, 0);
+        let re_routes = count(&chooser, PlanStrategy::ReRoute);
+        assert!((800..1200).contains(&re_routes), "{re_routes}");
+        let keeps = count(&chooser, PlanStrategy::KeepLastSelected);
+        assert!((7600..8400).contains(&keeps), "{keeps}");
+        // the selection is reproducible
+        assert_eq!(chooser.choose(&persons[42]), chooser.choose(&persons[42]));
+
+        // the time mutation is disabled after iteration 2
+        let chooser = StrategyChooser::from_config(&config, 3);
+        assert_eq!(
+            0,
+            count(&chooser, PlanStrategy::TimeMutation { range: 1800 })
+        );
+        // innovation is shut off after iteration 5
+        let chooser = StrategyChooser::from_config(&config, 5);
+        assert_eq!(10000, count(&chooser, PlanStrategy::KeepLastSelected));
+    }
+
+    #[test]
+    fn re_route_without_strategies() {
+        let chooser = StrategyChooser::from_config(&Strategy::default(), 0);
+        assert_eq!(
+            PlanStrategy::ReRoute,
+            chooser.choose(&Person::new(1, Plan::new()))
+        );
+    }
+
+    #[test]
+    fn mutate_end_times() {
+        let config = strategy(&[(PlanStrategy::TimeMutation { range: 600 }, 1., None)]);
+        let act_type = Id::<String>::create("st-act").internal();
+        let interaction = Id::<String>::create("car interaction").internal();
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., act_type, 0, None, Some(3600), None));
+        plan.add_act(Activity::interaction(0, interaction));
+        let person_id = Id::<Person>::create("st-person");
+        let mut population = Population::new();
+        population
+            .persons
+            .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+
+        let chooser = StrategyChooser::from_config(&config, 0);
+        assert_eq!(1, chooser.mutate_times(&mut population));
+
+        let acts = &population.persons[&person_id].plan.as_ref().unwrap().acts;
+        let end_time = acts[0].end_time.unwrap();
+        assert!((3000..=4200).contains(&end_time), "{end_time}");
+        assert_eq!(Some(0), acts[1].max_dur);
+        // the agent has drawn random numbers once
+        assert_eq!(1, population.persons[&person_id].rng_counter);
+    }
+}
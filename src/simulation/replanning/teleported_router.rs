@@ -1,13 +1,19 @@
 use geo::{Closest, ClosestPoint, EuclideanDistance, Line, Point};
+use nohash_hasher::IntMap;
 use std::fmt::Debug;
 
+use crate::simulation::config;
+use crate::simulation::id::Id;
 use crate::simulation::network::global_network::Network;
 use crate::simulation::wire_types::population::Activity;
 
+/// Routers for teleported legs. The `speed` passed into queries is the maximum velocity of the
+/// vehicle type of a leg. Routers may override it for a `mode`.
 pub trait TeleportedRouter {
     fn query_access_egress(
         &self,
         curr_act: &Activity,
+        mode: u64,
         access_egress_speed: f32,
         network: &Network,
     ) -> Teleportation;
@@ -16,6 +22,7 @@ pub trait TeleportedRouter {
         &self,
         curr_act: &Activity,
         next_act: &Activity,
+        mode: u64,
         access_egress_speed: f32,
     ) -> Teleportation;
 }
@@ -32,7 +39,16 @@ pub struct Teleportation {
     pub duration: u32,
 }
 
-pub struct BeeLineDistanceRouter {}
+/// Beeline speed and distance factor of a teleported mode, as in matsim's teleportation router.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TeleportationParams {
+    pub speed: f32,
+    pub distance_factor: f64,
+}
+
+pub struct BeeLineDistanceRouter {
+    params: IntMap<u64, TeleportationParams>,
+}
 
 impl Default for BeeLineDistanceRouter {
     fn default() -> Self {
@@ -42,12 +58,38 @@ impl Default for BeeLineDistanceRouter {
 
 impl BeeLineDistanceRouter {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            params: IntMap::default(),
+        }
     }
 
-    fn query_points(speed: f32, p1: Point, p2: Point) -> Teleportation {
-        let distance = p1.euclidean_distance(&p2);
-        let duration = (distance / speed as f64) as u32;
+    pub fn from_config(config: &config::Teleportation) -> Self {
+        let params = config
+            .modes
+            .iter()
+            .map(|m| {
+                let mode: Id<String> = Id::create(&m.mode);
+                let params = TeleportationParams {
+                    speed: m.beeline_speed,
+                    distance_factor: m.beeline_distance_factor,
+                };
+                (mode.internal(), params)
+            })
+            .collect();
+        Self { params }
+    }
+
+    fn query_points(&self, mode: u64, speed: f32, p1: Point, p2: Point) -> Teleportation {
+        let params = self
+            .params
+            .get(&mode)
+            .copied()
+            .unwrap_or(TeleportationParams {
+                speed,
+                distance_factor: 1.,
+            });
+        let distance = p1.euclidean_distance(&p2) * params.distance_factor;
+        let duration = (distance / params.speed as f64) as u32;
         Teleportation { distance, duration }
     }
 }
@@ -56,6 +98,7 @@ impl TeleportedRouter for BeeLineDistanceRouter {
     fn query_access_egress(
         &self,
         activity: &Activity,
+        mode: u64,
         speed: f32,
         network: &Network,
     ) -> Teleportation {
@@ -82,19 +125,20 @@ impl TeleportedRouter for BeeLineDistanceRouter {
                 panic!("Couldn't find closest point.")
             }
         };
-        Self::query_points(speed, curr_act_point, closest)
+        self.query_points(mode, speed, curr_act_point, closest)
     }
 
     fn query_between_acts(
         &self,
         curr_act: &Activity,
         next_act: &Activity,
+        mode: u64,
         speed: f32,
     ) -> Teleportation {
         let curr_act_point = Point::new(curr_act.x, curr_act.y);
         let next_act_point = Point::new(next_act.x, next_act.y);
 
-        Self::query_points(speed, curr_act_point, next_act_point)
+        self.query_points(mode, speed, curr_act_point, next_act_point)
     }
 }
 
@@ -102,7 +146,9 @@ impl TeleportedRouter for BeeLineDistanceRouter {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::simulation::config::{MetisOptions, PartitionMethod};
+    use crate::simulation::config::{
+        MetisOptions, PartitionMethod, Teleportation as TeleportationConfig, TeleportedModeParams,
+    };
     use crate::simulation::id::Id;
     use crate::simulation::network::global_network::Network;
     use crate::simulation::population::population::Population;
@@ -110,7 +156,7 @@ mod tests {
         BeeLineDistanceRouter, Teleportation, TeleportedRouter,
     };
     use crate::simulation::vehicles::garage::Garage;
-    use crate::simulation::wire_types::population::Person;
+    use crate::simulation::wire_types::population::{Activity, Person};
 
     #[test]
     fn test_teleported_router() {
@@ -133,7 +179,7 @@ mod tests {
             .unwrap();
 
         // Activity(-25,000;0), Link from(-20,000;0), to(-15,000;0) => distance to link 5,000
-        let walk = teleported_router.query_access_egress(agent.curr_act(), 1.2, &network);
+        let walk = teleported_router.query_access_egress(agent.curr_act(), 0, 1.2, &network);
         assert_eq!(
             walk,
             Teleportation {
@@ -143,7 +189,7 @@ mod tests {
         );
 
         // Activity(3,456;4,242), Link from(0;0), to(5,000;0) => distance to link 4,242
-        let walk = teleported_router.query_access_egress(agent.next_act(), 1.2, &network);
+        let walk = teleported_router.query_access_egress(agent.next_act(), 0, 1.2, &network);
         assert_eq!(
             walk,
             Teleportation {
@@ -152,4 +198,37 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn test_teleportation_params() {
+        let config = TeleportationConfig {
+            modes: vec![TeleportedModeParams {
+                mode: String::from("bike"),
+                beeline_speed: 4.,
+                beeline_distance_factor: 1.5,
+            }],
+        };
+        let router = BeeLineDistanceRouter::from_config(&config);
+        let bike = Id::<String>::get_from_ext("bike").internal();
+        let walk = Id::<String>::create("walk").internal();
+        let from = Activity::new(0., 0., 0, 0, None, None, None);
+        let to = Activity::new(30., 40., 0, 0, None, None, None);
+
+        // configured modes use beeline speed and distance factor
+        assert_eq!(
+            Teleportation {
+                distance: 75.,
+                duration: 18,
+            },
+            router.query_between_acts(&from, &to, bike, 1.)
+        );
+        // other modes use the passed speed and the plain beeline distance
+        assert_eq!(
+            Teleportation {
+                distance: 50.,
+                duration: 50,
+            },
+            router.query_between_acts(&from, &to, walk, 1.)
+        );
+    }
 }
@@ -0,0 +1,350 @@
+//! Replays the vehicle movements of a previous run as background traffic. This way, only a subset
+//! of the agents, e.g. the agents of a study area, has to be simulated, while the traffic of all
+//! other agents still occupies the network.
+//!
+//! The trips of the vehicles are reconstructed from the events file. A trip starts with the
+//! departure of its driver and passes all links the vehicle entered until the driver leaves it.
+//! Trips of simulated agents are skipped. Links which are not part of the network split a trip into
+//! several runs, e.g. if the network is a cut-out of the network of the previous run. Each run is
+//! injected at the time the vehicle entered its first link. Replayed vehicles are regular vehicles
+//! on the network, so they compete with the simulated vehicles for capacity, but they don't react
+//! to congestion. They are removed once they have reached the end of their run.
+//!
+//! With a cordon, the network is a cut-out of the network of the previous run. Agents, whose plans
+//! leave the cut-out, are not simulated at all. Their trips become boundary demand, which enters
+//! the cut-out where the vehicle crossed the cordon, and leaves it at the last link inside.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use nohash_hasher::{IntMap, IntSet};
+use tracing::info;
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::io::xml_events::XmlEventsReader;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::population::population::Population;
+use crate::simulation::time_queue::TimeQueue;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::events::event::Type;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// Id of the vehicle type of replayed vehicles. Replayed vehicles are recognized by this type.
+const REPLAY_VEH_TYPE: &str = "replay";
+
+/// Vehicle movements of a previous run, which depart on this partition.
+pub struct Replay {
+    vehicle_type: VehicleType,
+    departures: TimeQueue<Vehicle>,
+}
+
+/// Trip of a vehicle with the links it entered and the times it entered them. The first link is
+/// the departure link of the driver.
+#[derive(Debug, Clone, PartialEq)]
+struct Trip {
+    driver: u64,
+    vehicle: u64,
+    links: Vec<(u64, u32)>,
+}
+
+impl Replay {
+    /// Reads the trips of all agents, which are not simulated, from the events file. Agents of all
+    /// partitions are simulated, so the whole population file is read. Within a cordon, agents
+    /// whose plans leave the network are not simulated either. Only runs which start on links of
+    /// partition `rank` and not before `start_time` are kept.
+    pub fn from_config(
+        config: &config::Replay,
+        population: &Path,
+        network: &Network,
+        rank: u32,
+        start_time: u32,
+    ) -> Self {
+        let simulated = simulated_persons(population, |person| {
+            !config.cordon
+                || person
+                    .plan
+                    .as_ref()
+                    .is_some_and(|plan| plan.is_within(network))
+        });
+        let reader = XmlEventsReader::new(&PathBuf::from(&config.events));
+        let events = reader.flat_map(|(time, events)| events.into_iter().map(move |e| (time, e)));
+        let trips = read_trips(events, |person| simulated.contains(&person));
+
+        let mut replay = Replay {
+            vehicle_type: vehicle_type(config.pce),
+            departures: TimeQueue::new(),
+        };
+        for trip in &trips {
+            for run in runs(trip, network) {
+                let (first_link, depart) = run[0];
+                if network.links[first_link as usize].partition == rank && depart >= start_time {
+                    let vehicle = replay.vehicle(trip, run);
+                    replay.departures.add_at(vehicle, depart);
+                }
+            }
+        }
+        info!(
+            "#{rank} replays {} of {} trips from {}.",
+            replay.departures.len(),
+            trips.len(),
+            config.events
+        );
+        replay
+    }
+
+    /// The type of all replayed vehicles. It must be known to the garage of each partition, as
+    /// replayed vehicles may cross partitions.
+    pub fn vehicle_type(&self) -> &VehicleType {
+        &self.vehicle_type
+    }
+
+    pub fn is_replayed(&self, vehicle: &Vehicle) -> bool {
+        vehicle.r#type == self.vehicle_type.id
+    }
+
+    /// Vehicles which enter the network at `now`.
+    pub fn departures(&mut self, now: u32) -> Vec<Vehicle> {
+        self.departures.pop(now)
+    }
+
+    pub fn len(&self) -> usize {
+        self.departures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.departures.is_empty()
+    }
+
+    /// A vehicle with a driver, whose plan consists of a single leg along the links of the run.
+    /// The driver keeps the id of the original driver, so that events of the replayed vehicle can
+    /// be related to the previous run.
+    fn vehicle(&self, trip: &Trip, run: &[(u64, u32)]) -> Vehicle {
+        let act_type = Id::<String>::create(REPLAY_VEH_TYPE).internal();
+        let route: Vec<u64> = run.iter().map(|(link, _)| *link).collect();
+        let (start, end) = (route[0], *route.last().unwrap());
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., act_type, start, None, None, None));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: trip.vehicle,
+                distance: 0.,
+                route,
+            },
+            self.vehicle_type.net_mode,
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(0., 0., act_type, end, None, None, None));
+        let mut driver = Person::new(trip.driver, plan);
+        driver.advance_plan();
+        Vehicle::new(
+            trip.vehicle,
+            self.vehicle_type.id,
+            self.vehicle_type.max_v,
+            self.vehicle_type.pce,
+            Some(driver),
+        )
+    }
+}
+
+/// Replayed vehicles drive along the links of their runs as fast as the links permit.
+fn vehicle_type(pce: f32) -> VehicleType {
+    VehicleType {
+        id: Id::<VehicleType>::create(REPLAY_VEH_TYPE).internal(),
+        length: 7.5,
+        width: 1.,
+        max_v: f32::MAX,
+        pce,
+        fef: 0.,
+        net_mode: Id::<String>::create("car").internal(),
+        lod: LevelOfDetail::Network as i32,
+        seats: 1,
+        standing_room: 0,
+        attributes: Default::default(),
+    }
+}
+
+/// Internal ids of the persons of the population file, which are simulated.
+fn simulated_persons(population: &Path, is_simulated: impl Fn(&Person) -> bool) -> IntSet<u64> {
+    let persons = RefCell::new(IntSet::default());
+    // the filter only collects the ids, so that the plans are dropped right away.
+    Population::from_file_filtered(population, &mut Garage::new(), |person| {
+        if is_simulated(person) {
+            persons.borrow_mut().insert(person.id);
+        }
+        false
+    });
+    persons.into_inner()
+}
+
+/// Reconstructs the trips of vehicles from departure, person enters and leaves vehicle, and link
+/// enter events. Passengers are ignored. Trips of persons for which `is_simulated` is true, and
+/// trips which don't enter any link, e.g. teleported legs, are skipped.
+fn read_trips(
+    events: impl Iterator<Item = (u32, Event)>,
+    is_simulated: impl Fn(u64) -> bool,
+) -> Vec<Trip> {
+    let mut departures: IntMap<u64, (u64, u32)> = IntMap::default();
+    let mut open_trips: IntMap<u64, Trip> = IntMap::default();
+    let mut trips = Vec::new();
+
+    for (time, event) in events {
+        match event.r#type {
+            Some(Type::Departure(e)) if !is_simulated(e.person) => {
+                departures.insert(e.person, (e.link, time));
+            }
+            Some(Type::PersonEntersVeh(e)) if !open_trips.contains_key(&e.vehicle) => {
+                if let Some(departure) = departures.remove(&e.person) {
+                    open_trips.insert(
+                        e.vehicle,
+                        Trip {
+                            driver: e.person,
+                            vehicle: e.vehicle,
+                            links: vec![departure],
+                        },
+                    );
+                }
+            }
+            Some(Type::LinkEnter(e)) => {
+                if let Some(trip) = open_trips.get_mut(&e.vehicle) {
+                    trip.links.push((e.link, time));
+                }
+            }
+            Some(Type::PersonLeavesVeh(e)) => {
+                if open_trips
+                    .get(&e.vehicle)
+                    .is_some_and(|trip| trip.driver == e.person)
+                {
+                    let trip = open_trips.remove(&e.vehicle).unwrap();
+                    if trip.links.len() > 1 {
+                        trips.push(trip);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    trips
+}
+
+/// Splits the links of a trip into runs of consecutive links of the network.
+fn runs<'t>(trip: &'t Trip, network: &Network) -> Vec<&'t [(u64, u32)]> {
+    let link = |id: u64| network.links.get(id as usize);
+    let connected = |from: u64, to: u64| match (link(from), link(to)) {
+        (Some(from), Some(to)) => from.to == to.from,
+        _ => false,
+    };
+    let links = &trip.links;
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < links.len() {
+        if link(links[start].0).is_none() {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < links.len() && connected(links[end - 1].0, links[end].0) {
+            end += 1;
+        }
+        runs.push(&links[start..end]);
+        start = end;
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::Id;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::replay::{read_trips, runs, vehicle_type, Replay, Trip};
+    use crate::simulation::time_queue::TimeQueue;
+    use crate::simulation::wire_types::events::Event;
+
+    fn trip_events(person: u64, vehicle: u64, links: &[u64], start: u32) -> Vec<(u32, Event)> {
+        let mode = Id::<String>::create("car").internal();
+        let mut events = vec![
+            (start, Event::new_departure(person, links[0], mode)),
+            (start, Event::new_person_enters_veh(person, vehicle)),
+        ];
+        for (i, link) in links.iter().enumerate().skip(1) {
+            let time = start + i as u32 * 10;
+            events.push((time, Event::new_link_leave(links[i - 1], vehicle)));
+            events.push((time, Event::new_link_enter(*link, vehicle)));
+        }
+        let end = start + links.len() as u32 * 10;
+        events.push((end, Event::new_person_leaves_veh(person, vehicle)));
+        events
+    }
+
+    #[test]
+    fn read_trips_of_background_agents() {
+        let mut events = trip_events(1, 11, &[0, 1, 2], 100);
+        events.extend(trip_events(2, 12, &[2, 3], 105));
+        // a passenger enters the vehicle of person 1 without being its driver.
+        events.insert(2, (100, Event::new_person_enters_veh(3, 11)));
+        events.sort_by_key(|(time, _)| *time);
+
+        let trips = read_trips(events.into_iter(), |person| person == 2);
+        assert_eq!(
+            vec![Trip {
+                driver: 1,
+                vehicle: 11,
+                links: vec![(0, 100), (1, 110), (2, 120)],
+            }],
+            trips
+        );
+    }
+
+    #[test]
+    fn split_trips_at_missing_links() {
+        let mut network = Network::new();
+        let nodes: Vec<Node> = (0..4)
+            .map(|i| Node::new(Id::create(&format!("replay-n{i}")), i as f64, 0., 0, 1))
+            .collect();
+        for i in 0..3 {
+            network.add_link(Link::new_with_default(
+                Id::create(&format!("replay-l{i}")),
+                &nodes[i],
+                &nodes[i + 1],
+            ));
+        }
+        for node in nodes {
+            network.add_node(node);
+        }
+        let l = |i: usize| network.links[i].id.internal();
+        // links of the previous run, which are not part of the network, have larger ids.
+        let unknown = Id::<Link>::create("replay-x").internal();
+
+        let trip = Trip {
+            driver: 1,
+            vehicle: 1,
+            links: vec![(l(0), 0), (unknown, 10), (l(1), 20), (l(2), 30)],
+        };
+        assert_eq!(
+            vec![&trip.links[..1], &trip.links[2..]],
+            runs(&trip, &network)
+        );
+
+        // a route, which jumps between unconnected links, is split as well.
+        let trip = Trip {
+            driver: 1,
+            vehicle: 1,
+            links: vec![(l(0), 0), (l(2), 10)],
+        };
+        assert_eq!(2, runs(&trip, &network).len());
+
+        let replay = Replay {
+            vehicle_type: vehicle_type(0.5),
+            departures: TimeQueue::new(),
+        };
+        let vehicle = replay.vehicle(&trip, &trip.links[..1]);
+        assert!(replay.is_replayed(&vehicle));
+        assert_eq!(0.5, vehicle.pce());
+        assert_eq!(Some(l(0)), vehicle.curr_link_id());
+        assert_eq!(1, vehicle.driver().id);
+    }
+}
@@ -0,0 +1,539 @@
+use std::path::{Path, PathBuf};
+
+use crate::simulation::config;
+use crate::simulation::config::{Config, PartitionMethod, RoutingMode};
+use crate::simulation::id;
+use crate::simulation::id::{Id, SharedIdStore};
+use crate::simulation::io::output_files::get_numbered_output_filename;
+use crate::simulation::network::global_network::Network;
+use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::population::activity_links;
+use crate::simulation::population::interactions;
+use crate::simulation::population::population::Population;
+use crate::simulation::population::route_repair;
+use crate::simulation::population::validation;
+use crate::simulation::population::validation::PlanProblem;
+use crate::simulation::vehicles::defaults;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::population::Person;
+use crate::simulation::wire_types::vehicles::VehicleType;
+
+/// Network, vehicles, population and ids of a simulation run. The functions which load a scenario
+/// make sure the ids are loaded before anything which refers to them, and that vehicles are loaded
+/// before the population. Ids are looked up in the store of the current thread. The scenario holds
+/// on to that store, so that the scenario can be used on other threads, too. See
+/// [Scenario::use_ids].
+#[derive(Debug)]
+pub struct Scenario {
+    pub network: Network,
+    pub garage: Garage,
+    pub population: Population,
+    pub ids: SharedIdStore,
+}
+
+impl Scenario {
+    /// Loads a whole scenario. Binary files refer to the ids of an id store, which must be passed
+    /// in that case. Xml files create their ids while they are read. Activities without a link get
+    /// the closest link. See [activity_links::resolve_activity_links]. If no vehicles file is passed,
+    /// default vehicles are created for the modes of the population. See
+    /// [defaults::add_default_vehicles].
+    pub fn load(
+        network: &Path,
+        vehicles: Option<&Path>,
+        population: &Path,
+        ids: Option<&Path>,
+    ) -> Self {
+        if let Some(ids) = ids {
+            id::load_from_file(ids);
+        }
+        let network = Network::from_file_as_is(network);
+        let mut garage = if let Some(vehicles) = vehicles {
+            Garage::from_file(vehicles)
+        } else {
+            Garage::new()
+        };
+        let mut population = Population::from_file(population, &mut garage);
+        activity_links::resolve_activity_links(&mut population, &network);
+        if vehicles.is_none() {
+            defaults::add_default_vehicles(&mut garage, &population);
+        }
+        Scenario {
+            network,
+            garage,
+            population,
+            ids: id::shared_store(),
+        }
+    }
+
+    /// Loads the scenario of one partition, after the input for all partitions was prepared by
+    /// [crate::simulation::controller::partition_input]. The network is loaded completely, the
+    /// population only contains the agents which start on the partition. The ids are loaded from
+    /// the configured file, unless `ids_loaded` is true, because the current thread uses a store
+    /// which holds them already. This is the case, if partitions share the store of the partition
+    /// which prepared the input.
+    pub fn load_partition(config: &Config, rank: u32, ids_loaded: bool) -> Self {
+        if !ids_loaded {
+            load_ids(config);
+        }
+
+        let output_path = PathBuf::from(config.output().output_dir);
+        let network = Network::from_file_as_is(&get_numbered_output_filename(
+            &output_path,
+            &PathBuf::from(config.proto_files().network),
+            config.partitioning().num_parts,
+        ));
+        let mut garage = Garage::from_file(&PathBuf::from(config.proto_files().vehicles));
+        let population_file = PathBuf::from(config.proto_files().population);
+        let population = if config.replay().is_some_and(|replay| replay.cordon) {
+            // trips which cross the cordon are replayed from the events of the full run.
+            Population::from_file_within_network(&population_file, &network, &mut garage, rank)
+        } else {
+            Population::from_file_filtered_part(&population_file, &network, &mut garage, rank)
+        };
+        Scenario {
+            network,
+            garage,
+            population,
+            ids: id::shared_store(),
+        }
+    }
+
+    /// Inserts interaction activities and access and egress legs into plans without them, and
+    /// returns the number of changed plans. See [interactions::insert_interactions].
+    pub fn insert_interactions(&mut self, teleportation: &config::Teleportation) -> usize {
+        interactions::insert_interactions(
+            &mut self.population,
+            &self.network,
+            &self.garage,
+            teleportation,
+        )
+    }
+
+    /// Re-routes legs with broken routes and returns the number of repaired plans. See
+    /// [route_repair::repair_routes].
+    pub fn repair_routes(&mut self) -> usize {
+        route_repair::repair_routes(&mut self.population, &self.network, &self.garage)
+    }
+
+    /// Sets the distance of network routes which don't have one and returns the number of updated
+    /// legs. See [route_repair::compute_route_distances].
+    pub fn compute_route_distances(&mut self) -> usize {
+        route_repair::compute_route_distances(&mut self.population, &self.network, &self.garage)
+    }
+
+    /// Checks the plans of the population against the network and the vehicles. See
+    /// [validation::validate].
+    pub fn validate(&self, routing_mode: RoutingMode) -> Vec<PlanProblem> {
+        validation::validate(&self.population, &self.network, &self.garage, routing_mode)
+    }
+
+    /// Makes the current thread look up ids in the store of the scenario, e.g. if the scenario was
+    /// loaded on another thread.
+    pub fn use_ids(&self) {
+        id::use_shared_store(self.ids.clone());
+    }
+
+    /// The part of the network which is simulated by the partition with the given rank.
+    pub fn network_partition(&self, rank: u32, config: config::Simulation) -> SimNetworkPartition {
+        SimNetworkPartition::from_network(&self.network, rank, config)
+    }
+}
+
+/// Config modules, which look up persons, vehicles, links or nodes by their external ids.
+fn compact_ids_conflicts(config: &Config) -> Vec<&'static str> {
+    [
+        ("agent_tracing", config.agent_tracing().is_some()),
+        ("evacuation", config.evacuation().is_some()),
+        ("freight", config.freight().is_some()),
+        ("incidents", config.incidents().is_some()),
+        (
+            "park_and_ride",
+            !config.park_and_ride().facilities.is_empty(),
+        ),
+        ("replay", config.replay().is_some()),
+        ("speed_factors", config.speed_factors().is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, configured)| *configured)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Loads the configured id store into the store of the current thread.
+pub fn load_ids(config: &Config) {
+    let ids_path = PathBuf::from(config.proto_files().ids);
+    if config.proto_files().compact_ids {
+        let conflicts = compact_ids_conflicts(config);
+        assert!(
+            conflicts.is_empty(),
+            "Compact ids can't be used together with the config modules {conflicts:?}. They refer to persons, vehicles, links or nodes by their external ids, which are not loaded in compact mode."
+        );
+        id::load_from_file_compact(&ids_path);
+    } else {
+        id::load_from_file(&ids_path);
+    }
+}
+
+/// Sets up a scenario in code, e.g. for tests or synthetic experiments. Parts can either be passed
+/// directly or loaded from files. The parts are put together in [ScenarioBuilder::build] in the
+/// order the ids require, regardless of the order in which the builder methods were called:
+/// network first, then vehicle types, then the population.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use rust_q_sim::simulation::network::global_network::Network;
+/// # use rust_q_sim::simulation::scenario::ScenarioBuilder;
+/// let scenario = ScenarioBuilder::new()
+///     .network(Network::new())
+///     .vehicles_from(Path::new("vehicles.xml"))
+///     .population_from(Path::new("plans.xml"))
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct ScenarioBuilder {
+    network: Option<Network>,
+    network_file: Option<PathBuf>,
+    partitioning: Option<(u32, PartitionMethod)>,
+    vehicles_file: Option<PathBuf>,
+    vehicle_types: Vec<VehicleType>,
+    default_vehicles: bool,
+    population_file: Option<PathBuf>,
+    persons: Vec<Person>,
+}
+
+impl ScenarioBuilder {
+    pub fn new() -> Self {
+        ScenarioBuilder::default()
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn network_from(mut self, path: &Path) -> Self {
+        self.network_file = Some(path.to_path_buf());
+        self
+    }
+
+    /// Splits the network into `num_parts` partitions. Without this, the partitions of the nodes
+    /// and links are kept as they are.
+    pub fn partitions(mut self, num_parts: u32, method: PartitionMethod) -> Self {
+        self.partitioning = Some((num_parts, method));
+        self
+    }
+
+    pub fn vehicles_from(mut self, path: &Path) -> Self {
+        self.vehicles_file = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn vehicle_type(mut self, veh_type: VehicleType) -> Self {
+        self.vehicle_types.push(veh_type);
+        self
+    }
+
+    /// Creates a default vehicle type for each mode of the population, which has no vehicle type
+    /// yet. This allows to set up scenarios without a vehicles file.
+    pub fn default_vehicles(mut self) -> Self {
+        self.default_vehicles = true;
+        self
+    }
+
+    pub fn population_from(mut self, path: &Path) -> Self {
+        self.population_file = Some(path.to_path_buf());
+        self
+    }
+
+    /// Adds a person. Like persons loaded from xml, the person receives one vehicle of each vehicle
+    /// type of the scenario with the id `<person id>_<vehicle type id>`.
+    pub fn person(mut self, person: Person) -> Self {
+        self.persons.push(person);
+        self
+    }
+
+    pub fn population(mut self, population: Population) -> Self {
+        self.persons.extend(population.persons.into_values());
+        self
+    }
+
+    pub fn build(self) -> Scenario {
+        let mut network = match (self.network, self.network_file) {
+            (Some(network), None) => network,
+            (None, Some(path)) => Network::from_file_as_is(&path),
+            (None, None) => Network::new(),
+            (Some(_), Some(_)) => panic!("The network was set and should be loaded from a file."),
+        };
+        if let Some((num_parts, method)) = self.partitioning {
+            network.partition(num_parts, method);
+        }
+
+        let mut garage = if let Some(path) = self.vehicles_file {
+            Garage::from_file(&path)
+        } else {
+            Garage::new()
+        };
+        for veh_type in self.vehicle_types {
+            garage.add_veh_type(veh_type);
+        }
+
+        let mut population = if let Some(path) = self.population_file {
+            Population::from_file(&path, &mut garage)
+        } else {
+            Population::new()
+        };
+        let type_ids: Vec<Id<VehicleType>> = garage.vehicle_types.keys().cloned().collect();
+        for person in self.persons {
+            let person_id: Id<Person> = Id::get(person.id());
+            for type_id in &type_ids {
+                garage.add_veh_id(&person_id, type_id);
+            }
+            population.persons.insert(person_id, person);
+        }
+        activity_links::resolve_activity_links(&mut population, &network);
+        if self.default_vehicles {
+            defaults::add_default_vehicles(&mut garage, &population);
+        }
+
+        Scenario {
+            network,
+            garage,
+            population,
+            ids: id::shared_store(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    use nohash_hasher::IntSet;
+
+    use crate::simulation::config::Config;
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::communication::communicators::ChannelSimCommunicator;
+    use crate::simulation::messaging::communication::message_broker::NetMessageBroker;
+    use crate::simulation::messaging::events::{EventsPublisher, EventsSubscriber};
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::replanning::replanner::DummyReplanner;
+    use crate::simulation::scenario::{load_ids, ScenarioBuilder};
+    use crate::simulation::simulation::Simulation;
+    use crate::simulation::wire_types::events::event::Type;
+    use crate::simulation::wire_types::events::Event;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+    struct CollectingSubscriber {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventsSubscriber for CollectingSubscriber {
+        fn receive_event(&mut self, _time: u32, event: &Event) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn network() -> Network {
+        let mut network = Network::new();
+        let modes: IntSet<Id<String>> = [Id::create("car")].into_iter().collect();
+        for (i, x) in [0., 100., 200.].into_iter().enumerate() {
+            network.add_node(Node::new(Id::create(&format!("sb-n{i}")), x, 0., 0, 1));
+        }
+        for i in 0..2 {
+            network.add_link(Link::new(
+                Id::create(&format!("sb-l{i}")),
+                Id::get_from_ext(&format!("sb-n{i}")),
+                Id::get_from_ext(&format!("sb-n{}", i + 1)),
+                100.,
+                3600.,
+                10.,
+                1.,
+                modes.clone(),
+                0,
+            ));
+        }
+        network
+    }
+
+    fn person() -> Person {
+        let person_id: Id<Person> = Id::create("sb-p");
+        let veh_id = Id::<Vehicle>::create("sb-p_sb-car");
+        let home: Id<String> = Id::create("home");
+        let work: Id<String> = Id::create("work");
+        let link_0 = Id::<Link>::get_from_ext("sb-l0").internal();
+        let link_1 = Id::<Link>::get_from_ext("sb-l1").internal();
+
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(
+            0.,
+            0.,
+            home.internal(),
+            link_0,
+            None,
+            Some(10),
+            None,
+        ));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: veh_id.internal(),
+                distance: 100.,
+                route: vec![link_0, link_1],
+            },
+            Id::<String>::get_from_ext("car").internal(),
+            0,
+            Some(10),
+        ));
+        plan.add_act(Activity::new(
+            200.,
+            0.,
+            work.internal(),
+            link_1,
+            None,
+            None,
+            None,
+        ));
+        Person::new(person_id.internal(), plan)
+    }
+
+    fn car() -> VehicleType {
+        VehicleType {
+            id: Id::<VehicleType>::create("sb-car").internal(),
+            length: 7.5,
+            width: 1.,
+            max_v: 10.,
+            pce: 1.,
+            fef: 1.,
+            net_mode: Id::<String>::create("car").internal(),
+            lod: LevelOfDetail::Network as i32,
+            seats: 1,
+            standing_room: 0,
+            attributes: Default::default(),
+        }
+    }
+
+    #[test]
+    fn build_creates_vehicles() {
+        // the person refers to links, so the network has to be created first. The builder methods
+        // can still be called in any order.
+        let network = network();
+        let scenario = ScenarioBuilder::new()
+            .person(person())
+            .vehicle_type(car())
+            .network(network)
+            .build();
+
+        assert_eq!(2, scenario.network.links.len());
+        assert_eq!(1, scenario.population.persons.len());
+        let veh_id = scenario
+            .garage
+            .veh_id(&Id::get_from_ext("sb-p"), &Id::get_from_ext("sb-car"));
+        assert_eq!("sb-p_sb-car", veh_id.external());
+    }
+
+    #[test]
+    fn build_with_default_vehicles() {
+        let scenario = ScenarioBuilder::new()
+            .network(network())
+            .person(person())
+            .default_vehicles()
+            .build();
+
+        let car = scenario
+            .garage
+            .vehicle_types
+            .get(&Id::get_from_ext("car"))
+            .unwrap();
+        assert_eq!(LevelOfDetail::Network, car.lod());
+        let veh_id = scenario
+            .garage
+            .veh_id(&Id::get_from_ext("sb-p"), &Id::get_from_ext("car"));
+        assert!(scenario.garage.vehicles.contains_key(&veh_id));
+    }
+
+    #[test]
+    fn use_ids_on_other_thread() {
+        let scenario = ScenarioBuilder::new()
+            .network(network())
+            .vehicle_type(car())
+            .person(person())
+            .build();
+
+        let link = std::thread::spawn(move || {
+            scenario.use_ids();
+            let link: Id<Link> = Id::get_from_ext("sb-l1");
+            link.internal()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(Id::<Link>::get_from_ext("sb-l1").internal(), link);
+    }
+
+    #[test]
+    fn run_built_scenario() {
+        let scenario = ScenarioBuilder::new()
+            .network(network())
+            .vehicle_type(car())
+            .person(person())
+            .build();
+        let mut config = Config::new();
+        let mut sim_config = config.simulation();
+        sim_config.end_time = 100;
+        config.set_simulation(sim_config);
+
+        let partition = scenario.network_partition(0, config.simulation());
+        let comm = ChannelSimCommunicator::create_n_2_n(1).pop().unwrap();
+        let broker = NetMessageBroker::new(Rc::new(comm), &scenario.network, &partition);
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let mut events = EventsPublisher::new();
+        events.add_subscriber(Box::new(CollectingSubscriber {
+            events: collected.clone(),
+        }));
+
+        let mut sim = Simulation::new(
+            &config,
+            partition,
+            scenario.garage,
+            scenario.population,
+            broker,
+            events,
+            Box::new(DummyReplanner {}),
+        );
+        assert!(sim.run());
+
+        let arrivals = collected
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| matches!(e.r#type, Some(Type::Arrival(_))))
+            .count();
+        assert_eq!(1, arrivals);
+    }
+
+    #[test]
+    #[should_panic(expected = "[\"agent_tracing\"]")]
+    fn reject_compact_ids_with_agent_tracing() {
+        let yaml = r#"
+        modules:
+          protofiles:
+            type: ProtoFiles
+            network: network.binpb
+            population: population.binpb
+            vehicles: vehicles.binpb
+            ids: ids.binpb
+            compact_ids: true
+          agent_tracing:
+            type: AgentTracing
+            person_ids: [person]
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        load_ids(&config);
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::warn;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a handler for SIGINT and SIGTERM, which only records that a shutdown was requested.
+/// The simulation checks for this flag and stops at the end of a time step, so that outputs are
+/// written completely. A second signal terminates the process immediately.
+///
+/// This must be called at most once per process.
+pub fn install_signal_handler() {
+    ctrlc::set_handler(|| {
+        if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+            warn!("Received second shutdown signal. Exiting immediately.");
+            std::process::exit(130);
+        }
+        warn!("Received shutdown signal. Finishing the current time step and writing outputs. Send the signal again to exit immediately.");
+    })
+    .expect("Failed to install signal handler");
+}
+
+/// Whether a shutdown was requested for this process.
+pub fn is_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Requests a graceful shutdown, as if a signal was received.
+pub fn request() {
+    if !SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+        warn!("Shutdown was requested. Finishing the current time step and writing outputs.");
+    }
+}
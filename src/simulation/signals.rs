@@ -0,0 +1,366 @@
+//! Traffic signals at nodes. A signal system cycles through the phases of its node. During a
+//! phase, the in-links of the phase show green, while the other signalized in-links of the node
+//! show red, so that their vehicles wait at the end of the link. In-links, which are not part of
+//! any phase, are not signalized.
+//!
+//! Phases have fixed durations by default. A [SignalController] is called every
+//! `control_interval` seconds with detector data of the signalized in-links, i.e. their queue
+//! lengths and flows from [SimNetworkPartition::queue_length] and
+//! [SimNetworkPartition::link_outflow], and may adjust the durations of the phases, e.g. for
+//! actuated or adaptive control.
+
+use std::sync::Arc;
+
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::network::global_network::{Link, Node};
+use crate::simulation::network::sim_network::SimNetworkPartition;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalPhase {
+    /// Internal ids of the in-links, which show green during the phase.
+    pub links: Vec<u64>,
+    /// Duration of the phase in seconds. Changes apply, when the phase starts the next time.
+    pub duration: u32,
+}
+
+#[derive(Debug)]
+pub struct SignalSystem {
+    pub node: u64,
+    pub phases: Vec<SignalPhase>,
+    current: usize,
+    /// Time at which the current phase ends. `None`, before the first phase has started.
+    phase_end: Option<u32>,
+}
+
+/// Measurements of a signalized in-link at the time the controller is called.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detector {
+    pub link_id: u64,
+    /// Vehicles, which have reached the end of the link and wait to leave it.
+    pub queue_length: usize,
+    /// Vehicles on the link.
+    pub vehicles: usize,
+    /// Vehicles, which have left the link since the controller was called the last time.
+    pub flow: u64,
+}
+
+/// Adjusts the phases of signal systems based on detector data.
+pub trait SignalController {
+    /// Called every `control_interval` seconds for each signal system of the partition, with one
+    /// detector per signalized in-link of the node.
+    fn control(&mut self, now: u32, system: &mut SignalSystem, detectors: &[Detector]);
+}
+
+/// Creates the signal controller of a partition from the rank of the partition.
+pub type SignalControllerFactory = Arc<dyn Fn(u32) -> Box<dyn SignalController> + Send + Sync>;
+
+pub struct Signals {
+    systems: Vec<SignalSystem>,
+    control_interval: u32,
+    controller: Option<Box<dyn SignalController>>,
+    /// Outflow of the signalized links, when the controller was called the last time.
+    last_outflow: IntMap<u64, u64>,
+}
+
+impl SignalSystem {
+    pub fn new(node: u64, phases: Vec<SignalPhase>) -> Self {
+        assert!(
+            !phases.is_empty(),
+            "Signal system at node {node} has no phases."
+        );
+        SignalSystem {
+            node,
+            phases,
+            current: 0,
+            phase_end: None,
+        }
+    }
+
+    /// Index of the phase, which currently shows green.
+    pub fn current_phase(&self) -> usize {
+        self.current
+    }
+
+    /// Signalized in-links of the node, in the order of their first appearance in the phases.
+    pub fn links(&self) -> Vec<u64> {
+        let mut seen = IntSet::default();
+        self.phases
+            .iter()
+            .flat_map(|phase| phase.links.iter().copied())
+            .filter(|link| seen.insert(*link))
+            .collect()
+    }
+
+    /// Starts the next phase, if the current one has ended, and switches the signals of the
+    /// in-links accordingly.
+    fn switch(&mut self, network: &mut SimNetworkPartition, now: u32) {
+        match self.phase_end {
+            Some(end) if now < end => return,
+            Some(_) => self.current = (self.current + 1) % self.phases.len(),
+            None => {}
+        }
+        // phases last at least one second, so that controllers can't stall the cycle
+        self.phase_end = Some(now + self.phases[self.current].duration.max(1));
+
+        let green = &self.phases[self.current].links;
+        for link in self.links() {
+            network.set_signal(link, !green.contains(&link));
+        }
+    }
+}
+
+impl Signals {
+    /// Creates the signal systems of the nodes, which are part of this partition.
+    pub fn from_config(config: &config::Signals, network: &SimNetworkPartition) -> Self {
+        let systems = config
+            .systems
+            .iter()
+            .map(|system| (Id::<Node>::get_from_ext(&system.node).internal(), system))
+            .filter(|(node, _)| network.nodes.contains_key(node))
+            .map(|(node, system)| {
+                let in_links = &network.nodes.get(&node).unwrap().in_links;
+                let phases = system
+                    .phases
+                    .iter()
+                    .map(|phase| SignalPhase {
+                        links: phase
+                            .links
+                            .iter()
+                            .map(|link| {
+                                let id = Id::<Link>::get_from_ext(link).internal();
+                                assert!(
+                                    in_links.contains(&id),
+                                    "Signalized link {link} is not an in-link of node {}.",
+                                    system.node
+                                );
+                                id
+                            })
+                            .collect(),
+                        duration: phase.duration,
+                    })
+                    .collect();
+                SignalSystem::new(node, phases)
+            })
+            .collect();
+
+        Signals {
+            systems,
+            control_interval: config.control_interval.max(1),
+            controller: None,
+            last_outflow: IntMap::default(),
+        }
+    }
+
+    pub fn set_controller(&mut self, controller: Box<dyn SignalController>) {
+        self.controller = Some(controller);
+    }
+
+    /// Calls the controller, if `now` is a multiple of the control interval, and switches the
+    /// phases, which have ended. This is called before the nodes are moved in the time step.
+    pub fn update(&mut self, network: &mut SimNetworkPartition, now: u32) {
+        if let Some(controller) = self.controller.as_mut() {
+            if now % self.control_interval == 0 {
+                for system in &mut self.systems {
+                    let detectors: Vec<Detector> = system
+                        .links()
+                        .into_iter()
+                        .map(|link_id| {
+                            let outflow = network.link_outflow(link_id).unwrap();
+                            let last = self.last_outflow.insert(link_id, outflow).unwrap_or(0);
+                            Detector {
+                                link_id,
+                                queue_length: network.queue_length(link_id, now).unwrap(),
+                                vehicles: network.link_occupancy(link_id).unwrap().vehicles,
+                                flow: outflow - last,
+                            }
+                        })
+                        .collect();
+                    controller.control(now, system, &detectors);
+                }
+            }
+        }
+
+        for system in &mut self.systems {
+            system.switch(network, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::simulation::config;
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsPublisher;
+    use crate::simulation::network::global_network::{Link, Network, Node};
+    use crate::simulation::network::sim_network::SimNetworkPartition;
+    use crate::simulation::signals::{
+        Detector, SignalController, SignalPhase, SignalSystem, Signals,
+    };
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::test_utils;
+
+    /// Records the detectors and extends the green phase of link-b, while vehicles are on it.
+    struct Recording {
+        detectors: Rc<RefCell<Vec<(u32, Vec<Detector>)>>>,
+    }
+
+    impl SignalController for Recording {
+        fn control(&mut self, now: u32, system: &mut SignalSystem, detectors: &[Detector]) {
+            self.detectors.borrow_mut().push((now, detectors.to_vec()));
+            if detectors[1].vehicles > 0 {
+                system.phases[1].duration = 40;
+            }
+        }
+    }
+
+    fn signals_config(control_interval: u32) -> config::Signals {
+        config::Signals {
+            control_interval,
+            systems: vec![config::SignalSystem {
+                node: String::from("center"),
+                phases: vec![
+                    config::SignalPhase {
+                        links: vec![String::from("link-a")],
+                        duration: 20,
+                    },
+                    config::SignalPhase {
+                        links: vec![String::from("link-b")],
+                        duration: 10,
+                    },
+                ],
+            }],
+        }
+    }
+
+    /// Two links from node a and b into the center node, followed by a link to node c.
+    fn network() -> SimNetworkPartition {
+        let mut global_net = Network::new();
+        let node_a = Node::new(Id::create("a"), -100., 0., 0, 1);
+        let node_b = Node::new(Id::create("b"), 0., 100., 0, 1);
+        let center = Node::new(Id::create("center"), 0., 0., 0, 1);
+        let node_c = Node::new(Id::create("c"), 100., 0., 0, 1);
+        let links: Vec<Link> = [
+            ("link-a", &node_a, &center),
+            ("link-b", &node_b, &center),
+            ("link-c", &center, &node_c),
+        ]
+        .into_iter()
+        .map(|(id, from, to)| {
+            let mut link = Link::new_with_default(Id::create(id), from, to);
+            link.capacity = 3600.;
+            link.freespeed = 10.;
+            link
+        })
+        .collect();
+        for node in [node_a, node_b, center, node_c] {
+            global_net.add_node(node);
+        }
+        for link in links {
+            global_net.add_link(link);
+        }
+        SimNetworkPartition::from_network(&global_net, 0, test_utils::config())
+    }
+
+    fn link(id: &str) -> u64 {
+        Id::<Link>::get_from_ext(id).internal()
+    }
+
+    #[test]
+    fn phases_cycle() {
+        let mut network = network();
+        let mut signals = Signals::from_config(&signals_config(60), &network);
+        assert_eq!(
+            vec![
+                SignalPhase {
+                    links: vec![link("link-a")],
+                    duration: 20
+                },
+                SignalPhase {
+                    links: vec![link("link-b")],
+                    duration: 10
+                },
+            ],
+            signals.systems[0].phases
+        );
+
+        let mut phases = Vec::new();
+        for now in 0..65 {
+            signals.update(&mut network, now);
+            phases.push(signals.systems[0].current_phase());
+            let a_is_red = network.links.get(&link("link-a")).unwrap().is_red();
+            let b_is_red = network.links.get(&link("link-b")).unwrap().is_red();
+            assert_eq!(
+                a_is_red, !b_is_red,
+                "Both links show the same signal at {now}"
+            );
+        }
+        // link-a shows green for 20s, then link-b for 10s, and so on
+        assert_eq!(0, phases[0]);
+        assert_eq!(0, phases[19]);
+        assert_eq!(1, phases[20]);
+        assert_eq!(1, phases[29]);
+        assert_eq!(0, phases[30]);
+        assert_eq!(1, phases[50]);
+        assert_eq!(0, phases[60]);
+        assert!(!network.links.get(&link("link-a")).unwrap().is_red());
+    }
+
+    #[test]
+    fn controller_receives_detectors_and_adjusts_phases() {
+        let mut publisher = EventsPublisher::new();
+        let mut network = network();
+        let mut signals = Signals::from_config(&signals_config(30), &network);
+        let detectors = Rc::new(RefCell::new(Vec::new()));
+        signals.set_controller(Box::new(Recording {
+            detectors: detectors.clone(),
+        }));
+
+        // vehicles on link-b reach the center node after 10s, but link-b shows red until 20s. Its
+        // green phase is extended to 40s, as vehicles are on it at the first call.
+        for i in 0..3 {
+            let agent = test_utils::create_agent(i, vec![link("link-b"), link("link-c")]);
+            network.send_veh_en_route(Vehicle::new(i, 0, 10., 1., Some(agent)), None, 0);
+        }
+        for now in 0..31 {
+            signals.update(&mut network, now);
+            let _ = network.move_nodes(&mut publisher, now);
+            let _ = network.move_links(now);
+        }
+
+        let detectors = detectors.borrow();
+        assert_eq!(2, detectors.len());
+        let (time, at_start) = &detectors[0];
+        assert_eq!(0, *time);
+        assert_eq!(link("link-a"), at_start[0].link_id);
+        assert_eq!(link("link-b"), at_start[1].link_id);
+        assert_eq!(3, at_start[1].vehicles);
+        assert_eq!(0, at_start[1].queue_length);
+
+        // link-b released one vehicle per second, once its green phase started at 20s
+        let (time, after_green) = &detectors[1];
+        assert_eq!(30, *time);
+        assert_eq!(0, after_green[1].vehicles);
+        assert_eq!(0, after_green[1].queue_length);
+        assert_eq!(3, after_green[1].flow);
+        assert_eq!(0, after_green[0].flow);
+
+        assert_eq!(40, signals.systems[0].phases[1].duration);
+        assert_eq!(1, signals.systems[0].current_phase());
+        assert_eq!(Some(60), signals.systems[0].phase_end);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not an in-link of node")]
+    fn signalized_link_must_end_at_node() {
+        let network = network();
+        let mut config = signals_config(60);
+        config.systems[0].phases[0].links = vec![String::from("link-c")];
+        let _ = Signals::from_config(&config, &network);
+    }
+}
@@ -1,34 +1,91 @@
-use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::fmt::{Arguments, Debug};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
-use tracing::{info, instrument};
+use nohash_hasher::IntMap;
+use tracing::{debug, error, info, instrument};
 
-use crate::simulation::config::Config;
+use crate::simulation::agent_tracing::AgentTracer;
+use crate::simulation::config::{ActivityDurationInterpretation, Config, Profiling};
+use crate::simulation::control;
+use crate::simulation::crash_dump;
+use crate::simulation::engines::{Engine, EngineContext};
+use crate::simulation::evacuation::Evacuation;
+use crate::simulation::fares::FareSystem;
 use crate::simulation::id::Id;
 use crate::simulation::messaging::communication::communicators::SimCommunicator;
 use crate::simulation::messaging::communication::message_broker::NetMessageBroker;
 use crate::simulation::messaging::events::EventsPublisher;
+use crate::simulation::migration::MigratingAgent;
+use crate::simulation::mobsim_listener::{MobsimListener, SimState};
+use crate::simulation::network::global_network::Link;
+use crate::simulation::network::incidents::Incidents;
 use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::network::speed_factors::{SpeedFactors, SpeedVariation};
+use crate::simulation::opening_hours::OpeningHours;
 use crate::simulation::population::population::Population;
+use crate::simulation::profiling::memory;
+use crate::simulation::profiling::memory::{MemoryStats, MemoryStatsWriter};
 use crate::simulation::replanning::replanner::Replanner;
+use crate::simulation::replay::Replay;
+use crate::simulation::shutdown;
+use crate::simulation::signals::{SignalController, Signals};
+use crate::simulation::stuck_agents::{StuckAgent, StuckAgents};
+use crate::simulation::telemetry::Telemetry;
+use crate::simulation::teleportation::TeleportationEngine;
 use crate::simulation::time_queue::TimeQueue;
-use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::vehicles::garage::{Garage, VehicleAssignment};
 use crate::simulation::wire_types::events::Event;
 use crate::simulation::wire_types::messages::Vehicle;
 use crate::simulation::wire_types::population::Person;
-use crate::simulation::wire_types::vehicles::LevelOfDetail;
+use crate::simulation::wire_types::vehicles::{Household, LevelOfDetail, VehicleType};
+
+/// Number of time steps after which all partitions check whether a shutdown or a pause was
+/// requested.
+const SHUTDOWN_CHECK_INTERVAL: u32 = 60;
+/// Time between checks whether a paused simulation was resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
 
 pub struct Simulation<C>
 where
     C: SimCommunicator,
 {
     activity_q: TimeQueue<Person>,
-    teleportation_q: TimeQueue<Vehicle>,
+    teleportation: TeleportationEngine,
     network: SimNetworkPartition,
     garage: Garage,
     net_message_broker: NetMessageBroker<C>,
     events: EventsPublisher,
     replanner: Box<dyn Replanner>,
+    household_fallback: Option<Id<VehicleType>>,
+    /// Passengers waiting for their drivers, together with the time they started waiting.
+    waiting_passengers: IntMap<Id<Person>, Vec<(u32, Person)>>,
+    max_passenger_wait_time: u32,
+    park_and_ride_act: Option<Id<String>>,
+    mode_fallbacks: Vec<Id<VehicleType>>,
+    trace_agents: bool,
+    memory_stats: Option<MemoryStatsWriter>,
+    engines: Vec<Box<dyn Engine>>,
+    /// Index of the engine which handles legs of a mode.
+    engine_modes: IntMap<Id<String>, usize>,
+    listeners: Vec<Box<dyn MobsimListener>>,
+    fares: Option<FareSystem>,
+    evacuation: Option<Evacuation>,
+    replay: Option<Replay>,
+    incidents: Option<Incidents>,
+    speed_factors: Option<SpeedFactors>,
+    activity_durations: ActivityDurationInterpretation,
+    opening_hours: Option<OpeningHours>,
+    stuck_agents: Option<StuckAgents>,
+    /// Number of agents, which were removed from the simulation before finishing their plans.
+    aborted_agents: usize,
+    /// Interval of the consistency check, and the number of agents of all partitions at the start.
+    consistency_check: Option<(u32, u64)>,
+    telemetry: Option<Telemetry>,
+    signals: Option<Signals>,
+    early_receive: bool,
     start_time: u32,
     end_time: u32,
 }
@@ -38,39 +95,266 @@ where
     C: SimCommunicator + 'static,
 {
     pub fn new(
-        config: Config,
-        network: SimNetworkPartition,
-        garage: Garage,
+        config: &Config,
+        mut network: SimNetworkPartition,
+        mut garage: Garage,
         mut population: Population,
         net_message_broker: NetMessageBroker<C>,
-        events: EventsPublisher,
+        mut events: EventsPublisher,
         replanner: Box<dyn Replanner>,
     ) -> Self {
         let mut activity_q = TimeQueue::new();
+        let activity_durations = config.simulation().activity_duration_interpretation;
+
+        // household vehicles start on the partition of the household members
+        garage.park_household_vehicles(population.persons.keys());
 
         // take Persons and copy them into queues. This way we can keep population around to translate
         // ids for events processing...
         let agents = std::mem::take(&mut population.persons);
 
         for agent in agents.into_values() {
-            activity_q.add(agent, config.simulation().start_time);
+            let end_time = agent.wakeup_time(config.simulation().start_time, activity_durations);
+            activity_q.add_at(agent, end_time);
+        }
+
+        // only look up the fallback type if there are households. Otherwise, the type is not required
+        // to exist in the garage.
+        let household_fallback = if garage.households.is_empty() {
+            None
+        } else {
+            Some(Id::get_from_ext(&config.households().fallback_veh_type))
+        };
+
+        let park_and_ride = config.park_and_ride();
+        let park_and_ride_act = if park_and_ride.facilities.is_empty() {
+            None
+        } else {
+            for facility in park_and_ride.facilities {
+                garage.add_parking_facility(Id::get_from_ext(&facility.link), facility.capacity);
+            }
+            Some(Id::create(&park_and_ride.act_type))
+        };
+
+        let mode_availability = config.mode_availability();
+        for mode in &mode_availability.driver_modes {
+            garage.restrict_to_drivers(Id::create(mode));
         }
+        // fallback types which are not part of the scenario are ignored
+        let mode_fallbacks = mode_availability
+            .fallback_veh_types
+            .iter()
+            .map(|type_id| Id::create(type_id))
+            .filter(|type_id| garage.vehicle_types.contains_key(type_id))
+            .collect();
+
+        let trace_agents = if let Some(agent_tracing) = config.agent_tracing() {
+            let tracer = AgentTracer::new(&agent_tracing, net_message_broker.rank());
+            events.add_subscriber(Box::new(tracer));
+            true
+        } else {
+            false
+        };
+
+        let memory_stats = if let Profiling::CSV(_) = config.output().profiling {
+            let path = PathBuf::from(config.output().output_dir)
+                .join("instrument")
+                .join(format!("memory_process_{}.csv", net_message_broker.rank()));
+            Some(MemoryStatsWriter::new(&path))
+        } else {
+            None
+        };
+
+        let fares = config
+            .fares()
+            .map(|fares| FareSystem::from_file(&PathBuf::from(fares.file)));
+
+        let incidents = config
+            .incidents()
+            .map(|incidents| Incidents::from_file(&PathBuf::from(incidents.file)));
+
+        if let Some(speed_variation) = config.speed_variation() {
+            network.set_speed_variation(SpeedVariation::from_config(&speed_variation));
+        }
+
+        if let Some(transit_priority) = config.transit_priority() {
+            let modes = transit_priority
+                .modes
+                .iter()
+                .map(|mode| Id::<String>::create(mode).internal())
+                .collect();
+            network.set_transit_priority(modes);
+        }
+
+        let speed_factors = config
+            .speed_factors()
+            .map(|speed_factors| SpeedFactors::from_config(&speed_factors));
+
+        let opening_hours = config
+            .opening_hours()
+            .map(|opening_hours| OpeningHours::from_config(&opening_hours));
+
+        let stuck_agents = config.stuck_penalty().map(|stuck_penalty| {
+            StuckAgents::from_config(
+                &stuck_penalty,
+                &PathBuf::from(config.output().output_dir),
+                net_message_broker.rank(),
+            )
+        });
+
+        let telemetry = config
+            .telemetry()
+            .map(|telemetry| Telemetry::from_config(&telemetry));
+
+        let signals = config
+            .signals()
+            .map(|signals| Signals::from_config(&signals, &network));
+
+        let consistency_check = config
+            .consistency_check()
+            .map(|check| (check.interval.max(1), 0));
 
         Simulation {
             network,
             garage,
-            teleportation_q: TimeQueue::new(),
+            mode_fallbacks,
+            household_fallback,
+            waiting_passengers: IntMap::default(),
+            park_and_ride_act,
+            trace_agents,
+            memory_stats,
+            engines: Vec::new(),
+            engine_modes: IntMap::default(),
+            listeners: Vec::new(),
+            fares,
+            evacuation: None,
+            replay: None,
+            incidents,
+            speed_factors,
+            activity_durations,
+            opening_hours,
+            stuck_agents,
+            aborted_agents: 0,
+            consistency_check,
+            telemetry,
+            signals,
+            early_receive: config.communication().early_receive,
+            teleportation: TeleportationEngine::from_config(&config.teleportation()),
             activity_q,
             net_message_broker,
             events,
             replanner,
+            max_passenger_wait_time: config.simulation().max_passenger_wait_time,
             start_time: config.simulation().start_time,
             end_time: config.simulation().end_time,
         }
     }
 
+    /// Registers an engine for the legs of its modes. Each mode can only be handled by one engine.
+    pub fn add_engine(&mut self, engine: Box<dyn Engine>) {
+        let index = self.engines.len();
+        for mode in engine.modes() {
+            if let Some(other) = self.engine_modes.insert(mode.clone(), index) {
+                panic!(
+                    "Mode {} is handled by engines #{other} and #{index}.",
+                    mode.external()
+                );
+            }
+        }
+        self.engines.push(engine);
+    }
+
+    pub fn garage(&self) -> &Garage {
+        &self.garage
+    }
+
+    pub fn replanner(&self) -> &dyn Replanner {
+        self.replanner.as_ref()
+    }
+
+    /// Consumes the simulation and returns its replanner, so that it can be reused in the next
+    /// iteration.
+    pub fn into_replanner(self) -> Box<dyn Replanner> {
+        self.replanner
+    }
+
+    /// Removes the agents, which are at an activity, e.g. at the end of the run to carry their
+    /// executed plans into the next iteration.
+    pub fn take_agents_at_activity(&mut self) -> Vec<Person> {
+        self.activity_q.take_where(|_| true)
+    }
+
+    /// Registers a listener which is called before and after each time step.
+    pub fn add_listener(&mut self, listener: Box<dyn MobsimListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// Evacuates the agents inside the evacuation area at its trigger time.
+    pub fn set_evacuation(&mut self, evacuation: Evacuation) {
+        self.evacuation = Some(evacuation);
+    }
+
+    /// Injects the replayed vehicles of a previous run as background traffic. The vehicle type of
+    /// the replay must be registered in the garage.
+    pub fn set_replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+    }
+
+    /// Lets the controller adjust the phases of the signals every control interval. Requires the
+    /// signals module in the config.
+    pub fn set_signal_controller(&mut self, controller: Box<dyn SignalController>) {
+        self.signals
+            .as_mut()
+            .expect("A signal controller requires the signals module in the config.")
+            .set_controller(controller);
+    }
+
+    /// Removes an agent from this partition, so that it can be injected into another one with
+    /// [`Simulation::inject_agent`]. Agents on the network and in teleportation are taken together
+    /// with their vehicles. Returns None, if the agent isn't at an activity or driving on this
+    /// partition, e.g. if it waits as a passenger or travels on a split out link.
+    pub fn take_agent(&mut self, person: &Id<Person>) -> Option<MigratingAgent> {
+        let id = person.internal();
+        if let Some(end_time) = self.activity_q.end_time_of(id) {
+            let agent = self.activity_q.remove(id).unwrap();
+            return Some(MigratingAgent::Activity { agent, end_time });
+        }
+        if let Some(vehicle) = self.network.take_vehicle_of(id) {
+            return Some(MigratingAgent::Driving { vehicle });
+        }
+        self.teleportation
+            .take_vehicle_of(id)
+            .map(|(vehicle, arrival)| MigratingAgent::Teleported { vehicle, arrival })
+    }
+
+    /// Injects an agent, which was taken from another partition, at `link`. Activities end and
+    /// teleported agents arrive at their original times, but not before `now`. Agents at an
+    /// activity perform it at `link` from now on. Drivers continue their route from the next
+    /// occurrence of `link`, which must be part of this partition.
+    pub fn inject_agent(&mut self, agent: MigratingAgent, link: u64, now: u32) {
+        match agent {
+            MigratingAgent::Activity {
+                mut agent,
+                end_time,
+            } => {
+                agent.curr_act_mut().link_id = link;
+                self.activity_q.add_at(agent, end_time.max(now));
+            }
+            MigratingAgent::Driving { mut vehicle } => {
+                vehicle.route_index_to(link);
+                self.network
+                    .send_veh_en_route(vehicle, Some(&mut self.events), now);
+            }
+            MigratingAgent::Teleported { vehicle, arrival } => {
+                self.teleportation.add_at(vehicle, arrival.max(now));
+            }
+        }
+    }
+
+    /// Runs the simulation from start to end time. Returns false, if the simulation was stopped
+    /// early, because a shutdown was requested on any partition.
     #[tracing::instrument(level = "info", skip(self), fields(rank = self.net_message_broker.rank()))]
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> bool {
         // use fixed start and end times
         let mut now = self.start_time;
         info!(
@@ -81,7 +365,29 @@ where
             self.end_time,
         );
 
+        if self.net_message_broker.rank() == 0 {
+            control::set_time_range(self.start_time, self.end_time);
+        }
+        if let Some((interval, _)) = self.consistency_check {
+            let expected = self.net_message_broker.sum(self.owned_agents() as u64);
+            self.consistency_check = Some((interval, expected));
+        }
+
+        let mut completed = true;
         while now <= self.end_time {
+            // all partitions have to agree on the time step at which they stop. Otherwise, the
+            // remaining partitions would wait for messages forever.
+            if now % SHUTDOWN_CHECK_INTERVAL == 0 && self.stop_requested(now) {
+                info!(
+                    "#{} stops simulation at time step {now} due to a shutdown request.",
+                    self.net_message_broker.rank()
+                );
+                completed = false;
+                break;
+            }
+            if self.net_message_broker.rank() == 0 {
+                control::set_time(now);
+            }
             if now % 3600 == 0 {
                 let _hour = now / 3600;
                 let _min = (now % 3600) / 60;
@@ -93,36 +399,336 @@ where
                     self.network.veh_on_net()
                 );
             }
+            self.update_crash_dump(now);
+            if now % memory::MEMORY_STATS_INTERVAL == 0 {
+                self.write_memory_stats(now);
+            }
+            self.notify_listeners(now, |listener, state| listener.before_step(state));
+            self.apply_incidents(now);
+            self.apply_speed_factors(now);
+            self.evacuate(now);
             self.wakeup(now);
+            self.abort_waiting_passengers(now);
+            self.replay(now);
             self.terminate_teleportation(now);
+            if let Some(signals) = self.signals.as_mut() {
+                signals.update(&mut self.network, now);
+            }
+            if self.early_receive {
+                self.net_message_broker.begin_recv();
+            }
             self.move_nodes(now);
             self.move_links(now);
+            self.do_engine_steps(now);
+            self.check_consistency(now);
+            if let Some(telemetry) = self.telemetry.as_ref() {
+                telemetry.record(&self.network, &mut self.events, now);
+            }
 
             self.replanner.update_time(now, &mut self.events);
+            self.notify_listeners(now, |listener, state| listener.after_step(state));
 
             now += 1;
         }
 
+        self.finish_engines(now);
+        self.record_stuck_agents(now);
+        if self.aborted_agents > 0 {
+            info!(
+                "#{} removed {} agents from the simulation, which couldn't continue their plans.",
+                self.net_message_broker.rank(),
+                self.aborted_agents
+            );
+        }
+        self.notify_listeners(now, |listener, state| listener.finish(state));
         // maybe this belongs into the controller? Then this would have to be a &mut instead of owned.
         self.events.finish();
+        if let Some(writer) = self.memory_stats.as_mut() {
+            writer.flush();
+        }
+        completed
+    }
+
+    /// Number of agents, which are owned by this partition. Vehicles sent to other partitions are
+    /// received within the same time step, so only vehicles for future time steps are buffered.
+    /// Agents, which were removed as stuck, are still owned by the partition which removed them.
+    fn owned_agents(&self) -> usize {
+        self.aborted_agents
+            + self.activity_q.len()
+            + self
+                .network
+                .vehicles()
+                .filter(|vehicle| !self.is_replayed(vehicle))
+                .map(Vehicle::num_agents)
+                .sum::<usize>()
+            + self.teleportation.num_agents()
+            + self
+                .waiting_passengers
+                .values()
+                .map(Vec::len)
+                .sum::<usize>()
+            + self.engines.iter().map(|e| e.num_agents()).sum::<usize>()
+            + self.net_message_broker.buffered_in_agents()
+    }
+
+    /// Compares the number of agents owned by all partitions with the number of agents at the
+    /// start of the simulation. Agents stay at their last activity once they have finished their
+    /// plans, so the total must not change. Mismatches hint at vehicles, which were lost on the
+    /// way between partitions or dropped by an engine.
+    fn check_consistency(&mut self, now: u32) {
+        let (interval, expected) = match self.consistency_check {
+            Some(check) => check,
+            None => return,
+        };
+        if (now - self.start_time) % interval != 0 {
+            return;
+        }
+        let owned = self.owned_agents();
+        let total = self.net_message_broker.sum(owned as u64);
+        if total != expected {
+            error!(
+                "#{} consistency check failed at time step {now}: partitions own {total} agents, but {expected} agents were simulated. This partition owns {owned} agents: {} at activities, {} vehicles on the network, {} teleported vehicles, {} waiting passengers.",
+                self.net_message_broker.rank(),
+                self.activity_q.len(),
+                self.network.veh_on_net(),
+                self.teleportation.len(),
+                self.waiting_passengers.values().map(Vec::len).sum::<usize>(),
+            );
+        } else if self.net_message_broker.rank() == 0 {
+            debug!("Consistency check passed at time step {now}: {total} agents.");
+        }
+    }
+
+    /// Records all agents, which have not reached the last activity of their plans, as stuck. These
+    /// are agents on the network, in teleportation, waiting for their drivers, or at activities with
+    /// remaining legs.
+    fn record_stuck_agents(&mut self, now: u32) {
+        let stuck_agents = match self.stuck_agents.as_mut() {
+            Some(stuck_agents) => stuck_agents,
+            None => return,
+        };
+
+        let replay = self.replay.as_ref();
+        for vehicle in self
+            .network
+            .vehicles()
+            .filter(|vehicle| !replay.is_some_and(|replay| replay.is_replayed(vehicle)))
+        {
+            let link = vehicle.curr_link_id().unwrap();
+            for agent in vehicle.driver.iter().chain(vehicle.passengers.iter()) {
+                stuck_agents.record(agent, link, &mut self.events, now);
+            }
+        }
+        for vehicle in self.teleportation.take_all() {
+            let link = vehicle
+                .driver()
+                .curr_leg()
+                .route
+                .as_ref()
+                .unwrap()
+                .end_link();
+            for agent in vehicle.driver.iter().chain(vehicle.passengers.iter()) {
+                stuck_agents.record(agent, link, &mut self.events, now);
+            }
+        }
+        for (_, agent) in self.waiting_passengers.values().flatten() {
+            let link = agent.curr_leg().route.as_ref().unwrap().start_link();
+            stuck_agents.record(agent, link, &mut self.events, now);
+        }
+        for agent in self
+            .activity_q
+            .take_where(|agent| !agent.has_finished_plan())
+        {
+            stuck_agents.record(&agent, agent.curr_act().link_id, &mut self.events, now);
+        }
+        info!(
+            "#{} recorded {} stuck agents.",
+            self.net_message_broker.rank(),
+            stuck_agents.agents().len()
+        );
+        stuck_agents.finish();
+    }
+
+    /// Whether a shutdown was requested on any partition. While a pause is requested on any
+    /// partition, this waits until the simulation is resumed or stopped.
+    fn stop_requested(&mut self, now: u32) -> bool {
+        let mut paused = false;
+        loop {
+            if self.net_message_broker.any(shutdown::is_requested()) {
+                return true;
+            }
+            if !self.net_message_broker.any(control::is_paused()) {
+                if paused {
+                    info!(
+                        "#{} resumes at time step {now}.",
+                        self.net_message_broker.rank()
+                    );
+                }
+                return false;
+            }
+            if !paused {
+                info!(
+                    "#{} pauses at time step {now}.",
+                    self.net_message_broker.rank()
+                );
+                paused = true;
+            }
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
+
+    fn notify_listeners(&mut self, now: u32, notify: impl Fn(&mut dyn MobsimListener, &SimState)) {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let state = SimState {
+            time: now,
+            rank: self.net_message_broker.rank(),
+            network: &self.network,
+            garage: &self.garage,
+            activity_q: &self.activity_q,
+            teleportation_q: self.teleportation.queue(),
+        };
+        for listener in self.listeners.iter_mut() {
+            notify(listener.as_mut(), &state);
+        }
+    }
+
+    fn write_memory_stats(&mut self, now: u32) {
+        if let Some(writer) = self.memory_stats.as_mut() {
+            writer.write(&MemoryStats {
+                sim_time: now,
+                rank: self.net_message_broker.rank(),
+                rss: memory::rss(),
+                veh_on_net: self.network.veh_on_net(),
+                link_queues: self.network.link_queue_capacity(),
+                activity_q: self.activity_q.len(),
+                teleportation_q: self.teleportation.len(),
+                buffered_in_messages: self.net_message_broker.buffered_in_messages(),
+                pending_out_vehicles: self.net_message_broker.pending_out_vehicles(),
+            });
+        }
+    }
+
+    fn update_crash_dump(&self, now: u32) {
+        crash_dump::update(|s| {
+            s.time = now;
+            s.activity_q = self.activity_q.len();
+            s.teleportation_q = self.teleportation.len();
+            s.veh_on_net = self.network.veh_on_net();
+            s.active_nodes = self.network.active_nodes();
+            s.active_links = self.network.active_links();
+            s.buffered_in_messages = self.net_message_broker.buffered_in_messages();
+            s.pending_out_vehicles = self.net_message_broker.pending_out_vehicles();
+        });
+    }
+
+    fn apply_incidents(&mut self, now: u32) {
+        let incidents = if let Some(incidents) = &self.incidents {
+            incidents
+        } else {
+            return;
+        };
+        for (link, factor) in incidents.changes(now) {
+            debug!(
+                "#{} sets capacity factor of link {} to {factor} at {now}.",
+                self.net_message_broker.rank(),
+                Id::<Link>::get(link)
+            );
+            self.network.set_capacity_factor(link, factor);
+        }
+    }
+
+    /// Updates the speed factors of all links, whenever a profile changes. Profiles are also applied
+    /// at the start of the simulation, which might not be at the beginning of an hour.
+    fn apply_speed_factors(&mut self, now: u32) {
+        let speed_factors = if let Some(speed_factors) = &self.speed_factors {
+            speed_factors
+        } else {
+            return;
+        };
+        if now == self.start_time || speed_factors.changes_at(now) {
+            self.network
+                .set_speed_factors(|link| speed_factors.factor(link, now));
+        }
+    }
+
+    /// Sends the replayed vehicles, which depart at `now`, onto the network.
+    fn replay(&mut self, now: u32) {
+        let replay = match self.replay.as_mut() {
+            Some(replay) => replay,
+            None => return,
+        };
+        for vehicle in replay.departures(now) {
+            self.network.send_veh_en_route(vehicle, None, now);
+        }
+    }
+
+    fn is_replayed(&self, vehicle: &Vehicle) -> bool {
+        self.replay
+            .as_ref()
+            .is_some_and(|replay| replay.is_replayed(vehicle))
+    }
+
+    /// At the trigger time of the evacuation, the affected agents end their activities and depart
+    /// within this time step, so that they are replanned together with all other departing agents.
+    fn evacuate(&mut self, now: u32) {
+        let evacuation = match &self.evacuation {
+            Some(evacuation) if evacuation.trigger_time() == now => evacuation,
+            _ => return,
+        };
+        let agents = self
+            .activity_q
+            .take_where(|agent| evacuation.is_affected(agent));
+        info!(
+            "#{} evacuates {} agents at time step {now}.",
+            self.net_message_broker.rank(),
+            agents.len()
+        );
+        for mut agent in agents {
+            let safe_link = evacuation.evacuate(&mut agent, &self.garage, now);
+            self.events
+                .publish_event(now, &evacuation.event(agent.id, safe_link));
+            self.start_activity(agent, now);
+        }
     }
 
     #[tracing::instrument(level = "trace", skip(self), fields(rank = self.net_message_broker.rank()))]
     fn wakeup(&mut self, now: u32) {
         let agents = self.activity_q.pop(now);
 
-        for mut agent in agents {
-            self.update_agent(&mut agent, now);
+        // passengers are processed first, so that they are waiting for their driver, in case the
+        // driver departs within the same time step.
+        let (passengers, mut drivers): (Vec<_>, Vec<_>) = agents
+            .into_iter()
+            .partition(|agent| agent.next_leg().is_ride());
+
+        for passenger in passengers {
+            self.wait_for_driver(passenger, now);
+        }
 
+        // drivers are replanned together, so that the replanner can batch route queries of agents
+        // departing from the same location.
+        self.update_agents(&mut drivers, now);
+
+        for agent in drivers {
             let act_type: Id<String> = Id::get(agent.curr_act().act_type);
             self.events.publish_event(
                 now,
                 &Event::new_act_end(agent.id, agent.curr_act().link_id, act_type.internal()),
             );
 
-            let mut vehicle = self.departure(agent, now);
-            let veh_type_id = Id::get(vehicle.r#type);
-            let veh_type = self.garage.vehicle_types.get(&veh_type_id).unwrap();
+            if let Some(&engine) = self.engine_modes.get(&Id::get(agent.next_leg().mode)) {
+                self.depart_to_engine(engine, agent, now);
+                continue;
+            }
+
+            let mut vehicle = if let Some(vehicle) = self.departure(agent, now) {
+                vehicle
+            } else {
+                continue;
+            };
+            let veh_type = self.garage.veh_type(vehicle.r#type);
 
             match veh_type.lod() {
                 LevelOfDetail::Network => {
@@ -130,18 +736,30 @@ where
                         now,
                         &Event::new_person_enters_veh(vehicle.driver().id, vehicle.id),
                     );
+                    self.board_passengers(&mut vehicle, now);
                     //we don't pass the event publisher because a link enter event should not be published
                     self.network.send_veh_en_route(vehicle, None, now);
                 }
                 LevelOfDetail::Teleported => {
+                    self.board_passengers(&mut vehicle, now);
+                    self.teleportation.depart(&mut vehicle);
                     if Simulation::is_local_route(&vehicle, &self.net_message_broker) {
-                        self.teleportation_q.add(vehicle, now);
+                        self.teleportation.add(vehicle, now);
                     } else {
                         // set the pointer of the route to the last element, so that the current link
                         // is the destination of this leg. Setting this to the last element makes this
                         // logic independent of whether the agent has a Generic-Route with only start
                         // and end link or a full Network-Route, which is often the case for ride modes.
                         vehicle.route_index_to_last();
+                        self.trace_vehicle(
+                            now,
+                            &vehicle,
+                            format_args!(
+                                "sending teleported vehicle {} to the partition of link {}",
+                                vehicle.id,
+                                vehicle.curr_link_id().unwrap()
+                            ),
+                        );
                         self.net_message_broker.add_veh(vehicle, now);
                     }
                 }
@@ -149,12 +767,322 @@ where
         }
     }
 
-    fn departure(&mut self, mut agent: Person, now: u32) -> Vehicle {
+    /// Lets the agent depart on a leg which is simulated by a registered engine. The engine is
+    /// responsible for vehicles of such legs.
+    fn depart_to_engine(&mut self, engine: usize, mut agent: Person, now: u32) {
+        agent.advance_plan();
+        let leg = agent.curr_leg();
+        let start_link = leg.route.as_ref().unwrap().start_link();
+        self.events
+            .publish_event(now, &Event::new_departure(agent.id, start_link, leg.mode));
+
+        let mut ctx = EngineContext::new(
+            &mut self.events,
+            &mut self.garage,
+            self.net_message_broker.rank(),
+        );
+        self.engines[engine].receive_departure(agent, now, &mut ctx);
+        let arrivals = ctx.take_arrivals();
+        Self::start_activities(
+            &mut self.events,
+            &mut self.activity_q,
+            self.activity_durations,
+            self.opening_hours.as_ref(),
+            self.fares.as_ref(),
+            arrivals,
+            now,
+        );
+    }
+
+    fn do_engine_steps(&mut self, now: u32) {
+        for engine in self.engines.iter_mut() {
+            let mut ctx = EngineContext::new(
+                &mut self.events,
+                &mut self.garage,
+                self.net_message_broker.rank(),
+            );
+            engine.do_step(now, &mut ctx);
+            let arrivals = ctx.take_arrivals();
+            // can't call a method of self, while the engine is borrowed.
+            Self::start_activities(
+                &mut self.events,
+                &mut self.activity_q,
+                self.activity_durations,
+                self.opening_hours.as_ref(),
+                self.fares.as_ref(),
+                arrivals,
+                now,
+            );
+        }
+    }
+
+    fn finish_engines(&mut self, now: u32) {
+        for engine in self.engines.iter_mut() {
+            let mut ctx = EngineContext::new(
+                &mut self.events,
+                &mut self.garage,
+                self.net_message_broker.rank(),
+            );
+            engine.finish(&mut ctx);
+            let arrivals = ctx.take_arrivals();
+            Self::start_activities(
+                &mut self.events,
+                &mut self.activity_q,
+                self.activity_durations,
+                self.opening_hours.as_ref(),
+                self.fares.as_ref(),
+                arrivals,
+                now,
+            );
+        }
+    }
+
+    /// Publishes a money event with the fare of the agent's current leg, if the mode of the leg is
+    /// charged.
+    fn charge_fare(
+        events: &mut EventsPublisher,
+        fares: Option<&FareSystem>,
+        agent: &Person,
+        now: u32,
+    ) {
+        let fares = if let Some(fares) = fares {
+            fares
+        } else {
+            return;
+        };
+        let leg = agent.curr_leg();
+        if !fares.is_charged(&Id::get(leg.mode)) {
+            return;
+        }
+        let from = agent.prev_act();
+        let to = agent.next_act();
+        let fare = fares.fare(leg.distance(), (from.x, from.y), (to.x, to.y));
+        events.publish_event(now, &fares.money_event(agent.id, fare));
+    }
+
+    /// Agents which were handed back by an engine arrive at the end of their leg and start their
+    /// next activity.
+    fn start_activities(
+        events: &mut EventsPublisher,
+        activity_q: &mut TimeQueue<Person>,
+        activity_durations: ActivityDurationInterpretation,
+        opening_hours: Option<&OpeningHours>,
+        fares: Option<&FareSystem>,
+        agents: Vec<Person>,
+        now: u32,
+    ) {
+        for mut agent in agents {
+            Self::charge_fare(events, fares, &agent, now);
+            let leg = agent.curr_leg();
+            let route = leg.route.as_ref().unwrap();
+            events.publish_event(
+                now,
+                &Event::new_arrival(agent.id, route.end_link(), leg.mode, route.distance),
+            );
+
+            agent.advance_plan();
+            let act = agent.curr_act();
+            let act_type: Id<String> = Id::get(act.act_type);
+            events.publish_event(
+                now,
+                &Event::new_act_start(agent.id, act.link_id, act_type.internal()),
+            );
+            let end_time =
+                Self::activity_end_time(events, &agent, activity_durations, opening_hours, now);
+            activity_q.add_at(agent, end_time);
+        }
+    }
+
+    /// Puts the agent into the activity queue until its current activity ends.
+    fn start_activity(&mut self, agent: Person, now: u32) {
+        let end_time = Self::activity_end_time(
+            &mut self.events,
+            &agent,
+            self.activity_durations,
+            self.opening_hours.as_ref(),
+            now,
+        );
+        self.activity_q.add_at(agent, end_time);
+    }
+
+    /// End time of the agent's current activity, which starts at `now`. If the activity has
+    /// opening hours, agents who arrive early wait until it opens and leave when it closes.
+    fn activity_end_time(
+        events: &mut EventsPublisher,
+        agent: &Person,
+        activity_durations: ActivityDurationInterpretation,
+        opening_hours: Option<&OpeningHours>,
+        now: u32,
+    ) -> u32 {
+        let act = agent.curr_act();
+        let opening_time = match opening_hours.and_then(|o| o.get(act.act_type)) {
+            Some(opening_time) => opening_time,
+            None => return agent.wakeup_time(now, activity_durations),
+        };
+
+        if now < opening_time.open {
+            events.publish_event(
+                now,
+                &OpeningHours::waiting_event(
+                    agent.id,
+                    act.link_id,
+                    act.act_type,
+                    opening_time.open,
+                ),
+            );
+        }
+        let start = opening_time.start(now);
+        opening_time.clip(agent.wakeup_time(start, activity_durations), now)
+    }
+
+    /// Passengers of ride legs depart and wait at the start link of their leg until their driver
+    /// departs from the same link.
+    fn wait_for_driver(&mut self, mut passenger: Person, now: u32) {
+        let act_type: Id<String> = Id::get(passenger.curr_act().act_type);
+        self.events.publish_event(
+            now,
+            &Event::new_act_end(
+                passenger.id,
+                passenger.curr_act().link_id,
+                act_type.internal(),
+            ),
+        );
+
+        passenger.advance_plan();
+        let leg = passenger.curr_leg();
+        let route = leg.route.as_ref().unwrap();
+        self.events.publish_event(
+            now,
+            &Event::new_departure(passenger.id, route.start_link(), leg.mode),
+        );
+
+        let driver_id: Id<Person> = Id::get(leg.driver.unwrap());
+        self.trace_person(
+            now,
+            passenger.id,
+            format_args!("waiting for driver {}", driver_id.external()),
+        );
+        self.waiting_passengers
+            .entry(driver_id)
+            .or_default()
+            .push((now, passenger));
+    }
+
+    /// Removes passengers as stuck, which have been waiting for their drivers longer than the
+    /// configured maximum, e.g. because the driver has been removed from the simulation or doesn't
+    /// depart from the passenger's start link.
+    fn abort_waiting_passengers(&mut self, now: u32) {
+        let max_wait_time = self.max_passenger_wait_time;
+        let mut stuck = Vec::new();
+        self.waiting_passengers.retain(|_, waiting| {
+            let (waited_too_long, still_waiting): (Vec<_>, Vec<_>) = std::mem::take(waiting)
+                .into_iter()
+                .partition(|(since, _)| now - since >= max_wait_time);
+            *waiting = still_waiting;
+            stuck.extend(waited_too_long);
+            !waiting.is_empty()
+        });
+
+        for (_, passenger) in stuck {
+            let link = passenger.curr_leg().route.as_ref().unwrap().start_link();
+            self.abort_agent(&passenger, link, now);
+        }
+    }
+
+    fn board_passengers(&mut self, vehicle: &mut Vehicle, now: u32) {
+        let driver_id: Id<Person> = Id::get(vehicle.driver().id);
+        let start_link = vehicle
+            .driver()
+            .curr_leg()
+            .route
+            .as_ref()
+            .unwrap()
+            .start_link();
+
+        let waiting = if let Some(waiting) = self.waiting_passengers.get_mut(&driver_id) {
+            waiting
+        } else {
+            return;
+        };
+
+        // passengers board in the order they arrived. If all seats are taken, the remaining
+        // passengers keep their place at the start link and wait for the next trip of the driver.
+        let mut staying = Vec::with_capacity(waiting.len());
+        for (since, passenger) in std::mem::take(waiting) {
+            if passenger.curr_leg().route.as_ref().unwrap().start_link() != start_link {
+                staying.push((since, passenger));
+                continue;
+            }
+            let passenger_id = passenger.id;
+            match vehicle.board(passenger) {
+                Ok(_) => self
+                    .events
+                    .publish_event(now, &Event::new_person_enters_veh(passenger_id, vehicle.id)),
+                Err(passenger) => staying.push((since, passenger)),
+            }
+        }
+
+        if staying.is_empty() {
+            self.waiting_passengers.remove(&driver_id);
+        } else {
+            *waiting = staying;
+        }
+    }
+
+    /// Passengers leave the vehicle at the destination of the driver and start their next activity.
+    fn alight_passengers(&mut self, passengers: Vec<Person>, veh_id: u64, now: u32) {
+        for mut passenger in passengers {
+            self.events
+                .publish_event(now, &Event::new_person_leaves_veh(passenger.id, veh_id));
+
+            let mode = passenger.curr_leg().mode;
+            let distance = passenger.curr_leg().distance();
+            passenger.advance_plan();
+            let act = passenger.curr_act();
+            self.events.publish_event(
+                now,
+                &Event::new_arrival(passenger.id, act.link_id, mode, distance),
+            );
+            let act_type: Id<String> = Id::get(act.act_type);
+            self.events.publish_event(
+                now,
+                &Event::new_act_start(passenger.id, act.link_id, act_type.internal()),
+            );
+            self.start_activity(passenger, now);
+        }
+    }
+
+    /// Lets the agent depart on its next leg. Returns None, if the agent has no feasible mode for
+    /// the leg or its vehicle is unknown. Such agents are removed from the simulation as stuck.
+    fn departure(&mut self, mut agent: Person, now: u32) -> Option<Vehicle> {
         //here, current element counter is going to be increased
         agent.advance_plan();
 
         assert_ne!(agent.curr_plan_elem % 2, 0);
 
+        if !self.check_mode_availability(&mut agent, now) {
+            let link = agent.curr_leg().route.as_ref().unwrap().start_link();
+            self.abort_agent(&agent, link, now);
+            return None;
+        }
+
+        let requested = Id::get(agent.curr_leg().route.as_ref().unwrap().veh_id);
+        match self.garage.assign_veh(&Id::get(agent.id), &requested) {
+            Ok(VehicleAssignment::Assigned(veh_id)) => {
+                agent.curr_leg_mut().route.as_mut().unwrap().veh_id = veh_id.internal();
+            }
+            Ok(VehicleAssignment::Unavailable(household_id)) => {
+                self.fail_over(&mut agent, &requested, &household_id, now);
+            }
+            Err(unknown) => {
+                let vehicle = unknown.vehicle.external().to_string();
+                self.publish_unknown_vehicle(&agent, vehicle, now);
+                let link = agent.curr_leg().route.as_ref().unwrap().start_link();
+                self.abort_agent(&agent, link, now);
+                return None;
+            }
+        }
+
         let leg = agent.curr_leg();
         let route = leg.route.as_ref().unwrap();
         let leg_mode: Id<String> = Id::get(leg.mode);
@@ -164,19 +1092,249 @@ where
         );
 
         let veh_id = Id::get(route.veh_id);
-        self.garage.unpark_veh(agent, &veh_id)
+        if let Some(facility_link) = self.garage.parked_at(&veh_id) {
+            if facility_link.internal() != route.start_link() {
+                let mut attrs = std::collections::HashMap::new();
+                attrs.insert(
+                    String::from("person"),
+                    Id::<Person>::get(agent.id).external().to_string(),
+                );
+                attrs.insert(String::from("vehicle"), veh_id.external().to_string());
+                attrs.insert(String::from("link"), facility_link.external().to_string());
+                attrs.insert(
+                    String::from("departureLink"),
+                    Id::<Link>::get(route.start_link()).external().to_string(),
+                );
+                self.events.publish_event(
+                    now,
+                    &Event::new_generic("parkAndRideVehicleElsewhere", attrs),
+                );
+            }
+        }
+        self.network.parking.release(route.veh_id);
+        Some(self.garage.unpark_veh(agent, &veh_id))
+    }
+
+    /// Switches the agent's current leg to a fallback vehicle, if the agent may not use the vehicle
+    /// of the leg, e.g. because it has no driving license. Returns false, if there is no vehicle
+    /// the agent may use, or if the vehicle of the leg is unknown.
+    fn check_mode_availability(&mut self, agent: &mut Person, now: u32) -> bool {
+        let veh_id = agent.curr_leg().route.as_ref().unwrap().veh_id;
+        let requested_type = match Id::<Vehicle>::try_get(veh_id)
+            .and_then(|requested| self.garage.vehicles.get(&requested))
+        {
+            Some(requested_type) => requested_type,
+            None => {
+                self.publish_unknown_vehicle(agent, format!("#{veh_id}"), now);
+                return false;
+            }
+        };
+        if self.garage.is_available(agent, requested_type) {
+            return true;
+        }
+
+        let mut attrs = std::collections::HashMap::new();
+        attrs.insert(
+            String::from("person"),
+            Id::<Person>::get(agent.id).external().to_string(),
+        );
+        attrs.insert(
+            String::from("mainMode"),
+            Id::<String>::get(agent.curr_trip_main_mode())
+                .external()
+                .to_string(),
+        );
+        attrs.insert(
+            String::from("vehicleType"),
+            requested_type.external().to_string(),
+        );
+
+        match self
+            .garage
+            .get_veh_id(agent, requested_type, &self.mode_fallbacks)
+        {
+            Ok(veh_id) => {
+                self.switch_veh(agent, &veh_id);
+                attrs.insert(
+                    String::from("fallbackMode"),
+                    Id::<String>::get(agent.curr_leg().mode)
+                        .external()
+                        .to_string(),
+                );
+                self.events
+                    .publish_event(now, &Event::new_generic("modeUnavailable", attrs));
+                true
+            }
+            Err(_) => {
+                self.events
+                    .publish_event(now, &Event::new_generic("noFeasibleMode", attrs));
+                false
+            }
+        }
     }
 
-    fn update_agent(&mut self, agent: &mut Person, now: u32) {
-        self.replanner.replan(now, agent, &self.garage)
+    /// Lets the agent travel its current leg with another one of its vehicles. The leg's mode and
+    /// travel time are adjusted to the type of that vehicle. The travel time is the free flow
+    /// travel time along the route links with the maximum velocity of that vehicle type.
+    fn switch_veh(&self, agent: &mut Person, veh_id: &Id<Vehicle>) {
+        let veh_type_id = self.garage.vehicles.get(veh_id).unwrap();
+        let veh_type = self.garage.vehicle_types.get(veh_type_id).unwrap();
+
+        let leg = agent.curr_leg_mut();
+        leg.mode = veh_type.net_mode;
+        let route = leg.route.as_mut().unwrap();
+        route.veh_id = veh_id.internal();
+        leg.trav_time = self
+            .network
+            .free_flow_trav_time(&route.route, veh_type.max_v);
+    }
+
+    /// Publishes an event for an agent, whose leg names a vehicle, which is not part of the garage
+    /// of this partition. Plans are validated against the vehicles before the simulation starts, so
+    /// this only happens for legs which were changed during the simulation.
+    fn publish_unknown_vehicle(&mut self, agent: &Person, vehicle: String, now: u32) {
+        let attrs = std::collections::HashMap::from([
+            (
+                String::from("person"),
+                Id::<Person>::get(agent.id).external().to_string(),
+            ),
+            (String::from("vehicle"), vehicle),
+        ]);
+        self.events
+            .publish_event(now, &Event::new_generic("unknownVehicle", attrs));
+    }
+
+    /// The household vehicle requested by the agent is in use by another member. The agent switches
+    /// to its vehicle of the configured fallback type and travels with the corresponding mode instead.
+    fn fail_over(
+        &mut self,
+        agent: &mut Person,
+        requested: &Id<Vehicle>,
+        household_id: &Id<Household>,
+        now: u32,
+    ) {
+        let fallback_type_id = self
+            .household_fallback
+            .as_ref()
+            .expect("There are households but no fallback vehicle type.");
+        let fallback_type = self.garage.vehicle_types.get(fallback_type_id).unwrap();
+        let fallback_veh_id = self.garage.veh_id(&Id::get(agent.id), fallback_type_id);
+
+        let mut attrs = std::collections::HashMap::new();
+        attrs.insert(
+            String::from("person"),
+            Id::<Person>::get(agent.id).external().to_string(),
+        );
+        attrs.insert(String::from("vehicle"), requested.external().to_string());
+        attrs.insert(
+            String::from("household"),
+            household_id.external().to_string(),
+        );
+        attrs.insert(
+            String::from("fallbackMode"),
+            Id::<String>::get(fallback_type.net_mode)
+                .external()
+                .to_string(),
+        );
+        self.events.publish_event(
+            now,
+            &Event::new_generic("householdVehicleUnavailable", attrs),
+        );
+
+        self.switch_veh(agent, &fallback_veh_id);
+    }
+
+    /// Parks the vehicle after its driver has arrived. If the driver continues with a park and ride
+    /// activity, the vehicle is left at the facility of the destination link until the driver picks
+    /// it up on the way back. Otherwise, the driver searches for a parking spot around the
+    /// destination link. Returns the driver and the time it spent searching for a parking spot.
+    fn park_veh(&mut self, vehicle: Vehicle, now: u32) -> (Person, u32) {
+        let veh_id: Id<Vehicle> = Id::get(vehicle.id);
+        let veh_type_id = vehicle.r#type;
+        let agent = self.garage.park_veh(vehicle);
+
+        // only vehicles driving on the network occupy parking spots.
+        let veh_type = self.garage.veh_type(veh_type_id);
+        if veh_type.lod() != LevelOfDetail::Network {
+            return (agent, 0);
+        }
+
+        let next_act = agent.next_act();
+        let link_id = Id::get(next_act.link_id);
+        if self
+            .park_and_ride_act
+            .as_ref()
+            .map_or(false, |act| act.internal() == next_act.act_type)
+        {
+            if !self.garage.park_at_facility(&veh_id, &link_id) {
+                let mut attrs = std::collections::HashMap::new();
+                attrs.insert(
+                    String::from("person"),
+                    Id::<Person>::get(agent.id).external().to_string(),
+                );
+                attrs.insert(String::from("vehicle"), veh_id.external().to_string());
+                attrs.insert(String::from("link"), link_id.external().to_string());
+                self.events
+                    .publish_event(now, &Event::new_generic("parkAndRideFacilityFull", attrs));
+            }
+            (agent, 0)
+        } else {
+            let search_time = self.search_parking(&agent, &veh_id, &link_id, now);
+            (agent, search_time)
+        }
+    }
+
+    /// Returns the time the driver needs to find a parking spot.
+    fn search_parking(
+        &mut self,
+        agent: &Person,
+        veh_id: &Id<Vehicle>,
+        link_id: &Id<Link>,
+        now: u32,
+    ) -> u32 {
+        let parking = &mut self.network.parking;
+        let is_restricted = parking.is_restricted(link_id.internal());
+        let spot = parking.park(veh_id.internal(), link_id.internal());
+        // there is nothing to search for if the destination link has unlimited parking.
+        if !is_restricted {
+            return 0;
+        }
+
+        let mut attrs = std::collections::HashMap::new();
+        attrs.insert(
+            String::from("person"),
+            Id::<Person>::get(agent.id).external().to_string(),
+        );
+        attrs.insert(String::from("vehicle"), veh_id.external().to_string());
+        attrs.insert(String::from("link"), link_id.external().to_string());
+        attrs.insert(
+            String::from("parkingLink"),
+            Id::<Link>::get(spot.link).external().to_string(),
+        );
+        attrs.insert(String::from("searchTime"), spot.search_time.to_string());
+        attrs.insert(
+            String::from("searchDistance"),
+            spot.search_distance.to_string(),
+        );
+        attrs.insert(String::from("found"), spot.found.to_string());
+        self.events
+            .publish_event(now, &Event::new_generic("parkingSearch", attrs));
+        spot.search_time
+    }
+
+    fn update_agents(&mut self, agents: &mut [Person], now: u32) {
+        self.replanner.replan_batch(now, agents, &self.garage)
     }
 
     #[instrument(level = "trace", skip(self), fields(rank = self.net_message_broker.rank()))]
     fn terminate_teleportation(&mut self, now: u32) {
-        let teleportation_vehicles = self.teleportation_q.pop(now);
-        for vehicle in teleportation_vehicles {
+        let teleportation_vehicles = self.teleportation.arrivals(now);
+        for mut vehicle in teleportation_vehicles {
+            let passengers = vehicle.alight_all();
+            let veh_id = vehicle.id;
+
             // park the vehice - get the agent out of the vehicle
-            let mut agent = self.garage.park_veh(vehicle);
+            let (mut agent, _) = self.park_veh(vehicle, now);
 
             // emmit travelled
             let leg = agent.curr_leg();
@@ -187,10 +1345,12 @@ where
                 &Event::new_travelled(agent.id, route.distance, mode.internal()),
             );
 
+            Self::charge_fare(&mut self.events, self.fares.as_ref(), &agent, now);
+
             // emmit arrival
             self.events.publish_event(
                 now,
-                &Event::new_arrival(agent.id, route.end_link(), mode.internal()),
+                &Event::new_arrival(agent.id, route.end_link(), mode.internal(), route.distance),
             );
 
             // advance plan to activity and put agent into activity q.
@@ -203,7 +1363,8 @@ where
                 now,
                 &Event::new_act_start(agent.id, act.link_id, act_type.internal()),
             );
-            self.activity_q.add(agent, now);
+            self.start_activity(agent, now);
+            self.alight_passengers(passengers, veh_id, now);
         }
     }
 
@@ -211,25 +1372,66 @@ where
     fn move_nodes(&mut self, now: u32) {
         let exited_vehicles = self.network.move_nodes(&mut self.events, now);
 
-        for veh in exited_vehicles {
+        for mut veh in exited_vehicles {
+            crash_dump::update(|s| s.record_vehicle(veh.id));
+            // replayed vehicles leave the simulation at the end of their runs.
+            if self.is_replayed(&veh) {
+                continue;
+            }
+            // vehicles with a remaining route were rejected by a link they may not enter.
+            if veh.peek_next_route_element().is_some() {
+                self.abort_vehicle(veh, now);
+                continue;
+            }
             self.events
                 .publish_event(now, &Event::new_person_leaves_veh(veh.driver().id, veh.id));
-            let veh_type_id = Id::get(veh.r#type);
-            let veh_type = self.garage.vehicle_types.get(&veh_type_id).unwrap();
+            let passengers = veh.alight_all();
+            let veh_id = veh.id;
+            let veh_type = self.garage.veh_type(veh.r#type);
             let mode = veh_type.net_mode;
-            let mut agent = self.garage.park_veh(veh);
+            let (mut agent, search_time) = self.park_veh(veh, now);
+
+            Self::charge_fare(&mut self.events, self.fares.as_ref(), &agent, now);
 
             // move to next activity
+            let distance = agent.curr_leg().distance();
             agent.advance_plan();
             let act = agent.curr_act();
-            self.events
-                .publish_event(now, &Event::new_arrival(agent.id, act.link_id, mode));
+            self.events.publish_event(
+                now,
+                &Event::new_arrival(agent.id, act.link_id, mode, distance),
+            );
             let act_type: Id<String> = Id::get(act.act_type);
             self.events.publish_event(
                 now,
                 &Event::new_act_start(agent.id, act.link_id, act_type.internal()),
             );
-            self.activity_q.add(agent, now);
+            // the driver only starts its activity once it has found a parking spot.
+            self.start_activity(agent, now + search_time);
+            self.alight_passengers(passengers, veh_id, now);
+        }
+    }
+
+    /// Removes the driver and the passengers of a vehicle, which can't continue its route, from the
+    /// simulation as stuck. The vehicle is parked where it stopped.
+    fn abort_vehicle(&mut self, mut vehicle: Vehicle, now: u32) {
+        let link = vehicle.curr_link_id().unwrap();
+        let passengers = vehicle.alight_all();
+        let driver = self.garage.park_veh(vehicle);
+        for agent in std::iter::once(&driver).chain(passengers.iter()) {
+            self.abort_agent(agent, link, now);
+        }
+    }
+
+    /// Removes an agent from the simulation and reports it as stuck on `link`. If stuck agents
+    /// are not recorded, the stuck event is published without a penalty.
+    fn abort_agent(&mut self, agent: &Person, link: u64, now: u32) {
+        self.aborted_agents += 1;
+        match self.stuck_agents.as_mut() {
+            Some(stuck_agents) => stuck_agents.record(agent, link, &mut self.events, now),
+            None => self
+                .events
+                .publish_event(now, &StuckAgent::new(agent, link, now).event(0.)),
         }
     }
 
@@ -238,6 +1440,16 @@ where
         let (vehicles, storage_cap_updates) = self.network.move_links(now);
 
         for veh in vehicles {
+            crash_dump::update(|s| s.record_vehicle(veh.id));
+            self.trace_vehicle(
+                now,
+                &veh,
+                format_args!(
+                    "sending vehicle {} to the partition of link {}",
+                    veh.id,
+                    veh.curr_link_id().unwrap()
+                ),
+            );
             self.net_message_broker.add_veh(veh, now);
         }
 
@@ -247,21 +1459,44 @@ where
 
         let sync_messages = self.net_message_broker.send_recv(now);
 
-        for msg in sync_messages {
+        for mut msg in sync_messages {
             self.network
-                .apply_storage_cap_updates(msg.storage_capacities);
+                .apply_storage_cap_updates(std::mem::take(&mut msg.storage_capacities), msg.time);
 
-            for veh in msg.vehicles {
-                let veh_type_id = Id::get(veh.r#type);
-                let veh_type = self.garage.vehicle_types.get(&veh_type_id).unwrap();
+            let from_process = msg.from_process;
+            for veh in msg.vehicles.drain(..) {
+                crash_dump::update(|s| s.record_vehicle(veh.id));
+                self.trace_vehicle(
+                    now,
+                    &veh,
+                    format_args!("received vehicle {} from #{from_process}", veh.id),
+                );
+                let veh_type = self.garage.veh_type(veh.r#type);
                 match veh_type.lod() {
                     LevelOfDetail::Network => {
                         self.network
                             .send_veh_en_route(veh, Some(&mut self.events), now)
                     }
-                    LevelOfDetail::Teleported => self.teleportation_q.add(veh, now),
+                    LevelOfDetail::Teleported => self.teleportation.add(veh, now),
                 }
             }
+            self.net_message_broker.recycle(msg);
+        }
+    }
+
+    fn trace_person(&mut self, now: u32, person_id: u64, what: Arguments) {
+        if self.trace_agents {
+            if let Some(tracer) = self.events.get_subscriber::<AgentTracer>() {
+                tracer.trace_person(now, person_id, what);
+            }
+        }
+    }
+
+    fn trace_vehicle(&mut self, now: u32, vehicle: &Vehicle, what: Arguments) {
+        if self.trace_agents {
+            if let Some(tracer) = self.events.get_subscriber::<AgentTracer>() {
+                tracer.trace_vehicle(now, vehicle, what);
+            }
         }
     }
 
@@ -0,0 +1,189 @@
+//! Agents, which don't complete their plans by the end of the simulation, are stuck. Each of them
+//! is reported with a `stuckAndAbort` event, which carries the configured scoring penalty, and is
+//! listed in a summary file of its partition. Agents, which can't continue their plans during the
+//! simulation, e.g. because their route uses a link which doesn't allow their mode, are removed
+//! from the simulation as stuck right away.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsPublisher;
+use crate::simulation::network::global_network::Link;
+use crate::simulation::wire_types::events::Event;
+use crate::simulation::wire_types::population::Person;
+
+/// Event type of matsim's `PersonStuckEvent`.
+pub const STUCK_EVENT: &str = "stuckAndAbort";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuckAgent {
+    pub person: u64,
+    pub link: u64,
+    /// Mode of the current leg, or of the next leg if the agent is at an activity.
+    pub mode: u64,
+    pub time: u32,
+}
+
+impl StuckAgent {
+    pub fn new(agent: &Person, link: u64, now: u32) -> Self {
+        let mode = if agent.curr_plan_elem % 2 == 0 {
+            agent.next_leg().mode
+        } else {
+            agent.curr_leg().mode
+        };
+        StuckAgent {
+            person: agent.id,
+            link,
+            mode,
+            time: now,
+        }
+    }
+
+    pub fn event(&self, penalty: f64) -> Event {
+        let attrs = HashMap::from([
+            (
+                String::from("person"),
+                Id::<Person>::get(self.person).external().to_string(),
+            ),
+            (
+                String::from("link"),
+                Id::<Link>::get(self.link).external().to_string(),
+            ),
+            (
+                String::from("legMode"),
+                Id::<String>::get(self.mode).external().to_string(),
+            ),
+            (String::from("penalty"), penalty.to_string()),
+        ]);
+        Event::new_generic(STUCK_EVENT, attrs)
+    }
+}
+
+#[derive(Debug)]
+pub struct StuckAgents {
+    penalty: f64,
+    output_path: Option<PathBuf>,
+    agents: Vec<StuckAgent>,
+}
+
+impl StuckAgents {
+    pub fn new(penalty: f64, output_path: Option<PathBuf>) -> Self {
+        StuckAgents {
+            penalty,
+            output_path,
+            agents: Vec::new(),
+        }
+    }
+
+    pub fn from_config(config: &config::StuckPenalty, output_dir: &Path, rank: u32) -> Self {
+        Self::new(
+            config.penalty,
+            Some(output_dir.join(format!("stuck_agents_{rank}.csv"))),
+        )
+    }
+
+    pub fn agents(&self) -> &[StuckAgent] {
+        &self.agents
+    }
+
+    /// Records the agent as stuck on `link` and publishes a stuck event with the penalty.
+    pub fn record(&mut self, agent: &Person, link: u64, events: &mut EventsPublisher, now: u32) {
+        let stuck = StuckAgent::new(agent, link, now);
+        events.publish_event(now, &stuck.event(self.penalty));
+        self.agents.push(stuck);
+    }
+
+    /// Writes the stuck agents into the summary file, if there is one.
+    pub fn finish(&self) {
+        if let Some(path) = self.output_path.as_ref() {
+            self.write_csv(path);
+        }
+    }
+
+    fn write_csv(&self, path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(path)
+            .unwrap_or_else(|_| panic!("Failed to create stuck agents file at {path:?}"));
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "person,link,mode,time,penalty").unwrap();
+        for stuck in &self.agents {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                Id::<Person>::get(stuck.person).external(),
+                Id::<Link>::get(stuck.link).external(),
+                Id::<String>::get(stuck.mode).external(),
+                stuck.time,
+                self.penalty
+            )
+            .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::simulation::id::Id;
+    use crate::simulation::messaging::events::EventsPublisher;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::stuck_agents::{StuckAgent, StuckAgents};
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+
+    #[test]
+    fn record_and_write() {
+        let link = Id::<Link>::create("sa-link").internal();
+        let mode = Id::<String>::create("sa-mode").internal();
+        let act_type = Id::<String>::create("sa-act").internal();
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., act_type, link, Some(0), None, None));
+        plan.add_leg(Leg::new(
+            Route {
+                veh_id: 0,
+                distance: 0.,
+                route: vec![link],
+            },
+            mode,
+            0,
+            None,
+        ));
+        plan.add_act(Activity::new(0., 0., act_type, link, None, None, None));
+        let person_id = Id::<Person>::create("sa-person").internal();
+        let mut person = Person::new(person_id, plan);
+        assert!(!person.has_finished_plan());
+
+        let path = PathBuf::from("./test_output/simulation/stuck_agents/stuck_agents_0.csv");
+        let mut stuck_agents = StuckAgents::new(-100., Some(path.clone()));
+        let mut events = EventsPublisher::new();
+        // the agent never left its first activity
+        stuck_agents.record(&person, link, &mut events, 42);
+        assert_eq!(
+            &[StuckAgent {
+                person: person_id,
+                link,
+                mode,
+                time: 42
+            }],
+            stuck_agents.agents()
+        );
+
+        stuck_agents.finish();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            "person,link,mode,time,penalty\nsa-person,sa-link,sa-mode,42,-100\n",
+            content
+        );
+
+        person.advance_plan();
+        person.advance_plan();
+        assert!(person.has_finished_plan());
+    }
+}
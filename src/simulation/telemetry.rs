@@ -0,0 +1,70 @@
+//! Periodic record of the congestion on the network. Every `interval` time steps, each partition
+//! publishes the occupancy of its links which hold vehicles and the number of blocked in-links of
+//! its nodes as events, so that external controllers, e.g. adaptive signals, can react to live
+//! congestion. Listeners within the process can query [SimNetworkPartition::link_occupancy] and
+//! [SimNetworkPartition::blocked_in_links] directly instead.
+
+use std::collections::HashMap;
+
+use crate::simulation::config;
+use crate::simulation::id::Id;
+use crate::simulation::messaging::events::EventsPublisher;
+use crate::simulation::network::global_network::{Link, Node};
+use crate::simulation::network::sim_network::SimNetworkPartition;
+use crate::simulation::wire_types::events::Event;
+
+/// Event type for the occupancy of a link.
+pub const LINK_OCCUPANCY_EVENT: &str = "linkOccupancy";
+/// Event type for a node with in-links, whose vehicles wait to pass the node.
+pub const BLOCKED_NODE_EVENT: &str = "nodeBlocked";
+
+#[derive(Debug)]
+pub struct Telemetry {
+    interval: u32,
+}
+
+impl Telemetry {
+    pub fn from_config(config: &config::Telemetry) -> Self {
+        Telemetry {
+            interval: config.interval.max(1),
+        }
+    }
+
+    /// Publishes the occupancy of links and nodes, if `now` is a multiple of the interval. This is
+    /// called after nodes and links were moved in the time step.
+    pub fn record(&self, network: &SimNetworkPartition, events: &mut EventsPublisher, now: u32) {
+        if now % self.interval != 0 {
+            return;
+        }
+
+        for occupancy in network.link_occupancies() {
+            let attrs = HashMap::from([
+                (
+                    String::from("link"),
+                    Id::<Link>::get(occupancy.link_id).external().to_string(),
+                ),
+                (String::from("vehicles"), occupancy.vehicles.to_string()),
+                (
+                    String::from("usedStorage"),
+                    occupancy.used_storage.to_string(),
+                ),
+            ]);
+            events.publish_event(now, &Event::new_generic(LINK_OCCUPANCY_EVENT, attrs));
+        }
+
+        for node_id in network.nodes.keys() {
+            let blocked = network.blocked_in_links(*node_id, now).unwrap();
+            if blocked == 0 {
+                continue;
+            }
+            let attrs = HashMap::from([
+                (
+                    String::from("node"),
+                    Id::<Node>::get(*node_id).external().to_string(),
+                ),
+                (String::from("blockedInLinks"), blocked.to_string()),
+            ]);
+            events.publish_event(now, &Event::new_generic(BLOCKED_NODE_EVENT, attrs));
+        }
+    }
+}
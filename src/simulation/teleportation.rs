@@ -0,0 +1,198 @@
+//! Teleported legs. Vehicles of teleported modes don't move on the network. Instead, they arrive
+//! at the end of their leg after a travel time, which is computed when they depart.
+
+use nohash_hasher::IntMap;
+
+use crate::simulation::config;
+use crate::simulation::replanning::teleported_router::{BeeLineDistanceRouter, TeleportedRouter};
+use crate::simulation::time_queue::TimeQueue;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::{Activity, Person};
+
+pub struct TeleportationEngine {
+    router: BeeLineDistanceRouter,
+    q: TimeQueue<Vehicle>,
+    /// Id of the vehicle of each teleported driver.
+    vehicles_of_drivers: IntMap<u64, u64>,
+}
+
+impl TeleportationEngine {
+    pub fn new(router: BeeLineDistanceRouter) -> Self {
+        TeleportationEngine {
+            router,
+            q: TimeQueue::new(),
+            vehicles_of_drivers: IntMap::default(),
+        }
+    }
+
+    pub fn from_config(config: &config::Teleportation) -> Self {
+        Self::new(BeeLineDistanceRouter::from_config(config))
+    }
+
+    /// Computes travel time and distance of the driver's current leg from the beeline between the
+    /// activities of its trip, and the beeline speed of the mode or the maximum velocity of the
+    /// vehicle. This must be called on the partition where the vehicle departs, as the leg is
+    /// sent along with the vehicle to the partition of its destination.
+    pub fn depart(&self, vehicle: &mut Vehicle) {
+        let speed = vehicle.max_v;
+        let driver = vehicle.driver.as_mut().unwrap();
+        let teleportation = match trip_activities(driver) {
+            Some((from, to)) => {
+                self.router
+                    .query_between_acts(from, to, driver.curr_leg().mode, speed)
+            }
+            None => return,
+        };
+        let leg = driver.curr_leg_mut();
+        leg.trav_time = teleportation.duration;
+        leg.route.as_mut().unwrap().distance = teleportation.distance;
+    }
+
+    /// Teleports the vehicle, which arrives after the travel time of the driver's leg.
+    pub fn add(&mut self, vehicle: Vehicle, now: u32) {
+        self.vehicles_of_drivers
+            .insert(vehicle.driver().id, vehicle.id);
+        self.q.add(vehicle, now);
+    }
+
+    /// Teleports the vehicle, which arrives at the given time, e.g. because it is moved from
+    /// another partition during its leg.
+    pub fn add_at(&mut self, vehicle: Vehicle, arrival: u32) {
+        self.vehicles_of_drivers
+            .insert(vehicle.driver().id, vehicle.id);
+        self.q.add_at(vehicle, arrival);
+    }
+
+    /// Removes the vehicle of `driver` together with its arrival time.
+    pub fn take_vehicle_of(&mut self, driver: u64) -> Option<(Vehicle, u32)> {
+        let id = self.vehicles_of_drivers.remove(&driver)?;
+        let arrival = self.q.end_time_of(id)?;
+        self.q.remove(id).map(|vehicle| (vehicle, arrival))
+    }
+
+    /// Vehicles which arrive at the end of their legs at `now`.
+    pub fn arrivals(&mut self, now: u32) -> Vec<Vehicle> {
+        let arrivals = self.q.pop(now);
+        for vehicle in &arrivals {
+            self.vehicles_of_drivers.remove(&vehicle.driver().id);
+        }
+        arrivals
+    }
+
+    /// Removes all vehicles, regardless of their arrival times.
+    pub fn take_all(&mut self) -> Vec<Vehicle> {
+        self.vehicles_of_drivers.clear();
+        self.q.take_where(|_| true)
+    }
+
+    pub fn queue(&self) -> &TimeQueue<Vehicle> {
+        &self.q
+    }
+
+    /// Number of persons in the teleported vehicles, including the drivers.
+    pub fn num_agents(&self) -> usize {
+        self.q.values().map(Vehicle::num_agents).sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.q.is_empty()
+    }
+}
+
+/// The main activities before and after the agent's current leg. Coordinates of interaction
+/// activities may be placeholders, so that trips are teleported between the main activities.
+/// Access and egress legs connect a main activity with an interaction. They keep their travel
+/// times, which were computed from the coordinates of the links.
+fn trip_activities(agent: &Person) -> Option<(&Activity, &Activity)> {
+    if agent.prev_act().is_interaction() != agent.next_act().is_interaction() {
+        return None;
+    }
+    let acts = &agent.plan.as_ref().unwrap().acts;
+    let leg_index = ((agent.curr_plan_elem - 1) / 2) as usize;
+    let from = acts[..=leg_index]
+        .iter()
+        .rev()
+        .find(|act| !act.is_interaction())?;
+    let to = acts[leg_index + 1..]
+        .iter()
+        .find(|act| !act.is_interaction())?;
+    Some((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::config::{Teleportation, TeleportedModeParams};
+    use crate::simulation::id::Id;
+    use crate::simulation::teleportation::TeleportationEngine;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+
+    fn leg(mode: u64, trav_time: u32) -> Leg {
+        Leg::new(
+            Route {
+                veh_id: 0,
+                distance: 1.,
+                route: vec![0, 1],
+            },
+            mode,
+            trav_time,
+            None,
+        )
+    }
+
+    #[test]
+    fn teleport_by_beeline() {
+        let mut engine = TeleportationEngine::from_config(&Teleportation {
+            modes: vec![TeleportedModeParams {
+                mode: String::from("te-bike"),
+                beeline_speed: 5.,
+                beeline_distance_factor: 1.5,
+            }],
+        });
+        let bike = Id::<String>::get_from_ext("te-bike").internal();
+        let walk = Id::<String>::create("te-walk").internal();
+        let home = Id::<String>::create("te-home").internal();
+        let interaction = Id::<String>::create("te-bike interaction").internal();
+
+        // home - walk - interaction - bike - interaction - walk - home
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., home, 0, None, Some(0), None));
+        plan.add_leg(leg(walk, 7));
+        plan.add_act(Activity::interaction(0, interaction));
+        plan.add_leg(leg(bike, 1));
+        plan.add_act(Activity::interaction(1, interaction));
+        plan.add_leg(leg(walk, 7));
+        plan.add_act(Activity::new(300., 400., home, 1, None, None, None));
+        let mut driver = Person::new(Id::<Person>::create("te-person").internal(), plan);
+        driver.advance_plan();
+
+        // the access leg keeps its travel time
+        let mut vehicle = Vehicle::new(0, 0, 10., 1., Some(driver));
+        engine.depart(&mut vehicle);
+        assert_eq!(7, vehicle.driver().curr_leg().trav_time);
+
+        // the main leg is teleported between the home activities with the speed of the mode
+        let mut driver = vehicle.driver.take().unwrap();
+        driver.advance_plan();
+        driver.advance_plan();
+        let mut vehicle = Vehicle::new(0, 0, 10., 1., Some(driver));
+        engine.depart(&mut vehicle);
+        let leg = vehicle.driver().curr_leg();
+        assert_eq!(750., leg.route.as_ref().unwrap().distance);
+        assert_eq!(150, leg.trav_time);
+
+        engine.add(vehicle, 100);
+        let driver = Id::<Person>::get_from_ext("te-person").internal();
+        let (vehicle, arrival) = engine.take_vehicle_of(driver).unwrap();
+        assert_eq!(250, arrival);
+        assert!(engine.is_empty());
+        engine.add_at(vehicle, arrival);
+        assert!(engine.arrivals(249).is_empty());
+        assert_eq!(1, engine.arrivals(250).len());
+        assert!(engine.is_empty());
+    }
+}
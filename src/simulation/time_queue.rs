@@ -1,82 +1,342 @@
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::BTreeMap;
+
+use nohash_hasher::IntMap;
 
 pub trait EndTime {
     fn end_time(&self, now: u32) -> u32;
 }
 
-struct Entry<T>
-where
-    T: EndTime,
-{
-    end_time: u32,
-    value: T,
+/// Values which can be removed from or rescheduled in a [TimeQueue] by their id.
+pub trait QueueId {
+    fn queue_id(&self) -> u64;
 }
 
-impl<T> PartialEq<Self> for Entry<T>
-where
-    T: EndTime,
-{
-    fn eq(&self, _other: &Self) -> bool {
-        false // how bad is this...
-    }
-}
+/// Number of one second slots of the wheel. Values which are due later are kept in an ordered map
+/// of windows of this size, and are moved into the wheel once their window is reached.
+const WHEEL_SIZE: u32 = 3600;
 
-impl<T> Eq for Entry<T> where T: EndTime {}
-
-impl<T> PartialOrd<Self> for Entry<T>
-where
-    T: EndTime,
-{
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+struct Entry<T> {
+    end_time: u32,
+    value: T,
 }
 
-impl<T> Ord for Entry<T>
+/// Queue of values which are due at a time step, e.g. agents performing an activity. It is a
+/// timer wheel with one slot per second for the current hour. Values due in later hours wait in
+/// buckets per hour. This way, adding and popping a value takes constant time, instead of
+/// log(n) for a binary heap, which pays off for scenarios with millions of agents. An index from
+/// the ids of the values to their slots allows to remove and reschedule values in constant time,
+/// too.
+pub struct TimeQueue<T>
 where
-    T: EndTime,
+    T: EndTime + QueueId,
 {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.end_time.cmp(&self.end_time)
-    }
+    slots: Vec<Vec<Entry<T>>>,
+    /// Values of later windows, keyed by the index of their window.
+    overflow: BTreeMap<u32, Vec<Entry<T>>>,
+    /// Time step at which each value is due. It determines the slot or the overflow window of
+    /// the value. Values can only be removed by their id, if their ids are unique.
+    due: IntMap<u64, u32>,
+    /// The earliest time step which has not been popped yet. Slots hold the values of the window
+    /// of this time step.
+    next: u32,
+    in_wheel: usize,
+    len: usize,
 }
 
-#[derive(Default)]
-pub struct TimeQueue<T>
+impl<T> Default for TimeQueue<T>
 where
-    T: EndTime,
+    T: EndTime + QueueId,
 {
-    q: BinaryHeap<Entry<T>>,
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T> TimeQueue<T>
 where
-    T: EndTime,
+    T: EndTime + QueueId,
 {
     pub fn new() -> Self {
         TimeQueue {
-            q: BinaryHeap::new(),
+            slots: (0..WHEEL_SIZE).map(|_| Vec::new()).collect(),
+            overflow: BTreeMap::new(),
+            due: IntMap::default(),
+            next: 0,
+            in_wheel: 0,
+            len: 0,
         }
     }
 
     pub fn add(&mut self, value: T, now: u32) {
         let end_time = value.end_time(now);
-        self.q.push(Entry { end_time, value });
+        self.add_at(value, end_time);
+    }
+
+    /// Adds a value with an end time, which was computed by the caller.
+    pub fn add_at(&mut self, value: T, end_time: u32) {
+        self.insert(Entry { end_time, value });
+        self.len += 1;
+    }
+
+    fn insert(&mut self, entry: Entry<T>) {
+        // values which are already due are popped with the next call to pop.
+        let due = entry.end_time.max(self.next);
+        self.due.insert(entry.value.queue_id(), due);
+        if self.is_in_wheel(due) {
+            self.in_wheel += 1;
+        }
+        self.bucket_mut(due).push(entry);
+    }
+
+    fn is_in_wheel(&self, due: u32) -> bool {
+        due / WHEEL_SIZE == self.next / WHEEL_SIZE
+    }
+
+    /// The slot or the overflow window, which holds values due at `due`.
+    fn bucket_mut(&mut self, due: u32) -> &mut Vec<Entry<T>> {
+        if self.is_in_wheel(due) {
+            &mut self.slots[(due % WHEEL_SIZE) as usize]
+        } else {
+            self.overflow.entry(due / WHEEL_SIZE).or_default()
+        }
+    }
+
+    fn bucket(&self, due: u32) -> Option<&Vec<Entry<T>>> {
+        if self.is_in_wheel(due) {
+            Some(&self.slots[(due % WHEEL_SIZE) as usize])
+        } else {
+            self.overflow.get(&(due / WHEEL_SIZE))
+        }
     }
 
     pub fn pop(&mut self, now: u32) -> Vec<T> {
         let mut result: Vec<T> = Vec::new();
 
-        while let Some(entry_ref) = self.q.peek() {
-            if entry_ref.end_time <= now {
-                let entry = self.q.pop().unwrap();
+        while self.next <= now {
+            if self.in_wheel == 0 {
+                // nothing is due in the current window. Skip to the next window with values, or to
+                // the next time step, whichever comes first.
+                let next = match self.overflow.keys().next() {
+                    Some(window) if window * WHEEL_SIZE <= now => window * WHEEL_SIZE,
+                    _ => now + 1,
+                };
+                self.advance_to(next);
+                continue;
+            }
+
+            let slot = &mut self.slots[(self.next % WHEEL_SIZE) as usize];
+            self.in_wheel -= slot.len();
+            self.len -= slot.len();
+            for entry in slot.drain(..) {
+                let id = entry.value.queue_id();
+                if self.due.get(&id) == Some(&self.next) {
+                    self.due.remove(&id);
+                }
                 result.push(entry.value);
-            } else {
-                break;
             }
+            self.advance_to(self.next + 1);
         }
 
         result
     }
+
+    fn advance_to(&mut self, next: u32) {
+        let window = next / WHEEL_SIZE;
+        let changes_window = window != self.next / WHEEL_SIZE;
+        self.next = next;
+        if changes_window {
+            if let Some(entries) = self.overflow.remove(&window) {
+                for entry in entries {
+                    self.insert(entry);
+                }
+            }
+        }
+    }
+
+    /// All values in the queue, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots
+            .iter()
+            .chain(self.overflow.values())
+            .flatten()
+            .map(|entry| &entry.value)
+    }
+
+    /// Removes all values for which the predicate holds, regardless of their end time. The
+    /// remaining values keep their end times.
+    pub fn take_where(&mut self, predicate: impl Fn(&T) -> bool) -> Vec<T> {
+        let mut taken = Vec::new();
+        for entries in self.slots.iter_mut().chain(self.overflow.values_mut()) {
+            let (matching, kept): (Vec<_>, Vec<_>) = std::mem::take(entries)
+                .into_iter()
+                .partition(|entry| predicate(&entry.value));
+            *entries = kept;
+            taken.extend(matching.into_iter().map(|entry| entry.value));
+        }
+        for value in &taken {
+            self.due.remove(&value.queue_id());
+        }
+        self.overflow.retain(|_, entries| !entries.is_empty());
+        self.in_wheel = self.slots.iter().map(|slot| slot.len()).sum();
+        self.len -= taken.len();
+        taken
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes the value with the given id, e.g. because it leaves the simulation early.
+    pub fn remove(&mut self, id: u64) -> Option<T> {
+        self.remove_entry(id).map(|entry| {
+            self.len -= 1;
+            entry.value
+        })
+    }
+
+    fn remove_entry(&mut self, id: u64) -> Option<Entry<T>> {
+        let due = self.due.remove(&id)?;
+        let in_wheel = self.is_in_wheel(due);
+        let bucket = self.bucket_mut(due);
+        // keep the order of the remaining values, so that values due at the same time step are
+        // still popped in the order they were added.
+        let index = bucket
+            .iter()
+            .position(|entry| entry.value.queue_id() == id)
+            .unwrap();
+        let entry = bucket.remove(index);
+        if in_wheel {
+            self.in_wheel -= 1;
+        } else if bucket.is_empty() {
+            self.overflow.remove(&(due / WHEEL_SIZE));
+        }
+        Some(entry)
+    }
+
+    /// Moves the value with the given id to a new end time, which may be earlier or later than
+    /// the current one. This is needed, if the end time of an agent's activity is changed during
+    /// the day. Returns whether the value was found.
+    pub fn reschedule(&mut self, id: u64, end_time: u32) -> bool {
+        match self.remove_entry(id) {
+            Some(mut entry) => {
+                entry.end_time = end_time;
+                self.insert(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// End time of the value with the given id, if it is in the queue.
+    pub fn end_time_of(&self, id: u64) -> Option<u32> {
+        let due = self.due.get(&id)?;
+        self.bucket(*due)?
+            .iter()
+            .find(|entry| entry.value.queue_id() == id)
+            .map(|entry| entry.end_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::time_queue::{EndTime, QueueId, TimeQueue};
+
+    struct Wakeup {
+        id: u64,
+        time: u32,
+    }
+
+    impl EndTime for Wakeup {
+        fn end_time(&self, _now: u32) -> u32 {
+            self.time
+        }
+    }
+
+    impl QueueId for Wakeup {
+        fn queue_id(&self) -> u64 {
+            self.id
+        }
+    }
+
+    fn ids(values: Vec<Wakeup>) -> Vec<u64> {
+        values.iter().map(|v| v.id).collect()
+    }
+
+    #[test]
+    fn remove() {
+        let mut q = TimeQueue::new();
+        for id in 0..3 {
+            q.add(Wakeup { id, time: 10 }, 0);
+        }
+
+        assert_eq!(1, q.remove(1).unwrap().id);
+        assert!(q.remove(1).is_none());
+        assert_eq!(2, q.len());
+        let mut woken = ids(q.pop(10));
+        woken.sort();
+        assert_eq!(vec![0, 2], woken);
+    }
+
+    #[test]
+    fn reschedule() {
+        let mut q = TimeQueue::new();
+        q.add(Wakeup { id: 1, time: 10 }, 0);
+        q.add(Wakeup { id: 2, time: 20 }, 0);
+
+        // the first value wakes up later, the second one earlier
+        assert!(q.reschedule(1, 30));
+        assert!(q.reschedule(2, 5));
+        assert!(!q.reschedule(3, 5));
+        assert_eq!(Some(30), q.end_time_of(1));
+
+        assert_eq!(vec![2], ids(q.pop(10)));
+        assert!(q.pop(29).is_empty());
+        assert_eq!(vec![1], ids(q.pop(30)));
+    }
+
+    #[test]
+    fn remove_and_reschedule_across_windows() {
+        let mut q = TimeQueue::new();
+        q.add(Wakeup { id: 1, time: 7300 }, 0);
+        q.add(Wakeup { id: 2, time: 10 }, 0);
+        q.add(Wakeup { id: 3, time: 3700 }, 0);
+
+        // move values between the wheel and later windows
+        assert!(q.reschedule(1, 20));
+        assert!(q.reschedule(2, 4000));
+        assert_eq!(Some(20), q.end_time_of(1));
+        assert_eq!(Some(4000), q.end_time_of(2));
+        assert_eq!(3, q.remove(3).unwrap().id);
+        assert_eq!(None, q.end_time_of(3));
+        assert_eq!(2, q.len());
+
+        assert_eq!(vec![1], ids(q.pop(3999)));
+        assert_eq!(None, q.end_time_of(1));
+        assert_eq!(vec![2], ids(q.pop(4000)));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn pop_in_order_across_windows() {
+        let mut q = TimeQueue::new();
+        for (id, time) in [(1, 7300), (2, 5), (3, 3599), (4, 3600), (5, u32::MAX)] {
+            q.add(Wakeup { id, time }, 0);
+        }
+
+        assert!(q.pop(4).is_empty());
+        assert_eq!(vec![2, 3], ids(q.pop(3599)));
+        assert_eq!(vec![4], ids(q.pop(3600)));
+        // jumping ahead pops everything which is due in between
+        assert_eq!(vec![1], ids(q.pop(86400)));
+        assert_eq!(1, q.len());
+
+        // values which are due already are popped in the next time step
+        q.add(Wakeup { id: 6, time: 0 }, 86400);
+        assert_eq!(vec![6], ids(q.pop(86401)));
+        assert_eq!(Some(u32::MAX), q.end_time_of(5));
+    }
 }
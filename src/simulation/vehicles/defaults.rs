@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+
+use crate::simulation::id::Id;
+use crate::simulation::population::population::Population;
+use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
+use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+/// Vehicle type which is used for a mode, if no vehicles file is given. Cars and bikes are
+/// simulated on the network, all other modes are teleported. The vehicle type has the same id as
+/// the mode.
+pub fn default_vehicle_type(mode: &str) -> VehicleType {
+    let (lod, max_v, pce, length, seats) = match mode {
+        "car" => (LevelOfDetail::Network, 36.11, 1., 7.5, 4),
+        "bike" => (LevelOfDetail::Network, 4.17, 0.25, 2., 1),
+        "walk" => (LevelOfDetail::Teleported, 0.83, 0., 0., 1),
+        // e.g. pt or ride, which are usually teleported with about the speed of a car in a city.
+        _ => (LevelOfDetail::Teleported, 8.33, 0., 0., 1),
+    };
+    VehicleType {
+        id: Id::<VehicleType>::create(mode).internal(),
+        length,
+        width: 1.,
+        max_v,
+        pce,
+        fef: 0.,
+        net_mode: Id::<String>::create(mode).internal(),
+        lod: lod as i32,
+        seats,
+        standing_room: 0,
+        attributes: Default::default(),
+    }
+}
+
+/// Vehicle type with its own id and network mode, which inherits all other fields from `parent`.
+/// This is less error-prone than stating all fields, when vehicle types are created in code.
+pub fn inherit_vehicle_type(parent: &VehicleType, id: &str, mode: &str) -> VehicleType {
+    VehicleType {
+        id: Id::<VehicleType>::create(id).internal(),
+        net_mode: Id::<String>::create(mode).internal(),
+        ..parent.clone()
+    }
+}
+
+/// Creates a default vehicle type for each mode of the population, for which there is no vehicle
+/// type yet, and gives each person a vehicle of these types. Vehicles referenced by routes, which
+/// are not known to the garage, are registered with the vehicle type of the leg's mode. This way,
+/// simple scenarios can be run without a vehicles file.
+pub fn add_default_vehicles(garage: &mut Garage, population: &Population) {
+    // sorted, so that the ids of the vehicle types don't depend on the order of the population.
+    let modes: BTreeSet<String> = population
+        .persons
+        .values()
+        .flat_map(|p| p.plan.iter().flat_map(|plan| plan.legs.iter()))
+        .filter(|leg| !leg.is_ride())
+        .map(|leg| Id::<String>::get(leg.mode).external().to_string())
+        .collect();
+
+    let mut new_types = Vec::new();
+    for mode in modes {
+        let veh_type = default_vehicle_type(&mode);
+        let type_id: Id<VehicleType> = Id::get(veh_type.id);
+        if garage.vehicle_types.contains_key(&type_id) {
+            continue;
+        }
+        Id::<String>::create(&format!("{mode} interaction"));
+        garage.add_veh_type(veh_type);
+        new_types.push(type_id);
+    }
+
+    let mut person_ids: Vec<&Id<Person>> = population.persons.keys().collect();
+    person_ids.sort_by_key(|id| id.internal());
+    for person_id in person_ids {
+        for type_id in &new_types {
+            garage.add_veh_id(person_id, type_id);
+        }
+        let person = population.persons.get(person_id).unwrap();
+        for leg in person.plan.iter().flat_map(|plan| plan.legs.iter()) {
+            let route = match leg.route.as_ref() {
+                Some(route) if !leg.is_ride() => route,
+                _ => continue,
+            };
+            let veh_id: Id<Vehicle> = Id::get(route.veh_id);
+            if garage.vehicles.contains_key(&veh_id) {
+                continue;
+            }
+            let mode: Id<String> = Id::get(leg.mode);
+            garage
+                .vehicles
+                .insert(veh_id.clone(), Id::get_from_ext(mode.external()));
+            garage.set_owner(veh_id, person_id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::id::Id;
+    use crate::simulation::population::population::Population;
+    use crate::simulation::vehicles::defaults::{
+        add_default_vehicles, default_vehicle_type, inherit_vehicle_type,
+    };
+    use crate::simulation::vehicles::garage::Garage;
+    use crate::simulation::wire_types::messages::Vehicle;
+    use crate::simulation::wire_types::population::{Activity, Leg, Person, Plan, Route};
+    use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
+
+    #[test]
+    fn create_vehicles_per_mode() {
+        let person_id: Id<Person> = Id::create("dv-p");
+        let mut plan = Plan::new();
+        plan.add_act(Activity::new(0., 0., 0, 0, None, Some(0), None));
+        for mode in ["car", "walk"] {
+            let veh_id: Id<Vehicle> = Id::create(&format!("dv-p_{mode}"));
+            plan.add_leg(Leg::new(
+                Route {
+                    veh_id: veh_id.internal(),
+                    distance: 0.,
+                    route: vec![0, 1],
+                },
+                Id::<String>::create(mode).internal(),
+                0,
+                None,
+            ));
+            plan.add_act(Activity::new(0., 0., 0, 1, None, None, None));
+        }
+        let mut population = Population::new();
+        population
+            .persons
+            .insert(person_id.clone(), Person::new(person_id.internal(), plan));
+        let mut garage = Garage::new();
+
+        add_default_vehicles(&mut garage, &population);
+
+        assert_eq!(2, garage.vehicle_types.len());
+        let car = garage
+            .vehicle_types
+            .get(&Id::<VehicleType>::get_from_ext("car"))
+            .unwrap();
+        assert_eq!(LevelOfDetail::Network, car.lod());
+        let walk = garage
+            .vehicle_types
+            .get(&Id::<VehicleType>::get_from_ext("walk"))
+            .unwrap();
+        assert_eq!(LevelOfDetail::Teleported, walk.lod());
+        assert_eq!(
+            "dv-p_car",
+            garage
+                .veh_id(&person_id, &Id::get_from_ext("car"))
+                .external()
+        );
+        assert_eq!(2, garage.vehicles.len());
+    }
+
+    #[test]
+    fn inherit_from_default_type() {
+        let car = default_vehicle_type("car");
+        let truck = inherit_vehicle_type(&car, "dv-truck", "dv-freight");
+
+        assert_eq!(
+            Id::<VehicleType>::get_from_ext("dv-truck").internal(),
+            truck.id
+        );
+        assert_eq!(
+            Id::<String>::get_from_ext("dv-freight").internal(),
+            truck.net_mode
+        );
+        assert_eq!(car.max_v, truck.max_v);
+        assert_eq!(car.pce, truck.pce);
+        assert_eq!(car.length, truck.length);
+        assert_eq!(car.lod, truck.lod);
+    }
+}
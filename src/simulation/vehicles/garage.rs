@@ -1,17 +1,90 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use nohash_hasher::IntMap;
+use nohash_hasher::{IntMap, IntSet};
 
+use crate::simulation::id::id_map::IdMap;
 use crate::simulation::id::Id;
-use crate::simulation::vehicles::io::{from_file, to_file};
+use crate::simulation::network::global_network::Link;
+use crate::simulation::vehicles::io::{from_file, households_from_file, merge_xml_files, to_file};
 use crate::simulation::wire_types::messages::Vehicle;
 use crate::simulation::wire_types::population::Person;
-use crate::simulation::wire_types::vehicles::VehicleType;
+use crate::simulation::wire_types::vehicles::{Household, VehicleType};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Garage {
-    pub vehicles: IntMap<Id<Vehicle>, Id<VehicleType>>,
-    pub vehicle_types: IntMap<Id<VehicleType>, VehicleType>,
+    pub vehicles: IdMap<Vehicle, Id<VehicleType>>,
+    pub vehicle_types: IdMap<VehicleType, VehicleType>,
+    pub households: IntMap<Id<Household>, Household>,
+    person_2_household: IntMap<Id<Person>, Id<Household>>,
+    household_vehicles: IntSet<Id<Vehicle>>,
+    // household vehicles which are parked on this partition and can be used by a member
+    parked_household_vehicles: IntSet<Id<Vehicle>>,
+    parking_facilities: IntMap<Id<Link>, ParkingFacility>,
+    parked_at: IntMap<Id<Vehicle>, Id<Link>>,
+    driver_modes: IntSet<Id<String>>,
+    person_vehicles: IntMap<Id<Person>, IntMap<Id<VehicleType>, Id<Vehicle>>>,
+    /// Attributes of vehicles from the input. They are written back to output vehicle files. Apart
+    /// from the pce of a trailer, they are not interpreted by the simulation.
+    pub vehicle_attributes: IntMap<Id<Vehicle>, HashMap<String, String>>,
+}
+
+/// Vehicle attribute with the passenger car equivalents of a trailer, e.g. of a truck with trailer.
+/// They are added to the pce of the vehicle type.
+const TRAILER_PCE_ATTRIBUTE: &str = "trailerPce";
+/// Vehicle type attribute with the passenger car equivalents each passenger adds to a vehicle, e.g.
+/// for buses which carry the bikes of their passengers.
+const PCE_PER_PASSENGER_ATTRIBUTE: &str = "pcePerPassenger";
+
+fn pce_attribute(attributes: &HashMap<String, String>, key: &str) -> Option<f32> {
+    attributes.get(key).map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Attribute {key} must be a number, but was {value}."))
+    })
+}
+
+/// A person may neither use the requested vehicle type nor any of the fallback types.
+#[derive(Debug, PartialEq)]
+pub struct NoFeasibleMode {
+    pub person: Id<Person>,
+    pub requested: Id<VehicleType>,
+}
+
+/// A route names a vehicle, which is not part of the garage.
+#[derive(Debug, PartialEq)]
+pub struct UnknownVehicle {
+    pub person: Id<Person>,
+    pub vehicle: Id<Vehicle>,
+}
+
+/// Parking facility with a limited number of spots, e.g. at a park and ride station. Vehicles
+/// are still parked if the facility is full. Those vehicles are counted as overflow.
+#[derive(Debug, Default, Clone)]
+pub struct ParkingFacility {
+    pub capacity: u32,
+    parked: IntSet<Id<Vehicle>>,
+}
+
+impl ParkingFacility {
+    pub fn occupancy(&self) -> u32 {
+        self.parked.len() as u32
+    }
+
+    pub fn overflow(&self) -> u32 {
+        self.occupancy().saturating_sub(self.capacity)
+    }
+}
+
+/// Result of looking up which vehicle a person may use for a leg.
+#[derive(Debug, PartialEq)]
+pub enum VehicleAssignment {
+    /// The person may use the vehicle with this id. This is either the vehicle stated in the route,
+    /// or a vehicle from the household pool.
+    Assigned(Id<Vehicle>),
+    /// All vehicles of the requested type in the person's household are in use by other members.
+    Unavailable(Id<Household>),
 }
 
 #[derive(Debug)]
@@ -31,6 +104,15 @@ impl Garage {
         Garage {
             vehicles: Default::default(),
             vehicle_types: Default::default(),
+            households: Default::default(),
+            person_2_household: Default::default(),
+            household_vehicles: Default::default(),
+            parked_household_vehicles: Default::default(),
+            parking_facilities: Default::default(),
+            parked_at: Default::default(),
+            driver_modes: Default::default(),
+            person_vehicles: Default::default(),
+            vehicle_attributes: Default::default(),
         }
     }
 
@@ -42,6 +124,151 @@ impl Garage {
         to_file(self, file_path);
     }
 
+    /// File to which a partition writes its vehicles after the run. See [Garage::merge_partitions].
+    pub fn partition_file(output_dir: &Path, rank: u32) -> PathBuf {
+        output_dir.join(format!("output_vehicles.{rank}.xml.gz"))
+    }
+
+    /// Merges the vehicles files of all partitions into `output_vehicles.xml.gz` and removes them.
+    /// Partitions only create the vehicles of the persons they load, so that the garage of a single
+    /// partition doesn't contain all vehicles.
+    pub fn merge_partitions(output_dir: &Path, num_parts: u32) {
+        let paths: Vec<_> = (0..num_parts)
+            .map(|rank| Self::partition_file(output_dir, rank))
+            .collect();
+        merge_xml_files(&paths, &output_dir.join("output_vehicles.xml.gz"));
+        for path in paths {
+            fs::remove_file(&path)
+                .unwrap_or_else(|_| panic!("Failed to remove vehicles file {path:?}"));
+        }
+    }
+
+    /// Reads households from a matsim households file. The vehicle ids referenced by the households
+    /// must be known to the garage already. Therefore, this should be called after the population
+    /// was loaded.
+    pub fn add_households_from_file(&mut self, file_path: &Path) {
+        for household in households_from_file(file_path) {
+            self.add_household(household);
+        }
+    }
+
+    pub fn add_household(&mut self, household: Household) {
+        let id: Id<Household> = Id::get(household.id);
+        assert!(
+            !self.households.contains_key(&id),
+            "Household with id {id} already exists."
+        );
+
+        for veh_id in &household.vehicles {
+            let veh_id: Id<Vehicle> = Id::get(*veh_id);
+            assert!(
+                self.vehicles.contains_key(&veh_id),
+                "Vehicle {veh_id} of household {id} is not known to this garage."
+            );
+            self.household_vehicles.insert(veh_id);
+        }
+
+        for member in &household.members {
+            self.person_2_household.insert(Id::get(*member), id.clone());
+        }
+        self.households.insert(id, household);
+    }
+
+    /// Households are known to all partitions, but their vehicles can only be used on the
+    /// partition they are parked at. Initially, the vehicles of a household are parked on the
+    /// partition of its members, which are passed as `persons`.
+    pub fn park_household_vehicles<'a>(
+        &mut self,
+        persons: impl IntoIterator<Item = &'a Id<Person>>,
+    ) {
+        for person_id in persons {
+            let Some(household_id) = self.person_2_household.get(person_id) else {
+                continue;
+            };
+            let household = self.households.get(household_id).unwrap();
+            self.parked_household_vehicles
+                .extend(household.vehicles.iter().map(|id| Id::<Vehicle>::get(*id)));
+        }
+    }
+
+    pub fn add_parking_facility(&mut self, link_id: Id<Link>, capacity: u32) {
+        assert!(
+            !self.parking_facilities.contains_key(&link_id),
+            "Parking facility at link {link_id} already exists."
+        );
+        self.parking_facilities.insert(
+            link_id,
+            ParkingFacility {
+                capacity,
+                ..ParkingFacility::default()
+            },
+        );
+    }
+
+    pub fn parking_facility(&self, link_id: &Id<Link>) -> Option<&ParkingFacility> {
+        self.parking_facilities.get(link_id)
+    }
+
+    /// Parks a vehicle at the parking facility of a link. The vehicle stays there until it is
+    /// unparked by a leg starting at the same link. Returns false, if the facility was full
+    /// already and the vehicle is parked as overflow.
+    pub fn park_at_facility(&mut self, veh_id: &Id<Vehicle>, link_id: &Id<Link>) -> bool {
+        let facility = self
+            .parking_facilities
+            .get_mut(link_id)
+            .unwrap_or_else(|| panic!("There is no parking facility at link {link_id}."));
+        let has_free_spot = facility.occupancy() < facility.capacity;
+        facility.parked.insert(veh_id.clone());
+        self.parked_at.insert(veh_id.clone(), link_id.clone());
+        has_free_spot
+    }
+
+    /// Link of the parking facility, at which a vehicle is parked.
+    pub fn parked_at(&self, veh_id: &Id<Vehicle>) -> Option<&Id<Link>> {
+        self.parked_at.get(veh_id)
+    }
+
+    /// Decides which vehicle a person may use, when the person's route states the vehicle with
+    /// `requested` id. If the person is member of a household, which has vehicles of the requested
+    /// vehicle type in its pool, the first pool vehicle parked on this partition is assigned. Pool
+    /// vehicles which are in use by another member or parked on another partition are unavailable.
+    /// Otherwise, the requested vehicle is assigned. Returns an error, if the requested vehicle is
+    /// not part of the garage.
+    pub fn assign_veh(
+        &mut self,
+        person_id: &Id<Person>,
+        requested: &Id<Vehicle>,
+    ) -> Result<VehicleAssignment, UnknownVehicle> {
+        let requested_type = self.vehicles.get(requested).ok_or_else(|| UnknownVehicle {
+            person: person_id.clone(),
+            vehicle: requested.clone(),
+        })?;
+        let household_id = if let Some(id) = self.person_2_household.get(person_id) {
+            id
+        } else {
+            return Ok(VehicleAssignment::Assigned(requested.clone()));
+        };
+
+        let household = self.households.get(household_id).unwrap();
+        let mut pool = household
+            .vehicles
+            .iter()
+            .map(|id| Id::<Vehicle>::get(*id))
+            .filter(|id| self.vehicles.get(id) == Some(requested_type))
+            .peekable();
+
+        if pool.peek().is_none() {
+            return Ok(VehicleAssignment::Assigned(requested.clone()));
+        }
+
+        if let Some(free) = pool.find(|id| self.parked_household_vehicles.contains(id)) {
+            self.parked_household_vehicles.remove(&free);
+            Ok(VehicleAssignment::Assigned(free))
+        } else {
+            Ok(VehicleAssignment::Unavailable(household_id.clone()))
+        }
+    }
+
     pub fn add_veh_type(&mut self, veh_type: VehicleType) {
         assert!(
             !self.vehicle_types.contains_key(&Id::get(veh_type.id)),
@@ -53,15 +280,43 @@ impl Garage {
     }
 
     pub fn add_veh_id(&mut self, person_id: &Id<Person>, type_id: &Id<VehicleType>) -> Id<Vehicle> {
+        // vehicle ids are derived from the person's external id, which is unknown for compact ids.
+        assert!(
+            !person_id.external().is_empty(),
+            "Can't create a vehicle for person {}, which has no external id. Vehicles can't be created for ids loaded in compact mode.",
+            person_id.internal()
+        );
         let veh_id_ext = format!("{}_{}", person_id.external(), type_id.external());
         let veh_id = Id::create(&veh_id_ext);
 
         let veh_type = self.vehicle_types.get(type_id).unwrap();
         self.vehicles.insert(veh_id.clone(), Id::get(veh_type.id));
+        self.set_owner(veh_id.clone(), person_id.clone());
 
         veh_id
     }
 
+    /// Registers the person a vehicle was created for.
+    pub fn set_owner(&mut self, veh_id: Id<Vehicle>, person_id: Id<Person>) {
+        let type_id = self
+            .vehicles
+            .get(&veh_id)
+            .unwrap_or_else(|| panic!("Vehicle {veh_id} must be added before its owner is set."))
+            .clone();
+        self.person_vehicles
+            .entry(person_id)
+            .or_default()
+            .insert(type_id, veh_id);
+    }
+
+    /// Person a vehicle was created for.
+    pub fn owners(&self) -> IntMap<&Id<Vehicle>, &Id<Person>> {
+        self.person_vehicles
+            .iter()
+            .flat_map(|(person_id, vehicles)| vehicles.values().map(move |v| (v, person_id)))
+            .collect()
+    }
+
     pub fn add_veh(&mut self, _veh_id: Id<Vehicle>, _veh_type_id: Id<VehicleType>) {
         panic!(
             "This method can only be used with chained modes. Which is currently not implemented"
@@ -82,12 +337,66 @@ impl Garage {
          */
     }
 
+    /// Vehicle type by its internal id. Vehicles only store the internal id of their type, so this
+    /// avoids creating an [Id] on every departure and arrival.
+    pub fn veh_type(&self, type_id: u64) -> &VehicleType {
+        self.vehicle_types
+            .get_internal(type_id)
+            .unwrap_or_else(|| panic!("There is no vehicle type with internal id {type_id}."))
+    }
+
+    /// Vehicles with this network mode may only be used by persons who can drive.
+    pub fn restrict_to_drivers(&mut self, mode: Id<String>) {
+        self.driver_modes.insert(mode);
+    }
+
+    pub fn is_available(&self, person: &Person, veh_type_id: &Id<VehicleType>) -> bool {
+        let veh_type = self.vehicle_types.get(veh_type_id).unwrap();
+        !self.driver_modes.contains(&Id::get(veh_type.net_mode)) || person.can_drive()
+    }
+
+    /// Looks up the vehicle of a person for a vehicle type, respecting the person's mode
+    /// availability. If the person may not use the requested type, the person's vehicle of the first
+    /// available fallback type is returned.
+    pub fn get_veh_id(
+        &self,
+        person: &Person,
+        veh_type_id: &Id<VehicleType>,
+        fallback_types: &[Id<VehicleType>],
+    ) -> Result<Id<Vehicle>, NoFeasibleMode> {
+        let person_id = Id::get(person.id);
+        std::iter::once(veh_type_id)
+            .chain(fallback_types.iter())
+            .find(|type_id| self.is_available(person, type_id))
+            .map(|type_id| self.veh_id(&person_id, type_id))
+            .ok_or_else(|| NoFeasibleMode {
+                person: person_id,
+                requested: veh_type_id.clone(),
+            })
+    }
+
+    /// Vehicle of a person for a vehicle type. Vehicles with a registered owner are looked up by
+    /// their internal ids. Otherwise, the vehicle id is derived from the external ids of the person
+    /// and the vehicle type.
     pub fn veh_id(&self, person_id: &Id<Person>, veh_type_id: &Id<VehicleType>) -> Id<Vehicle> {
+        if let Some(veh_id) = self
+            .person_vehicles
+            .get(person_id)
+            .and_then(|vehicles| vehicles.get(veh_type_id))
+        {
+            return veh_id.clone();
+        }
         let external = format!("{}_{}", person_id.external(), veh_type_id.external());
         Id::get_from_ext(&external)
     }
 
     pub(crate) fn park_veh(&mut self, vehicle: Vehicle) -> Person {
+        // household vehicles become available for other members on the partition the trip ends on.
+        let veh_id = Id::get(vehicle.id);
+        if self.household_vehicles.contains(&veh_id) {
+            self.parked_household_vehicles.insert(veh_id);
+        }
+
         /*let id = self.vehicle_ids.get(vehicle.id);
         let veh_type = self.vehicle_type_ids.get(vehicle.r#type);
         let garage_veh = GarageVehicle { id, veh_type };
@@ -103,6 +412,17 @@ impl Garage {
     }
 
     pub fn unpark_veh(&mut self, person: Person, id: &Id<Vehicle>) -> Vehicle {
+        // vehicles left at a parking facility are expected to be picked up there. If the leg starts
+        // elsewhere, e.g. because the plan was changed, the vehicle is taken from the facility
+        // anyway. The caller may check [Garage::parked_at] beforehand to report this.
+        if let Some(link_id) = self.parked_at.remove(id) {
+            self.parking_facilities
+                .get_mut(&link_id)
+                .unwrap()
+                .parked
+                .remove(id);
+        }
+
         let veh_type_id = self
             .vehicles
             .get(id)
@@ -126,26 +446,36 @@ impl Garage {
         // we just create vehicles on the fly.
 
         let veh_type = self.vehicle_types.get(veh_type_id).unwrap();
+        let trailer_pce = self
+            .vehicle_attributes
+            .get(id)
+            .and_then(|attributes| pce_attribute(attributes, TRAILER_PCE_ATTRIBUTE))
+            .unwrap_or(0.);
 
-        Vehicle {
-            id: id.internal(),
-            curr_route_elem: 0,
-            r#type: veh_type.id,
-            max_v: veh_type.max_v,
-            pce: veh_type.pce,
-            driver: Some(person),
-            passengers: vec![],
-        }
+        let mut vehicle = Vehicle::from_type(id.internal(), veh_type, Some(person))
+            .with_pce_per_passenger(
+                pce_attribute(&veh_type.attributes, PCE_PER_PASSENGER_ATTRIBUTE).unwrap_or(0.),
+            );
+        vehicle.base_pce += trailer_pce;
+        vehicle
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
+    use assert_approx_eq::assert_approx_eq;
+
     use crate::simulation::id::Id;
-    use crate::simulation::vehicles::garage::Garage;
-    use crate::test_utils::create_vehicle_type;
+    use crate::simulation::network::global_network::Link;
+    use crate::simulation::vehicles::garage::{
+        Garage, NoFeasibleMode, UnknownVehicle, VehicleAssignment,
+    };
+    use crate::simulation::wire_types::population::{Person, Plan};
+    use crate::simulation::wire_types::vehicles::{Household, VehicleType};
+    use crate::test_utils::{create_agent, create_vehicle_type};
 
     #[test]
     fn add_veh_type() {
@@ -172,9 +502,253 @@ mod tests {
         garage.add_veh_type(veh_type2);
     }
 
+    #[test]
+    fn merge_partitions() {
+        let output_dir = PathBuf::from("./test_output/simulation/vehicles/garage/merge_partitions");
+        let type_id = Id::create("merge-type");
+        let mode = Id::create("car");
+        // each partition only knows the vehicle of its own person.
+        for (rank, person) in [(0, "merge-person-0"), (1, "merge-person-1")] {
+            let mut garage = Garage::new();
+            garage.add_veh_type(create_vehicle_type(&type_id, mode.clone()));
+            garage.add_veh_id(&Id::create(person), &type_id);
+            garage.to_file(&Garage::partition_file(&output_dir, rank));
+        }
+
+        Garage::merge_partitions(&output_dir, 2);
+
+        let merged = Garage::from_file(&output_dir.join("output_vehicles.xml.gz"));
+        assert_eq!(1, merged.vehicle_types.len());
+        assert_eq!(2, merged.vehicles.len());
+        assert!(!Garage::partition_file(&output_dir, 0).exists());
+        assert!(!Garage::partition_file(&output_dir, 1).exists());
+    }
+
     #[test]
     fn from_file() {
         let garage = Garage::from_file(&PathBuf::from("./assets/3-links/vehicles.xml"));
         assert_eq!(3, garage.vehicle_types.len());
     }
+
+    #[test]
+    fn get_veh_id_with_mode_availability() {
+        let mut garage = Garage::new();
+        let car: Id<VehicleType> = Id::create("car");
+        let walk: Id<VehicleType> = Id::create("walk");
+        garage.add_veh_type(create_vehicle_type(&car, Id::create("car")));
+        garage.add_veh_type(create_vehicle_type(&walk, Id::create("walk")));
+        garage.restrict_to_drivers(Id::get_from_ext("car"));
+        let person_id: Id<Person> = Id::create("person");
+        let car_veh = garage.add_veh_id(&person_id, &car);
+        let walk_veh = garage.add_veh_id(&person_id, &walk);
+        let mut person = Person::new(person_id.internal(), Plan::new());
+
+        assert_eq!(
+            Ok(car_veh),
+            garage.get_veh_id(&person, &car, &[walk.clone()])
+        );
+
+        person.set_attribute(Person::LICENSE, "no");
+        assert_eq!(
+            Ok(walk_veh),
+            garage.get_veh_id(&person, &car, &[walk.clone()])
+        );
+        assert_eq!(
+            Err(NoFeasibleMode {
+                person: person_id,
+                requested: car.clone()
+            }),
+            garage.get_veh_id(&person, &car, &[])
+        );
+    }
+
+    #[test]
+    fn park_at_facility() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("car");
+        garage.add_veh_type(create_vehicle_type(&type_id, Id::create("car")));
+        let link_id = Id::create("p+r");
+        garage.add_parking_facility(link_id.clone(), 1);
+        let veh_1 = garage.add_veh_id(&Id::create("person-1"), &type_id);
+        let veh_2 = garage.add_veh_id(&Id::create("person-2"), &type_id);
+
+        assert!(garage.park_at_facility(&veh_1, &link_id));
+        assert!(!garage.park_at_facility(&veh_2, &link_id));
+        let facility = garage.parking_facility(&link_id).unwrap();
+        assert_eq!(2, facility.occupancy());
+        assert_eq!(1, facility.overflow());
+
+        // picking up the vehicle at the facility frees the spot
+        let agent = create_agent(
+            Id::<Person>::get_from_ext("person-1").internal(),
+            vec![link_id.internal()],
+        );
+        garage.unpark_veh(agent, &veh_1);
+        assert_eq!(1, garage.parking_facility(&link_id).unwrap().occupancy());
+    }
+
+    #[test]
+    fn unpark_at_other_link() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("car");
+        garage.add_veh_type(create_vehicle_type(&type_id, Id::create("car")));
+        let link_id: Id<Link> = Id::create("p+r");
+        let other_link: Id<Link> = Id::create("other");
+        garage.add_parking_facility(link_id.clone(), 1);
+        let veh_id = garage.add_veh_id(&Id::create("person"), &type_id);
+        garage.park_at_facility(&veh_id, &link_id);
+        assert_eq!(Some(&link_id), garage.parked_at(&veh_id));
+
+        // the vehicle is taken from the facility, even though the leg starts elsewhere.
+        let agent = create_agent(
+            Id::<Person>::get_from_ext("person").internal(),
+            vec![other_link.internal()],
+        );
+        garage.unpark_veh(agent, &veh_id);
+        assert_eq!(None, garage.parked_at(&veh_id));
+        assert_eq!(0, garage.parking_facility(&link_id).unwrap().occupancy());
+    }
+
+    #[test]
+    fn assign_veh_without_household() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("car");
+        garage.add_veh_type(create_vehicle_type(&type_id, Id::create("car")));
+        let person_id = Id::create("person");
+        let veh_id = garage.add_veh_id(&person_id, &type_id);
+
+        assert_eq!(
+            Ok(VehicleAssignment::Assigned(veh_id.clone())),
+            garage.assign_veh(&person_id, &veh_id)
+        );
+        // vehicles outside households are never blocked
+        assert_eq!(
+            Ok(VehicleAssignment::Assigned(veh_id.clone())),
+            garage.assign_veh(&person_id, &veh_id)
+        );
+        let unknown = Id::create("unknown-vehicle");
+        assert_eq!(
+            Err(UnknownVehicle {
+                person: person_id.clone(),
+                vehicle: unknown.clone()
+            }),
+            garage.assign_veh(&person_id, &unknown)
+        );
+    }
+
+    #[test]
+    fn assign_veh_from_household_pool() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("car");
+        garage.add_veh_type(create_vehicle_type(&type_id, Id::create("car")));
+        let person_1: Id<Person> = Id::create("person-1");
+        let person_2: Id<Person> = Id::create("person-2");
+        let veh_1 = garage.add_veh_id(&person_1, &type_id);
+        let veh_2 = garage.add_veh_id(&person_2, &type_id);
+        garage.add_household(Household {
+            id: Id::<Household>::create("household").internal(),
+            members: vec![person_1.internal(), person_2.internal()],
+            vehicles: vec![veh_1.internal()],
+        });
+        // the household is not on this partition yet
+        assert_eq!(
+            Ok(VehicleAssignment::Unavailable(Id::get_from_ext(
+                "household"
+            ))),
+            garage.assign_veh(&person_2, &veh_2)
+        );
+        garage.park_household_vehicles([&person_1]);
+
+        // person 2 gets the shared vehicle instead of its own one.
+        assert_eq!(
+            Ok(VehicleAssignment::Assigned(veh_1.clone())),
+            garage.assign_veh(&person_2, &veh_2)
+        );
+        // person 1 can't use the car anymore
+        assert_eq!(
+            Ok(VehicleAssignment::Unavailable(Id::get_from_ext(
+                "household"
+            ))),
+            garage.assign_veh(&person_1, &veh_1)
+        );
+
+        // once the car is parked, it is available again
+        let agent = create_agent(person_2.internal(), vec![]);
+        let vehicle = garage.unpark_veh(agent, &veh_1);
+        garage.park_veh(vehicle);
+        assert_eq!(
+            Ok(VehicleAssignment::Assigned(veh_1.clone())),
+            garage.assign_veh(&person_1, &veh_1)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_household_reject_duplicate() {
+        let mut garage = Garage::new();
+        let id = Id::<Household>::create("household").internal();
+        garage.add_household(Household {
+            id,
+            members: vec![],
+            vehicles: vec![],
+        });
+        garage.add_household(Household {
+            id,
+            members: vec![],
+            vehicles: vec![],
+        });
+    }
+
+    #[test]
+    fn unpark_veh_with_passenger_capacity() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("car");
+        let mut veh_type = create_vehicle_type(&type_id, Id::create("car"));
+        veh_type.seats = 3;
+        garage.add_veh_type(veh_type);
+        let person_id: Id<Person> = Id::create("driver");
+        let veh_id = garage.add_veh_id(&person_id, &type_id);
+
+        let mut vehicle = garage.unpark_veh(create_agent(person_id.internal(), vec![]), &veh_id);
+        assert_eq!(2, vehicle.free_seats());
+
+        assert!(vehicle.board(create_agent(1, vec![])).is_ok());
+        assert!(vehicle.board(create_agent(2, vec![])).is_ok());
+        let rejected = vehicle.board(create_agent(3, vec![]));
+        assert_eq!(3, rejected.unwrap_err().id);
+        assert_eq!(0, vehicle.free_seats());
+
+        let passengers = vehicle.alight_all();
+        assert_eq!(2, passengers.len());
+        assert_eq!(2, vehicle.free_seats());
+    }
+
+    #[test]
+    fn unpark_veh_with_pce_attributes() {
+        let mut garage = Garage::new();
+        let type_id = Id::create("bus-with-bikes");
+        let mut veh_type = create_vehicle_type(&type_id, Id::create("car"));
+        veh_type.pce = 3.;
+        veh_type.seats = 10;
+        veh_type
+            .attributes
+            .insert(String::from("pcePerPassenger"), String::from("0.1"));
+        garage.add_veh_type(veh_type);
+        let person_id: Id<Person> = Id::create("bus-driver");
+        let veh_id = garage.add_veh_id(&person_id, &type_id);
+        garage.vehicle_attributes.insert(
+            veh_id.clone(),
+            HashMap::from([(String::from("trailerPce"), String::from("1"))]),
+        );
+
+        let mut vehicle = garage.unpark_veh(create_agent(person_id.internal(), vec![]), &veh_id);
+        assert_eq!(4., vehicle.pce());
+
+        vehicle.board(create_agent(1, vec![])).unwrap();
+        vehicle.board(create_agent(2, vec![])).unwrap();
+        assert_approx_eq!(4.2, vehicle.pce());
+
+        vehicle.alight_all();
+        assert_eq!(4., vehicle.pce());
+    }
 }
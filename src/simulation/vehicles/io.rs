@@ -1,4 +1,5 @@
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use tracing::info;
@@ -8,10 +9,19 @@ use crate::simulation::id::Id;
 use crate::simulation::io::attributes::{Attr, Attrs};
 use crate::simulation::io::xml;
 use crate::simulation::vehicles::garage::Garage;
+use crate::simulation::wire_types::messages::Vehicle;
+use crate::simulation::wire_types::population::Person;
 use crate::simulation::wire_types::vehicles::{
-    LevelOfDetail, VehicleToType, VehicleType, VehiclesContainer,
+    Household, LevelOfDetail, VehicleToType, VehicleType, VehiclesContainer,
 };
 
+/// Name of the vehicle attribute, which references the person a vehicle was created for.
+const OWNER_ATTRIBUTE: &str = "owner";
+const LOD_ATTRIBUTE: &str = "lod";
+/// Name of the vehicle type attribute, which references the vehicle type, from which unspecified
+/// fields are inherited. The referenced type must be defined before in the same file.
+const INHERITS_ATTRIBUTE: &str = "inherits";
+
 pub fn from_file(path: &Path) -> Garage {
     if path.extension().unwrap().eq("binpb") {
         load_from_proto(path)
@@ -35,9 +45,15 @@ pub fn to_file(garage: &Garage, path: &Path) {
 fn load_from_xml(path: &Path) -> Garage {
     let io_vehicles = IOVehicleDefinitions::from_file(path.to_str().unwrap());
     let mut result = Garage::new();
+    let mut inherited_types: HashMap<String, IOVehicleType> = HashMap::new();
     for io_veh_type in io_vehicles.veh_types {
+        let io_veh_type = inherit_veh_type(io_veh_type, &inherited_types);
+        inherited_types.insert(io_veh_type.id.clone(), io_veh_type.clone());
         add_io_veh_type(&mut result, io_veh_type);
     }
+    for io_veh in io_vehicles.vehicles {
+        add_io_veh(&mut result, io_veh);
+    }
     let keys_ext: Vec<_> = result.vehicle_types.keys().map(|k| k.external()).collect();
     info!(
         "Created Garage from file with vehicle types: {:?}",
@@ -53,17 +69,21 @@ fn write_to_xml(garage: &Garage, path: &Path) {
         .vehicle_types
         .values()
         .map(|t| {
-            let attrs = Attrs {
+            let mut attrs = Attrs {
                 attributes: vec![Attr {
-                    name: "lod".to_string(),
+                    name: LOD_ATTRIBUTE.to_string(),
                     class: "java.lang.String".to_string(),
                     value: t.lod().as_str_name().to_owned(),
                 }],
             };
+            attrs.extend_from_map(&t.attributes);
             IOVehicleType {
                 id: Id::<VehicleType>::get(t.id).external().to_owned(),
                 description: None,
-                capacity: None,
+                capacity: Some(IOCapacity {
+                    seats: Some(t.seats),
+                    standing_room_in_persons: Some(t.standing_room),
+                }),
                 length: Some(IODimension { meter: t.length }),
                 width: Some(IODimension { meter: t.width }),
                 maximum_velocity: Some(IOVelocity {
@@ -81,26 +101,90 @@ fn write_to_xml(garage: &Garage, path: &Path) {
         })
         .collect();
 
-    let io_vehicles = IOVehicleDefinitions { veh_types };
+    // vehicles are sorted, so that the file doesn't depend on the iteration order of the garage.
+    let owners = garage.owners();
+    let mut vehicles: Vec<_> = garage.vehicles.iter().collect();
+    vehicles.sort_by_key(|(id, _)| id.internal());
+    let vehicles = vehicles
+        .into_iter()
+        .map(|(id, type_id)| {
+            let mut attrs = Attrs::default();
+            if let Some(owner) = owners.get(id) {
+                attrs.attributes.push(Attr {
+                    name: OWNER_ATTRIBUTE.to_string(),
+                    class: "java.lang.String".to_string(),
+                    value: owner.external().to_owned(),
+                });
+            }
+            if let Some(attributes) = garage.vehicle_attributes.get(id) {
+                attrs.extend_from_map(attributes);
+            }
+            IOVehicle {
+                id: id.external().to_owned(),
+                veh_type: type_id.external().to_owned(),
+                attributes: (!attrs.attributes.is_empty()).then_some(attrs),
+            }
+        })
+        .collect();
+
+    let io_vehicles = IOVehicleDefinitions {
+        veh_types,
+        vehicles,
+    };
+
+    xml::write_to_file(&io_vehicles, path, "http://www.matsim.org/files/dtd http://www.matsim.org/files/dtd/vehicleDefinitions_v2.0.xsd")
+}
+
+/// Merges vehicles files into one file. Vehicle types and vehicles, which are contained in several
+/// files, are written once. Vehicles are ordered by their id, so that the merged file doesn't depend
+/// on the number of files.
+pub fn merge_xml_files(paths: &[PathBuf], path: &Path) {
+    let mut veh_types: Vec<IOVehicleType> = Vec::new();
+    let mut vehicles: BTreeMap<String, IOVehicle> = BTreeMap::new();
+    for part in paths {
+        let io_vehicles = IOVehicleDefinitions::from_file(part.to_str().unwrap());
+        for veh_type in io_vehicles.veh_types {
+            if !veh_types.iter().any(|t| t.id == veh_type.id) {
+                veh_types.push(veh_type);
+            }
+        }
+        for vehicle in io_vehicles.vehicles {
+            vehicles.entry(vehicle.id.clone()).or_insert(vehicle);
+        }
+    }
 
+    let io_vehicles = IOVehicleDefinitions {
+        veh_types,
+        vehicles: vehicles.into_values().collect(),
+    };
     xml::write_to_file(&io_vehicles, path, "http://www.matsim.org/files/dtd http://www.matsim.org/files/dtd/vehicleDefinitions_v2.0.xsd")
 }
 
 fn write_to_proto(garage: &Garage, path: &Path) {
     info!("Converting Garage into wire type");
     let vehicle_types = garage.vehicle_types.values().cloned().collect();
+    let owners = garage.owners();
     let vehicles = garage
         .vehicles
         .iter()
         .map(|e| VehicleToType {
             id: e.0.internal(),
             vehicle_type_id: e.1.internal(),
+            owner: owners.get(e.0).map(|p| p.internal()),
+            attributes: garage
+                .vehicle_attributes
+                .get(e.0)
+                .cloned()
+                .unwrap_or_default(),
         })
         .collect();
 
+    let households = garage.households.values().cloned().collect();
+
     let wire_format = VehiclesContainer {
         vehicle_types,
         vehicles,
+        households,
     };
     info!("Finished converting Garage into wire type");
     simulation::io::proto::write_to_file(wire_format, path);
@@ -118,14 +202,140 @@ fn load_from_proto(path: &Path) -> Garage {
         .into_iter()
         .map(|v_type| (Id::get(v_type.id), v_type))
         .collect();
-    Garage {
+    let mut garage = Garage {
         vehicles,
         vehicle_types,
+        ..Garage::default()
+    };
+    for vehicle in wire_garage.vehicles {
+        if let Some(owner) = vehicle.owner {
+            garage.set_owner(Id::get(vehicle.id), Id::get(owner));
+        }
+        if !vehicle.attributes.is_empty() {
+            garage
+                .vehicle_attributes
+                .insert(Id::get(vehicle.id), vehicle.attributes);
+        }
+    }
+    for household in wire_garage.households {
+        garage.add_household(household);
+    }
+    garage
+}
+
+pub fn households_from_file(path: &Path) -> Vec<Household> {
+    let io_households: IOHouseholds = xml::read_from_file(path.to_str().unwrap());
+    let result: Vec<_> = io_households
+        .households
+        .into_iter()
+        .map(|io_household| Household {
+            id: Id::<Household>::create(&io_household.id).internal(),
+            members: io_household
+                .members
+                .person_ids
+                .iter()
+                .map(|r| Id::<Person>::get_from_ext(&r.ref_id).internal())
+                .collect(),
+            vehicles: io_household
+                .vehicles
+                .unwrap_or_default()
+                .vehicle_ids
+                .iter()
+                .map(|r| Id::<Vehicle>::get_from_ext(&r.ref_id).internal())
+                .collect(),
+        })
+        .collect();
+    info!("Loaded {} households from file {path:?}", result.len());
+    result
+}
+
+fn add_io_veh(garage: &mut Garage, io_veh: IOVehicle) {
+    let id: Id<Vehicle> = Id::create(&io_veh.id);
+    let type_id: Id<VehicleType> = Id::get_from_ext(&io_veh.veh_type);
+    assert!(
+        garage.vehicle_types.contains_key(&type_id),
+        "Vehicle {} has type {}, which is not defined in the vehicles file.",
+        io_veh.id,
+        io_veh.veh_type
+    );
+    garage.vehicles.insert(id.clone(), type_id);
+    if let Some(owner) = io_veh
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.find(OWNER_ATTRIBUTE))
+    {
+        garage.set_owner(id.clone(), Id::<Person>::create(owner));
+    }
+    let attributes = Attrs::unknown_to_map(&io_veh.attributes, &[OWNER_ATTRIBUTE]);
+    if !attributes.is_empty() {
+        garage.vehicle_attributes.insert(id, attributes);
+    }
+}
+
+/// Fills the fields of a vehicle type, which are not given in the file, from the vehicle type
+/// referenced by its `inherits` attribute. This way, e.g. only the pce of a truck needs to be
+/// stated, if it inherits from a default vehicle type. The network mode and the level of detail
+/// are inherited as well. Types may inherit from types, which inherit themselves.
+fn inherit_veh_type(
+    io_veh_type: IOVehicleType,
+    types: &HashMap<String, IOVehicleType>,
+) -> IOVehicleType {
+    let parent_id = io_veh_type
+        .attributes
+        .as_ref()
+        .and_then(|attrs| attrs.find(INHERITS_ATTRIBUTE))
+        .map(String::from);
+    let parent = if let Some(parent_id) = parent_id {
+        types.get(&parent_id).unwrap_or_else(|| {
+            panic!(
+                "Vehicle type {} inherits from {parent_id}, which is not defined before it.",
+                io_veh_type.id
+            )
+        })
+    } else {
+        return io_veh_type;
+    };
+
+    let mut attributes = io_veh_type.attributes.unwrap_or_default();
+    if attributes.find(LOD_ATTRIBUTE).is_none() {
+        if let Some(lod) = parent
+            .attributes
+            .as_ref()
+            .and_then(|attrs| attrs.find(LOD_ATTRIBUTE))
+        {
+            attributes
+                .attributes
+                .push(Attr::new(LOD_ATTRIBUTE.to_string(), lod.to_string()));
+        }
+    }
+    IOVehicleType {
+        capacity: io_veh_type.capacity.or_else(|| parent.capacity.clone()),
+        length: io_veh_type.length.or_else(|| parent.length.clone()),
+        width: io_veh_type.width.or_else(|| parent.width.clone()),
+        maximum_velocity: io_veh_type
+            .maximum_velocity
+            .or_else(|| parent.maximum_velocity.clone()),
+        passenger_car_equivalents: io_veh_type
+            .passenger_car_equivalents
+            .or_else(|| parent.passenger_car_equivalents.clone()),
+        network_mode: io_veh_type
+            .network_mode
+            .or_else(|| parent.network_mode.clone()),
+        flow_efficiency_factor: io_veh_type
+            .flow_efficiency_factor
+            .or_else(|| parent.flow_efficiency_factor.clone()),
+        attributes: Some(attributes),
+        ..io_veh_type
     }
 }
 
 fn add_io_veh_type(garage: &mut Garage, io_veh_type: IOVehicleType) {
     let id: Id<VehicleType> = Id::create(&io_veh_type.id);
+    let attributes = Attrs::unknown_to_map(
+        &io_veh_type.attributes,
+        &[LOD_ATTRIBUTE, INHERITS_ATTRIBUTE],
+    );
+    let capacity = io_veh_type.capacity.unwrap_or_default();
     let net_mode: Id<String> =
         Id::create(&io_veh_type.network_mode.unwrap_or_default().network_mode);
     let lod = if let Some(attr) = io_veh_type
@@ -133,7 +343,7 @@ fn add_io_veh_type(garage: &mut Garage, io_veh_type: IOVehicleType) {
         .unwrap_or_default()
         .attributes
         .iter()
-        .find(|&attr| attr.name.eq("lod"))
+        .find(|&attr| attr.name.eq(LOD_ATTRIBUTE))
     {
         match attr.value.to_lowercase().as_str() {
             "teleported" => LevelOfDetail::Teleported,
@@ -162,6 +372,9 @@ fn add_io_veh_type(garage: &mut Garage, io_veh_type: IOVehicleType) {
             .factor,
         net_mode: net_mode.internal(),
         lod: lod as i32,
+        seats: capacity.seats.unwrap_or(1),
+        standing_room: capacity.standing_room_in_persons.unwrap_or(0),
+        attributes,
     };
     garage.add_veh_type(veh_type);
 }
@@ -171,6 +384,8 @@ fn add_io_veh_type(garage: &mut Garage, io_veh_type: IOVehicleType) {
 pub struct IOVehicleDefinitions {
     #[serde(rename = "vehicleType")]
     pub veh_types: Vec<IOVehicleType>,
+    #[serde(rename = "vehicle", default)]
+    pub vehicles: Vec<IOVehicle>,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -198,8 +413,18 @@ pub struct IOVehicleType {
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct IOVehicle {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub veh_type: String,
+    pub attributes: Option<Attrs>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct IOCapacity {
-    // leave emtpy for now
+    pub(crate) seats: Option<u32>,
+    pub(crate) standing_room_in_persons: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -273,6 +498,38 @@ pub struct IOCostInformation {
     // leave empty for now.
 }
 
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[serde(rename = "households")]
+pub struct IOHouseholds {
+    #[serde(rename = "household", default)]
+    pub households: Vec<IOHousehold>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct IOHousehold {
+    pub id: String,
+    pub members: IOHouseholdMembers,
+    pub vehicles: Option<IOHouseholdVehicles>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct IOHouseholdMembers {
+    #[serde(rename = "personId", default)]
+    pub person_ids: Vec<IORefId>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
+pub struct IOHouseholdVehicles {
+    #[serde(rename = "vehicleDefinitionId", default)]
+    pub vehicle_ids: Vec<IORefId>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct IORefId {
+    #[serde(rename = "refId")]
+    pub ref_id: String,
+}
+
 impl IOVehicleDefinitions {
     pub fn from_file(file: &str) -> Self {
         xml::read_from_file(file)
@@ -289,8 +546,9 @@ mod test {
     use crate::simulation::io::attributes::{Attr, Attrs};
     use crate::simulation::vehicles::garage::Garage;
     use crate::simulation::vehicles::io::{
-        add_io_veh_type, from_file, to_file, IODimension, IOFowEfficiencyFactor, IONetworkMode,
-        IOPassengerCarEquivalents, IOVehicleDefinitions, IOVehicleType, IOVelocity,
+        add_io_veh_type, from_file, load_from_xml, to_file, IODimension, IOFowEfficiencyFactor,
+        IOHouseholds, IONetworkMode, IOPassengerCarEquivalents, IOVehicleDefinitions,
+        IOVehicleType, IOVelocity,
     };
     use crate::simulation::wire_types::vehicles::{LevelOfDetail, VehicleType};
 
@@ -316,6 +574,7 @@ mod test {
                             <vehicleDefinitions xmlns=\"http://www.matsim.org/files/dtd\">\
                                 <vehicleType id=\"some-vehicle-id\">\
                                     <description>some-description</description>\
+                                    <capacity seats=\"5\" standingRoomInPersons=\"10\"/>\
                                     <length meter=\"9.5\"/>\
                                     <width meter=\"9.5\"/>\
                                     <maximumVelocity meterPerSecond=\"9.5\"/>\
@@ -345,6 +604,11 @@ mod test {
                 .network_mode
                 .as_str()
         );
+        assert_eq!(Some(5), veh_type.capacity.as_ref().unwrap().seats);
+        assert_eq!(
+            Some(10),
+            veh_type.capacity.as_ref().unwrap().standing_room_in_persons
+        );
         assert_eq!(9.5, veh_type.length.as_ref().unwrap().meter);
         assert_eq!(9.5, veh_type.width.as_ref().unwrap().meter);
         assert_eq!(
@@ -377,6 +641,9 @@ mod test {
             fef: 0.3,
             net_mode: Id::<String>::create("some network type 🚕").internal(),
             lod: LevelOfDetail::Teleported as i32,
+            seats: 4,
+            standing_room: 2,
+            attributes: Default::default(),
         });
         garage.add_veh_id(&Id::create("some-person"), &Id::get_from_ext("some-type"));
 
@@ -385,6 +652,45 @@ mod test {
         assert_eq!(garage.vehicle_types, loaded_garage.vehicle_types);
     }
 
+    #[test]
+    fn vehicles_to_from_file_xml() {
+        let file = &PathBuf::from(
+            "./test_output/simulation/vehicles/io/vehicles_to_from_file_xml/vehicles.xml.gz",
+        );
+        let mut garage = Garage::new();
+        garage.add_veh_type(VehicleType {
+            id: Id::<VehicleType>::create("owned-type").internal(),
+            length: 7.5,
+            width: 1.,
+            max_v: 10.,
+            pce: 1.,
+            fef: 1.,
+            net_mode: Id::<String>::create("car").internal(),
+            lod: LevelOfDetail::Network as i32,
+            seats: 1,
+            standing_room: 0,
+            attributes: [(String::from("fuel"), String::from("diesel"))].into(),
+        });
+        let person_id = Id::create("owner-person");
+        let veh_id = garage.add_veh_id(&person_id, &Id::get_from_ext("owned-type"));
+        garage.vehicle_attributes.insert(
+            veh_id.clone(),
+            [(String::from("color"), String::from("red"))].into(),
+        );
+
+        to_file(&garage, file);
+        let loaded_garage = from_file(file);
+
+        assert_eq!(garage.vehicles, loaded_garage.vehicles);
+        assert_eq!(
+            veh_id,
+            loaded_garage.veh_id(&person_id, &Id::get_from_ext("owned-type"))
+        );
+        assert_eq!(Some(&&person_id), loaded_garage.owners().get(&veh_id));
+        assert_eq!(garage.vehicle_attributes, loaded_garage.vehicle_attributes);
+        assert_eq!(garage.vehicle_types, loaded_garage.vehicle_types);
+    }
+
     #[test]
     fn test_to_from_file_proto() {
         let file = &PathBuf::from(
@@ -401,6 +707,9 @@ mod test {
             fef: 0.3,
             net_mode: Id::<String>::create("some network type 🚕").internal(),
             lod: LevelOfDetail::Teleported as i32,
+            seats: 4,
+            standing_room: 2,
+            attributes: Default::default(),
         });
         garage.add_veh_id(&Id::create("some-person"), &Id::get_from_ext("some-type"));
 
@@ -409,6 +718,41 @@ mod test {
 
         assert_eq!(garage.vehicle_types, loaded_garage.vehicle_types);
         assert_eq!(garage.vehicles, loaded_garage.vehicles);
+        assert_eq!(garage.owners(), loaded_garage.owners());
+    }
+
+    #[test]
+    fn households_from_string() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                            <households xmlns=\"http://www.matsim.org/files/dtd\">\
+                                <household id=\"hh-1\">\
+                                    <members>\
+                                        <personId refId=\"p-1\"/>\
+                                        <personId refId=\"p-2\"/>\
+                                    </members>\
+                                    <vehicles>\
+                                        <vehicleDefinitionId refId=\"p-1_car\"/>\
+                                    </vehicles>\
+                                </household>\
+                                <household id=\"hh-2\">\
+                                    <members>\
+                                        <personId refId=\"p-3\"/>\
+                                    </members>\
+                                </household>\
+                            </households>\
+                        ";
+        let households: IOHouseholds = from_str(xml).unwrap();
+
+        assert_eq!(2, households.households.len());
+        let first = households.households.first().unwrap();
+        assert_eq!("hh-1", first.id);
+        assert_eq!(2, first.members.person_ids.len());
+        assert_eq!("p-2", first.members.person_ids[1].ref_id);
+        assert_eq!(
+            "p-1_car",
+            first.vehicles.as_ref().unwrap().vehicle_ids[0].ref_id
+        );
+        assert!(households.households[1].vehicles.is_none());
     }
 
     #[test]
@@ -481,4 +825,78 @@ mod test {
         assert_eq!(veh_type.id, expected_id.internal());
         assert_eq!(veh_type.net_mode, expected_mode.internal())
     }
+
+    #[test]
+    fn inherit_from_default_type() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<vehicleDefinitions xmlns="http://www.matsim.org/files/dtd">
+    <vehicleType id="vi-default">
+        <attributes>
+            <attribute name="lod" class="java.lang.String">teleported</attribute>
+        </attributes>
+        <length meter="7.5"/>
+        <maximumVelocity meterPerSecond="20.0"/>
+        <passengerCarEquivalents pce="1.0"/>
+        <networkMode networkMode="vi-mode"/>
+    </vehicleType>
+    <vehicleType id="vi-truck">
+        <attributes>
+            <attribute name="inherits" class="java.lang.String">vi-default</attribute>
+        </attributes>
+        <passengerCarEquivalents pce="3.5"/>
+    </vehicleType>
+    <vehicleType id="vi-trailer-truck">
+        <attributes>
+            <attribute name="inherits" class="java.lang.String">vi-truck</attribute>
+        </attributes>
+        <length meter="18.0"/>
+    </vehicleType>
+</vehicleDefinitions>"#;
+        let file =
+            PathBuf::from("./test_output/simulation/vehicles/io/inherit_from_default_type.xml");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, xml).unwrap();
+
+        let garage = load_from_xml(&file);
+
+        let truck = garage
+            .vehicle_types
+            .get(&Id::get_from_ext("vi-truck"))
+            .unwrap();
+        assert_eq!(3.5, truck.pce);
+        assert_eq!(7.5, truck.length);
+        assert_eq!(20., truck.max_v);
+        assert_eq!(
+            Id::<String>::get_from_ext("vi-mode").internal(),
+            truck.net_mode
+        );
+        assert_eq!(LevelOfDetail::Teleported, truck.lod());
+        assert!(truck.attributes.is_empty());
+
+        let trailer_truck = garage
+            .vehicle_types
+            .get(&Id::get_from_ext("vi-trailer-truck"))
+            .unwrap();
+        assert_eq!(3.5, trailer_truck.pce);
+        assert_eq!(18., trailer_truck.length);
+        assert_eq!(20., trailer_truck.max_v);
+    }
+
+    #[test]
+    #[should_panic(expected = "inherits from vi-unknown")]
+    fn inherit_from_unknown_type() {
+        let xml = r#"<vehicleDefinitions>
+    <vehicleType id="vi-orphan">
+        <attributes>
+            <attribute name="inherits" class="java.lang.String">vi-unknown</attribute>
+        </attributes>
+    </vehicleType>
+</vehicleDefinitions>"#;
+        let file =
+            PathBuf::from("./test_output/simulation/vehicles/io/inherit_from_unknown_type.xml");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, xml).unwrap();
+
+        load_from_xml(&file);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod defaults;
 pub mod garage;
 mod io;
 mod vehicles;
@@ -39,6 +39,9 @@ pub fn create_vehicle_type(id: &Id<VehicleType>, net_mode: Id<String>) -> Vehicl
         fef: 0.0,
         net_mode: net_mode.internal(),
         lod: LevelOfDetail::Network as i32,
+        seats: 1,
+        standing_room: 0,
+        attributes: Default::default(),
     }
 }
 
@@ -48,5 +51,14 @@ pub fn config() -> config::Simulation {
         end_time: 0,
         sample_size: 1.0,
         stuck_threshold: u32::MAX,
+        max_parking_search_time: 0,
+        max_passenger_wait_time: u32::MAX,
+        core: config::SimulationCore::Tick,
+        node_capacity: None,
+        min_storage_of_one_vehicle: false,
+        parallel_links: false,
+        link_model: config::LinkModel::Queue,
+        activity_duration_interpretation:
+            config::ActivityDurationInterpretation::TryEndTimeThenDuration,
     }
 }
@@ -1,13 +1,27 @@
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use rust_q_sim::simulation::config::CommandLineArgs;
-use rust_q_sim::simulation::id::store_to_file;
+use rust_q_sim::simulation::config::{CommandLineArgs, Config};
+use rust_q_sim::simulation::controller::{
+    run_scenario, run_scenario_with_extensions, Extensions, ScenarioRun,
+};
+use rust_q_sim::simulation::controller_listener::{
+    ControllerEvent, ControllerListener, ControllerListenerFactory,
+};
+use rust_q_sim::simulation::engines::{Engine, EngineContext};
+use rust_q_sim::simulation::id::{store_to_file, Id};
 use rust_q_sim::simulation::messaging::communication::communicators::DummySimCommunicator;
+use rust_q_sim::simulation::mobsim_listener::{MobsimListener, SimState};
 use rust_q_sim::simulation::network::global_network::Network;
 use rust_q_sim::simulation::population::population::Population;
 use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::wire_types::events::event::Type;
+use rust_q_sim::simulation::wire_types::population::Person;
 
-use crate::test_simulation::{execute_sim, execute_sim_with_channels, TestSubscriber};
+use crate::test_simulation::{
+    execute_sim, execute_sim_with_channels, execute_sim_with_extensions, EmptySubscriber,
+    TestSubscriber,
+};
 
 mod test_simulation;
 
@@ -54,3 +68,314 @@ fn execute_3_links_2_parts() {
 
     execute_sim_with_channels(config_args, "./tests/resources/3-links/expected_events.xml");
 }
+
+/// Engine which simulates walk legs like teleportation and records when agents depart and arrive.
+struct WalkEngine {
+    on_leg: Vec<(u32, Person)>,
+    log: Arc<Mutex<Vec<(&'static str, u64, u32)>>>,
+}
+
+impl Engine for WalkEngine {
+    fn modes(&self) -> Vec<Id<String>> {
+        vec![Id::get_from_ext("walk")]
+    }
+
+    fn receive_departure(&mut self, agent: Person, now: u32, _ctx: &mut EngineContext) {
+        self.log.lock().unwrap().push(("departure", agent.id, now));
+        self.on_leg.push((now + agent.curr_leg().trav_time, agent));
+    }
+
+    fn do_step(&mut self, now: u32, ctx: &mut EngineContext) {
+        let (arrived, on_leg) = std::mem::take(&mut self.on_leg)
+            .into_iter()
+            .partition(|(arrival, _)| *arrival <= now);
+        self.on_leg = on_leg;
+        for (_, agent) in arrived {
+            self.log.lock().unwrap().push(("arrival", agent.id, now));
+            ctx.end_leg(agent);
+        }
+    }
+
+    fn num_agents(&self) -> usize {
+        self.on_leg.len()
+    }
+}
+
+#[test]
+fn engine_receives_departures() {
+    create_resources(&PathBuf::from(
+        "./test_output/simulation/execute_3_links_with_engine/",
+    ));
+    let config_args = CommandLineArgs {
+        config_path: "./tests/resources/3-links/3-links-config-engine.yml".to_string(),
+        num_parts: None,
+    };
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let engine = WalkEngine {
+        on_leg: Vec::new(),
+        log: log.clone(),
+    };
+
+    execute_sim_with_engines(
+        DummySimCommunicator(),
+        Box::new(EmptySubscriber {}),
+        config_args,
+        vec![Box::new(engine)],
+        Vec::new(),
+    );
+
+    // the access and the egress walk of the agent are simulated by the engine, the car leg in
+    // between by the network.
+    let person = Id::<Person>::get_from_ext("100").internal();
+    assert_eq!(
+        vec![
+            ("departure", person, 32400),
+            ("arrival", person, 32408),
+            ("departure", person, 32530),
+            ("arrival", person, 32546),
+        ],
+        *log.lock().unwrap()
+    );
+}
+
+/// Listener which records the time and the number of agents performing an activity or being
+/// teleported, whenever it is called.
+struct RecordingListener {
+    log: Arc<Mutex<Vec<(&'static str, u32, usize, usize)>>>,
+}
+
+impl RecordingListener {
+    fn record(&self, hook: &'static str, state: &SimState) {
+        self.log.lock().unwrap().push((
+            hook,
+            state.time,
+            state.activity_q.len(),
+            state.teleportation_q.len(),
+        ));
+    }
+}
+
+impl MobsimListener for RecordingListener {
+    fn before_step(&mut self, state: &SimState) {
+        self.record("before", state);
+    }
+
+    fn after_step(&mut self, state: &SimState) {
+        self.record("after", state);
+    }
+
+    fn finish(&mut self, state: &SimState) {
+        self.record("finish", state);
+    }
+}
+
+#[test]
+fn mobsim_listener_is_called_around_each_step() {
+    create_resources(&PathBuf::from(
+        "./test_output/simulation/execute_3_links_with_listener/",
+    ));
+    let config_args = CommandLineArgs {
+        config_path: "./tests/resources/3-links/3-links-config-listener.yml".to_string(),
+        num_parts: None,
+    };
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let listener = RecordingListener { log: log.clone() };
+
+    execute_sim_with_extensions(
+        DummySimCommunicator(),
+        Box::new(EmptySubscriber {}),
+        config_args,
+        Vec::new(),
+        vec![Box::new(listener)],
+    );
+
+    let log = log.lock().unwrap();
+    let (finish, steps) = log.split_last().unwrap();
+    assert_eq!("finish", finish.0);
+    // each time step until the end time is wrapped by a call before and a call after the step.
+    assert_eq!(2 * 86401, steps.len());
+    for (time, pair) in steps.chunks(2).enumerate() {
+        assert_eq!(("before", time as u32), (pair[0].0, pair[0].1));
+        assert_eq!(("after", time as u32), (pair[1].0, pair[1].1));
+    }
+    // the agent ends its activity and is teleported to the car in the step at 9:00.
+    assert_eq!(("before", 32400, 1, 0), steps[2 * 32400]);
+    assert_eq!(("after", 32400, 0, 1), steps[2 * 32400 + 1]);
+}
+
+#[test]
+fn run_scenario_3_links() {
+    create_resources(&PathBuf::from(
+        "./test_output/simulation/run_scenario_3_links/",
+    ));
+    let config = Config::from_file(&CommandLineArgs {
+        config_path: "./tests/resources/3-links/3-links-config-run-scenario.yml".to_string(),
+        num_parts: None,
+    });
+
+    let run = run_scenario(&config);
+
+    assert!(run.completed);
+    assert_eq!(20, run.events.len());
+    assert!(run.events.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+}
+
+/// Controller listener which records the hooks it is called with, together with the rank and the
+/// iteration.
+struct LifecycleListener {
+    log: Arc<Mutex<Vec<(u32, &'static str, u32)>>>,
+}
+
+impl LifecycleListener {
+    fn record(&self, hook: &'static str, event: &ControllerEvent) {
+        self.log
+            .lock()
+            .unwrap()
+            .push((event.rank, hook, event.iteration));
+    }
+}
+
+impl ControllerListener for LifecycleListener {
+    fn startup(&mut self, event: &ControllerEvent) {
+        self.record("startup", event);
+    }
+
+    fn iteration_starts(&mut self, event: &ControllerEvent) {
+        self.record("iteration_starts", event);
+    }
+
+    fn iteration_ends(&mut self, event: &ControllerEvent) {
+        self.record("iteration_ends", event);
+    }
+
+    fn shutdown(&mut self, event: &ControllerEvent, unexpected: bool) {
+        assert!(!unexpected);
+        self.record("shutdown", event);
+    }
+}
+
+/// Runs the scenario of the config with a [LifecycleListener] and returns the run together with the
+/// hooks each partition was called with.
+fn run_with_lifecycle_listener(
+    out_dir: &str,
+    config_path: &str,
+) -> (ScenarioRun, Vec<Vec<(&'static str, u32)>>) {
+    create_resources(&PathBuf::from(out_dir));
+    let config = Config::from_file(&CommandLineArgs {
+        config_path: config_path.to_string(),
+        num_parts: None,
+    });
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let factory_log = log.clone();
+    let factory: ControllerListenerFactory = Arc::new(move |_rank| {
+        Box::new(LifecycleListener {
+            log: factory_log.clone(),
+        })
+    });
+    let extensions = Extensions {
+        controller_listeners: vec![factory],
+        ..Extensions::default()
+    };
+
+    let run = run_scenario_with_extensions(&config, extensions);
+
+    // partitions run on their own threads, so only the calls of each partition are ordered.
+    let log = log.lock().unwrap();
+    let hooks = (0..config.partitioning().num_parts)
+        .map(|rank| {
+            log.iter()
+                .filter(|(r, _, _)| *r == rank)
+                .map(|(_, hook, iteration)| (*hook, *iteration))
+                .collect()
+        })
+        .collect();
+    (run, hooks)
+}
+
+#[test]
+fn controller_listener_is_called_at_iteration_start_and_end() {
+    let (run, hooks) = run_with_lifecycle_listener(
+        "./test_output/simulation/run_scenario_3_links_with_listener/",
+        "./tests/resources/3-links/3-links-config-controller-listener.yml",
+    );
+    assert!(run.completed);
+    assert_eq!(2, hooks.len());
+    for hooks in hooks {
+        assert_eq!(
+            vec![
+                ("startup", 0),
+                ("iteration_starts", 0),
+                ("iteration_ends", 0),
+                ("shutdown", 0),
+            ],
+            hooks
+        );
+    }
+}
+
+#[test]
+fn controller_runs_all_iterations() {
+    let (run, hooks) = run_with_lifecycle_listener(
+        "./test_output/simulation/run_scenario_3_links_iterations/",
+        "./tests/resources/3-links/3-links-config-iterations.yml",
+    );
+    assert!(run.completed);
+    assert_eq!(2, hooks.len());
+    for hooks in hooks {
+        assert_eq!(
+            vec![
+                ("startup", 0),
+                ("iteration_starts", 0),
+                ("iteration_ends", 0),
+                ("iteration_starts", 1),
+                ("iteration_ends", 1),
+                ("iteration_starts", 2),
+                ("iteration_ends", 2),
+                ("shutdown", 2),
+            ],
+            hooks
+        );
+    }
+    for iteration in 0..=2 {
+        let iteration_dir = run.output_dir.join(format!("ITERS/it.{iteration}"));
+        assert!(iteration_dir.join("output_vehicles.xml.gz").exists());
+    }
+
+    // the agent keeps its plan without re-routing, so it travels the same way in each iteration.
+    let departures: Vec<_> = run
+        .events
+        .iter()
+        .filter(|(_, event)| matches!(event.r#type, Some(Type::Departure(_))))
+        .map(|(time, _)| *time)
+        .collect();
+    assert!(!departures.is_empty());
+    assert_eq!(0, departures.len() % 3);
+    let per_iteration = departures.len() / 3;
+    assert_eq!(
+        departures[..per_iteration],
+        departures[per_iteration..2 * per_iteration]
+    );
+    assert_eq!(departures[..per_iteration], departures[2 * per_iteration..]);
+}
+
+#[test]
+fn controller_stops_once_converged() {
+    // there is only one route through the network, so re-routed agents never switch routes.
+    let (run, hooks) = run_with_lifecycle_listener(
+        "./test_output/simulation/run_scenario_3_links_converged/",
+        "./tests/resources/3-links/3-links-config-converged.yml",
+    );
+    assert!(run.completed);
+    assert_eq!(2, hooks.len());
+    for hooks in hooks {
+        assert_eq!(
+            vec![
+                ("startup", 0),
+                ("iteration_starts", 0),
+                ("iteration_ends", 0),
+                ("shutdown", 0),
+            ],
+            hooks
+        );
+    }
+}
@@ -1,11 +1,19 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use rust_q_sim::simulation::config::CommandLineArgs;
-use rust_q_sim::simulation::id::store_to_file;
+use rust_q_sim::simulation::config::{CommandLineArgs, Config};
+use rust_q_sim::simulation::controller::{run_scenario_with_extensions, Extensions, ScenarioRun};
+use rust_q_sim::simulation::id::{store_to_file, Id};
 use rust_q_sim::simulation::messaging::communication::communicators::DummySimCommunicator;
-use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::messaging::events::{EventsSubscriber, EventsSubscriberFactory};
+use rust_q_sim::simulation::network::global_network::{Link, Network};
 use rust_q_sim::simulation::population::population::Population;
 use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::wire_types::events::event::Type;
+use rust_q_sim::simulation::wire_types::events::Event;
 
 use crate::test_simulation::{execute_sim, execute_sim_with_channels, TestSubscriber};
 
@@ -99,3 +107,115 @@ fn execute_adhoc_routing_two_parts_with_updates() {
         "./tests/resources/adhoc_routing/with_updates/expected_events.xml",
     );
 }
+
+/// Records the links entered in each iteration.
+struct LinkEnterRecorder {
+    iteration: u32,
+    entered: Arc<Mutex<Vec<(u32, u64, u64)>>>,
+}
+
+impl EventsSubscriber for LinkEnterRecorder {
+    fn receive_event(&mut self, _time: u32, event: &Event) {
+        if let Some(Type::LinkEnter(e)) = &event.r#type {
+            self.entered
+                .lock()
+                .unwrap()
+                .push((self.iteration, e.vehicle, e.link));
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Runs the ad-hoc routing scenario without updates for several iterations. Returns the vehicles
+/// and links of the link enter events of each iteration, sorted by vehicle.
+fn run_iterations(out_dir: &str, config_path: &str) -> (ScenarioRun, Vec<Vec<(u64, u64)>>) {
+    let out_dir = PathBuf::from(out_dir);
+    create_resources(
+        &PathBuf::from("./assets/adhoc_routing/no_updates/"),
+        &out_dir,
+    );
+    let _ = fs::remove_dir_all(out_dir.join("ITERS"));
+    let config = Config::from_file(&CommandLineArgs {
+        config_path: config_path.to_string(),
+        num_parts: None,
+    });
+
+    // the subscribers of a partition are created once per iteration.
+    let entered = Arc::new(Mutex::new(Vec::new()));
+    let factory_entered = entered.clone();
+    let iterations: Mutex<HashMap<u32, u32>> = Mutex::default();
+    let factory: EventsSubscriberFactory = Arc::new(move |rank| {
+        let mut iterations = iterations.lock().unwrap();
+        let iteration = iterations.entry(rank).or_insert(0);
+        let recorder = LinkEnterRecorder {
+            iteration: *iteration,
+            entered: factory_entered.clone(),
+        };
+        *iteration += 1;
+        Box::new(recorder)
+    });
+    let extensions = Extensions {
+        events_subscribers: vec![factory],
+        ..Extensions::default()
+    };
+
+    let run = run_scenario_with_extensions(&config, extensions);
+
+    let entered = entered.lock().unwrap();
+    let last_iteration = config.strategy().last_iteration;
+    let links = (0..=last_iteration)
+        .map(|iteration| {
+            let mut links: Vec<_> = entered
+                .iter()
+                .filter(|(i, _, _)| *i == iteration)
+                .map(|(_, vehicle, link)| (*vehicle, *link))
+                .collect();
+            links.sort();
+            links
+        })
+        .collect();
+    (run, links)
+}
+
+#[test]
+#[serial_test::serial]
+fn adhoc_routing_stops_once_agents_keep_their_routes() {
+    let (run, links) = run_iterations(
+        "./test_output/simulation/adhoc_routing/no_updates/iterations/",
+        "./tests/resources/adhoc_routing/no_updates/config-iterations.yml",
+    );
+    assert!(run.completed);
+
+    // the placeholder routes of the input are replaced in iteration 0, so all re-routed legs
+    // switch routes. In iteration 1, the legs are compared with the routes of iteration 0, which
+    // they keep, so the switch rate falls below the threshold and the controller stops.
+    assert!(run.output_dir.join("ITERS/it.0").exists());
+    assert!(run.output_dir.join("ITERS/it.1").exists());
+    assert!(!run.output_dir.join("ITERS/it.2").exists());
+
+    let link5 = Id::<Link>::get_from_ext("link5").internal();
+    assert!(links[0].iter().any(|(_, link)| *link == link5));
+    assert_eq!(links[0], links[1]);
+    assert!(links[2].is_empty());
+}
+
+#[test]
+#[serial_test::serial]
+fn plans_of_the_previous_iteration_are_kept_after_innovation_shutoff() {
+    let (run, links) = run_iterations(
+        "./test_output/simulation/adhoc_routing/no_updates/innovation_shutoff/",
+        "./tests/resources/adhoc_routing/no_updates/config-innovation-shutoff.yml",
+    );
+    assert!(run.completed);
+
+    // agents are routed in iteration 0 only. Afterwards, only non-innovative strategies like
+    // KeepLastSelected are left, so that the agents drive the routes of iteration 0 instead of the
+    // placeholder routes of the input.
+    let link5 = Id::<Link>::get_from_ext("link5").internal();
+    assert!(links[0].iter().any(|(_, link)| *link == link5));
+    assert_eq!(links[0], links[1]);
+    assert_eq!(links[0], links[2]);
+}
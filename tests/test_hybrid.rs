@@ -0,0 +1,81 @@
+use std::any::Any;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+
+use rust_q_sim::simulation::config::CommandLineArgs;
+use rust_q_sim::simulation::controller::{run_hybrid_with_extensions, Extensions};
+use rust_q_sim::simulation::id::store_to_file;
+use rust_q_sim::simulation::io::xml_events::XmlEventsWriter;
+use rust_q_sim::simulation::messaging::events::{EventsSubscriber, EventsSubscriberFactory};
+use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::population::population::Population;
+use rust_q_sim::simulation::vehicles::garage::Garage;
+use rust_q_sim::simulation::wire_types::events::Event;
+
+struct SendingSubscriber {
+    sender: Sender<String>,
+}
+
+impl EventsSubscriber for SendingSubscriber {
+    fn receive_event(&mut self, time: u32, event: &Event) {
+        self.sender
+            .send(XmlEventsWriter::event_2_string(time, event))
+            .expect("Failed on sending event message!");
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn create_resources(out_dir: &PathBuf) {
+    let input_dir = PathBuf::from("./assets/3-links/");
+    let net = Network::from_file_as_is(&input_dir.join("3-links-network.xml"));
+    let mut garage = Garage::from_file(&input_dir.join("vehicles.xml"));
+    let pop = Population::from_file(&input_dir.join("1-agent-full-leg.xml"), &mut garage);
+
+    store_to_file(&out_dir.join("ids.binpb"));
+    net.to_file(&out_dir.join("3-links-network.binpb"));
+    pop.to_file(&out_dir.join("1-agent-full-leg.binpb"));
+    garage.to_file(&out_dir.join("vehicles.binpb"));
+}
+
+/// Runs two partitions as threads of a single mpi process. Mpi and the logging can only be
+/// initialized once per process, which is why this is the only test of this file. Run it with or
+/// without mpirun, e.g. `mpirun -n 1 cargo test --test test_hybrid`.
+#[test]
+fn execute_3_links_hybrid() {
+    create_resources(&PathBuf::from(
+        "./test_output/simulation/execute_3_links_hybrid/",
+    ));
+    let config_args = CommandLineArgs {
+        config_path: "./tests/resources/3-links/3-links-config-hybrid.yml".to_string(),
+        num_parts: None,
+    };
+
+    let (sender, receiver) = channel();
+    let subscriber: EventsSubscriberFactory = Arc::new(move |_rank| {
+        Box::new(SendingSubscriber {
+            sender: sender.clone(),
+        })
+    });
+    let extensions = Extensions {
+        events_subscribers: vec![subscriber],
+        ..Extensions::default()
+    };
+
+    run_hybrid_with_extensions(config_args, 2, extensions);
+
+    let mut events: Vec<String> = receiver.try_iter().collect();
+    let mut expected: Vec<String> =
+        std::fs::read_to_string("./tests/resources/3-links/expected_events.xml")
+            .unwrap()
+            .lines()
+            .filter(|l| l.starts_with("<event "))
+            .map(|l| format!("{l}\n"))
+            .collect();
+    events.sort();
+    expected.sort();
+    assert_eq!(expected, events);
+}
@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use rust_q_sim::simulation::config::CommandLineArgs;
+use rust_q_sim::simulation::id::store_to_file;
+use rust_q_sim::simulation::io::xml_events::{XmlEventsReader, XmlEventsWriter};
+use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::population::population::Population;
+use rust_q_sim::simulation::vehicles::garage::Garage;
+
+use crate::test_simulation::execute_sim_and_receive_events;
+
+mod test_simulation;
+
+/// Event types which are published by this crate and by MATSim. Other MATSim events, such as
+/// 'vehicle enters traffic', are ignored when comparing.
+const COMMON_EVENT_TYPES: [&str; 9] = [
+    "actend",
+    "actstart",
+    "departure",
+    "arrival",
+    "PersonEntersVehicle",
+    "PersonLeavesVehicle",
+    "entered link",
+    "left link",
+    "travelled",
+];
+
+/// Differences in seconds which are accepted between this crate and MATSim.
+#[derive(Debug, Clone, Copy)]
+struct Tolerance {
+    event_time: u32,
+    leg_travel_time: u32,
+}
+
+/// An event reduced to the attributes both implementations agree on. Vehicle ids are resolved to the
+/// persons inside the vehicle, as MATSim and this crate may use different vehicle ids.
+#[derive(Debug, Clone, PartialEq)]
+struct NormalizedEvent {
+    time: u32,
+    kind: String,
+    link: Option<String>,
+    act_type: Option<String>,
+    leg_mode: Option<String>,
+}
+
+impl NormalizedEvent {
+    fn same_kind(&self, other: &NormalizedEvent) -> bool {
+        self.kind == other.kind
+            && self.link == other.link
+            && self.act_type == other.act_type
+            && self.leg_mode == other.leg_mode
+    }
+}
+
+fn parse_attributes(line: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = line
+        .trim()
+        .trim_start_matches("<event")
+        .trim_end_matches("/>");
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 2..];
+        let end = after
+            .find('"')
+            .unwrap_or_else(|| panic!("Unterminated attribute in event: {line}"));
+        attrs.insert(key.to_string(), after[..end].to_string());
+        rest = &after[end + 1..];
+    }
+    attrs
+}
+
+/// Reads an events file written by MATSim and formats its events like the events of this crate.
+/// Event types which have no counterpart in this crate keep all their attributes.
+fn read_event_lines(path: &Path) -> Vec<String> {
+    XmlEventsReader::new(path)
+        .flat_map(|(time, events)| {
+            events
+                .into_iter()
+                .map(move |event| XmlEventsWriter::event_2_string(time, &event))
+        })
+        .collect()
+}
+
+/// Groups the events by person in the order they occur.
+fn normalize(lines: &[String]) -> BTreeMap<String, Vec<NormalizedEvent>> {
+    let mut result: BTreeMap<String, Vec<NormalizedEvent>> = BTreeMap::new();
+    let mut persons_in_vehicle: HashMap<String, Vec<String>> = HashMap::new();
+
+    for line in lines {
+        let attrs = parse_attributes(line);
+        let kind = attrs.get("type").expect("Event without type").as_str();
+        if !COMMON_EVENT_TYPES.contains(&kind) {
+            continue;
+        }
+        // MATSim writes times as floating point numbers.
+        let time = attrs
+            .get("time")
+            .expect("Event without time")
+            .parse::<f64>()
+            .unwrap()
+            .round() as u32;
+
+        let persons = if let Some(person) = attrs.get("person") {
+            let vehicle = attrs.get("vehicle");
+            match (kind, vehicle) {
+                ("PersonEntersVehicle", Some(vehicle)) => persons_in_vehicle
+                    .entry(vehicle.clone())
+                    .or_default()
+                    .push(person.clone()),
+                ("PersonLeavesVehicle", Some(vehicle)) => {
+                    if let Some(persons) = persons_in_vehicle.get_mut(vehicle) {
+                        persons.retain(|p| p != person);
+                    }
+                }
+                _ => {}
+            }
+            vec![person.clone()]
+        } else if let Some(vehicle) = attrs.get("vehicle") {
+            persons_in_vehicle.get(vehicle).cloned().unwrap_or_default()
+        } else {
+            vec![]
+        };
+
+        let event = NormalizedEvent {
+            time,
+            kind: kind.to_string(),
+            link: attrs.get("link").cloned(),
+            act_type: attrs.get("actType").cloned(),
+            leg_mode: attrs.get("legMode").cloned(),
+        };
+        for person in persons {
+            result.entry(person).or_default().push(event.clone());
+        }
+    }
+    result
+}
+
+/// Travel times of all legs of a person, computed from departure and arrival events.
+fn leg_travel_times(events: &[NormalizedEvent]) -> Vec<u32> {
+    let mut result = Vec::new();
+    let mut departure = None;
+    for event in events {
+        match event.kind.as_str() {
+            "departure" => departure = Some(event.time),
+            "arrival" => {
+                result.push(event.time - departure.take().expect("Arrival without departure"))
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+fn assert_parity(actual: &[String], reference: &[String], tolerance: Tolerance) {
+    let actual = normalize(actual);
+    let reference = normalize(reference);
+
+    assert_eq!(
+        reference.keys().collect::<Vec<_>>(),
+        actual.keys().collect::<Vec<_>>(),
+        "Events are published for different persons."
+    );
+
+    for (person, expected_events) in &reference {
+        let actual_events = actual.get(person).unwrap();
+        assert_eq!(
+            expected_events.len(),
+            actual_events.len(),
+            "Different number of events for person {person}. Expected: {expected_events:#?}, Actual: {actual_events:#?}"
+        );
+
+        for (expected, actual) in expected_events.iter().zip(actual_events) {
+            assert!(
+                expected.same_kind(actual),
+                "Different event sequence for person {person}. Expected {expected:?}, but was {actual:?}"
+            );
+            assert!(
+                expected.time.abs_diff(actual.time) <= tolerance.event_time,
+                "Event time of person {person} differs by more than {}s. Expected {expected:?}, but was {actual:?}",
+                tolerance.event_time
+            );
+        }
+
+        let expected_tt = leg_travel_times(expected_events);
+        let actual_tt = leg_travel_times(actual_events);
+        for (i, (expected, actual)) in expected_tt.iter().zip(&actual_tt).enumerate() {
+            assert!(
+                expected.abs_diff(*actual) <= tolerance.leg_travel_time,
+                "Travel time of leg {i} of person {person} differs by more than {}s. Expected {expected}, but was {actual}",
+                tolerance.leg_travel_time
+            );
+        }
+    }
+}
+
+fn create_resources(network: &Path, vehicles: &Path, population: &Path, out_dir: &Path) {
+    let net = Network::from_file_as_is(network);
+    let mut garage = Garage::from_file(vehicles);
+    let pop = Population::from_file(population, &mut garage);
+
+    store_to_file(&out_dir.join("ids.binpb"));
+    net.to_file(&out_dir.join("network.binpb"));
+    pop.to_file(&out_dir.join("population.binpb"));
+    garage.to_file(&out_dir.join("vehicles.binpb"));
+}
+
+/// Runs a scenario and compares its events with the events MATSim produced for the same scenario.
+/// The reference events are expected as `tests/resources/matsim_parity/<scenario>/output_events.xml.gz`.
+/// They are the `output_events.xml.gz` of a MATSim run of the same network, vehicles and plans with
+/// a single iteration (`lastIteration=0`). See `tests/resources/matsim_parity/README.md`.
+fn run_parity(
+    scenario: &str,
+    network: &str,
+    vehicles: &str,
+    population: &str,
+    tolerance: Tolerance,
+) {
+    let resources = PathBuf::from("./tests/resources/matsim_parity/").join(scenario);
+    create_resources(
+        &PathBuf::from(network),
+        &PathBuf::from(vehicles),
+        &PathBuf::from(population),
+        &PathBuf::from("./test_output/simulation/matsim_parity/").join(scenario),
+    );
+
+    let actual = execute_sim_and_receive_events(CommandLineArgs {
+        config_path: resources.join("config.yml").to_str().unwrap().to_string(),
+        num_parts: None,
+    });
+    let reference = read_event_lines(&resources.join("output_events.xml.gz"));
+
+    assert_parity(&actual, &reference, tolerance);
+}
+
+#[test]
+#[ignore = "requires MATSim reference events in tests/resources/matsim_parity/3-links"]
+fn parity_3_links() {
+    run_parity(
+        "3-links",
+        "./assets/3-links/3-links-network.xml",
+        "./assets/3-links/vehicles.xml",
+        "./assets/3-links/1-agent-full-leg.xml",
+        Tolerance {
+            event_time: 2,
+            leg_travel_time: 2,
+        },
+    );
+}
+
+#[test]
+#[ignore = "requires MATSim reference events in tests/resources/matsim_parity/equil"]
+fn parity_equil() {
+    run_parity(
+        "equil",
+        "./assets/equil/equil-network.xml",
+        "./assets/equil/equil-vehicles.xml",
+        "./assets/equil/equil-plans.xml.gz",
+        Tolerance {
+            event_time: 10,
+            leg_travel_time: 10,
+        },
+    );
+}
+
+/// Events as written by MATSim. Times are floats, there are additional event types and attributes
+/// and vehicle ids differ from the ones used by this crate.
+fn matsim_events() -> [String; 10] {
+    [
+        r#"<event time="32400.0" type="actend" person="100" link="link1" actType="home"  />"#,
+        r#"<event time="32400.0" type="departure" person="100" link="link1" legMode="car"  />"#,
+        r#"<event time="32400.0" type="PersonEntersVehicle" person="100" vehicle="100"  />"#,
+        r#"<event time="32400.0" type="vehicle enters traffic" person="100" link="link1" vehicle="100" networkMode="car" relativePosition="1.0"  />"#,
+        r#"<event time="32401.0" type="left link" vehicle="100" link="link1"  />"#,
+        r#"<event time="32401.0" type="entered link" vehicle="100" link="link2"  />"#,
+        r#"<event time="32502.0" type="vehicle leaves traffic" person="100" link="link2" vehicle="100" networkMode="car" relativePosition="1.0"  />"#,
+        r#"<event time="32502.0" type="PersonLeavesVehicle" person="100" vehicle="100"  />"#,
+        r#"<event time="32502.0" type="arrival" person="100" link="link2" legMode="car"  />"#,
+        r#"<event time="32502.0" type="actstart" person="100" link="link2" actType="work"  />"#,
+    ]
+    .map(String::from)
+}
+
+#[test]
+fn normalize_matsim_events() {
+    let matsim = matsim_events();
+    let own = [
+        r#"<event time="32400" type="actend" person="100" link="link1" actType="home" />"#,
+        r#"<event time="32400" type="departure" person="100" link="link1" legMode="car" />"#,
+        r#"<event time="32400" type="PersonEntersVehicle" person="100" vehicle="100_car" />"#,
+        r#"<event time="32401" type="left link" link="link1" vehicle="100_car" />"#,
+        r#"<event time="32401" type="entered link" link="link2" vehicle="100_car" />"#,
+        r#"<event time="32501" type="PersonLeavesVehicle" person="100" vehicle="100_car" />"#,
+        r#"<event time="32501" type="arrival" person="100" link="link2" legMode="car" />"#,
+        r#"<event time="32501" type="actstart" person="100" link="link2" actType="work" />"#,
+    ]
+    .map(String::from);
+
+    let normalized = normalize(&matsim);
+    let person = normalized.get("100").unwrap();
+    assert_eq!(8, person.len());
+    assert_eq!("left link", person[3].kind);
+    assert_eq!(vec![102], leg_travel_times(person));
+
+    assert_parity(
+        &own,
+        &matsim,
+        Tolerance {
+            event_time: 1,
+            leg_travel_time: 1,
+        },
+    );
+}
+
+#[test]
+fn read_matsim_events_file() {
+    let dir = PathBuf::from("./test_output/simulation/matsim_parity/read_matsim_events_file");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("output_events.xml");
+    let matsim = matsim_events();
+    std::fs::write(
+        &path,
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<events version=\"1.0\">\n{}\n</events>",
+            matsim.join("\n")
+        ),
+    )
+    .unwrap();
+
+    let read = read_event_lines(&path);
+
+    assert_eq!(matsim.len(), read.len());
+    assert_eq!(normalize(&matsim), normalize(&read));
+}
+
+#[test]
+#[should_panic(expected = "Different event sequence")]
+fn detect_different_routes() {
+    let reference = [
+        r#"<event time="0.0" type="PersonEntersVehicle" person="1" vehicle="1"  />"#,
+        r#"<event time="1.0" type="entered link" vehicle="1" link="link2"  />"#,
+    ]
+    .map(String::from);
+    let actual = [
+        r#"<event time="0" type="PersonEntersVehicle" person="1" vehicle="1_car" />"#,
+        r#"<event time="1" type="entered link" link="link3" vehicle="1_car" />"#,
+    ]
+    .map(String::from);
+
+    assert_parity(
+        &actual,
+        &reference,
+        Tolerance {
+            event_time: 0,
+            leg_travel_time: 0,
+        },
+    );
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use rust_q_sim::simulation::config::CommandLineArgs;
+use rust_q_sim::simulation::id::store_to_file;
+use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::population::population::Population;
+use rust_q_sim::simulation::vehicles::garage::Garage;
+
+use crate::test_simulation::execute_sim_and_collect_events;
+
+mod test_simulation;
+
+fn create_resources(out_dir: &PathBuf) {
+    let input_dir = PathBuf::from("./assets/equil/");
+    let net = Network::from_file_as_is(&input_dir.join("equil-network.xml"));
+    let mut garage = Garage::from_file(&input_dir.join("equil-vehicles.xml"));
+    let pop = Population::from_file(
+        &PathBuf::from("./tests/resources/partition_determinism/equil-staggered-plans.xml"),
+        &mut garage,
+    );
+
+    store_to_file(&out_dir.join("ids.binpb"));
+    net.to_file(&out_dir.join("equil-network.binpb"));
+    pop.to_file(&out_dir.join("equil-staggered-plans.binpb"));
+    garage.to_file(&out_dir.join("equil-vehicles.binpb"));
+}
+
+/// Runs the same scenario on 1, 2 and 4 partitions. The distribution of the simulation must not
+/// change its outcome. The agents take different routes, which cross partition boundaries once the
+/// network is split. They depart an hour apart, so that they never compete at a node and the
+/// random node strategy doesn't influence the result.
+#[test]
+#[serial_test::serial]
+fn events_independent_of_partitions() {
+    create_resources(&PathBuf::from(
+        "./test_output/simulation/partition_determinism/",
+    ));
+
+    let run = |num_parts: u32| {
+        execute_sim_and_collect_events(CommandLineArgs {
+            config_path: "./tests/resources/partition_determinism/config.yml".to_string(),
+            num_parts: Some(num_parts),
+        })
+    };
+
+    let expected = run(1);
+    assert!(!expected.is_empty());
+
+    for num_parts in [2, 4] {
+        let events = run(num_parts);
+        assert_eq!(
+            expected, events,
+            "Events of a run with {num_parts} partitions differ from a run with a single partition."
+        );
+    }
+}
@@ -1,3 +1,6 @@
+// this module is shared by several integration tests, which each use only some of its functions.
+#![allow(dead_code)]
+
 use std::any::Any;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -11,23 +14,23 @@ use nohash_hasher::IntMap;
 use tracing::info;
 
 use rust_q_sim::simulation::config::{CommandLineArgs, Config, RoutingMode};
-use rust_q_sim::simulation::controller::{get_numbered_output_filename, partition_input};
-use rust_q_sim::simulation::id;
+use rust_q_sim::simulation::controller::partition_input;
+use rust_q_sim::simulation::engines::Engine;
+use rust_q_sim::simulation::io::output_files::get_numbered_output_filename;
 use rust_q_sim::simulation::io::xml_events::XmlEventsWriter;
 use rust_q_sim::simulation::messaging::communication::communicators::{
     ChannelSimCommunicator, SimCommunicator,
 };
 use rust_q_sim::simulation::messaging::communication::message_broker::NetMessageBroker;
 use rust_q_sim::simulation::messaging::events::{EventsPublisher, EventsSubscriber};
-use rust_q_sim::simulation::network::global_network::Network;
+use rust_q_sim::simulation::mobsim_listener::MobsimListener;
 use rust_q_sim::simulation::network::sim_network::SimNetworkPartition;
-use rust_q_sim::simulation::population::population::Population;
 use rust_q_sim::simulation::replanning::replanner::{
     DummyReplanner, ReRouteTripReplanner, Replanner,
 };
 use rust_q_sim::simulation::replanning::routing::travel_time_collector::TravelTimeCollector;
+use rust_q_sim::simulation::scenario::Scenario;
 use rust_q_sim::simulation::simulation::Simulation;
-use rust_q_sim::simulation::vehicles::garage::Garage;
 use rust_q_sim::simulation::wire_types::events::Event;
 
 pub fn execute_sim_with_channels(config_args: CommandLineArgs, expected_events: &str) {
@@ -57,10 +60,61 @@ pub fn execute_sim_with_channels(config_args: CommandLineArgs, expected_events:
     try_join(handles);
 }
 
+/// Runs the scenario with as many partitions as configured and returns the events of all
+/// partitions in sorted order. This way, runs with different numbers of partitions can be compared,
+/// regardless of the order in which partitions publish their events.
+pub fn execute_sim_and_collect_events(config_args: CommandLineArgs) -> Vec<String> {
+    let mut events = execute_sim_and_receive_events(config_args);
+    events.sort();
+    events
+}
+
+/// Runs the scenario with as many partitions as configured and returns the events of all
+/// partitions in the order they were received. With a single partition, this is the order in which
+/// the events were published.
+pub fn execute_sim_and_receive_events(config_args: CommandLineArgs) -> Vec<String> {
+    let config = Config::from_file(&config_args);
+    let comms = ChannelSimCommunicator::create_n_2_n(config.partitioning().num_parts);
+    let (sender, receiver) = channel();
+
+    let handles: IntMap<u32, JoinHandle<()>> = comms
+        .into_iter()
+        .map(|comm| {
+            let config_args_clone = config_args.clone();
+            let subscr = SendingSubscriber {
+                rank: comm.rank(),
+                sender: sender.clone(),
+            };
+            (
+                comm.rank(),
+                thread::spawn(move || execute_sim(comm, Box::new(subscr), config_args_clone)),
+            )
+        })
+        .collect();
+    // only the subscribers of the simulation threads hold senders now. Once they are done, the
+    // receiver stops.
+    drop(sender);
+    try_join(handles);
+
+    receiver.iter().collect()
+}
+
 pub fn execute_sim<C: SimCommunicator + 'static>(
     comm: C,
     test_subscriber: Box<dyn EventsSubscriber + Send>,
     config_args: CommandLineArgs,
+) {
+    execute_sim_with_extensions(comm, test_subscriber, config_args, Vec::new(), Vec::new());
+}
+
+/// Like [execute_sim], but the given engines and mobsim listeners are registered in the simulation
+/// before it runs.
+pub fn execute_sim_with_extensions<C: SimCommunicator + 'static>(
+    comm: C,
+    test_subscriber: Box<dyn EventsSubscriber + Send>,
+    config_args: CommandLineArgs,
+    engines: Vec<Box<dyn Engine>>,
+    listeners: Vec<Box<dyn MobsimListener>>,
 ) {
     let rank = comm.rank();
 
@@ -75,8 +129,6 @@ pub fn execute_sim<C: SimCommunicator + 'static>(
         config.partitioning().num_parts,
     );
 
-    id::load_from_file(&PathBuf::from(config.proto_files().ids));
-
     if rank == 0 {
         info!("#{rank} preparing to create input for partitions.");
         //this call also loads the ids from the file.
@@ -88,16 +140,12 @@ pub fn execute_sim<C: SimCommunicator + 'static>(
         }
     }
 
-    let network = Network::from_file_as_is(&temp_network_file);
-    let mut garage = Garage::from_file(&PathBuf::from(config.proto_files().vehicles));
-
-    //let population: Population = Population::from_file(&temp_population_file, &mut garage);
-    let population: Population = Population::from_file_filtered_part(
-        &PathBuf::from(config.proto_files().population),
-        &network,
-        &mut garage,
-        comm.rank(),
-    );
+    let Scenario {
+        network,
+        garage,
+        population,
+        ..
+    } = Scenario::load_partition(&config, rank, false);
     let sim_net = SimNetworkPartition::from_network(&network, rank, config.simulation());
 
     let mut events = EventsPublisher::new();
@@ -113,14 +161,22 @@ pub fn execute_sim<C: SimCommunicator + 'static>(
             &sim_net,
             &garage,
             Rc::clone(&rc),
+            &config.teleportation(),
+            &config.routing(),
         ))
     } else {
         Box::new(DummyReplanner {})
     };
 
     let mut sim = Simulation::new(
-        config, sim_net, garage, population, broker, events, replanner,
+        &config, sim_net, garage, population, broker, events, replanner,
     );
+    for engine in engines {
+        sim.add_engine(engine);
+    }
+    for listener in listeners {
+        sim.add_listener(listener);
+    }
 
     sim.run();
 }
@@ -146,7 +202,7 @@ fn try_join(mut handles: IntMap<u32, JoinHandle<()>>) {
     }
 }
 
-struct EmptySubscriber {}
+pub struct EmptySubscriber {}
 
 impl EventsSubscriber for EmptySubscriber {
     fn receive_event(&mut self, _time: u32, _event: &Event) {